@@ -0,0 +1,220 @@
+// Numbers for the core's hot paths, to check performance work against:
+//
+//   cpu/instructions   instructions a second, PPU and APU running but
+//                      nothing drawn
+//   frame/idle         frames a second, rendering off
+//   frame/busy         background and 64 sprites on, OAM DMA every frame
+//   frame/<rom>        the same for every .nes under test-roms/bench/ (or
+//                      $NES_TEST_ROMS/bench/), if there are any
+//   state/*            snapshotting, saving and restoring a savestate
+//   ppu/frame          the PPU on its own, in visible scanlines a second
+//
+//     cargo bench --bench core -- frame/
+
+use std::fs;
+use std::hint::black_box;
+
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+
+use nes::mapper::nrom::Nrom;
+use nes::mapper::Mirroring;
+use nes::testing::asm::assemble;
+use nes::testing::test_roms_dir;
+use nes::{Emulator, Ppu};
+
+const PRG_SIZE: usize = 0x8000;
+const CHR_SIZE: usize = 0x2000;
+
+// Adds up RAM forever. Every instruction is one of the common ones.
+const CPU_LOOP: &str = "
+    loop:
+        lda $00,x
+        clc
+        adc #3
+        sta $00,x
+        inx
+        bne loop
+        iny
+        jmp loop
+";
+
+// Palette, a nametable of every tile and 64 sprites across the screen,
+// then on with NMI, the background and sprites and into CPU_LOOP.
+const BUSY_SETUP: &str = "
+        sei
+        ldx #$ff
+        txs
+    vblank1:
+        bit $2002
+        bpl vblank1
+    vblank2:
+        bit $2002
+        bpl vblank2
+        lda #$3f; sta $2006; lda #$00; sta $2006
+        ldx #0
+    palette:
+        txa; sta $2007
+        inx; cpx #32; bne palette
+        lda #$20; sta $2006; lda #$00; sta $2006
+        ldy #4
+    tiles:
+        txa; sta $2007
+        inx; bne tiles
+        dey; bne tiles
+    sprites:
+        txa; sta $0200,x; sta $0201,x; sta $0203,x
+        lda #0; sta $0202,x
+        inx; inx; inx; inx
+        bne sprites
+        lda #$80; sta $2000
+        lda #$1e; sta $2001
+";
+
+const BUSY_NMI: &str = "
+    nmi:
+        pha
+        lda #$02; sta $4014
+        lda #0; sta $2005; sta $2005
+        pla
+        rti
+";
+
+// An NROM-256 image running `source` from $8000, with `nmi` its NMI
+// handler's label if it has one, and CHR that isn't blank.
+fn rom(source: &str, nmi: Option<&str>) -> Vec<u8> {
+    let assembly = assemble(0x8000, source).unwrap();
+    let nmi = nmi.map_or(0x8000, |label| assembly.label(label).unwrap());
+    let mut rom = b"NES\x1a\x02\x01\x00\x00".to_vec();
+    rom.resize(16, 0);
+    let mut prg = assembly.bytes.clone();
+    prg.resize(PRG_SIZE, 0xEA);
+    prg[PRG_SIZE - 6..].copy_from_slice(&[nmi as u8, (nmi >> 8) as u8, 0x00, 0x80, 0x00, 0x80]);
+    rom.extend(prg);
+    rom.extend((0..CHR_SIZE).map(|i| (i * 7 + i / 16) as u8));
+    rom
+}
+
+fn emulator(rom: &[u8]) -> Emulator {
+    let mut emulator = Emulator::from_rom(rom).unwrap();
+    // past the setup code
+    emulator.run_frames(3);
+    emulator
+}
+
+fn cpu(c: &mut Criterion) {
+    const STEPS: u64 = 10_000;
+    let mut emulator = emulator(&rom(CPU_LOOP, None));
+    let mut group = c.benchmark_group("cpu");
+    group.throughput(Throughput::Elements(STEPS));
+    group.bench_function("instructions", |b| {
+        b.iter(|| {
+            for _ in 0..STEPS {
+                black_box(emulator.cpu_mut().step());
+            }
+        })
+    });
+    group.finish();
+}
+
+fn frames(c: &mut Criterion) {
+    let mut roms = vec![
+        ("idle".to_string(), rom(CPU_LOOP, None)),
+        (
+            "busy".to_string(),
+            rom(&[BUSY_SETUP, CPU_LOOP, BUSY_NMI].concat(), Some("nmi")),
+        ),
+    ];
+    if let Ok(entries) = fs::read_dir(test_roms_dir().join("bench")) {
+        let mut paths: Vec<_> = entries
+            .filter_map(|entry| Some(entry.ok()?.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "nes"))
+            .collect();
+        paths.sort();
+        for path in paths {
+            let name = path.file_stem().unwrap().to_string_lossy().into_owned();
+            roms.push((name, fs::read(&path).unwrap()));
+        }
+    }
+
+    let mut group = c.benchmark_group("frame");
+    group.throughput(Throughput::Elements(1));
+    for (name, rom) in roms {
+        let mut emulator = match Emulator::from_rom(&rom) {
+            Ok(mut emulator) => {
+                emulator.run_frames(3);
+                emulator
+            }
+            Err(err) => {
+                eprintln!("skipped {}: {}", name, err);
+                continue;
+            }
+        };
+        group.bench_function(name, |b| b.iter(|| emulator.run_frame()));
+    }
+    group.finish();
+}
+
+fn states(c: &mut Criterion) {
+    let mut emulator = emulator(&rom(
+        &[BUSY_SETUP, CPU_LOOP, BUSY_NMI].concat(),
+        Some("nmi"),
+    ));
+    let mut snapshot = emulator.snapshot();
+    let state = emulator.save_state();
+
+    let mut group = c.benchmark_group("state");
+    group.bench_function("snapshot", |b| {
+        b.iter(|| emulator.snapshot_into(&mut snapshot))
+    });
+    group.bench_function("save", |b| b.iter(|| black_box(emulator.save_state())));
+    group.bench_function("restore", |b| {
+        b.iter(|| emulator.restore(&snapshot).unwrap())
+    });
+    group.bench_function("load", |b| {
+        b.iter_batched(
+            || state.clone(),
+            |state| emulator.load_state(&state).unwrap(),
+            BatchSize::SmallInput,
+        )
+    });
+    group.finish();
+}
+
+fn ppu(c: &mut Criterion) {
+    const DOTS_PER_FRAME: usize = 341 * 262;
+    let chr: Vec<u8> = (0..CHR_SIZE).map(|i| (i * 7 + i / 16) as u8).collect();
+    let mut mapper = Nrom::new(vec![0; PRG_SIZE], chr, Mirroring::Horizontal);
+    let mut ppu = Ppu::new();
+    let mut write = |ppu: &mut Ppu, addr, value| ppu.write_register(addr, value, &mut mapper);
+    write(&mut ppu, 0x2006, 0x20);
+    write(&mut ppu, 0x2006, 0x00);
+    for i in 0..0x400 {
+        write(&mut ppu, 0x2007, i as u8);
+    }
+    write(&mut ppu, 0x2006, 0x3F);
+    write(&mut ppu, 0x2006, 0x00);
+    for i in 0..32 {
+        write(&mut ppu, 0x2007, i);
+    }
+    let mut oam = [0; 256];
+    for (i, byte) in oam.iter_mut().enumerate() {
+        *byte = if i % 4 == 2 { 0 } else { (i & !3) as u8 };
+    }
+    ppu.write_oam_dma(&oam);
+    write(&mut ppu, 0x2000, 0x00);
+    write(&mut ppu, 0x2001, 0x1E);
+
+    let mut group = c.benchmark_group("ppu");
+    group.throughput(Throughput::Elements(240));
+    group.bench_function("frame", |b| {
+        b.iter(|| {
+            for _ in 0..DOTS_PER_FRAME {
+                ppu.tick(&mut mapper);
+            }
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, cpu, frames, states, ppu);
+criterion_main!(benches);
@@ -0,0 +1,93 @@
+import init, { WebEmulator } from "./pkg/nes.js";
+
+const FRAME_RATE = 60.0988;
+// keep this much audio scheduled ahead of the playhead
+const AUDIO_LEAD = 0.05;
+
+// bit positions in the joypad byte
+const KEYS = {
+  KeyX: 0x01,       // A
+  KeyZ: 0x02,       // B
+  ShiftRight: 0x04, // Select
+  Enter: 0x08,      // Start
+  ArrowUp: 0x10,
+  ArrowDown: 0x20,
+  ArrowLeft: 0x40,
+  ArrowRight: 0x80,
+};
+
+const wasm = await init();
+const canvas = document.getElementById("screen");
+const context = canvas.getContext("2d");
+
+let emulator = null;
+let audio = null;
+let audioTime = 0;
+let buttons = 0;
+let lastTime = null;
+let pending = 0;
+
+function onKey(event, pressed) {
+  const bit = KEYS[event.code];
+  if (bit === undefined) return;
+  event.preventDefault();
+  buttons = pressed ? buttons | bit : buttons & ~bit;
+}
+addEventListener("keydown", (event) => onKey(event, true));
+addEventListener("keyup", (event) => onKey(event, false));
+addEventListener("blur", () => { buttons = 0; });
+
+function queueAudio(samples) {
+  if (samples.length === 0) return;
+  const buffer = audio.createBuffer(1, samples.length, audio.sampleRate);
+  buffer.copyToChannel(samples, 0);
+  const source = audio.createBufferSource();
+  source.buffer = buffer;
+  source.connect(audio.destination);
+  // after a stall, start again just ahead of the playhead
+  if (audioTime < audio.currentTime) audioTime = audio.currentTime + AUDIO_LEAD;
+  source.start(audioTime);
+  audioTime += buffer.duration;
+}
+
+function draw() {
+  const width = WebEmulator.width();
+  const height = WebEmulator.height();
+  const pixels = new Uint8ClampedArray(
+    wasm.memory.buffer, emulator.frameBufferPtr(), width * height * 4);
+  context.putImageData(new ImageData(pixels, width, height), 0, 0);
+}
+
+// requestAnimationFrame fires at the display's rate; run however many
+// console frames have come due since the last one.
+function tick(time) {
+  if (lastTime !== null) pending += (time - lastTime) * FRAME_RATE / 1000;
+  lastTime = time;
+  pending = Math.min(pending, 4);
+  while (pending >= 1) {
+    emulator.setButtons(0, buttons);
+    emulator.runFrame();
+    queueAudio(emulator.takeAudio());
+    pending -= 1;
+  }
+  draw();
+  requestAnimationFrame(tick);
+}
+
+document.getElementById("rom").addEventListener("change", async (event) => {
+  const file = event.target.files[0];
+  if (!file) return;
+  const running = emulator !== null;
+  try {
+    emulator = WebEmulator.loadRom(new Uint8Array(await file.arrayBuffer()));
+  } catch (err) {
+    alert(err.message ?? err);
+    return;
+  }
+  // browsers only start audio from a user gesture
+  audio ??= new AudioContext();
+  await audio.resume();
+  emulator.enableAudio(audio.sampleRate);
+  audioTime = 0;
+  if (!running) requestAnimationFrame(tick);
+});
@@ -0,0 +1,105 @@
+/*
+ * C API for the nes emulator core, built with
+ *
+ *     cargo build --release --lib --features ffi
+ *
+ * which makes libnes.so, libnes.dylib or nes.dll. Generated from
+ * src/ffi.rs, which has the details, by cbindgen; don't edit it by hand.
+ *
+ * Each NesEmulator is one console. Use a handle from one thread at a
+ * time. Pointers the library hands out stay valid until the next call on
+ * the same handle.
+ */
+
+#ifndef NES_H
+#define NES_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#define NES_API_VERSION 2
+
+#define NES_WIDTH 256
+
+#define NES_HEIGHT 240
+
+#define NES_OK 0
+
+// a null pointer, or a player that doesn't exist
+#define NES_ERROR_ARGUMENT -1
+
+#define NES_ERROR_ROM -2
+
+#define NES_ERROR_STATE -3
+
+// the buffer is too small; nes_state_size says how big to make it
+#define NES_ERROR_BUFFER -4
+
+// the emulator panicked; destroy the handle
+#define NES_ERROR_PANIC -5
+
+// for nes_set_buttons
+#define NES_BUTTON_A 1
+
+#define NES_BUTTON_B 2
+
+#define NES_BUTTON_SELECT 4
+
+#define NES_BUTTON_START 8
+
+#define NES_BUTTON_UP 16
+
+#define NES_BUTTON_DOWN 32
+
+#define NES_BUTTON_LEFT 64
+
+#define NES_BUTTON_RIGHT 128
+
+typedef struct NesEmulator NesEmulator;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// NES_API_VERSION as the library was built; check it matches.
+uint32_t nes_api_version(void);
+
+// A console with no cartridge. A sample_rate of 0 leaves audio off.
+NesEmulator *nes_create(uint32_t sample_rate);
+
+void nes_destroy(NesEmulator *nes);
+
+// An iNES or NES 2.0 image; the console powers on with it.
+int nes_load_rom(NesEmulator *nes, const uint8_t *data, size_t size);
+
+int nes_reset(NesEmulator *nes);
+
+// Runs to the end of the next frame; does nothing without a ROM.
+int nes_run_frame(NesEmulator *nes);
+
+// NES_WIDTH * NES_HEIGHT pixels of packed RGB, 3 bytes each, top row
+// first.
+const uint8_t *nes_framebuffer(const NesEmulator *nes);
+
+// The last frame's audio, mono floats from -1 to 1; count may be null.
+const float *nes_audio(const NesEmulator *nes, size_t *count);
+
+// Players 0-3; buttons are NES_BUTTON_* ored together, held from the
+// next frame on.
+int nes_set_buttons(NesEmulator *nes, uint32_t player, uint8_t buttons);
+
+// Room for a state saved now. States vary a little in size, so save
+// into a buffer of this size right away rather than keeping the number.
+size_t nes_state_size(const NesEmulator *nes);
+
+// Savestates. Size the buffer with nes_state_size just before saving;
+// written may be null.
+int nes_save_state(const NesEmulator *nes, uint8_t *buffer, size_t size, size_t *written);
+
+int nes_load_state(NesEmulator *nes, const uint8_t *data, size_t size);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* NES_H */
@@ -0,0 +1,30 @@
+const SOURCES: &[&str] = &[
+    "rcheevos/src/rc_compat.c",
+    "rcheevos/src/rc_util.c",
+    "rcheevos/src/rcheevos/alloc.c",
+    "rcheevos/src/rcheevos/condition.c",
+    "rcheevos/src/rcheevos/condset.c",
+    "rcheevos/src/rcheevos/consoleinfo.c",
+    "rcheevos/src/rcheevos/format.c",
+    "rcheevos/src/rcheevos/lboard.c",
+    "rcheevos/src/rcheevos/memref.c",
+    "rcheevos/src/rcheevos/operand.c",
+    "rcheevos/src/rcheevos/rc_validate.c",
+    "rcheevos/src/rcheevos/richpresence.c",
+    "rcheevos/src/rcheevos/runtime.c",
+    "rcheevos/src/rcheevos/runtime_progress.c",
+    "rcheevos/src/rcheevos/trigger.c",
+    "rcheevos/src/rcheevos/value.c",
+    "rcheevos/src/rhash/md5.c",
+];
+
+fn main() {
+    cc::Build::new()
+        .define("RC_DISABLE_LUA", None)
+        .define("RC_STATIC", None)
+        .include("rcheevos/include")
+        .files(SOURCES)
+        .warnings(false)
+        .compile("rcheevos");
+    println!("cargo:rerun-if-changed=rcheevos");
+}
@@ -0,0 +1,8 @@
+#ifndef RCHEEVOS_H
+#define RCHEEVOS_H
+
+#include "rc_runtime.h"
+#include "rc_runtime_types.h"
+#include "rc_consoles.h"
+
+#endif /* RCHEEVOS_H */
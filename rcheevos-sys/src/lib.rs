@@ -0,0 +1,83 @@
+// Declarations for rcheevos' runtime (include/rc_runtime.h), written out
+// by hand for the parts the emulator uses: activating achievements and
+// leaderboards by their logic, running them once a frame and resetting
+// them. Built without Lua, so the lua_State arguments are always null.
+
+#![allow(non_camel_case_types)]
+
+use core::ffi::{c_char, c_int, c_void};
+
+#[repr(C)]
+pub struct rc_runtime_t {
+    _private: [u8; 0],
+}
+
+#[repr(C)]
+pub struct lua_State {
+    _private: [u8; 0],
+}
+
+pub const RC_OK: c_int = 0;
+
+pub const RC_RUNTIME_EVENT_ACHIEVEMENT_ACTIVATED: u8 = 0;
+pub const RC_RUNTIME_EVENT_ACHIEVEMENT_PAUSED: u8 = 1;
+pub const RC_RUNTIME_EVENT_ACHIEVEMENT_RESET: u8 = 2;
+pub const RC_RUNTIME_EVENT_ACHIEVEMENT_TRIGGERED: u8 = 3;
+pub const RC_RUNTIME_EVENT_ACHIEVEMENT_PRIMED: u8 = 4;
+pub const RC_RUNTIME_EVENT_LBOARD_STARTED: u8 = 5;
+pub const RC_RUNTIME_EVENT_LBOARD_CANCELED: u8 = 6;
+pub const RC_RUNTIME_EVENT_LBOARD_UPDATED: u8 = 7;
+pub const RC_RUNTIME_EVENT_LBOARD_TRIGGERED: u8 = 8;
+pub const RC_RUNTIME_EVENT_ACHIEVEMENT_DISABLED: u8 = 9;
+pub const RC_RUNTIME_EVENT_LBOARD_DISABLED: u8 = 10;
+pub const RC_RUNTIME_EVENT_ACHIEVEMENT_UNPRIMED: u8 = 11;
+pub const RC_RUNTIME_EVENT_ACHIEVEMENT_PROGRESS_UPDATED: u8 = 12;
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct rc_runtime_event_t {
+    pub id: u32,
+    pub value: i32,
+    pub type_: u8,
+}
+
+// `num_bytes` bytes from `address`, little-endian.
+pub type rc_runtime_peek_t =
+    Option<unsafe extern "C" fn(address: u32, num_bytes: u32, ud: *mut c_void) -> u32>;
+
+pub type rc_runtime_event_handler_t =
+    Option<unsafe extern "C" fn(runtime_event: *const rc_runtime_event_t)>;
+
+extern "C" {
+    pub fn rc_runtime_alloc() -> *mut rc_runtime_t;
+    pub fn rc_runtime_destroy(runtime: *mut rc_runtime_t);
+
+    pub fn rc_runtime_activate_achievement(
+        runtime: *mut rc_runtime_t,
+        id: u32,
+        memaddr: *const c_char,
+        L: *mut lua_State,
+        funcs_idx: c_int,
+    ) -> c_int;
+    pub fn rc_runtime_deactivate_achievement(runtime: *mut rc_runtime_t, id: u32);
+
+    pub fn rc_runtime_activate_lboard(
+        runtime: *mut rc_runtime_t,
+        id: u32,
+        memaddr: *const c_char,
+        L: *mut lua_State,
+        funcs_idx: c_int,
+    ) -> c_int;
+    pub fn rc_runtime_deactivate_lboard(runtime: *mut rc_runtime_t, id: u32);
+
+    pub fn rc_runtime_do_frame(
+        runtime: *mut rc_runtime_t,
+        event_handler: rc_runtime_event_handler_t,
+        peek: rc_runtime_peek_t,
+        ud: *mut c_void,
+        L: *mut lua_State,
+    );
+    pub fn rc_runtime_reset(runtime: *mut rc_runtime_t);
+
+    pub fn rc_error_str(ret: c_int) -> *const c_char;
+}
@@ -0,0 +1,107 @@
+// How RetroAchievements tells games apart: the MD5 of the ROM without
+// its iNES header (or fwNES header, for disk images), in lowercase hex.
+// Two dumps that differ only in the header are the same game to it.
+
+const SHIFTS: [u32; 16] = [7, 12, 17, 22, 5, 9, 14, 20, 4, 11, 16, 23, 6, 10, 15, 21];
+
+// floor(abs(sin(i + 1)) * 2^32)
+#[rustfmt::skip]
+const K: [u32; 64] = [
+    0xd76a_a478, 0xe8c7_b756, 0x2420_70db, 0xc1bd_ceee,
+    0xf57c_0faf, 0x4787_c62a, 0xa830_4613, 0xfd46_9501,
+    0x6980_98d8, 0x8b44_f7af, 0xffff_5bb1, 0x895c_d7be,
+    0x6b90_1122, 0xfd98_7193, 0xa679_438e, 0x49b4_0821,
+    0xf61e_2562, 0xc040_b340, 0x265e_5a51, 0xe9b6_c7aa,
+    0xd62f_105d, 0x0244_1453, 0xd8a1_e681, 0xe7d3_fbc8,
+    0x21e1_cde6, 0xc337_07d6, 0xf4d5_0d87, 0x455a_14ed,
+    0xa9e3_e905, 0xfcef_a3f8, 0x676f_02d9, 0x8d2a_4c8a,
+    0xfffa_3942, 0x8771_f681, 0x6d9d_6122, 0xfde5_380c,
+    0xa4be_ea44, 0x4bde_cfa9, 0xf6bb_4b60, 0xbebf_bc70,
+    0x289b_7ec6, 0xeaa1_27fa, 0xd4ef_3085, 0x0488_1d05,
+    0xd9d4_d039, 0xe6db_99e5, 0x1fa2_7cf8, 0xc4ac_5665,
+    0xf429_2244, 0x432a_ff97, 0xab94_23a7, 0xfc93_a039,
+    0x655b_59c3, 0x8f0c_cc92, 0xffef_f47d, 0x8584_5dd1,
+    0x6fa8_7e4f, 0xfe2c_e6e0, 0xa301_4314, 0x4e08_11a1,
+    0xf753_7e82, 0xbd3a_f235, 0x2ad7_d2bb, 0xeb86_d391,
+];
+
+pub fn md5(data: &[u8]) -> [u8; 16] {
+    let mut h: [u32; 4] = [0x6745_2301, 0xefcd_ab89, 0x98ba_dcfe, 0x1032_5476];
+    let mut padded = data.to_vec();
+    padded.push(0x80);
+    while padded.len() % 64 != 56 {
+        padded.push(0);
+    }
+    padded.extend_from_slice(&((data.len() as u64) * 8).to_le_bytes());
+    for block in padded.chunks(64) {
+        let mut m = [0u32; 16];
+        for (i, word) in block.chunks(4).enumerate() {
+            m[i] = u32::from_le_bytes([word[0], word[1], word[2], word[3]]);
+        }
+        let [mut a, mut b, mut c, mut d] = h;
+        for i in 0..64 {
+            let (f, g) = match i {
+                0..=15 => ((b & c) | (!b & d), i),
+                16..=31 => ((d & b) | (!d & c), (5 * i + 1) % 16),
+                32..=47 => (b ^ c ^ d, (3 * i + 5) % 16),
+                _ => (c ^ (b | !d), (7 * i) % 16),
+            };
+            let shift = SHIFTS[i / 16 * 4 + i % 4];
+            let next = a
+                .wrapping_add(f)
+                .wrapping_add(K[i])
+                .wrapping_add(m[g])
+                .rotate_left(shift);
+            a = d;
+            d = c;
+            c = b;
+            b = b.wrapping_add(next);
+        }
+        for (state, value) in h.iter_mut().zip([a, b, c, d]) {
+            *state = state.wrapping_add(value);
+        }
+    }
+    let mut digest = [0; 16];
+    for (out, word) in digest.chunks_mut(4).zip(h) {
+        out.copy_from_slice(&word.to_le_bytes());
+    }
+    digest
+}
+
+pub fn rom_hash(rom: &[u8]) -> String {
+    let headered = rom.len() >= 16 && (rom.starts_with(b"NES\x1a") || rom.starts_with(b"FDS\x1a"));
+    let data = if headered { &rom[16..] } else { rom };
+    md5(data)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_md5_and_rom_hash() {
+        let hex = |data: &[u8]| -> String {
+            md5(data)
+                .iter()
+                .map(|byte| format!("{:02x}", byte))
+                .collect()
+        };
+        assert_eq!(hex(b""), "d41d8cd98f00b204e9800998ecf8427e");
+        assert_eq!(hex(b"abc"), "900150983cd24fb0d6963f7d28e17f72");
+        assert_eq!(
+            hex(b"The quick brown fox jumps over the lazy dog"),
+            "9e107d9d372bb6826bd81d3542a419d6"
+        );
+        // past one block
+        assert_eq!(hex(&[b'a'; 100]), "36a92cc94a9e0fa21f625f8bfb007adf");
+
+        let mut rom = b"NES\x1a".to_vec();
+        rom.resize(16, 0xFF);
+        rom.extend_from_slice(b"abc");
+        assert_eq!(rom_hash(&rom), "900150983cd24fb0d6963f7d28e17f72");
+        assert_eq!(rom_hash(b"abc"), "900150983cd24fb0d6963f7d28e17f72");
+    }
+}
@@ -0,0 +1,29 @@
+// RetroAchievements: a game's achievements and leaderboards, checked
+// against its memory after every frame by RetroAchievements' own runtime,
+// rcheevos (the rcheevos-sys crate, with the "rcheevos" feature; see
+// runtime.rs). The ROM is identified by the site's hash (hash.rs), which
+// is built either way. Talking to the site, logging in, fetching a set
+// and reporting unlocks, is up to a front end: it loads the set, as the
+// patch data the site's API returns, and gets back what happened each
+// frame.
+//
+// An achievement has to be seen not holding before it can unlock, so one
+// that's already true when the game loads (or after a reset) waits for
+// the state to change rather than firing at once. Leaderboards start,
+// then cancel or submit their value.
+//
+// Hardcore mode is the set played without help: the session refuses
+// savestate loads, rewind, slow motion, frame advance and cheats while
+// it's on. Nothing here enforces it; it's for a front end to report with
+// unlocks. Addresses are the CPU's, $0000-$FFFF, read without side
+// effects.
+
+pub mod hash;
+#[cfg(feature = "rcheevos")]
+mod runtime;
+
+pub use self::hash::rom_hash;
+#[cfg(feature = "rcheevos")]
+pub use self::runtime::{
+    Achievement, AchievementError, AchievementEvent, Achievements, Leaderboard,
+};
@@ -0,0 +1,487 @@
+// The set in rcheevos' runtime. It parses the logic when it's added and
+// keeps it; every frame it reads memory through a callback and reports
+// what changed through another, which takes no context, so events are
+// gathered in a thread-local for the length of the call.
+
+use std::cell::RefCell;
+use std::ffi::{c_char, c_int, c_void, CStr, CString};
+use std::fmt;
+use std::ptr::{self, NonNull};
+
+use rcheevos_sys::*;
+
+use crate::remote::json::{Json, JsonError};
+
+#[derive(Debug)]
+pub enum AchievementError {
+    Json(JsonError),
+    // an entry missing a field
+    Format(String),
+    // logic rcheevos refused, in its words
+    Logic { id: u32, error: String },
+}
+
+impl fmt::Display for AchievementError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AchievementError::Json(err) => write!(f, "{}", err),
+            AchievementError::Format(problem) => write!(f, "achievement set: {}", problem),
+            AchievementError::Logic { id, error } => write!(f, "logic of {}: {}", id, error),
+        }
+    }
+}
+
+impl std::error::Error for AchievementError {}
+
+impl From<JsonError> for AchievementError {
+    fn from(err: JsonError) -> Self {
+        AchievementError::Json(err)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AchievementEvent {
+    Unlocked { id: u32 },
+    LeaderboardStarted { id: u32 },
+    LeaderboardCanceled { id: u32 },
+    LeaderboardSubmitted { id: u32, value: i64 },
+}
+
+#[derive(Debug, Clone)]
+pub struct Achievement {
+    pub id: u32,
+    pub title: String,
+    pub description: String,
+    pub points: u32,
+    unlocked: bool,
+}
+
+impl Achievement {
+    pub fn is_unlocked(&self) -> bool {
+        self.unlocked
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Leaderboard {
+    pub id: u32,
+    pub title: String,
+    // how the site shows the value, e.g. SCORE or FRAMES
+    pub format: String,
+    // while started
+    running: bool,
+    current: i64,
+}
+
+impl Leaderboard {
+    // The value so far, while it's running.
+    pub fn current(&self) -> Option<i64> {
+        self.running.then_some(self.current)
+    }
+}
+
+#[derive(Debug)]
+struct Runtime(NonNull<rc_runtime_t>);
+
+impl Runtime {
+    fn new() -> Self {
+        // SAFETY: allocates and initializes a runtime of its own
+        let runtime = unsafe { rc_runtime_alloc() };
+        Runtime(NonNull::new(runtime).expect("rcheevos allocates its runtime"))
+    }
+
+    fn get(&self) -> *mut rc_runtime_t {
+        self.0.as_ptr()
+    }
+}
+
+impl Drop for Runtime {
+    fn drop(&mut self) {
+        // SAFETY: frees what rc_runtime_alloc gave, once
+        unsafe { rc_runtime_destroy(self.get()) }
+    }
+}
+
+// SAFETY: the runtime is plain data, only touched through &mut self
+unsafe impl Send for Runtime {}
+
+#[derive(Debug)]
+pub struct Achievements {
+    runtime: Runtime,
+    achievements: Vec<Achievement>,
+    leaderboards: Vec<Leaderboard>,
+    hardcore: bool,
+}
+
+impl Default for Achievements {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+thread_local! {
+    static EVENTS: RefCell<Vec<rc_runtime_event_t>> = const { RefCell::new(Vec::new()) };
+}
+
+// rc_runtime_activate_achievement or rc_runtime_activate_lboard.
+type Activate =
+    unsafe extern "C" fn(*mut rc_runtime_t, u32, *const c_char, *mut lua_State, c_int) -> c_int;
+
+unsafe extern "C" fn gather(event: *const rc_runtime_event_t) {
+    // SAFETY: rcheevos passes an event that lives for the call
+    let event = unsafe { *event };
+    EVENTS.with(|events| events.borrow_mut().push(event));
+}
+
+// Little-endian, as rcheevos wants it; past $FFFF reads 0.
+unsafe extern "C" fn peek(address: u32, num_bytes: u32, ud: *mut c_void) -> u32 {
+    // SAFETY: do_frame passes its reader, which outlives the call
+    let read = unsafe { &mut *(ud as *mut &mut dyn FnMut(u16) -> u8) };
+    (0..num_bytes.min(4)).fold(0, |value, byte| {
+        let byte_value = u16::try_from(address + byte).map_or(0, &mut *read);
+        value | (byte_value as u32) << (8 * byte)
+    })
+}
+
+fn activate(
+    runtime: &Runtime,
+    activate: Activate,
+    id: u32,
+    logic_text: &str,
+) -> Result<(), AchievementError> {
+    let logic = CString::new(logic_text).map_err(|_| AchievementError::Logic {
+        id,
+        error: "a NUL in the logic".to_string(),
+    })?;
+    // SAFETY: the runtime is live and the logic a C string; rcheevos
+    // keeps its own copy of what it parses
+    let result = unsafe { activate(runtime.get(), id, logic.as_ptr(), ptr::null_mut(), 0) };
+    if result == RC_OK {
+        return Ok(());
+    }
+    // SAFETY: rc_error_str gives a static string for any code
+    let error = unsafe { CStr::from_ptr(rc_error_str(result)) };
+    Err(AchievementError::Logic {
+        id,
+        error: error.to_string_lossy().into_owned(),
+    })
+}
+
+impl Achievements {
+    pub fn new() -> Self {
+        Achievements {
+            runtime: Runtime::new(),
+            achievements: Vec::new(),
+            leaderboards: Vec::new(),
+            hardcore: false,
+        }
+    }
+
+    // The patch data of RetroAchievements' API, with or without the
+    // {"PatchData": ...} around it.
+    pub fn from_json(text: &str) -> Result<Self, AchievementError> {
+        let json = Json::parse(text)?;
+        let set = json.get("PatchData").unwrap_or(&json);
+        let missing = |what: &str| AchievementError::Format(format!("missing {}", what));
+        let number = |entry: &Json, key: &str| {
+            entry
+                .get(key)
+                .and_then(Json::as_u64)
+                .map(|n| n as u32)
+                .ok_or_else(|| missing(key))
+        };
+        let text = |entry: &Json, key: &str| {
+            entry
+                .get(key)
+                .and_then(Json::as_str)
+                .unwrap_or_default()
+                .to_string()
+        };
+
+        let mut achievements = Achievements::new();
+        for entry in set
+            .get("Achievements")
+            .and_then(Json::as_array)
+            .unwrap_or(&[])
+        {
+            let id = number(entry, "ID")?;
+            let logic = entry.get("MemAddr").and_then(Json::as_str);
+            achievements.add_achievement(
+                id,
+                &text(entry, "Title"),
+                &text(entry, "Description"),
+                number(entry, "Points").unwrap_or(0),
+                logic.ok_or_else(|| missing("MemAddr"))?,
+            )?;
+        }
+        for entry in set
+            .get("Leaderboards")
+            .and_then(Json::as_array)
+            .unwrap_or(&[])
+        {
+            let id = number(entry, "ID")?;
+            let logic = entry.get("Mem").and_then(Json::as_str);
+            achievements.add_leaderboard(
+                id,
+                &text(entry, "Title"),
+                &text(entry, "Format"),
+                logic.ok_or_else(|| missing("Mem"))?,
+            )?;
+        }
+        Ok(achievements)
+    }
+
+    pub fn add_achievement(
+        &mut self,
+        id: u32,
+        title: &str,
+        description: &str,
+        points: u32,
+        logic_text: &str,
+    ) -> Result<(), AchievementError> {
+        activate(
+            &self.runtime,
+            rc_runtime_activate_achievement,
+            id,
+            logic_text,
+        )?;
+        self.achievements.push(Achievement {
+            id,
+            title: title.to_string(),
+            description: description.to_string(),
+            points,
+            unlocked: false,
+        });
+        Ok(())
+    }
+
+    // `logic_text` is STA:...::CAN:...::SUB:...::VAL:..., in any order.
+    pub fn add_leaderboard(
+        &mut self,
+        id: u32,
+        title: &str,
+        format: &str,
+        logic_text: &str,
+    ) -> Result<(), AchievementError> {
+        activate(&self.runtime, rc_runtime_activate_lboard, id, logic_text)?;
+        self.leaderboards.push(Leaderboard {
+            id,
+            title: title.to_string(),
+            format: format.to_string(),
+            running: false,
+            current: 0,
+        });
+        Ok(())
+    }
+
+    pub fn achievements(&self) -> &[Achievement] {
+        &self.achievements
+    }
+
+    pub fn leaderboards(&self) -> &[Leaderboard] {
+        &self.leaderboards
+    }
+
+    pub fn achievement(&self, id: u32) -> Option<&Achievement> {
+        self.achievements
+            .iter()
+            .find(|achievement| achievement.id == id)
+    }
+
+    pub fn leaderboard(&self, id: u32) -> Option<&Leaderboard> {
+        self.leaderboards.iter().find(|board| board.id == id)
+    }
+
+    // For the ones the site says the player already has.
+    pub fn mark_unlocked(&mut self, id: u32) {
+        for achievement in &mut self.achievements {
+            if achievement.id == id {
+                achievement.unlocked = true;
+                // SAFETY: the runtime is live
+                unsafe { rc_runtime_deactivate_achievement(self.runtime.get(), id) };
+            }
+        }
+    }
+
+    pub fn hardcore(&self) -> bool {
+        self.hardcore
+    }
+
+    pub fn set_hardcore(&mut self, hardcore: bool) {
+        self.hardcore = hardcore;
+    }
+
+    // After a reset or a state load: hit counts go, leaderboards stop
+    // and everything waits to be seen false again.
+    pub fn reset(&mut self) {
+        // SAFETY: the runtime is live
+        unsafe { rc_runtime_reset(self.runtime.get()) };
+        for board in &mut self.leaderboards {
+            board.running = false;
+        }
+    }
+
+    // After every frame, with a way to read the CPU's memory.
+    pub fn do_frame(&mut self, mut read: impl FnMut(u16) -> u8) -> Vec<AchievementEvent> {
+        let mut read: &mut dyn FnMut(u16) -> u8 = &mut read;
+        let ud = &mut read as *mut &mut dyn FnMut(u16) -> u8 as *mut c_void;
+        // SAFETY: the runtime is live, and `ud` is the reader peek expects
+        unsafe {
+            rc_runtime_do_frame(
+                self.runtime.get(),
+                Some(gather),
+                Some(peek),
+                ud,
+                ptr::null_mut(),
+            )
+        };
+        let happened = EVENTS.with(|events| std::mem::take(&mut *events.borrow_mut()));
+
+        let mut events = Vec::new();
+        for event in happened {
+            let (id, value) = (event.id, event.value as i64);
+            if event.type_ == RC_RUNTIME_EVENT_ACHIEVEMENT_TRIGGERED {
+                self.mark_unlocked(id);
+                events.push(AchievementEvent::Unlocked { id });
+                continue;
+            }
+            let Some(board) = self.leaderboards.iter_mut().find(|board| board.id == id) else {
+                continue;
+            };
+            match event.type_ {
+                RC_RUNTIME_EVENT_LBOARD_STARTED => {
+                    (board.running, board.current) = (true, value);
+                    events.push(AchievementEvent::LeaderboardStarted { id });
+                }
+                RC_RUNTIME_EVENT_LBOARD_UPDATED => board.current = value,
+                RC_RUNTIME_EVENT_LBOARD_CANCELED => {
+                    board.running = false;
+                    events.push(AchievementEvent::LeaderboardCanceled { id });
+                }
+                RC_RUNTIME_EVENT_LBOARD_TRIGGERED => {
+                    board.running = false;
+                    events.push(AchievementEvent::LeaderboardSubmitted { id, value });
+                }
+                _ => {}
+            }
+        }
+        events
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const SET: &str = r#"{"Success": true, "PatchData": {
+        "ID": 1, "Title": "Test",
+        "Achievements": [
+            {"ID": 10, "Title": "Level 2", "Description": "Reach it",
+             "Points": 5, "MemAddr": "0xH0010=2_d0xH0010=1"},
+            {"ID": 11, "Title": "Rich", "Description": "",
+             "Points": 10, "MemAddr": "0xH0011>=100"}
+        ],
+        "Leaderboards": [
+            {"ID": 20, "Title": "Fastest", "Format": "FRAMES",
+             "Mem": "STA:0xH0012=1::CAN:0xH0012=3::SUB:0xH0012=2::VAL:0xH0013*10_0xH0014"}
+        ]
+    }}"#;
+
+    fn frame(set: &mut Achievements, ram: &[u8]) -> Vec<AchievementEvent> {
+        set.do_frame(|addr| ram[addr as usize % ram.len()])
+    }
+
+    #[test]
+    fn test_unlocks_and_leaderboards() {
+        let mut set = Achievements::from_json(SET).unwrap();
+        assert_eq!(set.achievements().len(), 2);
+        assert_eq!(set.achievement(10).unwrap().points, 5);
+        let mut ram = [0u8; 0x20];
+        // already rich at the start: has to be seen false first
+        ram[0x11] = 150;
+
+        assert!(frame(&mut set, &ram).is_empty());
+        ram[0x10] = 1;
+        assert!(frame(&mut set, &ram).is_empty());
+        ram[0x10] = 2;
+        assert_eq!(
+            frame(&mut set, &ram),
+            [AchievementEvent::Unlocked { id: 10 }]
+        );
+        assert!(set.achievement(10).unwrap().is_unlocked());
+
+        ram[0x11] = 0;
+        frame(&mut set, &ram);
+        ram[0x11] = 100;
+        assert_eq!(
+            frame(&mut set, &ram),
+            [AchievementEvent::Unlocked { id: 11 }]
+        );
+
+        ram[0x12] = 1;
+        ram[0x13] = 4;
+        ram[0x14] = 2;
+        assert_eq!(
+            frame(&mut set, &ram),
+            [AchievementEvent::LeaderboardStarted { id: 20 }]
+        );
+        assert_eq!(set.leaderboards()[0].current(), Some(42));
+        ram[0x12] = 2;
+        assert_eq!(
+            frame(&mut set, &ram),
+            [AchievementEvent::LeaderboardSubmitted { id: 20, value: 42 }]
+        );
+        // it has to be seen not starting before it starts again
+        ram[0x12] = 0;
+        frame(&mut set, &ram);
+        ram[0x12] = 1;
+        assert_eq!(
+            frame(&mut set, &ram),
+            [AchievementEvent::LeaderboardStarted { id: 20 }]
+        );
+        ram[0x12] = 3;
+        assert_eq!(
+            frame(&mut set, &ram),
+            [AchievementEvent::LeaderboardCanceled { id: 20 }]
+        );
+    }
+
+    #[test]
+    fn test_reset_waits_again_and_bad_sets() {
+        let mut set = Achievements::new();
+        set.add_achievement(1, "On", "", 1, "0xH0000=1").unwrap();
+        set.add_achievement(2, "Had", "", 1, "0xH0000=1").unwrap();
+        set.mark_unlocked(2);
+        set.do_frame(|_| 0);
+        set.reset();
+        assert!(set.do_frame(|_| 1).is_empty());
+        set.do_frame(|_| 0);
+        assert_eq!(set.do_frame(|_| 1), [AchievementEvent::Unlocked { id: 1 }]);
+
+        assert!(matches!(
+            Achievements::from_json(r#"{"Achievements": [{"ID": 3, "MemAddr": "0xZ"}]}"#),
+            Err(AchievementError::Logic { id: 3, .. })
+        ));
+        assert!(matches!(
+            set.add_leaderboard(4, "", "", "STA:1=1::VAL:1"),
+            Err(AchievementError::Logic { id: 4, .. })
+        ));
+        assert!(Achievements::from_json("[").is_err());
+    }
+
+    #[test]
+    fn test_reads_words_little_endian() {
+        let mut set = Achievements::new();
+        set.add_achievement(1, "Word", "", 1, "0x 0010=4660")
+            .unwrap();
+        let mut ram = [0u8; 0x20];
+        frame(&mut set, &ram);
+        ram[0x10] = 0x34;
+        ram[0x11] = 0x12;
+        assert_eq!(
+            frame(&mut set, &ram),
+            [AchievementEvent::Unlocked { id: 1 }]
+        );
+        assert!(set.do_frame(|_| 0).is_empty());
+    }
+}
@@ -0,0 +1,257 @@
+// Band-limited step synthesis ("blip buffer").
+//
+// The APU output only changes on CPU clock edges, ~1.79MHz. Instead of
+// sampling that signal at the host rate (which aliases badly, the triangle
+// channel especially), every change in amplitude is recorded as a step and
+// drawn into the output as a band-limited step: a windowed-sinc impulse
+// that is integrated when samples are read. This both removes content above
+// the host Nyquist frequency and resamples to 44.1/48kHz in one pass.
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::f64::consts::PI;
+
+const PHASES: usize = 64;
+const KERNEL_WIDTH: usize = 16;
+const HALF_WIDTH: usize = KERNEL_WIDTH / 2;
+
+// Fraction of the output sample rate kept by the kernel. Slightly below
+// Nyquist (0.5) so the window's transition band doesn't fold back.
+const CUTOFF: f64 = 0.45;
+
+type Kernel = [[f32; KERNEL_WIDTH]; PHASES];
+
+#[derive(Debug)]
+pub struct BlipBuffer {
+    clock_rate: f64,
+    sample_rate: f64,
+    factor: f64, // output samples per input clock
+
+    // position, in output samples, of clock 0 of the current frame
+    offset: f64,
+    deltas: Vec<f32>,
+    integrator: f32,
+    amplitude: f32,
+    kernel: Box<Kernel>,
+}
+
+impl BlipBuffer {
+    pub fn new(clock_rate: f64, sample_rate: f64) -> Self {
+        BlipBuffer {
+            clock_rate,
+            sample_rate,
+            factor: sample_rate / clock_rate,
+            offset: 0.0,
+            deltas: Vec::new(),
+            integrator: 0.0,
+            amplitude: 0.0,
+            kernel: Box::new(build_kernel()),
+        }
+    }
+
+    pub fn clock_rate(&self) -> f64 {
+        self.clock_rate
+    }
+
+    pub fn sample_rate(&self) -> f64 {
+        self.sample_rate
+    }
+
+    // Changes the resampling ratio. Only call this between frames: clock
+    // times already passed to `add_delta` in the current frame were placed
+    // with the old ratio.
+    pub fn set_rates(&mut self, clock_rate: f64, sample_rate: f64) {
+        self.clock_rate = clock_rate;
+        self.sample_rate = sample_rate;
+        self.factor = sample_rate / clock_rate;
+    }
+
+    // Records a change of `delta` in the output at `clock` cycles after the
+    // start of the current frame.
+    pub fn add_delta(&mut self, clock: u32, delta: f32) {
+        if delta == 0.0 {
+            return;
+        }
+
+        let pos = self.offset + clock as f64 * self.factor;
+        let index = pos as usize;
+        let phase = ((pos - index as f64) * PHASES as f64) as usize;
+
+        if self.deltas.len() < index + KERNEL_WIDTH {
+            self.deltas.resize(index + KERNEL_WIDTH, 0.0);
+        }
+
+        let taps = &self.kernel[phase.min(PHASES - 1)];
+        for (slot, tap) in self.deltas[index..index + KERNEL_WIDTH]
+            .iter_mut()
+            .zip(taps)
+        {
+            *slot += delta * tap;
+        }
+    }
+
+    // Like `add_delta`, but takes the new absolute amplitude.
+    pub fn set_amplitude(&mut self, clock: u32, amplitude: f32) {
+        let delta = amplitude - self.amplitude;
+        self.amplitude = amplitude;
+        self.add_delta(clock, delta);
+    }
+
+    // Ends the current frame after `clocks` cycles, making the samples up to
+    // that point readable. Later clock times are relative to the new frame.
+    pub fn end_frame(&mut self, clocks: u32) {
+        self.offset += clocks as f64 * self.factor;
+    }
+
+    // Samples that no future `add_delta` can touch any more.
+    pub fn samples_avail(&self) -> usize {
+        self.offset as usize
+    }
+
+    pub fn read_samples(&mut self, out: &mut [f32]) -> usize {
+        let count = self.samples_avail().min(out.len());
+        if self.deltas.len() < count {
+            self.deltas.resize(count, 0.0);
+        }
+
+        for (sample, delta) in out.iter_mut().zip(&self.deltas[..count]) {
+            self.integrator += delta;
+            *sample = self.integrator;
+        }
+
+        self.deltas.drain(..count);
+        self.offset -= count as f64;
+        count
+    }
+
+    pub fn clear(&mut self) {
+        self.offset = 0.0;
+        self.deltas.clear();
+        self.integrator = 0.0;
+        self.amplitude = 0.0;
+    }
+}
+
+// One windowed-sinc impulse per sub-sample phase, each normalized so a unit
+// step integrates to exactly 1.0.
+fn build_kernel() -> Kernel {
+    let mut kernel = [[0.0; KERNEL_WIDTH]; PHASES];
+
+    for (phase, taps) in kernel.iter_mut().enumerate() {
+        let frac = phase as f64 / PHASES as f64;
+        let mut sum = 0.0;
+        let mut values = [0.0f64; KERNEL_WIDTH];
+
+        for (i, value) in values.iter_mut().enumerate() {
+            let x = i as f64 - HALF_WIDTH as f64 + 1.0 - frac;
+            *value = sinc(2.0 * CUTOFF * x) * blackman(x);
+            sum += *value;
+        }
+
+        for (tap, value) in taps.iter_mut().zip(values) {
+            *tap = (value / sum) as f32;
+        }
+    }
+
+    kernel
+}
+
+fn sinc(x: f64) -> f64 {
+    if x == 0.0 {
+        1.0
+    } else {
+        libm::sin(PI * x) / (PI * x)
+    }
+}
+
+fn blackman(x: f64) -> f64 {
+    let half = HALF_WIDTH as f64;
+    if x.abs() >= half {
+        return 0.0;
+    }
+    let t = PI * x / half;
+    0.42 + 0.5 * libm::cos(t) + 0.08 * libm::cos(2.0 * t)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::apu::NTSC_CPU_CLOCK as CLOCK;
+
+    #[test]
+    fn test_one_second_yields_sample_rate_samples() {
+        let mut blip = BlipBuffer::new(CLOCK, 48_000.0);
+        blip.end_frame(CLOCK as u32);
+
+        assert_eq!(blip.samples_avail(), 48_000);
+    }
+
+    #[test]
+    fn test_step_settles_to_amplitude() {
+        let mut blip = BlipBuffer::new(CLOCK, 44_100.0);
+        blip.set_amplitude(100, 0.5);
+        blip.end_frame(10_000);
+
+        let mut out = [0.0; 512];
+        let count = blip.read_samples(&mut out);
+
+        assert!(count > 200);
+        for sample in &out[count - 50..count] {
+            assert!((sample - 0.5).abs() < 0.0001);
+        }
+    }
+
+    #[test]
+    fn test_reading_continues_across_frames() {
+        let mut blip = BlipBuffer::new(CLOCK, 44_100.0);
+        let mut out = [0.0; 1024];
+
+        blip.set_amplitude(0, 1.0);
+        blip.end_frame(29_780);
+        let first = blip.read_samples(&mut out);
+
+        blip.end_frame(29_780);
+        let second = blip.read_samples(&mut out);
+
+        assert!(first + second >= 1466 && first + second <= 1467);
+        assert!((out[second - 1] - 1.0).abs() < 0.0001);
+    }
+
+    #[test]
+    fn test_ultrasonic_square_does_not_alias() {
+        // a ~40kHz square, like the triangle channel parked at a very high
+        // period to silence it; point sampling would produce a full-scale
+        // alias tone here
+        let mut blip = BlipBuffer::new(CLOCK, 44_100.0);
+        let mut clock = 0;
+        let mut high = false;
+        while clock < 100_000 {
+            high = !high;
+            blip.set_amplitude(clock, if high { 1.0 } else { 0.0 });
+            clock += 22;
+        }
+        blip.end_frame(100_000);
+
+        let mut out = [0.0; 4096];
+        let count = blip.read_samples(&mut out);
+        let settled = &out[100..count - 100];
+        let min = settled.iter().cloned().fold(f32::MAX, f32::min);
+        let max = settled.iter().cloned().fold(f32::MIN, f32::max);
+
+        assert!(max - min < 0.1, "peak to peak {}", max - min);
+    }
+
+    #[test]
+    fn test_clear_resets_output() {
+        let mut blip = BlipBuffer::new(CLOCK, 44_100.0);
+        blip.set_amplitude(0, 1.0);
+        blip.end_frame(10_000);
+        blip.clear();
+
+        assert_eq!(blip.samples_avail(), 0);
+        blip.end_frame(10_000);
+        let mut out = [0.0; 512];
+        let count = blip.read_samples(&mut out);
+        assert!(out[..count].iter().all(|s| *s == 0.0));
+    }
+}
@@ -0,0 +1,280 @@
+// Delta modulation channel. Sample bytes are fetched from CPU memory by DMA,
+// so the channel only asks for an address and the bus hands the byte back.
+
+use serde::{Deserialize, Serialize};
+
+type Address = u16;
+
+// CPU cycles per output bit
+const RATE_TABLE: [u16; 16] = [
+    428, 380, 340, 320, 286, 254, 226, 214, 190, 160, 142, 128, 106, 84, 72, 54,
+];
+const PAL_RATE_TABLE: [u16; 16] = [
+    398, 354, 316, 298, 276, 236, 210, 198, 176, 148, 132, 118, 98, 78, 66, 50,
+];
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Dmc {
+    irq_enabled: bool,
+    looping: bool,
+    irq_flag: bool,
+
+    timer_period: u16,
+    timer: u16,
+
+    sample_address: Address,
+    sample_length: u16,
+    current_address: Address,
+    bytes_remaining: u16,
+    sample_buffer: Option<u8>,
+
+    shift_register: u8,
+    bits_remaining: u8,
+    silence: bool,
+    output_level: u8,
+    #[serde(skip)]
+    pal: bool,
+}
+
+impl Default for Dmc {
+    fn default() -> Self {
+        Dmc {
+            irq_enabled: false,
+            looping: false,
+            irq_flag: false,
+            timer_period: RATE_TABLE[0],
+            timer: RATE_TABLE[0],
+            sample_address: 0xC000,
+            sample_length: 1,
+            current_address: 0xC000,
+            bytes_remaining: 0,
+            sample_buffer: None,
+            shift_register: 0,
+            bits_remaining: 8,
+            silence: true,
+            output_level: 0,
+            pal: false,
+        }
+    }
+}
+
+impl Dmc {
+    pub fn set_pal(&mut self, pal: bool) {
+        self.pal = pal;
+    }
+
+    // $4010
+    pub fn write_control(&mut self, value: u8) {
+        self.irq_enabled = value & 0b1000_0000 != 0;
+        self.looping = value & 0b0100_0000 != 0;
+        let table = if self.pal {
+            &PAL_RATE_TABLE
+        } else {
+            &RATE_TABLE
+        };
+        self.timer_period = table[(value & 0x0F) as usize];
+        if !self.irq_enabled {
+            self.irq_flag = false;
+        }
+    }
+
+    // $4011
+    pub fn write_output_level(&mut self, value: u8) {
+        self.output_level = value & 0x7F;
+    }
+
+    // $4012
+    pub fn write_sample_address(&mut self, value: u8) {
+        self.sample_address = 0xC000 | ((value as u16) << 6);
+    }
+
+    // $4013
+    pub fn write_sample_length(&mut self, value: u8) {
+        self.sample_length = ((value as u16) << 4) | 1;
+    }
+
+    // $4015 bit 4. Enabling only restarts the sample if the previous one has
+    // finished; disabling drops the remaining bytes (the buffered byte still
+    // plays out).
+    pub fn set_enabled(&mut self, enabled: bool) {
+        if !enabled {
+            self.bytes_remaining = 0;
+        } else if self.bytes_remaining == 0 {
+            self.restart();
+        }
+    }
+
+    fn restart(&mut self) {
+        self.current_address = self.sample_address;
+        self.bytes_remaining = self.sample_length;
+    }
+
+    pub fn bytes_remaining(&self) -> u16 {
+        self.bytes_remaining
+    }
+
+    pub fn irq_flag(&self) -> bool {
+        self.irq_flag
+    }
+
+    pub fn clear_irq(&mut self) {
+        self.irq_flag = false;
+    }
+
+    pub fn output(&self) -> u8 {
+        self.output_level
+    }
+
+    // Address the memory reader wants fetched, if its buffer is empty.
+    pub fn dma_request(&self) -> Option<Address> {
+        if self.sample_buffer.is_none() && self.bytes_remaining > 0 {
+            Some(self.current_address)
+        } else {
+            None
+        }
+    }
+
+    pub fn dma_complete(&mut self, value: u8) {
+        self.sample_buffer = Some(value);
+        self.current_address = self.current_address.checked_add(1).unwrap_or(0x8000);
+        self.bytes_remaining = self.bytes_remaining.saturating_sub(1);
+
+        if self.bytes_remaining == 0 {
+            if self.looping {
+                self.restart();
+            } else if self.irq_enabled {
+                self.irq_flag = true;
+            }
+        }
+    }
+
+    // One CPU cycle.
+    pub fn tick(&mut self) {
+        if self.timer > 1 {
+            self.timer -= 1;
+            return;
+        }
+        self.timer = self.timer_period;
+
+        if !self.silence {
+            if self.shift_register & 1 != 0 {
+                if self.output_level <= 125 {
+                    self.output_level += 2;
+                }
+            } else if self.output_level >= 2 {
+                self.output_level -= 2;
+            }
+        }
+        self.shift_register >>= 1;
+
+        self.bits_remaining = self.bits_remaining.saturating_sub(1);
+        if self.bits_remaining == 0 {
+            self.bits_remaining = 8;
+            match self.sample_buffer.take() {
+                Some(byte) => {
+                    self.shift_register = byte;
+                    self.silence = false;
+                }
+                None => self.silence = true,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // feeds DMA requests with `byte` until the sample ends
+    fn play(dmc: &mut Dmc, byte: u8, cycles: u32) {
+        for _ in 0..cycles {
+            if dmc.dma_request().is_some() {
+                dmc.dma_complete(byte);
+            }
+            dmc.tick();
+        }
+    }
+
+    #[test]
+    fn test_pal_rates() {
+        let mut dmc = Dmc::default();
+        dmc.write_control(0x0F);
+        assert_eq!(dmc.timer_period, 54);
+        dmc.set_pal(true);
+        dmc.write_control(0x0F);
+        assert_eq!(dmc.timer_period, 50);
+    }
+
+    #[test]
+    fn test_sample_address_and_length() {
+        let mut dmc = Dmc::default();
+        dmc.write_sample_address(0x01);
+        dmc.write_sample_length(0x02);
+        dmc.set_enabled(true);
+
+        assert_eq!(dmc.dma_request(), Some(0xC040));
+        assert_eq!(dmc.bytes_remaining(), 33);
+    }
+
+    #[test]
+    fn test_irq_when_sample_ends() {
+        let mut dmc = Dmc::default();
+        dmc.write_control(0x80 | 0x0F);
+        dmc.set_enabled(true);
+        dmc.dma_complete(0x00);
+
+        assert_eq!(dmc.bytes_remaining(), 0);
+        assert!(dmc.irq_flag());
+
+        dmc.write_control(0x0F);
+        assert!(!dmc.irq_flag());
+    }
+
+    #[test]
+    fn test_looping_sample_restarts() {
+        let mut dmc = Dmc::default();
+        dmc.write_control(0x80 | 0x40);
+        dmc.set_enabled(true);
+        dmc.dma_complete(0x00);
+
+        assert_eq!(dmc.bytes_remaining(), 1);
+        assert!(!dmc.irq_flag());
+    }
+
+    #[test]
+    fn test_disable_drops_remaining_bytes() {
+        let mut dmc = Dmc::default();
+        dmc.write_sample_length(0xFF);
+        dmc.set_enabled(true);
+        dmc.set_enabled(false);
+
+        assert_eq!(dmc.bytes_remaining(), 0);
+        assert_eq!(dmc.dma_request(), None);
+    }
+
+    #[test]
+    fn test_address_wraps_to_8000() {
+        let mut dmc = Dmc::default();
+        dmc.write_sample_address(0xFF);
+        dmc.write_sample_length(0xFF);
+        dmc.set_enabled(true);
+        for _ in 0..0x40 {
+            dmc.dma_complete(0);
+        }
+        assert_eq!(dmc.dma_request(), None); // buffer full
+        dmc.sample_buffer = None;
+        assert_eq!(dmc.dma_request(), Some(0x8000));
+    }
+
+    #[test]
+    fn test_output_level_follows_bits() {
+        let mut dmc = Dmc::default();
+        dmc.write_control(0x0F); // fastest rate
+        dmc.write_output_level(0x40);
+        dmc.write_sample_length(0x01);
+        dmc.set_enabled(true);
+
+        play(&mut dmc, 0xFF, 54 * 8 * 4);
+        assert!(dmc.output() > 0x40);
+    }
+}
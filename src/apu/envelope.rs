@@ -0,0 +1,92 @@
+// Volume envelope of the pulse and noise channels: either a constant volume
+// or a sawtooth that decays from 15 to 0, one step per `period + 1` quarter
+// frames, optionally looping.
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize)]
+pub struct Envelope {
+    start: bool,
+    looping: bool,
+    constant: bool,
+    period: u8,
+    divider: u8,
+    decay: u8,
+}
+
+impl Envelope {
+    // Low six bits of $4000/$4004/$400C. The loop flag doubles as the length
+    // counter halt bit.
+    pub fn write(&mut self, value: u8) {
+        self.looping = value & 0b0010_0000 != 0;
+        self.constant = value & 0b0001_0000 != 0;
+        self.period = value & 0x0F;
+    }
+
+    // Writing the channel's fourth register restarts the decay.
+    pub fn restart(&mut self) {
+        self.start = true;
+    }
+
+    // Clocked by the frame counter on every quarter frame.
+    pub fn clock(&mut self) {
+        if self.start {
+            self.start = false;
+            self.decay = 15;
+            self.divider = self.period;
+        } else if self.divider > 0 {
+            self.divider -= 1;
+        } else {
+            self.divider = self.period;
+            if self.decay > 0 {
+                self.decay -= 1;
+            } else if self.looping {
+                self.decay = 15;
+            }
+        }
+    }
+
+    pub fn output(&self) -> u8 {
+        if self.constant {
+            self.period
+        } else {
+            self.decay
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_constant_volume() {
+        let mut envelope = Envelope::default();
+        envelope.write(0b0001_0111);
+        envelope.restart();
+        envelope.clock();
+        envelope.clock();
+
+        assert_eq!(envelope.output(), 7);
+    }
+
+    #[test]
+    fn test_decay_and_loop() {
+        let mut envelope = Envelope::default();
+        envelope.write(0b0000_0000); // period 0: one step per clock
+        envelope.restart();
+        envelope.clock();
+        assert_eq!(envelope.output(), 15);
+
+        for _ in 0..15 {
+            envelope.clock();
+        }
+        assert_eq!(envelope.output(), 0);
+        envelope.clock();
+        assert_eq!(envelope.output(), 0);
+
+        envelope.write(0b0010_0000);
+        envelope.clock();
+        assert_eq!(envelope.output(), 15);
+    }
+}
@@ -0,0 +1,34 @@
+// Sound chips on the cartridge. Their output is summed with the 2A03 mix
+// before resampling, each chip scaled by a user-adjustable volume.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ExpansionChip {
+    Vrc6,
+    Vrc7,
+    Namco163,
+    Fds,
+    Sunsoft5B,
+    Mmc5,
+}
+
+pub trait ExpansionAudio: Send {
+    fn chip(&self) -> ExpansionChip;
+
+    // Advances the chip by one CPU cycle.
+    fn clock(&mut self);
+
+    // Register writes the board routes to the chip.
+    fn write_register(&mut self, addr: u16, value: u8);
+
+    // Readable registers (N163 sound RAM, FDS wave RAM); None for open bus.
+    fn read_register(&mut self, _addr: u16) -> Option<u8> {
+        None
+    }
+
+    fn channel_count(&self) -> usize;
+
+    // Current output of one channel, scaled so that 1.0 is as loud as a
+    // single APU pulse channel at full volume. Implementations bake their
+    // chip's hardware level relative to the 2A03 into this.
+    fn channel_output(&self, channel: usize) -> f32;
+}
@@ -0,0 +1,151 @@
+// The first-order filters between the 2A03 and the RF/AV output of a
+// front-loading NES: two high-passes at ~90Hz and ~440Hz and a low-pass at
+// ~14kHz (nesdev wiki, "APU Mixer"). They run on the resampled output.
+
+use core::f32::consts::PI;
+
+#[derive(Debug, Clone, Copy)]
+pub struct HighPass {
+    alpha: f32,
+    prev_input: f32,
+    prev_output: f32,
+}
+
+impl HighPass {
+    pub fn new(sample_rate: f32, cutoff: f32) -> Self {
+        let rc = 1.0 / (2.0 * PI * cutoff);
+        let dt = 1.0 / sample_rate;
+        HighPass {
+            alpha: rc / (rc + dt),
+            prev_input: 0.0,
+            prev_output: 0.0,
+        }
+    }
+
+    pub fn process(&mut self, input: f32) -> f32 {
+        let output = self.alpha * (self.prev_output + input - self.prev_input);
+        self.prev_input = input;
+        self.prev_output = output;
+        output
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct LowPass {
+    alpha: f32,
+    prev_output: f32,
+}
+
+impl LowPass {
+    pub fn new(sample_rate: f32, cutoff: f32) -> Self {
+        let rc = 1.0 / (2.0 * PI * cutoff);
+        let dt = 1.0 / sample_rate;
+        LowPass {
+            alpha: dt / (rc + dt),
+            prev_output: 0.0,
+        }
+    }
+
+    pub fn process(&mut self, input: f32) -> f32 {
+        self.prev_output += self.alpha * (input - self.prev_output);
+        self.prev_output
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct OutputFilter {
+    enabled: bool,
+    high_pass_90: HighPass,
+    high_pass_440: HighPass,
+    low_pass_14k: LowPass,
+}
+
+impl OutputFilter {
+    pub fn new(sample_rate: f32) -> Self {
+        OutputFilter {
+            enabled: true,
+            high_pass_90: HighPass::new(sample_rate, 90.0),
+            high_pass_440: HighPass::new(sample_rate, 440.0),
+            low_pass_14k: LowPass::new(sample_rate, 14_000.0),
+        }
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn process(&mut self, sample: f32) -> f32 {
+        if !self.enabled {
+            return sample;
+        }
+        let sample = self.high_pass_90.process(sample);
+        let sample = self.high_pass_440.process(sample);
+        self.low_pass_14k.process(sample)
+    }
+
+    pub fn process_buffer(&mut self, samples: &mut [f32]) {
+        for sample in samples {
+            *sample = self.process(*sample);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const RATE: f32 = 44_100.0;
+
+    fn peak_after_settling(filter: &mut OutputFilter, freq: f32) -> f32 {
+        let mut peak: f32 = 0.0;
+        for n in 0..RATE as usize {
+            let input = (2.0 * PI * freq * n as f32 / RATE).sin();
+            let output = filter.process(input);
+            if n > RATE as usize / 2 {
+                peak = peak.max(output.abs());
+            }
+        }
+        peak
+    }
+
+    #[test]
+    fn test_dc_offset_is_removed() {
+        let mut filter = OutputFilter::new(RATE);
+        let mut output = 1.0;
+        for _ in 0..RATE as usize {
+            output = filter.process(0.6);
+        }
+        assert!(output.abs() < 0.001);
+    }
+
+    #[test]
+    fn test_midrange_passes() {
+        let mut filter = OutputFilter::new(RATE);
+        assert!(peak_after_settling(&mut filter, 2_000.0) > 0.85);
+    }
+
+    #[test]
+    fn test_bass_and_treble_are_attenuated() {
+        let low = peak_after_settling(&mut OutputFilter::new(RATE), 60.0);
+        let high = peak_after_settling(&mut OutputFilter::new(RATE), 20_000.0);
+        let mid = peak_after_settling(&mut OutputFilter::new(RATE), 2_000.0);
+
+        assert!(low < mid / 2.0);
+        assert!(high < mid);
+    }
+
+    #[test]
+    fn test_disabled_filter_passes_through() {
+        let mut filter = OutputFilter::new(RATE);
+        filter.set_enabled(false);
+        let mut samples = [0.6, 0.1, 0.9];
+        filter.process_buffer(&mut samples);
+
+        assert!(!filter.is_enabled());
+        assert_eq!(samples, [0.6, 0.1, 0.9]);
+    }
+}
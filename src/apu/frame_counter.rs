@@ -0,0 +1,143 @@
+// The frame counter ($4017) divides the CPU clock into quarter and half
+// frames that drive envelopes and length counters, and raises the frame IRQ
+// at the end of each 4-step sequence. Step times are CPU cycles, longer
+// on a PAL console.
+//
+// The 3-4 cycle delay before a $4017 write resets the sequence is not
+// modelled.
+
+use serde::{Deserialize, Serialize};
+
+// the four steps, then the 5-step sequence's last; each sequence starts
+// over a cycle after its last step
+const NTSC_STEPS: [u32; 5] = [7457, 14913, 22371, 29829, 37281];
+const PAL_STEPS: [u32; 5] = [8313, 16627, 24939, 33253, 41565];
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct FrameClock {
+    pub quarter: bool,
+    pub half: bool,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct FrameCounter {
+    cycle: u32,
+    five_step: bool,
+    irq_inhibit: bool,
+    irq_flag: bool,
+    #[serde(skip)]
+    pal: bool,
+}
+
+impl FrameCounter {
+    pub fn set_pal(&mut self, pal: bool) {
+        self.pal = pal;
+    }
+
+    // Returns the units to clock immediately: selecting 5-step mode clocks
+    // everything at once.
+    pub fn write(&mut self, value: u8) -> FrameClock {
+        self.five_step = value & 0b1000_0000 != 0;
+        self.irq_inhibit = value & 0b0100_0000 != 0;
+        if self.irq_inhibit {
+            self.irq_flag = false;
+        }
+        self.cycle = 0;
+
+        FrameClock {
+            quarter: self.five_step,
+            half: self.five_step,
+        }
+    }
+
+    pub fn tick(&mut self) -> FrameClock {
+        self.cycle = self.cycle.wrapping_add(1);
+        let steps = if self.pal { &PAL_STEPS } else { &NTSC_STEPS };
+        let last = if self.five_step { steps[4] } else { steps[3] };
+
+        let cycle = self.cycle;
+        let clock = FrameClock {
+            quarter: cycle == steps[0] || cycle == steps[1] || cycle == steps[2] || cycle == last,
+            half: cycle == steps[1] || cycle == last,
+        };
+
+        if !self.five_step && cycle >= steps[3] - 1 && !self.irq_inhibit {
+            self.irq_flag = true;
+        }
+
+        if cycle > last {
+            self.cycle = 0;
+        }
+
+        clock
+    }
+
+    pub fn irq_flag(&self) -> bool {
+        self.irq_flag
+    }
+
+    pub fn clear_irq(&mut self) {
+        self.irq_flag = false;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn run(counter: &mut FrameCounter, cycles: u32) -> (u32, u32) {
+        let (mut quarters, mut halves) = (0, 0);
+        for _ in 0..cycles {
+            let clock = counter.tick();
+            quarters += clock.quarter as u32;
+            halves += clock.half as u32;
+        }
+        (quarters, halves)
+    }
+
+    const FOUR_STEP_PERIOD: u32 = NTSC_STEPS[3] + 1;
+
+    #[test]
+    fn test_four_step_sequence() {
+        let mut counter = FrameCounter::default();
+        assert_eq!(run(&mut counter, FOUR_STEP_PERIOD), (4, 2));
+        assert!(counter.irq_flag());
+        // and it starts over
+        assert_eq!(run(&mut counter, FOUR_STEP_PERIOD), (4, 2));
+    }
+
+    #[test]
+    fn test_five_step_sequence_has_no_irq() {
+        let mut counter = FrameCounter::default();
+        let immediate = counter.write(0x80);
+
+        assert!(immediate.quarter && immediate.half);
+        assert_eq!(run(&mut counter, NTSC_STEPS[4] + 1), (4, 2));
+        assert!(!counter.irq_flag());
+    }
+
+    #[test]
+    fn test_pal_steps_are_longer() {
+        let mut counter = FrameCounter::default();
+        counter.set_pal(true);
+        assert_eq!(run(&mut counter, FOUR_STEP_PERIOD), (3, 1));
+        assert!(!counter.irq_flag());
+        assert_eq!(
+            run(&mut counter, PAL_STEPS[3] + 1 - FOUR_STEP_PERIOD),
+            (1, 1)
+        );
+        assert!(counter.irq_flag());
+    }
+
+    #[test]
+    fn test_inhibit_clears_and_blocks_irq() {
+        let mut counter = FrameCounter::default();
+        run(&mut counter, FOUR_STEP_PERIOD);
+        assert!(counter.irq_flag());
+
+        counter.write(0x40);
+        assert!(!counter.irq_flag());
+        run(&mut counter, FOUR_STEP_PERIOD);
+        assert!(!counter.irq_flag());
+    }
+}
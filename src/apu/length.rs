@@ -0,0 +1,102 @@
+// Length counter shared by the pulse, triangle and noise channels. A channel
+// is silenced (and reports 0 in $4015) once its counter reaches zero.
+
+use serde::{Deserialize, Serialize};
+
+const LENGTH_TABLE: [u8; 32] = [
+    10, 254, 20, 2, 40, 4, 80, 6, 160, 8, 60, 10, 14, 12, 26, 14, //
+    12, 16, 24, 18, 48, 20, 96, 22, 192, 24, 72, 26, 16, 28, 32, 30,
+];
+
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize)]
+pub struct LengthCounter {
+    enabled: bool,
+    halt: bool,
+    counter: u8,
+}
+
+impl LengthCounter {
+    // $4015 enable bit; disabling clears the counter immediately.
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        if !enabled {
+            self.counter = 0;
+        }
+    }
+
+    pub fn set_halt(&mut self, halt: bool) {
+        self.halt = halt;
+    }
+
+    // Loads from the upper 5 bits of the channel's fourth register. Ignored
+    // while the channel is disabled.
+    pub fn load(&mut self, register: u8) {
+        if self.enabled {
+            self.counter = LENGTH_TABLE[(register >> 3) as usize];
+        }
+    }
+
+    // Clocked by the frame counter on every half frame.
+    pub fn clock(&mut self) {
+        if !self.halt && self.counter > 0 {
+            self.counter -= 1;
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.counter > 0
+    }
+
+    pub fn counter(&self) -> u8 {
+        self.counter
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_load_uses_length_table() {
+        let mut length = LengthCounter::default();
+        length.set_enabled(true);
+        length.load(0b0000_1000); // index 1
+
+        assert_eq!(length.counter(), 254);
+    }
+
+    #[test]
+    fn test_load_ignored_while_disabled() {
+        let mut length = LengthCounter::default();
+        length.load(0xF8);
+
+        assert!(!length.is_active());
+    }
+
+    #[test]
+    fn test_disable_clears_counter() {
+        let mut length = LengthCounter::default();
+        length.set_enabled(true);
+        length.load(0x00);
+        length.set_enabled(false);
+
+        assert!(!length.is_active());
+    }
+
+    #[test]
+    fn test_clock_counts_down_unless_halted() {
+        let mut length = LengthCounter::default();
+        length.set_enabled(true);
+        length.load(0b0001_1000); // index 3 -> 2
+
+        length.set_halt(true);
+        length.clock();
+        assert_eq!(length.counter(), 2);
+
+        length.set_halt(false);
+        length.clock();
+        length.clock();
+        length.clock();
+        assert_eq!(length.counter(), 0);
+    }
+}
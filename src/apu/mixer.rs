@@ -0,0 +1,413 @@
+// The 2A03 does not sum its channels linearly: the pulse pair and the
+// triangle/noise/DMC group each drive a resistor DAC whose output compresses
+// as more current flows. These tables are the standard approximation of that
+// curve (nesdev wiki, "APU Mixer"), indexed by the summed channel levels.
+
+use core::fmt;
+
+use super::expansion::{ExpansionAudio, ExpansionChip};
+
+const PULSE_TABLE_LEN: usize = 31; // 15 + 15 + 1
+const TND_TABLE_LEN: usize = 203; // 3 * 15 + 2 * 15 + 127 + 1
+
+const PULSE_TABLE: [f32; PULSE_TABLE_LEN] = build_pulse_table();
+const TND_TABLE: [f32; TND_TABLE_LEN] = build_tnd_table();
+
+const fn build_pulse_table() -> [f32; PULSE_TABLE_LEN] {
+    let mut table = [0.0; PULSE_TABLE_LEN];
+    let mut n = 1;
+    while n < PULSE_TABLE_LEN {
+        table[n] = 95.52 / (8128.0 / n as f32 + 100.0);
+        n += 1;
+    }
+    table
+}
+
+const fn build_tnd_table() -> [f32; TND_TABLE_LEN] {
+    let mut table = [0.0; TND_TABLE_LEN];
+    let mut n = 1;
+    while n < TND_TABLE_LEN {
+        table[n] = 163.67 / (24329.0 / n as f32 + 100.0);
+        n += 1;
+    }
+    table
+}
+
+// Current 4-bit (7-bit for the DMC) output level of every channel.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ChannelLevels {
+    pub pulse1: u8,
+    pub pulse2: u8,
+    pub triangle: u8,
+    pub noise: u8,
+    pub dmc: u8,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Channel {
+    Pulse1,
+    Pulse2,
+    Triangle,
+    Noise,
+    Dmc,
+    // nth channel of the cartridge's expansion audio chip, if any
+    Expansion(u8),
+}
+
+const MAX_EXPANSION_CHANNELS: u8 = 27;
+
+impl fmt::Display for Channel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Channel::Pulse1 => write!(f, "pulse1"),
+            Channel::Pulse2 => write!(f, "pulse2"),
+            Channel::Triangle => write!(f, "triangle"),
+            Channel::Noise => write!(f, "noise"),
+            Channel::Dmc => write!(f, "dmc"),
+            Channel::Expansion(n) => write!(f, "expansion{}", n),
+        }
+    }
+}
+
+impl Channel {
+    fn bit(self) -> u32 {
+        match self {
+            Channel::Pulse1 => 1 << 0,
+            Channel::Pulse2 => 1 << 1,
+            Channel::Triangle => 1 << 2,
+            Channel::Noise => 1 << 3,
+            Channel::Dmc => 1 << 4,
+            // past these, two channels would share a bit
+            Channel::Expansion(n) => {
+                assert!(n < MAX_EXPANSION_CHANNELS, "expansion channel {}", n);
+                1 << (5 + n)
+            }
+        }
+    }
+}
+
+const EXPANSION_CHIPS: usize = 6;
+
+#[derive(Debug)]
+pub struct Mixer {
+    muted: u32,
+    soloed: u32,
+    expansion_volume: [f32; EXPANSION_CHIPS],
+}
+
+impl Default for Mixer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Mixer {
+    pub fn new() -> Self {
+        Mixer {
+            muted: 0,
+            soloed: 0,
+            expansion_volume: [1.0; EXPANSION_CHIPS],
+        }
+    }
+
+    // 1.0 plays the chip at its hardware level relative to the 2A03.
+    pub fn set_expansion_volume(&mut self, chip: ExpansionChip, volume: f32) {
+        self.expansion_volume[chip as usize] = volume.max(0.0);
+    }
+
+    pub fn expansion_volume(&self, chip: ExpansionChip) -> f32 {
+        self.expansion_volume[chip as usize]
+    }
+
+    pub fn set_muted(&mut self, channel: Channel, muted: bool) {
+        if muted {
+            self.muted |= channel.bit();
+        } else {
+            self.muted &= !channel.bit();
+        }
+    }
+
+    pub fn is_muted(&self, channel: Channel) -> bool {
+        self.muted & channel.bit() != 0
+    }
+
+    pub fn toggle_muted(&mut self, channel: Channel) {
+        self.muted ^= channel.bit();
+    }
+
+    // While any channel is soloed, only soloed channels are heard and mutes
+    // are ignored.
+    pub fn set_solo(&mut self, channel: Channel, solo: bool) {
+        if solo {
+            self.soloed |= channel.bit();
+        } else {
+            self.soloed &= !channel.bit();
+        }
+    }
+
+    pub fn is_solo(&self, channel: Channel) -> bool {
+        self.soloed & channel.bit() != 0
+    }
+
+    pub fn toggle_solo(&mut self, channel: Channel) {
+        self.soloed ^= channel.bit();
+    }
+
+    pub fn clear_solo(&mut self) {
+        self.soloed = 0;
+    }
+
+    pub fn is_audible(&self, channel: Channel) -> bool {
+        if self.soloed != 0 {
+            self.is_solo(channel)
+        } else {
+            !self.is_muted(channel)
+        }
+    }
+
+    fn level(&self, channel: Channel, level: u8, mask: u8) -> usize {
+        if self.is_audible(channel) {
+            (level & mask) as usize
+        } else {
+            0
+        }
+    }
+
+    // Returns the mixed output in the range 0.0..=1.0 (roughly; the tables
+    // peak a little below that).
+    pub fn mix(&self, levels: &ChannelLevels) -> f32 {
+        let pulse = self.level(Channel::Pulse1, levels.pulse1, 0x0F)
+            + self.level(Channel::Pulse2, levels.pulse2, 0x0F);
+        let tnd = 3 * self.level(Channel::Triangle, levels.triangle, 0x0F)
+            + 2 * self.level(Channel::Noise, levels.noise, 0x0F)
+            + self.level(Channel::Dmc, levels.dmc, 0x7F);
+
+        PULSE_TABLE[pulse] + TND_TABLE[tnd]
+    }
+
+    // `mix` plus the cartridge's sound chip, if it has one.
+    pub fn mix_with_expansion(
+        &self,
+        levels: &ChannelLevels,
+        expansion: Option<&dyn ExpansionAudio>,
+    ) -> f32 {
+        let mut output = self.mix(levels);
+
+        if let Some(chip) = expansion {
+            let sum: f32 = (0..chip.channel_count())
+                .filter(|&n| self.is_audible(Channel::Expansion(n as u8)))
+                .map(|n| chip.channel_output(n))
+                .sum();
+            output += sum * PULSE_TABLE[15] * self.expansion_volume(chip.chip());
+        }
+
+        output
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn close(a: f32, b: f32) -> bool {
+        (a - b).abs() < 0.0005
+    }
+
+    #[test]
+    fn test_silence_mixes_to_zero() {
+        let mixer = Mixer::new();
+        assert_eq!(mixer.mix(&ChannelLevels::default()), 0.0);
+    }
+
+    #[test]
+    fn test_full_scale_matches_hardware_formula() {
+        let mixer = Mixer::new();
+        let levels = ChannelLevels {
+            pulse1: 15,
+            pulse2: 15,
+            triangle: 15,
+            noise: 15,
+            dmc: 127,
+        };
+
+        // pulse_table[30] + tnd_table[202]
+        assert!(close(mixer.mix(&levels), 0.2575 + 0.7425));
+    }
+
+    #[test]
+    fn test_pulse_mixing_is_non_linear() {
+        let mixer = Mixer::new();
+        let one = mixer.mix(&ChannelLevels {
+            pulse1: 15,
+            ..Default::default()
+        });
+        let both = mixer.mix(&ChannelLevels {
+            pulse1: 15,
+            pulse2: 15,
+            ..Default::default()
+        });
+
+        assert!(both > one);
+        assert!(both < 2.0 * one);
+    }
+
+    #[test]
+    fn test_triangle_is_louder_than_noise_at_same_level() {
+        let mixer = Mixer::new();
+        let triangle = mixer.mix(&ChannelLevels {
+            triangle: 8,
+            ..Default::default()
+        });
+        let noise = mixer.mix(&ChannelLevels {
+            noise: 8,
+            ..Default::default()
+        });
+
+        assert!(triangle > noise);
+    }
+
+    #[test]
+    fn test_out_of_range_levels_are_masked() {
+        let mixer = Mixer::new();
+        let masked = mixer.mix(&ChannelLevels {
+            pulse1: 0xFF,
+            dmc: 0xFF,
+            ..Default::default()
+        });
+        let max = mixer.mix(&ChannelLevels {
+            pulse1: 15,
+            dmc: 127,
+            ..Default::default()
+        });
+
+        assert_eq!(masked, max);
+    }
+
+    const LOUD: ChannelLevels = ChannelLevels {
+        pulse1: 15,
+        pulse2: 15,
+        triangle: 15,
+        noise: 15,
+        dmc: 127,
+    };
+
+    #[test]
+    fn test_muted_channel_is_silent() {
+        let mut mixer = Mixer::new();
+        for channel in [
+            Channel::Pulse1,
+            Channel::Pulse2,
+            Channel::Triangle,
+            Channel::Noise,
+            Channel::Dmc,
+        ] {
+            mixer.set_muted(channel, true);
+        }
+
+        assert!(mixer.is_muted(Channel::Dmc));
+        assert_eq!(mixer.mix(&LOUD), 0.0);
+
+        mixer.toggle_muted(Channel::Triangle);
+        let triangle_only = mixer.mix(&ChannelLevels {
+            triangle: 15,
+            ..Default::default()
+        });
+        assert_eq!(mixer.mix(&LOUD), triangle_only);
+    }
+
+    #[test]
+    fn test_solo_overrides_mutes() {
+        let mut mixer = Mixer::new();
+        mixer.set_muted(Channel::Noise, true);
+        mixer.set_solo(Channel::Noise, true);
+        mixer.set_solo(Channel::Pulse2, true);
+
+        assert!(mixer.is_audible(Channel::Noise));
+        assert!(mixer.is_audible(Channel::Pulse2));
+        assert!(!mixer.is_audible(Channel::Pulse1));
+        assert!(!mixer.is_audible(Channel::Expansion(0)));
+
+        let expected = Mixer::new().mix(&ChannelLevels {
+            pulse2: 15,
+            noise: 15,
+            ..Default::default()
+        });
+        assert_eq!(mixer.mix(&LOUD), expected);
+
+        mixer.clear_solo();
+        assert!(!mixer.is_audible(Channel::Noise));
+        assert!(mixer.is_audible(Channel::Pulse1));
+    }
+
+    struct TestChip([f32; 2]);
+
+    impl ExpansionAudio for TestChip {
+        fn chip(&self) -> ExpansionChip {
+            ExpansionChip::Vrc6
+        }
+        fn clock(&mut self) {}
+        fn write_register(&mut self, _addr: u16, _value: u8) {}
+        fn channel_count(&self) -> usize {
+            2
+        }
+        fn channel_output(&self, channel: usize) -> f32 {
+            self.0[channel]
+        }
+    }
+
+    #[test]
+    fn test_expansion_audio_is_added_to_the_mix() {
+        let mixer = Mixer::new();
+        let levels = ChannelLevels {
+            pulse1: 15,
+            ..Default::default()
+        };
+        let base = mixer.mix(&levels);
+
+        assert_eq!(mixer.mix_with_expansion(&levels, None), base);
+
+        // one expansion channel at 1.0 is as loud as one full pulse
+        let chip = TestChip([1.0, 0.0]);
+        let mixed = mixer.mix_with_expansion(&levels, Some(&chip));
+        assert!(close(mixed, 2.0 * base));
+    }
+
+    #[test]
+    fn test_expansion_volume_and_mutes() {
+        let mut mixer = Mixer::new();
+        let chip = TestChip([1.0, 0.5]);
+        let silent = ChannelLevels::default();
+        let full = mixer.mix_with_expansion(&silent, Some(&chip));
+
+        mixer.set_expansion_volume(ExpansionChip::Vrc6, 0.5);
+        assert_eq!(mixer.expansion_volume(ExpansionChip::Vrc6), 0.5);
+        assert_eq!(mixer.expansion_volume(ExpansionChip::Fds), 1.0);
+        assert!(close(
+            mixer.mix_with_expansion(&silent, Some(&chip)),
+            full / 2.0
+        ));
+
+        mixer.set_expansion_volume(ExpansionChip::Vrc6, 1.0);
+        mixer.set_muted(Channel::Expansion(0), true);
+        assert!(close(
+            mixer.mix_with_expansion(&silent, Some(&chip)),
+            full / 3.0
+        ));
+    }
+
+    #[test]
+    fn test_expansion_channels_are_independent() {
+        let mut mixer = Mixer::new();
+        mixer.set_muted(Channel::Expansion(2), true);
+
+        assert!(mixer.is_audible(Channel::Expansion(1)));
+        assert!(!mixer.is_audible(Channel::Expansion(2)));
+        assert!(mixer.is_audible(Channel::Dmc));
+    }
+
+    #[test]
+    #[should_panic(expected = "expansion channel 27")]
+    fn test_expansion_channels_past_the_mask_panic() {
+        Mixer::new().set_muted(Channel::Expansion(27), true);
+    }
+}
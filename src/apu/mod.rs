@@ -0,0 +1,406 @@
+pub mod blip;
+pub mod dmc;
+pub mod envelope;
+pub mod expansion;
+pub mod filter;
+pub mod frame_counter;
+pub mod length;
+pub mod mixer;
+pub mod noise;
+pub mod pulse;
+pub mod triangle;
+
+use alloc::vec::Vec;
+
+use serde::{Deserialize, Serialize};
+
+use blip::BlipBuffer;
+use dmc::Dmc;
+use expansion::ExpansionAudio;
+use filter::OutputFilter;
+use frame_counter::{FrameClock, FrameCounter};
+use length::LengthCounter;
+use mixer::{ChannelLevels, Mixer};
+use noise::Noise;
+use pulse::Pulse;
+use triangle::Triangle;
+
+use crate::region::Region;
+
+type Address = u16;
+type Value = u8;
+
+pub const NTSC_CPU_CLOCK: f64 = 1_789_773.0;
+pub const PAL_CPU_CLOCK: f64 = 1_662_607.0;
+
+const PULSE1: usize = 0;
+const PULSE2: usize = 1;
+const TRIANGLE: usize = 2;
+const NOISE: usize = 3;
+
+// Mixed output on its way to the host: the mix is recorded into the blip
+// buffer once per CPU cycle and read back, filtered, at the host rate.
+#[derive(Debug)]
+struct AudioOutput {
+    blip: BlipBuffer,
+    filter: OutputFilter,
+    clock: u32,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Apu {
+    pulses: [Pulse; 2],
+    triangle: Triangle,
+    noise: Noise,
+    lengths: [LengthCounter; 4],
+    dmc: Dmc,
+    frame_counter: FrameCounter,
+    // pulse timers run at half the CPU clock
+    odd_cycle: bool,
+    #[serde(skip)]
+    pub mixer: Mixer,
+    #[serde(skip)]
+    audio: Option<AudioOutput>,
+}
+
+impl Default for Apu {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Apu {
+    pub fn new() -> Self {
+        Apu {
+            pulses: [Pulse::new(true), Pulse::new(false)],
+            triangle: Triangle::default(),
+            noise: Noise::default(),
+            lengths: Default::default(),
+            dmc: Dmc::default(),
+            frame_counter: FrameCounter::default(),
+            odd_cycle: false,
+            mixer: Mixer::new(),
+            audio: None,
+        }
+    }
+
+    // $4000-$4013, $4015 and $4017.
+    pub fn write_register(&mut self, addr: Address, value: Value) {
+        tracing::trace!("write ${:04X} = ${:02X}", addr, value);
+        match addr {
+            0x4000 | 0x4004 => {
+                let n = ((addr >> 2) & 1) as usize;
+                self.pulses[n].write_control(value);
+                self.lengths[PULSE1 + n].set_halt(value & 0b0010_0000 != 0);
+            }
+            0x4001 | 0x4005 => self.pulses[((addr >> 2) & 1) as usize].write_sweep(value),
+            0x4002 | 0x4006 => self.pulses[((addr >> 2) & 1) as usize].write_timer_low(value),
+            0x4003 | 0x4007 => {
+                let n = ((addr >> 2) & 1) as usize;
+                self.pulses[n].write_timer_high(value);
+                self.lengths[PULSE1 + n].load(value);
+            }
+            0x4008 => {
+                self.triangle.write_linear(value);
+                self.lengths[TRIANGLE].set_halt(value & 0b1000_0000 != 0);
+            }
+            0x400A => self.triangle.write_timer_low(value),
+            0x400B => {
+                self.triangle.write_timer_high(value);
+                self.lengths[TRIANGLE].load(value);
+            }
+            0x400C => {
+                self.noise.write_control(value);
+                self.lengths[NOISE].set_halt(value & 0b0010_0000 != 0);
+            }
+            0x400E => self.noise.write_period(value),
+            0x400F => {
+                self.noise.write_length();
+                self.lengths[NOISE].load(value);
+            }
+            0x4010 => self.dmc.write_control(value),
+            0x4011 => self.dmc.write_output_level(value),
+            0x4012 => self.dmc.write_sample_address(value),
+            0x4013 => self.dmc.write_sample_length(value),
+            0x4015 => self.write_status(value),
+            0x4017 => {
+                let clock = self.frame_counter.write(value);
+                self.clock_frame(clock);
+            }
+            _ => {}
+        }
+    }
+
+    fn write_status(&mut self, value: Value) {
+        for (n, length) in self.lengths.iter_mut().enumerate() {
+            length.set_enabled(value & (1 << n) != 0);
+        }
+        self.dmc.set_enabled(value & 0b0001_0000 != 0);
+        self.dmc.clear_irq();
+    }
+
+    // $4015 as the CPU sees it: reading acknowledges the frame IRQ (but not
+    // the DMC one). Bit 5 is open bus and left clear.
+    pub fn read_status(&mut self) -> Value {
+        let status = self.peek_status();
+        self.frame_counter.clear_irq();
+        status
+    }
+
+    // $4015 without the read side effect, for debuggers.
+    pub fn peek_status(&self) -> Value {
+        let mut status = 0;
+        for (n, length) in self.lengths.iter().enumerate() {
+            if length.is_active() {
+                status |= 1 << n;
+            }
+        }
+        if self.dmc.bytes_remaining() > 0 {
+            status |= 0b0001_0000;
+        }
+        if self.frame_counter.irq_flag() {
+            status |= 0b0100_0000;
+        }
+        if self.dmc.irq_flag() {
+            status |= 0b1000_0000;
+        }
+        status
+    }
+
+    fn clock_frame(&mut self, clock: FrameClock) {
+        if clock.quarter {
+            for pulse in self.pulses.iter_mut() {
+                pulse.envelope.clock();
+            }
+            self.noise.envelope.clock();
+            self.triangle.clock_linear();
+        }
+        if clock.half {
+            for length in self.lengths.iter_mut() {
+                length.clock();
+            }
+            for pulse in self.pulses.iter_mut() {
+                pulse.clock_sweep();
+            }
+        }
+    }
+
+    // One CPU cycle.
+    pub fn tick(&mut self) {
+        let clock = self.frame_counter.tick();
+        self.clock_frame(clock);
+
+        if self.odd_cycle {
+            for pulse in self.pulses.iter_mut() {
+                pulse.tick();
+            }
+        }
+        self.odd_cycle = !self.odd_cycle;
+        self.triangle.tick(self.lengths[TRIANGLE].is_active());
+        self.noise.tick();
+        self.dmc.tick();
+    }
+
+    pub fn irq_pending(&self) -> bool {
+        self.frame_counter.irq_flag() || self.dmc.irq_flag()
+    }
+
+    pub fn dmc_dma_request(&self) -> Option<Address> {
+        self.dmc.dma_request()
+    }
+
+    pub fn dmc_dma_complete(&mut self, value: Value) {
+        self.dmc.dma_complete(value)
+    }
+
+    pub fn levels(&self) -> ChannelLevels {
+        ChannelLevels {
+            pulse1: self.pulses[0].output(self.lengths[PULSE1].is_active()),
+            pulse2: self.pulses[1].output(self.lengths[PULSE2].is_active()),
+            triangle: self.triangle.output(),
+            noise: self.noise.output(self.lengths[NOISE].is_active()),
+            dmc: self.dmc.output(),
+        }
+    }
+
+    // Which period tables and frame counter steps to use; see region.rs.
+    // Like the mixer, it isn't in savestates.
+    pub fn set_region(&mut self, region: Region) {
+        let pal = region.pal_apu();
+        self.frame_counter.set_pal(pal);
+        self.noise.set_pal(pal);
+        self.dmc.set_pal(pal);
+    }
+
+    // Mixer settings and the audio pipeline belong to the host, not the
+    // savestate.
+    pub(crate) fn take_host_state(&mut self, old: &mut Apu) {
+        self.mixer = core::mem::take(&mut old.mixer);
+        self.audio = old.audio.take();
+    }
+
+    // Starts producing host-rate samples. Until then the mix is never
+    // computed, which keeps headless runs cheap.
+    pub fn enable_audio(&mut self, clock_rate: f64, sample_rate: u32) {
+        self.audio = Some(AudioOutput {
+            blip: BlipBuffer::new(clock_rate, sample_rate as f64),
+            filter: OutputFilter::new(sample_rate as f32),
+            clock: 0,
+        });
+    }
+
+    pub fn disable_audio(&mut self) {
+        self.audio = None;
+    }
+
+    pub fn blip_mut(&mut self) -> Option<&mut BlipBuffer> {
+        self.audio.as_mut().map(|audio| &mut audio.blip)
+    }
+
+    // Records the current mix; called once per CPU cycle after `tick`, with
+    // the cartridge's sound chip if it has one.
+    pub fn record_output(&mut self, expansion: Option<&dyn ExpansionAudio>) {
+        if self.audio.is_none() {
+            return;
+        }
+        let sample = self.mixer.mix_with_expansion(&self.levels(), expansion);
+        if let Some(audio) = self.audio.as_mut() {
+            audio.blip.set_amplitude(audio.clock, sample);
+            audio.clock += 1;
+        }
+    }
+
+    // Makes everything recorded so far readable through `read_samples`.
+    pub fn end_audio_frame(&mut self) {
+        if let Some(audio) = self.audio.as_mut() {
+            audio.blip.end_frame(audio.clock);
+            audio.clock = 0;
+        }
+    }
+
+    // Appends the finished host-rate samples to `out`.
+    pub fn read_samples(&mut self, out: &mut Vec<f32>) {
+        let Some(audio) = self.audio.as_mut() else {
+            return;
+        };
+        let start = out.len();
+        out.resize(start + audio.blip.samples_avail(), 0.0);
+        let count = audio.blip.read_samples(&mut out[start..]);
+        out.truncate(start + count);
+        audio.filter.process_buffer(&mut out[start..]);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_status_reports_length_counters() {
+        let mut apu = Apu::new();
+        apu.write_register(0x4015, 0b0000_0101); // pulse 1 + triangle
+        apu.write_register(0x4003, 0xF8);
+        apu.write_register(0x4007, 0xF8); // pulse 2 disabled, ignored
+        apu.write_register(0x400B, 0xF8);
+
+        assert_eq!(apu.read_status() & 0x0F, 0b0000_0101);
+
+        apu.write_register(0x4015, 0b0000_0001);
+        assert_eq!(apu.read_status() & 0x0F, 0b0000_0001);
+    }
+
+    #[test]
+    fn test_length_counters_expire() {
+        let mut apu = Apu::new();
+        apu.write_register(0x4015, 0b0000_0001);
+        apu.write_register(0x4003, 0b0001_1000); // length 2
+        apu.write_register(0x4017, 0x40); // 4-step, no IRQ
+
+        for _ in 0..14913 {
+            apu.tick();
+        }
+        assert_eq!(apu.read_status() & 1, 1);
+        for _ in 0..29830 {
+            apu.tick();
+        }
+        assert_eq!(apu.read_status() & 1, 0);
+    }
+
+    #[test]
+    fn test_reading_status_clears_frame_irq() {
+        let mut apu = Apu::new();
+        for _ in 0..29830 {
+            apu.tick();
+        }
+
+        assert!(apu.irq_pending());
+        assert_eq!(apu.peek_status() & 0x40, 0x40);
+        assert_eq!(apu.read_status() & 0x40, 0x40);
+        assert_eq!(apu.read_status() & 0x40, 0);
+        assert!(!apu.irq_pending());
+    }
+
+    #[test]
+    fn test_dmc_status_and_irq() {
+        let mut apu = Apu::new();
+        apu.write_register(0x4010, 0x80);
+        apu.write_register(0x4013, 0x00); // 1 byte
+        apu.write_register(0x4015, 0b0001_0000);
+        assert_eq!(apu.peek_status() & 0x10, 0x10);
+
+        assert_eq!(apu.dmc_dma_request(), Some(0xC000));
+        apu.dmc_dma_complete(0x00);
+        let status = apu.read_status();
+        assert_eq!(status & 0x10, 0);
+        assert_eq!(status & 0x80, 0x80);
+
+        // DMC IRQ survives a status read, but not a status write
+        assert_eq!(apu.read_status() & 0x80, 0x80);
+        apu.write_register(0x4015, 0);
+        assert_eq!(apu.read_status() & 0x80, 0);
+    }
+
+    #[test]
+    fn test_pulse_registers_drive_levels() {
+        let mut apu = Apu::new();
+        apu.write_register(0x4015, 0b0000_0001);
+        apu.write_register(0x4000, 0b1011_1010); // 50%, constant volume 10
+        apu.write_register(0x4002, 0x40);
+        apu.write_register(0x4003, 0x08);
+
+        let mut seen = [false; 16];
+        for _ in 0..2000 {
+            apu.tick();
+            seen[apu.levels().pulse1 as usize] = true;
+        }
+        assert!(seen[0] && seen[10]);
+        assert_eq!(seen.iter().filter(|&&s| s).count(), 2);
+        assert_eq!(apu.levels().pulse2, 0);
+    }
+
+    #[test]
+    fn test_audio_output_produces_host_rate_samples() {
+        let mut apu = Apu::new();
+        let mut samples = Vec::new();
+        apu.record_output(None);
+        apu.end_audio_frame();
+        apu.read_samples(&mut samples);
+        assert!(samples.is_empty());
+
+        apu.enable_audio(NTSC_CPU_CLOCK, 48_000);
+        apu.write_register(0x4015, 0b0000_0100);
+        apu.write_register(0x4008, 0xFF);
+        apu.write_register(0x400A, 0x80);
+        apu.write_register(0x400B, 0x08);
+        for _ in 0..29781 {
+            apu.tick();
+            apu.record_output(None);
+        }
+        apu.end_audio_frame();
+        apu.read_samples(&mut samples);
+
+        // one NTSC frame is 800 samples at 48kHz
+        assert!((795..=800).contains(&samples.len()));
+        assert!(samples.iter().any(|&s| s.abs() > 0.01));
+    }
+}
@@ -0,0 +1,120 @@
+// Noise channel at $400C-$400F: a 15-bit linear feedback shift register
+// clocked from a 16-entry period table. Mode 1 taps bit 6 instead of bit 1,
+// giving a short 93-step metallic loop instead of white noise.
+
+use serde::{Deserialize, Serialize};
+
+use super::envelope::Envelope;
+
+// periods in CPU cycles
+const PERIOD_TABLE: [u16; 16] = [
+    4, 8, 16, 32, 64, 96, 128, 160, 202, 254, 380, 508, 762, 1016, 2034, 4068,
+];
+const PAL_PERIOD_TABLE: [u16; 16] = [
+    4, 8, 14, 30, 60, 88, 118, 148, 188, 236, 354, 472, 708, 944, 1890, 3778,
+];
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Noise {
+    short_mode: bool,
+    timer_period: u16,
+    timer: u16,
+    shift_register: u16,
+    pub envelope: Envelope,
+    #[serde(skip)]
+    pal: bool,
+}
+
+impl Default for Noise {
+    fn default() -> Self {
+        Noise {
+            short_mode: false,
+            timer_period: PERIOD_TABLE[0],
+            timer: 0,
+            shift_register: 1,
+            envelope: Envelope::default(),
+            pal: false,
+        }
+    }
+}
+
+impl Noise {
+    // $400C
+    pub fn write_control(&mut self, value: u8) {
+        self.envelope.write(value);
+    }
+
+    pub fn set_pal(&mut self, pal: bool) {
+        self.pal = pal;
+    }
+
+    // $400E
+    pub fn write_period(&mut self, value: u8) {
+        self.short_mode = value & 0b1000_0000 != 0;
+        let table = if self.pal {
+            &PAL_PERIOD_TABLE
+        } else {
+            &PERIOD_TABLE
+        };
+        self.timer_period = table[(value & 0x0F) as usize];
+    }
+
+    // $400F; the length counter load is handled by the caller.
+    pub fn write_length(&mut self) {
+        self.envelope.restart();
+    }
+
+    // One CPU cycle.
+    pub fn tick(&mut self) {
+        if self.timer > 1 {
+            self.timer -= 1;
+            return;
+        }
+        self.timer = self.timer_period;
+
+        let tap = if self.short_mode { 6 } else { 1 };
+        let feedback = (self.shift_register ^ (self.shift_register >> tap)) & 1;
+        self.shift_register = (self.shift_register >> 1) | (feedback << 14);
+    }
+
+    pub fn output(&self, length_active: bool) -> u8 {
+        if !length_active || self.shift_register & 1 != 0 {
+            0
+        } else {
+            self.envelope.output()
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn period_of(noise: &mut Noise) -> usize {
+        let start = noise.shift_register;
+        (1..=1 << 15)
+            .find(|_| {
+                for _ in 0..4 {
+                    noise.tick();
+                }
+                noise.shift_register == start
+            })
+            .unwrap()
+    }
+
+    #[test]
+    fn test_long_mode_period() {
+        let mut noise = Noise::default();
+        noise.write_period(0x00);
+
+        assert_eq!(period_of(&mut noise), 32767);
+    }
+
+    #[test]
+    fn test_short_mode_period() {
+        let mut noise = Noise::default();
+        noise.write_period(0x80);
+
+        assert_eq!(period_of(&mut noise), 93);
+    }
+}
@@ -0,0 +1,190 @@
+// Pulse (square) channels at $4000-$4007. An 11-bit timer clocked every
+// other CPU cycle steps an 8-step duty sequence; the sweep unit can bend
+// the period up or down on half frames. The two channels differ only in how
+// the sweep negates: pulse 1 uses ones' complement, pulse 2 twos'.
+
+use serde::{Deserialize, Serialize};
+
+use super::envelope::Envelope;
+
+const DUTY_TABLE: [[u8; 8]; 4] = [
+    [0, 1, 0, 0, 0, 0, 0, 0], // 12.5%
+    [0, 1, 1, 0, 0, 0, 0, 0], // 25%
+    [0, 1, 1, 1, 1, 0, 0, 0], // 50%
+    [1, 0, 0, 1, 1, 1, 1, 1], // 25% negated
+];
+
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize)]
+struct Sweep {
+    enabled: bool,
+    period: u8,
+    negate: bool,
+    shift: u8,
+    reload: bool,
+    divider: u8,
+}
+
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize)]
+pub struct Pulse {
+    ones_complement: bool,
+    duty: u8,
+    step: u8,
+    timer_period: u16,
+    timer: u16,
+    sweep: Sweep,
+    pub envelope: Envelope,
+}
+
+impl Pulse {
+    pub fn new(ones_complement: bool) -> Self {
+        Pulse {
+            ones_complement,
+            ..Default::default()
+        }
+    }
+
+    // $4000/$4004
+    pub fn write_control(&mut self, value: u8) {
+        self.duty = value >> 6;
+        self.envelope.write(value);
+    }
+
+    // $4001/$4005
+    pub fn write_sweep(&mut self, value: u8) {
+        self.sweep.enabled = value & 0b1000_0000 != 0;
+        self.sweep.period = (value >> 4) & 0b111;
+        self.sweep.negate = value & 0b0000_1000 != 0;
+        self.sweep.shift = value & 0b111;
+        self.sweep.reload = true;
+    }
+
+    // $4002/$4006
+    pub fn write_timer_low(&mut self, value: u8) {
+        self.timer_period = (self.timer_period & 0x0700) | value as u16;
+    }
+
+    // $4003/$4007; the length counter load is handled by the caller.
+    pub fn write_timer_high(&mut self, value: u8) {
+        self.timer_period = (self.timer_period & 0x00FF) | ((value as u16 & 0b111) << 8);
+        self.step = 0;
+        self.envelope.restart();
+    }
+
+    // One APU cycle (two CPU cycles).
+    pub fn tick(&mut self) {
+        if self.timer == 0 {
+            self.timer = self.timer_period;
+            self.step = self.step.wrapping_add(1) & 7;
+        } else {
+            self.timer -= 1;
+        }
+    }
+
+    fn sweep_target(&self) -> u16 {
+        let change = self.timer_period >> self.sweep.shift;
+        if !self.sweep.negate {
+            self.timer_period + change
+        } else if self.ones_complement {
+            self.timer_period.saturating_sub(change + 1)
+        } else {
+            self.timer_period.saturating_sub(change)
+        }
+    }
+
+    // The sweep unit silences the channel whenever the current period is
+    // too short or the target too long, even with the sweep disabled.
+    fn is_muted(&self) -> bool {
+        self.timer_period < 8 || self.sweep_target() > 0x7FF
+    }
+
+    // Clocked by the frame counter on every half frame.
+    pub fn clock_sweep(&mut self) {
+        if self.sweep.divider == 0 && self.sweep.enabled && self.sweep.shift > 0 && !self.is_muted()
+        {
+            self.timer_period = self.sweep_target();
+        }
+        if self.sweep.divider == 0 || self.sweep.reload {
+            self.sweep.divider = self.sweep.period;
+            self.sweep.reload = false;
+        } else {
+            self.sweep.divider -= 1;
+        }
+    }
+
+    pub fn timer_period(&self) -> u16 {
+        self.timer_period
+    }
+
+    pub fn output(&self, length_active: bool) -> u8 {
+        if !length_active
+            || self.is_muted()
+            || DUTY_TABLE[self.duty as usize & 3][self.step as usize & 7] == 0
+        {
+            0
+        } else {
+            self.envelope.output()
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn pulse(ones_complement: bool, period: u16) -> Pulse {
+        let mut pulse = Pulse::new(ones_complement);
+        pulse.write_control(0b1001_1111); // 50%, constant volume 15
+        pulse.write_timer_low(period as u8);
+        pulse.write_timer_high((period >> 8) as u8);
+        pulse
+    }
+
+    #[test]
+    fn test_duty_sequence() {
+        let mut pulse = pulse(false, 8);
+        let mut highs = 0;
+        for _ in 0..8 {
+            for _ in 0..9 {
+                pulse.tick();
+            }
+            if pulse.output(true) > 0 {
+                highs += 1;
+            }
+        }
+
+        assert_eq!(highs, 4);
+        assert_eq!(pulse.output(false), 0);
+    }
+
+    #[test]
+    fn test_short_period_is_muted() {
+        let mut pulse = pulse(false, 7);
+        for _ in 0..16 {
+            pulse.tick();
+            assert_eq!(pulse.output(true), 0);
+        }
+    }
+
+    #[test]
+    fn test_sweep_negate_differs_between_channels() {
+        let mut pulse1 = pulse(true, 0x100);
+        let mut pulse2 = pulse(false, 0x100);
+        for pulse in [&mut pulse1, &mut pulse2] {
+            pulse.write_sweep(0b1000_1001); // enabled, period 0, negate, shift 1
+            pulse.clock_sweep();
+        }
+
+        assert_eq!(pulse1.timer_period(), 0x100 - 0x80 - 1);
+        assert_eq!(pulse2.timer_period(), 0x100 - 0x80);
+    }
+
+    #[test]
+    fn test_sweep_overflow_mutes_without_changing_period() {
+        let mut pulse = pulse(false, 0x600);
+        pulse.write_sweep(0b1000_0001);
+        pulse.clock_sweep();
+
+        assert_eq!(pulse.timer_period(), 0x600);
+        assert!(pulse.is_muted());
+    }
+}
@@ -0,0 +1,111 @@
+// Triangle channel at $4008-$400B. The timer is clocked every CPU cycle and
+// steps a 32-step sequence 15..0, 0..15. There is no volume control; the
+// linear counter (and the length counter) simply freeze the sequencer,
+// which holds its last level rather than dropping to zero.
+
+use serde::{Deserialize, Serialize};
+
+const SEQUENCE: [u8; 32] = [
+    15, 14, 13, 12, 11, 10, 9, 8, 7, 6, 5, 4, 3, 2, 1, 0, //
+    0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15,
+];
+
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize)]
+pub struct Triangle {
+    control: bool,
+    linear_reload_value: u8,
+    linear_counter: u8,
+    linear_reload: bool,
+    timer_period: u16,
+    timer: u16,
+    step: u8,
+}
+
+impl Triangle {
+    // $4008. The control flag doubles as the length counter halt bit.
+    pub fn write_linear(&mut self, value: u8) {
+        self.control = value & 0b1000_0000 != 0;
+        self.linear_reload_value = value & 0x7F;
+    }
+
+    // $400A
+    pub fn write_timer_low(&mut self, value: u8) {
+        self.timer_period = (self.timer_period & 0x0700) | value as u16;
+    }
+
+    // $400B; the length counter load is handled by the caller.
+    pub fn write_timer_high(&mut self, value: u8) {
+        self.timer_period = (self.timer_period & 0x00FF) | ((value as u16 & 0b111) << 8);
+        self.linear_reload = true;
+    }
+
+    // Clocked by the frame counter on every quarter frame.
+    pub fn clock_linear(&mut self) {
+        if self.linear_reload {
+            self.linear_counter = self.linear_reload_value;
+        } else if self.linear_counter > 0 {
+            self.linear_counter -= 1;
+        }
+        if !self.control {
+            self.linear_reload = false;
+        }
+    }
+
+    // One CPU cycle.
+    pub fn tick(&mut self, length_active: bool) {
+        if self.timer == 0 {
+            self.timer = self.timer_period;
+            if length_active && self.linear_counter > 0 {
+                self.step = self.step.wrapping_add(1) & 31;
+            }
+        } else {
+            self.timer -= 1;
+        }
+    }
+
+    pub fn output(&self) -> u8 {
+        SEQUENCE[self.step as usize & 31]
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_sequence_needs_linear_counter() {
+        let mut triangle = Triangle::default();
+        triangle.write_linear(0x7F);
+        triangle.write_timer_low(0);
+        triangle.write_timer_high(0);
+        triangle.tick(true);
+        assert_eq!(triangle.output(), 15);
+
+        triangle.clock_linear();
+        for _ in 0..15 {
+            triangle.tick(true);
+        }
+        assert_eq!(triangle.output(), 0);
+        triangle.tick(true);
+        assert_eq!(triangle.output(), 0);
+        triangle.tick(true);
+        assert_eq!(triangle.output(), 1);
+
+        // a stopped length counter freezes the level
+        triangle.tick(false);
+        assert_eq!(triangle.output(), 1);
+    }
+
+    #[test]
+    fn test_linear_counter_counts_down() {
+        let mut triangle = Triangle::default();
+        triangle.write_linear(2);
+        triangle.write_timer_high(0);
+        triangle.clock_linear(); // reload to 2, clears the reload flag
+        triangle.clock_linear();
+        triangle.clock_linear();
+
+        triangle.tick(true);
+        assert_eq!(triangle.output(), 15);
+    }
+}
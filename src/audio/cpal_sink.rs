@@ -0,0 +1,122 @@
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use ::cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use ::cpal::{
+    BufferSize, Error, ErrorKind, FromSample, SampleFormat, SizedSample, Stream, StreamConfig,
+    SupportedBufferSize,
+};
+
+use super::AudioSink;
+
+type Queue = Arc<Mutex<VecDeque<f32>>>;
+
+// Plays through the host's default output device. Samples are handed to the
+// device callback through a shared queue; on underrun the last sample is
+// held instead of dropping to zero, which clicks less, and counted; the
+// wait for the first samples isn't.
+pub struct CpalSink {
+    _stream: Stream,
+    queue: Queue,
+    underruns: Arc<AtomicU64>,
+    sample_rate: u32,
+}
+
+impl CpalSink {
+    // `buffer` is the device's period in sample frames, kept to what the
+    // device says it can do; its default without one.
+    pub fn new(buffer: Option<u32>) -> Result<Self, Error> {
+        let host = ::cpal::default_host();
+        let device = host.default_output_device().ok_or_else(|| {
+            Error::with_message(ErrorKind::DeviceNotAvailable, "no output device")
+        })?;
+
+        let supported = device.default_output_config()?;
+        let mut config = supported.config();
+        if let Some(frames) = buffer {
+            let frames = match *supported.buffer_size() {
+                SupportedBufferSize::Range { min, max } => frames.clamp(min, max),
+                SupportedBufferSize::Unknown => frames,
+            };
+            config.buffer_size = BufferSize::Fixed(frames);
+        }
+        let queue: Queue = Arc::new(Mutex::new(VecDeque::new()));
+        let underruns = Arc::new(AtomicU64::new(0));
+
+        let shared = (queue.clone(), underruns.clone());
+        let stream = match supported.sample_format() {
+            SampleFormat::F32 => build_stream::<f32>(&device, config, shared)?,
+            SampleFormat::I16 => build_stream::<i16>(&device, config, shared)?,
+            SampleFormat::U16 => build_stream::<u16>(&device, config, shared)?,
+            format => {
+                return Err(Error::with_message(
+                    ErrorKind::UnsupportedConfig,
+                    format!("unsupported sample format {:?}", format),
+                ))
+            }
+        };
+        stream.play()?;
+
+        Ok(CpalSink {
+            _stream: stream,
+            queue,
+            underruns,
+            sample_rate: config.sample_rate,
+        })
+    }
+}
+
+fn build_stream<T>(
+    device: &::cpal::Device,
+    config: StreamConfig,
+    (queue, underruns): (Queue, Arc<AtomicU64>),
+) -> Result<Stream, Error>
+where
+    T: SizedSample + FromSample<f32>,
+{
+    let channels = config.channels as usize;
+    let mut last = 0.0;
+    let mut started = false;
+
+    device.build_output_stream(
+        config,
+        move |data: &mut [T], _: &::cpal::OutputCallbackInfo| {
+            let mut queue = queue.lock().unwrap();
+            let mut ran_dry = false;
+            for frame in data.chunks_mut(channels) {
+                match queue.pop_front() {
+                    Some(sample) => {
+                        last = sample;
+                        started = true;
+                    }
+                    None => ran_dry = started,
+                }
+                frame.fill(T::from_sample(last));
+            }
+            if ran_dry {
+                underruns.fetch_add(1, Ordering::Relaxed);
+            }
+        },
+        |err| eprintln!("audio stream error: {}", err),
+        None,
+    )
+}
+
+impl AudioSink for CpalSink {
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn push_samples(&mut self, samples: &[f32]) {
+        self.queue.lock().unwrap().extend(samples);
+    }
+
+    fn queued_samples(&self) -> usize {
+        self.queue.lock().unwrap().len()
+    }
+
+    fn underruns(&self) -> u64 {
+        self.underruns.load(Ordering::Relaxed)
+    }
+}
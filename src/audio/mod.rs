@@ -0,0 +1,128 @@
+// Where mixed, resampled audio goes. The APU never talks to an audio device
+// directly; frontends (cpal here, SDL2/WASM/libretro elsewhere) provide a
+// sink and the emulator pushes host-rate samples into it.
+//
+// How far ahead of the device to run is a trade each machine strikes for
+// itself: a deep queue and a big device buffer ride out hitches but are
+// heard late, shallow ones are heard sooner but crackle when the emulator
+// or the host stalls. Latency holds both, and a sink counts the times it
+// ran dry so the stats can say which way to go.
+
+use std::time::Duration;
+
+#[cfg(feature = "cpal")]
+pub mod cpal_sink;
+pub mod rate_control;
+pub mod wav;
+
+pub trait AudioSink {
+    // Output rate the samples pushed into this sink must already be at.
+    fn sample_rate(&self) -> u32;
+
+    // Queues mono samples in the range -1.0..=1.0 for playback.
+    fn push_samples(&mut self, samples: &[f32]);
+
+    // Samples pushed but not yet played.
+    fn queued_samples(&self) -> usize;
+
+    fn latency(&self) -> Duration {
+        Duration::from_secs_f64(self.queued_samples() as f64 / self.sample_rate() as f64)
+    }
+
+    // Times the device wanted samples and found the queue empty, since the
+    // sink was opened; 0 for sinks that can't tell.
+    fn underruns(&self) -> u64 {
+        0
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Latency {
+    // samples to keep queued, what rate control aims for
+    pub queued: usize,
+    // sample frames the device plays per period; its own choice if None
+    pub buffer: Option<u32>,
+}
+
+impl Default for Latency {
+    fn default() -> Self {
+        Latency {
+            queued: 2048,
+            buffer: None,
+        }
+    }
+}
+
+impl Latency {
+    pub fn duration(&self, sample_rate: u32) -> Duration {
+        Duration::from_secs_f64(self.queued as f64 / sample_rate as f64)
+    }
+}
+
+// Drops everything; for headless runs and tests.
+#[derive(Debug)]
+pub struct NullSink {
+    sample_rate: u32,
+}
+
+impl NullSink {
+    pub fn new(sample_rate: u32) -> Self {
+        NullSink { sample_rate }
+    }
+}
+
+impl AudioSink for NullSink {
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn push_samples(&mut self, _samples: &[f32]) {}
+
+    fn queued_samples(&self) -> usize {
+        0
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    struct QueueSink(Vec<f32>);
+
+    impl AudioSink for QueueSink {
+        fn sample_rate(&self) -> u32 {
+            48_000
+        }
+
+        fn push_samples(&mut self, samples: &[f32]) {
+            self.0.extend_from_slice(samples);
+        }
+
+        fn queued_samples(&self) -> usize {
+            self.0.len()
+        }
+    }
+
+    #[test]
+    fn test_latency_from_queued_samples() {
+        let mut sink = QueueSink(Vec::new());
+        sink.push_samples(&[0.0; 2400]);
+
+        assert_eq!(sink.latency(), Duration::from_millis(50));
+        assert_eq!(sink.underruns(), 0);
+        let target = Latency {
+            queued: 2400,
+            ..Latency::default()
+        };
+        assert_eq!(target.duration(48_000), sink.latency());
+    }
+
+    #[test]
+    fn test_null_sink_never_queues() {
+        let mut sink = NullSink::new(44_100);
+        sink.push_samples(&[0.5; 128]);
+
+        assert_eq!(sink.queued_samples(), 0);
+        assert_eq!(sink.latency(), Duration::ZERO);
+    }
+}
@@ -0,0 +1,109 @@
+// Dynamic rate control: rather than blocking emulation on the audio device
+// (which fights the video frame pacing), the resampling ratio is nudged by a
+// fraction of a percent each frame depending on how full the sink's queue
+// is. A filling queue makes us produce slightly fewer samples per frame, a
+// draining one slightly more, so the queue hovers at the target latency
+// without audible pitch change.
+
+use super::AudioSink;
+use crate::apu::blip::BlipBuffer;
+
+// Largest relative deviation from the nominal rate. 0.5% is well below what
+// anyone can hear.
+const DEFAULT_MAX_DELTA: f64 = 0.005;
+
+#[derive(Debug, Clone, Copy)]
+pub struct RateControl {
+    target_queued: usize,
+    max_delta: f64,
+}
+
+impl RateControl {
+    // `target_queued` is the queue depth, in samples, to hover around.
+    pub fn new(target_queued: usize) -> Self {
+        RateControl {
+            target_queued: target_queued.max(1),
+            max_delta: DEFAULT_MAX_DELTA,
+        }
+    }
+
+    pub fn with_max_delta(mut self, max_delta: f64) -> Self {
+        self.max_delta = max_delta;
+        self
+    }
+
+    pub fn target_queued(&self) -> usize {
+        self.target_queued
+    }
+
+    // Sample rate to resample to, given the nominal device rate and the
+    // number of samples currently queued.
+    pub fn adjusted_rate(&self, nominal: f64, queued: usize) -> f64 {
+        // 0.0 when empty, 0.5 at the target, 1.0 at twice the target
+        let fill = (queued as f64 / (2 * self.target_queued) as f64).min(1.0);
+        nominal * (1.0 + self.max_delta * (1.0 - 2.0 * fill))
+    }
+
+    // Retunes `blip` for the next frame from the sink's current fill level.
+    pub fn update(&self, blip: &mut BlipBuffer, sink: &dyn AudioSink) {
+        let rate = self.adjusted_rate(sink.sample_rate() as f64, sink.queued_samples());
+        blip.set_rates(blip.clock_rate(), rate);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::apu::NTSC_CPU_CLOCK;
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9
+    }
+
+    #[test]
+    fn test_nominal_rate_at_target() {
+        let control = RateControl::new(1024);
+        assert!(close(control.adjusted_rate(48_000.0, 1024), 48_000.0));
+    }
+
+    #[test]
+    fn test_empty_queue_speeds_up_production() {
+        let control = RateControl::new(1024);
+        assert!(close(control.adjusted_rate(48_000.0, 0), 48_240.0));
+    }
+
+    #[test]
+    fn test_full_queue_slows_down_production() {
+        let control = RateControl::new(1024);
+        assert!(close(control.adjusted_rate(48_000.0, 2048), 47_760.0));
+        // never more than max_delta, however far behind the device is
+        assert!(close(control.adjusted_rate(48_000.0, 100_000), 47_760.0));
+    }
+
+    #[test]
+    fn test_custom_max_delta() {
+        let control = RateControl::new(1000).with_max_delta(0.01);
+        assert!(close(control.adjusted_rate(44_100.0, 0), 44_541.0));
+    }
+
+    #[test]
+    fn test_update_retunes_blip_buffer() {
+        struct Full;
+        impl AudioSink for Full {
+            fn sample_rate(&self) -> u32 {
+                48_000
+            }
+            fn push_samples(&mut self, _samples: &[f32]) {}
+            fn queued_samples(&self) -> usize {
+                4096
+            }
+        }
+
+        let control = RateControl::new(1024);
+        let mut blip = BlipBuffer::new(NTSC_CPU_CLOCK, 48_000.0);
+        control.update(&mut blip, &Full);
+
+        assert!(close(blip.sample_rate(), 47_760.0));
+        assert!(close(blip.clock_rate(), NTSC_CPU_CLOCK));
+    }
+}
@@ -0,0 +1,223 @@
+// 16-bit mono PCM WAV capture of the emulator's audio output, for ripping
+// soundtracks and for comparing audio between builds.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufWriter, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use crate::apu::mixer::Channel;
+
+const HEADER_LEN: u32 = 44;
+
+pub struct WavWriter<W: Write + Seek> {
+    writer: Option<W>,
+    data_len: u32,
+}
+
+impl<W: Write + Seek> WavWriter<W> {
+    pub fn new(mut writer: W, sample_rate: u32) -> io::Result<Self> {
+        // the two length fields are patched in by `finish`
+        writer.write_all(b"RIFF")?;
+        writer.write_all(&0u32.to_le_bytes())?;
+        writer.write_all(b"WAVE")?;
+        writer.write_all(b"fmt ")?;
+        writer.write_all(&16u32.to_le_bytes())?; // fmt chunk length
+        writer.write_all(&1u16.to_le_bytes())?; // PCM
+        writer.write_all(&1u16.to_le_bytes())?; // mono
+        writer.write_all(&sample_rate.to_le_bytes())?;
+        writer.write_all(&(sample_rate * 2).to_le_bytes())?; // bytes per second
+        writer.write_all(&2u16.to_le_bytes())?; // bytes per frame
+        writer.write_all(&16u16.to_le_bytes())?; // bits per sample
+        writer.write_all(b"data")?;
+        writer.write_all(&0u32.to_le_bytes())?;
+
+        Ok(WavWriter {
+            writer: Some(writer),
+            data_len: 0,
+        })
+    }
+
+    pub fn write_samples(&mut self, samples: &[f32]) -> io::Result<()> {
+        let writer = self.writer.as_mut().expect("write after finish");
+        for sample in samples {
+            let pcm = (sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16;
+            writer.write_all(&pcm.to_le_bytes())?;
+        }
+        self.data_len += samples.len() as u32 * 2;
+        Ok(())
+    }
+
+    pub fn samples_written(&self) -> u32 {
+        self.data_len / 2
+    }
+
+    // Fills in the header lengths and hands back the underlying writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.write_lengths()?;
+        Ok(self.writer.take().unwrap())
+    }
+
+    fn write_lengths(&mut self) -> io::Result<()> {
+        let Some(writer) = self.writer.as_mut() else {
+            return Ok(());
+        };
+        writer.seek(SeekFrom::Start(4))?;
+        writer.write_all(&(HEADER_LEN - 8 + self.data_len).to_le_bytes())?;
+        writer.seek(SeekFrom::Start(40))?;
+        writer.write_all(&self.data_len.to_le_bytes())?;
+        writer.seek(SeekFrom::End(0))?;
+        writer.flush()
+    }
+}
+
+impl<W: Write + Seek> Drop for WavWriter<W> {
+    // keep the file playable even if the recording is never stopped cleanly
+    fn drop(&mut self) {
+        let _ = self.write_lengths();
+    }
+}
+
+type FileWriter = WavWriter<BufWriter<File>>;
+
+fn create(path: &Path, sample_rate: u32) -> io::Result<FileWriter> {
+    WavWriter::new(BufWriter::new(File::create(path)?), sample_rate)
+}
+
+// Start/stop capture of the final mix and, optionally, one stem file per
+// channel next to it (`song.wav`, `song.pulse1.wav`, ...).
+#[derive(Default)]
+pub struct AudioRecorder {
+    path: PathBuf,
+    sample_rate: u32,
+    stems: bool,
+    mix: Option<FileWriter>,
+    stem_writers: HashMap<Channel, FileWriter>,
+}
+
+impl AudioRecorder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn start(
+        &mut self,
+        path: impl AsRef<Path>,
+        sample_rate: u32,
+        stems: bool,
+    ) -> io::Result<()> {
+        self.stop()?;
+        self.path = path.as_ref().to_path_buf();
+        self.sample_rate = sample_rate;
+        self.stems = stems;
+        self.mix = Some(create(&self.path, sample_rate)?);
+        Ok(())
+    }
+
+    pub fn stop(&mut self) -> io::Result<()> {
+        if let Some(mix) = self.mix.take() {
+            mix.finish()?;
+        }
+        for (_, stem) in self.stem_writers.drain() {
+            stem.finish()?;
+        }
+        Ok(())
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.mix.is_some()
+    }
+
+    pub fn records_stems(&self) -> bool {
+        self.is_recording() && self.stems
+    }
+
+    pub fn push_mix(&mut self, samples: &[f32]) -> io::Result<()> {
+        match self.mix.as_mut() {
+            Some(mix) => mix.write_samples(samples),
+            None => Ok(()),
+        }
+    }
+
+    // Stem files are created the first time a channel produces samples, so
+    // carts without expansion audio don't leave empty files behind.
+    pub fn push_stem(&mut self, channel: Channel, samples: &[f32]) -> io::Result<()> {
+        if !self.records_stems() {
+            return Ok(());
+        }
+        if !self.stem_writers.contains_key(&channel) {
+            let path = stem_path(&self.path, channel);
+            self.stem_writers
+                .insert(channel, create(&path, self.sample_rate)?);
+        }
+        self.stem_writers
+            .get_mut(&channel)
+            .unwrap()
+            .write_samples(samples)
+    }
+}
+
+fn stem_path(path: &Path, channel: Channel) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!("{}.{}.wav", stem, channel))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::Cursor;
+
+    fn u32_at(bytes: &[u8], at: usize) -> u32 {
+        u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap())
+    }
+
+    #[test]
+    fn test_header_and_lengths() {
+        let mut wav = WavWriter::new(Cursor::new(Vec::new()), 48_000).unwrap();
+        wav.write_samples(&[0.0, 0.5, -0.5]).unwrap();
+        let bytes = wav.finish().unwrap().into_inner();
+
+        assert_eq!(&bytes[0..4], b"RIFF");
+        assert_eq!(&bytes[8..16], b"WAVEfmt ");
+        assert_eq!(u32_at(&bytes, 24), 48_000);
+        assert_eq!(&bytes[36..40], b"data");
+        assert_eq!(u32_at(&bytes, 40), 6);
+        assert_eq!(u32_at(&bytes, 4), 36 + 6);
+        assert_eq!(bytes.len(), 44 + 6);
+    }
+
+    #[test]
+    fn test_samples_are_clamped_pcm16() {
+        let mut wav = WavWriter::new(Cursor::new(Vec::new()), 44_100).unwrap();
+        wav.write_samples(&[1.0, -2.0, 0.0]).unwrap();
+        assert_eq!(wav.samples_written(), 3);
+        let bytes = wav.finish().unwrap().into_inner();
+
+        assert_eq!(i16::from_le_bytes([bytes[44], bytes[45]]), i16::MAX);
+        assert_eq!(i16::from_le_bytes([bytes[46], bytes[47]]), -i16::MAX);
+        assert_eq!(i16::from_le_bytes([bytes[48], bytes[49]]), 0);
+    }
+
+    #[test]
+    fn test_recorder_writes_mix_and_stems() {
+        let dir = std::env::temp_dir().join(format!("nes-wav-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("song.wav");
+
+        let mut recorder = AudioRecorder::new();
+        recorder.push_mix(&[0.1; 10]).unwrap(); // not recording yet, ignored
+        recorder.start(&path, 44_100, true).unwrap();
+        recorder.push_mix(&[0.1; 100]).unwrap();
+        recorder.push_stem(Channel::Triangle, &[0.2; 100]).unwrap();
+        recorder.stop().unwrap();
+        assert!(!recorder.is_recording());
+
+        let mix = std::fs::read(&path).unwrap();
+        assert_eq!(u32_at(&mix, 40), 200);
+        let stem = std::fs::read(dir.join("song.triangle.wav")).unwrap();
+        assert_eq!(u32_at(&stem, 40), 200);
+        assert!(!dir.join("song.pulse1.wav").exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,101 @@
+// The events as LiveSplit's server takes them: LiveSplit with its Server
+// component on (LiveSplit One through a bridge speaking the same), a
+// line to a command over TCP. The timer's game time is set from the
+// run's frames at every event and paused between them, so what LiveSplit
+// shows is the emulated time and not its own clock.
+
+use std::io::{self, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::time::Duration;
+
+use super::{format_time, frame_time, SplitEvent};
+
+pub const DEFAULT_ADDRESS: &str = "127.0.0.1:16834";
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(2);
+
+// What to send for `event`.
+pub fn commands(event: SplitEvent, frame_rate: f64) -> Vec<String> {
+    let set_time = |frames| {
+        format!(
+            "setgametime {}",
+            format_time(frame_time(frames, frame_rate))
+        )
+    };
+    match event {
+        SplitEvent::Started => vec![
+            "starttimer".to_string(),
+            "pausegametime".to_string(),
+            set_time(0),
+        ],
+        SplitEvent::Split { frames, .. } | SplitEvent::Finished { frames } => {
+            vec![set_time(frames), "split".to_string()]
+        }
+        SplitEvent::Reset => vec!["reset".to_string()],
+    }
+}
+
+#[derive(Debug)]
+pub struct LiveSplit {
+    stream: TcpStream,
+    frame_rate: f64,
+}
+
+impl LiveSplit {
+    pub fn connect(addr: SocketAddr, frame_rate: f64) -> io::Result<Self> {
+        let stream = TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT)?;
+        stream.set_nodelay(true)?;
+        Ok(LiveSplit { stream, frame_rate })
+    }
+
+    pub fn send(&mut self, event: SplitEvent) -> io::Result<()> {
+        let mut message = String::new();
+        for command in commands(event, self.frame_rate) {
+            message.push_str(&command);
+            message.push_str("\r\n");
+        }
+        self.stream.write_all(message.as_bytes())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::{BufRead, BufReader};
+    use std::net::TcpListener;
+
+    #[test]
+    fn test_sends_commands() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut livesplit = LiveSplit::connect(listener.local_addr().unwrap(), 60.0).unwrap();
+        let (server, _) = listener.accept().unwrap();
+
+        livesplit.send(SplitEvent::Started).unwrap();
+        livesplit
+            .send(SplitEvent::Split {
+                index: 0,
+                frames: 90,
+            })
+            .unwrap();
+        livesplit.send(SplitEvent::Reset).unwrap();
+        let mut server = BufReader::new(server);
+        let lines: Vec<_> = (0..6)
+            .map(|_| {
+                let mut line = String::new();
+                server.read_line(&mut line).unwrap();
+                line
+            })
+            .collect();
+        assert_eq!(
+            lines,
+            [
+                "starttimer\r\n",
+                "pausegametime\r\n",
+                "setgametime 0:00:00.000\r\n",
+                "setgametime 0:00:01.500\r\n",
+                "split\r\n",
+                "reset\r\n",
+            ]
+        );
+    }
+}
@@ -0,0 +1,364 @@
+// Autosplitting for speedruns: the timer starts, splits and resets on the
+// frame the game's memory says so, and the time is counted in emulated
+// frames, so it doesn't drift with the host or stutter with a slow one. A
+// splits file says what to watch, in TOML:
+//
+//     start = { address = 0x0770, from = 0, to = 1 }
+//     reset = { address = 0x0770, to = 0 }
+//
+//     [[split]]
+//     name = "1-1"
+//     address = 0x075F
+//     to = 1
+//     when = [{ address = 0x0770, value = 1 }]
+//
+// Each is a transition: the byte at `address` (with `mask`, if given,
+// $FF otherwise) changes between one frame and the next, from `from` to
+// `to` where they're given. Anything in `when` has to hold on that frame
+// too. Splits are taken in order, only the next one being watched; after
+// the last the run is finished until the reset. Addresses are the CPU's,
+// read without side effects.
+//
+// A loaded state or a rewind jumps the memory; forget() after one has the
+// next frame only sample it, so the jump isn't taken for a transition.
+// livesplit.rs sends the events on to LiveSplit.
+
+pub mod livesplit;
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::time::Duration;
+
+use serde::Deserialize;
+
+type Address = u16;
+type Value = u8;
+
+#[derive(Debug)]
+pub enum SplitsError {
+    Parse(toml::de::Error),
+    // parses, but can't be run
+    Value(String),
+}
+
+impl fmt::Display for SplitsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SplitsError::Parse(err) => write!(f, "{}", err),
+            SplitsError::Value(problem) => write!(f, "{}", problem),
+        }
+    }
+}
+
+impl std::error::Error for SplitsError {}
+
+impl From<toml::de::Error> for SplitsError {
+    fn from(err: toml::de::Error) -> Self {
+        SplitsError::Parse(err)
+    }
+}
+
+fn full_mask() -> Value {
+    0xFF
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct Level {
+    pub address: Address,
+    pub value: Value,
+    #[serde(default = "full_mask")]
+    pub mask: Value,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct Trigger {
+    pub address: Address,
+    pub from: Option<Value>,
+    pub to: Option<Value>,
+    #[serde(default = "full_mask")]
+    pub mask: Value,
+    #[serde(default)]
+    pub when: Vec<Level>,
+}
+
+impl Trigger {
+    fn fires(
+        &self,
+        previous: &BTreeMap<Address, Value>,
+        current: &BTreeMap<Address, Value>,
+    ) -> bool {
+        let (Some(&was), Some(&now)) = (previous.get(&self.address), current.get(&self.address))
+        else {
+            return false;
+        };
+        let (was, now) = (was & self.mask, now & self.mask);
+        was != now
+            && self.from.is_none_or(|from| from & self.mask == was)
+            && self.to.is_none_or(|to| to & self.mask == now)
+            && self.when.iter().all(|level| {
+                current.get(&level.address).copied().unwrap_or(0) & level.mask
+                    == level.value & level.mask
+            })
+    }
+
+    fn addresses(&self) -> impl Iterator<Item = Address> + '_ {
+        let when = self.when.iter().map(|level| level.address);
+        std::iter::once(self.address).chain(when)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct Split {
+    pub name: String,
+    #[serde(flatten)]
+    pub trigger: Trigger,
+}
+
+#[derive(Debug, Deserialize)]
+struct SplitsFile {
+    start: Trigger,
+    reset: Option<Trigger>,
+    #[serde(default)]
+    split: Vec<Split>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SplitEvent {
+    Started,
+    // `frames` since the start, the time of the run so far
+    Split { index: usize, frames: u64 },
+    // the last split
+    Finished { frames: u64 },
+    Reset,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Run {
+    Idle,
+    // on the split with this index
+    Running(usize),
+    Finished,
+}
+
+#[derive(Debug, Clone)]
+pub struct Autosplitter {
+    start: Trigger,
+    reset: Option<Trigger>,
+    splits: Vec<Split>,
+    run: Run,
+    // since the start
+    frames: u64,
+    // every watched byte as of the last frame; empty after forget()
+    previous: BTreeMap<Address, Value>,
+}
+
+impl Autosplitter {
+    pub fn new(start: Trigger, reset: Option<Trigger>, splits: Vec<Split>) -> Self {
+        Autosplitter {
+            start,
+            reset,
+            splits,
+            run: Run::Idle,
+            frames: 0,
+            previous: BTreeMap::new(),
+        }
+    }
+
+    pub fn from_toml(text: &str) -> Result<Self, SplitsError> {
+        let file: SplitsFile = toml::from_str(text)?;
+        if file.split.is_empty() {
+            return Err(SplitsError::Value("no [[split]] to take".to_string()));
+        }
+        Ok(Self::new(file.start, file.reset, file.split))
+    }
+
+    pub fn splits(&self) -> &[Split] {
+        &self.splits
+    }
+
+    // The split being watched for, while running.
+    pub fn current_split(&self) -> Option<usize> {
+        match self.run {
+            Run::Running(index) => Some(index),
+            _ => None,
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
+        matches!(self.run, Run::Running(_))
+    }
+
+    // Frames since the start; see frame_time.
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    pub fn forget(&mut self) {
+        self.previous.clear();
+    }
+
+    // Back to waiting for the start, as the reset trigger does.
+    pub fn reset(&mut self) {
+        self.run = Run::Idle;
+        self.frames = 0;
+    }
+
+    // After every frame, with a way to read the CPU's memory; what
+    // happened on it, in order.
+    pub fn do_frame(&mut self, mut read: impl FnMut(Address) -> Value) -> Vec<SplitEvent> {
+        let watched = self
+            .start
+            .addresses()
+            .chain(self.reset.iter().flat_map(Trigger::addresses))
+            .chain(
+                self.splits
+                    .iter()
+                    .flat_map(|split| split.trigger.addresses()),
+            );
+        let mut current = BTreeMap::new();
+        for addr in watched {
+            current.entry(addr).or_insert_with(|| read(addr));
+        }
+
+        let mut events = Vec::new();
+        if self.run != Run::Idle {
+            self.frames += u64::from(self.is_running());
+            if let Some(reset) = &self.reset {
+                if reset.fires(&self.previous, &current) {
+                    self.reset();
+                    events.push(SplitEvent::Reset);
+                }
+            }
+        }
+        match self.run {
+            Run::Idle if self.start.fires(&self.previous, &current) => {
+                self.run = Run::Running(0);
+                events.push(SplitEvent::Started);
+            }
+            Run::Running(index) if self.splits[index].trigger.fires(&self.previous, &current) => {
+                let frames = self.frames;
+                if index + 1 == self.splits.len() {
+                    self.run = Run::Finished;
+                    events.push(SplitEvent::Finished { frames });
+                } else {
+                    self.run = Run::Running(index + 1);
+                    events.push(SplitEvent::Split { index, frames });
+                }
+            }
+            _ => {}
+        }
+        self.previous = current;
+        events
+    }
+}
+
+// A run's time from its frames.
+pub fn frame_time(frames: u64, frame_rate: f64) -> Duration {
+    Duration::from_secs_f64(frames as f64 / frame_rate)
+}
+
+// H:MM:SS.mmm, as LiveSplit reads and shows it.
+pub fn format_time(time: Duration) -> String {
+    let millis = time.as_millis();
+    format!(
+        "{}:{:02}:{:02}.{:03}",
+        millis / 3_600_000,
+        millis / 60_000 % 60,
+        millis / 1000 % 60,
+        millis % 1000
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const SPLITS: &str = r#"
+        start = { address = 0x0770, from = 0, to = 1 }
+        reset = { address = 0x0770, to = 0 }
+
+        [[split]]
+        name = "1-1"
+        address = 0x075F
+        to = 1
+        when = [{ address = 0x0770, value = 1 }]
+
+        [[split]]
+        name = "1-2"
+        address = 0x0760
+        mask = 0x0F
+        from = 0
+    "#;
+
+    fn frame(splitter: &mut Autosplitter, ram: &[(Address, Value)]) -> Vec<SplitEvent> {
+        splitter.do_frame(|addr| {
+            ram.iter()
+                .find(|&&(at, _)| at == addr)
+                .map_or(0, |&(_, value)| value)
+        })
+    }
+
+    #[test]
+    fn test_splits_on_transitions() {
+        let mut splitter = Autosplitter::from_toml(SPLITS).unwrap();
+        assert_eq!(splitter.splits()[1].name, "1-2");
+        // a value that's already there on the first frame isn't a start
+        assert!(frame(&mut splitter, &[(0x0770, 1)]).is_empty());
+        assert!(frame(&mut splitter, &[]).is_empty());
+        assert_eq!(frame(&mut splitter, &[(0x0770, 1)]), [SplitEvent::Started]);
+        assert_eq!(splitter.current_split(), Some(0));
+
+        // not while the condition doesn't hold
+        frame(&mut splitter, &[(0x0770, 1)]);
+        frame(&mut splitter, &[(0x0770, 2), (0x075F, 1)]);
+        frame(&mut splitter, &[(0x0770, 1)]);
+        assert_eq!(
+            frame(&mut splitter, &[(0x0770, 1), (0x075F, 1)]),
+            [SplitEvent::Split {
+                index: 0,
+                frames: 4
+            }]
+        );
+        // only the low bits of $0760 count
+        frame(&mut splitter, &[(0x0770, 1), (0x075F, 1), (0x0760, 0x10)]);
+        assert_eq!(
+            frame(&mut splitter, &[(0x0770, 1), (0x075F, 1), (0x0760, 0x12)]),
+            [SplitEvent::Finished { frames: 6 }]
+        );
+        assert!(!splitter.is_running());
+        assert_eq!(frame(&mut splitter, &[]), [SplitEvent::Reset]);
+        assert_eq!(splitter.frames(), 0);
+    }
+
+    #[test]
+    fn test_forget_skips_a_jump() {
+        let mut splitter = Autosplitter::from_toml(SPLITS).unwrap();
+        frame(&mut splitter, &[]);
+        splitter.forget();
+        assert!(frame(&mut splitter, &[(0x0770, 1)]).is_empty());
+        frame(&mut splitter, &[]);
+        assert_eq!(frame(&mut splitter, &[(0x0770, 1)]), [SplitEvent::Started]);
+    }
+
+    #[test]
+    fn test_bad_splits_files() {
+        assert!(matches!(
+            Autosplitter::from_toml("start = { address = 1 }"),
+            Err(SplitsError::Value(_))
+        ));
+        assert!(matches!(
+            Autosplitter::from_toml("[[split]]\nname = \"x\"\naddress = 1"),
+            Err(SplitsError::Parse(_))
+        ));
+    }
+
+    #[test]
+    fn test_times() {
+        assert_eq!(format_time(frame_time(0, 60.0)), "0:00:00.000");
+        assert_eq!(format_time(frame_time(60 * 61 + 30, 60.0)), "0:01:01.500");
+        assert_eq!(
+            format_time(Duration::from_secs(3 * 3600 + 7)),
+            "3:00:07.000"
+        );
+    }
+}
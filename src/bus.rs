@@ -0,0 +1,747 @@
+// CPU address space:
+//
+//  $0000-$1FFF  2K internal RAM, mirrored every $0800
+//  $2000-$3FFF  PPU registers, mirrored every 8 bytes
+//  $4000-$4017  APU and I/O registers. $4014 starts OAM DMA; $4016/$4017
+//               read the two controller ports; writing $4016 strobes both,
+//               while writing $4017 goes to the APU frame counter. On a
+//               VS. System the reads carry its coins and switches too.
+//  $4018-$FFFF  cartridge, but for what attached peripherals claim; see
+//               peripheral.rs
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+
+use serde::{Deserialize, Serialize};
+
+use crate::apu::Apu;
+use crate::cheat::Cheats;
+use crate::debugger::watch::{Access, WatchHook};
+use crate::input::Controllers;
+use crate::mapper::{Mapper, Mirroring};
+use crate::peripheral::Peripherals;
+use crate::ppu::{Ppu, VISIBLE_SCANLINES};
+use crate::region::{Region, PAL_DOT_PHASES};
+use crate::rng::{RamInit, Rng};
+use crate::state::{Dirty, StateError};
+use crate::vs::VsSystem;
+
+type Address = u16;
+type Value = u8;
+
+pub trait Mem {
+    fn mem_read(&mut self, addr: Address) -> Value;
+
+    fn mem_write(&mut self, addr: Address, value: Value);
+
+    fn mem_read_u16(&mut self, addr: Address) -> u16 {
+        let lo = self.mem_read(addr) as u16;
+        let hi = self.mem_read(addr.wrapping_add(1)) as u16;
+        (hi << 8) | lo
+    }
+
+    fn mem_write_u16(&mut self, addr: Address, value: u16) {
+        let hi = (value >> 8) as u8;
+        let lo = (value & 0xff) as u8;
+        self.mem_write(addr, lo);
+        self.mem_write(addr.wrapping_add(1), hi)
+    }
+}
+
+// OAM DMA halts the CPU for 513 cycles (514 when it starts on an odd one,
+// which isn't tracked).
+const OAM_DMA_CYCLES: u16 = 513;
+
+// Serializes as the RAM section of a savestate; the chips have their own.
+#[derive(Serialize, Deserialize)]
+pub struct Bus {
+    #[serde(with = "crate::state::byte_array")]
+    cpu_ram: [Value; 0x800],
+    // what's been written since the last frame hash; see
+    // state::MachineHasher
+    #[serde(skip)]
+    pub(crate) ram_dirty: Dirty,
+    #[serde(skip)]
+    cartridge_dirty: Dirty,
+    #[serde(skip, default = "Ppu::new")]
+    pub ppu: Ppu,
+    #[serde(skip, default = "Apu::new")]
+    pub apu: Apu,
+    #[serde(skip, default = "Controllers::new")]
+    pub controllers: Controllers,
+    #[serde(skip)]
+    cartridge: Option<Box<dyn Mapper>>,
+    dma_stall: u16,
+    // every access goes past this for watchpoints
+    #[serde(skip)]
+    watch: WatchHook,
+    // laid over cartridge reads, and written to RAM each frame
+    #[serde(skip)]
+    cheats: Cheats,
+    // for VS. System games; savestates have it as a section of its own
+    #[serde(skip)]
+    pub vs: Option<VsSystem>,
+    #[serde(skip)]
+    region: Region,
+    // where a PAL CPU cycle falls in the five that make 16 PPU dots; a
+    // section of its own too
+    #[serde(skip)]
+    pub(crate) dot_phase: u8,
+    // overclocking: scanlines the CPU gets to itself after the picture
+    #[serde(skip)]
+    extra_scanlines: u16,
+    // CPU cycles of them still to run; a section of its own as well
+    #[serde(skip)]
+    pub(crate) overclock_cycles: u32,
+    // the host's own devices, which stay with the bus; their state is a
+    // section too
+    #[serde(skip)]
+    pub peripherals: Peripherals,
+    // everything random draws from this; a section of its own
+    #[serde(skip)]
+    pub(crate) rng: Rng,
+}
+
+// Stands in for the cartridge when the slot is empty.
+struct NoCartridge;
+
+impl Mapper for NoCartridge {
+    fn read_prg(&mut self, _addr: Address) -> Value {
+        0
+    }
+
+    fn write_prg(&mut self, _addr: Address, _value: Value) {}
+
+    fn read_chr(&mut self, _addr: Address) -> Value {
+        0
+    }
+
+    fn write_chr(&mut self, _addr: Address, _value: Value) {}
+
+    fn mirroring(&self) -> Mirroring {
+        Mirroring::Horizontal
+    }
+}
+
+fn cartridge_or<'a>(
+    cartridge: &'a mut Option<Box<dyn Mapper>>,
+    empty: &'a mut NoCartridge,
+) -> &'a mut dyn Mapper {
+    match cartridge.as_deref_mut() {
+        Some(mapper) => mapper,
+        None => empty,
+    }
+}
+
+impl Default for Bus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Bus {
+    pub fn new() -> Self {
+        Bus {
+            cpu_ram: [0; 0x800],
+            ram_dirty: Dirty::default(),
+            cartridge_dirty: Dirty::default(),
+            ppu: Ppu::new(),
+            apu: Apu::new(),
+            controllers: Controllers::new(),
+            cartridge: None,
+            dma_stall: 0,
+            watch: WatchHook::default(),
+            cheats: Cheats::default(),
+            vs: None,
+            region: Region::Ntsc,
+            dot_phase: 0,
+            extra_scanlines: 0,
+            overclock_cycles: 0,
+            peripherals: Peripherals::default(),
+            rng: Rng::default(),
+        }
+    }
+
+    // Seeds the machine's randomness and fills RAM as it powers on; see
+    // rng.rs.
+    pub fn power_on(&mut self, seed: u64, ram: RamInit) {
+        self.rng = Rng::new(seed);
+        ram.fill(&mut self.cpu_ram, &mut self.rng);
+        self.ram_dirty.mark_all();
+    }
+
+    pub fn rng_mut(&mut self) -> &mut Rng {
+        &mut self.rng
+    }
+
+    // The console's timing, for the PPU and APU too; see region.rs.
+    pub fn set_region(&mut self, region: Region) {
+        self.region = region.console();
+        self.ppu.set_region(region);
+        self.apu.set_region(region);
+        if self.region != Region::Pal {
+            self.dot_phase = 0;
+        }
+    }
+
+    pub fn region(&self) -> Region {
+        self.region
+    }
+
+    // Once the last visible line is drawn, stops the PPU, APU, mapper and
+    // peripherals for `lines` scanlines' worth of CPU cycles, which the
+    // CPU runs on its own. A game that hasn't finished its frame's work by
+    // vblank gets that much longer, and the picture and raster timing are
+    // as they were. The rest falls behind the CPU, though: the APU makes
+    // no sound in those cycles, which keeps audio in step with the
+    // picture, but its frame IRQ and DMC, and any IRQ counter on the
+    // board or a peripheral, come that many cycles later than the game
+    // would count on.
+    pub fn set_extra_scanlines(&mut self, lines: u16) {
+        self.extra_scanlines = lines;
+    }
+
+    pub fn extra_scanlines(&self) -> u16 {
+        self.extra_scanlines
+    }
+
+    pub fn insert_cartridge(&mut self, cartridge: Box<dyn Mapper>) {
+        self.cartridge = Some(cartridge);
+        self.cartridge_dirty.mark_all();
+    }
+
+    pub fn cartridge(&mut self) -> Option<&mut (dyn Mapper + 'static)> {
+        self.cartridge_dirty.mark_all();
+        self.cartridge.as_deref_mut()
+    }
+
+    // See Mapper::prg_offset.
+    pub fn prg_offset(&self, addr: Address) -> Option<usize> {
+        self.cartridge.as_ref()?.prg_offset(addr)
+    }
+
+    pub fn has_cartridge(&self) -> bool {
+        self.cartridge.is_some()
+    }
+
+    pub fn remove_cartridge(&mut self) -> Option<Box<dyn Mapper>> {
+        self.cartridge_dirty.mark_all();
+        self.cartridge.take()
+    }
+
+    // A read without side effects, for debuggers and trace logs. The
+    // controller ports and write-only registers read as 0.
+    pub fn peek(&mut self, addr: Address) -> Value {
+        match addr {
+            0x0000..=0x1FFF => self.cpu_ram[(addr & 0x07FF) as usize],
+            0x2000..=0x3FFF => self.ppu.peek_register(addr),
+            0x4015 => self.apu.peek_status(),
+            0x4018..=0xFFFF if !self.peripherals.is_empty() => match self.peripherals.peek(addr) {
+                Some(value) => value,
+                None if addr >= 0x4020 => self.read_cartridge(addr),
+                None => 0,
+            },
+            0x4020..=0xFFFF => self.read_cartridge(addr),
+            _ => 0,
+        }
+    }
+
+    // The PPU's address space the same way; see Ppu::peek.
+    pub fn peek_ppu(&mut self, addr: Address) -> Value {
+        let mut empty = NoCartridge;
+        let mapper = cartridge_or(&mut self.cartridge, &mut empty);
+        self.ppu.peek(addr, mapper)
+    }
+
+    // A write to the PPU's address space that leaves the registers alone.
+    pub fn poke_ppu(&mut self, addr: Address, value: Value) {
+        let mut empty = NoCartridge;
+        let mapper = cartridge_or(&mut self.cartridge, &mut empty);
+        self.ppu.write(addr, value, mapper);
+    }
+
+    // Cartridge space as the CPU sees it, through any Game Genie codes.
+    // Peeks go through them too, so a disassembly shows the patched code.
+    fn read_cartridge(&mut self, addr: Address) -> Value {
+        let Some(cartridge) = self.cartridge.as_mut() else {
+            return 0;
+        };
+        let value = cartridge.read_prg(addr);
+        if self.cheats.is_active() {
+            self.cheats.patch_prg(addr, value)
+        } else {
+            value
+        }
+    }
+
+    // RAM cheats, as each frame starts. Only RAM is written, so nothing
+    // else notices.
+    fn write_cheats(&mut self) {
+        for patch in self.cheats.take_ram_writes() {
+            match patch.addr {
+                0x0000..=0x1FFF => {
+                    let index = (patch.addr & 0x07FF) as usize;
+                    self.cpu_ram[index] = patch.value;
+                    self.ram_dirty.mark(index);
+                }
+                _ => {
+                    let offset = (patch.addr - 0x6000) as usize;
+                    if let Some(byte) = self.cartridge_ram_mut().get_mut(offset) {
+                        *byte = patch.value;
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn cheats(&self) -> &Cheats {
+        &self.cheats
+    }
+
+    pub fn cheats_mut(&mut self) -> &mut Cheats {
+        &mut self.cheats
+    }
+
+    pub fn watch_mut(&mut self) -> &mut WatchHook {
+        &mut self.watch
+    }
+
+    // The 2K of internal RAM, unmirrored.
+    pub fn ram(&self) -> &[Value] {
+        &self.cpu_ram
+    }
+
+    pub fn ram_mut(&mut self) -> &mut [Value] {
+        self.ram_dirty.mark_all();
+        &mut self.cpu_ram
+    }
+
+    // The cartridge's work RAM; empty without one.
+    pub fn cartridge_ram(&self) -> &[Value] {
+        self.cartridge
+            .as_ref()
+            .map_or(&[], |cartridge| cartridge.prg_ram())
+    }
+
+    pub fn cartridge_ram_mut(&mut self) -> &mut [Value] {
+        self.cartridge_dirty.mark_all();
+        match self.cartridge.as_mut() {
+            Some(cartridge) => cartridge.prg_ram_mut(),
+            None => &mut [],
+        }
+    }
+
+    pub fn cartridge_state(&self) -> Vec<u8> {
+        self.cartridge
+            .as_ref()
+            .map_or_else(Vec::new, |cartridge| cartridge.save_state())
+    }
+
+    pub fn load_cartridge_state(&mut self, state: &[u8]) -> Result<(), StateError> {
+        self.cartridge_dirty.mark_all();
+        match self.cartridge.as_mut() {
+            Some(cartridge) => cartridge.load_state(state),
+            None if state.is_empty() => Ok(()),
+            None => Err(StateError::WrongCartridge),
+        }
+    }
+
+    // Whether the cartridge might have changed since the last call: it
+    // was written to, from either side, or it's a board that changes by
+    // itself.
+    pub(crate) fn take_cartridge_written(&mut self) -> bool {
+        let written = self.cartridge_dirty.take().any();
+        let chr_written = self.ppu.chr_dirty.take().any();
+        written
+            || chr_written
+            || self
+                .cartridge
+                .as_ref()
+                .is_some_and(|cartridge| cartridge.changes_unwritten())
+    }
+
+    // After a savestate load: the cartridge and the host-side parts of the
+    // chips come over from the machine being replaced.
+    pub(crate) fn take_host_state(&mut self, old: &mut Bus) {
+        self.cartridge = old.cartridge.take();
+        self.watch = core::mem::take(&mut old.watch);
+        self.cheats = core::mem::take(&mut old.cheats);
+        // a state from before a VS. game had its section keeps the
+        // switches as they are
+        self.vs = match old.vs.take() {
+            Some(vs) => Some(self.vs.take().unwrap_or(vs)),
+            None => None,
+        };
+        self.ppu.take_host_state(&mut old.ppu);
+        self.ppu.set_vs(old.ppu.vs());
+        self.apu.take_host_state(&mut old.apu);
+        self.set_region(old.region);
+        self.extra_scanlines = old.extra_scanlines;
+        self.peripherals = core::mem::take(&mut old.peripherals);
+    }
+
+    pub fn irq_pending(&self) -> bool {
+        let cartridge = self.cartridge.as_ref();
+        self.apu.irq_pending()
+            || cartridge.is_some_and(|mapper| mapper.irq_pending())
+            || self.peripherals.irq_pending()
+    }
+
+    pub fn poll_nmi(&mut self) -> bool {
+        self.ppu.poll_nmi()
+    }
+
+    // CPU cycles owed to DMA since the last call.
+    pub fn take_dma_stall(&mut self) -> u16 {
+        core::mem::take(&mut self.dma_stall)
+    }
+
+    fn oam_dma(&mut self, page: Value) {
+        tracing::trace!("OAM DMA from ${:02X}00", page);
+        let base = (page as Address) << 8;
+        let mut data = [0; 256];
+        for (i, value) in data.iter_mut().enumerate() {
+            *value = self.mem_read(base + i as Address);
+        }
+        self.ppu.write_oam_dma(&data);
+        self.dma_stall += OAM_DMA_CYCLES;
+    }
+
+    // Advances everything clocked off the CPU by `cycles`.
+    pub fn tick(&mut self, cycles: u8) {
+        let frame = self.ppu.frame_count();
+        for _ in 0..cycles {
+            // the CPU's alone; see set_extra_scanlines
+            if self.overclock_cycles > 0 {
+                self.overclock_cycles -= 1;
+                continue;
+            }
+            let mut empty = NoCartridge;
+            let mapper = cartridge_or(&mut self.cartridge, &mut empty);
+            for _ in 0..self.region.ppu_dots(self.dot_phase) {
+                self.ppu.tick(mapper);
+                if self.ppu.scanline() == VISIBLE_SCANLINES && self.ppu.dot() == 0 {
+                    self.overclock_cycles = self.region.scanline_cycles(self.extra_scanlines);
+                }
+            }
+            if self.region == Region::Pal {
+                self.dot_phase = (self.dot_phase + 1) % PAL_DOT_PHASES;
+            }
+            self.controllers.sense_light(
+                self.ppu.frame(),
+                self.ppu.scanline(),
+                self.ppu.dot().saturating_sub(1),
+            );
+
+            mapper.clock();
+            if !self.peripherals.is_empty() {
+                self.peripherals.clock();
+            }
+            self.apu.tick();
+            let mut expansion = mapper.expansion_audio();
+            if let Some(chip) = expansion.as_mut() {
+                chip.clock();
+            }
+            self.apu.record_output(expansion.as_deref());
+
+            if let Some(addr) = self.apu.dmc_dma_request() {
+                let value = self.mem_read(addr);
+                self.apu.dmc_dma_complete(value);
+            }
+        }
+        if self.ppu.frame_count() != frame {
+            if self.cheats.writes_ram() {
+                self.write_cheats();
+            }
+            if let Some(vs) = self.vs.as_mut() {
+                vs.frame_done();
+            }
+        }
+    }
+}
+
+impl core::fmt::Debug for Bus {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_struct("Bus")
+            .field("ppu", &self.ppu)
+            .field("apu", &self.apu)
+            .field("cartridge", &self.cartridge.is_some())
+            .finish()
+    }
+}
+
+impl Mem for Bus {
+    fn mem_read(&mut self, addr: Address) -> Value {
+        let value = match addr {
+            0x0000..=0x1FFF => self.cpu_ram[(addr & 0x07FF) as usize],
+            0x2000..=0x3FFF => {
+                let mut empty = NoCartridge;
+                let mapper = cartridge_or(&mut self.cartridge, &mut empty);
+                self.ppu.read_register(addr, mapper)
+            }
+            0x4015 => self.apu.read_status(),
+            0x4016 | 0x4017 => {
+                let port = (addr & 1) as usize;
+                match self.vs.as_ref() {
+                    Some(vs) => vs.read(port) | self.controllers.read(port),
+                    // the upper bits are open bus, usually $40 from the
+                    // address
+                    None => 0x40 | self.controllers.read(port),
+                }
+            }
+            0x4018..=0xFFFF if !self.peripherals.is_empty() => {
+                match self.peripherals.claiming(addr) {
+                    Some(device) => device.read(addr),
+                    None if addr >= 0x4020 => self.read_cartridge(addr),
+                    None => 0,
+                }
+            }
+            0x4020..=0xFFFF => self.read_cartridge(addr),
+            _ => 0,
+        };
+        if self.watch.is_armed() {
+            self.watch.access(addr, value, Access::Read);
+        }
+        value
+    }
+
+    fn mem_write(&mut self, addr: Address, value: Value) {
+        if self.watch.is_armed() {
+            self.watch.access(addr, value, Access::Write);
+        }
+        if let Some(device) = self.peripherals.claiming(addr) {
+            device.write(addr, value);
+            return;
+        }
+        match addr {
+            0x0000..=0x1FFF => {
+                let index = (addr & 0x07FF) as usize;
+                self.cpu_ram[index] = value;
+                self.ram_dirty.mark(index);
+            }
+            0x2000..=0x3FFF => {
+                let mut empty = NoCartridge;
+                let mapper = cartridge_or(&mut self.cartridge, &mut empty);
+                self.ppu.write_register(addr, value, mapper);
+            }
+            0x4014 => self.oam_dma(value),
+            0x4000..=0x4013 | 0x4015 | 0x4017 => self.apu.write_register(addr, value),
+            0x4016 => {
+                self.controllers.write(value);
+                if let Some(cartridge) = self.cartridge.as_mut() {
+                    self.cartridge_dirty.mark_all();
+                    cartridge.write_4016(value);
+                }
+            }
+            0x4020..=0xFFFF => {
+                if let Some(cartridge) = self.cartridge.as_mut() {
+                    // RAM at $6000-$7FFF is too busy to be worth it
+                    if !(0x6000..0x8000).contains(&addr) {
+                        tracing::trace!(target: "nes::mapper", "write ${:04X} = ${:02X}", addr, value);
+                    }
+                    self.cartridge_dirty.mark_all();
+                    cartridge.write_prg(addr, value);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::cheat::game_genie::GameGenie;
+    use crate::cheat::Cheat;
+    use crate::mapper::nrom::Nrom;
+    use crate::mapper::Mirroring;
+    use crate::peripheral::Peripheral;
+
+    #[test]
+    fn test_ram_is_mirrored() {
+        let mut bus = Bus::new();
+        bus.mem_write(0x0001, 0x42);
+
+        assert_eq!(bus.mem_read(0x0801), 0x42);
+        assert_eq!(bus.mem_read(0x1801), 0x42);
+    }
+
+    #[test]
+    fn test_cartridge_space() {
+        let mut bus = Bus::new();
+        assert_eq!(bus.mem_read(0x8000), 0);
+
+        let mut prg = vec![0; 0x4000];
+        prg[0] = 0x99;
+        bus.insert_cartridge(Box::new(Nrom::new(prg, Vec::new(), Mirroring::Vertical)));
+        assert_eq!(bus.mem_read(0x8000), 0x99);
+    }
+
+    #[test]
+    fn test_game_genie_codes_patch_cartridge_reads() {
+        let mut bus = Bus::new();
+        let mut prg = vec![0; 0x4000];
+        prg[0x11D9] = 0xCE;
+        bus.insert_cartridge(Box::new(Nrom::new(prg, Vec::new(), Mirroring::Vertical)));
+        // SXIOPO is $91D9 = $AD; 16K is mirrored at $D1D9 too, which it
+        // leaves alone
+        let lives: GameGenie = "SXIOPO".parse().unwrap();
+        bus.cheats_mut().add(Cheat::new(lives));
+        assert_eq!(bus.mem_read(0x91D9), 0xAD);
+        assert_eq!(bus.peek(0x91D9), 0xAD);
+        assert_eq!(bus.mem_read(0xD1D9), 0xCE);
+        bus.cheats_mut().set_enabled(0, false);
+        assert_eq!(bus.mem_read(0x91D9), 0xCE);
+    }
+
+    #[test]
+    fn test_apu_status_register() {
+        let mut bus = Bus::new();
+        bus.mem_write(0x4015, 0x01);
+        bus.mem_write(0x4003, 0xF8);
+
+        assert_eq!(bus.mem_read(0x4015) & 0x01, 0x01);
+    }
+
+    #[test]
+    fn test_controller_port() {
+        use crate::input::joypad::JoypadButton;
+
+        let mut bus = Bus::new();
+        bus.controllers.set_buttons(0, JoypadButton::BUTTON_B);
+        bus.mem_write(0x4016, 1);
+        bus.mem_write(0x4016, 0);
+
+        assert_eq!(bus.mem_read(0x4016), 0x40);
+        assert_eq!(bus.mem_read(0x4016), 0x41);
+    }
+
+    #[test]
+    fn test_second_controller_port() {
+        use crate::input::joypad::JoypadButton;
+
+        let mut bus = Bus::new();
+        bus.controllers.set_buttons(0, JoypadButton::BUTTON_A);
+        bus.controllers.set_buttons(1, JoypadButton::BUTTON_B);
+        bus.mem_write(0x4016, 1);
+        bus.mem_write(0x4016, 0);
+
+        assert_eq!(bus.mem_read(0x4017), 0x40);
+        assert_eq!(bus.mem_read(0x4017), 0x41);
+        // port 1 is shifted independently
+        assert_eq!(bus.mem_read(0x4016), 0x41);
+    }
+
+    #[test]
+    fn test_4017_write_goes_to_frame_counter_not_joypad() {
+        use crate::input::joypad::JoypadButton;
+
+        let mut bus = Bus::new();
+        bus.controllers.set_buttons(1, JoypadButton::RIGHT);
+        bus.mem_write(0x4016, 1);
+        bus.mem_write(0x4016, 0);
+        bus.mem_write(0x4017, 0x40); // inhibit frame IRQ, must not re-strobe
+
+        for _ in 0..7 {
+            assert_eq!(bus.mem_read(0x4017), 0x40);
+        }
+        assert_eq!(bus.mem_read(0x4017), 0x41);
+
+        for _ in 0..120 {
+            bus.tick(250);
+        }
+        assert!(!bus.irq_pending());
+    }
+
+    #[test]
+    fn test_ppu_registers_are_mirrored() {
+        let mut bus = Bus::new();
+        bus.mem_write(0x3456, 0x20); // $2006
+        bus.mem_write(0x2006, 0x00);
+        bus.mem_write(0x2007, 0x5A);
+        bus.mem_write(0x2006, 0x20);
+        bus.mem_write(0x2006, 0x00);
+
+        bus.mem_read(0x2007);
+        assert_eq!(bus.mem_read(0x3FFF), 0x5A);
+    }
+
+    #[test]
+    fn test_oam_dma() {
+        let mut bus = Bus::new();
+        bus.mem_write(0x0200, 0x11);
+        bus.mem_write(0x02FF, 0x22);
+        bus.mem_write(0x4014, 0x02);
+
+        assert_eq!(bus.ppu.oam()[0x00], 0x11);
+        assert_eq!(bus.ppu.oam()[0xFF], 0x22);
+        assert_eq!(bus.take_dma_stall(), 513);
+        assert_eq!(bus.take_dma_stall(), 0);
+    }
+
+    #[test]
+    fn test_ppu_runs_three_dots_per_cycle() {
+        let mut bus = Bus::new();
+        bus.tick(10);
+        assert_eq!(bus.ppu.dot(), 30);
+    }
+
+    #[test]
+    fn test_dmc_reads_samples_through_the_bus() {
+        let mut prg = vec![0; 0x4000];
+        prg[0x0000] = 0xFF; // $C000, first sample byte
+        let mut bus = Bus::new();
+        bus.insert_cartridge(Box::new(Nrom::new(prg, Vec::new(), Mirroring::Vertical)));
+
+        bus.mem_write(0x4013, 0x00);
+        bus.mem_write(0x4015, 0x10);
+        bus.tick(1);
+
+        assert_eq!(bus.mem_read(0x4015) & 0x10, 0);
+    }
+
+    // raises IRQ after 8 clocks; reads give how many it's had
+    #[derive(Default)]
+    struct Timer(u8);
+
+    impl Peripheral for Timer {
+        fn read(&mut self, _addr: Address) -> Value {
+            self.0
+        }
+
+        fn write(&mut self, _addr: Address, _value: Value) {
+            self.0 = 0;
+        }
+
+        fn clock(&mut self) {
+            self.0 = self.0.saturating_add(1);
+        }
+
+        fn irq_pending(&self) -> bool {
+            self.0 >= 8
+        }
+    }
+
+    #[test]
+    fn test_peripherals_go_before_the_cartridge() {
+        let mut bus = Bus::new();
+        bus.insert_cartridge(Box::new(Nrom::new(
+            vec![0xEA; 0x4000],
+            Vec::new(),
+            Mirroring::Vertical,
+        )));
+        bus.peripherals
+            .attach(0x4018..=0x8000, Box::new(Timer::default()))
+            .unwrap();
+        bus.tick(5);
+        assert_eq!(bus.mem_read(0x8000), 5);
+        assert_eq!(bus.peek(0x4018), 0);
+        assert_eq!(bus.mem_read(0x8001), 0xEA);
+        assert!(!bus.irq_pending());
+
+        bus.tick(3);
+        assert!(bus.irq_pending());
+        bus.mem_write(0x6000, 0);
+        assert!(!bus.irq_pending());
+    }
+}
@@ -0,0 +1,383 @@
+// iNES and NES 2.0 ROM images. A 16 byte header:
+//
+//   0-3   "NES\x1a"
+//   4     PRG ROM size in 16K units
+//   5     CHR ROM size in 8K units (0 means the board has CHR RAM)
+//   6     mapper low nibble, four-screen, trainer, battery, mirroring
+//   7     mapper high nibble, NES 2.0 marker in bits 2-3, console type
+//         (bit 0 on iNES: a VS. System game)
+//   8-15  NES 2.0: mapper/submapper high bits, ROM size high bits, RAM
+//         sizes, timing, VS. System PPU and hardware; iNES: mostly
+//         unused
+//
+// followed by an optional 512 byte trainer, PRG ROM and CHR ROM. PRG and
+// CHR are slices of the image, not copies of it (see rom.rs), so a
+// cartridge costs no more than the file, and its clones nothing.
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::fmt;
+
+use crate::mapper::action53::Action53;
+use crate::mapper::caltron::Caltron;
+use crate::mapper::nrom::Nrom;
+use crate::mapper::nwc::Nwc;
+use crate::mapper::vs::VsUnisystem;
+use crate::mapper::{Mapper, Mirroring};
+pub use crate::region::Region;
+pub use crate::rom::Rom;
+use crate::vs::{VsCabinet, VsHardware, VsPpu};
+
+const NES_TAG: &[u8; 4] = b"NES\x1a";
+const HEADER_LEN: usize = 16;
+const TRAINER_LEN: usize = 512;
+const PRG_ROM_PAGE_SIZE: usize = 0x4000;
+const CHR_ROM_PAGE_SIZE: usize = 0x2000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RomFormat {
+    INes,
+    Nes20,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum CartridgeError {
+    BadMagic,
+    Truncated { expected: usize, actual: usize },
+    UnsupportedMapper(u16),
+    UnsupportedVs(VsHardware),
+}
+
+impl fmt::Display for CartridgeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CartridgeError::BadMagic => write!(f, "not an iNES file"),
+            CartridgeError::Truncated { expected, actual } => write!(
+                f,
+                "file is truncated: header needs {} bytes, got {}",
+                expected, actual
+            ),
+            CartridgeError::UnsupportedMapper(id) => write!(f, "mapper {} is not supported", id),
+            CartridgeError::UnsupportedVs(hardware) => {
+                write!(f, "VS. System {:?} hardware is not supported", hardware)
+            }
+        }
+    }
+}
+
+impl core::error::Error for CartridgeError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cartridge {
+    pub format: RomFormat,
+    pub mapper: u16,
+    pub submapper: u8,
+    pub mirroring: Mirroring,
+    pub battery: bool,
+    pub region: Region,
+    // for VS. System arcade games; see vs.rs
+    pub vs: Option<VsCabinet>,
+    pub trainer: Option<Vec<u8>>,
+    pub prg_rom: Rom,
+    // empty for boards with CHR RAM
+    pub chr_rom: Rom,
+}
+
+impl Cartridge {
+    // Copies the image, as the cartridge outlives the borrow; from_rom
+    // doesn't.
+    pub fn from_bytes(raw: &[u8]) -> Result<Self, CartridgeError> {
+        Self::from_rom(raw)
+    }
+
+    // A Vec, an Arc<[u8]>, a &'static [u8] or a memory map, which the
+    // cartridge keeps and slices rather than copying.
+    pub fn from_rom(rom: impl Into<Rom>) -> Result<Self, CartridgeError> {
+        let rom = rom.into();
+        let raw = &rom[..];
+        if raw.len() < 4 || &raw[0..4] != NES_TAG {
+            return Err(CartridgeError::BadMagic);
+        }
+        if raw.len() < HEADER_LEN {
+            return Err(CartridgeError::Truncated {
+                expected: HEADER_LEN,
+                actual: raw.len(),
+            });
+        }
+
+        let format = if raw[7] & 0x0C == 0x08 {
+            RomFormat::Nes20
+        } else {
+            RomFormat::INes
+        };
+
+        let mut mapper = ((raw[7] & 0xF0) | (raw[6] >> 4)) as u16;
+        let mut submapper = 0;
+        let mut prg_pages = raw[4] as usize;
+        let mut chr_pages = raw[5] as usize;
+        let mut region = Region::Ntsc;
+        let mut vs = (raw[7] & 0x03 == 0x01).then(VsCabinet::default);
+
+        match format {
+            RomFormat::Nes20 => {
+                mapper |= ((raw[8] & 0x0F) as u16) << 8;
+                submapper = raw[8] >> 4;
+                prg_pages |= ((raw[9] & 0x0F) as usize) << 8;
+                chr_pages |= ((raw[9] >> 4) as usize) << 8;
+                region = match raw[12] & 0x03 {
+                    0 => Region::Ntsc,
+                    1 => Region::Pal,
+                    2 => Region::Multi,
+                    _ => Region::Dendy,
+                };
+                if let Some(cabinet) = vs.as_mut() {
+                    cabinet.ppu = VsPpu::from_nes2(raw[13] & 0x0F).unwrap_or_default();
+                    cabinet.hardware = VsHardware::from_nes2(raw[13] >> 4);
+                }
+            }
+            RomFormat::INes => {
+                // junk from old dumping tools ("DiskDude!") lives in bytes
+                // 7-15; if the tail isn't zero the high nibble can't be
+                // trusted either
+                if raw[12..16].iter().any(|&b| b != 0) {
+                    mapper &= 0x0F;
+                    vs = None;
+                }
+                if raw[9] & 0x01 != 0 {
+                    region = Region::Pal;
+                }
+            }
+        }
+
+        // only VS. games use the board
+        if mapper == 99 && vs.is_none() {
+            vs = Some(VsCabinet::default());
+        }
+
+        let mirroring = if raw[6] & 0b1000 != 0 {
+            Mirroring::FourScreen
+        } else if raw[6] & 0b1 != 0 {
+            Mirroring::Vertical
+        } else {
+            Mirroring::Horizontal
+        };
+        let battery = raw[6] & 0b10 != 0;
+        let has_trainer = raw[6] & 0b100 != 0;
+
+        let prg_start = HEADER_LEN + if has_trainer { TRAINER_LEN } else { 0 };
+        let prg_len = prg_pages * PRG_ROM_PAGE_SIZE;
+        let chr_start = prg_start + prg_len;
+        let chr_len = chr_pages * CHR_ROM_PAGE_SIZE;
+        if raw.len() < chr_start + chr_len {
+            return Err(CartridgeError::Truncated {
+                expected: chr_start + chr_len,
+                actual: raw.len(),
+            });
+        }
+
+        tracing::debug!(
+            ?format,
+            mapper,
+            submapper,
+            prg_len,
+            chr_len,
+            ?region,
+            vs = vs.is_some(),
+            "cartridge"
+        );
+        Ok(Cartridge {
+            format,
+            mapper,
+            submapper,
+            mirroring,
+            battery,
+            region,
+            vs,
+            trainer: has_trainer.then(|| raw[HEADER_LEN..prg_start].to_vec()),
+            prg_rom: rom.slice(prg_start..chr_start),
+            chr_rom: rom.slice(chr_start..chr_start + chr_len),
+        })
+    }
+
+    // CRC32 of the PRG and CHR ROM, leaving out the header and trainer, so
+    // the same game matches however its header was written.
+    pub fn crc32(&self) -> u32 {
+        let mut hasher = crc32fast::Hasher::new();
+        hasher.update(&self.prg_rom);
+        hasher.update(&self.chr_rom);
+        hasher.finalize()
+    }
+
+    // iNES headers rarely set their PAL bit, so for those a country tag in
+    // the file name has the last word; see region.rs.
+    pub fn guess_region(&mut self, file_name: &str) {
+        if self.format == RomFormat::INes {
+            if let Some(region) = Region::from_file_name(file_name) {
+                self.region = region;
+            }
+        }
+    }
+
+    // The board the ROM runs on.
+    pub fn into_mapper(self) -> Result<Box<dyn Mapper>, CartridgeError> {
+        if let Some(cabinet) = self.vs.filter(|cabinet| !cabinet.hardware.is_supported()) {
+            return Err(CartridgeError::UnsupportedVs(cabinet.hardware));
+        }
+        match self.mapper {
+            0 => Ok(Box::new(Nrom::new(
+                self.prg_rom,
+                self.chr_rom,
+                self.mirroring,
+            ))),
+            28 => Ok(Box::new(Action53::new(self.prg_rom, self.chr_rom))),
+            41 => Ok(Box::new(Caltron::new(self.prg_rom, self.chr_rom))),
+            99 => Ok(Box::new(VsUnisystem::new(self.prg_rom, self.chr_rom))),
+            105 => Ok(Box::new(Nwc::new(self.prg_rom))),
+            id => Err(CartridgeError::UnsupportedMapper(id)),
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+
+    // A valid image with every PRG byte set to its page number and the
+    // given header bytes 6 and 7.
+    pub(crate) fn test_rom(prg_pages: u8, chr_pages: u8, flags6: u8, flags7: u8) -> Vec<u8> {
+        let mut rom = NES_TAG.to_vec();
+        rom.extend_from_slice(&[prg_pages, chr_pages, flags6, flags7, 0, 0, 0, 0, 0, 0, 0, 0]);
+        if flags6 & 0b100 != 0 {
+            rom.extend(core::iter::repeat_n(0xEE, TRAINER_LEN));
+        }
+        for page in 0..prg_pages {
+            rom.extend(core::iter::repeat_n(page, PRG_ROM_PAGE_SIZE));
+        }
+        rom.extend(core::iter::repeat_n(
+            0xCC,
+            chr_pages as usize * CHR_ROM_PAGE_SIZE,
+        ));
+        rom
+    }
+
+    #[test]
+    fn test_parses_ines_header() {
+        let cart = Cartridge::from_bytes(&test_rom(2, 1, 0x31, 0x00)).unwrap();
+        assert_eq!(cart.format, RomFormat::INes);
+        assert_eq!(cart.mapper, 3);
+        assert_eq!(cart.mirroring, Mirroring::Vertical);
+        assert!(!cart.battery);
+        assert_eq!(cart.prg_rom.len(), 2 * PRG_ROM_PAGE_SIZE);
+        assert_eq!(cart.prg_rom[PRG_ROM_PAGE_SIZE], 1);
+        assert_eq!(cart.chr_rom.len(), CHR_ROM_PAGE_SIZE);
+    }
+
+    #[test]
+    fn test_trainer_and_battery() {
+        let cart = Cartridge::from_bytes(&test_rom(1, 0, 0b0110, 0x00)).unwrap();
+        assert!(cart.battery);
+        assert_eq!(cart.trainer.as_deref().map(<[u8]>::len), Some(TRAINER_LEN));
+        assert_eq!(cart.prg_rom[0], 0);
+        assert!(cart.chr_rom.is_empty());
+    }
+
+    #[test]
+    fn test_nes2_header() {
+        let mut rom = test_rom(1, 1, 0x10, 0x08);
+        rom[8] = 0x21; // submapper 2, mapper bits 8-11 = 1
+        rom[12] = 0x01;
+        let cart = Cartridge::from_bytes(&rom).unwrap();
+
+        assert_eq!(cart.format, RomFormat::Nes20);
+        assert_eq!(cart.mapper, 0x101);
+        assert_eq!(cart.submapper, 2);
+        assert_eq!(cart.region, Region::Pal);
+    }
+
+    #[test]
+    fn test_file_name_only_overrides_ines() {
+        let mut nes2 = test_rom(1, 1, 0x00, 0x08);
+        nes2[12] = 0x03;
+        let mut cart = Cartridge::from_bytes(&nes2).unwrap();
+        cart.guess_region("Game (USA).nes");
+        assert_eq!(cart.region, Region::Dendy);
+
+        let mut cart = Cartridge::from_bytes(&test_rom(1, 1, 0x00, 0x00)).unwrap();
+        cart.guess_region("Game.nes");
+        assert_eq!(cart.region, Region::Ntsc);
+        cart.guess_region("Game (Europe).nes");
+        assert_eq!(cart.region, Region::Pal);
+    }
+
+    #[test]
+    fn test_vs_headers() {
+        let cart = Cartridge::from_bytes(&test_rom(2, 2, 0x30, 0x61)).unwrap();
+        assert_eq!(cart.mapper, 99);
+        assert_eq!(cart.vs, Some(VsCabinet::default()));
+
+        // NES 2.0 says which PPU
+        let mut rom = test_rom(2, 2, 0x00, 0x09);
+        rom[13] = 0x05;
+        let cart = Cartridge::from_bytes(&rom).unwrap();
+        assert_eq!(cart.vs.map(|cabinet| cabinet.ppu), Some(VsPpu::Rp2c04(4)));
+        assert!(cart.into_mapper().is_ok());
+
+        rom[13] = 0x50;
+        let dual = Cartridge::from_bytes(&rom).unwrap();
+        assert_eq!(
+            dual.into_mapper().err(),
+            Some(CartridgeError::UnsupportedVs(VsHardware::DualSystem))
+        );
+        let plain = Cartridge::from_bytes(&test_rom(2, 2, 0x00, 0x00)).unwrap();
+        assert_eq!(plain.vs, None);
+    }
+
+    #[test]
+    fn test_crc32_ignores_header() {
+        let mut cart = Cartridge::from_bytes(&test_rom(1, 0, 0, 0)).unwrap();
+        cart.prg_rom = Rom::from_static(b"12345");
+        cart.chr_rom = Rom::from_static(b"6789");
+        assert_eq!(cart.crc32(), 0xCBF43926);
+
+        let plain = Cartridge::from_bytes(&test_rom(1, 1, 0x00, 0x00)).unwrap();
+        let battery = Cartridge::from_bytes(&test_rom(1, 1, 0x06, 0x00)).unwrap();
+        assert_eq!(plain.crc32(), battery.crc32());
+    }
+
+    #[test]
+    fn test_shares_the_image() {
+        let image = Rom::from(test_rom(2, 1, 0b0100, 0x00));
+        let cart = Cartridge::from_rom(image.clone()).unwrap();
+        assert!(cart.prg_rom.shares_store(&image));
+        assert!(cart.chr_rom.shares_store(&image));
+        assert_eq!(
+            *cart.prg_rom,
+            image[HEADER_LEN + TRAINER_LEN..][..2 * PRG_ROM_PAGE_SIZE]
+        );
+
+        let clone = cart.clone();
+        assert!(clone.prg_rom.shares_store(&image));
+        assert_eq!(Cartridge::from_bytes(&image).unwrap(), cart);
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(
+            Cartridge::from_bytes(b"PK\x03\x04"),
+            Err(CartridgeError::BadMagic)
+        );
+        let rom = test_rom(2, 1, 0, 0);
+        assert_eq!(
+            Cartridge::from_bytes(&rom[..rom.len() - 1]),
+            Err(CartridgeError::Truncated {
+                expected: rom.len(),
+                actual: rom.len() - 1
+            })
+        );
+        let cart = Cartridge::from_bytes(&test_rom(1, 1, 0xF0, 0xF0)).unwrap();
+        assert_eq!(
+            cart.into_mapper().err(),
+            Some(CartridgeError::UnsupportedMapper(0xFF))
+        );
+    }
+}
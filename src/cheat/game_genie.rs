@@ -0,0 +1,183 @@
+// Game Genie codes. The Genie sat between the cartridge and the console
+// and answered reads of one ROM address with a value of its own; an
+// 8-letter code only does so while the cartridge's byte there is the
+// compare value, so a game that switches banks has the code hit the bank
+// it was made for and no other.
+//
+// Each letter is four bits. The bits are shuffled across the address,
+// value and compare as the table in the Genie's manual has them; the
+// high bit of the third letter says whether the code is 8 letters long.
+
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+use core::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+type Address = u16;
+type Value = u8;
+
+const LETTERS: &[u8; 16] = b"APZLGITYEOXUKSVN";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GameGenieError {
+    Length(usize),
+    Letter(char),
+}
+
+impl fmt::Display for GameGenieError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GameGenieError::Length(len) => {
+                write!(f, "a Game Genie code is 6 or 8 letters, not {}", len)
+            }
+            GameGenieError::Letter(letter) => {
+                write!(f, "'{}' isn't a Game Genie letter", letter)
+            }
+        }
+    }
+}
+
+impl core::error::Error for GameGenieError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct GameGenie {
+    // $8000-$FFFF
+    pub addr: Address,
+    pub value: Value,
+    pub compare: Option<Value>,
+}
+
+impl GameGenie {
+    // What the CPU reads at `addr` with the code in, given the cartridge's
+    // byte there.
+    pub fn apply(&self, addr: Address, value: Value) -> Option<Value> {
+        (addr == self.addr && self.compare.is_none_or(|compare| compare == value))
+            .then_some(self.value)
+    }
+}
+
+impl FromStr for GameGenie {
+    type Err = GameGenieError;
+
+    // Case and dashes don't matter: "sxiopo" and "SXI-OPO" are "SXIOPO".
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let mut n = Vec::with_capacity(8);
+        for letter in text.chars().filter(|&c| c != '-') {
+            let upper = letter.to_ascii_uppercase();
+            let digit = LETTERS
+                .iter()
+                .position(|&l| l as char == upper)
+                .ok_or(GameGenieError::Letter(letter))?;
+            n.push(digit as u16);
+        }
+        if n.len() != 6 && n.len() != 8 {
+            return Err(GameGenieError::Length(n.len()));
+        }
+
+        let addr = 0x8000
+            | ((n[3] & 7) << 12)
+            | ((n[5] & 7) << 8)
+            | ((n[4] & 8) << 8)
+            | ((n[2] & 7) << 4)
+            | ((n[1] & 8) << 4)
+            | (n[4] & 7)
+            | (n[3] & 8);
+        let value = ((n[1] & 7) << 4) | ((n[0] & 8) << 4) | (n[0] & 7);
+        let (value, compare) = if n.len() == 6 {
+            (value | (n[5] & 8), None)
+        } else {
+            let compare = ((n[7] & 7) << 4) | ((n[6] & 8) << 4) | (n[6] & 7) | (n[5] & 8);
+            (value | (n[7] & 8), Some(compare as Value))
+        };
+        Ok(GameGenie {
+            addr,
+            value: value as Value,
+            compare,
+        })
+    }
+}
+
+impl TryFrom<String> for GameGenie {
+    type Error = GameGenieError;
+
+    fn try_from(text: String) -> Result<Self, Self::Error> {
+        text.parse()
+    }
+}
+
+impl From<GameGenie> for String {
+    fn from(code: GameGenie) -> Self {
+        code.to_string()
+    }
+}
+
+// The code's letters, in capitals.
+impl fmt::Display for GameGenie {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (addr, value) = (self.addr, self.value as u16);
+        let long = self.compare.is_some() as u16;
+        let mut n = vec![
+            ((value >> 4) & 8) | (value & 7),
+            ((addr >> 4) & 8) | ((value >> 4) & 7),
+            (long << 3) | ((addr >> 4) & 7),
+            (addr & 8) | ((addr >> 12) & 7),
+            ((addr >> 8) & 8) | (addr & 7),
+        ];
+        match self.compare {
+            None => n.push((value & 8) | ((addr >> 8) & 7)),
+            Some(compare) => {
+                let compare = compare as u16;
+                n.push((compare & 8) | ((addr >> 8) & 7));
+                n.push(((compare >> 4) & 8) | (compare & 7));
+                n.push((value & 8) | ((compare >> 4) & 7));
+            }
+        }
+        for digit in n {
+            write!(f, "{}", LETTERS[digit as usize] as char)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_decodes_and_encodes() {
+        // Super Mario Bros.: infinite lives
+        let code: GameGenie = "SXIOPO".parse().unwrap();
+        assert_eq!(
+            code,
+            GameGenie {
+                addr: 0x91D9,
+                value: 0xAD,
+                compare: None,
+            }
+        );
+        assert_eq!(code.to_string(), "SXIOPO");
+        assert_eq!("sxi-opo".parse::<GameGenie>(), Ok(code));
+
+        let code = GameGenie {
+            addr: 0xD1DD,
+            value: 0x14,
+            compare: Some(0xAE),
+        };
+        let text = code.to_string();
+        assert_eq!(text.len(), 8);
+        assert_eq!(text.parse::<GameGenie>(), Ok(code));
+        assert_eq!(code.apply(0xD1DD, 0xAE), Some(0x14));
+        assert_eq!(code.apply(0xD1DD, 0x00), None);
+        assert_eq!(code.apply(0xD1DE, 0xAE), None);
+
+        assert_eq!("SXIOP".parse::<GameGenie>(), Err(GameGenieError::Length(5)));
+        assert_eq!(
+            "SXIOPB".parse::<GameGenie>(),
+            Err(GameGenieError::Letter('B'))
+        );
+    }
+}
@@ -0,0 +1,431 @@
+// Cheats and the tools for finding them.
+//
+// A game's cheats are kept in a file of their own in the cheats
+// directory, {game}.toml, which Session reads when the game starts and
+// writes when the list changes. Each one is a Game Genie or Pro Action
+// Rocky code, laid over cartridge reads, or a raw RAM value, either
+// written once when the cheat is switched on ("poke") or put back at the
+// start of every frame ("freeze"). Cheats with the same group go on and
+// off together:
+//
+//     [[cheat]]
+//     game_genie = "SXIOPO"
+//     name = "Infinite lives"
+//
+//     [[cheat]]
+//     freeze = "075A:09"
+//     name = "Nine lives"
+//     group = "Lives"
+//     enabled = false
+//
+// The bus asks Cheats about every cartridge read the CPU makes and once a
+// frame for RAM to write, so what's in effect is kept apart in short lists
+// of its own.
+
+pub mod game_genie;
+pub mod raw;
+pub mod rocky;
+#[cfg(feature = "std")]
+pub mod search;
+
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt;
+use core::str::FromStr;
+#[cfg(feature = "std")]
+use std::fs;
+#[cfg(feature = "std")]
+use std::io;
+#[cfg(feature = "std")]
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use game_genie::GameGenie;
+use raw::RamPatch;
+use rocky::ProActionRocky;
+
+type Address = u16;
+type Value = u8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Code {
+    GameGenie(GameGenie),
+    #[serde(rename = "rocky")]
+    ProActionRocky(ProActionRocky),
+    Poke(RamPatch),
+    Freeze(RamPatch),
+}
+
+impl Code {
+    // What a cartridge read goes through, for the ROM codes.
+    fn prg_patch(self) -> Option<GameGenie> {
+        match self {
+            Code::GameGenie(code) => Some(code),
+            Code::ProActionRocky(code) => Some(code.into()),
+            Code::Poke(_) | Code::Freeze(_) => None,
+        }
+    }
+}
+
+impl fmt::Display for Code {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Code::GameGenie(code) => write!(f, "{}", code),
+            Code::ProActionRocky(code) => write!(f, "{}", code),
+            Code::Poke(patch) => write!(f, "{} once", patch),
+            Code::Freeze(patch) => write!(f, "{}", patch),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CodeError(pub String);
+
+impl fmt::Display for CodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "'{}' isn't a Game Genie code, a Pro Action Rocky code or a RAM address:value",
+            self.0
+        )
+    }
+}
+
+impl core::error::Error for CodeError {}
+
+impl FromStr for Code {
+    type Err = CodeError;
+
+    // Told apart by their look: "0075:09" freezes RAM, eight hex digits
+    // are Pro Action Rocky and letters are Game Genie. Pokes are only
+    // written out in the file.
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let err = || CodeError(text.to_string());
+        if text.contains(':') {
+            text.parse().map(Code::Freeze).map_err(|_| err())
+        } else if text.len() == 8 && text.chars().all(|c| c.is_ascii_hexdigit()) {
+            text.parse().map(Code::ProActionRocky).map_err(|_| err())
+        } else {
+            text.parse().map(Code::GameGenie).map_err(|_| err())
+        }
+    }
+}
+
+impl From<GameGenie> for Code {
+    fn from(code: GameGenie) -> Self {
+        Code::GameGenie(code)
+    }
+}
+
+impl From<ProActionRocky> for Code {
+    fn from(code: ProActionRocky) -> Self {
+        Code::ProActionRocky(code)
+    }
+}
+
+fn enabled() -> bool {
+    true
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Cheat {
+    #[serde(flatten)]
+    pub code: Code,
+    #[serde(default)]
+    pub name: String,
+    // empty for none
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub group: String,
+    #[serde(default = "enabled")]
+    pub enabled: bool,
+}
+
+impl Cheat {
+    // Switched on, with no name or group.
+    pub fn new(code: impl Into<Code>) -> Self {
+        Cheat {
+            code: code.into(),
+            name: String::new(),
+            group: String::new(),
+            enabled: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Cheats {
+    #[serde(default, rename = "cheat")]
+    cheats: Vec<Cheat>,
+    // everything off at once, keeping which cheats were on
+    #[serde(skip)]
+    suspended: bool,
+    // the ROM codes in effect
+    #[serde(skip)]
+    active: Vec<GameGenie>,
+    #[serde(skip)]
+    freezes: Vec<RamPatch>,
+    // switched on since the last frame
+    #[serde(skip)]
+    pokes: Vec<RamPatch>,
+}
+
+impl Cheats {
+    // No file yet is no cheats.
+    #[cfg(feature = "std")]
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let mut cheats: Cheats = match fs::read_to_string(path) {
+            Ok(text) => toml::from_str(&text)
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Self::default(),
+            Err(err) => return Err(err),
+        };
+        for index in 0..cheats.cheats.len() {
+            cheats.switched_on(index);
+        }
+        cheats.update();
+        Ok(cheats)
+    }
+
+    #[cfg(feature = "std")]
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let text = toml::to_string(self).map_err(io::Error::other)?;
+        fs::write(path, text)
+    }
+
+    fn update(&mut self) {
+        self.active.clear();
+        self.freezes.clear();
+        if self.suspended {
+            return;
+        }
+        for cheat in self.cheats.iter().filter(|cheat| cheat.enabled) {
+            match cheat.code {
+                Code::Freeze(patch) => self.freezes.push(patch),
+                code => self.active.extend(code.prg_patch()),
+            }
+        }
+    }
+
+    // A poke is written when its cheat goes on.
+    fn switched_on(&mut self, index: usize) {
+        if let Cheat {
+            code: Code::Poke(patch),
+            enabled: true,
+            ..
+        } = self.cheats[index]
+        {
+            self.pokes.push(patch);
+        }
+    }
+
+    pub fn cheats(&self) -> &[Cheat] {
+        &self.cheats
+    }
+
+    // Adds the cheat; one with the same code already in the list takes
+    // its place, keeping the old name and group unless the new one has
+    // them. Returns its index.
+    pub fn add(&mut self, cheat: Cheat) -> usize {
+        let index = match self.cheats.iter().position(|old| old.code == cheat.code) {
+            Some(index) => {
+                let old = &mut self.cheats[index];
+                old.enabled = cheat.enabled;
+                if !cheat.name.is_empty() {
+                    old.name = cheat.name;
+                }
+                if !cheat.group.is_empty() {
+                    old.group = cheat.group;
+                }
+                index
+            }
+            None => {
+                self.cheats.push(cheat);
+                self.cheats.len() - 1
+            }
+        };
+        self.switched_on(index);
+        self.update();
+        index
+    }
+
+    // False if there's no such cheat.
+    pub fn set_enabled(&mut self, index: usize, enabled: bool) -> bool {
+        let Some(cheat) = self.cheats.get_mut(index) else {
+            return false;
+        };
+        let was = core::mem::replace(&mut cheat.enabled, enabled);
+        if !was {
+            self.switched_on(index);
+        }
+        self.update();
+        true
+    }
+
+    pub fn remove(&mut self, index: usize) -> Option<Cheat> {
+        (index < self.cheats.len()).then(|| {
+            let cheat = self.cheats.remove(index);
+            self.update();
+            cheat
+        })
+    }
+
+    // The groups cheats are in, in the order they first come up.
+    pub fn groups(&self) -> Vec<&str> {
+        let mut groups: Vec<&str> = Vec::new();
+        for cheat in &self.cheats {
+            if !cheat.group.is_empty() && !groups.contains(&cheat.group.as_str()) {
+                groups.push(&cheat.group);
+            }
+        }
+        groups
+    }
+
+    // Every cheat in the group on or off; returns how many there are.
+    pub fn set_group_enabled(&mut self, group: &str, enabled: bool) -> usize {
+        let indices: Vec<usize> = (0..self.cheats.len())
+            .filter(|&index| self.cheats[index].group == group)
+            .collect();
+        for &index in &indices {
+            let was = core::mem::replace(&mut self.cheats[index].enabled, enabled);
+            if !was {
+                self.switched_on(index);
+            }
+        }
+        self.update();
+        indices.len()
+    }
+
+    // Cheats from `other` whose code isn't in the list yet go on the end.
+    pub fn merge(&mut self, other: Cheats) {
+        for cheat in other.cheats {
+            if !self.cheats.iter().any(|mine| mine.code == cheat.code) {
+                self.cheats.push(cheat);
+            }
+        }
+        self.pokes.extend(other.pokes);
+        self.update();
+    }
+
+    pub fn is_suspended(&self) -> bool {
+        self.suspended
+    }
+
+    pub fn set_suspended(&mut self, suspended: bool) {
+        self.suspended = suspended;
+        self.update();
+    }
+
+    pub(crate) fn is_active(&self) -> bool {
+        !self.active.is_empty()
+    }
+
+    // What a CPU read of the cartridge at `addr` gives instead of `value`.
+    // The first code that matches wins.
+    pub(crate) fn patch_prg(&self, addr: Address, value: Value) -> Value {
+        self.active
+            .iter()
+            .find_map(|code| code.apply(addr, value))
+            .unwrap_or(value)
+    }
+
+    pub(crate) fn writes_ram(&self) -> bool {
+        !self.freezes.is_empty() || !self.pokes.is_empty()
+    }
+
+    // What to write to RAM as a frame starts: pokes switched on since the
+    // last one, then the freezes.
+    pub(crate) fn take_ram_writes(&mut self) -> Vec<RamPatch> {
+        let mut writes = core::mem::take(&mut self.pokes);
+        if self.suspended {
+            writes.clear();
+        }
+        writes.extend_from_slice(&self.freezes);
+        writes
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_enabled_codes_patch_reads() {
+        let lives: GameGenie = "SXIOPO".parse().unwrap();
+        let mut cheats = Cheats::default();
+        let cheat = Cheat {
+            name: "Infinite lives".to_string(),
+            ..Cheat::new(lives)
+        };
+        assert_eq!(cheats.add(cheat), 0);
+        assert_eq!(cheats.patch_prg(0x91D9, 0xCE), 0xAD);
+        assert_eq!(cheats.patch_prg(0x91DA, 0xCE), 0xCE);
+
+        cheats.set_suspended(true);
+        assert!(!cheats.is_active());
+        cheats.set_suspended(false);
+        assert!(cheats.set_enabled(0, false));
+        assert_eq!(cheats.patch_prg(0x91D9, 0xCE), 0xCE);
+        assert_eq!(cheats.add(Cheat::new(lives)), 0);
+        assert_eq!(cheats.cheats()[0].name, "Infinite lives");
+
+        let rocky = ProActionRocky {
+            addr: 0xC000,
+            value: 0xEA,
+            compare: 0x20,
+        };
+        cheats.add(Cheat::new(rocky));
+        assert_eq!(cheats.patch_prg(0xC000, 0x20), 0xEA);
+        assert_eq!(cheats.patch_prg(0xC000, 0x4C), 0x4C);
+
+        let text = toml::to_string(&cheats).unwrap();
+        assert!(text.contains("game_genie = \"SXIOPO\""), "{}", text);
+        assert!(text.contains(&format!("rocky = \"{}\"", rocky)), "{}", text);
+        let mut loaded: Cheats = toml::from_str(&text).unwrap();
+        loaded.update();
+        assert_eq!(loaded, cheats);
+    }
+
+    #[test]
+    fn test_ram_cheats_and_groups() {
+        let mut cheats = Cheats::default();
+        let patch = |text: &str| text.parse::<RamPatch>().unwrap();
+        cheats.add(Cheat {
+            group: "Lives".to_string(),
+            ..Cheat::new(Code::Freeze(patch("075A:09")))
+        });
+        cheats.add(Cheat {
+            group: "Lives".to_string(),
+            ..Cheat::new(Code::Poke(patch("$6000:$01")))
+        });
+        cheats.add(Cheat::new(Code::Poke(patch("10:ff"))));
+        assert_eq!(cheats.groups(), ["Lives"]);
+        assert_eq!(
+            cheats.take_ram_writes(),
+            [patch("6000:01"), patch("0010:FF"), patch("075A:09")]
+        );
+        // pokes were written; the freeze goes on
+        assert_eq!(cheats.take_ram_writes(), [patch("075A:09")]);
+
+        assert_eq!(cheats.set_group_enabled("Lives", false), 2);
+        assert!(!cheats.writes_ram());
+        cheats.set_group_enabled("Lives", true);
+        assert_eq!(
+            cheats.take_ram_writes(),
+            [patch("6000:01"), patch("075A:09")]
+        );
+
+        assert_eq!("075A:09".parse(), Ok(Code::Freeze(patch("075A:09"))));
+        assert!("8000:09".parse::<Code>().is_err());
+        assert!("075A:100".parse::<Code>().is_err());
+        assert!(matches!("SXIOPO".parse::<Code>(), Ok(Code::GameGenie(_))));
+        assert!(matches!(
+            "1234ABCD".parse::<Code>(),
+            Ok(Code::ProActionRocky(_))
+        ));
+    }
+}
@@ -0,0 +1,83 @@
+// Raw cheats: a value for a RAM address, as the RAM search turns one up,
+// written "0075:09" in hex. Only RAM can take one, the internal 2K with
+// its mirrors and work RAM at $6000-$7FFF; anywhere else a write would
+// land on a register.
+
+use alloc::string::{String, ToString};
+use core::fmt;
+use core::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+type Address = u16;
+type Value = u8;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RawError(pub String);
+
+impl fmt::Display for RawError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "'{}' isn't a RAM address and value, like 0075:09",
+            self.0
+        )
+    }
+}
+
+impl core::error::Error for RawError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct RamPatch {
+    pub addr: Address,
+    pub value: Value,
+}
+
+impl RamPatch {
+    pub fn is_ram(addr: Address) -> bool {
+        matches!(addr, 0x0000..=0x1FFF | 0x6000..=0x7FFF)
+    }
+}
+
+impl FromStr for RamPatch {
+    type Err = RawError;
+
+    // "0075:09", "$0075:$09" or "75:9".
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let err = || RawError(text.to_string());
+        let hex = |part: &str| {
+            let part = part.trim();
+            u16::from_str_radix(part.strip_prefix('$').unwrap_or(part), 16).map_err(|_| err())
+        };
+        let (addr, value) = text.split_once(':').ok_or_else(err)?;
+        let (addr, value) = (hex(addr)?, hex(value)?);
+        if !Self::is_ram(addr) || value > 0xFF {
+            return Err(err());
+        }
+        Ok(RamPatch {
+            addr,
+            value: value as Value,
+        })
+    }
+}
+
+impl TryFrom<String> for RamPatch {
+    type Error = RawError;
+
+    fn try_from(text: String) -> Result<Self, Self::Error> {
+        text.parse()
+    }
+}
+
+impl From<RamPatch> for String {
+    fn from(patch: RamPatch) -> Self {
+        patch.to_string()
+    }
+}
+
+impl fmt::Display for RamPatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:04X}:{:02X}", self.addr, self.value)
+    }
+}
@@ -0,0 +1,157 @@
+// Pro Action Rocky codes: eight hex digits that, once unscrambled, say
+// the same as an 8-letter Game Genie code, a ROM address with a value and
+// a compare. The scrambling runs the code's bits against a key that
+// changes with every 1 it sees, most significant bit first, and puts them
+// back in the order of SHIFTS; bit 15 of the result is left out, as the
+// address is always in $8000-$FFFF.
+
+use alloc::string::{String, ToString};
+use core::fmt;
+use core::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use super::game_genie::GameGenie;
+
+type Address = u16;
+type Value = u8;
+
+const KEY: u32 = 0xFCBD_D274;
+const KEY_FEEDBACK: u32 = 0xB830_9722;
+// where each bit of the code ends up, for the last bit of the code first
+const SHIFTS: [u8; 31] = [
+    3, 13, 14, 1, 6, 9, 5, 0, 12, 7, 2, 8, 10, 11, 4, 19, 21, 23, 22, 20, 17, 16, 18, 29, 31, 24,
+    26, 25, 30, 27, 28,
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RockyError(pub String);
+
+impl fmt::Display for RockyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "'{}' isn't a Pro Action Rocky code, which is 8 hex digits",
+            self.0
+        )
+    }
+}
+
+impl core::error::Error for RockyError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct ProActionRocky {
+    // $8000-$FFFF
+    pub addr: Address,
+    pub value: Value,
+    pub compare: Value,
+}
+
+// The Genie code that does the same.
+impl From<ProActionRocky> for GameGenie {
+    fn from(code: ProActionRocky) -> Self {
+        GameGenie {
+            addr: code.addr,
+            value: code.value,
+            compare: Some(code.compare),
+        }
+    }
+}
+
+impl FromStr for ProActionRocky {
+    type Err = RockyError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let digits = text.trim();
+        if digits.len() != 8 || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(RockyError(text.to_string()));
+        }
+        let mut input =
+            u32::from_str_radix(digits, 16).map_err(|_| RockyError(text.to_string()))?;
+        let mut key = KEY;
+        let mut output = 0u32;
+        for &shift in SHIFTS.iter().rev() {
+            if (input ^ key) & 0x8000_0000 != 0 {
+                output |= 1 << shift;
+                key ^= KEY_FEEDBACK;
+            }
+            input <<= 1;
+            key <<= 1;
+        }
+        Ok(ProActionRocky {
+            addr: (output & 0x7FFF) as Address | 0x8000,
+            value: (output >> 24) as Value,
+            compare: (output >> 16) as Value,
+        })
+    }
+}
+
+impl TryFrom<String> for ProActionRocky {
+    type Error = RockyError;
+
+    fn try_from(text: String) -> Result<Self, Self::Error> {
+        text.parse()
+    }
+}
+
+impl From<ProActionRocky> for String {
+    fn from(code: ProActionRocky) -> Self {
+        code.to_string()
+    }
+}
+
+// The scrambling run the other way: each bit of the code is whatever
+// makes the key give the wanted bit.
+impl fmt::Display for ProActionRocky {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let output =
+            (self.value as u32) << 24 | (self.compare as u32) << 16 | (self.addr & 0x7FFF) as u32;
+        let mut key = KEY;
+        let mut input = 0u32;
+        for (bit, &shift) in SHIFTS.iter().rev().enumerate() {
+            let wanted = (output >> shift) & 1 != 0;
+            let top = key & 0x8000_0000 != 0;
+            if wanted != top {
+                input |= 0x8000_0000 >> bit;
+            }
+            if wanted {
+                key ^= KEY_FEEDBACK;
+            }
+            key <<= 1;
+        }
+        write!(f, "{:08X}", input)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_unscrambles_and_scrambles() {
+        let code = ProActionRocky {
+            addr: 0x91D9,
+            value: 0xAD,
+            compare: 0xCE,
+        };
+        let text = code.to_string();
+        assert_eq!(text.len(), 8);
+        assert_eq!(text.parse::<ProActionRocky>(), Ok(code));
+        assert_eq!(text.to_lowercase().parse::<ProActionRocky>(), Ok(code));
+        assert_eq!(
+            GameGenie::from(code),
+            GameGenie {
+                addr: 0x91D9,
+                value: 0xAD,
+                compare: Some(0xCE),
+            }
+        );
+
+        // every code means something
+        let any: ProActionRocky = "00000000".parse().unwrap();
+        assert_eq!(any.to_string(), "00000000");
+        assert!("0000000".parse::<ProActionRocky>().is_err());
+        assert!("0000000G".parse::<ProActionRocky>().is_err());
+    }
+}
@@ -0,0 +1,214 @@
+// RAM search, the way cheat hunters find where a game keeps lives or
+// health: start with every address, play a little, keep the ones that
+// changed the way the number on screen did, and repeat until a handful
+// are left. Every filter also takes a fresh snapshot, so "increased"
+// always means since the last step.
+//
+// The internal 2K and the cartridge's work RAM are searched; ROM can't
+// hold anything worth finding. 16-bit values are little-endian, as 6502
+// code keeps them, and don't run across from one RAM into the other.
+
+use crate::bus::Bus;
+use crate::emulator::Emulator;
+
+type Address = u16;
+
+const RAM_SIZE: usize = 0x800;
+const WORK_RAM: Address = 0x6000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValueSize {
+    Byte,
+    Word,
+}
+
+impl ValueSize {
+    fn bits(self) -> u32 {
+        match self {
+            ValueSize::Byte => 8,
+            ValueSize::Word => 16,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compare {
+    Equal(i64),
+    NotEqual(i64),
+    Increased,
+    Decreased,
+    Changed,
+    Unchanged,
+    // by exactly this much, wrapping around as the value would; negative
+    // for down
+    ChangedBy(i64),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Candidate {
+    pub addr: Address,
+    pub previous: i64,
+    pub current: i64,
+}
+
+#[derive(Debug, Clone)]
+pub struct RamSearch {
+    size: ValueSize,
+    signed: bool,
+    // offsets into the snapshot; see address()
+    candidates: Vec<usize>,
+    snapshot: Vec<u8>,
+}
+
+// The internal RAM, then work RAM.
+fn searchable(bus: &Bus) -> Vec<u8> {
+    let mut memory = bus.ram().to_vec();
+    memory.extend_from_slice(bus.cartridge_ram());
+    memory
+}
+
+fn address(offset: usize) -> Address {
+    if offset < RAM_SIZE {
+        offset as Address
+    } else {
+        WORK_RAM + (offset - RAM_SIZE) as Address
+    }
+}
+
+impl RamSearch {
+    // Every address is a candidate to begin with.
+    pub fn new(emulator: &Emulator, size: ValueSize, signed: bool) -> Self {
+        let snapshot = searchable(&emulator.cpu().bus);
+        let mut search = RamSearch {
+            size,
+            signed,
+            candidates: Vec::new(),
+            snapshot,
+        };
+        search.candidates = (0..search.snapshot.len())
+            .filter(|&offset| search.value(&search.snapshot, offset).is_some())
+            .collect();
+        search
+    }
+
+    pub fn size(&self) -> ValueSize {
+        self.size
+    }
+
+    pub fn signed(&self) -> bool {
+        self.signed
+    }
+
+    fn value(&self, memory: &[u8], offset: usize) -> Option<i64> {
+        let lo = *memory.get(offset)?;
+        match (self.size, self.signed) {
+            (ValueSize::Byte, false) => Some(lo as i64),
+            (ValueSize::Byte, true) => Some(lo as i8 as i64),
+            (ValueSize::Word, signed) => {
+                // not across the end of internal RAM
+                if offset + 1 == RAM_SIZE {
+                    return None;
+                }
+                let word = u16::from_le_bytes([lo, *memory.get(offset + 1)?]);
+                Some(if signed {
+                    word as i16 as i64
+                } else {
+                    word as i64
+                })
+            }
+        }
+    }
+
+    fn matches(&self, compare: Compare, previous: i64, current: i64) -> bool {
+        let mask = (1i64 << self.size.bits()) - 1;
+        match compare {
+            Compare::Equal(value) => current == value,
+            Compare::NotEqual(value) => current != value,
+            Compare::Increased => current > previous,
+            Compare::Decreased => current < previous,
+            Compare::Changed => current != previous,
+            Compare::Unchanged => current == previous,
+            Compare::ChangedBy(by) => (current - previous) & mask == by & mask,
+        }
+    }
+
+    // Keeps the candidates that match and snapshots for the next step;
+    // returns how many are left.
+    pub fn filter(&mut self, emulator: &Emulator, compare: Compare) -> usize {
+        let memory = searchable(&emulator.cpu().bus);
+        let candidates = std::mem::take(&mut self.candidates);
+        self.candidates = candidates
+            .into_iter()
+            .filter(|&offset| {
+                match (
+                    self.value(&self.snapshot, offset),
+                    self.value(&memory, offset),
+                ) {
+                    (Some(previous), Some(current)) => self.matches(compare, previous, current),
+                    _ => false,
+                }
+            })
+            .collect();
+        self.snapshot = memory;
+        self.candidates.len()
+    }
+
+    // A new baseline without throwing anything out.
+    pub fn snapshot(&mut self, emulator: &Emulator) {
+        self.snapshot = searchable(&emulator.cpu().bus);
+    }
+
+    pub fn len(&self) -> usize {
+        self.candidates.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.candidates.is_empty()
+    }
+
+    // What's left, with the value at the last snapshot and now.
+    pub fn candidates(&self, emulator: &Emulator) -> Vec<Candidate> {
+        let memory = searchable(&emulator.cpu().bus);
+        self.candidates
+            .iter()
+            .filter_map(|&offset| {
+                Some(Candidate {
+                    addr: address(offset),
+                    previous: self.value(&self.snapshot, offset)?,
+                    current: self.value(&memory, offset)?,
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::emulator::test::counting_rom;
+
+    #[test]
+    fn test_narrows_down_to_the_frame_counter() {
+        let mut emulator = Emulator::from_rom(&counting_rom()).unwrap();
+        let mut search = RamSearch::new(&emulator, ValueSize::Byte, false);
+        // 2K of RAM and 8K of work RAM
+        assert_eq!(search.len(), 0x2800);
+
+        emulator.run_frames(3);
+        search.filter(&emulator, Compare::Increased);
+        emulator.run_frame();
+        search.filter(&emulator, Compare::ChangedBy(1));
+        emulator.run_frame();
+        search.filter(&emulator, Compare::ChangedBy(1));
+        let found = search.candidates(&emulator);
+        assert_eq!(found.len(), 1, "{:?}", found);
+        assert_eq!(found[0].addr, 0x0000);
+        assert_eq!(found[0].current, found[0].previous);
+
+        emulator.run_frame();
+        assert_eq!(
+            search.filter(&emulator, Compare::Equal(found[0].current + 1)),
+            1
+        );
+    }
+}
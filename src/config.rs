@@ -0,0 +1,622 @@
+// User settings from config.toml in the platform config directory
+// (~/.config/nes on Linux, ~/Library/Application Support/nes on macOS,
+// %APPDATA%\nes on Windows). Every key is optional:
+//
+//     [video]
+//     scale = 4
+//     palette = "palettes/smooth.pal"
+//     shader = "crt"
+//     sprite_limit = true
+//     pacing = "vsync"      # or "timer", "audio"
+//     aspect = "ntsc"       # 8:7 pixels, or "square"
+//     integer_scaling = true
+//     letterbox = true      # false stretches to the window
+//     fullscreen = false
+//     fullscreen_mode = "borderless"   # or "exclusive"
+//     screenshot = "raw"    # or "filtered", as the window shows it
+//     clip_seconds = 10     # what the save_clip hotkey saves as a GIF
+//     osd = true            # messages like "State saved" over the picture
+//     stats = "osd"         # frame rate, speed and audio queue; or "title"
+//     input_display = true  # the buttons each frame ran with, bottom right
+//
+//     [emulation]
+//     speed = 1.0
+//     fast_forward = 4.0
+//     rewind_seconds = 60   # how far back holding rewind goes; 0 is off
+//     extra_scanlines = 0   # overclocking, for games that slow down
+//     ram_init = "zeros"    # RAM at power-on; or "ones", "random"
+//     seed = 0              # for everything random, RAM included
+//     hot_reload = "keep"   # reload the ROM when it's rebuilt; or "reset"
+//     battery = "mapped"    # .sav files memory-mapped; or "buffered"
+//
+//     [audio]
+//     sample_rate = 44100
+//     latency = 1024        # samples kept queued; more if it crackles
+//     buffer = 512          # the device's period in samples; its own otherwise
+//
+//     [paths]
+//     states = "states"
+//     saves = "saves"       # battery RAM, as {game}.sav
+//     cheats = "cheats"     # codes and RAM cheats, as {game}.toml
+//     screenshots = "/home/me/Pictures/nes"
+//     recordings = "/home/me/Videos/nes"
+//     ffmpeg = "/opt/ffmpeg/bin/ffmpeg"   # found on PATH otherwise
+//
+//     [keys.player1]
+//     a = ["X"]
+//     b = ["Z"]
+//
+//     [hotkeys]
+//     pause = ["P"]
+//
+//     [emulation]
+//     region = "pal"        # or "ntsc", "dendy"; the ROM decides otherwise
+//
+//     [region.pal.video]
+//     palette = "palettes/pal.pal"
+//
+//     [game."1A2B3C4D".video]
+//     sprite_limit = false
+//
+//     [game."1A2B3C4D".vs]  # a VS. System game
+//     ppu = "2c04-0003"     # when the header doesn't say
+//     dip_switches = 0x41   # switch 1 in bit 0
+//
+// A [game."CRC32"] table has the same layout as the file and is laid over
+// it for the game whose PRG and CHR ROM have that checksum, as `nes info`
+// prints it. [region.ntsc], [region.pal] and [region.dendy] tables are
+// laid over it the same way for games that run on that console, beneath
+// any [game] table. [keys] and [hotkeys] tables replace the default bindings
+// rather than adding to them. Relative palette and directory paths are
+// taken from the config file's directory.
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use crate::audio::Latency;
+use crate::frontend::battery::BatteryBacking;
+use crate::frontend::hotkeys::Hotkeys;
+use crate::frontend::stats::StatsDisplay;
+use crate::frontend::watch::ReloadMode;
+use crate::frontend::{FullscreenMode, Options, Pacing, ScreenshotStage};
+use crate::input::keymap::KeyBindings;
+use crate::region::Region;
+use crate::render::viewport::{PixelAspect, Scaling};
+use crate::rng::RamInit;
+use crate::vs::VsPpu;
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(io::Error),
+    Parse(toml::de::Error),
+    // a [game] table whose name isn't a CRC32, or whose contents don't fit
+    Game(String, Option<toml::de::Error>),
+    // the same for a [region] table and its console's name
+    Region(String, Option<toml::de::Error>),
+    // right type, unusable value
+    Value(&'static str),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigError::Io(err) => write!(f, "{}", err),
+            ConfigError::Parse(err) => write!(f, "{}", err),
+            ConfigError::Game(name, None) => {
+                write!(f, "[game.\"{}\"]: expected an 8 digit hex CRC32", name)
+            }
+            ConfigError::Game(name, Some(err)) => write!(f, "[game.\"{}\"]: {}", name, err),
+            ConfigError::Region(name, None) => {
+                write!(f, "[region.{}]: expected ntsc, pal or dendy", name)
+            }
+            ConfigError::Region(name, Some(err)) => write!(f, "[region.{}]: {}", name, err),
+            ConfigError::Value(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl From<io::Error> for ConfigError {
+    fn from(err: io::Error) -> Self {
+        ConfigError::Io(err)
+    }
+}
+
+impl From<toml::de::Error> for ConfigError {
+    fn from(err: toml::de::Error) -> Self {
+        ConfigError::Parse(err)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct VideoConfig {
+    pub scale: Option<u32>,
+    pub palette: Option<PathBuf>,
+    pub shader: Option<String>,
+    pub sprite_limit: bool,
+    pub pacing: Option<Pacing>,
+    pub aspect: Option<PixelAspect>,
+    pub integer_scaling: Option<bool>,
+    pub letterbox: Option<bool>,
+    pub fullscreen: Option<bool>,
+    pub fullscreen_mode: Option<FullscreenMode>,
+    pub screenshot: Option<ScreenshotStage>,
+    pub clip_seconds: Option<f64>,
+    pub osd: Option<bool>,
+    pub stats: Option<StatsDisplay>,
+    pub input_display: Option<bool>,
+}
+
+impl Default for VideoConfig {
+    fn default() -> Self {
+        VideoConfig {
+            scale: None,
+            palette: None,
+            shader: None,
+            sprite_limit: true,
+            pacing: None,
+            aspect: None,
+            integer_scaling: None,
+            letterbox: None,
+            fullscreen: None,
+            fullscreen_mode: None,
+            screenshot: None,
+            clip_seconds: None,
+            osd: None,
+            stats: None,
+            input_display: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct EmulationConfig {
+    pub speed: Option<f64>,
+    pub fast_forward: Option<f64>,
+    pub rewind_seconds: Option<f64>,
+    pub region: Option<Region>,
+    pub extra_scanlines: Option<u16>,
+    pub ram_init: Option<RamInit>,
+    pub seed: Option<u64>,
+    pub hot_reload: Option<ReloadMode>,
+    pub battery: Option<BatteryBacking>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AudioConfig {
+    pub sample_rate: Option<u32>,
+    // samples to keep queued
+    pub latency: Option<usize>,
+    // the device's period, in samples
+    pub buffer: Option<u32>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PathsConfig {
+    pub states: Option<PathBuf>,
+    pub saves: Option<PathBuf>,
+    pub cheats: Option<PathBuf>,
+    pub screenshots: Option<PathBuf>,
+    pub recordings: Option<PathBuf>,
+    pub ffmpeg: Option<PathBuf>,
+}
+
+impl PathsConfig {
+    pub fn states_dir(&self) -> PathBuf {
+        self.states
+            .clone()
+            .unwrap_or_else(|| data_dir().join("states"))
+    }
+
+    pub fn saves_dir(&self) -> PathBuf {
+        self.saves
+            .clone()
+            .unwrap_or_else(|| data_dir().join("saves"))
+    }
+
+    pub fn cheats_dir(&self) -> PathBuf {
+        self.cheats
+            .clone()
+            .unwrap_or_else(|| data_dir().join("cheats"))
+    }
+
+    pub fn screenshots_dir(&self) -> PathBuf {
+        self.screenshots
+            .clone()
+            .unwrap_or_else(|| data_dir().join("screenshots"))
+    }
+
+    pub fn recordings_dir(&self) -> PathBuf {
+        self.recordings
+            .clone()
+            .unwrap_or_else(|| data_dir().join("recordings"))
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct VsConfig {
+    pub ppu: Option<VsPpu>,
+    pub dip_switches: Option<u8>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub emulation: EmulationConfig,
+    pub video: VideoConfig,
+    pub audio: AudioConfig,
+    pub paths: PathsConfig,
+    pub keys: Option<KeyBindings>,
+    pub hotkeys: Option<Hotkeys>,
+    pub vs: VsConfig,
+}
+
+pub fn default_path() -> Option<PathBuf> {
+    Some(dirs::config_dir()?.join("nes").join("config.toml"))
+}
+
+// Where savestates, battery saves, screenshots and recordings go unless
+// [paths] says otherwise.
+fn data_dir() -> PathBuf {
+    dirs::data_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("nes")
+}
+
+impl Config {
+    // The settings for the game with checksum `crc32` on a `region`
+    // console; None, or a game without a [game] table, gets the file's own
+    // settings and its [region] table's.
+    pub fn from_toml(text: &str, crc32: Option<u32>, region: Region) -> Result<Self, ConfigError> {
+        let mut base: toml::Table = text.parse()?;
+        let games = match base.remove("game") {
+            Some(toml::Value::Table(games)) => games,
+            Some(_) => return Err(ConfigError::Game(String::new(), None)),
+            None => toml::Table::new(),
+        };
+        let regions = match base.remove("region") {
+            Some(toml::Value::Table(regions)) => regions,
+            Some(_) => return Err(ConfigError::Region(String::new(), None)),
+            None => toml::Table::new(),
+        };
+        toml::Value::Table(base.clone()).try_into::<Config>()?;
+
+        let mut chosen = None;
+        for (name, overrides) in regions {
+            let console = match name.parse::<Region>() {
+                Ok(console) => console,
+                Err(_) => return Err(ConfigError::Region(name, None)),
+            };
+            let toml::Value::Table(overrides) = overrides else {
+                return Err(ConfigError::Region(name, None));
+            };
+            let mut merged = base.clone();
+            merge(&mut merged, overrides);
+            let settings: Config = toml::Value::Table(merged.clone())
+                .try_into()
+                .map_err(|err| ConfigError::Region(name, Some(err)))?;
+            settings.check()?;
+            if console == region.console() {
+                chosen = Some(merged);
+            }
+        }
+        if let Some(merged) = chosen {
+            base = merged;
+        }
+        let mut config: Config = toml::Value::Table(base.clone()).try_into()?;
+
+        // every table is checked, not just the one for this game, so a typo
+        // shows up the first time any game is loaded
+        for (name, overrides) in games {
+            let game = parse_crc(&name).ok_or_else(|| ConfigError::Game(name.clone(), None))?;
+            let toml::Value::Table(overrides) = overrides else {
+                return Err(ConfigError::Game(name, None));
+            };
+            let mut merged = base.clone();
+            merge(&mut merged, overrides);
+            let settings: Config = toml::Value::Table(merged)
+                .try_into()
+                .map_err(|err| ConfigError::Game(name, Some(err)))?;
+            settings.check()?;
+            if Some(game) == crc32 {
+                config = settings;
+            }
+        }
+        config.check()?;
+        Ok(config)
+    }
+
+    fn check(&self) -> Result<(), ConfigError> {
+        let emulation = &self.emulation;
+        if emulation.speed.is_some_and(|speed| speed <= 0.0) {
+            return Err(ConfigError::Value("emulation.speed must be above 0"));
+        }
+        if emulation.fast_forward.is_some_and(|speed| speed < 1.0) {
+            return Err(ConfigError::Value(
+                "emulation.fast_forward must be at least 1",
+            ));
+        }
+        if emulation.extra_scanlines.is_some_and(|lines| lines > 1000) {
+            return Err(ConfigError::Value(
+                "emulation.extra_scanlines must be at most 1000",
+            ));
+        }
+        let rewind = emulation.rewind_seconds;
+        if rewind.is_some_and(|seconds| !(0.0..=600.0).contains(&seconds)) {
+            return Err(ConfigError::Value(
+                "emulation.rewind_seconds must be from 0 to 600",
+            ));
+        }
+        // about 2MB a second
+        let clip = self.video.clip_seconds;
+        if clip.is_some_and(|seconds| !(0.0..=60.0).contains(&seconds)) {
+            return Err(ConfigError::Value(
+                "video.clip_seconds must be from 0 to 60",
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn load(path: &Path, crc32: Option<u32>, region: Region) -> Result<Self, ConfigError> {
+        let mut config = Self::from_toml(&fs::read_to_string(path)?, crc32, region)?;
+        if let Some(dir) = path.parent() {
+            config.resolve_paths(dir);
+        }
+        Ok(config)
+    }
+
+    fn resolve_paths(&mut self, dir: &Path) {
+        let paths = [
+            &mut self.video.palette,
+            &mut self.paths.states,
+            &mut self.paths.saves,
+            &mut self.paths.cheats,
+            &mut self.paths.screenshots,
+            &mut self.paths.recordings,
+        ];
+        for path in paths.into_iter().flatten() {
+            if path.is_relative() {
+                *path = dir.join(&*path);
+            }
+        }
+    }
+
+    // Front end options, short of the shader: loading one can fail, so
+    // that's up to the caller.
+    pub fn options(&self) -> Options {
+        let defaults = Options::default();
+        let video = &self.video;
+        Options {
+            scale: video.scale.unwrap_or(defaults.scale),
+            scaling: Scaling {
+                aspect: video.aspect.unwrap_or(defaults.scaling.aspect),
+                integer: video.integer_scaling.unwrap_or(defaults.scaling.integer),
+                letterbox: video.letterbox.unwrap_or(defaults.scaling.letterbox),
+            },
+            sample_rate: self.audio.sample_rate.unwrap_or(defaults.sample_rate),
+            audio_latency: Latency {
+                queued: self.audio.latency.unwrap_or(defaults.audio_latency.queued),
+                buffer: self.audio.buffer.or(defaults.audio_latency.buffer),
+            },
+            bindings: self.keys.clone().unwrap_or(defaults.bindings),
+            hotkeys: self.hotkeys.clone().unwrap_or(defaults.hotkeys),
+            screenshot_dir: self.paths.screenshots_dir(),
+            screenshot: video.screenshot.unwrap_or(defaults.screenshot),
+            saves_dir: self.paths.saves_dir(),
+            battery: self.emulation.battery.unwrap_or(defaults.battery),
+            cheats_dir: self.paths.cheats_dir(),
+            states_dir: self.paths.states_dir(),
+            recent_file: Some(data_dir().join("recent.toml")),
+            recordings_dir: self.paths.recordings_dir(),
+            ffmpeg: self.paths.ffmpeg.clone().unwrap_or(defaults.ffmpeg),
+            clip_seconds: video.clip_seconds.unwrap_or(defaults.clip_seconds),
+            rewind_seconds: self
+                .emulation
+                .rewind_seconds
+                .unwrap_or(defaults.rewind_seconds),
+            speed: self.emulation.speed.unwrap_or(defaults.speed),
+            fast_forward_speed: self
+                .emulation
+                .fast_forward
+                .unwrap_or(defaults.fast_forward_speed),
+            pacing: video.pacing.unwrap_or(defaults.pacing),
+            fullscreen: video.fullscreen.unwrap_or(defaults.fullscreen),
+            fullscreen_mode: video.fullscreen_mode.unwrap_or(defaults.fullscreen_mode),
+            osd: video.osd.unwrap_or(defaults.osd),
+            stats: video.stats.unwrap_or(defaults.stats),
+            input_display: video.input_display.unwrap_or(defaults.input_display),
+            hot_reload: self.emulation.hot_reload.or(defaults.hot_reload),
+            ..defaults
+        }
+    }
+}
+
+fn parse_crc(name: &str) -> Option<u32> {
+    if name.len() != 8 {
+        return None;
+    }
+    u32::from_str_radix(name, 16).ok()
+}
+
+// Tables merge key by key; anything else in `overrides` replaces what was
+// there.
+fn merge(base: &mut toml::Table, overrides: toml::Table) {
+    for (key, value) in overrides {
+        match (base.get_mut(&key), value) {
+            (Some(toml::Value::Table(inner)), toml::Value::Table(value)) => merge(inner, value),
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::input::joypad::JoypadButton;
+    use crate::input::keymap::Key;
+
+    const CONFIG: &str = r#"
+        [video]
+        scale = 4
+        palette = "smooth.pal"
+        pacing = "audio"
+        aspect = "ntsc"
+        fullscreen_mode = "exclusive"
+
+        [audio]
+        latency = 1024
+        buffer = 256
+
+        [emulation]
+        ram_init = "random"
+        seed = 42
+        hot_reload = "keep"
+        battery = "mapped"
+
+        [keys.player1]
+        a = ["Q"]
+        b = ["W"]
+
+        [game."cbf43926".video]
+        palette = "/palettes/other.pal"
+        sprite_limit = false
+
+        [game."cbf43926".keys.player1]
+        a = ["Space"]
+
+        [game."cbf43926".vs]
+        ppu = "RP2C04-0003"
+        dip_switches = 0x41
+    "#;
+
+    #[test]
+    fn test_game_table_overrides_file() {
+        let config = Config::from_toml(CONFIG, None, Region::Ntsc).unwrap();
+        assert_eq!(config.video.scale, Some(4));
+        assert_eq!(config.video.palette, Some(PathBuf::from("smooth.pal")));
+        assert!(config.video.sprite_limit);
+
+        let game = Config::from_toml(CONFIG, Some(0xCBF43926), Region::Ntsc).unwrap();
+        assert_eq!(game.video.scale, Some(4));
+        assert_eq!(
+            game.video.palette,
+            Some(PathBuf::from("/palettes/other.pal"))
+        );
+        assert!(!game.video.sprite_limit);
+        assert_eq!(game.audio.latency, Some(1024));
+
+        let keys = game.keys.unwrap();
+        assert_eq!(keys.keys_for(0, JoypadButton::BUTTON_A), ["space"]);
+        assert_eq!(keys.keys_for(0, JoypadButton::BUTTON_B), ["w"]);
+        assert!(keys.bindings_for(&Key::new("Return")).is_empty());
+        assert_eq!(game.vs.ppu, Some(VsPpu::Rp2c04(3)));
+        assert_eq!(game.vs.dip_switches, Some(0x41));
+        assert_eq!(config.vs, VsConfig::default());
+
+        let other = Config::from_toml(CONFIG, Some(0x12345678), Region::Ntsc).unwrap();
+        assert_eq!(other, config);
+    }
+
+    #[test]
+    fn test_defaults_and_options() {
+        let config = Config::from_toml("", None, Region::Ntsc).unwrap();
+        assert_eq!(config, Config::default());
+        let options = config.options();
+        assert_eq!(options.scale, Options::default().scale);
+        assert_eq!(options.bindings, KeyBindings::default_bindings());
+
+        let mut config = Config::from_toml(CONFIG, None, Region::Ntsc).unwrap();
+        config.resolve_paths(Path::new("/home/me/.config/nes"));
+        assert_eq!(
+            config.video.palette,
+            Some(PathBuf::from("/home/me/.config/nes/smooth.pal"))
+        );
+        let latency = config.options().audio_latency;
+        assert_eq!((latency.queued, latency.buffer), (1024, Some(256)));
+        assert_eq!(config.options().pacing, Pacing::Audio);
+        assert_eq!(config.options().scaling.aspect, PixelAspect::Ntsc);
+        assert!(config.options().scaling.letterbox);
+        assert_eq!(config.options().fullscreen_mode, FullscreenMode::Exclusive);
+        assert!(!config.options().fullscreen);
+        assert_eq!(config.emulation.ram_init, Some(RamInit::Random));
+        assert_eq!(config.emulation.seed, Some(42));
+        assert_eq!(config.options().hot_reload, Some(ReloadMode::Keep));
+        assert_eq!(config.options().battery, BatteryBacking::Mapped);
+    }
+
+    #[test]
+    fn test_region_table_under_game_table() {
+        let text = r#"
+            [video]
+            scale = 2
+
+            [region.pal.video]
+            scale = 3
+            palette = "pal.pal"
+
+            [game."cbf43926".video]
+            scale = 4
+        "#;
+        let ntsc = Config::from_toml(text, None, Region::Multi).unwrap();
+        assert_eq!(ntsc.video.scale, Some(2));
+        assert_eq!(ntsc.video.palette, None);
+        let pal = Config::from_toml(text, None, Region::Pal).unwrap();
+        assert_eq!(pal.video.scale, Some(3));
+        assert_eq!(pal.video.palette, Some(PathBuf::from("pal.pal")));
+        let game = Config::from_toml(text, Some(0xCBF43926), Region::Pal).unwrap();
+        assert_eq!(game.video.scale, Some(4));
+        assert_eq!(game.video.palette, Some(PathBuf::from("pal.pal")));
+
+        let forced = Config::from_toml("[emulation]\nregion = \"dendy\"", None, Region::Ntsc);
+        assert_eq!(forced.unwrap().emulation.region, Some(Region::Dendy));
+        assert!(matches!(
+            Config::from_toml("[region.secam.video]\nscale = 2", None, Region::Ntsc),
+            Err(ConfigError::Region(name, None)) if name == "secam"
+        ));
+    }
+
+    #[test]
+    fn test_mistakes_are_reported() {
+        assert!(matches!(
+            Config::from_toml("[video]\nscael = 2", None, Region::Ntsc),
+            Err(ConfigError::Parse(_))
+        ));
+        assert!(matches!(
+            Config::from_toml("[game.\"Zelda\".video]\nscale = 2", None, Region::Ntsc),
+            Err(ConfigError::Game(name, None)) if name == "Zelda"
+        ));
+        assert!(matches!(
+            Config::from_toml("[vs]\nppu = \"2c07\"", None, Region::Ntsc),
+            Err(ConfigError::Parse(_))
+        ));
+        assert!(matches!(
+            Config::from_toml("[emulation]\nspeed = 0.0", None, Region::Ntsc),
+            Err(ConfigError::Value(_))
+        ));
+        assert!(matches!(
+            Config::from_toml("[emulation]\nextra_scanlines = 5000", None, Region::Ntsc),
+            Err(ConfigError::Value(_))
+        ));
+        // a broken table for some other game still counts
+        assert!(matches!(
+            Config::from_toml(
+                "[game.\"00000001\".video]\nscale = \"big\"",
+                Some(2),
+                Region::Ntsc
+            ),
+            Err(ConfigError::Game(_, Some(_)))
+        ));
+    }
+}
@@ -0,0 +1,901 @@
+// The 2A03's 6502 core: registers, the official instruction set with cycle
+// counts, and interrupts. Everything outside the CPU is reached through the
+// bus.
+//
+// An opcode outside the official set jams the CPU, as the KIL opcodes do
+// a real one: it stops where it is, taking no interrupts, while the rest
+// of the console runs on, until a reset. Games never get there, but a
+// core that runs whatever bytes it's given can't panic instead.
+
+use alloc::boxed::Box;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use serde::{Deserialize, Serialize};
+
+use crate::bus::{Bus, Mem};
+use crate::mapper::nrom::Nrom;
+use crate::mapper::Mirroring;
+use crate::opcodes;
+
+type Address = u16;
+type Value = u8;
+
+const STACK: Address = 0x0100;
+pub const STACK_RESET: u8 = 0xFD;
+
+// status flags
+pub const CARRY: Value = 0b0000_0001;
+pub const ZERO: Value = 0b0000_0010;
+pub const INTERRUPT_DISABLE: Value = 0b0000_0100;
+pub const DECIMAL_MODE: Value = 0b0000_1000;
+pub const BREAK: Value = 0b0001_0000;
+pub const BREAK2: Value = 0b0010_0000;
+pub const OVERFLOW: Value = 0b0100_0000;
+pub const NEGATIVE: Value = 0b1000_0000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(non_camel_case_types)]
+pub enum AddressingMode {
+    Immediate,
+    ZeroPage,
+    ZeroPage_X,
+    ZeroPage_Y,
+    Absolute,
+    Absolute_X,
+    Absolute_Y,
+    Indirect_X,
+    Indirect_Y,
+    Relative,
+    NonAddressing,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Cpu {
+    pub register_a: Value,
+    pub register_x: Value,
+    pub register_y: Value,
+    pub status: Value,
+    pub program_counter: u16,
+    pub stack_pointer: u8,
+    pub cycles: u64,
+    #[serde(skip)]
+    pub bus: Bus,
+
+    // page-crossing and branch penalties for the instruction in flight
+    extra_cycles: u8,
+    // saved in a section of its own; see state.rs
+    #[serde(skip)]
+    pub(crate) jammed: bool,
+}
+
+impl Mem for Cpu {
+    fn mem_read(&mut self, addr: Address) -> Value {
+        self.bus.mem_read(addr)
+    }
+
+    fn mem_write(&mut self, addr: Address, value: Value) {
+        self.bus.mem_write(addr, value)
+    }
+}
+
+impl Default for Cpu {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn page_crossed(a: Address, b: Address) -> bool {
+    a & 0xFF00 != b & 0xFF00
+}
+
+impl Cpu {
+    pub fn new() -> Self {
+        Cpu {
+            register_a: 0,
+            register_x: 0,
+            register_y: 0,
+            status: 0,
+            program_counter: 0,
+            stack_pointer: STACK_RESET,
+            cycles: 0,
+            bus: Bus::new(),
+            extra_cycles: 0,
+            jammed: false,
+        }
+    }
+
+    pub fn jammed(&self) -> bool {
+        self.jammed
+    }
+
+    fn lda(&mut self, mode: &AddressingMode) {
+        let value = self.read_operand(mode);
+        self.set_register_a(value);
+    }
+
+    fn ldx(&mut self, mode: &AddressingMode) {
+        self.register_x = self.read_operand(mode);
+        self.update_zero_and_negative_flags(self.register_x);
+    }
+
+    fn ldy(&mut self, mode: &AddressingMode) {
+        self.register_y = self.read_operand(mode);
+        self.update_zero_and_negative_flags(self.register_y);
+    }
+
+    fn sta(&mut self, mode: &AddressingMode) {
+        let (addr, _) = self.get_op_address(mode);
+        self.mem_write(addr, self.register_a);
+    }
+
+    fn stx(&mut self, mode: &AddressingMode) {
+        let (addr, _) = self.get_op_address(mode);
+        self.mem_write(addr, self.register_x);
+    }
+
+    fn sty(&mut self, mode: &AddressingMode) {
+        let (addr, _) = self.get_op_address(mode);
+        self.mem_write(addr, self.register_y);
+    }
+
+    fn inx(&mut self) {
+        self.register_x = self.register_x.wrapping_add(1);
+        self.update_zero_and_negative_flags(self.register_x);
+    }
+
+    fn iny(&mut self) {
+        self.register_y = self.register_y.wrapping_add(1);
+        self.update_zero_and_negative_flags(self.register_y);
+    }
+
+    fn dex(&mut self) {
+        self.register_x = self.register_x.wrapping_sub(1);
+        self.update_zero_and_negative_flags(self.register_x);
+    }
+
+    fn dey(&mut self) {
+        self.register_y = self.register_y.wrapping_sub(1);
+        self.update_zero_and_negative_flags(self.register_y);
+    }
+
+    fn inc(&mut self, mode: &AddressingMode) {
+        let (addr, _) = self.get_op_address(mode);
+        let value = self.mem_read(addr).wrapping_add(1);
+        self.mem_write(addr, value);
+        self.update_zero_and_negative_flags(value);
+    }
+
+    fn dec(&mut self, mode: &AddressingMode) {
+        let (addr, _) = self.get_op_address(mode);
+        let value = self.mem_read(addr).wrapping_sub(1);
+        self.mem_write(addr, value);
+        self.update_zero_and_negative_flags(value);
+    }
+
+    fn tax(&mut self) {
+        self.register_x = self.register_a;
+        self.update_zero_and_negative_flags(self.register_x)
+    }
+
+    fn tay(&mut self) {
+        self.register_y = self.register_a;
+        self.update_zero_and_negative_flags(self.register_y)
+    }
+
+    fn tsx(&mut self) {
+        self.register_x = self.stack_pointer;
+        self.update_zero_and_negative_flags(self.register_x)
+    }
+
+    fn txa(&mut self) {
+        self.set_register_a(self.register_x);
+    }
+
+    fn tya(&mut self) {
+        self.set_register_a(self.register_y);
+    }
+
+    // the NES 2A03 has no decimal mode, so ADC/SBC are always binary
+    fn add_to_register_a(&mut self, value: Value) {
+        let sum = self.register_a as u16 + value as u16 + (self.status & CARRY) as u16;
+        let result = sum as u8;
+
+        self.set_flag(CARRY, sum > 0xFF);
+        self.set_flag(
+            OVERFLOW,
+            (value ^ result) & (result ^ self.register_a) & 0x80 != 0,
+        );
+        self.set_register_a(result);
+    }
+
+    fn adc(&mut self, mode: &AddressingMode) {
+        let value = self.read_operand(mode);
+        self.add_to_register_a(value);
+    }
+
+    fn sbc(&mut self, mode: &AddressingMode) {
+        let value = self.read_operand(mode);
+        self.add_to_register_a(!value);
+    }
+
+    fn and(&mut self, mode: &AddressingMode) {
+        let value = self.read_operand(mode);
+        self.set_register_a(self.register_a & value);
+    }
+
+    fn eor(&mut self, mode: &AddressingMode) {
+        let value = self.read_operand(mode);
+        self.set_register_a(self.register_a ^ value);
+    }
+
+    fn ora(&mut self, mode: &AddressingMode) {
+        let value = self.read_operand(mode);
+        self.set_register_a(self.register_a | value);
+    }
+
+    fn compare(&mut self, mode: &AddressingMode, register: Value) {
+        let value = self.read_operand(mode);
+        self.set_flag(CARRY, register >= value);
+        self.update_zero_and_negative_flags(register.wrapping_sub(value));
+    }
+
+    fn bit(&mut self, mode: &AddressingMode) {
+        let (addr, _) = self.get_op_address(mode);
+        let value = self.mem_read(addr);
+
+        self.set_flag(ZERO, self.register_a & value == 0);
+        self.set_flag(NEGATIVE, value & 0b1000_0000 != 0);
+        self.set_flag(OVERFLOW, value & 0b0100_0000 != 0);
+    }
+
+    // Shared by the four shifts/rotates: applies `op` to A or to memory,
+    // op returning (result, carry out).
+    fn shift(&mut self, mode: &AddressingMode, op: fn(Value, bool) -> (Value, bool)) {
+        let carry_in = self.status & CARRY != 0;
+
+        if let AddressingMode::NonAddressing = mode {
+            let (result, carry) = op(self.register_a, carry_in);
+            self.set_flag(CARRY, carry);
+            self.set_register_a(result);
+        } else {
+            let (addr, _) = self.get_op_address(mode);
+            let (result, carry) = op(self.mem_read(addr), carry_in);
+            self.mem_write(addr, result);
+            self.set_flag(CARRY, carry);
+            self.update_zero_and_negative_flags(result);
+        }
+    }
+
+    fn branch(&mut self, condition: bool) {
+        if !condition {
+            return;
+        }
+
+        let offset = self.mem_read(self.program_counter) as i8;
+        let next = self.program_counter.wrapping_add(1);
+        let target = next.wrapping_add(offset as u16);
+
+        self.extra_cycles += if page_crossed(next, target) { 2 } else { 1 };
+        self.program_counter = target;
+    }
+
+    pub(crate) fn stack_push(&mut self, value: Value) {
+        self.mem_write(STACK + self.stack_pointer as u16, value);
+        self.stack_pointer = self.stack_pointer.wrapping_sub(1);
+    }
+
+    pub(crate) fn stack_pop(&mut self) -> Value {
+        self.stack_pointer = self.stack_pointer.wrapping_add(1);
+        self.mem_read(STACK + self.stack_pointer as u16)
+    }
+
+    pub(crate) fn stack_push_u16(&mut self, value: u16) {
+        self.stack_push((value >> 8) as u8);
+        self.stack_push((value & 0xff) as u8);
+    }
+
+    pub(crate) fn stack_pop_u16(&mut self) -> u16 {
+        let lo = self.stack_pop() as u16;
+        let hi = self.stack_pop() as u16;
+        hi << 8 | lo
+    }
+
+    fn php(&mut self) {
+        // PHP always pushes with both break bits set
+        self.stack_push(self.status | BREAK | BREAK2);
+    }
+
+    fn plp(&mut self) {
+        self.status = self.stack_pop() & !BREAK | BREAK2;
+    }
+
+    fn pla(&mut self) {
+        let value = self.stack_pop();
+        self.set_register_a(value);
+    }
+
+    fn jsr(&mut self) {
+        // pushes the address of the last byte of the JSR itself
+        self.stack_push_u16(self.program_counter.wrapping_add(1));
+        self.program_counter = self.mem_read_u16(self.program_counter);
+    }
+
+    fn rts(&mut self) {
+        self.program_counter = self.stack_pop_u16().wrapping_add(1);
+    }
+
+    fn rti(&mut self) {
+        self.plp();
+        self.program_counter = self.stack_pop_u16();
+    }
+
+    fn jmp_indirect(&mut self) {
+        let ptr = self.mem_read_u16(self.program_counter);
+
+        // 6502 bug: the pointer's high byte is fetched without carrying into
+        // the page, so JMP ($10FF) reads $10FF and $1000
+        let lo = self.mem_read(ptr) as u16;
+        let hi = self.mem_read((ptr & 0xFF00) | (ptr.wrapping_add(1) & 0x00FF)) as u16;
+        self.program_counter = hi << 8 | lo;
+    }
+
+    fn interrupt(&mut self, vector: Address) {
+        tracing::trace!(
+            "interrupt through ${:04X} from ${:04X}",
+            vector,
+            self.program_counter
+        );
+        self.stack_push_u16(self.program_counter);
+        self.stack_push((self.status | BREAK2) & !BREAK);
+        self.status |= INTERRUPT_DISABLE;
+        self.program_counter = self.mem_read_u16(vector);
+        self.bus.tick(7);
+        self.cycles += 7;
+    }
+
+    pub fn irq(&mut self) {
+        if self.status & INTERRUPT_DISABLE == 0 {
+            self.interrupt(0xFFFE);
+        }
+    }
+
+    pub fn nmi(&mut self) {
+        self.interrupt(0xFFFA);
+    }
+
+    fn read_operand(&mut self, mode: &AddressingMode) -> Value {
+        let (addr, crossed) = self.get_op_address(mode);
+        if crossed {
+            self.extra_cycles += 1;
+        }
+        self.mem_read(addr)
+    }
+
+    // Address of the operand for the instruction whose opcode was just
+    // fetched, and whether indexing crossed a page.
+    fn get_op_address(&mut self, mode: &AddressingMode) -> (Address, bool) {
+        match mode {
+            AddressingMode::Immediate => (self.program_counter, false),
+            AddressingMode::ZeroPage => (self.mem_read(self.program_counter) as u16, false),
+            AddressingMode::Absolute => (self.mem_read_u16(self.program_counter), false),
+            AddressingMode::ZeroPage_X => {
+                let pos = self.mem_read(self.program_counter);
+                (pos.wrapping_add(self.register_x) as u16, false)
+            }
+            AddressingMode::ZeroPage_Y => {
+                let pos = self.mem_read(self.program_counter);
+                (pos.wrapping_add(self.register_y) as u16, false)
+            }
+
+            AddressingMode::Absolute_X => {
+                let base = self.mem_read_u16(self.program_counter);
+                let addr = base.wrapping_add(self.register_x as u16);
+                (addr, page_crossed(base, addr))
+            }
+
+            AddressingMode::Absolute_Y => {
+                let base = self.mem_read_u16(self.program_counter);
+                let addr = base.wrapping_add(self.register_y as u16);
+                (addr, page_crossed(base, addr))
+            }
+
+            AddressingMode::Indirect_X => {
+                let base = self.mem_read(self.program_counter);
+
+                let ptr = base.wrapping_add(self.register_x);
+                let lo = self.mem_read(ptr as u16);
+                let hi = self.mem_read(ptr.wrapping_add(1) as u16);
+
+                ((hi as u16) << 8 | (lo as u16), false)
+            }
+
+            AddressingMode::Indirect_Y => {
+                let base = self.mem_read(self.program_counter);
+
+                let lo = self.mem_read(base as u16);
+                let hi = self.mem_read(base.wrapping_add(1) as u16);
+                let deref_base = (hi as u16) << 8 | (lo as u16);
+                let deref = deref_base.wrapping_add(self.register_y as u16);
+
+                (deref, page_crossed(deref_base, deref))
+            }
+
+            AddressingMode::Relative | AddressingMode::NonAddressing => {
+                panic!("mode {:?} is not supported", mode);
+            }
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.register_a = 0;
+        self.register_x = 0;
+        self.register_y = 0;
+        self.stack_pointer = STACK_RESET;
+        self.status = INTERRUPT_DISABLE | BREAK2;
+        self.jammed = false;
+
+        self.program_counter = self.mem_read_u16(0xFFFC);
+        tracing::debug!("reset to ${:04X}", self.program_counter);
+    }
+
+    // Wraps `program` in a 32K NROM image at $8000 with the reset vector
+    // pointing at it; anything past 32K is dropped.
+    pub fn load(&mut self, program: Vec<Value>) {
+        let mut prg = vec![0; 0x8000];
+        let len = program.len().min(prg.len());
+        prg[..len].copy_from_slice(&program[..len]);
+        prg[0x7FFC] = 0x00;
+        prg[0x7FFD] = 0x80;
+        self.bus
+            .insert_cartridge(Box::new(Nrom::new(prg, Vec::new(), Mirroring::Horizontal)));
+    }
+
+    pub fn load_and_run(&mut self, program: Vec<Value>) {
+        self.load(program);
+        self.reset();
+        self.run();
+    }
+
+    fn set_flag(&mut self, flag: Value, on: bool) {
+        if on {
+            self.status |= flag;
+        } else {
+            self.status &= !flag;
+        }
+    }
+
+    fn set_register_a(&mut self, value: Value) {
+        self.register_a = value;
+        self.update_zero_and_negative_flags(value);
+    }
+
+    fn update_zero_and_negative_flags(&mut self, result: Value) {
+        // is zero?
+        if result == 0 {
+            self.status |= 0b0000_0010; // set zero flag
+        } else {
+            self.status &= 0b1111_1101; // unset zero flag
+        }
+
+        // is negative?
+        if result & 0b1000_0000 != 0 {
+            self.status |= 0b1000_0000; // set negative flag
+        } else {
+            self.status &= 0b0111_1111; // unset negative flag
+        }
+    }
+
+    pub fn run(&mut self) {
+        while self.step() {}
+    }
+
+    // Takes a pending NMI or IRQ, moving the PC to its handler. `step`
+    // does this itself; calling it first shows where the next
+    // instruction really is.
+    // Returns whether one was taken.
+    pub fn service_interrupts(&mut self) -> bool {
+        if self.bus.poll_nmi() {
+            self.nmi();
+        } else if self.bus.irq_pending() && self.status & INTERRUPT_DISABLE == 0 {
+            self.irq();
+        } else {
+            return false;
+        }
+        true
+    }
+
+    // Executes one instruction, servicing a pending NMI or IRQ first.
+    // Returns false after a BRK, which ends `run`, and while jammed, when
+    // it only lets a cycle go by.
+    pub fn step(&mut self) -> bool {
+        if self.jammed {
+            self.bus.tick(1);
+            self.cycles += 1;
+            return false;
+        }
+        self.service_interrupts();
+
+        let code = self.mem_read(self.program_counter);
+        let Some(opcode) = opcodes::OPCODES_MAP.get(&code) else {
+            tracing::warn!("jammed on ${:02X} at ${:04X}", code, self.program_counter);
+            self.jammed = true;
+            return false;
+        };
+        self.program_counter = self.program_counter.wrapping_add(1);
+        let program_counter_state = self.program_counter;
+
+        let mode = &opcode.mode;
+        self.extra_cycles = 0;
+
+        match code {
+            // BRK: an IRQ with the break bit set in the pushed status,
+            // returning past the padding byte after the opcode
+            0x00 => {
+                self.stack_push_u16(self.program_counter.wrapping_add(1));
+                self.php();
+                self.status |= INTERRUPT_DISABLE;
+                self.program_counter = self.mem_read_u16(0xFFFE);
+                self.bus.tick(opcode.cycles);
+                self.cycles += opcode.cycles as u64;
+                return false;
+            }
+
+            0xea => {} // NOP
+
+            0xa9 | 0xa5 | 0xb5 | 0xad | 0xbd | 0xb9 | 0xa1 | 0xb1 => self.lda(mode),
+            0xa2 | 0xa6 | 0xb6 | 0xae | 0xbe => self.ldx(mode),
+            0xa0 | 0xa4 | 0xb4 | 0xac | 0xbc => self.ldy(mode),
+            0x85 | 0x95 | 0x8d | 0x9d | 0x99 | 0x81 | 0x91 => self.sta(mode),
+            0x86 | 0x96 | 0x8e => self.stx(mode),
+            0x84 | 0x94 | 0x8c => self.sty(mode),
+
+            0x69 | 0x65 | 0x75 | 0x6d | 0x7d | 0x79 | 0x61 | 0x71 => self.adc(mode),
+            0xe9 | 0xe5 | 0xf5 | 0xed | 0xfd | 0xf9 | 0xe1 | 0xf1 => self.sbc(mode),
+            0x29 | 0x25 | 0x35 | 0x2d | 0x3d | 0x39 | 0x21 | 0x31 => self.and(mode),
+            0x49 | 0x45 | 0x55 | 0x4d | 0x5d | 0x59 | 0x41 | 0x51 => self.eor(mode),
+            0x09 | 0x05 | 0x15 | 0x0d | 0x1d | 0x19 | 0x01 | 0x11 => self.ora(mode),
+
+            0x0a | 0x06 | 0x16 | 0x0e | 0x1e => self.shift(mode, |v, _| (v << 1, v & 0x80 != 0)),
+            0x4a | 0x46 | 0x56 | 0x4e | 0x5e => self.shift(mode, |v, _| (v >> 1, v & 1 != 0)),
+            0x2a | 0x26 | 0x36 | 0x2e | 0x3e => {
+                self.shift(mode, |v, c| (v << 1 | c as u8, v & 0x80 != 0))
+            }
+            0x6a | 0x66 | 0x76 | 0x6e | 0x7e => {
+                self.shift(mode, |v, c| (v >> 1 | (c as u8) << 7, v & 1 != 0))
+            }
+
+            0xe6 | 0xf6 | 0xee | 0xfe => self.inc(mode),
+            0xc6 | 0xd6 | 0xce | 0xde => self.dec(mode),
+            0xe8 => self.inx(),
+            0xc8 => self.iny(),
+            0xca => self.dex(),
+            0x88 => self.dey(),
+
+            0xc9 | 0xc5 | 0xd5 | 0xcd | 0xdd | 0xd9 | 0xc1 | 0xd1 => {
+                self.compare(mode, self.register_a)
+            }
+            0xe0 | 0xe4 | 0xec => self.compare(mode, self.register_x),
+            0xc0 | 0xc4 | 0xcc => self.compare(mode, self.register_y),
+            0x24 | 0x2c => self.bit(mode),
+
+            0x4c => self.program_counter = self.mem_read_u16(self.program_counter),
+            0x6c => self.jmp_indirect(),
+            0x20 => self.jsr(),
+            0x60 => self.rts(),
+            0x40 => self.rti(),
+
+            0xd0 => self.branch(self.status & ZERO == 0),
+            0xf0 => self.branch(self.status & ZERO != 0),
+            0x50 => self.branch(self.status & OVERFLOW == 0),
+            0x70 => self.branch(self.status & OVERFLOW != 0),
+            0x10 => self.branch(self.status & NEGATIVE == 0),
+            0x30 => self.branch(self.status & NEGATIVE != 0),
+            0x90 => self.branch(self.status & CARRY == 0),
+            0xb0 => self.branch(self.status & CARRY != 0),
+
+            0x18 => self.status &= !CARRY,
+            0xd8 => self.status &= !DECIMAL_MODE,
+            0x58 => self.status &= !INTERRUPT_DISABLE,
+            0xb8 => self.status &= !OVERFLOW,
+            0x38 => self.status |= CARRY,
+            0xf8 => self.status |= DECIMAL_MODE,
+            0x78 => self.status |= INTERRUPT_DISABLE,
+
+            0xaa => self.tax(),
+            0xa8 => self.tay(),
+            0xba => self.tsx(),
+            0x8a => self.txa(),
+            0x9a => self.stack_pointer = self.register_x,
+            0x98 => self.tya(),
+
+            0x48 => self.stack_push(self.register_a),
+            0x68 => self.pla(),
+            0x08 => self.php(),
+            0x28 => self.plp(),
+
+            _ => unreachable!("{} is in the opcode table", opcode.mnemonic),
+        }
+
+        let cycles = opcode.cycles + self.extra_cycles;
+        self.bus.tick(cycles);
+        self.cycles += cycles as u64;
+
+        let stall = self.bus.take_dma_stall();
+        for _ in 0..stall {
+            self.bus.tick(1);
+        }
+        self.cycles += stall as u64;
+
+        if program_counter_state == self.program_counter {
+            self.program_counter = self.program_counter.wrapping_add((opcode.len - 1) as u16);
+        }
+
+        true
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::testing::asm::asm;
+
+    #[test]
+    fn test_mem_read() {
+        let mut cpu = Cpu::new();
+        cpu.load(vec![0xa9, 0x05, 0x00]);
+
+        assert_eq!(cpu.mem_read(0x8000), 0xa9);
+        assert_eq!(cpu.mem_read(0x8001), 0x05);
+
+        assert_eq!(cpu.mem_read_u16(0x8000), 0x05a9)
+    }
+
+    #[test]
+    fn test_0xa9_lda_load() {
+        let mut cpu = Cpu::new();
+        cpu.load_and_run(vec![0xa9, 0x05, 0x00]);
+
+        assert_eq!(cpu.register_a, 0x05);
+        assert!(cpu.status & 0b0000_0010 == 0b00); // zero flag not set
+        assert!(cpu.status & 0b1000_0000 == 0) // negative flag not set
+    }
+
+    #[test]
+    fn test_0xa9_lda_zero_flag() {
+        let mut cpu = Cpu::new();
+        cpu.load_and_run(vec![0xa9, 0x00, 0x00]); // load 5; break;
+        assert!(cpu.status & 0b0000_0010 == 0b10); // zero flag set
+    }
+
+    #[test]
+    fn test_0xaa_tax() {
+        let mut cpu = Cpu::new();
+        cpu.load_and_run(vec![0xa9, 0x05, 0xaa, 0x00]); // load 5; tax; break;
+
+        assert_eq!(cpu.register_x, 0x05)
+    }
+
+    #[test]
+    fn test_5_ops_working_together() {
+        let mut cpu = Cpu::new();
+        cpu.load_and_run(vec![0xa9, 0xc0, 0xaa, 0xe8, 0x00]);
+
+        assert_eq!(cpu.register_x, 0xc1)
+    }
+
+    #[test]
+    fn test_inx_overflow() {
+        let mut cpu = Cpu::new();
+        cpu.register_x = 0xff;
+
+        // lda 0xff
+        // tax
+        // inx
+        // inx
+        // break
+        cpu.load_and_run(vec![0xa9, 0xff, 0xaa, 0xe8, 0xe8, 0x00]);
+
+        assert_eq!(cpu.register_x, 1)
+    }
+
+    #[test]
+    fn test_lda_from_memory() {
+        // pointer
+        let mut cpu = Cpu::new();
+        cpu.mem_write(0x10, 0x55); // load data at 0x10
+        cpu.load_and_run(vec![0xa5, 0x10, 0x00]); // read data at 0x10
+
+        assert_eq!(cpu.register_a, 0x55);
+    }
+    #[test]
+    fn test_adc_carry_and_overflow() {
+        let mut cpu = Cpu::new();
+        // lda #$50; adc #$50; brk
+        cpu.load_and_run(vec![0xa9, 0x50, 0x69, 0x50, 0x00]);
+        assert_eq!(cpu.register_a, 0xa0);
+        assert!(cpu.status & OVERFLOW != 0);
+        assert!(cpu.status & CARRY == 0);
+
+        // lda #$ff; adc #$02; brk
+        cpu.load_and_run(vec![0xa9, 0xff, 0x69, 0x02, 0x00]);
+        assert_eq!(cpu.register_a, 0x01);
+        assert!(cpu.status & CARRY != 0);
+        assert!(cpu.status & OVERFLOW == 0);
+    }
+
+    #[test]
+    fn test_sbc_borrow() {
+        let mut cpu = Cpu::new();
+        // lda #$05; sec; sbc #$06; brk
+        cpu.load_and_run(vec![0xa9, 0x05, 0x38, 0xe9, 0x06, 0x00]);
+
+        assert_eq!(cpu.register_a, 0xff);
+        assert!(cpu.status & CARRY == 0); // borrowed
+        assert!(cpu.status & NEGATIVE != 0);
+    }
+
+    #[test]
+    fn test_sta_and_ldx_ldy() {
+        let mut cpu = Cpu::new();
+        // lda #$42; sta $0200; ldx $0200; ldy #$07; sty $10; brk
+        cpu.load_and_run(vec![
+            0xa9, 0x42, 0x8d, 0x00, 0x02, 0xae, 0x00, 0x02, 0xa0, 0x07, 0x84, 0x10, 0x00,
+        ]);
+
+        assert_eq!(cpu.register_x, 0x42);
+        assert_eq!(cpu.register_y, 0x07);
+        assert_eq!(cpu.mem_read(0x10), 0x07);
+    }
+
+    #[test]
+    fn test_branch_loop() {
+        let mut cpu = Cpu::new();
+        cpu.load_and_run(asm("ldx #$05; loop: dex; bne loop; brk"));
+
+        assert_eq!(cpu.register_x, 0);
+        assert!(cpu.status & ZERO != 0);
+    }
+
+    #[test]
+    fn test_jsr_rts() {
+        let mut cpu = Cpu::new();
+        cpu.load_and_run(asm("jsr sub; inx; brk; sub: ldx #$10; rts"));
+
+        assert_eq!(cpu.register_x, 0x11);
+        // balanced apart from the three bytes BRK pushed
+        assert_eq!(cpu.stack_pointer, STACK_RESET - 3);
+    }
+
+    #[test]
+    fn test_stack_push_pull() {
+        let mut cpu = Cpu::new();
+        // lda #$33; pha; lda #$00; pla; php; plp; brk
+        cpu.load_and_run(vec![0xa9, 0x33, 0x48, 0xa9, 0x00, 0x68, 0x08, 0x28, 0x00]);
+
+        assert_eq!(cpu.register_a, 0x33);
+        assert_eq!(cpu.stack_pointer, STACK_RESET - 3);
+        assert!(cpu.status & BREAK == 0);
+    }
+
+    #[test]
+    fn test_compare_sets_carry_and_zero() {
+        let mut cpu = Cpu::new();
+        // lda #$10; cmp #$10; brk
+        cpu.load_and_run(vec![0xa9, 0x10, 0xc9, 0x10, 0x00]);
+        assert!(cpu.status & (CARRY | ZERO) == CARRY | ZERO);
+
+        // ldx #$01; cpx #$02; brk
+        cpu.load_and_run(vec![0xa2, 0x01, 0xe0, 0x02, 0x00]);
+        assert!(cpu.status & CARRY == 0);
+        assert!(cpu.status & NEGATIVE != 0);
+    }
+
+    #[test]
+    fn test_shifts_and_rotates() {
+        let mut cpu = Cpu::new();
+        // lda #$81; asl a; brk
+        cpu.load_and_run(vec![0xa9, 0x81, 0x0a, 0x00]);
+        assert_eq!(cpu.register_a, 0x02);
+        assert!(cpu.status & CARRY != 0);
+
+        // sec; lda #$01; ror a; brk
+        cpu.load_and_run(vec![0x38, 0xa9, 0x01, 0x6a, 0x00]);
+        assert_eq!(cpu.register_a, 0x80);
+        assert!(cpu.status & CARRY != 0);
+
+        // lda #$40; sta $20; lsr $20; rol $20; brk
+        cpu.load_and_run(vec![0xa9, 0x40, 0x85, 0x20, 0x46, 0x20, 0x26, 0x20, 0x00]);
+        assert_eq!(cpu.mem_read(0x20), 0x40);
+    }
+
+    #[test]
+    fn test_bit() {
+        let mut cpu = Cpu::new();
+        cpu.mem_write(0x30, 0b1100_0000);
+        // lda #$01; bit $30; brk
+        cpu.load_and_run(vec![0xa9, 0x01, 0x24, 0x30, 0x00]);
+
+        assert!(cpu.status & ZERO != 0);
+        assert!(cpu.status & NEGATIVE != 0);
+        assert!(cpu.status & OVERFLOW != 0);
+    }
+
+    #[test]
+    fn test_jmp_indirect_page_bug() {
+        let mut cpu = Cpu::new();
+        cpu.mem_write(0x02ff, 0x06);
+        cpu.mem_write(0x0200, 0x80); // high byte comes from $0200, not $0300
+        cpu.mem_write(0x0300, 0x90);
+        // jmp ($02ff); brk; brk; brk; inx; brk
+        cpu.load_and_run(vec![0x6c, 0xff, 0x02, 0x00, 0x00, 0x00, 0xe8, 0x00]);
+
+        assert_eq!(cpu.register_x, 1);
+    }
+
+    #[test]
+    fn test_indexed_modes() {
+        let mut cpu = Cpu::new();
+        cpu.mem_write(0x05, 0x11);
+        cpu.mem_write(0x40, 0x00); // pointer to $0200
+        cpu.mem_write(0x41, 0x02);
+        cpu.mem_write(0x0200, 0x33);
+        cpu.mem_write(0x0210, 0x22);
+
+        cpu.load_and_run(asm("ldx #$04; lda $01,x; tay; lda ($3c,x); brk"));
+        assert_eq!(cpu.register_y, 0x11);
+        assert_eq!(cpu.register_a, 0x33);
+
+        cpu.load_and_run(asm("ldy #$10; lda ($40),y; brk"));
+        assert_eq!(cpu.register_a, 0x22);
+    }
+
+    #[test]
+    fn test_cycle_counting() {
+        let mut cpu = Cpu::new();
+        // lda #$01 (2); ldx #$ff (2); lda $80f0,x (4 + 1 page cross); brk (7)
+        cpu.load_and_run(vec![0xa9, 0x01, 0xa2, 0xff, 0xbd, 0xf0, 0x80, 0x00]);
+        assert_eq!(cpu.cycles, 16);
+
+        // ldx #$01; dex (2); bne +0 not taken (2); beq +0 taken (3); brk (7)
+        let mut cpu = Cpu::new();
+        cpu.load_and_run(vec![0xa2, 0x01, 0xca, 0xd0, 0x00, 0xf0, 0x00, 0x00]);
+        assert_eq!(cpu.cycles, 16);
+    }
+
+    #[test]
+    fn test_brk_pushes_state_and_jumps_to_irq_vector() {
+        let mut cpu = Cpu::new();
+        let mut prg = vec![0; 0x8000];
+        prg[0x7FFC..0x8000].copy_from_slice(&[0x00, 0x80, 0x34, 0x92]);
+        cpu.bus
+            .insert_cartridge(Box::new(Nrom::new(prg, Vec::new(), Mirroring::Horizontal)));
+        cpu.reset();
+
+        assert!(!cpu.step());
+        assert_eq!(cpu.program_counter, 0x9234);
+        assert_eq!(cpu.stack_pop() & BREAK, BREAK);
+        assert_eq!(cpu.stack_pop_u16(), 0x8002);
+    }
+
+    #[test]
+    fn test_irq_taken_when_enabled() {
+        let mut cpu = Cpu::new();
+        let mut prg = vec![0; 0x8000];
+        // reset: cli; loop: jmp loop
+        prg[0..4].copy_from_slice(&[0x58, 0x4c, 0x01, 0x80]);
+        // irq handler at $9000: inx; lda $4015 (acknowledge); rti
+        prg[0x1000..0x1005].copy_from_slice(&[0xe8, 0xad, 0x15, 0x40, 0x40]);
+        prg[0x7FFC..0x8000].copy_from_slice(&[0x00, 0x80, 0x00, 0x90]);
+        cpu.bus
+            .insert_cartridge(Box::new(Nrom::new(prg, Vec::new(), Mirroring::Horizontal)));
+        cpu.reset();
+
+        while cpu.cycles < 30_000 {
+            cpu.step();
+        }
+
+        assert_eq!(cpu.register_x, 1);
+        assert!(cpu.status & INTERRUPT_DISABLE == 0);
+    }
+}
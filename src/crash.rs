@@ -0,0 +1,242 @@
+// Crash dumps: when the emulator panics mid-game, what's needed to see it
+// happen again, in one text file to attach to a bug report:
+//
+//     nes crash dump
+//     core: 0.1.0
+//     rom: 1A2B3C4D
+//     frame: 1234
+//     panic: attempt to subtract with overflow
+//     at: src/ppu/mod.rs:512:9
+//     cpu: C5F5  A9 07     LDA #$07    A:00 X:00 Y:00 P:24 SP:FD CYC:7
+//
+//     trace:
+//     ...the last lines a --trace-last ring kept
+//
+//     backtrace:
+//     ...
+//
+//     state:
+//     ...the savestate, compressed, in base64
+//
+// `nes game.nes --savestate dump.crash` loads the state back. It's taken
+// once the panic has unwound out of the frame, so it's the machine part
+// way through whatever panicked; the trace says what led up to it.
+//
+// All of it is opt-in. install_hook() adds a panic hook, ahead of the one
+// already there, that notes where the panic was and a backtrace, which
+// the panic's payload doesn't carry; guard() catches the unwind, writes
+// the dump and carries on unwinding. Without the hook a dump has the
+// message alone.
+
+use std::any::Any;
+use std::backtrace::Backtrace;
+use std::cell::RefCell;
+use std::fs;
+use std::io;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+
+use crate::emulator::Emulator;
+use crate::state;
+use crate::trace;
+
+const MAGIC: &str = "nes crash dump";
+const WRAP: usize = 76;
+
+#[derive(Debug, Clone)]
+struct Panicked {
+    location: Option<String>,
+    backtrace: String,
+}
+
+thread_local! {
+    // what the hook saw, for the thread that panicked
+    static PANICKED: RefCell<Option<Panicked>> = const { RefCell::new(None) };
+}
+
+pub fn install_hook() {
+    let previous = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        let panicked = Panicked {
+            location: info.location().map(|location| location.to_string()),
+            backtrace: Backtrace::force_capture().to_string(),
+        };
+        PANICKED.with(|last| *last.borrow_mut() = Some(panicked));
+        previous(info);
+    }));
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CrashDump {
+    pub core: String,
+    pub crc32: u32,
+    pub frame: u64,
+    pub message: String,
+    pub location: Option<String>,
+    // the next instruction, as a trace line
+    pub cpu: Option<String>,
+    pub trace: Vec<String>,
+    pub backtrace: Option<String>,
+    // compressed; None if taking it panicked too
+    pub state: Option<Vec<u8>>,
+}
+
+fn message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "(not a message)".to_string()
+    }
+}
+
+impl CrashDump {
+    // After a panic in `emulator`, with what it panicked with.
+    pub fn capture(emulator: &mut Emulator, payload: &(dyn Any + Send)) -> Self {
+        let panicked = PANICKED.with(|last| last.borrow_mut().take());
+        // the machine may be in no state to be looked at
+        let cpu = panic::catch_unwind(AssertUnwindSafe(|| trace::trace_line(emulator.cpu_mut())));
+        let state =
+            panic::catch_unwind(AssertUnwindSafe(|| state::compress(&emulator.save_state())));
+        let trace = emulator.trace_mut().map_or(Vec::new(), |trace| {
+            trace.recent().map(str::to_string).collect()
+        });
+        CrashDump {
+            core: env!("CARGO_PKG_VERSION").to_string(),
+            crc32: emulator.crc32(),
+            frame: emulator.frame_count(),
+            message: message(payload),
+            location: panicked
+                .as_ref()
+                .and_then(|panicked| panicked.location.clone()),
+            cpu: cpu.ok(),
+            trace,
+            backtrace: panicked.map(|panicked| panicked.backtrace),
+            state: state.ok(),
+        }
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut text = format!(
+            "{}\ncore: {}\nrom: {:08X}\nframe: {}\npanic: {}\n",
+            MAGIC, self.core, self.crc32, self.frame, self.message
+        );
+        if let Some(location) = &self.location {
+            text.push_str(&format!("at: {}\n", location));
+        }
+        if let Some(cpu) = &self.cpu {
+            text.push_str(&format!("cpu: {}\n", cpu));
+        }
+        if !self.trace.is_empty() {
+            text.push_str("\ntrace:\n");
+            for line in &self.trace {
+                text.push_str(line);
+                text.push('\n');
+            }
+        }
+        if let Some(backtrace) = &self.backtrace {
+            text.push_str("\nbacktrace:\n");
+            text.push_str(backtrace.trim_end());
+            text.push('\n');
+        }
+        if let Some(state) = &self.state {
+            text.push_str("\nstate:\n");
+            let encoded = STANDARD.encode(state);
+            for line in encoded.as_bytes().chunks(WRAP) {
+                text.push_str(&String::from_utf8_lossy(line));
+                text.push('\n');
+            }
+        }
+        text
+    }
+
+    // As crash-CRC32-FRAME.crash in `dir`, which it makes.
+    pub fn write(&self, dir: &Path) -> io::Result<PathBuf> {
+        fs::create_dir_all(dir)?;
+        let path = dir.join(format!("crash-{:08X}-{}.crash", self.crc32, self.frame));
+        fs::write(&path, self.to_text())?;
+        Ok(path)
+    }
+}
+
+// The savestate in a dump's text, ready for Emulator::load_state.
+pub fn state(text: &str) -> io::Result<Vec<u8>> {
+    let invalid = |problem: String| io::Error::new(io::ErrorKind::InvalidData, problem);
+    if !text.starts_with(MAGIC) {
+        return Err(invalid("not a crash dump".to_string()));
+    }
+    let Some((_, encoded)) = text.split_once("\nstate:\n") else {
+        return Err(invalid("the crash dump has no state".to_string()));
+    };
+    let encoded: String = encoded.split_whitespace().collect();
+    let state = STANDARD
+        .decode(encoded)
+        .map_err(|err| invalid(err.to_string()))?;
+    state::decompress(&state)
+        .map(|state| state.into_owned())
+        .map_err(|err| invalid(err.to_string()))
+}
+
+// Runs `run` on the emulator; should it panic, writes a dump to `dir`,
+// says so on stderr and carries on unwinding.
+pub fn guard<R>(dir: &Path, emulator: &mut Emulator, run: impl FnOnce(&mut Emulator) -> R) -> R {
+    match panic::catch_unwind(AssertUnwindSafe(|| run(emulator))) {
+        Ok(result) => result,
+        Err(payload) => dump_and_resume(dir, emulator, payload),
+    }
+}
+
+// For a panic already caught, where guard's closure can't be used.
+pub fn dump_and_resume(dir: &Path, emulator: &mut Emulator, payload: Box<dyn Any + Send>) -> ! {
+    let dump = CrashDump::capture(emulator, &*payload);
+    match dump.write(dir) {
+        Ok(path) => eprintln!("crash dump written to {}", path.display()),
+        Err(err) => eprintln!("can't write a crash dump in {}: {}", dir.display(), err),
+    }
+    panic::resume_unwind(payload)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::emulator::test::counting_rom;
+    use crate::trace::{TraceOptions, Tracer};
+
+    #[test]
+    fn test_dumps_what_crashed() {
+        let mut emulator = Emulator::from_rom(&counting_rom()).unwrap();
+        let options = TraceOptions {
+            ring: Some(3),
+            ..TraceOptions::default()
+        };
+        emulator.set_trace(Some(Tracer::with_options(Box::new(io::sink()), options)));
+        let dir = std::env::temp_dir().join(format!("nes-crash-{}", std::process::id()));
+
+        let crashed = panic::catch_unwind(AssertUnwindSafe(|| {
+            guard(&dir, &mut emulator, |emulator| {
+                emulator.run_frames(2);
+                panic!("a bug at frame {}", emulator.frame_count());
+            })
+        }));
+        assert!(crashed.is_err());
+
+        let path = dir.join(format!("crash-{:08X}-2.crash", emulator.crc32()));
+        let text = fs::read_to_string(&path).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(text.starts_with("nes crash dump\n"), "{}", text);
+        assert!(text.contains("\nframe: 2\npanic: a bug at frame 2\n"));
+        assert!(text.contains("\ncpu: 800A  4C 0A 80  JMP $800A"));
+        let trace = text.split_once("\ntrace:\n").unwrap().1;
+        assert_eq!(trace.lines().take_while(|line| !line.is_empty()).count(), 3);
+
+        let state = state(&text).unwrap();
+        let mut again = Emulator::from_rom(&counting_rom()).unwrap();
+        again.load_state(&state).unwrap();
+        assert_eq!(again.state_hash(), emulator.state_hash());
+        assert!(super::state("nes crash dump\npanic: ?\n").is_err());
+    }
+}
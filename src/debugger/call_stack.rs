@@ -0,0 +1,124 @@
+// A shadow of the 6502's call stack, for showing where the game is and
+// for stepping out. JSR, BRK and taken interrupts push a frame, noting
+// the stack pointer from before they pushed. RTS, RTI and TXS drop every
+// frame whose bytes the stack pointer has moved back past, not just the
+// top one, which copes with what games do to the stack: a subroutine that
+// drops its return address with PLA PLA and then returns ends both
+// frames at that RTS, TXS back to the top ends them all, and pushing an
+// address to RTS to leaves the stack pointer below the frame, so it stays.
+//
+// It's only kept while Emulator::run is checking, so after run_frame it
+// can hold frames the game has since left; the next return or TXS run()
+// sees clears those out.
+
+use std::fmt;
+
+type Address = u16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CallKind {
+    Subroutine,
+    Nmi,
+    Irq,
+    Brk,
+}
+
+impl fmt::Display for CallKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CallKind::Subroutine => write!(f, "JSR"),
+            CallKind::Nmi => write!(f, "NMI"),
+            CallKind::Irq => write!(f, "IRQ"),
+            CallKind::Brk => write!(f, "BRK"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CallFrame {
+    pub kind: CallKind,
+    // the JSR or BRK, or the instruction an interrupt came before
+    pub from: Address,
+    // the subroutine or handler
+    pub to: Address,
+    // the stack pointer before the call pushed anything, which it's back
+    // to once it returns
+    pub sp: u8,
+}
+
+impl fmt::Display for CallFrame {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} ${:04X} from ${:04X}", self.kind, self.to, self.from)
+    }
+}
+
+// Deeper than the stack page allows is runaway recursion or garbage.
+const MAX_FRAMES: usize = 128;
+
+#[derive(Debug, Clone, Default)]
+pub struct CallStack {
+    frames: Vec<CallFrame>,
+}
+
+impl CallStack {
+    // Outermost first.
+    pub fn frames(&self) -> &[CallFrame] {
+        &self.frames
+    }
+
+    pub fn depth(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn clear(&mut self) {
+        self.frames.clear();
+    }
+
+    pub(crate) fn push(&mut self, frame: CallFrame) {
+        if self.frames.len() == MAX_FRAMES {
+            self.frames.remove(0);
+        }
+        self.frames.push(frame);
+    }
+
+    // After a return or TXS left the stack pointer at `sp`.
+    pub(crate) fn unwind(&mut self, sp: u8) {
+        while self.frames.last().is_some_and(|frame| sp >= frame.sp) {
+            self.frames.pop();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_frames_go_with_their_stack_bytes() {
+        let frame = |to, sp| CallFrame {
+            kind: CallKind::Subroutine,
+            from: 0x8000,
+            to,
+            sp,
+        };
+        let mut stack = CallStack::default();
+        stack.push(frame(0x8100, 0xFF));
+        stack.push(frame(0x8200, 0xFD));
+        stack.push(frame(0x8300, 0xFB));
+        // an RTS to an address pushed inside the subroutine
+        stack.unwind(0xF9);
+        assert_eq!(stack.depth(), 3);
+        stack.unwind(0xFB);
+        assert_eq!(stack.depth(), 2);
+        // PLA PLA RTS in $8200 returns out of $8100 too
+        stack.unwind(0xFF);
+        assert_eq!(stack.depth(), 0);
+
+        stack.push(frame(0x8100, 0xFF));
+        stack.push(frame(0x8200, 0xFD));
+        // TXS to somewhere in the middle
+        stack.unwind(0xFE);
+        assert_eq!(stack.frames(), [frame(0x8100, 0xFF)]);
+        assert_eq!(frame(0x8100, 0xFF).to_string(), "JSR $8100 from $8000");
+    }
+}
@@ -0,0 +1,172 @@
+// Which bytes of PRG ROM have run, by their place in the ROM rather than
+// the CPU address, so every bank counts on its own however the mapper
+// swaps them in. Emulator::run marks each instruction, opcode and operand,
+// before it runs; like the code log it sees nothing run_frame does.
+//
+// The summary gives the share of the ROM and of each 16K bank that ran;
+// the map has a row for every 64 bytes, # for code that ran and . for
+// the rest, which is where a romhacker would look for paths still
+// unexplored. Tests can use ranges() and percent() to check a test
+// ROM went everywhere they expect.
+
+use std::fmt::Write as _;
+use std::ops::Range;
+
+use crate::bus::Bus;
+use crate::opcodes::OPCODES_MAP;
+
+type Address = u16;
+
+const BANK_SIZE: usize = 0x4000;
+const MAP_ROW: usize = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BankCoverage {
+    pub bank: usize,
+    pub executed: usize,
+    pub size: usize,
+}
+
+impl BankCoverage {
+    pub fn percent(&self) -> f64 {
+        100.0 * self.executed as f64 / self.size.max(1) as f64
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Coverage {
+    executed: Vec<bool>,
+}
+
+impl Coverage {
+    // For a PRG ROM of `size` bytes.
+    pub fn new(size: usize) -> Self {
+        Coverage {
+            executed: vec![false; size],
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        self.executed.len()
+    }
+
+    // Before the instruction at `pc` runs.
+    pub(crate) fn log(&mut self, bus: &mut Bus, pc: Address) {
+        let len = OPCODES_MAP
+            .get(&bus.peek(pc))
+            .map_or(1, |opcode| opcode.len);
+        for i in 0..len as Address {
+            if let Some(offset) = bus.prg_offset(pc.wrapping_add(i)) {
+                if let Some(byte) = self.executed.get_mut(offset) {
+                    *byte = true;
+                }
+            }
+        }
+    }
+
+    pub fn is_executed(&self, offset: usize) -> bool {
+        self.executed.get(offset).copied().unwrap_or(false)
+    }
+
+    pub fn executed(&self) -> usize {
+        self.executed.iter().filter(|&&ran| ran).count()
+    }
+
+    pub fn percent(&self) -> f64 {
+        100.0 * self.executed() as f64 / self.size().max(1) as f64
+    }
+
+    // Runs of bytes that ran, as PRG offsets, in order.
+    pub fn ranges(&self) -> Vec<Range<usize>> {
+        let mut ranges: Vec<Range<usize>> = Vec::new();
+        for (offset, _) in self.executed.iter().enumerate().filter(|(_, &ran)| ran) {
+            match ranges.last_mut() {
+                Some(range) if range.end == offset => range.end += 1,
+                _ => ranges.push(offset..offset + 1),
+            }
+        }
+        ranges
+    }
+
+    pub fn banks(&self) -> Vec<BankCoverage> {
+        self.executed
+            .chunks(BANK_SIZE)
+            .enumerate()
+            .map(|(bank, bytes)| BankCoverage {
+                bank,
+                executed: bytes.iter().filter(|&&ran| ran).count(),
+                size: bytes.len(),
+            })
+            .collect()
+    }
+
+    pub fn summary(&self) -> String {
+        let mut out = format!(
+            "{} of {} PRG bytes ran ({:.1}%)\n",
+            self.executed(),
+            self.size(),
+            self.percent()
+        );
+        for bank in self.banks() {
+            let _ = writeln!(
+                out,
+                "  bank {:02X}: {:5} bytes ({:.1}%)",
+                bank.bank,
+                bank.executed,
+                bank.percent()
+            );
+        }
+        out
+    }
+
+    // A row per 64 bytes, each starting with the bank and the offset in
+    // it.
+    pub fn map(&self) -> String {
+        let mut out = String::new();
+        for (row, bytes) in self.executed.chunks(MAP_ROW).enumerate() {
+            let offset = row * MAP_ROW;
+            let _ = write!(
+                out,
+                "{:02X}:{:04X} ",
+                offset / BANK_SIZE,
+                offset % BANK_SIZE
+            );
+            out.extend(bytes.iter().map(|&ran| if ran { '#' } else { '.' }));
+            out.push('\n');
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::emulator::test::counting_rom;
+    use crate::emulator::Emulator;
+
+    #[test]
+    fn test_marks_what_ran_by_rom_offset() {
+        let mut emulator = Emulator::from_rom(&counting_rom()).unwrap();
+        emulator.start_coverage();
+        emulator.run();
+        emulator.run();
+        let coverage: &Coverage = emulator.debugger().coverage().unwrap();
+        assert_eq!(coverage.size(), 0x8000);
+        // the reset code and the NMI handler, operands and all
+        assert_eq!(coverage.ranges(), [0x0000..0x000D, 0x1000..0x1003]);
+        assert_eq!(coverage.executed(), 16);
+        let banks = coverage.banks();
+        assert_eq!(banks.len(), 2);
+        assert_eq!((banks[0].executed, banks[1].executed), (16, 0));
+        assert!(coverage.summary().contains("bank 01:     0 bytes (0.0%)"));
+
+        let map = coverage.map();
+        assert_eq!(map.lines().count(), 0x8000 / 64);
+        assert!(map.starts_with(&format!("00:0000 {}{}\n", "#".repeat(13), ".".repeat(51))));
+        assert!(map.contains(&format!("00:1000 ###{}\n", ".".repeat(61))));
+        assert!(map.contains("01:0000 ....."));
+
+        assert!(emulator.debugger_mut().stop_coverage().is_some());
+        assert!(!emulator.debugger().is_active());
+    }
+}
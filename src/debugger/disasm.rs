@@ -0,0 +1,366 @@
+// Disassembly for debugger views: a listing around an address, with
+// labels made up for the places code goes to: sub_XXXX for JSR targets,
+// loc_XXXX for jumps and branches.
+//
+// Bytes alone can't tell code from data, so the listing leans on a
+// CodeLog when there is one. Emulator::run fills it in while it's on,
+// marking every instruction that ran and every address an absolute
+// operand read. A line then is Code if it ran, Data if it never did but
+// was read, and Unknown, decoded as if it were code, otherwise. Labels
+// come from everything the log has seen run plus the listing itself,
+// and loaded symbols (symbols.rs) take over from them wherever they say
+// something.
+//
+// The log goes by CPU address, so on a bank-switched board it mixes up
+// whatever banks were mapped in at the time.
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use super::symbols::{self, Symbols};
+use crate::bus::Bus;
+use crate::cpu::AddressingMode;
+use crate::opcodes::{OpCode, OPCODES_MAP};
+use crate::trace;
+
+type Address = u16;
+
+const CODE: u8 = 1;
+// the bytes after an opcode that ran
+const OPERAND: u8 = 2;
+const DATA: u8 = 4;
+
+#[derive(Clone)]
+pub struct CodeLog {
+    flags: Vec<u8>,
+}
+
+impl fmt::Debug for CodeLog {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let code = self
+            .flags
+            .iter()
+            .filter(|&&flags| flags & CODE != 0)
+            .count();
+        f.debug_struct("CodeLog").field("code", &code).finish()
+    }
+}
+
+impl Default for CodeLog {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CodeLog {
+    pub fn new() -> Self {
+        CodeLog {
+            flags: vec![0; 0x10000],
+        }
+    }
+
+    // Before the instruction at `pc` runs.
+    pub(crate) fn log(&mut self, bus: &mut Bus, pc: Address) {
+        let Some(opcode) = OPCODES_MAP.get(&bus.peek(pc)) else {
+            return;
+        };
+        self.flags[pc as usize] |= CODE;
+        for i in 1..opcode.len as Address {
+            self.flags[pc.wrapping_add(i) as usize] |= OPERAND;
+        }
+        let reads = matches!(
+            opcode.mode,
+            AddressingMode::Absolute | AddressingMode::Absolute_X | AddressingMode::Absolute_Y
+        ) && target(opcode, bus, pc).is_none();
+        if reads {
+            let operand =
+                u16::from_le_bytes([bus.peek(pc.wrapping_add(1)), bus.peek(pc.wrapping_add(2))]);
+            self.flags[operand as usize] |= DATA;
+        }
+    }
+
+    pub fn is_code(&self, addr: Address) -> bool {
+        self.flags[addr as usize] & CODE != 0
+    }
+
+    pub fn is_data(&self, addr: Address) -> bool {
+        self.flags[addr as usize] & (CODE | DATA) == DATA
+    }
+
+    fn inside_instruction(&self, addr: Address) -> bool {
+        self.flags[addr as usize] & (CODE | OPERAND) == OPERAND
+    }
+
+    pub fn clear(&mut self) {
+        self.flags.fill(0);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineKind {
+    Code,
+    Data,
+    Unknown,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Line {
+    pub addr: Address,
+    pub bytes: Vec<u8>,
+    pub label: Option<String>,
+    // with addresses written as their labels
+    pub text: String,
+    pub comment: Option<String>,
+    pub kind: LineKind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Target {
+    Sub(Address),
+    Loc(Address),
+}
+
+// Where a JSR, absolute JMP or branch goes.
+fn target(opcode: &OpCode, bus: &mut Bus, addr: Address) -> Option<Target> {
+    let lo = bus.peek(addr.wrapping_add(1));
+    let hi = bus.peek(addr.wrapping_add(2));
+    match (opcode.code, opcode.mode) {
+        (0x20, _) => Some(Target::Sub(u16::from_le_bytes([lo, hi]))),
+        (0x4c, _) => Some(Target::Loc(u16::from_le_bytes([lo, hi]))),
+        (_, AddressingMode::Relative) => Some(Target::Loc(
+            addr.wrapping_add(2).wrapping_add(lo as i8 as u16),
+        )),
+        _ => None,
+    }
+}
+
+fn decode(
+    bus: &mut Bus,
+    log: Option<&CodeLog>,
+    addr: Address,
+) -> (Option<&'static OpCode>, LineKind) {
+    if log.is_some_and(|log| log.is_data(addr)) {
+        return (None, LineKind::Data);
+    }
+    match OPCODES_MAP.get(&bus.peek(addr)) {
+        Some(opcode) if log.is_some_and(|log| log.is_code(addr)) => (Some(opcode), LineKind::Code),
+        Some(opcode) => (Some(opcode), LineKind::Unknown),
+        None => (None, LineKind::Data),
+    }
+}
+
+fn line_len(bus: &mut Bus, log: Option<&CodeLog>, addr: Address) -> usize {
+    decode(bus, log, addr)
+        .0
+        .map_or(1, |opcode| opcode.len as usize)
+}
+
+// Where up to `count` instructions before `addr` start. Goes back as far
+// as they could reach and takes the first starting point whose
+// instructions line up with `addr` without starting inside one that ran.
+fn starts_before(
+    bus: &mut Bus,
+    log: Option<&CodeLog>,
+    addr: Address,
+    count: usize,
+) -> Vec<Address> {
+    let end = addr as usize;
+    for back in (1..=(count * 3).min(end)).rev() {
+        let mut starts = Vec::new();
+        let mut at = end - back;
+        while at < end {
+            if log.is_some_and(|log| log.inside_instruction(at as Address)) {
+                break;
+            }
+            starts.push(at as Address);
+            at += line_len(bus, log, at as Address);
+        }
+        if at == end {
+            let skip = starts.len().saturating_sub(count);
+            return starts.split_off(skip);
+        }
+    }
+    Vec::new()
+}
+
+// Labels for everything the log has seen run. Subroutines win over
+// plain locations.
+pub fn labels(bus: &mut Bus, log: &CodeLog) -> BTreeMap<Address, String> {
+    let mut labels = BTreeMap::new();
+    for addr in 0..=0xFFFF {
+        if log.is_code(addr) {
+            add_label(bus, &mut labels, addr);
+        }
+    }
+    labels
+}
+
+fn add_label(bus: &mut Bus, labels: &mut BTreeMap<Address, String>, addr: Address) {
+    let Some(opcode) = OPCODES_MAP.get(&bus.peek(addr)) else {
+        return;
+    };
+    match target(opcode, bus, addr) {
+        Some(Target::Sub(to)) => {
+            labels.insert(to, format!("sub_{:04X}", to));
+        }
+        Some(Target::Loc(to)) => {
+            labels
+                .entry(to)
+                .or_insert_with(|| format!("loc_{:04X}", to));
+        }
+        None => {}
+    }
+}
+
+// `before` lines leading up to `addr`, then `addr` and `after` - 1 more.
+pub fn around(
+    bus: &mut Bus,
+    log: Option<&CodeLog>,
+    symbols: &Symbols,
+    addr: Address,
+    before: usize,
+    after: usize,
+) -> Vec<Line> {
+    let mut starts = starts_before(bus, log, addr, before);
+    let mut at = addr as usize;
+    for _ in 0..after {
+        if at > 0xFFFF {
+            break;
+        }
+        starts.push(at as Address);
+        at += line_len(bus, log, at as Address);
+    }
+
+    let mut labels = log.map_or_else(BTreeMap::new, |log| labels(bus, log));
+    for &start in &starts {
+        if decode(bus, log, start).1 != LineKind::Data {
+            add_label(bus, &mut labels, start);
+        }
+    }
+    starts
+        .into_iter()
+        .map(|start| line(bus, log, symbols, &labels, start))
+        .collect()
+}
+
+fn line(
+    bus: &mut Bus,
+    log: Option<&CodeLog>,
+    symbols: &Symbols,
+    labels: &BTreeMap<Address, String>,
+    addr: Address,
+) -> Line {
+    let (opcode, kind) = decode(bus, log, addr);
+    let bank = symbols::bank(bus, addr);
+    let (text, len) = match opcode {
+        Some(opcode) => {
+            let (text, len) = trace::disassemble(bus, addr);
+            let mut text = symbols.substitute(bus, &text);
+            if let Some(Target::Sub(to) | Target::Loc(to)) = target(opcode, bus, addr) {
+                if let Some(label) = labels.get(&to) {
+                    text = text.replacen(&format!("${:04X}", to), label, 1);
+                }
+            }
+            (text, len)
+        }
+        None => (format!(".db ${:02X}", bus.peek(addr)), 1),
+    };
+    Line {
+        addr,
+        bytes: (0..len as Address)
+            .map(|i| bus.peek(addr.wrapping_add(i)))
+            .collect(),
+        label: symbols
+            .lookup(addr, bank)
+            .filter(|symbol| symbol.addr == addr)
+            .map(|symbol| symbol.name.clone())
+            .or_else(|| labels.get(&addr).cloned()),
+        text,
+        comment: symbols.comment(addr, bank).map(str::to_string),
+        kind,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::emulator::test::counting_rom;
+    use crate::emulator::Emulator;
+
+    fn summary(lines: &[Line]) -> Vec<(Address, Option<&str>, String, LineKind)> {
+        lines
+            .iter()
+            .map(|line| {
+                (
+                    line.addr,
+                    line.label.as_deref(),
+                    line.text.clone(),
+                    line.kind,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_listing_has_labels_and_knows_code_from_data() {
+        let mut rom = counting_rom();
+        let prg = &mut rom[16..16 + 0x8000];
+        // jsr $8100; jmp $8000
+        prg[..6].copy_from_slice(&[0x20, 0x00, 0x81, 0x4c, 0x00, 0x80]);
+        // $8100: lda $8200; inx; rts
+        prg[0x100..0x105].copy_from_slice(&[0xad, 0x00, 0x82, 0xe8, 0x60]);
+        // $8200: a table that would decode as LDA #$A9
+        prg[0x200..0x202].copy_from_slice(&[0xa9, 0xa9]);
+        let mut emulator = Emulator::from_rom(&rom).unwrap();
+
+        // without a log it's all guesswork
+        let lines = emulator.disassemble_around(0x8200, 0, 1);
+        assert_eq!(
+            (lines[0].kind, lines[0].text.as_str()),
+            (LineKind::Unknown, "LDA #$A9")
+        );
+
+        emulator.debugger_mut().set_code_logging(true);
+        emulator.run();
+        assert_eq!(
+            summary(&emulator.disassemble_around(0x8003, 1, 1)),
+            [
+                (
+                    0x8000,
+                    Some("loc_8000"),
+                    "JSR sub_8100".to_string(),
+                    LineKind::Code
+                ),
+                (0x8003, None, "JMP loc_8000".to_string(), LineKind::Code),
+            ]
+        );
+        let lines = emulator.disassemble_around(0x8104, 2, 1);
+        assert_eq!(
+            summary(&lines),
+            [
+                (
+                    0x8100,
+                    Some("sub_8100"),
+                    "LDA $8200".to_string(),
+                    LineKind::Code
+                ),
+                (0x8103, None, "INX".to_string(), LineKind::Code),
+                (0x8104, None, "RTS".to_string(), LineKind::Code),
+            ]
+        );
+        assert_eq!(lines[0].bytes, [0xad, 0x00, 0x82]);
+        let lines = emulator.disassemble_around(0x8200, 0, 2);
+        assert_eq!(
+            (lines[0].kind, lines[0].text.as_str()),
+            (LineKind::Data, ".db $A9")
+        );
+        assert_eq!(lines[1].addr, 0x8201);
+
+        // lines up on what ran rather than on the operand bytes
+        let lines = emulator.disassemble_around(0x8103, 1, 0);
+        assert_eq!(
+            lines.iter().map(|line| line.addr).collect::<Vec<_>>(),
+            [0x8100]
+        );
+    }
+}
@@ -0,0 +1,418 @@
+// Conditions for breakpoints and watchpoints: a small C-like expression
+// language, parsed once when the condition is set and evaluated each time
+// its breakpoint is reached. A condition that comes out non-zero stops.
+//
+//     A == 0x20 && [$00FE] > 3
+//     scanline >= 240 || !I
+//     value == 0 && PC != $C123
+//
+// Numbers are decimal, 0x hex or $ hex. Names, in any case:
+//
+//     A X Y SP P PC     registers
+//     C Z I D V N       flags, 0 or 1
+//     scanline dot      where the PPU is
+//     frame cycles      frames and CPU cycles so far
+//     addr value        the access a watchpoint saw; 0 for breakpoints
+//
+// [expr] is the byte at that address and {expr} the little-endian word,
+// both read without side effects. Operators, loosest first: || && | ^ &
+// == != < <= > >= + - and the unary ! - ~. Everything works on i64;
+// comparisons and ! give 0 or 1.
+
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::{String, ToString};
+use core::fmt;
+
+use super::watch::WatchHit;
+use crate::cpu::{Cpu, CARRY, DECIMAL_MODE, INTERRUPT_DISABLE, NEGATIVE, OVERFLOW, ZERO};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Var {
+    A,
+    X,
+    Y,
+    Sp,
+    P,
+    Pc,
+    Flag(u8),
+    Scanline,
+    Dot,
+    Frame,
+    Cycles,
+    Addr,
+    Value,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BinOp {
+    Or,
+    And,
+    BitOr,
+    BitXor,
+    BitAnd,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Add,
+    Sub,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum UnOp {
+    Not,
+    Neg,
+    Complement,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Node {
+    Number(i64),
+    Var(Var),
+    Byte(Box<Node>),
+    Word(Box<Node>),
+    Unary(UnOp, Box<Node>),
+    Binary(BinOp, Box<Node>, Box<Node>),
+}
+
+// A parsed condition; Display gives back the text it came from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Expr {
+    source: String,
+    root: Node,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExprError {
+    // in bytes, into the text
+    pub position: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ExprError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at column {}", self.message, self.position + 1)
+    }
+}
+
+impl core::error::Error for ExprError {}
+
+impl Expr {
+    pub fn parse(text: &str) -> Result<Self, ExprError> {
+        let mut parser = Parser { text, at: 0 };
+        let root = parser.expr(0)?;
+        parser.skip_space();
+        if parser.at < text.len() {
+            return Err(parser.error("expected an operator"));
+        }
+        Ok(Expr {
+            source: text.trim().to_string(),
+            root,
+        })
+    }
+
+    pub fn eval(&self, cpu: &mut Cpu, hit: Option<&WatchHit>) -> i64 {
+        eval(&self.root, cpu, hit)
+    }
+
+    pub fn is_true(&self, cpu: &mut Cpu, hit: Option<&WatchHit>) -> bool {
+        self.eval(cpu, hit) != 0
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
+
+fn eval(node: &Node, cpu: &mut Cpu, hit: Option<&WatchHit>) -> i64 {
+    match node {
+        Node::Number(n) => *n,
+        Node::Var(var) => match var {
+            Var::A => cpu.register_a as i64,
+            Var::X => cpu.register_x as i64,
+            Var::Y => cpu.register_y as i64,
+            Var::Sp => cpu.stack_pointer as i64,
+            Var::P => cpu.status as i64,
+            Var::Pc => cpu.program_counter as i64,
+            Var::Flag(flag) => (cpu.status & flag != 0) as i64,
+            Var::Scanline => cpu.bus.ppu.scanline() as i64,
+            Var::Dot => cpu.bus.ppu.dot() as i64,
+            Var::Frame => cpu.bus.ppu.frame_count() as i64,
+            Var::Cycles => cpu.cycles as i64,
+            Var::Addr => hit.map_or(0, |hit| hit.addr as i64),
+            Var::Value => hit.map_or(0, |hit| hit.value as i64),
+        },
+        Node::Byte(addr) => {
+            let addr = eval(addr, cpu, hit) as u16;
+            cpu.bus.peek(addr) as i64
+        }
+        Node::Word(addr) => {
+            let addr = eval(addr, cpu, hit) as u16;
+            let lo = cpu.bus.peek(addr) as i64;
+            let hi = cpu.bus.peek(addr.wrapping_add(1)) as i64;
+            hi << 8 | lo
+        }
+        Node::Unary(op, operand) => {
+            let operand = eval(operand, cpu, hit);
+            match op {
+                UnOp::Not => (operand == 0) as i64,
+                UnOp::Neg => operand.wrapping_neg(),
+                UnOp::Complement => !operand,
+            }
+        }
+        // && and || don't evaluate the right side when the left decides
+        Node::Binary(BinOp::And, left, right) => {
+            (eval(left, cpu, hit) != 0 && eval(right, cpu, hit) != 0) as i64
+        }
+        Node::Binary(BinOp::Or, left, right) => {
+            (eval(left, cpu, hit) != 0 || eval(right, cpu, hit) != 0) as i64
+        }
+        Node::Binary(op, left, right) => {
+            let (l, r) = (eval(left, cpu, hit), eval(right, cpu, hit));
+            match op {
+                BinOp::BitOr => l | r,
+                BinOp::BitXor => l ^ r,
+                BinOp::BitAnd => l & r,
+                BinOp::Eq => (l == r) as i64,
+                BinOp::Ne => (l != r) as i64,
+                BinOp::Lt => (l < r) as i64,
+                BinOp::Le => (l <= r) as i64,
+                BinOp::Gt => (l > r) as i64,
+                BinOp::Ge => (l >= r) as i64,
+                BinOp::Add => l.wrapping_add(r),
+                BinOp::Sub => l.wrapping_sub(r),
+                BinOp::And | BinOp::Or => unreachable!(),
+            }
+        }
+    }
+}
+
+// Binary operators by precedence level, loosest first. Longer spellings
+// come before their prefixes so "<=" isn't read as "<".
+const LEVELS: &[&[(&str, BinOp)]] = &[
+    &[("||", BinOp::Or)],
+    &[("&&", BinOp::And)],
+    &[("|", BinOp::BitOr)],
+    &[("^", BinOp::BitXor)],
+    &[("&", BinOp::BitAnd)],
+    &[("==", BinOp::Eq), ("!=", BinOp::Ne)],
+    &[
+        ("<=", BinOp::Le),
+        (">=", BinOp::Ge),
+        ("<", BinOp::Lt),
+        (">", BinOp::Gt),
+    ],
+    &[("+", BinOp::Add), ("-", BinOp::Sub)],
+];
+
+struct Parser<'a> {
+    text: &'a str,
+    at: usize,
+}
+
+impl Parser<'_> {
+    fn error(&self, message: &str) -> ExprError {
+        ExprError {
+            position: self.at,
+            message: message.to_string(),
+        }
+    }
+
+    fn rest(&self) -> &str {
+        &self.text[self.at..]
+    }
+
+    fn skip_space(&mut self) {
+        let rest = self.rest();
+        self.at += rest.len() - rest.trim_start().len();
+    }
+
+    fn eat(&mut self, token: &str) -> bool {
+        self.skip_space();
+        if self.rest().starts_with(token) {
+            self.at += token.len();
+            return true;
+        }
+        false
+    }
+
+    // The operator at this level, unless it's the start of a longer one
+    // from a looser level ("|" of "||", "&" of "&&").
+    fn operator(&mut self, level: usize) -> Option<BinOp> {
+        self.skip_space();
+        let rest = self.rest();
+        let (token, op) = LEVELS[level]
+            .iter()
+            .find(|(token, _)| rest.starts_with(token))?;
+        let longer = LEVELS[..level]
+            .iter()
+            .flat_map(|ops| ops.iter())
+            .any(|(other, _)| other.len() > token.len() && rest.starts_with(other));
+        if longer {
+            return None;
+        }
+        self.at += token.len();
+        Some(*op)
+    }
+
+    fn expr(&mut self, level: usize) -> Result<Node, ExprError> {
+        if level == LEVELS.len() {
+            return self.unary();
+        }
+        let mut left = self.expr(level + 1)?;
+        while let Some(op) = self.operator(level) {
+            let right = self.expr(level + 1)?;
+            left = Node::Binary(op, Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn unary(&mut self) -> Result<Node, ExprError> {
+        let op = if self.eat("!") {
+            UnOp::Not
+        } else if self.eat("-") {
+            UnOp::Neg
+        } else if self.eat("~") {
+            UnOp::Complement
+        } else {
+            return self.primary();
+        };
+        Ok(Node::Unary(op, Box::new(self.unary()?)))
+    }
+
+    fn primary(&mut self) -> Result<Node, ExprError> {
+        for (open, close, read) in [
+            ("(", ")", None),
+            ("[", "]", Some(false)),
+            ("{", "}", Some(true)),
+        ] {
+            if !self.eat(open) {
+                continue;
+            }
+            let inner = self.expr(0)?;
+            if !self.eat(close) {
+                return Err(self.error(&format!("expected '{}'", close)));
+            }
+            return Ok(match read {
+                None => inner,
+                Some(false) => Node::Byte(Box::new(inner)),
+                Some(true) => Node::Word(Box::new(inner)),
+            });
+        }
+
+        self.skip_space();
+        let start = self.at;
+        let word_len = self
+            .rest()
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '$'))
+            .unwrap_or(self.rest().len());
+        if word_len == 0 {
+            return Err(self.error("expected a number, name or '('"));
+        }
+        let word = &self.text[start..start + word_len];
+        let number = if let Some(hex) = word.strip_prefix('$') {
+            Some(i64::from_str_radix(hex, 16))
+        } else if let Some(hex) = word.strip_prefix("0x").or_else(|| word.strip_prefix("0X")) {
+            Some(i64::from_str_radix(hex, 16))
+        } else if word.starts_with(|c: char| c.is_ascii_digit()) {
+            Some(word.parse())
+        } else {
+            None
+        };
+        let node = match number {
+            Some(Ok(n)) => Node::Number(n),
+            Some(Err(_)) => return Err(self.error(&format!("bad number '{}'", word))),
+            None => Node::Var(
+                variable(word).ok_or_else(|| self.error(&format!("unknown name '{}'", word)))?,
+            ),
+        };
+        self.at += word_len;
+        Ok(node)
+    }
+}
+
+fn variable(name: &str) -> Option<Var> {
+    let var = match name.to_ascii_lowercase().as_str() {
+        "a" => Var::A,
+        "x" => Var::X,
+        "y" => Var::Y,
+        "sp" => Var::Sp,
+        "p" => Var::P,
+        "pc" => Var::Pc,
+        "c" => Var::Flag(CARRY),
+        "z" => Var::Flag(ZERO),
+        "i" => Var::Flag(INTERRUPT_DISABLE),
+        "d" => Var::Flag(DECIMAL_MODE),
+        "v" => Var::Flag(OVERFLOW),
+        "n" => Var::Flag(NEGATIVE),
+        "scanline" => Var::Scanline,
+        "dot" => Var::Dot,
+        "frame" => Var::Frame,
+        "cycles" => Var::Cycles,
+        "addr" => Var::Addr,
+        "value" => Var::Value,
+        _ => return None,
+    };
+    Some(var)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::bus::Mem;
+    use crate::debugger::watch::Access;
+
+    fn eval_str(text: &str, cpu: &mut Cpu) -> i64 {
+        Expr::parse(text).unwrap().eval(cpu, None)
+    }
+
+    #[test]
+    fn test_evaluates_against_the_machine() {
+        let mut cpu = Cpu::new();
+        cpu.register_a = 0x20;
+        cpu.register_x = 3;
+        cpu.status = CARRY | NEGATIVE;
+        cpu.mem_write(0x00FE, 4);
+        cpu.mem_write(0x00FF, 0x12);
+
+        assert_eq!(eval_str("A == 0x20 && [0x00FE] > 3", &mut cpu), 1);
+        assert_eq!(eval_str("a == $21 || [$FE] > 4", &mut cpu), 0);
+        assert_eq!(eval_str("{$FE}", &mut cpu), 0x1204);
+        assert_eq!(eval_str("[$FC + x - 1]", &mut cpu), 4);
+        assert_eq!(eval_str("C && N && !Z", &mut cpu), 1);
+        assert_eq!(eval_str("P & 0x81 | 2 ^ 2", &mut cpu), 0x81);
+        assert_eq!(eval_str("1 + 2 == 3 & 1", &mut cpu), 1);
+        assert_eq!(eval_str("-1 < 0 && ~0 == -1", &mut cpu), 1);
+        assert_eq!(eval_str("10 >= 10 && 9 <= 8", &mut cpu), 0);
+        assert_eq!(eval_str("scanline + dot + frame", &mut cpu), 0);
+
+        let hit = WatchHit {
+            id: 1,
+            addr: 0x0042,
+            value: 7,
+            access: Access::Write,
+        };
+        let expr = Expr::parse(" value == 7 && addr == $42 ").unwrap();
+        assert!(expr.is_true(&mut cpu, Some(&hit)));
+        assert!(!expr.is_true(&mut cpu, None));
+        assert_eq!(expr.to_string(), "value == 7 && addr == $42");
+    }
+
+    #[test]
+    fn test_reports_where_parsing_failed() {
+        let error = |text| Expr::parse(text).unwrap_err();
+        assert_eq!(
+            error("A == ").to_string(),
+            "expected a number, name or '(' at column 6"
+        );
+        assert_eq!(error("[$10").message, "expected ']'");
+        assert_eq!(error("lives > 3").message, "unknown name 'lives'");
+        assert_eq!(error("$XY").message, "bad number '$XY'");
+        assert_eq!(error("A B").position, 2);
+    }
+}
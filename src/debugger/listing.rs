@@ -0,0 +1,473 @@
+// Whole PRG banks disassembled straight from the ROM, for reading a game
+// without running it (`nes disasm`). A listing covers one 16K bank, the
+// unit FCEUX and symbol files (symbols.rs) count in, and the lines are
+// disasm.rs's, with the same sub_XXXX and loc_XXXX labels for what the
+// bank's own code jumps to and loaded symbols ahead of them.
+//
+// Nothing says which address a bank runs at, so it's a guess: the last
+// bank at $C000, where most boards keep it fixed for the vectors, and
+// every other one at $8000. A bank that ends at $FFFF lists its last six
+// bytes as the NMI, reset and IRQ vectors and names what they point to.
+//
+// A CDL file from FCEUX's code/data logger does away with most guessing.
+// It has a byte of flags for every byte of PRG (and then CHR):
+//
+//   bit 0     ran as code
+//   bit 1     read as data
+//   bits 2-3  the 8K window, $8000 + n * $2000, it was mapped in at
+//
+// Bytes logged as data become .db lines, code is decoded where it ran,
+// and the window the bank was first seen in gives its address. The rest
+// is Unknown and decoded as code, as long as an instruction wouldn't run
+// into logged bytes; the listing marks those lines with a ?.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::ops::Range;
+
+use super::disasm::{Line, LineKind};
+use super::symbols::{self, Symbols, BANK_SIZE};
+use crate::cpu::AddressingMode;
+use crate::opcodes::{OpCode, OPCODES_MAP};
+use crate::trace;
+
+type Address = u16;
+
+const CODE: u8 = 1;
+const DATA: u8 = 2;
+const WINDOW: u8 = 0x0C;
+const WINDOW_SIZE: usize = 0x2000;
+
+// data bytes to a .db line
+const DATA_PER_LINE: usize = 8;
+
+const VECTORS: [(&str, &str); 3] = [("nmi", "NMI"), ("reset", "reset"), ("irq", "IRQ")];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CdlError {
+    // shorter than the ROM's PRG
+    TooShort { len: usize, prg: usize },
+}
+
+impl fmt::Display for CdlError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CdlError::TooShort { len, prg } => write!(
+                f,
+                "{} bytes is too short for a CDL of {}K of PRG",
+                len,
+                prg / 1024
+            ),
+        }
+    }
+}
+
+impl std::error::Error for CdlError {}
+
+#[derive(Debug, Clone)]
+pub struct Cdl {
+    prg: Vec<u8>,
+}
+
+impl Cdl {
+    // The PRG part of a .cdl, for a ROM with `prg` bytes of it.
+    pub fn parse(bytes: &[u8], prg: usize) -> Result<Cdl, CdlError> {
+        match bytes.get(..prg) {
+            Some(flags) => Ok(Cdl {
+                prg: flags.to_vec(),
+            }),
+            None => Err(CdlError::TooShort {
+                len: bytes.len(),
+                prg,
+            }),
+        }
+    }
+
+    pub fn is_code(&self, offset: usize) -> bool {
+        self.prg[offset] & CODE != 0
+    }
+
+    pub fn is_data(&self, offset: usize) -> bool {
+        self.prg[offset] & (CODE | DATA) == DATA
+    }
+
+    fn is_logged(&self, offset: usize) -> bool {
+        self.prg[offset] & (CODE | DATA) != 0
+    }
+
+    // Where the bank covering `offsets` was mapped, going by its first
+    // logged byte. None if nothing was, or if that doesn't give a place
+    // for the whole bank.
+    fn base(&self, offsets: Range<usize>) -> Option<Address> {
+        let start = offsets.start;
+        let offset = offsets.clone().find(|&offset| self.is_logged(offset))?;
+        let window = ((self.prg[offset] & WINDOW) >> 2) as usize;
+        let addr = 0x8000 + window * WINDOW_SIZE + offset % WINDOW_SIZE;
+        let base = addr.checked_sub(offset - start)?;
+        (0x8000..=0x10000 - offsets.len())
+            .contains(&base)
+            .then_some(base as Address)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Bank {
+    pub number: usize,
+    pub base: Address,
+    pub lines: Vec<Line>,
+    // whether a CDL said what's code and what's data
+    pub logged: bool,
+}
+
+impl Bank {
+    pub fn end(&self) -> Address {
+        self.lines.last().map_or(self.base, |line| {
+            line.addr + (line.bytes.len().max(1) - 1) as Address
+        })
+    }
+}
+
+impl fmt::Display for Bank {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "; PRG bank {} (${:X}), ${:04X}-${:04X}",
+            self.number,
+            self.number,
+            self.base,
+            self.end()
+        )?;
+        writeln!(f, ".org ${:04X}", self.base)?;
+        for line in &self.lines {
+            if let Some(label) = &line.label {
+                writeln!(f)?;
+                writeln!(f, "{}:", label)?;
+            }
+            // a .db line has its bytes in the text already
+            let bytes = match line.text.starts_with(".db") {
+                true => String::new(),
+                false => line
+                    .bytes
+                    .iter()
+                    .map(|byte| format!("{:02X}", byte))
+                    .collect::<Vec<_>>()
+                    .join(" "),
+            };
+            let unknown = self.logged && line.kind == LineKind::Unknown;
+            let text = format!(
+                "{:04X}  {:<8} {} {}",
+                line.addr,
+                bytes,
+                if unknown { '?' } else { ' ' },
+                line.text
+            );
+            match &line.comment {
+                Some(comment) => {
+                    // comments over several lines get a ; each
+                    let comment = comment.replace('\n', "\n; ");
+                    writeln!(f, "{:<40} ; {}", text, comment)?
+                }
+                None => writeln!(f, "{}", text.trim_end())?,
+            }
+        }
+        Ok(())
+    }
+}
+
+// A listing for every 16K bank of `prg`.
+pub fn disassemble_prg(prg: &[u8], cdl: Option<&Cdl>, symbols: &Symbols) -> Vec<Bank> {
+    let banks = prg.len().div_ceil(BANK_SIZE);
+    (0..banks)
+        .map(|number| {
+            let offsets = number * BANK_SIZE..((number + 1) * BANK_SIZE).min(prg.len());
+            let base = cdl
+                .and_then(|cdl| cdl.base(offsets.clone()))
+                .unwrap_or(if number + 1 == banks { 0xC000 } else { 0x8000 });
+            disassemble_bank(prg, number, base, cdl, symbols)
+        })
+        .collect()
+}
+
+enum Item {
+    Instruction(&'static OpCode),
+    Byte,
+}
+
+// The bank `number` of `prg`, taken to be mapped at `base`.
+pub fn disassemble_bank(
+    prg: &[u8],
+    number: usize,
+    base: Address,
+    cdl: Option<&Cdl>,
+    symbols: &Symbols,
+) -> Bank {
+    let start = number * BANK_SIZE;
+    let bytes = &prg[start..(start + BANK_SIZE).min(prg.len())];
+    let addr = |i: usize| base.wrapping_add(i as Address);
+    let in_bank =
+        |to: Address| (to as usize) >= base as usize && ((to - base) as usize) < bytes.len();
+    let kind = |i: usize| match cdl {
+        Some(cdl) if cdl.is_code(start + i) => LineKind::Code,
+        Some(cdl) if cdl.is_data(start + i) => LineKind::Data,
+        _ => LineKind::Unknown,
+    };
+    let vectors = base as usize + bytes.len() == 0x10000 && bytes.len() >= 6;
+    let end = if vectors {
+        bytes.len() - 6
+    } else {
+        bytes.len()
+    };
+
+    // what each byte starts, and the labels the code asks for
+    let mut items = Vec::new();
+    let mut labels = BTreeMap::new();
+    let mut i = 0;
+    while i < end {
+        let opcode = match kind(i) {
+            LineKind::Data => None,
+            LineKind::Code => OPCODES_MAP
+                .get(&bytes[i])
+                .filter(|op| i + op.len as usize <= end),
+            // only if it stays clear of what was logged
+            LineKind::Unknown => OPCODES_MAP.get(&bytes[i]).filter(|op| {
+                i + op.len as usize <= end
+                    && (1..op.len as usize).all(|n| kind(i + n) == LineKind::Unknown)
+            }),
+        };
+        match opcode {
+            Some(opcode) => {
+                if let Some(target) = target(opcode, bytes, i, addr(i)) {
+                    let (to, label) = match target {
+                        Target::Sub(to) => (to, format!("sub_{:04X}", to)),
+                        Target::Loc(to) => (to, format!("loc_{:04X}", to)),
+                    };
+                    if in_bank(to) {
+                        match target {
+                            Target::Sub(_) => {
+                                labels.insert(to, label);
+                            }
+                            Target::Loc(_) => {
+                                labels.entry(to).or_insert(label);
+                            }
+                        }
+                    }
+                }
+                items.push((i, Item::Instruction(opcode)));
+                i += opcode.len as usize;
+            }
+            None => {
+                items.push((i, Item::Byte));
+                i += 1;
+            }
+        }
+    }
+    let vector = |n: usize| {
+        let at = end + n * 2;
+        u16::from_le_bytes([bytes[at], bytes[at + 1]])
+    };
+    if vectors {
+        // backwards, so NMI's name wins when IRQ shares its handler
+        for (n, (name, _)) in VECTORS.iter().enumerate().rev() {
+            if in_bank(vector(n)) {
+                labels.insert(vector(n), name.to_string());
+            }
+        }
+    }
+
+    let name = |to: Address| {
+        let bank = in_bank(to).then_some(number);
+        symbols
+            .name_in(to, bank)
+            .or_else(|| labels.get(&to).cloned())
+    };
+    let label = |at: Address| {
+        symbols
+            .lookup(at, Some(number))
+            .filter(|symbol| symbol.addr == at)
+            .map(|symbol| symbol.name.clone())
+            .or_else(|| labels.get(&at).cloned())
+    };
+    let line = |i: usize, len: usize, text: String, kind: LineKind| Line {
+        addr: addr(i),
+        bytes: bytes[i..i + len].to_vec(),
+        label: label(addr(i)),
+        text,
+        comment: symbols.comment(addr(i), Some(number)).map(str::to_string),
+        kind,
+    };
+
+    let mut lines: Vec<Line> = Vec::new();
+    for (i, item) in items {
+        match item {
+            Item::Instruction(opcode) => {
+                let [lo, hi] = [1, 2].map(|n| bytes.get(i + n).copied().unwrap_or(0));
+                let text = trace::format_operand(opcode, addr(i), lo, hi);
+                let text = symbols::substitute_with(&text, name);
+                lines.push(line(i, opcode.len as usize, text, kind(i)));
+            }
+            Item::Byte => {
+                // onto the line before if that's bytes too and has room
+                if let Some(last) = lines.last_mut() {
+                    let follows = last.addr.wrapping_add(last.bytes.len() as Address) == addr(i);
+                    if follows
+                        && last.text.starts_with(".db")
+                        && last.kind == kind(i)
+                        && last.bytes.len() < DATA_PER_LINE
+                        && label(addr(i)).is_none()
+                        && symbols.comment(addr(i), Some(number)).is_none()
+                    {
+                        last.bytes.push(bytes[i]);
+                        last.text.push_str(&format!(",${:02X}", bytes[i]));
+                        continue;
+                    }
+                }
+                let text = format!(".db ${:02X}", bytes[i]);
+                lines.push(line(i, 1, text, kind(i)));
+            }
+        }
+    }
+    if vectors {
+        for (n, (_, what)) in VECTORS.iter().enumerate() {
+            let to = vector(n);
+            let text = format!(".dw {}", name(to).unwrap_or_else(|| format!("${:04X}", to)));
+            let mut line = line(end + n * 2, 2, text, LineKind::Data);
+            line.comment
+                .get_or_insert_with(|| format!("{} vector", what));
+            lines.push(line);
+        }
+    }
+    Bank {
+        number,
+        base,
+        lines,
+        logged: cdl.is_some(),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Target {
+    Sub(Address),
+    Loc(Address),
+}
+
+// Where a JSR, absolute JMP or branch at bytes[i] goes.
+fn target(opcode: &OpCode, bytes: &[u8], i: usize, addr: Address) -> Option<Target> {
+    let lo = bytes[i + 1..].first().copied().unwrap_or(0);
+    let hi = bytes[i + 1..].get(1).copied().unwrap_or(0);
+    match (opcode.code, opcode.mode) {
+        (0x20, _) => Some(Target::Sub(u16::from_le_bytes([lo, hi]))),
+        (0x4c, _) => Some(Target::Loc(u16::from_le_bytes([lo, hi]))),
+        (_, AddressingMode::Relative) => Some(Target::Loc(
+            addr.wrapping_add(2).wrapping_add(lo as i8 as u16),
+        )),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn summary(bank: &Bank, addr: Address) -> (Option<&str>, &str, LineKind) {
+        let line = bank.lines.iter().find(|line| line.addr == addr).unwrap();
+        (line.label.as_deref(), line.text.as_str(), line.kind)
+    }
+
+    fn prg() -> Vec<u8> {
+        let mut prg = vec![0xea; 2 * BANK_SIZE];
+        let bank = &mut prg[BANK_SIZE..];
+        // $C000: jsr $C010; jmp $C000
+        bank[..6].copy_from_slice(&[0x20, 0x10, 0xc0, 0x4c, 0x00, 0xc0]);
+        // $C010: lda $C020,x; rts
+        bank[0x10..0x14].copy_from_slice(&[0xbd, 0x20, 0xc0, 0x60]);
+        // $C020: a table that would decode as LDA #$A9
+        bank[0x20..0x24].copy_from_slice(&[0xa9, 0xa9, 0x0a, 0xea]);
+        // $C030: rti
+        bank[0x30] = 0x40;
+        bank[BANK_SIZE - 6..].copy_from_slice(&[0x30, 0xc0, 0x00, 0xc0, 0x30, 0xc0]);
+        prg
+    }
+
+    #[test]
+    fn test_banks_without_a_cdl() {
+        let banks = disassemble_prg(&prg(), None, &Symbols::new());
+        assert_eq!(
+            banks
+                .iter()
+                .map(|bank| (bank.base, bank.end()))
+                .collect::<Vec<_>>(),
+            [(0x8000, 0xBFFF), (0xC000, 0xFFFF)]
+        );
+        let bank = &banks[1];
+        assert_eq!(
+            summary(bank, 0xC000),
+            (Some("reset"), "JSR sub_C010", LineKind::Unknown)
+        );
+        assert_eq!(
+            summary(bank, 0xC003),
+            (None, "JMP reset", LineKind::Unknown)
+        );
+        assert_eq!(
+            summary(bank, 0xC010),
+            (Some("sub_C010"), "LDA $C020,X", LineKind::Unknown)
+        );
+        // guesswork without a log
+        assert_eq!(summary(bank, 0xC020), (None, "LDA #$A9", LineKind::Unknown));
+        assert_eq!(summary(bank, 0xFFFC), (None, ".dw reset", LineKind::Data));
+
+        let text = bank.to_string();
+        assert!(text.starts_with("; PRG bank 1 ($1), $C000-$FFFF\n.org $C000\n\nreset:\n"));
+        assert!(text.contains("\nC003  4C 00 C0   JMP reset\n"));
+        assert!(text.contains("\nnmi:\nC030  40         RTI\n"));
+        assert!(text.ends_with("FFFE  30 C0      .dw nmi                 ; IRQ vector\n"));
+    }
+
+    #[test]
+    fn test_a_cdl_separates_code_from_data() {
+        let mut prg = prg();
+        prg[BANK_SIZE + 0x0f] = 0xa9;
+        // all of bank 1 seen at $C000-$FFFF, the $E000 half in window 3
+        let mut log = vec![0; prg.len() + 0x2000];
+        let at = |addr: usize| BANK_SIZE + addr - 0xC000;
+        for addr in (0xC000..0xC006).chain(0xC010..0xC014).chain([0xC030]) {
+            log[at(addr)] = CODE | 0x08;
+        }
+        log[at(0xC020)] = DATA | 0x08;
+        log[at(0xC021)] = DATA | 0x08;
+        // bank 0 only ever at $C000 too
+        log[0x100] = CODE | 0x08;
+        let cdl = Cdl::parse(&log, prg.len()).unwrap();
+
+        let mut symbols = Symbols::new();
+        symbols
+            .load_nl("$C010#update#keeps time\n", Some(1))
+            .unwrap();
+        let banks = disassemble_prg(&prg, Some(&cdl), &symbols);
+        assert_eq!(banks[0].base, 0xC000);
+        let bank = &banks[1];
+        assert_eq!(
+            summary(bank, 0xC000),
+            (Some("reset"), "JSR update", LineKind::Code)
+        );
+        assert_eq!(
+            summary(bank, 0xC010),
+            (Some("update"), "LDA $C020,X", LineKind::Code)
+        );
+        assert_eq!(summary(bank, 0xC020), (None, ".db $A9,$A9", LineKind::Data));
+        assert_eq!(summary(bank, 0xC022), (None, "ASL A", LineKind::Unknown));
+        // would run into logged code
+        assert_eq!(summary(bank, 0xC00F), (None, ".db $A9", LineKind::Unknown));
+
+        let text = bank.to_string();
+        assert!(text.contains("\nupdate:\nC010  BD 20 C0   LDA $C020,X             ; keeps time\n"));
+        assert!(text.contains("\nC020             .db $A9,$A9\nC022  0A       ? ASL A\n"));
+
+        assert_eq!(
+            Cdl::parse(&log[..100], prg.len()).unwrap_err(),
+            CdlError::TooShort {
+                len: 100,
+                prg: prg.len()
+            }
+        );
+    }
+}
@@ -0,0 +1,212 @@
+// Memory editing for debug UIs. Emulator::read_memory and write_memory
+// go straight at the machine; a MemoryPort is the same thing for a UI on
+// another thread. It queues writes and keeps copies of the ranges it was
+// asked to show. The emulator brings both up to date in sync_memory,
+// which each run_frame and run starts with, so a port works while the
+// game plays and, when the front end calls sync_memory itself, while
+// it's paused. The machine only changes between instructions.
+//
+// The CPU space is the bus: reads don't disturb anything (see Bus::peek)
+// but writes do what a CPU write would, registers and bank switches
+// included. The others are plain memory. PPU space goes through the
+// cartridge for pattern tables, so CHR RAM takes writes and CHR ROM
+// ignores them, and palette entries read back as stored, whatever
+// greyscale says. PRG RAM is the cartridge's work RAM, unbanked.
+
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use crate::bus::{Bus, Mem};
+
+type Address = u16;
+type Value = u8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MemorySpace {
+    Cpu,
+    Ppu,
+    Oam,
+    PrgRam,
+}
+
+impl MemorySpace {
+    // Addresses past this wrap.
+    pub fn size(self, bus: &Bus) -> usize {
+        match self {
+            MemorySpace::Cpu => 0x10000,
+            MemorySpace::Ppu => 0x4000,
+            MemorySpace::Oam => 0x100,
+            MemorySpace::PrgRam => bus.cartridge_ram().len(),
+        }
+    }
+}
+
+pub(crate) fn read(bus: &mut Bus, space: MemorySpace, addr: Address) -> Value {
+    match space {
+        MemorySpace::Cpu => bus.peek(addr),
+        MemorySpace::Ppu => bus.peek_ppu(addr),
+        MemorySpace::Oam => bus.ppu.oam()[addr as usize % 0x100],
+        MemorySpace::PrgRam => {
+            let ram = bus.cartridge_ram();
+            match ram.len() {
+                0 => 0,
+                len => ram[addr as usize % len],
+            }
+        }
+    }
+}
+
+pub(crate) fn write(bus: &mut Bus, space: MemorySpace, addr: Address, value: Value) {
+    match space {
+        MemorySpace::Cpu => bus.mem_write(addr, value),
+        MemorySpace::Ppu => bus.poke_ppu(addr, value),
+        MemorySpace::Oam => bus.ppu.oam_mut()[addr as usize % 0x100] = value,
+        MemorySpace::PrgRam => {
+            let ram = bus.cartridge_ram_mut();
+            let len = ram.len();
+            if len > 0 {
+                ram[addr as usize % len] = value;
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+struct View {
+    id: usize,
+    space: MemorySpace,
+    start: Address,
+    len: usize,
+    data: Vec<Value>,
+}
+
+#[derive(Debug, Default)]
+struct Shared {
+    writes: Vec<(MemorySpace, Address, Value)>,
+    views: Vec<View>,
+    next_id: usize,
+}
+
+// Clones share one queue; hand one to each thread that needs it.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryPort {
+    shared: Arc<Mutex<Shared>>,
+}
+
+impl MemoryPort {
+    fn lock(&self) -> MutexGuard<'_, Shared> {
+        // a UI thread that panicked mid-call leaves nothing half done
+        self.shared
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    // Lands at the next sync, in the order written.
+    pub fn write(&self, space: MemorySpace, addr: Address, values: &[Value]) {
+        let mut shared = self.lock();
+        for (i, &value) in values.iter().enumerate() {
+            shared
+                .writes
+                .push((space, addr.wrapping_add(i as Address), value));
+        }
+    }
+
+    // `len` bytes from `start`, copied at every sync until closed. Returns
+    // the id to read it by.
+    pub fn open_view(&self, space: MemorySpace, start: Address, len: usize) -> usize {
+        let mut shared = self.lock();
+        shared.next_id += 1;
+        let id = shared.next_id;
+        shared.views.push(View {
+            id,
+            space,
+            start,
+            len,
+            data: Vec::new(),
+        });
+        id
+    }
+
+    pub fn close_view(&self, id: usize) {
+        self.lock().views.retain(|view| view.id != id);
+    }
+
+    // The copy from the last sync: empty before the first one, or for an
+    // id that isn't open.
+    pub fn view(&self, id: usize) -> Vec<Value> {
+        self.lock()
+            .views
+            .iter()
+            .find(|view| view.id == id)
+            .map_or_else(Vec::new, |view| view.data.clone())
+    }
+
+    pub(crate) fn sync(&self, bus: &mut Bus) {
+        let mut shared = self.lock();
+        for (space, addr, value) in std::mem::take(&mut shared.writes) {
+            write(bus, space, addr, value);
+        }
+        for view in shared.views.iter_mut() {
+            view.data.clear();
+            for i in 0..view.len {
+                let addr = view.start.wrapping_add(i as Address);
+                view.data.push(read(bus, view.space, addr));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::emulator::test::counting_rom;
+    use crate::emulator::Emulator;
+
+    #[test]
+    fn test_reads_and_writes_each_space() {
+        let mut emulator = Emulator::from_rom(&counting_rom()).unwrap();
+        emulator.write_memory(MemorySpace::Cpu, 0x0842, 7);
+        assert_eq!(emulator.read_memory(MemorySpace::Cpu, 0x0042), 7);
+        // PRG ROM ignores writes
+        emulator.write_memory(MemorySpace::Cpu, 0x8000, 0);
+        assert_eq!(emulator.read_memory(MemorySpace::Cpu, 0x8000), 0xa9);
+
+        // the counting ROM's nametables are mirrored horizontally
+        emulator.write_memory(MemorySpace::Ppu, 0x2001, 0x11);
+        assert_eq!(emulator.read_memory(MemorySpace::Ppu, 0x2401), 0x11);
+        emulator.write_memory(MemorySpace::Ppu, 0x3F10, 0x30);
+        assert_eq!(emulator.read_memory(MemorySpace::Ppu, 0x3F00), 0x30);
+
+        emulator.write_memory(MemorySpace::Oam, 0x104, 0x55);
+        assert_eq!(emulator.cpu().bus.ppu.oam()[4], 0x55);
+        emulator.write_memory(MemorySpace::PrgRam, 0x10, 1);
+        assert_eq!(emulator.cpu().bus.cartridge_ram()[0x10], 1);
+        assert_eq!(MemorySpace::PrgRam.size(&emulator.cpu().bus), 0x2000);
+    }
+
+    #[test]
+    fn test_port_syncs_with_another_thread() {
+        let mut emulator = Emulator::from_rom(&counting_rom()).unwrap();
+        let port = emulator.memory_port();
+        let (port, view) = std::thread::spawn(move || {
+            let view = port.open_view(MemorySpace::Cpu, 0x0000, 2);
+            port.write(MemorySpace::Cpu, 0x0001, &[9]);
+            // nothing until the emulator syncs
+            assert!(port.view(view).is_empty());
+            (port, view)
+        })
+        .join()
+        .unwrap();
+
+        // synced as the frame starts, so it's a frame behind
+        emulator.run_frame();
+        emulator.run_frame();
+        assert_eq!(port.view(view), [0, 9]);
+        // and the NMI handler has counted one by now
+        emulator.sync_memory();
+        assert_eq!(port.view(view), [1, 9]);
+
+        port.close_view(view);
+        emulator.sync_memory();
+        assert!(port.view(view).is_empty());
+    }
+}
@@ -0,0 +1,804 @@
+// The debugger's side of the emulator: what should stop it and why it
+// stopped. Emulator::run checks before every instruction, after any
+// pending interrupt has moved the PC to its handler, so a breakpoint on
+// an NMI routine is hit at its first instruction. run_frame ignores all
+// of this, so front ends that only play games pay for none of it beyond
+// an empty check.
+//
+// Stopping leaves the machine just before the instruction at the
+// breakpoint. The next run starts by executing it rather than stopping
+// again straight away. Watchpoints (watch.rs) stop just after the
+// instruction that made the access, and PPU breakpoints (ppu_events.rs)
+// just after the one during which the PPU got there. Any of them can have
+// a condition (expr.rs) and then only stops when it holds.
+//
+// Stepping goes by the shadow call stack (call_stack.rs): over a JSR
+// runs until the stack is back to where it was, out until it's one
+// shorter. Any other stop cancels a step. The profiler (profiler.rs)
+// samples by the same stack. The event timeline (timeline.rs) shares the
+// watch hook without ever stopping.
+//
+// A build without std has only the watch hook and its conditions, which
+// the bus needs; the debugger itself belongs to the front ends.
+
+#[cfg(feature = "std")]
+pub mod call_stack;
+#[cfg(feature = "std")]
+pub mod coverage;
+#[cfg(feature = "std")]
+pub mod disasm;
+pub mod expr;
+#[cfg(feature = "std")]
+pub mod listing;
+#[cfg(feature = "std")]
+pub mod memory;
+#[cfg(feature = "std")]
+pub mod ppu_events;
+#[cfg(feature = "std")]
+pub mod profiler;
+#[cfg(feature = "std")]
+pub mod state_diff;
+#[cfg(feature = "std")]
+pub mod symbols;
+#[cfg(feature = "std")]
+pub mod timeline;
+pub mod watch;
+
+#[cfg(feature = "std")]
+use std::fmt;
+
+#[cfg(feature = "std")]
+use crate::bus::Bus;
+#[cfg(feature = "std")]
+use crate::cpu::Cpu;
+#[cfg(feature = "std")]
+use call_stack::{CallFrame, CallKind, CallStack};
+#[cfg(feature = "std")]
+use coverage::Coverage;
+#[cfg(feature = "std")]
+use disasm::CodeLog;
+#[cfg(feature = "std")]
+use expr::Expr;
+#[cfg(feature = "std")]
+use ppu_events::{PpuBreakpoint, PpuEvent, PpuPosition};
+#[cfg(feature = "std")]
+use profiler::Profiler;
+#[cfg(feature = "std")]
+use symbols::Symbols;
+#[cfg(feature = "std")]
+use timeline::Timeline;
+#[cfg(feature = "std")]
+use watch::{Access, WatchHit, WatchHook, WatchKind, Watchpoint};
+
+#[cfg(feature = "std")]
+type Address = u16;
+
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Breakpoint {
+    pub id: usize,
+    pub addr: Address,
+    pub enabled: bool,
+    pub condition: Option<Expr>,
+}
+
+// Why run returned before the frame was finished.
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopReason {
+    Breakpoint {
+        id: usize,
+        pc: Address,
+    },
+    // `pc` is the instruction that made the access
+    Watchpoint {
+        id: usize,
+        pc: Address,
+        addr: Address,
+        value: u8,
+        access: Access,
+    },
+    // `pc` is where the step ended, the next instruction to run
+    Step {
+        pc: Address,
+    },
+    // `pc` is the instruction during which it happened; `scanline` and
+    // `dot` are where the PPU got to by the end of it
+    Ppu {
+        id: usize,
+        pc: Address,
+        event: PpuEvent,
+        scanline: u16,
+        dot: u16,
+    },
+}
+
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StepMode {
+    // one instruction, following JSRs and interrupts
+    Into,
+    // one instruction, but a JSR (or an interrupt taken on the way) runs
+    // until it has returned
+    Over,
+    // until the current subroutine or interrupt handler returns
+    Out,
+}
+
+#[cfg(feature = "std")]
+impl fmt::Display for StopReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StopReason::Breakpoint { id, pc } => write!(f, "breakpoint {} at ${:04X}", id, pc),
+            StopReason::Watchpoint {
+                id,
+                pc,
+                addr,
+                value,
+                access,
+            } => write!(
+                f,
+                "watchpoint {}: {} of ${:02X} at ${:04X} by ${:04X}",
+                id, access, value, addr, pc
+            ),
+            StopReason::Step { pc } => write!(f, "step to ${:04X}", pc),
+            StopReason::Ppu {
+                id,
+                pc,
+                event,
+                scanline,
+                dot,
+            } => write!(
+                f,
+                "PPU breakpoint {} ({}) at scanline {} dot {} by ${:04X}",
+                id, event, scanline, dot, pc
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RunStatus {
+    // the PPU finished a picture
+    FrameDone,
+    Stopped(StopReason),
+}
+
+#[cfg(feature = "std")]
+#[derive(Debug, Default)]
+pub struct Debugger {
+    breakpoints: Vec<Breakpoint>,
+    watchpoints: Vec<Watchpoint>,
+    ppu_breakpoints: Vec<PpuBreakpoint>,
+    // shared by all three kinds
+    next_id: usize,
+    // where the last stop was, so resuming doesn't stop there again
+    stopped_at: Option<Address>,
+    calls: CallStack,
+    // the pending step and the call depth it started at
+    step: Option<(StepMode, usize)>,
+    code_log: Option<Box<CodeLog>>,
+    profiler: Option<Box<Profiler>>,
+    coverage: Option<Box<Coverage>>,
+    timeline: Option<Box<Timeline>>,
+    symbols: Symbols,
+}
+
+#[cfg(feature = "std")]
+impl Debugger {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Returns the new breakpoint's id. Ids aren't reused.
+    pub fn add_breakpoint(&mut self, addr: Address) -> usize {
+        self.next_id += 1;
+        self.breakpoints.push(Breakpoint {
+            id: self.next_id,
+            addr,
+            enabled: true,
+            condition: None,
+        });
+        self.next_id
+    }
+
+    // `start` to `end` inclusive; returns the id like add_breakpoint.
+    pub fn add_watchpoint(&mut self, start: Address, end: Address, kind: WatchKind) -> usize {
+        self.next_id += 1;
+        self.watchpoints.push(Watchpoint {
+            id: self.next_id,
+            start: start.min(end),
+            end: start.max(end),
+            kind,
+            enabled: true,
+            condition: None,
+        });
+        self.next_id
+    }
+
+    // Stops on a PPU event (ppu_events.rs); returns the id like
+    // add_breakpoint.
+    pub fn add_ppu_breakpoint(&mut self, event: PpuEvent) -> usize {
+        self.next_id += 1;
+        self.ppu_breakpoints.push(PpuBreakpoint {
+            id: self.next_id,
+            event,
+            enabled: true,
+            condition: None,
+        });
+        self.next_id
+    }
+
+    fn count(&self) -> usize {
+        self.breakpoints.len() + self.watchpoints.len() + self.ppu_breakpoints.len()
+    }
+
+    // Any kind.
+    pub fn remove(&mut self, id: usize) -> bool {
+        let before = self.count();
+        self.breakpoints.retain(|breakpoint| breakpoint.id != id);
+        self.watchpoints.retain(|watch| watch.id != id);
+        self.ppu_breakpoints.retain(|ppu| ppu.id != id);
+        self.count() != before
+    }
+
+    // The enabled flag and condition of whichever kind has `id`.
+    fn settings(&mut self, id: usize) -> Option<(&mut bool, &mut Option<Expr>)> {
+        if let Some(b) = self.breakpoints.iter_mut().find(|b| b.id == id) {
+            return Some((&mut b.enabled, &mut b.condition));
+        }
+        if let Some(w) = self.watchpoints.iter_mut().find(|w| w.id == id) {
+            return Some((&mut w.enabled, &mut w.condition));
+        }
+        let p = self.ppu_breakpoints.iter_mut().find(|p| p.id == id)?;
+        Some((&mut p.enabled, &mut p.condition))
+    }
+
+    pub fn set_enabled(&mut self, id: usize, enabled: bool) -> bool {
+        self.settings(id).map(|(flag, _)| *flag = enabled).is_some()
+    }
+
+    // None makes it unconditional again.
+    pub fn set_condition(&mut self, id: usize, condition: Option<Expr>) -> bool {
+        self.settings(id)
+            .map(|(_, expr)| *expr = condition)
+            .is_some()
+    }
+
+    pub fn clear(&mut self) {
+        self.breakpoints.clear();
+        self.watchpoints.clear();
+        self.ppu_breakpoints.clear();
+    }
+
+    pub fn ppu_breakpoints(&self) -> &[PpuBreakpoint] {
+        &self.ppu_breakpoints
+    }
+
+    pub fn breakpoints(&self) -> &[Breakpoint] {
+        &self.breakpoints
+    }
+
+    pub fn watchpoints(&self) -> &[Watchpoint] {
+        &self.watchpoints
+    }
+
+    // Whether there's anything to check at all.
+    pub fn is_active(&self) -> bool {
+        self.count() > 0
+            || self.step.is_some()
+            || self.code_log.is_some()
+            || self.profiler.is_some()
+            || self.coverage.is_some()
+            || self.timeline.is_some()
+    }
+
+    // What ran and what was read as data, for disassembly (disasm.rs).
+    // Logging keeps run checking every instruction; turning it off drops
+    // the log.
+    pub fn set_code_logging(&mut self, on: bool) {
+        match (on, self.code_log.is_some()) {
+            (true, false) => self.code_log = Some(Box::default()),
+            (false, true) => self.code_log = None,
+            _ => {}
+        }
+    }
+
+    pub fn code_log(&self) -> Option<&CodeLog> {
+        self.code_log.as_deref()
+    }
+
+    pub(crate) fn code_log_mut(&mut self) -> Option<&mut CodeLog> {
+        self.code_log.as_deref_mut()
+    }
+
+    // A sample every `period` cycles of what run runs, from a fresh
+    // start; see profiler.rs.
+    pub fn start_profiling(&mut self, period: u64) {
+        self.profiler = Some(Box::new(Profiler::new(period)));
+    }
+
+    // What was sampled.
+    pub fn stop_profiling(&mut self) -> Option<Profiler> {
+        self.profiler.take().map(|profiler| *profiler)
+    }
+
+    pub fn profiler(&self) -> Option<&Profiler> {
+        self.profiler.as_deref()
+    }
+
+    // After `pc` ran, before the call stack sees what it did.
+    pub(crate) fn profile(&mut self, bus: &Bus, pc: Address, cycles: u64) {
+        if let Some(profiler) = self.profiler.as_mut() {
+            profiler.sample(bus, pc, cycles, &self.calls);
+        }
+    }
+
+    // What of a PRG ROM of `size` bytes runs from here on; see
+    // coverage.rs and Emulator::start_coverage.
+    pub fn start_coverage(&mut self, size: usize) {
+        self.coverage = Some(Box::new(Coverage::new(size)));
+    }
+
+    pub fn stop_coverage(&mut self) -> Option<Coverage> {
+        self.coverage.take().map(|coverage| *coverage)
+    }
+
+    pub fn coverage(&self) -> Option<&Coverage> {
+        self.coverage.as_deref()
+    }
+
+    pub(crate) fn coverage_mut(&mut self) -> Option<&mut Coverage> {
+        self.coverage.as_deref_mut()
+    }
+
+    // Register writes, interrupts and sprite 0 hits from here on; see
+    // timeline.rs.
+    pub fn start_timeline(&mut self) {
+        self.timeline = Some(Box::default());
+    }
+
+    pub fn stop_timeline(&mut self) -> Option<Timeline> {
+        self.timeline.take().map(|timeline| *timeline)
+    }
+
+    pub fn timeline(&self) -> Option<&Timeline> {
+        self.timeline.as_deref()
+    }
+
+    // After the instruction at `pc`, before watchpoints look at the hits.
+    pub(crate) fn log_events(&mut self, bus: &mut Bus, pc: Address, before: PpuPosition) {
+        if let Some(timeline) = self.timeline.as_mut() {
+            let hook = bus.watch_mut();
+            let writes = if hook.has_hits() {
+                hook.take_hits_for(timeline::HOOK_ID)
+            } else {
+                Vec::new()
+            };
+            timeline.instruction(&writes, &bus.ppu, pc, before);
+        }
+    }
+
+    // As run returns FrameDone.
+    pub(crate) fn frame_done(&mut self) {
+        if let Some(timeline) = self.timeline.as_mut() {
+            timeline.end_frame();
+        }
+    }
+
+    // Names for disassembly and traces; see symbols.rs.
+    pub fn symbols(&self) -> &Symbols {
+        &self.symbols
+    }
+
+    pub fn symbols_mut(&mut self) -> &mut Symbols {
+        &mut self.symbols
+    }
+
+    // Finishes on a later run, possibly several frames on for a long
+    // subroutine; breakpoints and watchpoints still stop it on the way.
+    pub fn step(&mut self, mode: StepMode) {
+        self.step = Some((mode, self.calls.depth()));
+    }
+
+    pub fn cancel_step(&mut self) {
+        self.step = None;
+    }
+
+    pub fn is_stepping(&self) -> bool {
+        self.step.is_some()
+    }
+
+    pub fn call_stack(&self) -> &CallStack {
+        &self.calls
+    }
+
+    pub fn call_depth(&self) -> usize {
+        self.calls.depth()
+    }
+
+    // Just after the interrupt moved the PC from `from` to its handler.
+    pub(crate) fn entered_interrupt(&mut self, cpu: &mut Cpu, from: Address) {
+        let nmi = u16::from_le_bytes([cpu.bus.peek(0xFFFA), cpu.bus.peek(0xFFFB)]);
+        let is_nmi = cpu.program_counter == nmi;
+        if let Some(timeline) = self.timeline.as_mut() {
+            timeline.interrupt(&cpu.bus.ppu, cpu.program_counter, is_nmi);
+        }
+        self.calls.push(CallFrame {
+            kind: if is_nmi { CallKind::Nmi } else { CallKind::Irq },
+            from,
+            to: cpu.program_counter,
+            // the return address and status
+            sp: cpu.stack_pointer.wrapping_add(3),
+        });
+    }
+
+    // After `opcode` at `pc` has run.
+    pub(crate) fn track_calls(&mut self, cpu: &Cpu, opcode: u8, pc: Address) {
+        let (kind, pushed) = match opcode {
+            0x20 => (CallKind::Subroutine, 2),
+            0x00 => (CallKind::Brk, 3),
+            // RTS, RTI, TXS
+            0x60 | 0x40 | 0x9a => {
+                self.calls.unwind(cpu.stack_pointer);
+                return;
+            }
+            _ => return,
+        };
+        self.calls.push(CallFrame {
+            kind,
+            from: pc,
+            to: cpu.program_counter,
+            sp: cpu.stack_pointer.wrapping_add(pushed),
+        });
+    }
+
+    // After an instruction has run, leaving the PC at `pc`.
+    pub(crate) fn stepped(&mut self, pc: Address) -> Option<StopReason> {
+        let (mode, start) = self.step?;
+        let depth = self.calls.depth();
+        let done = match mode {
+            StepMode::Into => true,
+            StepMode::Over => depth <= start,
+            StepMode::Out => depth < start,
+        };
+        if !done {
+            return None;
+        }
+        self.step = None;
+        self.stopped_at = Some(pc);
+        Some(StopReason::Step { pc })
+    }
+
+    // Before the instruction at the PC.
+    pub(crate) fn check(&mut self, cpu: &mut Cpu) -> Option<StopReason> {
+        let pc = cpu.program_counter;
+        if self.stopped_at.take() == Some(pc) {
+            return None;
+        }
+        let breakpoint = self.breakpoints.iter().find(|breakpoint| {
+            breakpoint.enabled
+                && breakpoint.addr == pc
+                && breakpoint
+                    .condition
+                    .as_ref()
+                    .is_none_or(|condition| condition.is_true(cpu, None))
+        })?;
+        self.stopped_at = Some(pc);
+        self.step = None;
+        Some(StopReason::Breakpoint {
+            id: breakpoint.id,
+            pc,
+        })
+    }
+
+    // Watchpoints, and register writes for PPU breakpoints.
+    pub(crate) fn arm(&self, hook: &mut WatchHook) {
+        hook.arm(&self.watchpoints);
+        if let Some(timeline) = self.timeline.as_ref() {
+            timeline.arm(hook);
+        }
+        for ppu in self.ppu_breakpoints.iter().filter(|ppu| ppu.enabled) {
+            if let Some(addr) = ppu.event.register() {
+                hook.add(ppu.id, addr, addr, WatchKind::Write);
+            }
+        }
+    }
+
+    // After the instruction at `pc`, with what the bus saw it do.
+    pub(crate) fn check_hits(
+        &mut self,
+        cpu: &mut Cpu,
+        pc: Address,
+        hits: &[WatchHit],
+    ) -> Option<StopReason> {
+        let ppu_event = |id| {
+            self.ppu_breakpoints
+                .iter()
+                .find(|ppu| ppu.id == id)
+                .map(|ppu| ppu.event)
+        };
+        let hit = hits.iter().find(|hit| {
+            let watch = self.watchpoints.iter().find(|watch| watch.id == hit.id);
+            let ppu = self.ppu_breakpoints.iter().find(|ppu| ppu.id == hit.id);
+            watch
+                .and_then(|watch| watch.condition.as_ref())
+                .or(ppu.and_then(|ppu| ppu.condition.as_ref()))
+                .is_none_or(|condition| condition.is_true(cpu, Some(hit)))
+        })?;
+        let reason = match ppu_event(hit.id) {
+            Some(event) => StopReason::Ppu {
+                id: hit.id,
+                pc,
+                event,
+                scanline: cpu.bus.ppu.scanline(),
+                dot: cpu.bus.ppu.dot(),
+            },
+            None => StopReason::Watchpoint {
+                id: hit.id,
+                pc,
+                addr: hit.addr,
+                value: hit.value,
+                access: hit.access,
+            },
+        };
+        self.step = None;
+        Some(reason)
+    }
+
+    // After the instruction at `pc`, which started with the PPU at
+    // `before`.
+    pub(crate) fn check_ppu(
+        &mut self,
+        cpu: &mut Cpu,
+        pc: Address,
+        before: PpuPosition,
+    ) -> Option<StopReason> {
+        let now = PpuPosition::of(&cpu.bus.ppu);
+        let ppu = self.ppu_breakpoints.iter().find(|ppu| {
+            ppu.enabled
+                && before.passed(now, ppu.event)
+                && ppu
+                    .condition
+                    .as_ref()
+                    .is_none_or(|condition| condition.is_true(cpu, None))
+        })?;
+        let reason = StopReason::Ppu {
+            id: ppu.id,
+            pc,
+            event: ppu.event,
+            scanline: cpu.bus.ppu.scanline(),
+            dot: cpu.bus.ppu.dot(),
+        };
+        self.step = None;
+        Some(reason)
+    }
+
+    pub(crate) fn has_ppu_breakpoints(&self) -> bool {
+        !self.ppu_breakpoints.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::bus::Mem;
+    use crate::emulator::test::counting_rom;
+    use crate::emulator::Emulator;
+
+    #[test]
+    fn test_breakpoints_stop_run_and_resume() {
+        let mut emulator = Emulator::from_rom(&counting_rom()).unwrap();
+        // the NMI handler
+        let id = emulator.debugger_mut().add_breakpoint(0x9000);
+        let stop = StopReason::Breakpoint { id, pc: 0x9000 };
+        // the NMI is raised as the frame ends and taken at the start of
+        // the next run
+        assert_eq!(emulator.run(), RunStatus::FrameDone);
+        assert_eq!(emulator.run(), RunStatus::Stopped(stop));
+        assert_eq!(emulator.cpu().program_counter, 0x9000);
+        assert_eq!(emulator.frame_count(), 1);
+        let nmi = emulator.debugger().call_stack().frames()[0];
+        assert_eq!((nmi.kind, nmi.from), (CallKind::Nmi, 0x800A));
+        assert_eq!(emulator.cpu_mut().mem_read(0x00), 0);
+
+        // the rest of the frame, through the handler
+        assert_eq!(emulator.run(), RunStatus::FrameDone);
+        assert_eq!(emulator.frame_count(), 2);
+        assert_eq!(emulator.cpu_mut().mem_read(0x00), 1);
+        assert_eq!(emulator.run(), RunStatus::Stopped(stop));
+        assert_eq!(stop.to_string(), format!("breakpoint {} at $9000", id));
+
+        emulator.debugger_mut().set_enabled(id, false);
+        assert_eq!(emulator.run(), RunStatus::FrameDone);
+        assert_eq!(emulator.run(), RunStatus::FrameDone);
+        emulator.debugger_mut().set_enabled(id, true);
+        // run_frame plays on regardless
+        emulator.run_frame();
+        assert_eq!(emulator.frame_count(), 5);
+
+        assert!(emulator.debugger_mut().remove(id));
+        assert!(!emulator.debugger_mut().remove(id));
+        assert!(!emulator.debugger().is_active());
+        assert_eq!(emulator.run(), RunStatus::FrameDone);
+    }
+
+    #[test]
+    fn test_conditions_decide_whether_to_stop() {
+        let mut emulator = Emulator::from_rom(&counting_rom()).unwrap();
+        let debugger = emulator.debugger_mut();
+        let nmi = debugger.add_breakpoint(0x9000);
+        debugger.set_condition(nmi, Some(Expr::parse("[$00] == 2").unwrap()));
+        let write = debugger.add_watchpoint(0x0000, 0x00FF, WatchKind::Write);
+        debugger.set_condition(write, Some(Expr::parse("value >= 4 && frame > 1").unwrap()));
+        assert!(!debugger.set_condition(99, None));
+
+        let mut stops = Vec::new();
+        while stops.len() < 2 {
+            if let RunStatus::Stopped(stop) = emulator.run() {
+                stops.push((stop, emulator.frame_count()));
+            }
+        }
+        assert_eq!(
+            stops[0],
+            (
+                StopReason::Breakpoint {
+                    id: nmi,
+                    pc: 0x9000
+                },
+                3
+            )
+        );
+        assert!(matches!(
+            stops[1],
+            (StopReason::Watchpoint { id, value: 4, .. }, 4) if id == write
+        ));
+    }
+
+    #[test]
+    fn test_steps_follow_the_call_depth() {
+        let mut rom = counting_rom();
+        let prg = &mut rom[16..16 + 0x8000];
+        // jsr $8100; jmp $8000, without turning on NMIs
+        prg[..6].copy_from_slice(&[0x20, 0x00, 0x81, 0x4c, 0x00, 0x80]);
+        // $8100: jsr $8200; rts
+        prg[0x100..0x104].copy_from_slice(&[0x20, 0x00, 0x82, 0x60]);
+        // $8200: inx; rts
+        prg[0x200..0x202].copy_from_slice(&[0xe8, 0x60]);
+        let mut emulator = Emulator::from_rom(&rom).unwrap();
+        let step = |emulator: &mut Emulator, mode| {
+            emulator.debugger_mut().step(mode);
+            loop {
+                if let RunStatus::Stopped(stop) = emulator.run() {
+                    return stop;
+                }
+            }
+        };
+
+        assert_eq!(
+            step(&mut emulator, StepMode::Into),
+            StopReason::Step { pc: 0x8100 }
+        );
+        step(&mut emulator, StepMode::Into);
+        let calls = emulator.debugger().call_stack().frames();
+        assert_eq!(
+            calls
+                .iter()
+                .map(|call| (call.from, call.to))
+                .collect::<Vec<_>>(),
+            [(0x8000, 0x8100), (0x8100, 0x8200)]
+        );
+        assert_eq!(
+            step(&mut emulator, StepMode::Out),
+            StopReason::Step { pc: 0x8103 }
+        );
+        assert_eq!(emulator.cpu().register_x, 1);
+        // over an RTS leaves the frame too, and over a JMP is one step
+        assert_eq!(
+            step(&mut emulator, StepMode::Over),
+            StopReason::Step { pc: 0x8003 }
+        );
+        assert_eq!(
+            step(&mut emulator, StepMode::Over),
+            StopReason::Step { pc: 0x8000 }
+        );
+        assert_eq!(emulator.debugger().call_depth(), 0);
+
+        // both calls run inside one step
+        let stop = step(&mut emulator, StepMode::Over);
+        assert_eq!(stop, StopReason::Step { pc: 0x8003 });
+        assert_eq!(stop.to_string(), "step to $8003");
+        assert_eq!(emulator.cpu().register_x, 2);
+
+        // a breakpoint on the way wins and ends the step
+        step(&mut emulator, StepMode::Into);
+        let id = emulator.debugger_mut().add_breakpoint(0x8200);
+        assert_eq!(
+            step(&mut emulator, StepMode::Over),
+            StopReason::Breakpoint { id, pc: 0x8200 }
+        );
+        assert!(!emulator.debugger().is_stepping());
+        assert_eq!(
+            step(&mut emulator, StepMode::Out),
+            StopReason::Step { pc: 0x8103 }
+        );
+    }
+
+    #[test]
+    fn test_ppu_breakpoints_stop_where_it_happened() {
+        let mut emulator = Emulator::from_rom(&counting_rom()).unwrap();
+        let debugger = emulator.debugger_mut();
+        let mask = debugger.add_ppu_breakpoint(PpuEvent::RegisterWrite(1));
+        let line = debugger.add_ppu_breakpoint(PpuEvent::Dot {
+            scanline: 100,
+            dot: 0,
+        });
+
+        // the reset handler's sta $2001
+        let RunStatus::Stopped(stop) = emulator.run() else {
+            panic!("ran through");
+        };
+        assert!(matches!(
+            stop,
+            StopReason::Ppu { id, pc: 0x8007, event: PpuEvent::RegisterWrite(1), .. } if id == mask
+        ));
+        let RunStatus::Stopped(StopReason::Ppu {
+            id, scanline, dot, ..
+        }) = emulator.run()
+        else {
+            panic!("didn't reach scanline 100");
+        };
+        assert_eq!((id, scanline), (line, 100));
+        assert!(dot < 24, "{}", dot);
+        assert_eq!(emulator.run(), RunStatus::FrameDone);
+
+        emulator
+            .debugger_mut()
+            .set_condition(line, Some(Expr::parse("frame == 3").unwrap()));
+        let frame = (0..5)
+            .find(|_| emulator.run() != RunStatus::FrameDone)
+            .map(|_| emulator.frame_count());
+        assert_eq!(frame, Some(3));
+    }
+
+    #[test]
+    fn test_watchpoints_say_who_touched_what() {
+        let mut emulator = Emulator::from_rom(&counting_rom()).unwrap();
+        let debugger = emulator.debugger_mut();
+        let lives = debugger.add_watchpoint(0x0000, 0x0000, WatchKind::Write);
+        let ctrl = debugger.add_watchpoint(0x2000, 0x2000, WatchKind::Access);
+        // the reset handler's sta $2000, and the write has happened
+        let RunStatus::Stopped(stop) = emulator.run() else {
+            panic!("ran through");
+        };
+        assert_eq!(
+            stop,
+            StopReason::Watchpoint {
+                id: ctrl,
+                pc: 0x8002,
+                addr: 0x2000,
+                value: 0x80,
+                access: Access::Write,
+            }
+        );
+        assert_eq!(emulator.cpu().program_counter, 0x8005);
+        assert_eq!(
+            stop.to_string(),
+            format!("watchpoint {}: write of $80 at $2000 by $8002", ctrl)
+        );
+
+        emulator.debugger_mut().set_enabled(ctrl, false);
+        assert_eq!(emulator.run(), RunStatus::FrameDone);
+        // inc $00 in the NMI handler reads, then writes
+        let RunStatus::Stopped(StopReason::Watchpoint { id, pc, value, .. }) = emulator.run()
+        else {
+            panic!("no write to $00");
+        };
+        assert_eq!((id, pc, value), (lives, 0x9000, 1));
+        assert_eq!(emulator.cpu_mut().mem_read(0x00), 1);
+        // run_frame doesn't arm the hook
+        emulator.run_frame();
+        assert!(!emulator.cpu_mut().bus.watch_mut().is_armed());
+    }
+}
@@ -0,0 +1,137 @@
+// Breakpoints on what the PPU is doing rather than where the CPU is, for
+// raster effects: a point in the frame, a write to one of its registers,
+// or sprite 0 hitting. Like watchpoints they stop just after the
+// instruction during which it happened, which for a scanline and dot
+// means the first one to run past it.
+
+use std::fmt;
+
+use super::expr::Expr;
+use crate::ppu::registers::StatusRegister;
+use crate::ppu::Ppu;
+
+type Address = u16;
+
+const DOTS_PER_LINE: u32 = 341;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PpuEvent {
+    // every frame
+    Dot { scanline: u16, dot: u16 },
+    // 0-7 for $2000-$2007, mirrors included
+    RegisterWrite(u8),
+    SpriteZeroHit,
+}
+
+impl PpuEvent {
+    // The watched address for a register write.
+    pub(crate) fn register(self) -> Option<Address> {
+        match self {
+            PpuEvent::RegisterWrite(register) => Some(0x2000 | (register & 7) as Address),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for PpuEvent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PpuEvent::Dot { scanline, dot } => write!(f, "scanline {} dot {}", scanline, dot),
+            PpuEvent::RegisterWrite(register) => {
+                write!(f, "write to ${:04X}", 0x2000 | (register & 7) as Address)
+            }
+            PpuEvent::SpriteZeroHit => write!(f, "sprite 0 hit"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PpuBreakpoint {
+    pub id: usize,
+    pub event: PpuEvent,
+    pub enabled: bool,
+    pub condition: Option<Expr>,
+}
+
+// Where the PPU was before an instruction.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct PpuPosition {
+    scanline: u16,
+    dot: u16,
+    sprite_zero_hit: bool,
+    // in the frame, which goes by the console
+    lines: u16,
+}
+
+impl PpuPosition {
+    pub(crate) fn of(ppu: &Ppu) -> Self {
+        PpuPosition {
+            scanline: ppu.scanline(),
+            dot: ppu.dot(),
+            sprite_zero_hit: ppu.status.contains(StatusRegister::SPRITE_ZERO_HIT),
+            lines: ppu.region().scanlines(),
+        }
+    }
+
+    fn linear(scanline: u16, dot: u16) -> u32 {
+        scanline as u32 * DOTS_PER_LINE + dot as u32
+    }
+
+    // Whether the PPU went from here to `now` past `event`. Register
+    // writes come through the watch hook instead.
+    pub(crate) fn passed(self, now: PpuPosition, event: PpuEvent) -> bool {
+        match event {
+            PpuEvent::Dot { scanline, dot } => {
+                let before = Self::linear(self.scanline, self.dot);
+                let after = Self::linear(now.scanline, now.dot);
+                let target = Self::linear(scanline, dot) % (DOTS_PER_LINE * self.lines as u32);
+                if after >= before {
+                    before < target && target <= after
+                } else {
+                    // into the next frame
+                    target > before || target <= after
+                }
+            }
+            PpuEvent::SpriteZeroHit => !self.sprite_zero_hit && now.sprite_zero_hit,
+            PpuEvent::RegisterWrite(_) => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_positions_passed() {
+        let at = |scanline, dot| PpuPosition {
+            scanline,
+            dot,
+            sprite_zero_hit: false,
+            lines: 262,
+        };
+        let event = PpuEvent::Dot {
+            scanline: 100,
+            dot: 5,
+        };
+        assert!(at(100, 0).passed(at(100, 5), event));
+        assert!(at(99, 340).passed(at(100, 20), event));
+        assert!(!at(100, 5).passed(at(100, 20), event));
+        assert!(!at(100, 6).passed(at(100, 20), event));
+
+        let event = PpuEvent::Dot {
+            scanline: 0,
+            dot: 2,
+        };
+        assert!(at(261, 330).passed(at(0, 4), event));
+        assert!(!at(261, 330).passed(at(0, 1), event));
+
+        let hit = PpuPosition {
+            sprite_zero_hit: true,
+            ..at(30, 0)
+        };
+        assert!(at(30, 0).passed(hit, PpuEvent::SpriteZeroHit));
+        assert!(!hit.passed(hit, PpuEvent::SpriteZeroHit));
+        assert_eq!(PpuEvent::RegisterWrite(5).to_string(), "write to $2005");
+    }
+}
@@ -0,0 +1,240 @@
+// A sampling profiler for the game's own code: every `period` CPU cycles
+// it notes the instruction running and the shadow call stack under it
+// (call_stack.rs), each address with the PRG bank it was in, so code at
+// the same address in two banks is told apart. Emulator::run samples
+// after each instruction, charging it with every sample point its cycles
+// crossed; like the call stack it sees nothing run_frame does.
+//
+// The results come out two ways: a report of where the samples fell, by
+// function (the innermost call's target) and by address, and folded
+// stacks, one `main;outer;inner count` line per stack, which
+// flamegraph.pl and inferno turn into a flame graph. Names come from the
+// loaded symbols, and addresses without one are written 02:C000, bank
+// first, or $0300 for code outside PRG ROM.
+
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use super::call_stack::CallStack;
+use super::symbols::{self, Symbols};
+use crate::bus::Bus;
+
+type Address = u16;
+
+// about 300 samples a frame
+pub const DEFAULT_PERIOD: u64 = 100;
+// what runs outside any call, from reset
+const ROOT: &str = "main";
+const REPORT_LINES: usize = 20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Location {
+    pub addr: Address,
+    // the 16K PRG bank, for ROM
+    pub bank: Option<usize>,
+}
+
+impl Location {
+    fn of(bus: &Bus, addr: Address) -> Self {
+        Location {
+            addr,
+            bank: symbols::bank(bus, addr),
+        }
+    }
+
+    pub fn label(&self, symbols: &Symbols) -> String {
+        match symbols.lookup(self.addr, self.bank) {
+            Some(symbol) if symbol.addr == self.addr => symbol.name.clone(),
+            _ => match self.bank {
+                Some(bank) => format!("{:02X}:{:04X}", bank, self.addr),
+                None => format!("${:04X}", self.addr),
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Profiler {
+    period: u64,
+    // the cycle count the next sample is due at
+    next: Option<u64>,
+    samples: u64,
+    // by call stack, outermost target first
+    stacks: HashMap<Vec<Location>, u64>,
+    addresses: HashMap<Location, u64>,
+}
+
+impl Default for Profiler {
+    fn default() -> Self {
+        Self::new(DEFAULT_PERIOD)
+    }
+}
+
+impl Profiler {
+    // A sample every `period` cycles; 1 is every cycle.
+    pub fn new(period: u64) -> Self {
+        Profiler {
+            period: period.max(1),
+            next: None,
+            samples: 0,
+            stacks: HashMap::new(),
+            addresses: HashMap::new(),
+        }
+    }
+
+    pub fn period(&self) -> u64 {
+        self.period
+    }
+
+    pub fn samples(&self) -> u64 {
+        self.samples
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::new(self.period);
+    }
+
+    // After the instruction at `pc` ran, leaving the CPU at `cycles`.
+    pub(crate) fn sample(&mut self, bus: &Bus, pc: Address, cycles: u64, calls: &CallStack) {
+        let next = *self.next.get_or_insert(cycles);
+        if cycles < next {
+            return;
+        }
+        let count = (cycles - next) / self.period + 1;
+        self.next = Some(next + count * self.period);
+        self.samples += count;
+        *self.addresses.entry(Location::of(bus, pc)).or_default() += count;
+        let stack = calls
+            .frames()
+            .iter()
+            .map(|frame| Location::of(bus, frame.to))
+            .collect();
+        *self.stacks.entry(stack).or_default() += count;
+    }
+
+    // Samples by address, most first.
+    pub fn hotspots(&self) -> Vec<(Location, u64)> {
+        let mut hotspots: Vec<_> = self.addresses.iter().map(|(&at, &n)| (at, n)).collect();
+        hotspots.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        hotspots
+    }
+
+    // Samples by the function they fell in, None for outside any call,
+    // most first.
+    pub fn functions(&self) -> Vec<(Option<Location>, u64)> {
+        let mut functions: HashMap<_, u64> = HashMap::new();
+        for (stack, &count) in &self.stacks {
+            *functions.entry(stack.last().copied()).or_default() += count;
+        }
+        let mut functions: Vec<_> = functions.into_iter().collect();
+        functions.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        functions
+    }
+
+    // For flamegraph.pl or inferno-flamegraph, in a stable order.
+    pub fn folded(&self, symbols: &Symbols) -> String {
+        let mut lines: Vec<String> = self
+            .stacks
+            .iter()
+            .map(|(stack, count)| {
+                let mut line = ROOT.to_string();
+                for at in stack {
+                    line.push(';');
+                    line.push_str(&at.label(symbols));
+                }
+                format!("{} {}", line, count)
+            })
+            .collect();
+        lines.sort();
+        lines.iter().map(|line| format!("{}\n", line)).collect()
+    }
+
+    pub fn write_folded(&self, path: &Path, symbols: &Symbols) -> io::Result<()> {
+        fs::write(path, self.folded(symbols))
+    }
+
+    // The top functions and addresses with their share of the samples.
+    pub fn report(&self, symbols: &Symbols) -> String {
+        let share = |count: u64| 100.0 * count as f64 / self.samples.max(1) as f64;
+        let mut out = format!(
+            "{} samples, one every {} cycles\n\nfunction\n",
+            self.samples, self.period
+        );
+        for (function, count) in self.functions().into_iter().take(REPORT_LINES) {
+            let name = function.map_or(ROOT.to_string(), |at| at.label(symbols));
+            let _ = writeln!(out, "{:6.2}% {:8} {}", share(count), count, name);
+        }
+        out.push_str("\naddress\n");
+        for (at, count) in self.hotspots().into_iter().take(REPORT_LINES) {
+            let _ = writeln!(
+                out,
+                "{:6.2}% {:8} {}",
+                share(count),
+                count,
+                at.label(symbols)
+            );
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::debugger::symbols::Symbol;
+    use crate::emulator::test::counting_rom;
+    use crate::emulator::Emulator;
+
+    #[test]
+    fn test_samples_by_stack_and_address() {
+        let mut emulator = Emulator::from_rom(&counting_rom()).unwrap();
+        let start = emulator.cpu().cycles;
+        emulator.debugger_mut().start_profiling(10);
+        for _ in 0..4 {
+            emulator.run();
+        }
+        let profiler = emulator.debugger().profiler().unwrap();
+        let samples = profiler.samples();
+        let expected = (emulator.cpu().cycles - start) / 10;
+        assert!(samples.abs_diff(expected) <= 1, "{} {}", samples, expected);
+
+        let main_loop = Location {
+            addr: 0x800A,
+            bank: Some(0),
+        };
+        let hotspots = profiler.hotspots();
+        assert_eq!(hotspots[0].0, main_loop);
+        assert!(hotspots.iter().any(|(at, _)| at.addr == 0x9002));
+        let functions = profiler.functions();
+        assert_eq!(functions[0].0, None);
+        assert_eq!(functions[1].0.map(|at| at.addr), Some(0x9000));
+
+        let mut symbols = Symbols::new();
+        symbols.insert(Symbol {
+            name: "nmi".to_string(),
+            comment: None,
+            addr: 0x9000,
+            size: 1,
+            bank: Some(0),
+        });
+        let folded = profiler.folded(&symbols);
+        let lines: Vec<_> = folded.lines().collect();
+        assert_eq!(lines.len(), 2, "{}", folded);
+        assert!(lines[0].starts_with("main "));
+        assert!(lines[1].starts_with("main;nmi "));
+        let total: u64 = lines
+            .iter()
+            .map(|line| line.rsplit(' ').next().unwrap().parse::<u64>().unwrap())
+            .sum();
+        assert_eq!(total, samples);
+        assert_eq!(main_loop.label(&Symbols::new()), "00:800A");
+        assert!(profiler.report(&symbols).contains("% "));
+
+        let profiler = emulator.debugger_mut().stop_profiling().unwrap();
+        assert_eq!(profiler.period(), 10);
+        assert!(!emulator.debugger().is_active());
+    }
+}
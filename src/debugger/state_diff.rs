@@ -0,0 +1,643 @@
+// What differs between two savestates, field by field: the thing to look
+// at when two machines that should agree (netplay peers, a movie and its
+// replay, a build before and after a change) stop agreeing.
+//
+// Each state is unpacked into the machine it describes and every part
+// that went into it is serialized again, this time into a flat list of
+// named values (`cpu.register_a`, `ppu.scanline`, `apu.pulse1.timer`)
+// instead of bytes. Enum variants and Some are part of the name, so a
+// controller swapped for another shows as fields that only one side has.
+// Byte arrays (RAM, VRAM, OAM, CHR RAM) are compared as runs of differing
+// bytes. The mapper's section is whatever the board wrote about itself,
+// so it's compared as bytes too.
+//
+//     cpu.register_a: $00 | $01
+//     bus.cpu_ram[$0010..$0011]: 00 00 | 01 FF
+//     mapper[$0002]: 03 | 04
+//
+// Compressed states and in-memory snapshots both work.
+
+use std::collections::BTreeMap;
+use std::fmt::{self, Write as _};
+
+use serde::ser::{self, Serialize};
+
+use crate::state::{self, StateError};
+
+// longer runs of differing bytes are cut short when printed
+const SHOWN_BYTES: usize = 16;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Difference {
+    pub field: String,
+    // "-" when only the other state has the field
+    pub left: String,
+    pub right: String,
+}
+
+impl fmt::Display for Difference {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {} | {}", self.field, self.left, self.right)
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StateDiff {
+    pub differences: Vec<Difference>,
+}
+
+impl StateDiff {
+    pub fn is_empty(&self) -> bool {
+        self.differences.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.differences.len()
+    }
+}
+
+impl fmt::Display for StateDiff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_empty() {
+            return writeln!(f, "the states are the same");
+        }
+        for difference in &self.differences {
+            writeln!(f, "{}", difference)?;
+        }
+        Ok(())
+    }
+}
+
+pub fn diff(left: &[u8], right: &[u8]) -> Result<StateDiff, StateError> {
+    let left = fields(&state::decompress(left)?)?;
+    let right = fields(&state::decompress(right)?)?;
+    let mut remaining: BTreeMap<&str, &Leaf> = right
+        .iter()
+        .map(|(name, leaf)| (name.as_str(), leaf))
+        .collect();
+
+    let mut differences = Vec::new();
+    for (name, leaf) in &left {
+        match remaining.remove(name.as_str()) {
+            Some(other) => compare(name, leaf, other, &mut differences),
+            None => differences.push(Difference {
+                field: name.clone(),
+                left: leaf.to_string(),
+                right: "-".to_string(),
+            }),
+        }
+    }
+    // in the order the right-hand state has them
+    for (name, leaf) in &right {
+        if remaining.contains_key(name.as_str()) {
+            differences.push(Difference {
+                field: name.clone(),
+                left: "-".to_string(),
+                right: leaf.to_string(),
+            });
+        }
+    }
+    Ok(StateDiff { differences })
+}
+
+fn fields(data: &[u8]) -> Result<Vec<(String, Leaf)>, StateError> {
+    let (cpu, mapper) = state::unpack(data)?;
+    let bus = &cpu.bus;
+    let mut out = Vec::new();
+    flatten("cpu", &cpu, &mut out);
+    flatten("bus", bus, &mut out);
+    flatten("ppu", &bus.ppu, &mut out);
+    flatten("apu", &bus.apu, &mut out);
+    flatten("input", &bus.controllers, &mut out);
+    flatten("vs", &bus.vs, &mut out);
+    flatten("dot_phase", &bus.dot_phase, &mut out);
+    flatten("overclock_cycles", &bus.overclock_cycles, &mut out);
+    flatten("jammed", &cpu.jammed, &mut out);
+    out.push(("mapper".to_string(), Leaf::Bytes(mapper.to_vec())));
+    Ok(out)
+}
+
+fn flatten<T: Serialize + ?Sized>(name: &str, value: &T, out: &mut Vec<(String, Leaf)>) {
+    let mut flattener = Flattener {
+        path: name.to_string(),
+        out,
+    };
+    // only a Serialize impl of our own could fail, and none do
+    value
+        .serialize(&mut flattener)
+        .expect("flattening a state can't fail");
+}
+
+fn compare(name: &str, left: &Leaf, right: &Leaf, out: &mut Vec<Difference>) {
+    match (left, right) {
+        (Leaf::Bytes(left), Leaf::Bytes(right)) => compare_bytes(name, left, right, out),
+        _ if left != right => out.push(Difference {
+            field: name.to_string(),
+            left: left.to_string(),
+            right: right.to_string(),
+        }),
+        _ => {}
+    }
+}
+
+// One difference per run of bytes that differ.
+fn compare_bytes(name: &str, left: &[u8], right: &[u8], out: &mut Vec<Difference>) {
+    if left.len() != right.len() {
+        out.push(Difference {
+            field: format!("{}.len", name),
+            left: left.len().to_string(),
+            right: right.len().to_string(),
+        });
+    }
+    let len = left.len().min(right.len());
+    let mut i = 0;
+    while i < len {
+        if left[i] == right[i] {
+            i += 1;
+            continue;
+        }
+        let start = i;
+        while i < len && left[i] != right[i] {
+            i += 1;
+        }
+        let field = if i - start == 1 {
+            format!("{}[${:04X}]", name, start)
+        } else {
+            format!("{}[${:04X}..${:04X}]", name, start, i - 1)
+        };
+        out.push(Difference {
+            field,
+            left: hex_bytes(&left[start..i]),
+            right: hex_bytes(&right[start..i]),
+        });
+    }
+}
+
+fn hex_bytes(bytes: &[u8]) -> String {
+    let mut text = String::new();
+    for (i, byte) in bytes.iter().take(SHOWN_BYTES).enumerate() {
+        if i > 0 {
+            text.push(' ');
+        }
+        let _ = write!(text, "{:02X}", byte);
+    }
+    if bytes.len() > SHOWN_BYTES {
+        let _ = write!(text, " ... ({} bytes)", bytes.len());
+    }
+    text
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Leaf {
+    Bool(bool),
+    // and how many bytes wide
+    Unsigned(u64, u8),
+    Signed(i64),
+    Float(f64),
+    Text(String),
+    Bytes(Vec<u8>),
+    Unit,
+}
+
+impl fmt::Display for Leaf {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Leaf::Bool(value) => write!(f, "{}", value),
+            Leaf::Unsigned(value, 1) => write!(f, "${:02X}", value),
+            Leaf::Unsigned(value, 2) => write!(f, "${:04X}", value),
+            Leaf::Unsigned(value, _) => write!(f, "{}", value),
+            Leaf::Signed(value) => write!(f, "{}", value),
+            Leaf::Float(value) => write!(f, "{}", value),
+            Leaf::Text(text) => write!(f, "{}", text),
+            Leaf::Bytes(bytes) => write!(f, "{}", hex_bytes(bytes)),
+            Leaf::Unit => write!(f, "()"),
+        }
+    }
+}
+
+// A serde serializer that writes (name, value) pairs instead of bytes.
+struct Flattener<'a> {
+    path: String,
+    out: &'a mut Vec<(String, Leaf)>,
+}
+
+impl Flattener<'_> {
+    fn leaf(&mut self, leaf: Leaf) -> Result<(), fmt::Error> {
+        self.out.push((self.path.clone(), leaf));
+        Ok(())
+    }
+
+    // Adds `.name` or `[index]` to the path, returning where it was.
+    fn push(&mut self, segment: fmt::Arguments) -> usize {
+        let len = self.path.len();
+        self.path.write_fmt(segment).expect("writing to a String");
+        len
+    }
+
+    fn nested<T: Serialize + ?Sized>(
+        &mut self,
+        segment: fmt::Arguments,
+        value: &T,
+    ) -> Result<(), fmt::Error> {
+        let len = self.push(segment);
+        let result = value.serialize(&mut *self);
+        self.path.truncate(len);
+        result
+    }
+}
+
+// Sequences, tuples, maps and structs. A sequence of nothing but u8s is
+// folded into one Bytes leaf at the end.
+struct Compound<'a, 'b> {
+    flattener: &'b mut Flattener<'a>,
+    // the path's length before a variant name was added
+    restore: usize,
+    start: usize,
+    index: usize,
+}
+
+impl<'a, 'b> Compound<'a, 'b> {
+    fn new(flattener: &'b mut Flattener<'a>, restore: usize) -> Self {
+        let start = flattener.out.len();
+        Compound {
+            flattener,
+            restore,
+            start,
+            index: 0,
+        }
+    }
+
+    fn element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), fmt::Error> {
+        let index = self.index;
+        self.index += 1;
+        self.flattener.nested(format_args!("[{}]", index), value)
+    }
+
+    fn field<T: Serialize + ?Sized>(&mut self, name: &str, value: &T) -> Result<(), fmt::Error> {
+        self.flattener.nested(format_args!(".{}", name), value)
+    }
+
+    fn end_seq(self) -> Result<(), fmt::Error> {
+        let out = &mut *self.flattener.out;
+        let elements = &out[self.start..];
+        let bytes: Option<Vec<u8>> = elements
+            .iter()
+            .map(|(_, leaf)| match leaf {
+                &Leaf::Unsigned(byte, 1) => Some(byte as u8),
+                _ => None,
+            })
+            .collect();
+        if let Some(bytes) = bytes.filter(|bytes| bytes.len() == self.index && !bytes.is_empty()) {
+            out.truncate(self.start);
+            out.push((self.flattener.path.clone(), Leaf::Bytes(bytes)));
+        }
+        self.end()
+    }
+
+    fn end(self) -> Result<(), fmt::Error> {
+        self.flattener.path.truncate(self.restore);
+        Ok(())
+    }
+}
+
+impl<'a, 'b> ser::Serializer for &'b mut Flattener<'a> {
+    type Ok = ();
+    type Error = fmt::Error;
+    type SerializeSeq = Compound<'a, 'b>;
+    type SerializeTuple = Compound<'a, 'b>;
+    type SerializeTupleStruct = Compound<'a, 'b>;
+    type SerializeTupleVariant = Compound<'a, 'b>;
+    type SerializeMap = Compound<'a, 'b>;
+    type SerializeStruct = Compound<'a, 'b>;
+    type SerializeStructVariant = Compound<'a, 'b>;
+
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+
+    fn serialize_bool(self, value: bool) -> Result<(), fmt::Error> {
+        self.leaf(Leaf::Bool(value))
+    }
+
+    fn serialize_i8(self, value: i8) -> Result<(), fmt::Error> {
+        self.leaf(Leaf::Signed(value as i64))
+    }
+
+    fn serialize_i16(self, value: i16) -> Result<(), fmt::Error> {
+        self.leaf(Leaf::Signed(value as i64))
+    }
+
+    fn serialize_i32(self, value: i32) -> Result<(), fmt::Error> {
+        self.leaf(Leaf::Signed(value as i64))
+    }
+
+    fn serialize_i64(self, value: i64) -> Result<(), fmt::Error> {
+        self.leaf(Leaf::Signed(value))
+    }
+
+    fn serialize_u8(self, value: u8) -> Result<(), fmt::Error> {
+        self.leaf(Leaf::Unsigned(value as u64, 1))
+    }
+
+    fn serialize_u16(self, value: u16) -> Result<(), fmt::Error> {
+        self.leaf(Leaf::Unsigned(value as u64, 2))
+    }
+
+    fn serialize_u32(self, value: u32) -> Result<(), fmt::Error> {
+        self.leaf(Leaf::Unsigned(value as u64, 4))
+    }
+
+    fn serialize_u64(self, value: u64) -> Result<(), fmt::Error> {
+        self.leaf(Leaf::Unsigned(value, 8))
+    }
+
+    fn serialize_f32(self, value: f32) -> Result<(), fmt::Error> {
+        self.leaf(Leaf::Float(value as f64))
+    }
+
+    fn serialize_f64(self, value: f64) -> Result<(), fmt::Error> {
+        self.leaf(Leaf::Float(value))
+    }
+
+    fn serialize_char(self, value: char) -> Result<(), fmt::Error> {
+        self.leaf(Leaf::Text(value.to_string()))
+    }
+
+    fn serialize_str(self, value: &str) -> Result<(), fmt::Error> {
+        self.leaf(Leaf::Text(value.to_string()))
+    }
+
+    fn serialize_bytes(self, value: &[u8]) -> Result<(), fmt::Error> {
+        self.leaf(Leaf::Bytes(value.to_vec()))
+    }
+
+    fn serialize_none(self) -> Result<(), fmt::Error> {
+        self.leaf(Leaf::Text("None".to_string()))
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<(), fmt::Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<(), fmt::Error> {
+        self.leaf(Leaf::Unit)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<(), fmt::Error> {
+        self.leaf(Leaf::Unit)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+    ) -> Result<(), fmt::Error> {
+        self.leaf(Leaf::Text(variant.to_string()))
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<(), fmt::Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<(), fmt::Error> {
+        self.nested(format_args!(".{}", variant), value)
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Compound<'a, 'b>, fmt::Error> {
+        let restore = self.path.len();
+        Ok(Compound::new(self, restore))
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Compound<'a, 'b>, fmt::Error> {
+        let restore = self.path.len();
+        Ok(Compound::new(self, restore))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Compound<'a, 'b>, fmt::Error> {
+        let restore = self.path.len();
+        Ok(Compound::new(self, restore))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Compound<'a, 'b>, fmt::Error> {
+        let restore = self.push(format_args!(".{}", variant));
+        Ok(Compound::new(self, restore))
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Compound<'a, 'b>, fmt::Error> {
+        let restore = self.path.len();
+        Ok(Compound::new(self, restore))
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Compound<'a, 'b>, fmt::Error> {
+        let restore = self.path.len();
+        Ok(Compound::new(self, restore))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Compound<'a, 'b>, fmt::Error> {
+        let restore = self.push(format_args!(".{}", variant));
+        Ok(Compound::new(self, restore))
+    }
+}
+
+impl ser::SerializeSeq for Compound<'_, '_> {
+    type Ok = ();
+    type Error = fmt::Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), fmt::Error> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<(), fmt::Error> {
+        self.end_seq()
+    }
+}
+
+impl ser::SerializeTuple for Compound<'_, '_> {
+    type Ok = ();
+    type Error = fmt::Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), fmt::Error> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<(), fmt::Error> {
+        self.end_seq()
+    }
+}
+
+impl ser::SerializeTupleStruct for Compound<'_, '_> {
+    type Ok = ();
+    type Error = fmt::Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), fmt::Error> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<(), fmt::Error> {
+        Compound::end(self)
+    }
+}
+
+impl ser::SerializeTupleVariant for Compound<'_, '_> {
+    type Ok = ();
+    type Error = fmt::Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), fmt::Error> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<(), fmt::Error> {
+        Compound::end(self)
+    }
+}
+
+// Entries go by their place in the map, key and value both.
+impl ser::SerializeMap for Compound<'_, '_> {
+    type Ok = ();
+    type Error = fmt::Error;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), fmt::Error> {
+        let index = self.index;
+        self.flattener.nested(format_args!("[{}].key", index), key)
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), fmt::Error> {
+        let index = self.index;
+        self.index += 1;
+        self.flattener
+            .nested(format_args!("[{}].value", index), value)
+    }
+
+    fn end(self) -> Result<(), fmt::Error> {
+        Compound::end(self)
+    }
+}
+
+impl ser::SerializeStruct for Compound<'_, '_> {
+    type Ok = ();
+    type Error = fmt::Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        name: &'static str,
+        value: &T,
+    ) -> Result<(), fmt::Error> {
+        self.field(name, value)
+    }
+
+    fn end(self) -> Result<(), fmt::Error> {
+        Compound::end(self)
+    }
+}
+
+impl ser::SerializeStructVariant for Compound<'_, '_> {
+    type Ok = ();
+    type Error = fmt::Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        name: &'static str,
+        value: &T,
+    ) -> Result<(), fmt::Error> {
+        self.field(name, value)
+    }
+
+    fn end(self) -> Result<(), fmt::Error> {
+        Compound::end(self)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::bus::Mem;
+    use crate::emulator::test::counting_rom;
+    use crate::emulator::Emulator;
+    use crate::input::zapper::Zapper;
+    use crate::input::PortDevice;
+
+    #[test]
+    fn test_same_state_has_no_differences() {
+        let mut emulator = Emulator::from_rom(&counting_rom()).unwrap();
+        emulator.run_frames(2);
+        let state = emulator.save_state();
+        let diff = diff(&state, &state).unwrap();
+        assert!(diff.is_empty());
+        assert_eq!(diff.to_string(), "the states are the same\n");
+    }
+
+    #[test]
+    fn test_reports_registers_and_ram_runs() {
+        let mut emulator = Emulator::from_rom(&counting_rom()).unwrap();
+        emulator.run_frame();
+        let before = emulator.save_state();
+        let cpu = emulator.cpu_mut();
+        cpu.register_x = 0x42;
+        cpu.mem_write(0x0010, 1);
+        cpu.mem_write(0x0011, 2);
+        cpu.mem_write(0x0300, 3);
+        let after = emulator.save_state();
+
+        let diff = diff(&before, &after).unwrap();
+        let lines: Vec<String> = diff.differences.iter().map(|d| d.to_string()).collect();
+        assert!(
+            lines.contains(&"cpu.register_x: $00 | $42".to_string()),
+            "{:?}",
+            lines
+        );
+        assert!(lines.contains(&"bus.cpu_ram[$0010..$0011]: 00 00 | 01 02".to_string()));
+        assert!(lines.contains(&"bus.cpu_ram[$0300]: 00 | 03".to_string()));
+        assert_eq!(diff.len(), 3, "{}", diff);
+    }
+
+    #[test]
+    fn test_fields_only_one_side_has() {
+        let mut emulator = Emulator::from_rom(&counting_rom()).unwrap();
+        let before = emulator.save_state();
+        let zapper = PortDevice::Zapper(Zapper::new());
+        emulator.cpu_mut().bus.controllers.connect(1, zapper);
+        let after = emulator.save_state();
+        let diff = diff(&before, &after).unwrap();
+        assert!(diff
+            .differences
+            .iter()
+            .any(|d| d.field.starts_with("input.") && d.left == "-"));
+
+        // compressed files compare with what they hold
+        assert!(super::diff(&state::compress(&before), &before)
+            .unwrap()
+            .is_empty());
+        assert!(super::diff(b"nope", &before).is_err());
+    }
+}
@@ -0,0 +1,409 @@
+// Symbols from the game's own toolchain, shown in disassembly and trace
+// logs in place of bare addresses. Two formats load:
+//
+//  - ld65 debug files (--dbgfile, .dbg): every label the linker placed,
+//    with the segment telling which PRG bank it went in.
+//  - FCEUX name lists (.nl): `$C000#name#comment` lines, `$0200/10#...`
+//    for a 16-byte array. The file name says where they apply, as FCEUX
+//    has it: game.nes.ram.nl for $0000-$7FFF and game.nes.N.nl for the
+//    16K PRG bank N (in hex).
+//
+// A symbol in a bank only stands for its address while that bank is
+// mapped in, which the mapper answers (Mapper::prg_offset); one without a
+// bank always does.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::bus::Bus;
+
+type Address = u16;
+
+// PRG banks as FCEUX and the iNES header count them.
+pub(crate) const BANK_SIZE: usize = 0x4000;
+const INES_HEADER: usize = 16;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Symbol {
+    pub name: String,
+    pub comment: Option<String>,
+    pub addr: Address,
+    // bytes covered, 1 except for arrays
+    pub size: u16,
+    pub bank: Option<usize>,
+}
+
+#[derive(Debug)]
+pub enum SymbolError {
+    Io(io::Error),
+    Parse { line: usize, message: String },
+    UnknownFormat,
+}
+
+impl fmt::Display for SymbolError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SymbolError::Io(err) => write!(f, "{}", err),
+            SymbolError::Parse { line, message } => write!(f, "line {}: {}", line, message),
+            SymbolError::UnknownFormat => write!(f, "not a .dbg or .nl file"),
+        }
+    }
+}
+
+impl std::error::Error for SymbolError {}
+
+impl From<io::Error> for SymbolError {
+    fn from(err: io::Error) -> Self {
+        SymbolError::Io(err)
+    }
+}
+
+fn parse_error(line: usize, message: impl Into<String>) -> SymbolError {
+    SymbolError::Parse {
+        line,
+        message: message.into(),
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Symbols {
+    by_addr: BTreeMap<Address, Vec<Symbol>>,
+}
+
+impl Symbols {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Goes by the extension, and for .nl files by the name; see the top
+    // of the file.
+    pub fn load(&mut self, path: &Path) -> Result<usize, SymbolError> {
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_ascii_lowercase())
+            .unwrap_or_default();
+        let text = fs::read_to_string(path)?;
+        if name.ends_with(".dbg") {
+            self.load_dbg(&text)
+        } else if let Some(stem) = name.strip_suffix(".nl") {
+            let bank = match stem.rsplit_once('.') {
+                Some((_, "ram")) | None => None,
+                Some((_, bank)) => {
+                    Some(usize::from_str_radix(bank, 16).map_err(|_| SymbolError::UnknownFormat)?)
+                }
+            };
+            self.load_nl(&text, bank)
+        } else {
+            Err(SymbolError::UnknownFormat)
+        }
+    }
+
+    // Returns how many symbols it added.
+    pub fn load_nl(&mut self, text: &str, bank: Option<usize>) -> Result<usize, SymbolError> {
+        let mut added: Vec<Symbol> = Vec::new();
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim_end();
+            let Some(rest) = line.strip_prefix('$') else {
+                // the rest of a comment that went over several lines
+                match added.last_mut() {
+                    Some(Symbol {
+                        comment: Some(comment),
+                        ..
+                    }) if !line.is_empty() => {
+                        comment.push('\n');
+                        comment.push_str(line);
+                    }
+                    _ if line.trim().is_empty() => {}
+                    _ => return Err(parse_error(i + 1, "expected $address#name#comment")),
+                }
+                continue;
+            };
+            let mut fields = rest.splitn(3, '#');
+            let location = fields.next().unwrap_or_default();
+            let (addr, size) = match location.split_once('/') {
+                Some((addr, size)) => (addr, parse_number(size)),
+                None => (location, Some(1)),
+            };
+            let addr = u16::from_str_radix(addr, 16)
+                .map_err(|_| parse_error(i + 1, format!("bad address ${}", addr)))?;
+            let size = size
+                .and_then(|size| u16::try_from(size).ok())
+                .filter(|&size| size > 0)
+                .ok_or_else(|| parse_error(i + 1, "bad array size"))?;
+            let name = fields.next().unwrap_or_default().trim().to_string();
+            let comment = fields
+                .next()
+                .map(|comment| comment.trim_end_matches('#').to_string())
+                .filter(|comment| !comment.is_empty());
+            added.push(Symbol {
+                name,
+                comment,
+                addr,
+                size,
+                bank: if addr >= 0x8000 { bank } else { None },
+            });
+        }
+        let count = added.len();
+        added.into_iter().for_each(|symbol| self.insert(symbol));
+        Ok(count)
+    }
+
+    // Labels only; ca65 .dbg files also list constants, which aren't
+    // addresses.
+    pub fn load_dbg(&mut self, text: &str) -> Result<usize, SymbolError> {
+        // id -> (start, file offset)
+        let mut segments = BTreeMap::new();
+        let mut labels = Vec::new();
+        for (i, line) in text.lines().enumerate() {
+            let Some((kind, fields)) = line.split_once(char::is_whitespace) else {
+                continue;
+            };
+            let fields = parse_fields(fields).map_err(|message| parse_error(i + 1, message))?;
+            let field = |key: &str| fields.iter().find(|(k, _)| k == key).map(|(_, v)| v);
+            let number = |key: &str| field(key).and_then(|value| parse_number(value));
+            match kind {
+                "seg" => {
+                    let id = number("id").ok_or_else(|| parse_error(i + 1, "seg without id"))?;
+                    segments.insert(id, (number("start").unwrap_or(0), number("ooffs")));
+                }
+                "sym" if field("type").is_some_and(|kind| kind == "lab") => {
+                    let name =
+                        field("name").ok_or_else(|| parse_error(i + 1, "sym without name"))?;
+                    let value = number("val")
+                        .and_then(|value| Address::try_from(value).ok())
+                        .ok_or_else(|| parse_error(i + 1, format!("bad address for {}", name)))?;
+                    let size = number("size").and_then(|size| u16::try_from(size).ok());
+                    labels.push((name.clone(), value, size.unwrap_or(1).max(1), number("seg")));
+                }
+                _ => {}
+            }
+        }
+        let count = labels.len();
+        for (name, addr, size, segment) in labels {
+            let bank = segment
+                .and_then(|id| segments.get(&id))
+                .and_then(|&(start, offset)| {
+                    let offset = offset? + (addr as usize).checked_sub(start)?;
+                    (addr >= 0x8000).then(|| offset.checked_sub(INES_HEADER))?
+                })
+                .map(|offset| offset / BANK_SIZE);
+            self.insert(Symbol {
+                name,
+                comment: None,
+                addr,
+                size,
+                bank,
+            });
+        }
+        Ok(count)
+    }
+
+    pub fn insert(&mut self, symbol: Symbol) {
+        self.by_addr.entry(symbol.addr).or_default().push(symbol);
+    }
+
+    pub fn len(&self) -> usize {
+        self.by_addr.values().map(Vec::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.by_addr.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.by_addr.clear();
+    }
+
+    // The named symbol at `addr`, or covering it for an array, that
+    // applies with `bank` mapped in there.
+    pub fn lookup(&self, addr: Address, bank: Option<usize>) -> Option<&Symbol> {
+        let applies = |symbol: &&Symbol| {
+            !symbol.name.is_empty() && symbol.bank.is_none_or(|b| Some(b) == bank)
+        };
+        if let Some(symbol) = self.by_addr.get(&addr).and_then(|s| s.iter().find(applies)) {
+            return Some(symbol);
+        }
+        self.by_addr
+            .range(..addr)
+            .rev()
+            .take(0x100)
+            .flat_map(|(_, symbols)| symbols.iter())
+            .filter(applies)
+            .find(|symbol| addr - symbol.addr < symbol.size)
+    }
+
+    // The comment at exactly `addr`, named or not.
+    pub fn comment(&self, addr: Address, bank: Option<usize>) -> Option<&str> {
+        self.by_addr
+            .get(&addr)?
+            .iter()
+            .filter(|symbol| symbol.bank.is_none_or(|b| Some(b) == bank))
+            .find_map(|symbol| symbol.comment.as_deref())
+    }
+
+    // `name` or `name+3` for what's mapped at `addr` right now.
+    pub fn name(&self, bus: &Bus, addr: Address) -> Option<String> {
+        self.name_in(addr, bank(bus, addr))
+    }
+
+    // The same with `bank` taken to be what's mapped at `addr`.
+    pub fn name_in(&self, addr: Address, bank: Option<usize>) -> Option<String> {
+        let symbol = self.lookup(addr, bank)?;
+        Some(match addr - symbol.addr {
+            0 => symbol.name.clone(),
+            offset => format!("{}+{}", symbol.name, offset),
+        })
+    }
+
+    // Swaps the addresses in a disassembled instruction for names:
+    // `$1234` and zero-page `$12`, but not `#$12`.
+    pub fn substitute(&self, bus: &Bus, text: &str) -> String {
+        if self.is_empty() {
+            return text.to_string();
+        }
+        substitute_with(text, |addr| self.name(bus, addr))
+    }
+}
+
+// The same with names from `name`, for text that isn't about what's
+// mapped in right now.
+pub fn substitute_with(text: &str, name: impl Fn(Address) -> Option<String>) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(at) = rest.find('$') {
+        let immediate = rest[..at].ends_with('#');
+        out.push_str(&rest[..at]);
+        let digits = rest[at + 1..]
+            .bytes()
+            .take_while(u8::is_ascii_hexdigit)
+            .count();
+        let hex = &rest[at + 1..at + 1 + digits];
+        let found = match (immediate, digits) {
+            (false, 2 | 4) => u16::from_str_radix(hex, 16).ok().and_then(&name),
+            _ => None,
+        };
+        match found {
+            Some(name) => out.push_str(&name),
+            None => {
+                out.push('$');
+                out.push_str(hex);
+            }
+        }
+        rest = &rest[at + 1 + digits..];
+    }
+    out.push_str(rest);
+    out
+}
+
+// The 16K PRG bank mapped at `addr`, if it's ROM.
+pub(crate) fn bank(bus: &Bus, addr: Address) -> Option<usize> {
+    bus.prg_offset(addr).map(|offset| offset / BANK_SIZE)
+}
+
+fn parse_number(text: &str) -> Option<usize> {
+    let text = text.trim();
+    match text.strip_prefix("0x").or_else(|| text.strip_prefix('$')) {
+        Some(hex) => usize::from_str_radix(hex, 16).ok(),
+        None => text.parse().ok(),
+    }
+}
+
+// `key=value,key="quoted, value"` as ld65 writes them.
+fn parse_fields(text: &str) -> Result<Vec<(String, String)>, String> {
+    let mut fields = Vec::new();
+    let mut rest = text.trim();
+    while !rest.is_empty() {
+        let (key, after) = rest
+            .split_once('=')
+            .ok_or_else(|| format!("expected key=value at {:?}", rest))?;
+        let (value, after) = match after.strip_prefix('"') {
+            Some(quoted) => {
+                let end = quoted.find('"').ok_or("unterminated string")?;
+                (&quoted[..end], &quoted[end + 1..])
+            }
+            None => after.split_once(',').map_or((after, ""), |(v, a)| (v, a)),
+        };
+        fields.push((key.trim().to_string(), value.to_string()));
+        rest = after.strip_prefix(',').unwrap_or(after).trim_start();
+    }
+    Ok(fields)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::emulator::test::counting_rom;
+    use crate::emulator::Emulator;
+
+    #[test]
+    fn test_name_lists_and_debug_files() {
+        let mut symbols = Symbols::new();
+        let ram = "$0000#frames#counted by the NMI\n$0200/10#buffer#\n$0010##just a comment\nthat goes on\n";
+        assert_eq!(symbols.load_nl(ram, None).unwrap(), 3);
+        assert_eq!(
+            symbols.load_nl("$C000#only_in_bank_1#", Some(1)).unwrap(),
+            1
+        );
+        let dbg = concat!(
+            "version\tmajor=2,minor=0\n",
+            "seg\tid=0,name=\"CODE\",start=0x008000,size=0x0100,addrsize=absolute,type=ro,oname=\"game, v1.nes\",ooffs=16\n",
+            "seg\tid=1,name=\"ZEROPAGE\",start=0x000000,size=0x0010,addrsize=zeropage,type=rw\n",
+            "sym\tid=0,name=\"reset\",addrsize=absolute,scope=0,def=1,val=0x8000,seg=0,type=lab\n",
+            "sym\tid=1,name=\"PPUCTRL\",addrsize=absolute,scope=0,def=2,val=0x2000,type=equ\n",
+            "sym\tid=2,name=\"temp\",addrsize=zeropage,size=2,scope=0,def=3,val=0x02,seg=1,type=lab\n",
+        );
+        assert_eq!(symbols.load_dbg(dbg).unwrap(), 2);
+        assert_eq!(symbols.len(), 6);
+
+        let reset = symbols.lookup(0x8000, Some(0)).unwrap();
+        assert_eq!((reset.name.as_str(), reset.bank), ("reset", Some(0)));
+        assert!(symbols.lookup(0x8000, Some(1)).is_none());
+        assert!(symbols.lookup(0xC000, Some(0)).is_none());
+        assert_eq!(symbols.lookup(0x0209, None).unwrap().name, "buffer");
+        assert!(symbols.lookup(0x020A, None).is_none());
+        assert_eq!(symbols.lookup(0x0003, None).unwrap().name, "temp");
+        // comments without a name don't name anything
+        assert!(symbols.lookup(0x0010, None).is_none());
+        assert_eq!(
+            symbols.comment(0x0010, None),
+            Some("just a comment\nthat goes on")
+        );
+
+        assert!(matches!(
+            symbols.load_nl("$zz#bad#", None),
+            Err(SymbolError::Parse { line: 1, .. })
+        ));
+        assert!(matches!(
+            symbols.load(Path::new("game.sym")),
+            Err(SymbolError::UnknownFormat | SymbolError::Io(_))
+        ));
+    }
+
+    #[test]
+    fn test_names_follow_the_mapped_bank() {
+        let mut emulator = Emulator::from_rom(&counting_rom()).unwrap();
+        let symbols = emulator.debugger_mut().symbols_mut();
+        symbols
+            .load_nl("$8000#reset#\n$C000#other_bank#", Some(0))
+            .unwrap();
+        symbols.load_nl("$0200/4#buffer#", None).unwrap();
+        let symbols = emulator.debugger().symbols().clone();
+        let bus = &emulator.cpu().bus;
+
+        assert_eq!(symbols.substitute(bus, "JMP $8000"), "JMP reset",);
+        // $C000 is bank 1 on a 32K NROM board
+        assert_eq!(symbols.substitute(bus, "JMP $C000"), "JMP $C000");
+        assert_eq!(symbols.substitute(bus, "LDA $0202,X"), "LDA buffer+2,X");
+        assert_eq!(symbols.substitute(bus, "LDA #$02"), "LDA #$02");
+
+        emulator.debugger_mut().set_code_logging(true);
+        emulator.run();
+        let lines = emulator.disassemble_around(0x800A, 0, 1);
+        assert_eq!(lines[0].text, "JMP loc_800A");
+        let lines = emulator.disassemble_around(0x8000, 0, 1);
+        assert_eq!(lines[0].label.as_deref(), Some("reset"));
+    }
+}
@@ -0,0 +1,259 @@
+// An event timeline for raster debugging: every write to a PPU, APU,
+// controller or mapper register, every NMI and IRQ taken and sprite 0
+// hitting, each with the scanline and dot it happened at and the
+// instruction behind it, frame by frame. A front end lays them out on a
+// 341x262 grid the way Mesen's event viewer does.
+//
+// Writes come through the watch hook, so like the rest of the debugger
+// the timeline only sees what Emulator::run runs. The CPU catches the
+// PPU up after each instruction, so a write is placed where the PPU was
+// when its instruction finished, the last cycle being when a store
+// writes; an interrupt where its handler starts, and a sprite 0 hit at
+// the end of the instruction it happened during. A frame runs from one
+// vblank to the next, as run counts them, so its list starts with the
+// last vblank's events and goes on with the picture's, in order.
+//
+// Cartridge RAM at $6000-$7FFF isn't a register and is left out.
+
+use std::fmt;
+
+use super::ppu_events::{PpuEvent, PpuPosition};
+use super::watch::{WatchHit, WatchHook, WatchKind};
+use crate::ppu::Ppu;
+
+type Address = u16;
+
+// What the timeline's watches go by; real ids count up from 1.
+pub(crate) const HOOK_ID: usize = usize::MAX;
+// a frame's worth; past this a frame's events are dropped and counted
+const MAX_EVENTS: usize = 0x10000;
+
+const REGISTERS: [(Address, Address); 4] = [
+    (0x2000, 0x3FFF),
+    (0x4000, 0x4017),
+    (0x4020, 0x5FFF),
+    (0x8000, 0xFFFF),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventKind {
+    Write { addr: Address, value: u8 },
+    Nmi,
+    Irq,
+    SpriteZeroHit,
+}
+
+// What a viewer colours by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Category {
+    Ppu,
+    Apu,
+    Input,
+    Mapper,
+    Nmi,
+    Irq,
+    SpriteZeroHit,
+}
+
+impl EventKind {
+    pub fn category(&self) -> Category {
+        match *self {
+            // OAM DMA counts with the PPU
+            EventKind::Write {
+                addr: 0x2000..=0x3FFF | 0x4014,
+                ..
+            } => Category::Ppu,
+            EventKind::Write { addr: 0x4016, .. } => Category::Input,
+            EventKind::Write {
+                addr: 0x4000..=0x4017,
+                ..
+            } => Category::Apu,
+            EventKind::Write { .. } => Category::Mapper,
+            EventKind::Nmi => Category::Nmi,
+            EventKind::Irq => Category::Irq,
+            EventKind::SpriteZeroHit => Category::SpriteZeroHit,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Event {
+    pub kind: EventKind,
+    pub scanline: u16,
+    pub dot: u16,
+    // the instruction that wrote, or was running; for an interrupt, the
+    // handler
+    pub pc: Address,
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:3}:{:3} ${:04X} ", self.scanline, self.dot, self.pc)?;
+        match self.kind {
+            EventKind::Write { addr, value } => write!(f, "${:02X} to ${:04X}", value, addr),
+            EventKind::Nmi => write!(f, "NMI"),
+            EventKind::Irq => write!(f, "IRQ"),
+            EventKind::SpriteZeroHit => write!(f, "sprite 0 hit"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Timeline {
+    current: Vec<Event>,
+    last: Vec<Event>,
+    // past MAX_EVENTS, in the frame running and the last
+    dropping: usize,
+    dropped: usize,
+    frames: u64,
+}
+
+impl Timeline {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // The last whole frame's events, in the order they happened.
+    pub fn frame(&self) -> &[Event] {
+        &self.last
+    }
+
+    // So far in the frame running.
+    pub fn current(&self) -> &[Event] {
+        &self.current
+    }
+
+    // How many the last frame had past MAX_EVENTS.
+    pub fn dropped(&self) -> usize {
+        self.dropped
+    }
+
+    // Frames finished since it started.
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    pub(crate) fn arm(&self, hook: &mut WatchHook) {
+        for (start, end) in REGISTERS {
+            hook.add(HOOK_ID, start, end, WatchKind::Write);
+        }
+    }
+
+    fn push(&mut self, kind: EventKind, ppu: &Ppu, pc: Address) {
+        if self.current.len() == MAX_EVENTS {
+            self.dropping += 1;
+            return;
+        }
+        self.current.push(Event {
+            kind,
+            scanline: ppu.scanline(),
+            dot: ppu.dot(),
+            pc,
+        });
+    }
+
+    // Just after an interrupt sent the CPU to its handler at `pc`.
+    pub(crate) fn interrupt(&mut self, ppu: &Ppu, pc: Address, nmi: bool) {
+        let kind = if nmi { EventKind::Nmi } else { EventKind::Irq };
+        self.push(kind, ppu, pc);
+    }
+
+    // After the instruction at `pc`, which started with the PPU at
+    // `before` and made `writes`, the hook's hits for HOOK_ID.
+    pub(crate) fn instruction(
+        &mut self,
+        writes: &[WatchHit],
+        ppu: &Ppu,
+        pc: Address,
+        before: PpuPosition,
+    ) {
+        for hit in writes {
+            let kind = EventKind::Write {
+                addr: hit.addr,
+                value: hit.value,
+            };
+            self.push(kind, ppu, pc);
+        }
+        if before.passed(PpuPosition::of(ppu), PpuEvent::SpriteZeroHit) {
+            self.push(EventKind::SpriteZeroHit, ppu, pc);
+        }
+    }
+
+    pub(crate) fn end_frame(&mut self) {
+        self.last = std::mem::take(&mut self.current);
+        self.dropped = std::mem::take(&mut self.dropping);
+        self.frames += 1;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::emulator::test::counting_rom;
+    use crate::emulator::Emulator;
+
+    #[test]
+    fn test_records_writes_and_interrupts_by_frame() {
+        let mut rom = counting_rom();
+        // the reset code writes $2000; log a mapper write from the NMI
+        // handler too: inc $00; sta $8000; rti
+        rom[16 + 0x1000..16 + 0x1006].copy_from_slice(&[0xe6, 0x00, 0x8d, 0x00, 0x80, 0x40]);
+        let mut emulator = Emulator::from_rom(&rom).unwrap();
+        emulator.debugger_mut().start_timeline();
+        emulator.run();
+        let first = emulator.debugger().timeline().unwrap().frame();
+        assert_eq!(first.len(), 2);
+        assert_eq!(first[1].kind.category(), Category::Ppu);
+        assert_eq!(
+            (first[1].kind, first[1].scanline, first[1].pc),
+            (
+                EventKind::Write {
+                    addr: 0x2001,
+                    value: 0x08
+                },
+                0,
+                0x8007
+            )
+        );
+        emulator.run();
+
+        let timeline = emulator.debugger().timeline().unwrap();
+        assert_eq!(timeline.frames(), 2);
+        let events = timeline.frame();
+        assert_eq!(events[0].kind, EventKind::Nmi);
+        assert_eq!(events[0].pc, 0x9000);
+        assert_eq!(events[0].scanline, 241);
+        assert_eq!(
+            events[1].kind,
+            EventKind::Write {
+                addr: 0x8000,
+                value: 0x08
+            }
+        );
+        assert_eq!(events[1].kind.category(), Category::Mapper);
+        assert_eq!(events[1].pc, 0x9002);
+        assert!(events[1].dot > events[0].dot);
+        assert_eq!(events.len(), 2, "{:?}", events);
+        assert!(timeline.current().is_empty());
+        assert_eq!(
+            events[0].to_string(),
+            format!("241:{:3} $9000 NMI", events[0].dot)
+        );
+
+        assert!(emulator.debugger_mut().stop_timeline().is_some());
+        assert!(!emulator.debugger().is_active());
+    }
+
+    #[test]
+    fn test_categories() {
+        let write = |addr| EventKind::Write { addr, value: 0 }.category();
+        assert_eq!(write(0x2001), Category::Ppu);
+        assert_eq!(write(0x3FFF), Category::Ppu);
+        assert_eq!(write(0x4014), Category::Ppu);
+        assert_eq!(write(0x4016), Category::Input);
+        assert_eq!(write(0x4017), Category::Apu);
+        assert_eq!(write(0x4000), Category::Apu);
+        assert_eq!(write(0x5100), Category::Mapper);
+        assert_eq!(write(0xC000), Category::Mapper);
+    }
+}
@@ -0,0 +1,191 @@
+// Watchpoints: stop when the CPU touches an address. The bus reports every
+// access it serves to a WatchHook while Emulator::run has one armed, which
+// notes the matching ones; run stops once the instruction that made them
+// has finished, so a write has landed by the time anyone looks, and the
+// first whose condition holds is the one reported.
+//
+// Addresses are compared as given and with the mirrors folded away, so a
+// watch on $0042 also sees $0842 and one on $2002 sees $3FFA. Opcode and
+// operand fetches are reads like any other.
+
+use alloc::vec::Vec;
+use core::fmt;
+
+use super::expr::Expr;
+
+type Address = u16;
+type Value = u8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    Read,
+    Write,
+}
+
+impl fmt::Display for Access {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Access::Read => write!(f, "read"),
+            Access::Write => write!(f, "write"),
+        }
+    }
+}
+
+// Which accesses a watchpoint stops on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WatchKind {
+    Read,
+    Write,
+    // either
+    Access,
+}
+
+impl WatchKind {
+    fn matches(self, access: Access) -> bool {
+        match self {
+            WatchKind::Read => access == Access::Read,
+            WatchKind::Write => access == Access::Write,
+            WatchKind::Access => true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Watchpoint {
+    pub id: usize,
+    // inclusive
+    pub start: Address,
+    pub end: Address,
+    pub kind: WatchKind,
+    pub enabled: bool,
+    pub condition: Option<Expr>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WatchHit {
+    pub id: usize,
+    pub addr: Address,
+    pub value: Value,
+    pub access: Access,
+}
+
+// Internal RAM repeats every 2K up to $1FFF and the PPU's eight registers
+// every 8 bytes up to $3FFF.
+fn unmirror(addr: Address) -> Address {
+    match addr {
+        0x0000..=0x1FFF => addr & 0x07FF,
+        0x2000..=0x3FFF => 0x2000 | (addr & 0x0007),
+        _ => addr,
+    }
+}
+
+// The bus's end. Empty unless armed, so playing pays one branch per
+// access. The debugger arms it; a port without one can too, through
+// Bus::watch_mut.
+#[derive(Debug, Default)]
+pub struct WatchHook {
+    // (id, start, end, kind) of the enabled ones
+    watchpoints: Vec<(usize, Address, Address, WatchKind)>,
+    hits: Vec<WatchHit>,
+}
+
+impl WatchHook {
+    pub fn arm(&mut self, watchpoints: &[Watchpoint]) {
+        self.watchpoints.clear();
+        self.watchpoints.extend(
+            watchpoints
+                .iter()
+                .filter(|watch| watch.enabled)
+                .map(|watch| (watch.id, watch.start, watch.end, watch.kind)),
+        );
+        self.hits.clear();
+    }
+
+    // One more, for things that aren't Watchpoints but stop the same way.
+    pub fn add(&mut self, id: usize, start: Address, end: Address, kind: WatchKind) {
+        self.watchpoints.push((id, start, end, kind));
+    }
+
+    pub fn disarm(&mut self) {
+        self.watchpoints.clear();
+        self.hits.clear();
+    }
+
+    #[inline]
+    pub(crate) fn is_armed(&self) -> bool {
+        !self.watchpoints.is_empty()
+    }
+
+    pub(crate) fn access(&mut self, addr: Address, value: Value, access: Access) {
+        let unmirrored = unmirror(addr);
+        let covers =
+            |start, end| (start..=end).contains(&addr) || (start..=end).contains(&unmirrored);
+        for &(id, start, end, kind) in &self.watchpoints {
+            if kind.matches(access) && covers(start, end) {
+                self.hits.push(WatchHit {
+                    id,
+                    addr,
+                    value,
+                    access,
+                });
+            }
+        }
+    }
+
+    // What the last instruction set off, in the order it happened.
+    pub fn take_hits(&mut self) -> Vec<WatchHit> {
+        core::mem::take(&mut self.hits)
+    }
+
+    // Just the hits for `id`, leaving the rest.
+    pub fn take_hits_for(&mut self, id: usize) -> Vec<WatchHit> {
+        let (taken, rest) = self.hits.drain(..).partition(|hit| hit.id == id);
+        self.hits = rest;
+        taken
+    }
+
+    pub fn has_hits(&self) -> bool {
+        !self.hits.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_hook_matches_kinds_and_mirrors() {
+        let watch = |id, start, end, kind| Watchpoint {
+            id,
+            start,
+            end,
+            kind,
+            enabled: true,
+            condition: None,
+        };
+        let mut hook = WatchHook::default();
+        assert!(!hook.is_armed());
+        hook.arm(&[
+            watch(1, 0x0042, 0x0042, WatchKind::Write),
+            watch(2, 0x2002, 0x2002, WatchKind::Read),
+            Watchpoint {
+                enabled: false,
+                ..watch(3, 0x0000, 0xFFFF, WatchKind::Access)
+            },
+        ]);
+        assert!(hook.is_armed());
+
+        hook.access(0x0042, 7, Access::Read);
+        assert!(!hook.has_hits());
+        hook.access(0x0842, 7, Access::Write);
+        hook.access(0x3FFA, 0x80, Access::Read);
+        let hits = hook.take_hits();
+        assert_eq!(hits.len(), 2);
+        assert_eq!((hits[0].id, hits[0].addr, hits[0].value), (1, 0x0842, 7));
+        assert_eq!((hits[1].id, hits[1].access), (2, Access::Read));
+
+        hook.disarm();
+        hook.access(0x0042, 7, Access::Write);
+        assert!(!hook.has_hits());
+    }
+}
@@ -0,0 +1,1292 @@
+// The whole console behind one type: load a cartridge, feed it input and
+// pull finished frames out. Frontends only need this; everything inside is
+// still reachable for debuggers and tools.
+//
+//     let mut emulator = Emulator::builder()
+//         .rom("smb.nes")
+//         .region(Region::Ntsc)
+//         .audio_sample_rate(48000)
+//         .audio_latency(Latency { queued: 1024, buffer: Some(256) })
+//         .build()?;
+//     emulator.set_input(&input);
+//     emulator.run_frame();
+//     show(emulator.frame(), emulator.audio_samples());
+
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+
+use crate::apu::mixer::Mixer;
+use crate::audio::rate_control::RateControl;
+use crate::audio::{AudioSink, Latency};
+use crate::cartridge::{Cartridge, CartridgeError};
+use crate::cheat::Cheats;
+use crate::cpu::Cpu;
+use crate::debugger::disasm::{self, Line};
+use crate::debugger::memory::{self, MemoryPort, MemorySpace};
+use crate::debugger::ppu_events::PpuPosition;
+use crate::debugger::{Debugger, RunStatus};
+use crate::input::joypad::JoypadButton;
+use crate::input::InputState;
+use crate::movie::{FrameInput, MovieCommand};
+use crate::peripheral::{Peripheral, PeripheralError, PeripheralId};
+use crate::region::Region;
+use crate::render::frame::Frame;
+use crate::render::palette::Palette;
+use crate::rng::RamInit;
+use crate::rom::Rom;
+use crate::run_ahead::Rollback;
+use crate::state::{self, FrameHash, MachineHasher, Snapshot, StateError};
+use crate::trace::Tracer;
+use crate::vs::{VsPpu, VsSystem};
+
+#[derive(Debug)]
+pub struct Emulator {
+    cpu: Cpu,
+    // the cartridge keeps its work RAM with a battery
+    battery: bool,
+    crc32: u32,
+    prg_size: usize,
+    sample_rate: Option<u32>,
+    audio_latency: Latency,
+    audio_buffer: Vec<f32>,
+    trace: Option<Tracer>,
+    debugger: Debugger,
+    memory_port: Option<MemoryPort>,
+    // what every power-on starts from; see rng.rs
+    seed: u64,
+    ram_init: RamInit,
+    // with state hashing on, and the state it hashes
+    hasher: Option<MachineHasher>,
+}
+
+// Nothing in a machine is tied to the thread that made it, so servers can
+// run many of them on a pool. Checked here rather than left to whoever
+// first tries.
+const _: () = {
+    const fn assert_send<T: Send>() {}
+    assert_send::<Emulator>();
+    assert_send::<EmulatorBuilder>();
+    assert_send::<Cpu>();
+    assert_send::<Cartridge>();
+    assert_send::<Snapshot>();
+};
+
+// What to build an Emulator from and how to set it up; everything but the
+// ROM can be left out.
+#[derive(Debug, Default)]
+pub struct EmulatorBuilder {
+    rom: Option<RomSource>,
+    region: Option<Region>,
+    palette: Option<Palette>,
+    sample_rate: Option<u32>,
+    audio_latency: Latency,
+    sprite_limit: Option<bool>,
+    extra_scanlines: u16,
+    seed: u64,
+    ram_init: RamInit,
+}
+
+#[derive(Debug)]
+enum RomSource {
+    Path(PathBuf),
+    Bytes(Rom),
+    Cartridge(Cartridge),
+}
+
+#[derive(Debug)]
+pub enum BuildError {
+    NoRom,
+    Read(PathBuf, io::Error),
+    Cartridge(CartridgeError),
+}
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BuildError::NoRom => write!(f, "no ROM to run"),
+            BuildError::Read(path, err) => write!(f, "can't read {}: {}", path.display(), err),
+            BuildError::Cartridge(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl Error for BuildError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            BuildError::NoRom => None,
+            BuildError::Read(_, err) => Some(err),
+            BuildError::Cartridge(err) => Some(err),
+        }
+    }
+}
+
+impl From<CartridgeError> for BuildError {
+    fn from(err: CartridgeError) -> Self {
+        BuildError::Cartridge(err)
+    }
+}
+
+impl EmulatorBuilder {
+    // An iNES file's name can say which console it's for; see region.rs.
+    pub fn rom(mut self, path: impl Into<PathBuf>) -> Self {
+        self.rom = Some(RomSource::Path(path.into()));
+        self
+    }
+
+    // Kept as it is and shared, not copied, unless it's borrowed; see
+    // rom.rs.
+    pub fn rom_bytes(mut self, rom: impl Into<Rom>) -> Self {
+        self.rom = Some(RomSource::Bytes(rom.into()));
+        self
+    }
+
+    pub fn cartridge(mut self, cartridge: Cartridge) -> Self {
+        self.rom = Some(RomSource::Cartridge(cartridge));
+        self
+    }
+
+    // Instead of the one the cartridge is for.
+    pub fn region(mut self, region: Region) -> Self {
+        self.region = Some(region);
+        self
+    }
+
+    pub fn palette(mut self, palette: Palette) -> Self {
+        self.palette = Some(palette);
+        self
+    }
+
+    // Audio stays off without one.
+    pub fn audio_sample_rate(mut self, sample_rate: u32) -> Self {
+        self.sample_rate = Some(sample_rate);
+        self
+    }
+
+    // What the frontend opening the device should aim for; see
+    // Emulator::audio_latency.
+    pub fn audio_latency(mut self, latency: Latency) -> Self {
+        self.audio_latency = latency;
+        self
+    }
+
+    pub fn sprite_limit(mut self, enabled: bool) -> Self {
+        self.sprite_limit = Some(enabled);
+        self
+    }
+
+    pub fn extra_scanlines(mut self, lines: u16) -> Self {
+        self.extra_scanlines = lines;
+        self
+    }
+
+    // For everything the machine randomizes; 0 without one.
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    // Zeros without it.
+    pub fn ram_init(mut self, ram: RamInit) -> Self {
+        self.ram_init = ram;
+        self
+    }
+
+    pub fn build(self) -> Result<Emulator, BuildError> {
+        let cartridge = match self.rom.ok_or(BuildError::NoRom)? {
+            RomSource::Path(path) => {
+                let rom = match fs::read(&path) {
+                    Ok(rom) => rom,
+                    Err(err) => return Err(BuildError::Read(path, err)),
+                };
+                let mut cartridge = Cartridge::from_rom(rom)?;
+                if let Some(name) = path.file_name() {
+                    cartridge.guess_region(&name.to_string_lossy());
+                }
+                cartridge
+            }
+            RomSource::Bytes(rom) => Cartridge::from_rom(rom)?,
+            RomSource::Cartridge(cartridge) => cartridge,
+        };
+        let mut emulator = Emulator::new(cartridge)?;
+        emulator.power_on(self.seed, self.ram_init);
+        if let Some(region) = self.region {
+            emulator.set_region(region);
+        }
+        if let Some(palette) = self.palette {
+            emulator.set_palette(palette);
+        }
+        if let Some(enabled) = self.sprite_limit {
+            emulator.set_sprite_limit(enabled);
+        }
+        emulator.set_extra_scanlines(self.extra_scanlines);
+        if let Some(sample_rate) = self.sample_rate {
+            emulator.enable_audio(sample_rate);
+        }
+        emulator.set_audio_latency(self.audio_latency);
+        Ok(emulator)
+    }
+}
+
+impl Emulator {
+    pub fn builder() -> EmulatorBuilder {
+        EmulatorBuilder::default()
+    }
+
+    pub fn new(cartridge: Cartridge) -> Result<Self, CartridgeError> {
+        let (battery, crc32) = (cartridge.battery, cartridge.crc32());
+        let prg_size = cartridge.prg_rom.len();
+        let mut cpu = Cpu::new();
+        cpu.bus.set_region(cartridge.region);
+        if let Some(cabinet) = cartridge.vs {
+            cpu.bus.vs = Some(VsSystem::new());
+            cpu.bus.ppu.set_vs(Some(cabinet.ppu));
+        }
+        cpu.bus.insert_cartridge(cartridge.into_mapper()?);
+        cpu.reset();
+        Ok(Emulator {
+            cpu,
+            battery,
+            crc32,
+            prg_size,
+            sample_rate: None,
+            audio_latency: Latency::default(),
+            audio_buffer: Vec::new(),
+            trace: None,
+            debugger: Debugger::new(),
+            memory_port: None,
+            seed: 0,
+            ram_init: RamInit::default(),
+            hasher: None,
+        })
+    }
+
+    // A console with the slot empty, e.g. while a launcher picks a game;
+    // it isn't meant to be run.
+    pub fn empty() -> Self {
+        Emulator {
+            cpu: Cpu::new(),
+            battery: false,
+            crc32: 0,
+            prg_size: 0,
+            sample_rate: None,
+            audio_latency: Latency::default(),
+            audio_buffer: Vec::new(),
+            trace: None,
+            debugger: Debugger::new(),
+            memory_port: None,
+            seed: 0,
+            ram_init: RamInit::default(),
+            hasher: None,
+        }
+    }
+
+    pub fn from_rom(rom: &[u8]) -> Result<Self, CartridgeError> {
+        Self::new(Cartridge::from_bytes(rom)?)
+    }
+
+    // Another cartridge, as if the console were switched off, the cartridge
+    // swapped and switched on again. Palette, sprite limit, audio,
+    // tracing, breakpoints, the memory port, peripherals, the seed and
+    // state hashing (starting over) carry over.
+    // Save the old cartridge's battery RAM first.
+    pub fn insert_cartridge(&mut self, cartridge: Cartridge) -> Result<(), CartridgeError> {
+        let mut next = Emulator::new(cartridge)?;
+        next.cpu.bus.ppu.take_host_state(&mut self.cpu.bus.ppu);
+        next.set_extra_scanlines(self.extra_scanlines());
+        next.power_on(self.seed, self.ram_init);
+        next.cpu.bus.peripherals = std::mem::take(&mut self.cpu.bus.peripherals);
+        if let Some(sample_rate) = self.sample_rate {
+            next.enable_audio(sample_rate);
+        }
+        next.audio_latency = self.audio_latency;
+        next.trace = self.trace.take();
+        next.debugger = std::mem::take(&mut self.debugger);
+        // coverage is of the ROM that was in
+        if next.debugger.stop_coverage().is_some() {
+            next.start_coverage();
+        }
+        next.memory_port = self.memory_port.take();
+        next.set_state_hashing(self.hasher.is_some());
+        *self = next;
+        Ok(())
+    }
+
+    // The seed and RAM contents to power on with, from here and every
+    // cartridge after. Meant for before the first frame: it's the power
+    // switch, short of restarting the CPU.
+    pub fn power_on(&mut self, seed: u64, ram: RamInit) {
+        self.seed = seed;
+        self.ram_init = ram;
+        self.cpu.bus.power_on(seed, ram);
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn has_cartridge(&self) -> bool {
+        self.cpu.bus.has_cartridge()
+    }
+
+    // The cartridge's checksum, see Cartridge::crc32; 0 with none in.
+    pub fn crc32(&self) -> u32 {
+        self.crc32
+    }
+
+    // The battery-backed RAM, as a .sav file holds it; None if the
+    // cartridge has no battery.
+    pub fn battery_ram(&self) -> Option<&[u8]> {
+        let ram = self.cpu.bus.cartridge_ram();
+        (self.battery && !ram.is_empty()).then_some(ram)
+    }
+
+    // A short file fills the start of the RAM and extra bytes are left out,
+    // so saves from emulators that size RAM differently still load.
+    pub fn load_battery_ram(&mut self, data: &[u8]) {
+        if !self.battery {
+            return;
+        }
+        let ram = self.cpu.bus.cartridge_ram_mut();
+        let len = ram.len().min(data.len());
+        ram[..len].copy_from_slice(&data[..len]);
+    }
+
+    // The reset button: CPU and PPU restart, RAM keeps its contents.
+    pub fn reset(&mut self) {
+        self.cpu.bus.ppu.reset();
+        self.cpu.reset();
+    }
+
+    // One frame of a movie: the reset button if it's pressed, then the
+    // pads. A power cycle needs the ROM again; see movie/replay.rs.
+    pub fn play_frame(&mut self, input: &FrameInput) {
+        self.set_input(input);
+        self.run_frame();
+    }
+
+    // The pads for the frames that follow, and the reset button or a coin
+    // if the input has them.
+    pub fn set_input(&mut self, input: &FrameInput) {
+        if input.commands.contains(MovieCommand::SOFT_RESET) {
+            self.reset();
+        }
+        if input.commands.contains(MovieCommand::VS_COIN) {
+            self.insert_coin(0);
+        }
+        input.apply(&mut self.cpu.bus.controllers);
+    }
+
+    // Runs until the PPU finishes the next picture (the start of vblank).
+    pub fn run_frame(&mut self) {
+        let span = tracing::debug_span!("frame", number = self.cpu.bus.ppu.frame_count());
+        let _entered = span.enter();
+        self.sync_memory();
+        let target = self.cpu.bus.ppu.frame_count() + 1;
+        while self.cpu.bus.ppu.frame_count() < target {
+            if let Some(trace) = self.trace.as_mut() {
+                // first, so the line is for the instruction that runs
+                if self.cpu.service_interrupts() {
+                    trace.interrupt(&self.cpu);
+                }
+                trace.log(&mut self.cpu, self.debugger.symbols());
+            }
+            self.cpu.step();
+        }
+        self.cpu.bus.apu.end_audio_frame();
+        self.hash_frame();
+    }
+
+    // run_frame for debuggers: stops early if a breakpoint is hit, just
+    // before the instruction there, a watchpoint, just after the one that
+    // made the access, or a step finishes. Running again carries on with
+    // the same frame.
+    pub fn run(&mut self) -> RunStatus {
+        if !self.debugger.is_active() {
+            self.run_frame();
+            return RunStatus::FrameDone;
+        }
+        self.sync_memory();
+        self.debugger.arm(self.cpu.bus.watch_mut());
+        let status = self.run_debugged();
+        self.cpu.bus.watch_mut().disarm();
+        status
+    }
+
+    fn run_debugged(&mut self) -> RunStatus {
+        let target = self.cpu.bus.ppu.frame_count() + 1;
+        while self.cpu.bus.ppu.frame_count() < target {
+            let from = self.cpu.program_counter;
+            if self.cpu.service_interrupts() {
+                self.debugger.entered_interrupt(&mut self.cpu, from);
+                if let Some(trace) = self.trace.as_mut() {
+                    trace.interrupt(&self.cpu);
+                }
+            }
+            let pc = self.cpu.program_counter;
+            if let Some(reason) = self.debugger.check(&mut self.cpu) {
+                return RunStatus::Stopped(reason);
+            }
+            if let Some(trace) = self.trace.as_mut() {
+                trace.log(&mut self.cpu, self.debugger.symbols());
+            }
+            if let Some(log) = self.debugger.code_log_mut() {
+                log.log(&mut self.cpu.bus, pc);
+            }
+            if let Some(coverage) = self.debugger.coverage_mut() {
+                coverage.log(&mut self.cpu.bus, pc);
+            }
+            let opcode = self.cpu.bus.peek(pc);
+            let before = PpuPosition::of(&self.cpu.bus.ppu);
+            self.cpu.step();
+            self.debugger.profile(&self.cpu.bus, pc, self.cpu.cycles);
+            self.debugger.track_calls(&self.cpu, opcode, pc);
+            self.debugger.log_events(&mut self.cpu.bus, pc, before);
+            if self.cpu.bus.watch_mut().has_hits() {
+                let hits = self.cpu.bus.watch_mut().take_hits();
+                if let Some(reason) = self.debugger.check_hits(&mut self.cpu, pc, &hits) {
+                    return RunStatus::Stopped(reason);
+                }
+            }
+            if self.debugger.has_ppu_breakpoints() {
+                if let Some(reason) = self.debugger.check_ppu(&mut self.cpu, pc, before) {
+                    return RunStatus::Stopped(reason);
+                }
+            }
+            if let Some(reason) = self.debugger.stepped(self.cpu.program_counter) {
+                return RunStatus::Stopped(reason);
+            }
+        }
+        self.cpu.bus.apu.end_audio_frame();
+        self.hash_frame();
+        self.debugger.frame_done();
+        RunStatus::FrameDone
+    }
+
+    // PRG coverage of the cartridge in, from a fresh start; see
+    // debugger/coverage.rs.
+    pub fn start_coverage(&mut self) {
+        self.debugger.start_coverage(self.prg_size);
+    }
+
+    pub fn run_frames(&mut self, frames: u64) {
+        for _ in 0..frames {
+            self.run_frame();
+        }
+    }
+
+    // Logs every instruction from here on; see trace.rs.
+    pub fn set_trace(&mut self, trace: Option<Tracer>) {
+        self.trace = trace;
+    }
+
+    pub fn trace_mut(&mut self) -> Option<&mut Tracer> {
+        self.trace.as_mut()
+    }
+
+    pub fn debugger(&self) -> &Debugger {
+        &self.debugger
+    }
+
+    pub fn debugger_mut(&mut self) -> &mut Debugger {
+        &mut self.debugger
+    }
+
+    // See cheat/mod.rs; they go with the cartridge, so another one starts
+    // without any.
+    pub fn cheats(&self) -> &Cheats {
+        self.cpu.bus.cheats()
+    }
+
+    pub fn cheats_mut(&mut self) -> &mut Cheats {
+        self.cpu.bus.cheats_mut()
+    }
+
+    // A listing for a debugger, `before` lines up to `addr` and `after`
+    // from it; see debugger/disasm.rs.
+    pub fn disassemble_around(&mut self, addr: u16, before: usize, after: usize) -> Vec<Line> {
+        disasm::around(
+            &mut self.cpu.bus,
+            self.debugger.code_log(),
+            self.debugger.symbols(),
+            addr,
+            before,
+            after,
+        )
+    }
+
+    // The memory editor's view; see debugger/memory.rs.
+    pub fn read_memory(&mut self, space: MemorySpace, addr: u16) -> u8 {
+        memory::read(&mut self.cpu.bus, space, addr)
+    }
+
+    pub fn write_memory(&mut self, space: MemorySpace, addr: u16, value: u8) {
+        memory::write(&mut self.cpu.bus, space, addr, value);
+    }
+
+    // For a memory editor on another thread; every call hands out a
+    // handle to the same port.
+    pub fn memory_port(&mut self) -> MemoryPort {
+        self.memory_port
+            .get_or_insert_with(MemoryPort::default)
+            .clone()
+    }
+
+    // Applies the port's writes and refreshes its views. Running does
+    // this each frame; call it while paused to keep a port live.
+    pub fn sync_memory(&mut self) {
+        if let Some(port) = &self.memory_port {
+            port.sync(&mut self.cpu.bus);
+        }
+    }
+
+    pub fn set_palette(&mut self, palette: Palette) {
+        self.cpu.bus.ppu.set_colours(palette);
+    }
+
+    pub fn palette(&self) -> Palette {
+        self.cpu.bus.ppu.colours()
+    }
+
+    pub fn mixer_mut(&mut self) -> &mut Mixer {
+        &mut self.cpu.bus.apu.mixer
+    }
+
+    pub fn set_sprite_limit(&mut self, enabled: bool) {
+        self.cpu.bus.ppu.set_sprite_limit(enabled);
+    }
+
+    // Overclocking; see Bus::set_extra_scanlines.
+    pub fn set_extra_scanlines(&mut self, lines: u16) {
+        self.cpu.bus.set_extra_scanlines(lines);
+    }
+
+    pub fn extra_scanlines(&self) -> u16 {
+        self.cpu.bus.extra_scanlines()
+    }
+
+    // A device of the host's own on the bus at `range`; see peripheral.rs.
+    pub fn attach_peripheral(
+        &mut self,
+        range: RangeInclusive<u16>,
+        device: Box<dyn Peripheral>,
+    ) -> Result<PeripheralId, PeripheralError> {
+        self.cpu.bus.peripherals.attach(range, device)
+    }
+
+    pub fn detach_peripheral(&mut self, id: PeripheralId) -> Option<Box<dyn Peripheral>> {
+        self.cpu.bus.peripherals.detach(id)
+    }
+
+    pub fn peripheral_mut(&mut self, id: PeripheralId) -> Option<&mut (dyn Peripheral + 'static)> {
+        self.cpu.bus.peripherals.get_mut(id)
+    }
+
+    // Audio is off until a frontend asks for it at its device's rate.
+    pub fn enable_audio(&mut self, sample_rate: u32) {
+        self.sample_rate = Some(sample_rate);
+        let clock = self.region().cpu_clock();
+        self.cpu.bus.apu.enable_audio(clock, sample_rate);
+    }
+
+    // The console the game runs on, from the cartridge until set.
+    pub fn region(&self) -> Region {
+        self.cpu.bus.region()
+    }
+
+    // Best done before the first frame, as a power cycle would be.
+    pub fn set_region(&mut self, region: Region) {
+        self.cpu.bus.set_region(region);
+        if let Some(sample_rate) = self.sample_rate {
+            self.enable_audio(sample_rate);
+        }
+    }
+
+    pub fn frame_rate(&self) -> f64 {
+        self.region().frame_rate()
+    }
+
+    // Appends the samples of the frames run since the last call.
+    pub fn read_audio(&mut self, out: &mut Vec<f32>) {
+        self.cpu.bus.apu.read_samples(out);
+    }
+
+    // The samples of the frames run since the last read, as read_audio
+    // would append them.
+    pub fn audio_samples(&mut self) -> &[f32] {
+        self.audio_buffer.clear();
+        self.cpu.bus.apu.read_samples(&mut self.audio_buffer);
+        &self.audio_buffer
+    }
+
+    // Throws away the samples of the frames run since the last read, for
+    // frames nobody will hear.
+    pub fn discard_audio(&mut self) {
+        self.audio_buffer.clear();
+        self.cpu.bus.apu.read_samples(&mut self.audio_buffer);
+    }
+
+    // What the last push_audio, audio_samples or discard_audio took, for
+    // recording.
+    pub fn last_audio(&self) -> &[f32] {
+        &self.audio_buffer
+    }
+
+    // The queue depth and device buffer audio is meant to run with. The
+    // emulator itself only produces samples; this is for whoever opens the
+    // device (the buffer) and pushes to it (rate_control, for push_audio).
+    pub fn audio_latency(&self) -> Latency {
+        self.audio_latency
+    }
+
+    pub fn set_audio_latency(&mut self, latency: Latency) {
+        self.audio_latency = latency;
+    }
+
+    pub fn rate_control(&self) -> RateControl {
+        RateControl::new(self.audio_latency.queued)
+    }
+
+    // Hands the finished samples to `sink` and retunes the resampler
+    // against its queue depth.
+    pub fn push_audio(&mut self, sink: &mut dyn AudioSink, rate: &RateControl) {
+        self.audio_buffer.clear();
+        self.cpu.bus.apu.read_samples(&mut self.audio_buffer);
+        sink.push_samples(&self.audio_buffer);
+        if let Some(blip) = self.cpu.bus.apu.blip_mut() {
+            rate.update(blip, sink);
+        }
+    }
+
+    // Everything but the ROM, see state.rs.
+    pub fn save_state(&self) -> Vec<u8> {
+        state::save(&self.cpu)
+    }
+
+    pub fn load_state(&mut self, data: &[u8]) -> Result<(), StateError> {
+        state::load(&mut self.cpu, data)
+    }
+
+    // The same, but cheap enough for every frame: no compression, and
+    // snapshot_into reuses the last snapshot's memory.
+    pub fn snapshot(&self) -> Snapshot {
+        let mut snapshot = Snapshot::default();
+        self.snapshot_into(&mut snapshot);
+        snapshot
+    }
+
+    pub fn snapshot_into(&self, snapshot: &mut Snapshot) {
+        state::save_into(&self.cpu, snapshot.data_mut());
+    }
+
+    pub fn restore(&mut self, snapshot: &Snapshot) -> Result<(), StateError> {
+        state::load(&mut self.cpu, snapshot.as_bytes())
+    }
+
+    // The machine hashed as it is now, all of it: what frame_hash's
+    // `state` would be after a frame ending here.
+    pub fn state_hash(&self) -> u64 {
+        state::hash_machine(&self.cpu)
+    }
+
+    // Hashes the machine at the end of every frame from here on, only
+    // going over what changed; see MachineHasher. It's what movie replays
+    // and netplay compare frame by frame to tell two machines haven't
+    // drifted apart. Turning it on starts the rolling hash over.
+    pub fn set_state_hashing(&mut self, enabled: bool) {
+        self.hasher = enabled.then(MachineHasher::new);
+    }
+
+    // The last frame's, with hashing on and a frame run since.
+    pub fn frame_hash(&self) -> Option<FrameHash> {
+        self.hasher
+            .as_ref()
+            .map(MachineHasher::last)
+            .filter(|hash| hash.frames > 0)
+    }
+
+    fn hash_frame(&mut self) {
+        if let Some(hasher) = self.hasher.as_mut() {
+            hasher.update(&mut self.cpu);
+        }
+    }
+
+    // Through a file, compressed; makes the directory.
+    pub fn save_state_to(&self, path: &Path) -> io::Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, state::compress(&self.save_state()))
+    }
+
+    pub fn load_state_from(&mut self, path: &Path) -> io::Result<()> {
+        let data = fs::read(path)?;
+        state::decompress(&data)
+            .and_then(|state| self.load_state(&state))
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+
+    pub fn frame(&self) -> &Frame {
+        self.cpu.bus.ppu.frame()
+    }
+
+    pub fn frame_count(&self) -> u64 {
+        self.cpu.bus.ppu.frame_count()
+    }
+
+    pub fn set_buttons(&mut self, player: usize, buttons: JoypadButton) {
+        self.cpu.bus.controllers.set_buttons(player, buttons);
+    }
+
+    // What the last frame ran with, for an input display; see InputState.
+    pub fn input_state(&self) -> InputState {
+        self.cpu.bus.controllers.state()
+    }
+
+    // Whether the cartridge is a VS. System game; see vs.rs. The rest of
+    // these do nothing otherwise.
+    pub fn is_vs(&self) -> bool {
+        self.cpu.bus.vs.is_some()
+    }
+
+    pub fn vs_ppu(&self) -> Option<VsPpu> {
+        self.cpu.bus.ppu.vs()
+    }
+
+    // For a dump whose header doesn't say, or says wrong.
+    pub fn set_vs_ppu(&mut self, ppu: VsPpu) {
+        if self.is_vs() {
+            self.cpu.bus.ppu.set_vs(Some(ppu));
+        }
+    }
+
+    pub fn dip_switches(&self) -> Option<u8> {
+        self.cpu.bus.vs.as_ref().map(|vs| vs.dip_switches)
+    }
+
+    // Switch 1 is bit 0. Games read them at any time, but most only
+    // take them in at power on or reset.
+    pub fn set_dip_switches(&mut self, switches: u8) {
+        if let Some(vs) = self.cpu.bus.vs.as_mut() {
+            vs.dip_switches = switches;
+        }
+    }
+
+    // Into slot 0 or 1.
+    pub fn insert_coin(&mut self, slot: usize) {
+        if let Some(vs) = self.cpu.bus.vs.as_mut() {
+            vs.insert_coin(slot);
+        }
+    }
+
+    pub fn set_service_button(&mut self, pressed: bool) {
+        if let Some(vs) = self.cpu.bus.vs.as_mut() {
+            vs.set_service(pressed);
+        }
+    }
+
+    pub fn cpu(&self) -> &Cpu {
+        &self.cpu
+    }
+
+    pub fn cpu_mut(&mut self) -> &mut Cpu {
+        &mut self.cpu
+    }
+}
+
+// Run-ahead rolls back through snapshots. A state taken from this machine
+// always loads into it, so a failure here is a bug.
+impl Rollback for Emulator {
+    type State = Snapshot;
+
+    fn save_state(&mut self) -> Snapshot {
+        self.snapshot()
+    }
+
+    fn load_state(&mut self, state: &Snapshot) {
+        self.restore(state).expect("own snapshot restores");
+    }
+
+    fn run_frame(&mut self, input: &FrameInput, present: bool) {
+        self.play_frame(input);
+        if !present {
+            self.discard_audio();
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+    use crate::bus::Mem;
+    use crate::cartridge::test::test_rom;
+    use crate::run_ahead::RunAhead;
+
+    // NROM image whose reset handler turns on NMI and the background, then
+    // spins; the NMI handler counts frames at $00.
+    pub(crate) fn counting_rom() -> Vec<u8> {
+        let mut rom = test_rom(2, 1, 0, 0);
+        let prg = &mut rom[16..16 + 0x8000];
+        prg.fill(0);
+        let reset = [
+            0xa9, 0x80, 0x8d, 0x00, 0x20, // lda #$80; sta $2000
+            0xa9, 0x08, 0x8d, 0x01, 0x20, // lda #$08; sta $2001
+            0x4c, 0x0a, 0x80, // jmp *
+        ];
+        prg[..reset.len()].copy_from_slice(&reset);
+        // nmi at $9000: inc $00; rti
+        prg[0x1000..0x1003].copy_from_slice(&[0xe6, 0x00, 0x40]);
+        prg[0x7FFA..0x8000].copy_from_slice(&[0x00, 0x90, 0x00, 0x80, 0x00, 0x80]);
+        rom
+    }
+
+    #[test]
+    fn test_runs_frames_and_takes_nmis() {
+        let mut emulator = Emulator::from_rom(&counting_rom()).unwrap();
+        emulator.run_frame();
+        let start = emulator.cpu().cycles;
+        emulator.run_frame();
+        emulator.run_frame();
+
+        assert_eq!(emulator.frame_count(), 3);
+        // the third NMI is raised at the very end of run_frame
+        assert_eq!(emulator.cpu_mut().mem_read(0x00), 2);
+        // 341 * 262 / 3 CPU cycles per frame, give or take an instruction
+        let per_frame = (emulator.cpu().cycles - start) / 2;
+        assert!((29_700..29_860).contains(&per_frame), "{}", per_frame);
+    }
+
+    #[test]
+    fn test_extra_scanlines_lengthen_the_frame_for_the_cpu() {
+        let mut emulator = Emulator::from_rom(&counting_rom()).unwrap();
+        emulator.set_extra_scanlines(100);
+        emulator.run_frame();
+        let start = emulator.cpu().cycles;
+        let ppu_start = emulator.cpu().bus.ppu.frame_count();
+        emulator.run_frame();
+        emulator.run_frame();
+        // 100 more lines of 341 / 3 cycles each
+        let per_frame = (emulator.cpu().cycles - start) / 2;
+        assert!((41_060..41_230).contains(&per_frame), "{}", per_frame);
+        assert_eq!(emulator.cpu().bus.ppu.frame_count() - ppu_start, 2);
+
+        // a state from partway through the extra lines picks up there
+        while emulator.cpu().bus.overclock_cycles == 0 {
+            emulator.cpu_mut().step();
+        }
+        let state = emulator.save_state();
+        let mut other = Emulator::from_rom(&counting_rom()).unwrap();
+        other.load_state(&state).unwrap();
+        assert_eq!(
+            other.cpu().bus.overclock_cycles,
+            emulator.cpu().bus.overclock_cycles
+        );
+        // carried over like the sprite limit
+        other
+            .insert_cartridge(Cartridge::from_bytes(&counting_rom()).unwrap())
+            .unwrap();
+        assert_eq!(other.extra_scanlines(), 0);
+        emulator
+            .insert_cartridge(Cartridge::from_bytes(&counting_rom()).unwrap())
+            .unwrap();
+        assert_eq!(emulator.extra_scanlines(), 100);
+    }
+
+    #[test]
+    fn test_rejects_bad_roms() {
+        assert_eq!(
+            Emulator::from_rom(b"nope").err(),
+            Some(CartridgeError::BadMagic)
+        );
+    }
+
+    #[test]
+    fn test_reset_restarts_at_vector() {
+        let mut emulator = Emulator::from_rom(&counting_rom()).unwrap();
+        emulator.run_frame();
+        emulator.reset();
+        assert_eq!(emulator.cpu().program_counter, 0x8000);
+    }
+
+    #[test]
+    fn test_audio_samples_per_frame() {
+        let mut emulator = Emulator::from_rom(&counting_rom()).unwrap();
+        let mut samples = Vec::new();
+        emulator.run_frame();
+        emulator.read_audio(&mut samples);
+        assert!(samples.is_empty());
+
+        emulator.enable_audio(48_000);
+        emulator.run_frame();
+        emulator.read_audio(&mut samples);
+        samples.clear();
+        emulator.run_frame();
+        emulator.read_audio(&mut samples);
+        assert!((780..=820).contains(&samples.len()), "{}", samples.len());
+    }
+
+    #[test]
+    fn test_builder_sets_everything_up() {
+        let mut palette = Palette::default();
+        palette.colours[0x0F] = (1, 2, 3);
+        let mut emulator = Emulator::builder()
+            .rom_bytes(counting_rom())
+            .region(Region::Pal)
+            .palette(palette)
+            .audio_sample_rate(48_000)
+            .audio_latency(Latency {
+                queued: 1024,
+                buffer: Some(256),
+            })
+            .extra_scanlines(20)
+            .build()
+            .unwrap();
+        assert_eq!(emulator.region(), Region::Pal);
+        assert_eq!(emulator.audio_latency().buffer, Some(256));
+        assert_eq!(emulator.rate_control().target_queued(), 1024);
+        assert_eq!(emulator.palette(), palette);
+        assert_eq!(emulator.extra_scanlines(), 20);
+
+        let mut input = FrameInput::default();
+        input.pads[0] = JoypadButton::START;
+        emulator.set_input(&input);
+        emulator.run_frame();
+        let joypad = emulator.cpu.bus.controllers.joypad(0).unwrap();
+        assert_eq!(joypad.buttons(), JoypadButton::START);
+        emulator.audio_samples();
+        emulator.run_frame();
+        // 48000 / 50.007
+        let samples = emulator.audio_samples().len();
+        assert!((940..=980).contains(&samples), "{}", samples);
+        assert!(emulator.audio_samples().is_empty());
+    }
+
+    #[test]
+    fn test_seeded_power_on() {
+        let build = |seed| {
+            Emulator::builder()
+                .rom_bytes(counting_rom())
+                .seed(seed)
+                .ram_init(RamInit::Random)
+                .build()
+                .unwrap()
+        };
+        let mut emulator = build(5);
+        let ram = emulator.cpu.bus.ram().to_vec();
+        assert_ne!(ram, [0; 0x800]);
+        assert_eq!(build(5).cpu.bus.ram(), ram);
+        assert_ne!(build(6).cpu.bus.ram(), ram);
+
+        // the same again from a cartridge swap
+        emulator.run_frame();
+        let cartridge = Cartridge::from_bytes(&counting_rom()).unwrap();
+        emulator.insert_cartridge(cartridge).unwrap();
+        assert_eq!(emulator.cpu.bus.ram(), ram);
+        assert_eq!(emulator.seed(), 5);
+    }
+
+    #[test]
+    fn test_builder_errors() {
+        assert!(matches!(
+            Emulator::builder().build(),
+            Err(BuildError::NoRom)
+        ));
+        let missing = Emulator::builder().rom("/nonexistent/game (E).nes").build();
+        assert!(matches!(missing, Err(BuildError::Read(..))));
+        let bad = Emulator::builder().rom_bytes(b"NES".to_vec()).build();
+        assert!(matches!(
+            bad,
+            Err(BuildError::Cartridge(CartridgeError::BadMagic))
+        ));
+    }
+
+    #[test]
+    fn test_builder_guesses_region_from_file_name() {
+        let dir = std::env::temp_dir().join(format!("nes-builder-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("Counter (Europe).nes");
+        fs::write(&path, counting_rom()).unwrap();
+        let emulator = Emulator::builder().rom(&path).build().unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(emulator.region(), Region::Pal);
+    }
+
+    #[test]
+    fn test_savestate_round_trip() {
+        let mut emulator = Emulator::from_rom(&counting_rom()).unwrap();
+        emulator.run_frame();
+        emulator.cpu_mut().mem_write(0x6000, 0x5A);
+        let state = emulator.save_state();
+        let cycles = emulator.cpu().cycles;
+
+        emulator.run_frame();
+        emulator.run_frame();
+        emulator.cpu_mut().mem_write(0x6000, 0);
+        emulator.load_state(&state).unwrap();
+
+        assert_eq!(emulator.frame_count(), 1);
+        assert_eq!(emulator.cpu().cycles, cycles);
+        assert_eq!(emulator.cpu_mut().mem_read(0x00), 0);
+        assert_eq!(emulator.cpu_mut().mem_read(0x6000), 0x5A);
+        assert_eq!(emulator.save_state(), state);
+    }
+
+    #[test]
+    fn test_new_cartridge_powers_on_afresh() {
+        let mut rom = counting_rom();
+        // battery
+        rom[6] |= 0b10;
+        let mut emulator = Emulator::from_rom(&counting_rom()).unwrap();
+        emulator.enable_audio(48_000);
+        emulator.set_sprite_limit(false);
+        emulator.run_frames(3);
+        emulator.cpu_mut().mem_write(0x6000, 0x5A);
+        assert_eq!(emulator.battery_ram(), None);
+
+        emulator
+            .insert_cartridge(Cartridge::from_bytes(&rom).unwrap())
+            .unwrap();
+        assert_eq!(emulator.frame_count(), 0);
+        assert_eq!(emulator.cpu().program_counter, 0x8000);
+        assert_eq!(emulator.cpu_mut().mem_read(0x6000), 0);
+        assert!(!emulator.cpu().bus.ppu.sprite_limit());
+        let mut samples = Vec::new();
+        emulator.run_frames(2);
+        emulator.read_audio(&mut samples);
+        assert!(!samples.is_empty());
+
+        emulator.load_battery_ram(&[1, 2, 3]);
+        assert_eq!(emulator.cpu_mut().mem_read(0x6002), 3);
+        assert_eq!(emulator.battery_ram().unwrap().len(), 0x2000);
+    }
+
+    #[test]
+    fn test_snapshots_and_compressed_files() {
+        let mut emulator = Emulator::from_rom(&counting_rom()).unwrap();
+        emulator.run_frame();
+        let mut snapshot = emulator.snapshot();
+        assert_eq!(snapshot.as_bytes(), emulator.save_state());
+        emulator.run_frame();
+        let capacity = snapshot.as_bytes().as_ptr();
+        emulator.snapshot_into(&mut snapshot);
+        assert_eq!(snapshot.as_bytes().as_ptr(), capacity);
+        emulator.run_frame();
+        emulator.restore(&snapshot).unwrap();
+        assert_eq!(emulator.frame_count(), 2);
+
+        let dir = std::env::temp_dir().join(format!("nes-state-file-{}", std::process::id()));
+        let path = dir.join("slot.state");
+        emulator.save_state_to(&path).unwrap();
+        let file = fs::read(&path).unwrap();
+        assert!(file.len() < snapshot.as_bytes().len() / 4);
+        emulator.run_frame();
+        emulator.load_state_from(&path).unwrap();
+        assert_eq!(emulator.frame_count(), 2);
+        // and as they were before compression
+        fs::write(&path, emulator.save_state()).unwrap();
+        emulator.load_state_from(&path).unwrap();
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_hashes_every_frame_once_asked() {
+        let mut emulator = Emulator::from_rom(&counting_rom()).unwrap();
+        emulator.run_frame();
+        assert_eq!(emulator.frame_hash(), None);
+        emulator.set_state_hashing(true);
+        assert_eq!(emulator.frame_hash(), None);
+
+        for frame in 1..=3 {
+            emulator.run_frame();
+            let hash = emulator.frame_hash().unwrap();
+            assert_eq!(hash.frames, frame);
+            let mut copy = Emulator::from_rom(&counting_rom()).unwrap();
+            copy.load_state(&emulator.save_state()).unwrap();
+            assert_eq!(hash.state, copy.state_hash());
+        }
+        emulator
+            .insert_cartridge(Cartridge::from_bytes(&counting_rom()).unwrap())
+            .unwrap();
+        assert_eq!(emulator.frame_hash(), None);
+        emulator.run_frame();
+        assert_eq!(emulator.frame_hash().map(|hash| hash.frames), Some(1));
+    }
+
+    #[test]
+    fn test_runs_ahead_on_snapshots() {
+        let mut emulator = Emulator::from_rom(&counting_rom()).unwrap();
+        let mut run_ahead = RunAhead::new(2);
+        let input = FrameInput::default();
+        run_ahead.run_frame(&mut emulator, input);
+        assert_eq!(emulator.frame_count(), 3);
+        run_ahead.run_frame(&mut emulator, input);
+        assert_eq!(emulator.frame_count(), 4);
+        assert_eq!(run_ahead.rollbacks(), 0);
+        let confirmed = run_ahead.confirmed_state().unwrap().clone();
+        emulator.restore(&confirmed).unwrap();
+        assert_eq!(emulator.frame_count(), 2);
+    }
+
+    #[test]
+    fn test_vs_coins_and_switches_reach_the_game() {
+        let program = crate::testing::asm::assemble(
+            0x8000,
+            "
+                lda #$80; sta $2000
+            loop: jmp loop
+            nmi:
+                lda $4016; sta $10
+                lda $4017; sta $11
+                rti
+            ",
+        )
+        .unwrap();
+        let nmi = program.label("nmi").unwrap();
+        // mapper 99, marked as a VS. game
+        let mut rom = test_rom(2, 2, 0x30, 0x61);
+        let prg = &mut rom[16..16 + 0x8000];
+        prg[..program.bytes.len()].copy_from_slice(&program.bytes);
+        prg[0x7FFA..0x7FFC].copy_from_slice(&nmi.to_le_bytes());
+        prg[0x7FFC..0x7FFE].copy_from_slice(&[0x00, 0x80]);
+
+        let mut emulator = Emulator::from_rom(&rom).unwrap();
+        assert!(emulator.is_vs());
+        assert_eq!(emulator.vs_ppu(), Some(VsPpu::Rp2c03));
+        emulator.set_dip_switches(0b1000_0001);
+        emulator.run_frames(2);
+        assert_eq!(emulator.cpu.bus.peek(0x10), 0b0000_1000);
+        assert_eq!(emulator.cpu.bus.peek(0x11), 0b1000_0000);
+
+        let input = FrameInput {
+            commands: MovieCommand::VS_COIN,
+            ..FrameInput::default()
+        };
+        emulator.play_frame(&input);
+        assert_eq!(emulator.cpu.bus.peek(0x10) & 0x20, 0x20);
+        let state = emulator.save_state();
+        emulator.run_frames(4);
+        assert_eq!(emulator.cpu.bus.peek(0x10) & 0x20, 0);
+
+        // the coin is still going in, in the state
+        emulator.set_dip_switches(0);
+        emulator.load_state(&state).unwrap();
+        assert_eq!(emulator.dip_switches(), Some(0b1000_0001));
+        emulator.run_frame();
+        assert_eq!(emulator.cpu.bus.peek(0x10) & 0x20, 0x20);
+
+        emulator.set_vs_ppu(VsPpu::Rc2c05(1));
+        emulator.load_state(&state).unwrap();
+        assert_eq!(emulator.vs_ppu(), Some(VsPpu::Rc2c05(1)));
+
+        let mut console = Emulator::from_rom(&counting_rom()).unwrap();
+        console.set_vs_ppu(VsPpu::Rp2c04(1));
+        assert_eq!(console.vs_ppu(), None);
+        assert_eq!(console.dip_switches(), None);
+    }
+
+    #[test]
+    fn test_bad_savestate_changes_nothing() {
+        let mut emulator = Emulator::from_rom(&counting_rom()).unwrap();
+        emulator.run_frame();
+        let state = emulator.save_state();
+
+        assert!(matches!(
+            emulator.load_state(&state[..state.len() / 2]),
+            Err(StateError::Decode(_))
+        ));
+        assert_eq!(emulator.save_state(), state);
+    }
+
+    #[test]
+    fn test_machines_run_on_other_threads() {
+        let mut here = Emulator::from_rom(&counting_rom()).unwrap();
+        here.run_frames(10);
+        let workers: Vec<_> = (0..4)
+            .map(|_| {
+                let mut emulator = Emulator::from_rom(&counting_rom()).unwrap();
+                emulator.run_frames(5);
+                std::thread::spawn(move || {
+                    emulator.run_frames(5);
+                    emulator
+                })
+            })
+            .collect();
+        for worker in workers {
+            let mut emulator = worker.join().unwrap();
+            assert_eq!(emulator.state_hash(), here.state_hash());
+            emulator.run_frame();
+            assert_eq!(emulator.frame_count(), 11);
+        }
+    }
+
+    // a latch at $5000 that reads back one more than was written
+    struct Latch(u8);
+
+    impl Peripheral for Latch {
+        fn read(&mut self, _addr: u16) -> u8 {
+            self.0.wrapping_add(1)
+        }
+
+        fn write(&mut self, _addr: u16, value: u8) {
+            self.0 = value;
+        }
+
+        fn peek(&self, _addr: u16) -> u8 {
+            self.0
+        }
+
+        fn save_state(&self) -> Vec<u8> {
+            vec![self.0]
+        }
+
+        fn load_state(&mut self, state: &[u8]) -> Result<(), StateError> {
+            self.0 = *state.first().ok_or(StateError::WrongPeripherals)?;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_peripherals_are_on_the_bus_and_in_states() {
+        // lda #$42; sta $5000; lda $5001; sta $10; jmp *
+        let program = [
+            0xa9, 0x42, 0x8d, 0x00, 0x50, 0xad, 0x01, 0x50, 0x85, 0x10, 0x4c, 0x0a, 0x80,
+        ];
+        let rom = crate::testing::fuzz::program_rom(0, &program);
+        let mut emulator = Emulator::from_rom(&rom).unwrap();
+        assert!(matches!(
+            emulator.attach_peripheral(0x4000..=0x5000, Box::new(Latch(0))),
+            Err(PeripheralError::BadRange(_))
+        ));
+        let id = emulator
+            .attach_peripheral(0x5000..=0x5001, Box::new(Latch(0)))
+            .unwrap();
+        emulator.run_frame();
+        assert_eq!(emulator.cpu.bus.peek(0x10), 0x43);
+        assert_eq!(emulator.cpu.bus.peek(0x5000), 0x42);
+
+        let state = emulator.save_state();
+        emulator.peripheral_mut(id).unwrap().write(0x5000, 7);
+        emulator.load_state(&state).unwrap();
+        assert_eq!(emulator.cpu.bus.peek(0x5000), 0x42);
+
+        // a state for one device doesn't load with two
+        let second = emulator
+            .attach_peripheral(0x5002..=0x5002, Box::new(Latch(0)))
+            .unwrap();
+        assert_eq!(
+            emulator.load_state(&state),
+            Err(StateError::WrongPeripherals)
+        );
+        emulator.detach_peripheral(second).unwrap();
+
+        emulator
+            .insert_cartridge(Cartridge::from_bytes(&rom).unwrap())
+            .unwrap();
+        assert_eq!(emulator.cpu.bus.peek(0x5000), 0x42);
+        emulator.detach_peripheral(id).unwrap();
+        emulator.load_state(&state).unwrap_err();
+    }
+}
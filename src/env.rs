@@ -0,0 +1,239 @@
+// A reinforcement-learning environment in the manner of Gym (and nes-py on
+// top of it): reset() goes back to the start, step() holds the buttons
+// for a frame, or `frame_skip` of them, and hands back what the agent
+// sees. That's the picture, packed RGB, the 2K of RAM if asked for (what
+// rewards and episode ends are usually read from) and the frames since
+// the reset; rewards themselves are the game's business and left to
+// whoever wraps this.
+//
+// Nothing's shown or played: the machine has no audio output and runs as
+// fast as the host does. An Env is Send, so a batch of them can be
+// stepped together on threads, one action each: see step_all.
+//
+//     let mut env = Env::new(emulator, EnvOptions::default());
+//     let mut observation = env.reset();
+//     while !done(&observation) {
+//         observation = env.step(policy(&observation));
+//     }
+
+use std::thread;
+
+use crate::emulator::Emulator;
+use crate::input::joypad::JoypadButton;
+use crate::render::frame::Frame;
+use crate::state::{Snapshot, StateError};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EnvOptions {
+    // frames each step holds its buttons for, at least one
+    pub frame_skip: u32,
+    // the RAM with every observation
+    pub observe_ram: bool,
+    // frames run past the power-on before the start is taken, to get by
+    // a boot the agent has nothing to do with
+    pub warmup_frames: u32,
+}
+
+impl Default for EnvOptions {
+    fn default() -> Self {
+        EnvOptions {
+            frame_skip: 1,
+            observe_ram: false,
+            warmup_frames: 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Observation {
+    // Frame::WIDTH by Frame::HEIGHT, three bytes a pixel
+    pub pixels: Vec<u8>,
+    pub ram: Option<Vec<u8>>,
+    // since the reset
+    pub frame: u64,
+}
+
+#[derive(Debug)]
+pub struct Env {
+    emulator: Emulator,
+    options: EnvOptions,
+    start: Snapshot,
+    // the emulator's frame count at the start
+    start_frame: u64,
+}
+
+impl Env {
+    // The start is where the emulator is now, after the warmup.
+    pub fn new(mut emulator: Emulator, options: EnvOptions) -> Self {
+        emulator.run_frames(options.warmup_frames.into());
+        let start = emulator.snapshot();
+        let start_frame = emulator.frame_count();
+        Env {
+            emulator,
+            options,
+            start,
+            start_frame,
+        }
+    }
+
+    // Takes the start from somewhere else, a level select say.
+    pub fn set_start(&mut self, snapshot: Snapshot) -> Result<(), StateError> {
+        self.emulator.restore(&snapshot)?;
+        self.start_frame = self.emulator.frame_count();
+        self.start = snapshot;
+        Ok(())
+    }
+
+    pub fn reset(&mut self) -> Observation {
+        self.emulator
+            .restore(&self.start)
+            .expect("the start was taken from this machine");
+        self.observe()
+    }
+
+    // Player one's buttons.
+    pub fn step(&mut self, buttons: JoypadButton) -> Observation {
+        self.step_players(&[buttons])
+    }
+
+    // A player each, from one; anyone left out lets go.
+    pub fn step_players(&mut self, buttons: &[JoypadButton]) -> Observation {
+        for player in 0..2 {
+            let held = buttons
+                .get(player)
+                .copied()
+                .unwrap_or(JoypadButton::empty());
+            self.emulator.set_buttons(player, held);
+        }
+        self.emulator
+            .run_frames(self.options.frame_skip.max(1).into());
+        self.observe()
+    }
+
+    pub fn frame_count(&self) -> u64 {
+        self.emulator.frame_count() - self.start_frame
+    }
+
+    // The picture and RAM again, without the copy an Observation makes.
+    pub fn frame(&self) -> &Frame {
+        self.emulator.frame()
+    }
+
+    pub fn ram(&self) -> &[u8] {
+        self.emulator.cpu().bus.ram()
+    }
+
+    pub fn emulator(&self) -> &Emulator {
+        &self.emulator
+    }
+
+    pub fn emulator_mut(&mut self) -> &mut Emulator {
+        &mut self.emulator
+    }
+
+    fn observe(&self) -> Observation {
+        Observation {
+            pixels: self.frame().data.clone(),
+            ram: self.options.observe_ram.then(|| self.ram().to_vec()),
+            frame: self.frame_count(),
+        }
+    }
+}
+
+// Steps every env with its own action, a thread for each of up to `jobs`
+// at a time; the observations come back in the envs' order.
+pub fn step_all(envs: &mut [Env], actions: &[JoypadButton], jobs: usize) -> Vec<Observation> {
+    assert_eq!(envs.len(), actions.len(), "an action for every env");
+    let per_job = envs.len().div_ceil(jobs.max(1)).max(1);
+    thread::scope(|scope| {
+        let running: Vec<_> = envs
+            .chunks_mut(per_job)
+            .zip(actions.chunks(per_job))
+            .map(|(envs, actions)| {
+                scope.spawn(move || {
+                    envs.iter_mut()
+                        .zip(actions)
+                        .map(|(env, &buttons)| env.step(buttons))
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+        running
+            .into_iter()
+            .flat_map(|job| job.join().unwrap())
+            .collect()
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::testing::fuzz::program_rom;
+
+    // $10 is 1 while A is held
+    fn reading_a_program() -> Vec<u8> {
+        let program = [
+            0xa9, 0x01, 0x8d, 0x16, 0x40, // lda #1; sta $4016
+            0xa9, 0x00, 0x8d, 0x16, 0x40, // lda #0; sta $4016
+            0xad, 0x16, 0x40, // lda $4016
+            0x29, 0x01, 0x85, 0x10, // and #1; sta $10
+            0x4c, 0x00, 0x80, // jmp $8000
+        ];
+        program_rom(0, &program)
+    }
+
+    #[test]
+    fn test_steps_and_resets() {
+        let emulator = Emulator::from_rom(&reading_a_program()).unwrap();
+        let options = EnvOptions {
+            frame_skip: 2,
+            observe_ram: true,
+            warmup_frames: 1,
+        };
+        let mut env = Env::new(emulator, options);
+
+        let start = env.reset();
+        assert_eq!(start.frame, 0);
+        assert_eq!(start.pixels.len(), Frame::WIDTH * Frame::HEIGHT * 3);
+        let ram = start.ram.as_ref().unwrap();
+        assert_eq!(ram.len(), 0x800);
+        assert_eq!(ram[0x10], 0);
+
+        let idle = env.step(JoypadButton::empty());
+        assert_eq!(idle.frame, 2);
+        assert_eq!(idle.ram.as_ref().unwrap()[0x10], 0);
+        let pressed = env.step(JoypadButton::BUTTON_A);
+        assert_eq!(pressed.frame, 4);
+        assert_eq!(pressed.ram.as_ref().unwrap()[0x10], 1);
+
+        assert_eq!(env.reset(), start);
+    }
+
+    #[test]
+    fn test_steps_many_at_once() {
+        let rom = reading_a_program();
+        let mut envs: Vec<_> = (0..5)
+            .map(|_| {
+                let options = EnvOptions {
+                    observe_ram: true,
+                    ..EnvOptions::default()
+                };
+                Env::new(Emulator::from_rom(&rom).unwrap(), options)
+            })
+            .collect();
+        let actions = [
+            JoypadButton::BUTTON_A,
+            JoypadButton::empty(),
+            JoypadButton::BUTTON_A,
+            JoypadButton::empty(),
+            JoypadButton::BUTTON_A,
+        ];
+        let observations = step_all(&mut envs, &actions, 2);
+        assert_eq!(observations.len(), 5);
+        for (observation, buttons) in observations.iter().zip(actions) {
+            let held = observation.ram.as_ref().unwrap()[0x10];
+            assert_eq!(held == 1, buttons == JoypadButton::BUTTON_A);
+            assert_eq!(observation.frame, 1);
+        }
+    }
+}
@@ -0,0 +1,321 @@
+// A C API for embedding the emulator in programs that aren't Rust: built
+// into the cdylib with the `ffi` feature and declared in include/nes.h,
+// which cbindgen writes from this file (see cbindgen.toml); the `///`
+// comments here are the header's.
+//
+//     cargo build --release --lib --features ffi
+//     cbindgen --config cbindgen.toml --output include/nes.h src/ffi.rs
+//
+// Unlike libretro it's a set of handles rather than one loaded game, so a
+// host can run as many consoles as it likes, each from one thread at a
+// time. Functions return NES_OK or a negative error code; pointers handed
+// in must be valid as the header describes, or null where it says that's
+// allowed, and pointers handed out last until the next call on the same
+// handle. A panic doesn't unwind into C: the call returns NES_ERROR_PANIC,
+// or null or 0, and the handle should only be destroyed after. The
+// version goes up whenever anything in the header changes.
+
+#![allow(clippy::missing_safety_doc)]
+
+use std::ffi::c_int;
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use std::slice;
+
+use crate::cartridge::Cartridge;
+use crate::emulator::Emulator;
+use crate::input::joypad::JoypadButton;
+use crate::input::keymap::MAX_PLAYERS;
+
+pub const NES_API_VERSION: u32 = 2;
+
+pub const NES_WIDTH: u32 = 256;
+pub const NES_HEIGHT: u32 = 240;
+
+pub const NES_OK: c_int = 0;
+/// a null pointer, or a player that doesn't exist
+pub const NES_ERROR_ARGUMENT: c_int = -1;
+pub const NES_ERROR_ROM: c_int = -2;
+pub const NES_ERROR_STATE: c_int = -3;
+/// the buffer is too small; nes_state_size says how big to make it
+pub const NES_ERROR_BUFFER: c_int = -4;
+/// the emulator panicked; destroy the handle
+pub const NES_ERROR_PANIC: c_int = -5;
+
+/// for nes_set_buttons
+pub const NES_BUTTON_A: u8 = 0x01;
+pub const NES_BUTTON_B: u8 = 0x02;
+pub const NES_BUTTON_SELECT: u8 = 0x04;
+pub const NES_BUTTON_START: u8 = 0x08;
+pub const NES_BUTTON_UP: u8 = 0x10;
+pub const NES_BUTTON_DOWN: u8 = 0x20;
+pub const NES_BUTTON_LEFT: u8 = 0x40;
+pub const NES_BUTTON_RIGHT: u8 = 0x80;
+
+pub struct NesEmulator {
+    emulator: Emulator,
+    // samples of the last frame
+    audio: Vec<f32>,
+}
+
+// `body`'s result, or `panicked` if it panics.
+fn guard<T>(panicked: T, body: impl FnOnce() -> T) -> T {
+    panic::catch_unwind(AssertUnwindSafe(body)).unwrap_or(panicked)
+}
+
+/// NES_API_VERSION as the library was built; check it matches.
+#[no_mangle]
+pub extern "C" fn nes_api_version() -> u32 {
+    NES_API_VERSION
+}
+
+/// A console with no cartridge. A sample_rate of 0 leaves audio off.
+#[no_mangle]
+pub extern "C" fn nes_create(sample_rate: u32) -> *mut NesEmulator {
+    guard(ptr::null_mut(), || {
+        let mut emulator = Emulator::empty();
+        if sample_rate > 0 {
+            emulator.enable_audio(sample_rate);
+        }
+        Box::into_raw(Box::new(NesEmulator {
+            emulator,
+            audio: Vec::new(),
+        }))
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn nes_destroy(nes: *mut NesEmulator) {
+    guard((), || {
+        if !nes.is_null() {
+            drop(Box::from_raw(nes));
+        }
+    })
+}
+
+/// An iNES or NES 2.0 image; the console powers on with it.
+#[no_mangle]
+pub unsafe extern "C" fn nes_load_rom(
+    nes: *mut NesEmulator,
+    data: *const u8,
+    size: usize,
+) -> c_int {
+    guard(NES_ERROR_PANIC, || {
+        let (Some(nes), false) = (nes.as_mut(), data.is_null()) else {
+            return NES_ERROR_ARGUMENT;
+        };
+        let rom = slice::from_raw_parts(data, size);
+        let loaded = Cartridge::from_bytes(rom)
+            .ok()
+            .and_then(|cartridge| nes.emulator.insert_cartridge(cartridge).ok());
+        match loaded {
+            Some(()) => NES_OK,
+            None => NES_ERROR_ROM,
+        }
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn nes_reset(nes: *mut NesEmulator) -> c_int {
+    guard(NES_ERROR_PANIC, || match nes.as_mut() {
+        Some(nes) => {
+            nes.emulator.reset();
+            NES_OK
+        }
+        None => NES_ERROR_ARGUMENT,
+    })
+}
+
+/// Runs to the end of the next frame; does nothing without a ROM.
+#[no_mangle]
+pub unsafe extern "C" fn nes_run_frame(nes: *mut NesEmulator) -> c_int {
+    guard(NES_ERROR_PANIC, || {
+        let Some(nes) = nes.as_mut() else {
+            return NES_ERROR_ARGUMENT;
+        };
+        if nes.emulator.has_cartridge() {
+            nes.emulator.run_frame();
+            nes.audio.clear();
+            nes.emulator.read_audio(&mut nes.audio);
+        }
+        NES_OK
+    })
+}
+
+/// NES_WIDTH * NES_HEIGHT pixels of packed RGB, 3 bytes each, top row
+/// first.
+#[no_mangle]
+pub unsafe extern "C" fn nes_framebuffer(nes: *const NesEmulator) -> *const u8 {
+    guard(ptr::null(), || match nes.as_ref() {
+        Some(nes) => nes.emulator.frame().data.as_ptr(),
+        None => ptr::null(),
+    })
+}
+
+/// The last frame's audio, mono floats from -1 to 1; count may be null.
+#[no_mangle]
+pub unsafe extern "C" fn nes_audio(nes: *const NesEmulator, count: *mut usize) -> *const f32 {
+    guard(ptr::null(), || {
+        let Some(nes) = nes.as_ref() else {
+            return ptr::null();
+        };
+        if let Some(count) = count.as_mut() {
+            *count = nes.audio.len();
+        }
+        nes.audio.as_ptr()
+    })
+}
+
+/// Players 0-3; buttons are NES_BUTTON_* ored together, held from the
+/// next frame on.
+#[no_mangle]
+pub unsafe extern "C" fn nes_set_buttons(nes: *mut NesEmulator, player: u32, buttons: u8) -> c_int {
+    guard(NES_ERROR_PANIC, || match nes.as_mut() {
+        Some(nes) if (player as usize) < MAX_PLAYERS => {
+            nes.emulator
+                .set_buttons(player as usize, JoypadButton::from_bits_retain(buttons));
+            NES_OK
+        }
+        _ => NES_ERROR_ARGUMENT,
+    })
+}
+
+/// Room for a state saved now. States vary a little in size, so save
+/// into a buffer of this size right away rather than keeping the number.
+#[no_mangle]
+pub unsafe extern "C" fn nes_state_size(nes: *const NesEmulator) -> usize {
+    guard(0, || {
+        nes.as_ref()
+            .map_or(0, |nes| nes.emulator.save_state().len())
+    })
+}
+
+/// Savestates. Size the buffer with nes_state_size just before saving;
+/// written may be null.
+#[no_mangle]
+pub unsafe extern "C" fn nes_save_state(
+    nes: *const NesEmulator,
+    buffer: *mut u8,
+    size: usize,
+    written: *mut usize,
+) -> c_int {
+    guard(NES_ERROR_PANIC, || {
+        let (Some(nes), false) = (nes.as_ref(), buffer.is_null()) else {
+            return NES_ERROR_ARGUMENT;
+        };
+        let state = nes.emulator.save_state();
+        if state.len() > size {
+            return NES_ERROR_BUFFER;
+        }
+        slice::from_raw_parts_mut(buffer, state.len()).copy_from_slice(&state);
+        if let Some(written) = written.as_mut() {
+            *written = state.len();
+        }
+        NES_OK
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn nes_load_state(
+    nes: *mut NesEmulator,
+    data: *const u8,
+    size: usize,
+) -> c_int {
+    guard(NES_ERROR_PANIC, || {
+        let (Some(nes), false) = (nes.as_mut(), data.is_null()) else {
+            return NES_ERROR_ARGUMENT;
+        };
+        match nes.emulator.load_state(slice::from_raw_parts(data, size)) {
+            Ok(()) => NES_OK,
+            Err(_) => NES_ERROR_STATE,
+        }
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::emulator::test::counting_rom;
+    use crate::render::frame::Frame;
+
+    #[test]
+    fn test_header_is_what_cbindgen_makes() {
+        let dir = env!("CARGO_MANIFEST_DIR");
+        let config = cbindgen::Config::from_file(format!("{}/cbindgen.toml", dir)).unwrap();
+        let mut header = Vec::new();
+        cbindgen::Builder::new()
+            .with_config(config)
+            .with_src(format!("{}/src/ffi.rs", dir))
+            .generate()
+            .unwrap()
+            .write(&mut header);
+        assert!(
+            header == include_bytes!("../include/nes.h"),
+            "include/nes.h is out of date; see the top of src/ffi.rs"
+        );
+
+        assert_eq!(NES_WIDTH as usize, Frame::WIDTH);
+        assert_eq!(NES_HEIGHT as usize, Frame::HEIGHT);
+        let buttons = [
+            NES_BUTTON_A,
+            NES_BUTTON_B,
+            NES_BUTTON_SELECT,
+            NES_BUTTON_START,
+            NES_BUTTON_UP,
+            NES_BUTTON_DOWN,
+            NES_BUTTON_LEFT,
+            NES_BUTTON_RIGHT,
+        ];
+        let bits: Vec<u8> = JoypadButton::all()
+            .iter()
+            .map(|button| button.bits())
+            .collect();
+        assert_eq!(buttons[..], bits);
+    }
+
+    #[test]
+    fn test_panics_stay_on_this_side() {
+        assert_eq!(
+            guard(NES_ERROR_PANIC, || panic!("in the core")),
+            NES_ERROR_PANIC
+        );
+        assert_eq!(guard(NES_ERROR_PANIC, || NES_OK), NES_OK);
+    }
+
+    #[test]
+    fn test_runs_a_game_through_handles() {
+        unsafe {
+            let nes = nes_create(48_000);
+            assert_eq!(nes_run_frame(nes), NES_OK);
+            assert_eq!(nes_run_frame(ptr::null_mut()), NES_ERROR_ARGUMENT);
+            assert_eq!(nes_load_rom(nes, b"junk".as_ptr(), 4), NES_ERROR_ROM);
+            let rom = counting_rom();
+            assert_eq!(nes_load_rom(nes, rom.as_ptr(), rom.len()), NES_OK);
+            assert_eq!(nes_set_buttons(nes, 4, 0), NES_ERROR_ARGUMENT);
+            assert_eq!(nes_set_buttons(nes, 0, 0x08), NES_OK);
+
+            nes_run_frame(nes);
+            let mut count = 0;
+            assert!(!nes_audio(nes, &mut count).is_null());
+            assert!(count > 700, "{} samples", count);
+            assert!(!nes_framebuffer(nes).is_null());
+
+            let mut state = vec![0; nes_state_size(nes)];
+            let mut written = 0;
+            assert_eq!(
+                nes_save_state(nes, state.as_mut_ptr(), 1, &mut written),
+                NES_ERROR_BUFFER
+            );
+            assert_eq!(
+                nes_save_state(nes, state.as_mut_ptr(), state.len(), &mut written),
+                NES_OK
+            );
+            nes_run_frame(nes);
+            nes_run_frame(nes);
+            assert_eq!(nes_load_state(nes, state.as_ptr(), written), NES_OK);
+            assert_eq!((*nes).emulator.frame_count(), 1);
+            assert_eq!(nes_load_state(nes, state.as_ptr(), 3), NES_ERROR_STATE);
+            nes_destroy(nes);
+        }
+    }
+}
@@ -0,0 +1,205 @@
+// A game's battery RAM on disk, as {game}.sav. Two ways to keep it:
+//
+//   Buffered  the session checks the RAM every second or so and, if the
+//             game changed it, writes the whole file next to the old one
+//             and renames it over, so a crash partway through leaves one
+//             or the other whole
+//   Mapped    the file is memory-mapped once the game first saves, and the
+//             RAM copied into the map every frame it changes. The OS
+//             writes the pages back on its own, so a save is on its way to
+//             disk a frame after the game makes it, even if the emulator
+//             is killed before it could flush anything
+//
+// Mapped needs an OS with mmap; on wasm, or if the file can't be mapped,
+// it falls back to Buffered. Either way, a RAM the game never touched
+// isn't written.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+#[cfg(not(target_arch = "wasm32"))]
+use memmap2::MmapMut;
+use serde::Deserialize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BatteryBacking {
+    #[default]
+    Buffered,
+    Mapped,
+}
+
+#[derive(Debug)]
+pub struct BatteryFile {
+    path: PathBuf,
+    backing: BatteryBacking,
+    // the RAM as the file has it, until there's a map to compare with
+    saved: Vec<u8>,
+    #[cfg(not(target_arch = "wasm32"))]
+    map: Option<MmapMut>,
+}
+
+impl BatteryFile {
+    pub fn new(path: PathBuf, backing: BatteryBacking) -> Self {
+        BatteryFile {
+            path,
+            backing: if cfg!(target_arch = "wasm32") {
+                BatteryBacking::Buffered
+            } else {
+                backing
+            },
+            saved: Vec::new(),
+            #[cfg(not(target_arch = "wasm32"))]
+            map: None,
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn backing(&self) -> BatteryBacking {
+        self.backing
+    }
+
+    pub fn is_mapped(&self) -> bool {
+        self.backing == BatteryBacking::Mapped
+    }
+
+    // The save, None if the game hasn't made one yet.
+    pub fn read(&self) -> io::Result<Option<Vec<u8>>> {
+        match fs::read(&self.path) {
+            Ok(data) => Ok(Some(data)),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    // The RAM once the save is loaded into it, which changes are measured
+    // from.
+    pub fn loaded(&mut self, ram: &[u8]) {
+        self.saved = ram.to_vec();
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            self.map = None;
+        }
+    }
+
+    pub fn is_dirty(&self, ram: &[u8]) -> bool {
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(map) = self.map.as_ref() {
+            return map[..] != *ram;
+        }
+        self.saved != ram
+    }
+
+    // Only if it changed.
+    pub fn write(&mut self, ram: &[u8]) -> io::Result<()> {
+        if !self.is_dirty(ram) {
+            return Ok(());
+        }
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        if self.is_mapped() {
+            if self.map.as_ref().is_none_or(|map| map.len() != ram.len()) {
+                match self.map_file(ram.len()) {
+                    Ok(map) => self.map = Some(map),
+                    // said once, then buffered from here on
+                    Err(err) => {
+                        self.backing = BatteryBacking::Buffered;
+                        self.map = None;
+                        return Err(err);
+                    }
+                }
+            }
+            if let Some(map) = self.map.as_mut() {
+                map.copy_from_slice(ram);
+            }
+            return Ok(());
+        }
+        let partial = self.path.with_extension("sav.part");
+        let mut file = fs::File::create(&partial)?;
+        io::Write::write_all(&mut file, ram)?;
+        file.sync_all()?;
+        fs::rename(&partial, &self.path)?;
+        self.saved = ram.to_vec();
+        Ok(())
+    }
+
+    // Asks the OS to write the map's pages now, for a clean exit; they'd
+    // get there anyway.
+    pub fn flush(&self) -> io::Result<()> {
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(map) = self.map.as_ref() {
+            return map.flush();
+        }
+        Ok(())
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn map_file(&self, len: usize) -> io::Result<MmapMut> {
+        let file = fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&self.path)?;
+        file.set_len(len as u64)?;
+        // SAFETY: the map is only read and written through here; another
+        // program changing the .sav while the game runs would be as much
+        // of a problem for the buffered writes
+        unsafe { MmapMut::map_mut(&file) }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn sav(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("nes-battery-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir.join("game.sav")
+    }
+
+    #[test]
+    fn test_buffered_writes_changes_only() {
+        let path = sav("buffered");
+        let mut battery = BatteryFile::new(path.clone(), BatteryBacking::Buffered);
+        assert_eq!(battery.read().unwrap(), None);
+        battery.loaded(&[0; 4]);
+        battery.write(&[0; 4]).unwrap();
+        assert!(!path.exists());
+
+        battery.write(&[1, 2, 3, 4]).unwrap();
+        assert!(!battery.is_dirty(&[1, 2, 3, 4]));
+        assert_eq!(battery.read().unwrap(), Some(vec![1, 2, 3, 4]));
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[test]
+    fn test_mapped_saves_are_in_the_file_at_once() {
+        let path = sav("mapped");
+        let mut battery = BatteryFile::new(path.clone(), BatteryBacking::Mapped);
+        battery.loaded(&[0; 4]);
+        battery.write(&[0; 4]).unwrap();
+        assert!(!path.exists());
+
+        battery.write(&[5, 0, 0, 0]).unwrap();
+        assert!(!battery.is_dirty(&[5, 0, 0, 0]));
+        // no flush, and the map still open
+        assert_eq!(fs::read(&path).unwrap(), [5, 0, 0, 0]);
+        battery.write(&[5, 6, 0, 0]).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), [5, 6, 0, 0]);
+
+        // a save of another size is made to fit
+        let mut next = BatteryFile::new(path.clone(), BatteryBacking::Mapped);
+        next.loaded(&[5, 6]);
+        next.write(&[5, 6, 7, 8, 9, 10]).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), [5, 6, 7, 8, 9, 10]);
+        assert!(!path.with_extension("sav.part").exists());
+    }
+}
@@ -0,0 +1,97 @@
+// No window, no audio device, no pacing: run a fixed number of frames as
+// fast as the host allows and optionally write out the last picture and
+// the contents of RAM. For automated tests, benchmarks and servers.
+// Frames go through Emulator::run, so a debugger that's been set up, the
+// profiler say, sees them; anything it stops for is run straight past.
+
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use crate::debugger::RunStatus;
+use crate::emulator::Emulator;
+
+#[derive(Debug, Clone, Default)]
+pub struct HeadlessOptions {
+    pub frames: u64,
+    // the final frame, as PPM
+    pub dump_frame: Option<PathBuf>,
+    // the 2K of internal RAM, raw
+    pub dump_ram: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HeadlessReport {
+    pub frames: u64,
+    pub cycles: u64,
+    pub elapsed: Duration,
+}
+
+impl HeadlessReport {
+    pub fn frames_per_second(&self) -> f64 {
+        self.frames as f64 / self.elapsed.as_secs_f64().max(f64::EPSILON)
+    }
+}
+
+pub fn run(emulator: &mut Emulator, options: &HeadlessOptions) -> io::Result<HeadlessReport> {
+    let cycles = emulator.cpu().cycles;
+    let started = Instant::now();
+    for _ in 0..options.frames {
+        while emulator.run() != RunStatus::FrameDone {}
+    }
+    let report = HeadlessReport {
+        frames: options.frames,
+        cycles: emulator.cpu().cycles - cycles,
+        elapsed: started.elapsed(),
+    };
+
+    if let Some(path) = options.dump_frame.as_ref() {
+        fs::write(path, emulator.frame().to_ppm())?;
+    }
+    if let Some(path) = options.dump_ram.as_ref() {
+        fs::write(path, emulator.cpu().bus.ram())?;
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::cartridge::test::test_rom;
+
+    #[test]
+    fn test_runs_frames_and_dumps() {
+        let mut rom = test_rom(2, 1, 0, 0);
+        let prg = &mut rom[16..16 + 0x8000];
+        // inc $10; jmp $8000
+        prg[..5].copy_from_slice(&[0xe6, 0x10, 0x4c, 0x00, 0x80]);
+        prg[0x7FFA..].copy_from_slice(&[0x00, 0x80, 0x00, 0x80, 0x00, 0x80]);
+        let mut emulator = Emulator::from_rom(&rom).unwrap();
+
+        let dir = std::env::temp_dir();
+        let id = std::process::id();
+        let options = HeadlessOptions {
+            frames: 3,
+            dump_frame: Some(dir.join(format!("nes-test-{}-frame.ppm", id))),
+            dump_ram: Some(dir.join(format!("nes-test-{}-ram.bin", id))),
+        };
+        let report = run(&mut emulator, &options).unwrap();
+        let frame = fs::read(options.dump_frame.as_ref().unwrap()).unwrap();
+        let ram = fs::read(options.dump_ram.as_ref().unwrap()).unwrap();
+        fs::remove_file(options.dump_frame.unwrap()).unwrap();
+        fs::remove_file(options.dump_ram.unwrap()).unwrap();
+
+        assert_eq!(report.frames, 3);
+        assert_eq!(emulator.frame_count(), 3);
+        // the first frame after power-on only runs from scanline 0 to vblank
+        assert!(
+            (86_800..87_100).contains(&report.cycles),
+            "{}",
+            report.cycles
+        );
+        assert_eq!(frame, emulator.frame().to_ppm());
+        assert_eq!(ram.len(), 0x800);
+        assert_ne!(ram[0x10], 0);
+    }
+}
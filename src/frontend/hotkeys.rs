@@ -0,0 +1,280 @@
+// Hotkeys: keys that drive the emulator rather than the game. A key bound
+// to an action never reaches the game bindings. Front ends only pass key
+// names to Session, which carries the actions out; the ones it can't do
+// itself, like Quit, come back to the front end.
+//
+// In config.toml each action takes a list of keys, and a [hotkeys] table
+// replaces the defaults below:
+//
+//     [hotkeys]
+//     save_state = ["F5"]
+//     fast_forward = ["Tab"]
+//     mute_triangle = ["3"]
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use serde::Deserialize;
+
+use crate::apu::mixer::Channel;
+use crate::input::keymap::Key;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Action {
+    Quit,
+    Reset,
+    Pause,
+    // one frame while paused; pauses first if running
+    FrameAdvance,
+    // step through the slow-motion speeds
+    SpeedDown,
+    SpeedUp,
+    // held: these last until the key is released
+    FastForward,
+    Rewind,
+    // to and from the selected slot
+    SaveState,
+    LoadState,
+    NextSlot,
+    PreviousSlot,
+    Screenshot,
+    Fullscreen,
+    // start or stop a video recording
+    Record,
+    // the last few seconds as a GIF
+    SaveClip,
+    // write out a --trace-last ring buffer
+    DumpTrace,
+    // every cheat off, or back on
+    ToggleCheats,
+    // the buttons drawn over the picture, or not
+    ToggleInputDisplay,
+    // a VS. System game's coin slot
+    InsertCoin,
+    ToggleChannel(Channel),
+}
+
+const ACTIONS: [(Action, &str); 25] = [
+    (Action::Quit, "quit"),
+    (Action::Reset, "reset"),
+    (Action::Pause, "pause"),
+    (Action::FrameAdvance, "frame_advance"),
+    (Action::SpeedDown, "speed_down"),
+    (Action::SpeedUp, "speed_up"),
+    (Action::FastForward, "fast_forward"),
+    (Action::Rewind, "rewind"),
+    (Action::SaveState, "save_state"),
+    (Action::LoadState, "load_state"),
+    (Action::NextSlot, "next_slot"),
+    (Action::PreviousSlot, "previous_slot"),
+    (Action::Screenshot, "screenshot"),
+    (Action::Fullscreen, "fullscreen"),
+    (Action::Record, "record"),
+    (Action::SaveClip, "save_clip"),
+    (Action::DumpTrace, "dump_trace"),
+    (Action::ToggleCheats, "toggle_cheats"),
+    (Action::ToggleInputDisplay, "toggle_input_display"),
+    (Action::InsertCoin, "insert_coin"),
+    (Action::ToggleChannel(Channel::Pulse1), "mute_pulse1"),
+    (Action::ToggleChannel(Channel::Pulse2), "mute_pulse2"),
+    (Action::ToggleChannel(Channel::Triangle), "mute_triangle"),
+    (Action::ToggleChannel(Channel::Noise), "mute_noise"),
+    (Action::ToggleChannel(Channel::Dmc), "mute_dmc"),
+];
+
+impl Action {
+    pub fn from_name(name: &str) -> Option<Action> {
+        ACTIONS
+            .iter()
+            .find(|(_, n)| *n == name)
+            .map(|(action, _)| *action)
+    }
+
+    pub fn is_held(self) -> bool {
+        matches!(self, Action::FastForward | Action::Rewind)
+    }
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = ACTIONS
+            .iter()
+            .find(|(action, _)| action == self)
+            .map_or("?", |(_, name)| name);
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownAction(pub String);
+
+impl fmt::Display for UnknownAction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "unknown hotkey action '{}'", self.0)
+    }
+}
+
+impl std::error::Error for UnknownAction {}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "HashMap<String, Vec<String>>")]
+pub struct Hotkeys {
+    actions: HashMap<Key, Action>,
+}
+
+impl TryFrom<HashMap<String, Vec<String>>> for Hotkeys {
+    type Error = UnknownAction;
+
+    fn try_from(table: HashMap<String, Vec<String>>) -> Result<Self, Self::Error> {
+        let mut hotkeys = Hotkeys::new();
+        for (name, keys) in table {
+            let action = Action::from_name(&name).ok_or(UnknownAction(name))?;
+            for key in keys {
+                hotkeys.bind(&key, action);
+            }
+        }
+        Ok(hotkeys)
+    }
+}
+
+impl Hotkeys {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // A key does one thing; binding it again replaces the old action.
+    pub fn bind(&mut self, key: &str, action: Action) {
+        self.actions.insert(Key::new(key), action);
+    }
+
+    pub fn unbind_key(&mut self, key: &str) {
+        self.actions.remove(&Key::new(key));
+    }
+
+    pub fn action_for(&self, key: &str) -> Option<Action> {
+        self.actions.get(&Key::new(key)).copied()
+    }
+
+    // Clear of the default game keys for both players.
+    pub fn default_hotkeys() -> Self {
+        let mut hotkeys = Hotkeys::new();
+        let defaults = [
+            ("Escape", Action::Quit),
+            ("F1", Action::Reset),
+            ("P", Action::Pause),
+            ("N", Action::FrameAdvance),
+            ("F2", Action::SpeedDown),
+            ("F3", Action::SpeedUp),
+            ("Tab", Action::FastForward),
+            ("Backspace", Action::Rewind),
+            ("F4", Action::PreviousSlot),
+            ("F5", Action::SaveState),
+            ("F6", Action::NextSlot),
+            ("F7", Action::LoadState),
+            ("F8", Action::SaveClip),
+            ("F9", Action::Record),
+            ("F10", Action::DumpTrace),
+            ("F11", Action::Fullscreen),
+            ("F12", Action::Screenshot),
+            ("C", Action::ToggleCheats),
+            ("D", Action::ToggleInputDisplay),
+            ("9", Action::InsertCoin),
+            ("1", Action::ToggleChannel(Channel::Pulse1)),
+            ("2", Action::ToggleChannel(Channel::Pulse2)),
+            ("3", Action::ToggleChannel(Channel::Triangle)),
+            ("4", Action::ToggleChannel(Channel::Noise)),
+            ("5", Action::ToggleChannel(Channel::Dmc)),
+        ];
+        for (key, action) in defaults {
+            hotkeys.bind(key, action);
+        }
+        hotkeys
+    }
+}
+
+// Which hotkeys are down, so auto-repeat doesn't fire an action again.
+#[derive(Debug, Default)]
+pub struct HotkeyInput {
+    pub hotkeys: Hotkeys,
+    held: HashSet<Key>,
+}
+
+impl HotkeyInput {
+    pub fn new(hotkeys: Hotkeys) -> Self {
+        HotkeyInput {
+            hotkeys,
+            held: HashSet::new(),
+        }
+    }
+
+    pub fn is_bound(&self, key: &str) -> bool {
+        self.hotkeys.action_for(key).is_some()
+    }
+
+    // The action, if this press starts one.
+    pub fn key_down(&mut self, key: &str) -> Option<Action> {
+        let action = self.hotkeys.action_for(key)?;
+        self.held.insert(Key::new(key)).then_some(action)
+    }
+
+    pub fn key_up(&mut self, key: &str) -> Option<Action> {
+        self.held.remove(&Key::new(key));
+        self.hotkeys.action_for(key)
+    }
+
+    // Actions whose keys were down, for when focus is lost.
+    pub fn release_all(&mut self) -> Vec<Action> {
+        self.held
+            .drain()
+            .filter_map(|key| self.hotkeys.actions.get(&key).copied())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::input::keymap::KeyBindings;
+
+    #[test]
+    fn test_hotkeys_from_toml() {
+        let hotkeys: Hotkeys =
+            toml::from_str("pause = [\"Space\"]\nmute_noise = [\"N\", \"4\"]").unwrap();
+        assert_eq!(hotkeys.action_for("space"), Some(Action::Pause));
+        assert_eq!(
+            hotkeys.action_for("4"),
+            Some(Action::ToggleChannel(Channel::Noise))
+        );
+        assert_eq!(hotkeys.action_for("Escape"), None);
+
+        let err = toml::from_str::<Hotkeys>("rewnd = [\"R\"]").unwrap_err();
+        assert!(err.to_string().contains("unknown hotkey action 'rewnd'"));
+    }
+
+    #[test]
+    fn test_defaults_leave_game_keys_alone() {
+        let hotkeys = Hotkeys::default_hotkeys();
+        let bindings = KeyBindings::default_bindings();
+        for key in hotkeys.actions.keys() {
+            assert!(bindings.bindings_for(key).is_empty(), "{:?}", key);
+        }
+        for (action, name) in ACTIONS {
+            assert_eq!(action.to_string(), name);
+        }
+    }
+
+    #[test]
+    fn test_repeats_are_swallowed() {
+        let mut input = HotkeyInput::new(Hotkeys::default_hotkeys());
+        assert_eq!(input.key_down("P"), Some(Action::Pause));
+        assert_eq!(input.key_down("P"), None);
+        assert_eq!(input.key_up("P"), Some(Action::Pause));
+        assert_eq!(input.key_down("p"), Some(Action::Pause));
+
+        assert_eq!(input.key_down("Tab"), Some(Action::FastForward));
+        let mut released = input.release_all();
+        released.sort_by_key(|action| action.to_string());
+        assert_eq!(released, [Action::FastForward, Action::Pause]);
+        assert_eq!(input.key_down("Z"), None);
+    }
+}
@@ -0,0 +1,232 @@
+// The games played lately, kept in recent.toml in the data directory, and
+// the screen that lists them when nes is started without a ROM, e.g. from
+// a desktop shortcut. The launcher is drawn into a frame with the OSD font
+// like everything else on screen and is worked with the first player's
+// joypad, so keyboards and gamepads both drive it with the game bindings:
+// up and down pick, Start or A plays. Dropping a ROM on the window works
+// too.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::input::joypad::JoypadButton;
+use crate::render::frame::Frame;
+use crate::render::osd::draw_text;
+
+const MAX_RECENT: usize = 20;
+
+// launcher layout, in frame pixels
+const LEFT: usize = 16;
+const TOP: usize = 16;
+const ROW_HEIGHT: usize = 11;
+const VISIBLE_ROWS: usize = 17;
+const MAX_NAME: usize = 28;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecentRom {
+    pub path: PathBuf,
+    pub title: String,
+    // of PRG and CHR ROM, as `nes info` prints it
+    pub crc32: u32,
+    // seconds since the Unix epoch
+    pub last_played: u64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecentRoms {
+    // most recently played first
+    #[serde(default, rename = "rom")]
+    pub roms: Vec<RecentRom>,
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs())
+}
+
+impl RecentRoms {
+    // No file yet is an empty list.
+    pub fn load(path: &Path) -> io::Result<Self> {
+        match fs::read_to_string(path) {
+            Ok(text) => {
+                toml::from_str(&text).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+            }
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(err) => Err(err),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let text = toml::to_string(self).map_err(io::Error::other)?;
+        fs::write(path, text)
+    }
+
+    // Moves the ROM to the top, stamped with the current time.
+    pub fn played(&mut self, path: &Path, title: &str, crc32: u32) {
+        self.roms.retain(|rom| rom.path != path);
+        self.roms.insert(
+            0,
+            RecentRom {
+                path: path.to_path_buf(),
+                title: title.to_string(),
+                crc32,
+                last_played: now(),
+            },
+        );
+        self.roms.truncate(MAX_RECENT);
+    }
+}
+
+fn age(last_played: u64, now: u64) -> String {
+    match now.saturating_sub(last_played) / 86_400 {
+        0 => "today".to_string(),
+        1 => "yesterday".to_string(),
+        days => format!("{} days ago", days),
+    }
+}
+
+#[derive(Debug)]
+pub struct Launcher {
+    roms: Vec<RecentRom>,
+    selected: usize,
+    held: JoypadButton,
+}
+
+impl Launcher {
+    // ROMs that have gone missing since are left out.
+    pub fn new(recent: &RecentRoms) -> Self {
+        Launcher {
+            roms: recent
+                .roms
+                .iter()
+                .filter(|rom| rom.path.exists())
+                .cloned()
+                .collect(),
+            selected: 0,
+            // a button held at startup doesn't count until let go
+            held: JoypadButton::all(),
+        }
+    }
+
+    pub fn selected(&self) -> Option<&RecentRom> {
+        self.roms.get(self.selected)
+    }
+
+    // Once per frame with the first player's buttons; returns the ROM to
+    // play when one is picked. Only presses count, not holding.
+    pub fn update(&mut self, buttons: JoypadButton) -> Option<PathBuf> {
+        let pressed = buttons & !self.held;
+        self.held = buttons;
+        if self.roms.is_empty() {
+            return None;
+        }
+        if pressed.contains(JoypadButton::UP) {
+            self.selected = self.selected.checked_sub(1).unwrap_or(self.roms.len() - 1);
+        }
+        if pressed.contains(JoypadButton::DOWN) {
+            self.selected = (self.selected + 1) % self.roms.len();
+        }
+        if pressed.intersects(JoypadButton::START | JoypadButton::BUTTON_A) {
+            return self.selected().map(|rom| rom.path.clone());
+        }
+        None
+    }
+
+    pub fn draw(&self, frame: &mut Frame) {
+        *frame = Frame::new();
+        if self.roms.is_empty() {
+            draw_text(frame, LEFT, TOP, "No games played yet.");
+            draw_text(frame, LEFT, TOP + 2 * ROW_HEIGHT, "Drop a ROM file here");
+            draw_text(frame, LEFT, TOP + 3 * ROW_HEIGHT, "or run: nes game.nes");
+            return;
+        }
+        draw_text(frame, LEFT, TOP, "Recent games");
+        let now = now();
+        // scrolled to keep the selection in view
+        let first = (self.selected + 1).saturating_sub(VISIBLE_ROWS);
+        for (row, rom) in self.roms.iter().skip(first).take(VISIBLE_ROWS).enumerate() {
+            let marker = if first + row == self.selected {
+                '>'
+            } else {
+                ' '
+            };
+            let title: String = rom.title.chars().take(MAX_NAME).collect();
+            let line = format!("{} {:<28} {}", marker, title, age(rom.last_played, now));
+            draw_text(frame, LEFT - 8, TOP + (row + 2) * ROW_HEIGHT, &line);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_recent_list_round_trips_and_reorders() {
+        let dir = std::env::temp_dir().join(format!("nes-recent-{}", std::process::id()));
+        let file = dir.join("recent.toml");
+        assert!(RecentRoms::load(&file).unwrap().roms.is_empty());
+
+        let mut recent = RecentRoms::default();
+        recent.played(Path::new("/roms/smb.nes"), "smb", 0x3337EC46);
+        recent.played(Path::new("/roms/zelda.nes"), "zelda", 0xD7AE93DF);
+        recent.played(Path::new("/roms/smb.nes"), "smb", 0x3337EC46);
+        let titles: Vec<_> = recent.roms.iter().map(|rom| rom.title.as_str()).collect();
+        assert_eq!(titles, ["smb", "zelda"]);
+
+        recent.save(&file).unwrap();
+        assert_eq!(RecentRoms::load(&file).unwrap(), recent);
+        fs::remove_dir_all(dir).unwrap();
+
+        assert_eq!(age(1000, 1000 + 3600), "today");
+        assert_eq!(age(1000, 1000 + 3 * 86_400), "3 days ago");
+    }
+
+    #[test]
+    fn test_launcher_picks_with_the_joypad() {
+        let dir = std::env::temp_dir().join(format!("nes-launcher-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let mut recent = RecentRoms::default();
+        for name in ["a.nes", "b.nes", "gone.nes"] {
+            recent.played(&dir.join(name), name, 0);
+            if name != "gone.nes" {
+                fs::write(dir.join(name), b"").unwrap();
+            }
+        }
+        let mut launcher = Launcher::new(&recent);
+        assert_eq!(launcher.roms.len(), 2);
+        assert_eq!(launcher.selected().unwrap().title, "b.nes");
+
+        // Start from before the launcher came up
+        assert_eq!(launcher.update(JoypadButton::START), None);
+        launcher.update(JoypadButton::empty());
+        launcher.update(JoypadButton::DOWN);
+        // held, not pressed again
+        launcher.update(JoypadButton::DOWN);
+        assert_eq!(launcher.selected().unwrap().title, "a.nes");
+        // UP is the new press; round the top to the bottom
+        launcher.update(JoypadButton::DOWN | JoypadButton::UP);
+        launcher.update(JoypadButton::empty());
+        launcher.update(JoypadButton::UP);
+        assert_eq!(launcher.selected().unwrap().title, "a.nes");
+        assert_eq!(
+            launcher.update(JoypadButton::BUTTON_A),
+            Some(dir.join("a.nes"))
+        );
+
+        let mut frame = Frame::new();
+        frame.set_pixel(0, 0, (1, 2, 3));
+        launcher.draw(&mut frame);
+        assert_eq!(frame.pixel(0, 0), (0, 0, 0));
+        assert_ne!(frame, Frame::new());
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,2511 @@
+// Playable front ends: a window, input devices and an audio device wrapped
+// around an Emulator. Each one sits behind a feature so the core crate
+// builds without any system libraries.
+//
+// Everything that isn't tied to a windowing library lives here: options,
+// the keyboard/gamepad to joypad translation, hotkeys, frame pacing and
+// the per-frame emulate/present/queue-audio sequence. A front end only
+// turns its own events into key names and GamepadEvents and puts the
+// finished frame on screen.
+
+pub mod battery;
+pub mod headless;
+pub mod hotkeys;
+pub mod launcher;
+#[cfg(feature = "winit")]
+pub mod post_process;
+#[cfg(feature = "sdl2")]
+pub mod sdl;
+pub mod stats;
+#[cfg(feature = "tui")]
+pub mod tui;
+pub mod watch;
+#[cfg(feature = "wasm")]
+pub mod web;
+#[cfg(feature = "winit")]
+pub mod winit;
+
+use std::fs;
+use std::io;
+use std::net::{SocketAddr, TcpListener};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde::Deserialize;
+
+#[cfg(feature = "rcheevos")]
+use crate::achievements::{AchievementEvent, Achievements};
+use crate::audio::rate_control::RateControl;
+use crate::audio::{AudioSink, Latency, NullSink};
+use crate::autosplit::livesplit::LiveSplit;
+use crate::autosplit::{format_time, frame_time, Autosplitter, SplitEvent};
+use crate::cartridge::Cartridge;
+use crate::cheat::{Cheat, Cheats};
+use crate::config::PathsConfig;
+use crate::crash;
+use crate::debugger::memory::{MemoryPort, MemorySpace};
+use crate::emulator::Emulator;
+use crate::input::gamepad::{GamepadInput, GamepadMapping};
+use crate::input::joypad::JoypadButton;
+use crate::input::keymap::{KeyBindings, KeyboardInput, MAX_PLAYERS};
+use crate::movie::MovieCommand;
+use crate::netplay::{Advance, Netplay, NetplayError};
+use crate::remote::json::Json;
+use crate::remote::{self, Event, RemoteServer, Request, RpcError, NO_GAME};
+use crate::render::clip::ClipRecorder;
+use crate::render::frame::{encode_png, Frame};
+use crate::render::input_display;
+use crate::render::osd::Osd;
+use crate::render::shader::PostShader;
+use crate::render::video::VideoRecorder;
+use crate::render::viewport::Scaling;
+use crate::rewind::RewindBuffer;
+use crate::script::Scripts;
+use crate::state::Snapshot;
+use battery::{BatteryBacking, BatteryFile};
+use hotkeys::{Action, HotkeyInput, Hotkeys};
+use launcher::{Launcher, RecentRoms};
+use stats::{AudioStats, Stats, StatsDisplay, StatsMeter};
+use watch::{ReloadMode, RomWatcher};
+
+pub use crate::region::{NTSC_FRAME_RATE, PAL_FRAME_RATE};
+
+// How far pacing may fall behind before it gives up catching up.
+const MAX_LAG_FRAMES: u32 = 4;
+
+// thread::sleep can overshoot by a millisecond or more, so waits sleep
+// until this close to the deadline and spin for the rest.
+const SPIN_MARGIN: Duration = Duration::from_micros(1500);
+
+// How often audio pacing looks at the queue.
+const AUDIO_POLL: Duration = Duration::from_millis(1);
+
+// Displays this close to the console's rate get one frame per refresh.
+const VSYNC_SNAP: f64 = 0.05;
+const REFRESH_SMOOTHING: f64 = 0.1;
+
+// What SpeedDown and SpeedUp step through.
+const SPEEDS: [f64; 5] = [0.125, 0.25, 0.5, 0.75, 1.0];
+
+// Savestate slots per game, 0-9.
+const SLOTS: u8 = 10;
+
+// A rewind point every other frame, so holding rewind plays back at about
+// normal speed, in at most 64MB.
+const REWIND_INTERVAL: u64 = 2;
+const REWIND_MEMORY: usize = 64 << 20;
+
+// How often battery RAM is checked and written if the game changed it, in
+// emulated frames. Mapped saves are copied every frame; see battery.rs.
+const BATTERY_FLUSH_INTERVAL: u64 = 60;
+
+// What decides when the next frame runs.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Pacing {
+    // sleeping to the console's frame rate
+    #[default]
+    Timer,
+    // the front end presents with vsync, which does the waiting; frames are
+    // run for the time that actually goes by, see RefreshClock
+    Vsync,
+    // the audio device: a frame runs whenever the queue drops to the
+    // target latency
+    Audio,
+}
+
+// What the fullscreen hotkey switches to.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FullscreenMode {
+    // a window covering the screen at the desktop's resolution
+    #[default]
+    Borderless,
+    // the display to ourselves, in its largest mode; no compositor in the
+    // way, but switching makes the screen blank for a moment
+    Exclusive,
+}
+
+// Which picture the screenshot hotkey saves.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ScreenshotStage {
+    // the console's 256x240 frame
+    #[default]
+    Raw,
+    // what the window shows: scaled, and through the shader if there is
+    // one. Front ends that can't read their output back save Raw.
+    Filtered,
+}
+
+// Where a window was, in the window system's pixels. Not every window
+// system lets us know or set the position.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Geometry {
+    pub position: Option<(i32, i32)>,
+    pub size: (u32, u32),
+}
+
+// Whether a window is fullscreen, and the geometry to give it back when
+// it stops being.
+#[derive(Debug, Clone, Copy)]
+pub struct WindowState {
+    pub mode: FullscreenMode,
+    windowed: Option<Geometry>,
+}
+
+impl WindowState {
+    pub fn new(mode: FullscreenMode) -> Self {
+        WindowState {
+            mode,
+            windowed: None,
+        }
+    }
+
+    pub fn is_fullscreen(&self) -> bool {
+        self.windowed.is_some()
+    }
+
+    pub fn enter(&mut self, windowed: Geometry) {
+        self.windowed.get_or_insert(windowed);
+    }
+
+    // The geometry to restore, if the window was fullscreen.
+    pub fn leave(&mut self) -> Option<Geometry> {
+        self.windowed.take()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Options {
+    // window size as a multiple of 256x240
+    pub scale: u32,
+    pub scaling: Scaling,
+    pub sample_rate: u32,
+    // samples to keep queued and the device's buffer
+    pub audio_latency: Latency,
+    pub bindings: KeyBindings,
+    pub mapping: GamepadMapping,
+    pub hotkeys: Hotkeys,
+    // the ROM file being played, for the recent list
+    pub rom: Option<PathBuf>,
+    // where the recent list is kept; None keeps none
+    pub recent_file: Option<PathBuf>,
+    // names screenshots and battery saves
+    pub game: String,
+    // battery RAM goes here as {game}.sav
+    pub saves_dir: PathBuf,
+    pub battery: BatteryBacking,
+    // and cheats as {game}.toml
+    pub cheats_dir: PathBuf,
+    // savestate slots, by the ROM's checksum
+    pub states_dir: PathBuf,
+    pub screenshot_dir: PathBuf,
+    pub screenshot: ScreenshotStage,
+    pub recordings_dir: PathBuf,
+    // the program that encodes recordings
+    pub ffmpeg: PathBuf,
+    // start recording into this file
+    pub record: Option<PathBuf>,
+    // how much play SaveClip keeps; 0 turns it off
+    pub clip_seconds: f64,
+    // how far back Rewind goes; 0 turns it off
+    pub rewind_seconds: f64,
+    // emulated frames per real one; below 1 is slow motion
+    pub speed: f64,
+    // the speed while fast-forward is held
+    pub fast_forward_speed: f64,
+    pub frame_rate: f64,
+    pub pacing: Pacing,
+    // start fullscreen
+    pub fullscreen: bool,
+    pub fullscreen_mode: FullscreenMode,
+    // messages and status drawn over the picture
+    pub osd: bool,
+    pub stats: StatsDisplay,
+    // the joypads over the picture; see render/input_display.rs
+    pub input_display: bool,
+    // post-processing for front ends with a GPU path; None is plain
+    // scaling
+    pub shader: Option<PostShader>,
+    // serve the remote control protocol here
+    pub remote: Option<SocketAddr>,
+    // play against another instance; see netplay/
+    pub netplay: Option<NetplayRole>,
+    // a RetroAchievements set, as the patch data the site serves
+    #[cfg(feature = "rcheevos")]
+    pub achievements: Option<PathBuf>,
+    // play it without savestates, rewind, slow motion or cheats
+    #[cfg(feature = "rcheevos")]
+    pub hardcore: bool,
+    // a splits file to time a speedrun by; see autosplit/
+    pub autosplit: Option<PathBuf>,
+    // and a LiveSplit server to send the splits to
+    pub livesplit: Option<SocketAddr>,
+    // write a crash dump here if emulating panics; see crash.rs
+    pub crash_dumps: Option<PathBuf>,
+    // reload the ROM when it's rebuilt; see watch.rs
+    pub hot_reload: Option<ReloadMode>,
+    // symbol files the debugger has loaded, read again on a reload
+    pub symbols: Vec<PathBuf>,
+    // a Lua script to run alongside the game; see script/lua.rs
+    #[cfg(feature = "lua")]
+    pub script: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NetplayRole {
+    // wait for a guest here, with this input delay and rollback window
+    Host {
+        addr: SocketAddr,
+        delay: u8,
+        window: u8,
+    },
+    Join(SocketAddr),
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            scale: 3,
+            scaling: Scaling::default(),
+            sample_rate: 48_000,
+            audio_latency: Latency::default(),
+            bindings: KeyBindings::default_bindings(),
+            mapping: GamepadMapping::default(),
+            hotkeys: Hotkeys::default_hotkeys(),
+            rom: None,
+            recent_file: None,
+            game: "nes".to_string(),
+            saves_dir: PathsConfig::default().saves_dir(),
+            battery: BatteryBacking::Buffered,
+            cheats_dir: PathsConfig::default().cheats_dir(),
+            states_dir: PathsConfig::default().states_dir(),
+            screenshot_dir: PathsConfig::default().screenshots_dir(),
+            screenshot: ScreenshotStage::Raw,
+            recordings_dir: PathsConfig::default().recordings_dir(),
+            ffmpeg: PathBuf::from("ffmpeg"),
+            record: None,
+            clip_seconds: 10.0,
+            rewind_seconds: 60.0,
+            speed: 1.0,
+            fast_forward_speed: 4.0,
+            frame_rate: NTSC_FRAME_RATE,
+            pacing: Pacing::Timer,
+            fullscreen: false,
+            fullscreen_mode: FullscreenMode::Borderless,
+            osd: true,
+            stats: StatsDisplay::Off,
+            input_display: false,
+            shader: None,
+            remote: None,
+            netplay: None,
+            #[cfg(feature = "rcheevos")]
+            achievements: None,
+            #[cfg(feature = "rcheevos")]
+            hardcore: false,
+            autosplit: None,
+            livesplit: None,
+            crash_dumps: None,
+            hot_reload: None,
+            symbols: Vec::new(),
+            #[cfg(feature = "lua")]
+            script: None,
+        }
+    }
+}
+
+// The host's default output device through cpal when that feature is
+// enabled, or silence. `buffer` is the device's period in sample frames.
+pub fn default_sink(sample_rate: u32, buffer: Option<u32>) -> Box<dyn AudioSink> {
+    #[cfg(not(feature = "cpal"))]
+    let _ = buffer;
+    #[cfg(feature = "cpal")]
+    match crate::audio::cpal_sink::CpalSink::new(buffer) {
+        Ok(sink) => return Box::new(sink),
+        Err(err) => eprintln!("no audio: {}", err),
+    }
+    Box::new(NullSink::new(sample_rate))
+}
+
+// Keyboard and gamepads together; a player's buttons are whatever either
+// of them holds.
+#[derive(Debug, Default)]
+pub struct Input {
+    pub keyboard: KeyboardInput,
+    pub gamepads: GamepadInput,
+}
+
+impl Input {
+    pub fn new(bindings: KeyBindings, mapping: GamepadMapping) -> Self {
+        Input {
+            keyboard: KeyboardInput::new(bindings),
+            gamepads: GamepadInput::new(mapping),
+        }
+    }
+
+    pub fn buttons(&self, player: usize) -> JoypadButton {
+        self.keyboard.buttons(player) | self.gamepads.buttons(player)
+    }
+
+    pub fn apply(&self, emulator: &mut Emulator) {
+        for player in 0..MAX_PLAYERS {
+            emulator.set_buttons(player, self.buttons(player));
+        }
+    }
+}
+
+// Wall-clock frame deadlines at the console's frame rate.
+#[derive(Debug, Clone, Copy)]
+pub struct FramePacer {
+    frame_time: Duration,
+    deadline: Instant,
+}
+
+impl FramePacer {
+    pub fn new(frame_rate: f64) -> Self {
+        FramePacer {
+            frame_time: Duration::from_secs_f64(1.0 / frame_rate),
+            deadline: Instant::now(),
+        }
+    }
+
+    pub fn deadline(&self) -> Instant {
+        self.deadline
+    }
+
+    pub fn is_due(&self, now: Instant) -> bool {
+        now >= self.deadline
+    }
+
+    // Moves on to the next frame. After a long stall (window dragged,
+    // machine asleep) the schedule restarts from `now` rather than running
+    // a burst of frames to catch up.
+    pub fn advance(&mut self, now: Instant) {
+        self.deadline += self.frame_time;
+        if now > self.deadline + self.frame_time * MAX_LAG_FRAMES {
+            self.deadline = now;
+        }
+    }
+
+    // Blocks until the current frame is due, then advances.
+    pub fn wait(&mut self) {
+        sleep_until(self.deadline);
+        self.advance(Instant::now());
+    }
+}
+
+pub fn sleep_until(deadline: Instant) {
+    let now = Instant::now();
+    if deadline > now + SPIN_MARGIN {
+        thread::sleep(deadline - now - SPIN_MARGIN);
+    }
+    while Instant::now() < deadline {
+        thread::yield_now();
+    }
+}
+
+// Console frames per display refresh, for vsync pacing. A display within a
+// few percent of the console's rate gets exactly one frame per refresh and
+// the 0.16% between 60Hz and 60.0988Hz is left to audio rate control; any
+// other display (50Hz, 144Hz) gets frames for the time that went by,
+// averaged over a few refreshes to ride out jitter.
+#[derive(Debug, Clone, Copy)]
+pub struct RefreshClock {
+    frame_rate: f64,
+    last: Option<Instant>,
+    average: f64,
+}
+
+impl RefreshClock {
+    pub fn new(frame_rate: f64) -> Self {
+        RefreshClock {
+            frame_rate,
+            last: None,
+            average: 1.0,
+        }
+    }
+
+    // Frames owed for the refresh that happened at `now`.
+    pub fn tick(&mut self, now: Instant) -> f64 {
+        if let Some(last) = self.last {
+            let frames = (now - last).as_secs_f64() * self.frame_rate;
+            let frames = frames.min(MAX_LAG_FRAMES as f64);
+            self.average += (frames - self.average) * REFRESH_SMOOTHING;
+        }
+        self.last = Some(now);
+        if (self.average - 1.0).abs() < VSYNC_SNAP {
+            1.0
+        } else {
+            self.average
+        }
+    }
+}
+
+// The window title: the ROM's path, or what the front end was started
+// with before there is one, and the stats if they go there.
+#[derive(Debug)]
+pub struct Title {
+    base: String,
+    shown: Option<String>,
+}
+
+impl Title {
+    pub fn new(base: &str) -> Self {
+        Title {
+            base: base.to_string(),
+            shown: None,
+        }
+    }
+
+    // After each frame; the title to set if it changed.
+    pub fn update(&mut self, session: &Session) -> Option<&str> {
+        let mut title = session
+            .rom()
+            .map_or(self.base.clone(), |rom| rom.display().to_string());
+        if let Some(stats) = session.title_stats() {
+            title = format!("{} | {}", title, stats);
+        }
+        if self.shown.as_ref() == Some(&title) {
+            return None;
+        }
+        Some(self.shown.insert(title))
+    }
+}
+
+// "{game}-{n}-frame{frame}.{extension}", numbered one past the game's
+// highest screenshot or recording so far. Creates the directory.
+fn numbered_path(dir: &Path, game: &str, frame: u64, extension: &str) -> io::Result<PathBuf> {
+    fs::create_dir_all(dir)?;
+    let prefix = format!("{}-", game);
+    let mut next = 1;
+    for entry in fs::read_dir(dir)? {
+        let name = entry?.file_name();
+        let number = name
+            .to_str()
+            .and_then(|name| name.strip_prefix(&prefix))
+            .and_then(|rest| rest.split('-').next())
+            .and_then(|number| number.parse::<u32>().ok());
+        if let Some(number) = number {
+            next = next.max(number + 1);
+        }
+    }
+    Ok(dir.join(format!("{}-{:03}-frame{}.{}", game, next, frame, extension)))
+}
+
+// The emulator plus everything a front end drives it with.
+pub struct Session {
+    pub emulator: Emulator,
+    pub input: Input,
+    pub pacer: FramePacer,
+    pub hotkeys: HotkeyInput,
+    pub scripts: Scripts,
+    pacing: Pacing,
+    refresh: RefreshClock,
+    sink: Box<dyn AudioSink>,
+    rate_control: RateControl,
+    paused: bool,
+    // run one frame while paused
+    advance: bool,
+    speed: f64,
+    fast_forward: bool,
+    fast_forward_speed: f64,
+    // frames owed at the current speed, carried between calls
+    credit: f64,
+    rewinding: bool,
+    rewind: RewindBuffer,
+    states_dir: PathBuf,
+    slot: u8,
+    rom: Option<PathBuf>,
+    game: String,
+    saves_dir: PathBuf,
+    battery: BatteryFile,
+    cheats_dir: PathBuf,
+    recent_file: Option<PathBuf>,
+    launcher: Option<Launcher>,
+    screenshot_dir: PathBuf,
+    screenshot: ScreenshotStage,
+    // a Filtered screenshot for the front end to take
+    capture: bool,
+    recorder: Option<VideoRecorder>,
+    recordings_dir: PathBuf,
+    ffmpeg: PathBuf,
+    // recordings are scaled to the window's starting size
+    record_size: (u32, u32),
+    frame_rate: f64,
+    clip_seconds: f64,
+    clip: ClipRecorder,
+    osd: Osd,
+    show_osd: bool,
+    meter: StatsMeter,
+    show_stats: StatsDisplay,
+    show_input: bool,
+    crash_dumps: Option<PathBuf>,
+    reload_mode: Option<ReloadMode>,
+    watcher: Option<RomWatcher>,
+    symbol_files: Vec<PathBuf>,
+    // the frame with the OSD drawn over it
+    display: Frame,
+    remote: Option<RemoteServer>,
+    // buttons a remote client holds, over the player's
+    remote_buttons: [Option<JoypadButton>; MAX_PLAYERS],
+    // what "pause" subscribers were last told
+    remote_paused: bool,
+    netplay: Option<Netplay>,
+    // false while a netplay guest plays the host's save, which isn't its
+    // own to write
+    keep_battery: bool,
+    // a reset to go out with the next netplay input
+    netplay_commands: MovieCommand,
+    #[cfg(feature = "rcheevos")]
+    achievements: Option<Achievements>,
+    // unlocks and leaderboard results for the front end to report
+    #[cfg(feature = "rcheevos")]
+    achievement_events: Vec<AchievementEvent>,
+    autosplit: Option<Autosplitter>,
+    livesplit: Option<LiveSplit>,
+    split_events: Vec<SplitEvent>,
+}
+
+impl Session {
+    pub fn new(mut emulator: Emulator, options: Options, sink: Box<dyn AudioSink>) -> Self {
+        emulator.enable_audio(sink.sample_rate());
+        emulator.set_audio_latency(options.audio_latency);
+        let rate_control = emulator.rate_control();
+        let clip = ClipRecorder::new(emulator.palette(), options.clip_seconds, options.frame_rate);
+        let mut session = Session {
+            emulator,
+            input: Input::new(options.bindings, options.mapping),
+            pacer: FramePacer::new(options.frame_rate),
+            hotkeys: HotkeyInput::new(options.hotkeys),
+            scripts: Scripts::new(),
+            pacing: options.pacing,
+            refresh: RefreshClock::new(options.frame_rate),
+            sink,
+            rate_control,
+            paused: false,
+            advance: false,
+            speed: options.speed,
+            fast_forward: false,
+            fast_forward_speed: options.fast_forward_speed,
+            credit: 0.0,
+            rewinding: false,
+            rewind: RewindBuffer::new(
+                (options.rewind_seconds * options.frame_rate / REWIND_INTERVAL as f64) as usize,
+                REWIND_MEMORY,
+            ),
+            states_dir: options.states_dir,
+            slot: 0,
+            rom: None,
+            game: options.game,
+            battery: BatteryFile::new(PathBuf::new(), options.battery),
+            saves_dir: options.saves_dir,
+            cheats_dir: options.cheats_dir,
+            recent_file: options.recent_file,
+            launcher: None,
+            screenshot_dir: options.screenshot_dir,
+            screenshot: options.screenshot,
+            capture: false,
+            recorder: None,
+            recordings_dir: options.recordings_dir,
+            ffmpeg: options.ffmpeg,
+            record_size: options.scaling.window_size(options.scale),
+            frame_rate: options.frame_rate,
+            clip_seconds: options.clip_seconds,
+            clip,
+            osd: Osd::new(),
+            show_osd: options.osd,
+            meter: StatsMeter::new(options.frame_rate),
+            show_stats: options.stats,
+            show_input: options.input_display,
+            crash_dumps: options.crash_dumps.clone(),
+            reload_mode: options.hot_reload,
+            watcher: None,
+            symbol_files: options.symbols,
+            display: Frame::new(),
+            remote: None,
+            remote_buttons: [None; MAX_PLAYERS],
+            remote_paused: false,
+            netplay: None,
+            keep_battery: true,
+            netplay_commands: MovieCommand::empty(),
+            #[cfg(feature = "rcheevos")]
+            achievements: None,
+            #[cfg(feature = "rcheevos")]
+            achievement_events: Vec::new(),
+            autosplit: None,
+            livesplit: None,
+            split_events: Vec::new(),
+        };
+        if let Some(addr) = options.remote {
+            match RemoteServer::bind(addr) {
+                Ok(server) => {
+                    eprintln!("remote control on ws://{}", server.local_addr());
+                    session.remote = Some(server);
+                }
+                Err(err) => eprintln!("can't serve remote control on {}: {}", addr, err),
+            }
+        }
+        if let Err(err) = session.load_battery() {
+            eprintln!("can't read {}: {}", session.battery_path().display(), err);
+        }
+        session.load_cheats();
+        #[cfg(feature = "lua")]
+        if let Some(path) = options.script {
+            let loaded =
+                crate::script::lua::load_file(&mut session.scripts, &mut session.emulator, &path);
+            if let Err(err) = loaded {
+                eprintln!("can't run {}: {}", path.display(), err);
+            }
+        }
+        #[cfg(feature = "rcheevos")]
+        if let Some(path) = options.achievements {
+            session.load_achievements(&path, options.hardcore);
+        }
+        if let Some(path) = options.autosplit {
+            session.load_splits(&path, options.livesplit);
+        }
+        if let Some(role) = options.netplay {
+            session.start_netplay(role);
+        }
+        // an empty console has the launcher pick a game
+        if !session.emulator.has_cartridge() {
+            session.launcher = Some(Launcher::new(&session.recent()));
+        } else if let Some(path) = options.rom {
+            session.remember(&path);
+            session.watcher = session.reload_mode.map(|_| RomWatcher::new(&path));
+            session.rom = Some(path);
+        }
+        if let Some(path) = options.record {
+            if let Err(err) = session.start_recording(&path) {
+                eprintln!("can't record to {}: {}", path.display(), err);
+            }
+        }
+        session
+    }
+
+    // Connects before the first frame; hosting waits here for the guest.
+    // Cheats go off on both sides, as the guest's game is the host's.
+    fn start_netplay(&mut self, role: NetplayRole) {
+        if !self.emulator.has_cartridge() {
+            eprintln!("netplay needs a ROM");
+            return;
+        }
+        let connected = match role {
+            NetplayRole::Host {
+                addr,
+                delay,
+                window,
+            } => TcpListener::bind(addr)
+                .map_err(NetplayError::from)
+                .and_then(|listener| {
+                    eprintln!("waiting for player 2 on {}", listener.local_addr()?);
+                    Netplay::host(&listener, &mut self.emulator, delay, window)
+                }),
+            NetplayRole::Join(addr) => Netplay::join(addr, &mut self.emulator),
+        };
+        let netplay = match connected {
+            Ok(netplay) => netplay,
+            Err(err) => {
+                eprintln!("can't start netplay: {}", err);
+                return;
+            }
+        };
+        self.emulator.cheats_mut().set_suspended(true);
+        self.keep_battery = netplay.player() == 0;
+        let peer = netplay
+            .peer()
+            .map_or("?".to_string(), |peer| peer.to_string());
+        self.osd.show(format!(
+            "Netplay with {} as player {}",
+            peer,
+            netplay.player() + 1
+        ));
+        self.netplay = Some(netplay);
+    }
+
+    // Hardcore mode takes the cheats away for as long as the set is
+    // loaded; see achievements/.
+    #[cfg(feature = "rcheevos")]
+    fn load_achievements(&mut self, path: &Path, hardcore: bool) {
+        let loaded = fs::read_to_string(path)
+            .map_err(|err| err.to_string())
+            .and_then(|text| Achievements::from_json(&text).map_err(|err| err.to_string()));
+        let mut achievements = match loaded {
+            Ok(achievements) => achievements,
+            Err(err) => {
+                eprintln!("can't read {}: {}", path.display(), err);
+                return;
+            }
+        };
+        achievements.set_hardcore(hardcore);
+        if hardcore {
+            self.emulator.cheats_mut().set_suspended(true);
+        }
+        self.osd.show(format!(
+            "{} achievements{}",
+            achievements.achievements().len(),
+            if hardcore { ", hardcore" } else { "" }
+        ));
+        self.achievements = Some(achievements);
+    }
+
+    #[cfg(feature = "rcheevos")]
+    pub fn achievements(&self) -> Option<&Achievements> {
+        self.achievements.as_ref()
+    }
+
+    // For the ones the site says the player already has, and the like.
+    #[cfg(feature = "rcheevos")]
+    pub fn achievements_mut(&mut self) -> Option<&mut Achievements> {
+        self.achievements.as_mut()
+    }
+
+    // What's happened since the last call.
+    #[cfg(feature = "rcheevos")]
+    pub fn take_achievement_events(&mut self) -> Vec<AchievementEvent> {
+        std::mem::take(&mut self.achievement_events)
+    }
+
+    #[cfg(feature = "rcheevos")]
+    fn is_hardcore(&self) -> bool {
+        self.achievements
+            .as_ref()
+            .is_some_and(|achievements| achievements.hardcore())
+    }
+
+    #[cfg(not(feature = "rcheevos"))]
+    fn is_hardcore(&self) -> bool {
+        false
+    }
+
+    // After a reset, a loaded state or a rewind.
+    fn reset_watchers(&mut self) {
+        #[cfg(feature = "rcheevos")]
+        if let Some(achievements) = self.achievements.as_mut() {
+            achievements.reset();
+        }
+        if let Some(autosplit) = self.autosplit.as_mut() {
+            autosplit.forget();
+        }
+    }
+
+    fn load_splits(&mut self, path: &Path, livesplit: Option<SocketAddr>) {
+        let loaded = fs::read_to_string(path)
+            .map_err(|err| err.to_string())
+            .and_then(|text| Autosplitter::from_toml(&text).map_err(|err| err.to_string()));
+        match loaded {
+            Ok(autosplit) => self.autosplit = Some(autosplit),
+            Err(err) => {
+                eprintln!("can't read {}: {}", path.display(), err);
+                return;
+            }
+        }
+        if let Some(addr) = livesplit {
+            match LiveSplit::connect(addr, self.frame_rate) {
+                Ok(livesplit) => self.livesplit = Some(livesplit),
+                Err(err) => eprintln!("can't reach LiveSplit on {}: {}", addr, err),
+            }
+        }
+    }
+
+    pub fn autosplit(&self) -> Option<&Autosplitter> {
+        self.autosplit.as_ref()
+    }
+
+    // What's happened since the last call.
+    pub fn take_split_events(&mut self) -> Vec<SplitEvent> {
+        std::mem::take(&mut self.split_events)
+    }
+
+    fn check_splits(&mut self) {
+        let Some(autosplit) = self.autosplit.as_mut() else {
+            return;
+        };
+        let emulator = &mut self.emulator;
+        let events = autosplit.do_frame(|addr| emulator.read_memory(MemorySpace::Cpu, addr));
+        for &event in &events {
+            let time = |frames| format_time(frame_time(frames, self.frame_rate));
+            self.osd.show(match event {
+                SplitEvent::Started => "Timer started".to_string(),
+                SplitEvent::Split { index, frames } => {
+                    format!("{}: {}", autosplit.splits()[index].name, time(frames))
+                }
+                SplitEvent::Finished { frames } => format!("Finished: {}", time(frames)),
+                SplitEvent::Reset => "Timer reset".to_string(),
+            });
+            if let Some(livesplit) = self.livesplit.as_mut() {
+                if let Err(err) = livesplit.send(event) {
+                    eprintln!("lost LiveSplit: {}", err);
+                    self.livesplit = None;
+                }
+            }
+        }
+        self.split_events.extend(events);
+    }
+
+    #[cfg(feature = "rcheevos")]
+    fn check_achievements(&mut self) {
+        let Some(achievements) = self.achievements.as_mut() else {
+            return;
+        };
+        let emulator = &mut self.emulator;
+        let events = achievements.do_frame(|addr| emulator.read_memory(MemorySpace::Cpu, addr));
+        for event in &events {
+            let message = match *event {
+                AchievementEvent::Unlocked { id } => achievements.achievement(id).map(|cheevo| {
+                    format!(
+                        "Achievement unlocked: {} ({} points)",
+                        cheevo.title, cheevo.points
+                    )
+                }),
+                AchievementEvent::LeaderboardStarted { id } => achievements
+                    .leaderboard(id)
+                    .map(|board| format!("{} started", board.title)),
+                AchievementEvent::LeaderboardCanceled { id } => achievements
+                    .leaderboard(id)
+                    .map(|board| format!("{} failed", board.title)),
+                AchievementEvent::LeaderboardSubmitted { id, value } => achievements
+                    .leaderboard(id)
+                    .map(|board| format!("{}: {}", board.title, value)),
+            };
+            if let Some(message) = message {
+                self.osd.show(message);
+            }
+        }
+        self.achievement_events.extend(events);
+    }
+
+    // Front ends send every key here: hotkeys are picked out and the rest
+    // goes to the game. Returns the action a press started, so the front
+    // end can see to Quit.
+    pub fn key_down(&mut self, key: &str) -> Option<Action> {
+        if !self.hotkeys.is_bound(key) {
+            self.input.keyboard.key_down(key);
+            return None;
+        }
+        let action = self.hotkeys.key_down(key)?;
+        self.perform(action, true);
+        Some(action)
+    }
+
+    pub fn key_up(&mut self, key: &str) {
+        match self.hotkeys.key_up(key) {
+            Some(action) => self.perform(action, false),
+            None => self.input.keyboard.key_up(key),
+        }
+    }
+
+    // For when the window loses focus and releases will never come.
+    pub fn release_keys(&mut self) {
+        self.flush_battery();
+        self.input.keyboard.release_all();
+        for action in self.hotkeys.release_all() {
+            self.perform(action, false);
+        }
+    }
+
+    // `pressed` is false when the key of a held action is let go; other
+    // actions only happen on the press.
+    pub fn perform(&mut self, action: Action, pressed: bool) {
+        // before the game is stopped or thrown back, or the player walks
+        // away, make sure its save is on disk
+        let stops = [
+            Action::Quit,
+            Action::Reset,
+            Action::Pause,
+            Action::LoadState,
+        ];
+        if pressed && stops.contains(&action) {
+            self.flush_battery();
+        }
+        // anything that puts one machine somewhere the other isn't
+        let diverges = [
+            Action::FastForward,
+            Action::Rewind,
+            Action::Pause,
+            Action::FrameAdvance,
+            Action::SpeedDown,
+            Action::SpeedUp,
+            Action::LoadState,
+            Action::ToggleCheats,
+        ];
+        if self.netplay.is_some() && diverges.contains(&action) {
+            if pressed {
+                self.osd.show("Not during netplay");
+            }
+            return;
+        }
+        // anything that helps
+        let helps = [
+            Action::Rewind,
+            Action::FrameAdvance,
+            Action::SpeedDown,
+            Action::LoadState,
+            Action::ToggleCheats,
+        ];
+        if self.is_hardcore() && helps.contains(&action) {
+            if pressed {
+                self.osd.show("Not in hardcore mode");
+            }
+            return;
+        }
+        match action {
+            Action::FastForward => self.fast_forward = pressed,
+            Action::Rewind => self.rewinding = pressed,
+            _ if !pressed => {}
+            // the front end's to carry out
+            Action::Quit | Action::Fullscreen => {}
+            // there's no game yet
+            _ if self.launcher.is_some() => {}
+            // both machines reset on the same frame
+            Action::Reset if self.netplay.is_some() => {
+                self.netplay_commands |= MovieCommand::SOFT_RESET;
+                self.osd.show("Reset");
+            }
+            Action::Reset => {
+                self.emulator.reset();
+                self.reset_watchers();
+                self.osd.show("Reset");
+            }
+            Action::Pause => self.paused = !self.paused,
+            Action::FrameAdvance => {
+                self.advance = self.paused;
+                self.paused = true;
+            }
+            Action::SpeedDown => {
+                let slower = SPEEDS.iter().rev().find(|&&speed| speed < self.speed);
+                self.speed = *slower.unwrap_or(&SPEEDS[0]);
+                self.show_speed();
+            }
+            Action::SpeedUp => {
+                let faster = SPEEDS.iter().find(|&&speed| speed > self.speed);
+                self.speed = *faster.unwrap_or(&self.speed);
+                self.show_speed();
+            }
+            Action::SaveState => match self.save_slot(self.slot) {
+                Ok(()) => self.osd.show(format!("State {} saved", self.slot)),
+                Err(err) => {
+                    eprintln!("can't save state: {}", err);
+                    self.osd.show("Can't save state");
+                }
+            },
+            Action::LoadState => match self.load_slot(self.slot) {
+                Ok(()) => self.osd.show(format!("State {} loaded", self.slot)),
+                Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                    self.osd.show(format!("No state {}", self.slot))
+                }
+                Err(err) => {
+                    eprintln!("can't load state: {}", err);
+                    self.osd.show("Can't load state");
+                }
+            },
+            Action::NextSlot => self.select_slot((self.slot + 1) % SLOTS),
+            Action::PreviousSlot => self.select_slot((self.slot + SLOTS - 1) % SLOTS),
+            Action::Screenshot if self.screenshot == ScreenshotStage::Filtered => {
+                self.capture = true
+            }
+            Action::Screenshot => match self.save_screenshot() {
+                Ok(_) => self.osd.show("Screenshot saved"),
+                Err(err) => {
+                    eprintln!("can't save screenshot: {}", err);
+                    self.osd.show("Can't save screenshot");
+                }
+            },
+            Action::Record => self.toggle_recording(),
+            Action::SaveClip => self.save_clip(),
+            Action::DumpTrace => self.dump_trace(),
+            Action::ToggleInputDisplay => self.show_input = !self.show_input,
+            Action::ToggleCheats => {
+                let cheats = self.emulator.cheats_mut();
+                let suspended = !cheats.is_suspended();
+                cheats.set_suspended(suspended);
+                self.osd
+                    .show(if suspended { "Cheats off" } else { "Cheats on" });
+            }
+            Action::InsertCoin if !self.emulator.is_vs() => {}
+            Action::InsertCoin if self.netplay.is_some() => {
+                self.netplay_commands |= MovieCommand::VS_COIN;
+                self.osd.show("Coin");
+            }
+            Action::InsertCoin => {
+                self.emulator.insert_coin(0);
+                self.osd.show("Coin");
+            }
+            Action::ToggleChannel(channel) => {
+                let mixer = self.emulator.mixer_mut();
+                mixer.toggle_muted(channel);
+                let state = if mixer.is_muted(channel) {
+                    "muted"
+                } else {
+                    "on"
+                };
+                self.osd.show(format!("{} {}", channel, state));
+            }
+        }
+    }
+
+    // Where a slot's state goes: named by the ROM's checksum, so renaming
+    // the file keeps its states and another dump doesn't load them.
+    pub fn slot_path(&self, slot: u8) -> PathBuf {
+        self.states_dir
+            .join(format!("{:08X}-{}.state", self.emulator.crc32(), slot))
+    }
+
+    pub fn slot(&self) -> u8 {
+        self.slot
+    }
+
+    pub fn select_slot(&mut self, slot: u8) {
+        self.slot = slot % SLOTS;
+        let state = if self.slot_path(self.slot).exists() {
+            ""
+        } else {
+            " (empty)"
+        };
+        self.osd.show(format!("Slot {}{}", self.slot, state));
+    }
+
+    pub fn save_slot(&self, slot: u8) -> io::Result<()> {
+        self.emulator.save_state_to(&self.slot_path(slot))
+    }
+
+    pub fn load_slot(&mut self, slot: u8) -> io::Result<()> {
+        self.emulator.load_state_from(&self.slot_path(slot))?;
+        self.reset_watchers();
+        Ok(())
+    }
+
+    fn show_speed(&mut self) {
+        self.osd
+            .show(format!("Speed {}%", (self.speed * 100.0).round()));
+    }
+
+    // A message for the OSD, from the front end.
+    pub fn show_message(&mut self, text: impl Into<String>) {
+        self.osd.show(text);
+    }
+
+    pub fn osd(&self) -> &Osd {
+        &self.osd
+    }
+
+    // What's going on right now, for the top of the screen.
+    fn status(&self) -> Option<String> {
+        let mut status = Vec::new();
+        if let Some(netplay) = self.netplay.as_ref() {
+            status.push(format!("Netplay P{}", netplay.player() + 1));
+        } else if self.rewinding {
+            status.push("Rewinding".to_string());
+        } else if self.paused {
+            status.push("Paused".to_string());
+        } else if self.fast_forward {
+            status.push(format!("Fast forward {}x", self.fast_forward_speed));
+        }
+        if self.is_recording() {
+            status.push("Recording".to_string());
+        }
+        (!status.is_empty()).then(|| status.join("  "))
+    }
+
+    // See Emulator::memory_port; kept live while paused too.
+    pub fn memory_port(&mut self) -> MemoryPort {
+        self.emulator.memory_port()
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    pub fn speed(&self) -> f64 {
+        if self.fast_forward {
+            self.fast_forward_speed
+        } else {
+            self.speed
+        }
+    }
+
+    pub fn set_speed(&mut self, speed: f64) {
+        self.speed = speed;
+    }
+
+    pub fn pacing(&self) -> Pacing {
+        self.pacing
+    }
+
+    // Whether the next frame should run now. Front ends that can't block
+    // ask this and come back at deadline().
+    pub fn is_due(&self, now: Instant) -> bool {
+        match self.pacing {
+            Pacing::Timer => self.pacer.is_due(now),
+            Pacing::Vsync => true,
+            Pacing::Audio => match self.sink.queued_samples() {
+                // no device, or an underrun: go by the clock
+                0 => self.pacer.is_due(now),
+                queued => queued <= self.rate_control.target_queued(),
+            },
+        }
+    }
+
+    pub fn deadline(&self, now: Instant) -> Instant {
+        match self.pacing {
+            Pacing::Timer => self.pacer.deadline(),
+            Pacing::Vsync => now,
+            Pacing::Audio => self.pacer.deadline().min(now + AUDIO_POLL),
+        }
+    }
+
+    // After each frame is shown.
+    pub fn advance(&mut self, now: Instant) {
+        self.pacer.advance(now);
+    }
+
+    // Blocks until the next frame is due, for front ends with a loop of
+    // their own.
+    pub fn wait(&mut self) {
+        loop {
+            let now = Instant::now();
+            if self.is_due(now) {
+                break;
+            }
+            sleep_until(self.deadline(now));
+        }
+        self.advance(Instant::now());
+    }
+
+    // Called once per host frame. Latches input, emulates as many frames as
+    // the speed calls for (none, some of the time, in slow motion) and
+    // queues the last one's audio. Rewind steps back instead, and a paused
+    // session shows the same frame again. The OSD is drawn over what's
+    // returned; screenshots and recordings go without it.
+    pub fn run_frame(&mut self) -> &Frame {
+        self.serve_remote();
+        if let Some(launcher) = self.launcher.as_mut() {
+            if let Some(path) = launcher.update(self.input.buttons(0)) {
+                if let Err(err) = self.load_rom(&path) {
+                    eprintln!("can't load {}: {}", path.display(), err);
+                    self.osd.show("Can't load ROM");
+                }
+            }
+        }
+        self.osd.tick();
+        if let Some(launcher) = self.launcher.as_ref() {
+            launcher.draw(&mut self.display);
+            self.osd.draw(&mut self.display);
+            return &self.display;
+        }
+
+        if self.watcher.as_mut().is_some_and(RomWatcher::poll) {
+            self.reload_rom();
+        }
+        if self.crash_dumps.is_some() {
+            if let Err(panic) = panic::catch_unwind(AssertUnwindSafe(|| self.emulate())) {
+                let dir = self.crash_dumps.take().unwrap_or_default();
+                crash::dump_and_resume(&dir, &mut self.emulator, panic);
+            }
+        } else {
+            self.emulate();
+        }
+        self.measure();
+        self.osd.set_status(self.status());
+        let osd = self.show_osd && !self.osd.is_empty();
+        if !osd && !self.show_input && !self.scripts.has_drawings() {
+            return self.emulator.frame();
+        }
+        self.display.clone_from(self.emulator.frame());
+        self.scripts.draw(&mut self.display);
+        if self.show_input {
+            input_display::draw(&mut self.display, &self.emulator.input_state());
+        }
+        if osd {
+            self.osd.draw(&mut self.display);
+        }
+        &self.display
+    }
+
+    fn measure(&mut self) {
+        let to_time = |samples: usize| {
+            Duration::from_secs_f64(samples as f64 / self.sink.sample_rate() as f64)
+        };
+        let (queued, target) = (
+            to_time(self.sink.queued_samples()),
+            to_time(self.rate_control.target_queued()),
+        );
+        let now = Instant::now();
+        let audio = AudioStats {
+            queued,
+            target,
+            underruns: self.sink.underruns(),
+        };
+        if self.meter.frame(now, self.emulator.frame_count(), audio)
+            && self.show_stats == StatsDisplay::Osd
+        {
+            self.osd.set_corner(Some(self.meter.stats().to_string()));
+        }
+    }
+
+    pub fn stats(&self) -> Stats {
+        self.meter.stats()
+    }
+
+    // What to put after the window title, for front ends that have one;
+    // changes twice a second at most.
+    pub fn title_stats(&self) -> Option<String> {
+        (self.show_stats == StatsDisplay::Title).then(|| self.stats().to_string())
+    }
+
+    // The ROM file playing, if it's known.
+    pub fn rom(&self) -> Option<&Path> {
+        self.rom.as_deref()
+    }
+
+    pub fn in_launcher(&self) -> bool {
+        self.launcher.is_some()
+    }
+
+    fn recent(&self) -> RecentRoms {
+        let Some(file) = self.recent_file.as_ref() else {
+            return RecentRoms::default();
+        };
+        RecentRoms::load(file).unwrap_or_else(|err| {
+            eprintln!("can't read {}: {}", file.display(), err);
+            RecentRoms::default()
+        })
+    }
+
+    // Puts the ROM at the top of the recent list.
+    fn remember(&mut self, path: &Path) {
+        let Some(file) = self.recent_file.as_ref() else {
+            return;
+        };
+        let path = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        let mut recent = self.recent();
+        recent.played(&path, &self.game, self.emulator.crc32());
+        if let Err(err) = recent.save(file) {
+            eprintln!("can't save {}: {}", file.display(), err);
+        }
+    }
+
+    fn dump_trace(&mut self) {
+        let Some(trace) = self.emulator.trace_mut() else {
+            self.osd.show("Not tracing");
+            return;
+        };
+        match trace.dump() {
+            Ok(lines) => self.osd.show(format!("Trace: {} lines written", lines)),
+            Err(err) => {
+                eprintln!("can't write trace: {}", err);
+                self.osd.show("Can't write trace");
+            }
+        }
+    }
+
+    // Answers what remote clients asked since the last frame, then tells
+    // subscribers if the pause changed, however it came about.
+    fn serve_remote(&mut self) {
+        let Some(remote) = self.remote.take() else {
+            return;
+        };
+        for call in remote.poll() {
+            let outcome = self.answer(&call.request);
+            remote.reply(call, outcome);
+        }
+        if self.paused != self.remote_paused {
+            self.remote_paused = self.paused;
+            let paused = Json::from(self.paused);
+            remote.notify(Event::Pause, Json::object([("paused", paused)]));
+        }
+        self.remote = Some(remote);
+    }
+
+    fn answer(&mut self, request: &Request) -> Result<Json, RpcError> {
+        if self.launcher.is_some() && *request != Request::Status {
+            return Err(RpcError::new(NO_GAME, "no game is loaded"));
+        }
+        Ok(match *request {
+            Request::Status => Json::object([
+                ("frame", Json::from(self.emulator.frame_count())),
+                ("paused", Json::from(self.paused)),
+                (
+                    "rom",
+                    Json::from(self.rom.as_ref().map(|rom| rom.display().to_string())),
+                ),
+            ]),
+            Request::Frame => Json::object([
+                ("width", Json::from(Frame::WIDTH as u64)),
+                ("height", Json::from(Frame::HEIGHT as u64)),
+                (
+                    "rgb",
+                    Json::from(STANDARD.encode(&self.emulator.frame().data)),
+                ),
+            ]),
+            Request::Screenshot => {
+                let png = self.emulator.frame().to_png();
+                Json::object([("png", Json::from(STANDARD.encode(png)))])
+            }
+            Request::Pause => {
+                self.flush_battery();
+                self.paused = true;
+                Json::Null
+            }
+            Request::Resume => {
+                self.paused = false;
+                Json::Null
+            }
+            Request::Step { frames } => {
+                self.paused = true;
+                self.emulate_frames(frames);
+                Json::object([("frame", Json::from(self.emulator.frame_count()))])
+            }
+            Request::Reset => {
+                self.emulator.reset();
+                self.reset_watchers();
+                Json::Null
+            }
+            Request::ReadMemory { space, addr, len } => {
+                let bytes = (0..len)
+                    .map(|i| {
+                        let addr = addr.wrapping_add(i as u16);
+                        self.emulator.read_memory(space, addr) as u64
+                    })
+                    .collect::<Vec<_>>();
+                Json::from(bytes)
+            }
+            Request::WriteMemory {
+                space,
+                addr,
+                ref values,
+            } => {
+                for (i, &value) in values.iter().enumerate() {
+                    self.emulator
+                        .write_memory(space, addr.wrapping_add(i as u16), value);
+                }
+                Json::Null
+            }
+            Request::SetInput { player, buttons } => {
+                self.remote_buttons[player] = buttons;
+                Json::Null
+            }
+        })
+    }
+
+    fn emulate(&mut self) {
+        if self.netplay.is_some() {
+            self.emulate_netplay();
+            return;
+        }
+        if self.rewinding {
+            self.step_back();
+            return;
+        }
+
+        let frames = if self.paused {
+            std::mem::take(&mut self.advance) as u32
+        } else {
+            let owed = match self.pacing {
+                Pacing::Vsync => self.refresh.tick(Instant::now()),
+                _ => 1.0,
+            };
+            self.credit += self.speed() * owed;
+            let frames = self.credit.floor();
+            self.credit -= frames;
+            frames as u32
+        };
+        if frames == 0 {
+            self.emulator.sync_memory();
+        }
+        self.emulate_frames(frames);
+    }
+
+    // Runs frames with the input held now, queueing the last one's audio.
+    fn emulate_frames(&mut self, frames: u32) {
+        self.input.apply(&mut self.emulator);
+        for (player, buttons) in self.remote_buttons.iter().enumerate() {
+            if let Some(buttons) = *buttons {
+                self.emulator.set_buttons(player, buttons);
+            }
+        }
+        for frame in 1..=frames {
+            self.scripts.before_frame(&mut self.emulator);
+            self.emulator.run_frame();
+            self.scripts.after_frame(&mut self.emulator);
+            self.finish_frame(frame == frames);
+        }
+        for message in self.scripts.take_messages() {
+            self.osd.show(message);
+        }
+        if self.scripts.take_pause() {
+            self.paused = true;
+        }
+    }
+
+    // Netplay runs at normal speed with nothing paused or rewound, and
+    // when Netplay::advance has to wait for the other player the picture
+    // holds. Scripts are left out, as they could poke one
+    // machine and not the other.
+    fn emulate_netplay(&mut self) {
+        let owed = match self.pacing {
+            Pacing::Vsync => self.refresh.tick(Instant::now()),
+            _ => 1.0,
+        };
+        self.credit += owed;
+        let frames = self.credit.floor() as u32;
+        self.credit -= frames as f64;
+        let Some(mut netplay) = self.netplay.take() else {
+            return;
+        };
+        let buttons = self.input.buttons(0);
+        let mut ran = 0;
+        let mut error = None;
+        while ran < frames {
+            let commands = std::mem::take(&mut self.netplay_commands);
+            match netplay.advance(&mut self.emulator, buttons, commands) {
+                Ok(Advance::Ran(_)) => {
+                    ran += 1;
+                    self.finish_frame(ran == frames);
+                }
+                Ok(Advance::Waiting) => break,
+                Err(err) => {
+                    error = Some(err);
+                    break;
+                }
+            }
+        }
+        if ran == 0 {
+            self.emulator.sync_memory();
+        }
+        match error {
+            None => self.netplay = Some(netplay),
+            Some(err) => {
+                eprintln!("netplay stopped: {}", err);
+                self.osd.show(format!("Netplay stopped: {}", err));
+            }
+        }
+    }
+
+    // What follows every emulated frame; `last` is the one whose audio is
+    // played.
+    fn finish_frame(&mut self, last: bool) {
+        if last {
+            self.emulator
+                .push_audio(self.sink.as_mut(), &self.rate_control);
+        } else {
+            self.emulator.discard_audio();
+        }
+        self.record_frame();
+        self.record_rewind();
+        #[cfg(feature = "rcheevos")]
+        self.check_achievements();
+        self.check_splits();
+        if self.battery.is_mapped() {
+            if let Err(err) = self.save_battery() {
+                eprintln!("can't save {}: {}", self.battery.path().display(), err);
+            }
+        } else if self
+            .emulator
+            .frame_count()
+            .is_multiple_of(BATTERY_FLUSH_INTERVAL)
+        {
+            self.flush_battery();
+        }
+        if let Some(remote) = self.remote.as_ref() {
+            if remote.has_subscribers(Event::Frame) {
+                let frame = Json::from(self.emulator.frame_count());
+                remote.notify(Event::Frame, Json::object([("frame", frame)]));
+            }
+            if remote.has_subscribers(Event::Input) {
+                let input =
+                    remote::input_json(self.emulator.frame_count(), &self.emulator.input_state());
+                remote.notify(Event::Input, input);
+            }
+        }
+    }
+
+    fn record_rewind(&mut self) {
+        if self.emulator.frame_count().is_multiple_of(REWIND_INTERVAL) {
+            self.rewind.push(self.emulator.snapshot().into_bytes());
+        }
+    }
+
+    // States don't hold the picture, so a frame is run from the rewind
+    // point to have something to show.
+    fn step_back(&mut self) {
+        let Some(state) = self.rewind.pop() else {
+            return;
+        };
+        if self.emulator.restore(&Snapshot::from(state)).is_ok() {
+            self.reset_watchers();
+            self.emulator.run_frame();
+            self.emulator.discard_audio();
+            self.record_frame();
+        }
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recorder.is_some()
+    }
+
+    pub fn start_recording(&mut self, path: &Path) -> io::Result<()> {
+        self.stop_recording()?;
+        self.recorder = Some(VideoRecorder::start(
+            &self.ffmpeg,
+            path,
+            self.record_size,
+            self.frame_rate,
+            self.sink.sample_rate(),
+        )?);
+        Ok(())
+    }
+
+    // The finished file, if there was a recording.
+    pub fn stop_recording(&mut self) -> io::Result<Option<PathBuf>> {
+        self.recorder.take().map(VideoRecorder::finish).transpose()
+    }
+
+    fn toggle_recording(&mut self) {
+        let result = if self.is_recording() {
+            self.stop_recording().map(|path| {
+                path.map(|path| (format!("saved {}", path.display()), "Recording saved"))
+            })
+        } else {
+            numbered_path(
+                &self.recordings_dir,
+                &self.game,
+                self.emulator.frame_count(),
+                "mkv",
+            )
+            .and_then(|path| {
+                self.start_recording(&path)?;
+                Ok(Some((
+                    format!("recording to {}", path.display()),
+                    "Recording",
+                )))
+            })
+        };
+        match result {
+            Ok(Some((message, short))) => {
+                eprintln!("{}", message);
+                self.osd.show(short);
+            }
+            Ok(None) => {}
+            Err(err) => {
+                eprintln!("can't record: {}", err);
+                self.osd.show("Can't record");
+            }
+        }
+    }
+
+    // Encoding takes a moment, so the GIF is written on a thread of its
+    // own and play carries on.
+    fn save_clip(&mut self) {
+        if self.clip.is_empty() {
+            return;
+        }
+        let path = match numbered_path(
+            &self.screenshot_dir,
+            &self.game,
+            self.emulator.frame_count(),
+            "gif",
+        ) {
+            Ok(path) => path,
+            Err(err) => return eprintln!("can't save clip: {}", err),
+        };
+        self.osd.show("Saving clip");
+        let clip = self.clip.clip();
+        thread::spawn(move || match clip.save(&path) {
+            Ok(()) => eprintln!("saved {}", path.display()),
+            Err(err) => eprintln!("can't save clip {}: {}", path.display(), err),
+        });
+    }
+
+    // A write error, e.g. ffmpeg quitting, ends the recording rather than
+    // the game.
+    fn record_frame(&mut self) {
+        self.clip.push(self.emulator.frame());
+        let Some(recorder) = self.recorder.as_mut() else {
+            return;
+        };
+        if let Err(err) = recorder.push_frame(self.emulator.frame(), self.emulator.last_audio()) {
+            eprintln!("recording stopped: {}", err);
+            self.recorder = None;
+        }
+    }
+
+    pub fn save_screenshot(&self) -> io::Result<PathBuf> {
+        let path = numbered_path(
+            &self.screenshot_dir,
+            &self.game,
+            self.emulator.frame_count(),
+            "png",
+        )?;
+        fs::write(&path, self.emulator.frame().to_png())?;
+        Ok(path)
+    }
+
+    // Whether a Filtered screenshot was asked for since the last call. The
+    // front end reads back what it drew and hands it to save_capture.
+    pub fn take_capture(&mut self) -> bool {
+        std::mem::take(&mut self.capture)
+    }
+
+    pub fn save_capture(&mut self, width: u32, height: u32, rgb: &[u8]) -> io::Result<PathBuf> {
+        let path = numbered_path(
+            &self.screenshot_dir,
+            &self.game,
+            self.emulator.frame_count(),
+            "png",
+        )?;
+        fs::write(&path, encode_png(width, height, rgb))?;
+        self.osd.show("Screenshot saved");
+        Ok(path)
+    }
+
+    fn battery_path(&self) -> PathBuf {
+        self.saves_dir.join(format!("{}.sav", self.game))
+    }
+
+    // Reads the game's .sav, if its cartridge has a battery and there is
+    // one.
+    fn load_battery(&mut self) -> io::Result<()> {
+        self.battery = BatteryFile::new(self.battery_path(), self.battery.backing());
+        if self.emulator.battery_ram().is_none() {
+            return Ok(());
+        }
+        // nothing to write until the game saves something
+        if let Some(data) = self.battery.read()? {
+            self.emulator.load_battery_ram(&data);
+        }
+        self.battery
+            .loaded(self.emulator.battery_ram().unwrap_or(&[]));
+        Ok(())
+    }
+
+    // Whether the game has changed its battery RAM since it was last
+    // read or written.
+    pub fn battery_dirty(&self) -> bool {
+        self.emulator
+            .battery_ram()
+            .is_some_and(|ram| self.battery.is_dirty(ram))
+    }
+
+    // Writes battery RAM if it changed; see battery.rs.
+    pub fn save_battery(&mut self) -> io::Result<()> {
+        if !self.keep_battery {
+            return Ok(());
+        }
+        match self.emulator.battery_ram() {
+            Some(ram) => self.battery.write(ram),
+            None => Ok(()),
+        }
+    }
+
+    fn flush_battery(&mut self) {
+        if let Err(err) = self.save_battery().and_then(|()| self.battery.flush()) {
+            eprintln!("can't save {}: {}", self.battery.path().display(), err);
+        }
+    }
+
+    fn cheats_path(&self) -> PathBuf {
+        self.cheats_dir.join(format!("{}.toml", self.game))
+    }
+
+    // The game's saved cheats join any it already has, e.g. from the
+    // command line.
+    fn load_cheats(&mut self) {
+        if !self.emulator.has_cartridge() {
+            return;
+        }
+        match Cheats::load(&self.cheats_path()) {
+            Ok(cheats) => self.emulator.cheats_mut().merge(cheats),
+            Err(err) => eprintln!("can't read {}: {}", self.cheats_path().display(), err),
+        }
+    }
+
+    // Changes to the list are written straight away.
+    pub fn save_cheats(&self) -> io::Result<()> {
+        self.emulator.cheats().save(&self.cheats_path())
+    }
+
+    fn store_cheats(&mut self) {
+        if let Err(err) = self.save_cheats() {
+            eprintln!("can't save {}: {}", self.cheats_path().display(), err);
+            self.osd.show("Can't save cheats");
+        }
+    }
+
+    pub fn add_cheat(&mut self, cheat: Cheat) {
+        let code = cheat.code;
+        self.emulator.cheats_mut().add(cheat);
+        self.store_cheats();
+        self.osd.show(format!("Cheat {} on", code));
+    }
+
+    pub fn set_cheat_enabled(&mut self, index: usize, enabled: bool) {
+        if self.emulator.cheats_mut().set_enabled(index, enabled) {
+            self.store_cheats();
+        }
+    }
+
+    pub fn set_cheat_group_enabled(&mut self, group: &str, enabled: bool) {
+        if self.emulator.cheats_mut().set_group_enabled(group, enabled) > 0 {
+            self.store_cheats();
+            let state = if enabled { "on" } else { "off" };
+            self.osd.show(format!("{} {}", group, state));
+        }
+    }
+
+    pub fn remove_cheat(&mut self, index: usize) {
+        if self.emulator.cheats_mut().remove(index).is_some() {
+            self.store_cheats();
+        }
+    }
+
+    // Swaps in the ROM at `path`, e.g. one dropped on the window. The old
+    // game's battery RAM is saved and its recording finished, then the
+    // console powers on with the new cartridge and that game's .sav.
+    // Nothing changes if the ROM can't be used.
+    pub fn load_rom(&mut self, path: &Path) -> io::Result<()> {
+        let rom = fs::read(path)?;
+        let mut cartridge = Cartridge::from_rom(rom)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        if let Some(name) = path.file_name() {
+            cartridge.guess_region(&name.to_string_lossy());
+        }
+        self.flush_battery();
+        self.emulator
+            .insert_cartridge(cartridge)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        match self.stop_recording() {
+            Ok(Some(path)) => eprintln!("saved {}", path.display()),
+            Ok(None) => {}
+            Err(err) => eprintln!("can't finish recording: {}", err),
+        }
+
+        self.game = path.file_stem().map_or("nes".to_string(), |stem| {
+            stem.to_string_lossy().into_owned()
+        });
+        self.paused = false;
+        self.advance = false;
+        self.credit = 0.0;
+        self.rewind.clear();
+        // a PAL game after an NTSC one runs at 50Hz
+        self.frame_rate = self.emulator.frame_rate();
+        self.pacer = FramePacer::new(self.frame_rate);
+        self.refresh = RefreshClock::new(self.frame_rate);
+        self.meter = StatsMeter::new(self.frame_rate);
+        self.clip = ClipRecorder::new(self.emulator.palette(), self.clip_seconds, self.frame_rate);
+        if let Err(err) = self.load_battery() {
+            eprintln!("can't read {}: {}", self.battery_path().display(), err);
+        }
+        self.load_cheats();
+        // the set was for the old game
+        #[cfg(feature = "rcheevos")]
+        {
+            self.achievements = None;
+        }
+        self.launcher = None;
+        self.remember(path);
+        self.watcher = self.reload_mode.map(|_| RomWatcher::new(path));
+        self.rom = Some(path.to_path_buf());
+        self.osd.show(format!("Loaded {}", self.game));
+        Ok(())
+    }
+
+    // A new build of the ROM that's in; see watch.rs. Not in netplay,
+    // where the other side would still have the old one.
+    pub fn reload_rom(&mut self) {
+        let (Some(mode), Some(path)) = (self.reload_mode, self.rom.clone()) else {
+            return;
+        };
+        if self.netplay.is_some() {
+            return;
+        }
+        let state = (mode == ReloadMode::Keep).then(|| self.emulator.save_state());
+        if let Err(err) = self.load_rom(&path) {
+            eprintln!("can't reload {}: {}", path.display(), err);
+            self.osd.show("Can't reload ROM");
+            return;
+        }
+        let kept = state.is_some_and(|state| self.emulator.load_state(&state).is_ok());
+        let symbols = self.emulator.debugger_mut().symbols_mut();
+        if !self.symbol_files.is_empty() {
+            symbols.clear();
+        }
+        for file in &self.symbol_files {
+            if let Err(err) = symbols.load(file) {
+                eprintln!("{}: {}", file.display(), err);
+            }
+        }
+        self.osd.show(match (mode, kept) {
+            (ReloadMode::Keep, false) => "Reloaded; the state didn't fit, so reset",
+            (ReloadMode::Keep, true) => "Reloaded, state kept",
+            (ReloadMode::Reset, _) => "Reloaded",
+        });
+    }
+}
+
+// Drops run while a panic unwinds too, so a crash in the emulator or a
+// front end still gets the save written.
+impl Drop for Session {
+    fn drop(&mut self) {
+        self.flush_battery();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::bus::Mem;
+    use crate::cheat::game_genie::GameGenie;
+    use crate::cheat::Code;
+    use crate::debugger::memory::MemorySpace;
+    use crate::emulator::test::counting_rom;
+    use crate::input::gamepad::{GamepadEvent, PadButton};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    // Tests run side by side, so each session gets its own states.
+    fn session() -> Session {
+        static SESSIONS: AtomicUsize = AtomicUsize::new(0);
+        let n = SESSIONS.fetch_add(1, Ordering::Relaxed);
+        let states_dir =
+            std::env::temp_dir().join(format!("nes-session-{}-{}", std::process::id(), n));
+        let emulator = Emulator::from_rom(&counting_rom()).unwrap();
+        Session::new(
+            emulator,
+            Options {
+                cheats_dir: states_dir.join("cheats"),
+                states_dir,
+                ..Options::default()
+            },
+            Box::new(NullSink::new(48_000)),
+        )
+    }
+
+    #[test]
+    fn test_refresh_clock_snaps_to_near_rates() {
+        let start = Instant::now();
+        let mut clock = RefreshClock::new(NTSC_FRAME_RATE);
+        for n in 0..100 {
+            let now = start + Duration::from_secs_f64(n as f64 / 60.0);
+            assert_eq!(clock.tick(now), 1.0);
+        }
+
+        let mut clock = RefreshClock::new(NTSC_FRAME_RATE);
+        let mut owed = 0.0;
+        for n in 0..100 {
+            owed = clock.tick(start + Duration::from_secs_f64(n as f64 / 144.0));
+        }
+        assert!((owed - NTSC_FRAME_RATE / 144.0).abs() < 0.01, "{}", owed);
+    }
+
+    #[test]
+    fn test_audio_pacing_follows_the_queue() {
+        struct Queue(usize);
+        impl AudioSink for Queue {
+            fn sample_rate(&self) -> u32 {
+                48_000
+            }
+            fn push_samples(&mut self, _samples: &[f32]) {}
+            fn queued_samples(&self) -> usize {
+                self.0
+            }
+        }
+        let session = |queued| {
+            let emulator = Emulator::from_rom(&counting_rom()).unwrap();
+            let options = Options {
+                pacing: Pacing::Audio,
+                audio_latency: Latency {
+                    queued: 1024,
+                    buffer: None,
+                },
+                ..Options::default()
+            };
+            Session::new(emulator, options, Box::new(Queue(queued)))
+        };
+        let now = Instant::now();
+        assert!(session(1000).is_due(now));
+        assert!(!session(1100).is_due(now));
+        // an empty queue falls back to the timer
+        let mut empty = session(0);
+        empty.advance(now);
+        assert!(!empty.is_due(now));
+        assert!(empty.deadline(now) <= now + AUDIO_POLL);
+    }
+
+    #[test]
+    fn test_hotkeys_drive_the_session() {
+        let mut session = session();
+        session.run_frame();
+
+        assert_eq!(session.key_down("P"), Some(Action::Pause));
+        session.run_frame();
+        assert_eq!(session.emulator.frame_count(), 1);
+        session.key_up("P");
+        session.key_down("P");
+        session.run_frame();
+        assert_eq!(session.emulator.frame_count(), 2);
+
+        assert_eq!(session.key_down("F5"), Some(Action::SaveState));
+        session.key_down("Tab");
+        session.run_frame();
+        assert_eq!(session.emulator.frame_count(), 6);
+        session.release_keys();
+        session.run_frame();
+        assert_eq!(session.emulator.frame_count(), 7);
+
+        session.key_down("F7");
+        assert_eq!(session.emulator.frame_count(), 2);
+        assert_eq!(session.emulator.cpu_mut().mem_read(0x00), 1);
+        fs::remove_dir_all(&session.states_dir).unwrap();
+
+        // hotkeys never reach the game
+        session
+            .input
+            .keyboard
+            .bindings
+            .bind("P", 0, JoypadButton::START);
+        session.key_down("P");
+        assert_eq!(session.input.buttons(0), JoypadButton::empty());
+        assert_eq!(session.key_down("Escape"), Some(Action::Quit));
+        assert_eq!(session.key_down("F11"), Some(Action::Fullscreen));
+    }
+
+    #[test]
+    fn test_speeds_and_frame_advance() {
+        let mut session = Session::new(
+            Emulator::from_rom(&counting_rom()).unwrap(),
+            Options {
+                speed: 0.5,
+                fast_forward_speed: 2.5,
+                ..Options::default()
+            },
+            Box::new(NullSink::new(48_000)),
+        );
+        let run = |session: &mut Session| {
+            let start = session.emulator.frame_count();
+            session.run_frame();
+            session.emulator.frame_count() - start
+        };
+        assert_eq!([run(&mut session), run(&mut session)], [0, 1]);
+
+        session.key_down("Tab");
+        assert_eq!(session.speed(), 2.5);
+        assert_eq!([run(&mut session), run(&mut session)], [2, 3]);
+        session.key_up("Tab");
+
+        session.key_down("F3");
+        assert_eq!(session.speed(), 0.75);
+        session.key_down("F2");
+        session.key_up("F2");
+        session.key_down("F2");
+        assert_eq!(session.speed(), 0.25);
+
+        // the first press pauses, the next ones step
+        session.key_down("N");
+        assert!(session.is_paused());
+        assert_eq!(run(&mut session), 0);
+        session.key_up("N");
+        session.key_down("N");
+        assert_eq!([run(&mut session), run(&mut session)], [1, 0]);
+    }
+
+    #[test]
+    fn test_rewind_steps_back_to_saved_points() {
+        let mut session = session();
+        for _ in 0..25 {
+            session.run_frame();
+        }
+        session.key_down("Backspace");
+        session.run_frame();
+        assert_eq!(session.emulator.frame_count(), 25);
+        session.run_frame();
+        assert_eq!(session.emulator.frame_count(), 23);
+        for _ in 0..20 {
+            session.run_frame();
+        }
+        // the oldest point is after frame 2
+        assert_eq!(session.emulator.frame_count(), 3);
+
+        session.key_up("Backspace");
+        session.run_frame();
+        assert_eq!(session.emulator.frame_count(), 4);
+    }
+
+    #[test]
+    fn test_screenshots_are_numbered_per_game() {
+        let dir = std::env::temp_dir().join(format!("nes-shots-test-{}", std::process::id()));
+        let mut session = session();
+        session.game = "smb".to_string();
+        session.screenshot_dir = dir.clone();
+        session.emulator.run_frame();
+
+        let first = session.save_screenshot().unwrap();
+        assert_eq!(first, dir.join("smb-001-frame1.png"));
+        fs::write(dir.join("smb-041-frame9.png"), b"").unwrap();
+        fs::write(dir.join("smb3-077-frame1.png"), b"").unwrap();
+        let next = session.save_capture(2, 1, &[0; 6]).unwrap();
+        assert_eq!(next, dir.join("smb-042-frame1.png"));
+        assert!(fs::read(&first).unwrap().starts_with(b"\x89PNG"));
+
+        session.screenshot = ScreenshotStage::Filtered;
+        session.perform(Action::Screenshot, true);
+        assert!(session.take_capture());
+        assert!(!session.take_capture());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_switching_roms_keeps_battery_saves() {
+        let dir = std::env::temp_dir().join(format!("nes-rom-switch-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let mut rom = counting_rom();
+        rom[6] |= 0b10;
+        fs::write(dir.join("zelda.nes"), &rom).unwrap();
+        fs::write(dir.join("plain.nes"), counting_rom()).unwrap();
+        fs::write(dir.join("junk.nes"), b"not a rom").unwrap();
+        let options = Options {
+            saves_dir: dir.join("saves"),
+            cheats_dir: dir.join("cheats"),
+            ..Options::default()
+        };
+        let mut session = Session::new(
+            Emulator::from_rom(&counting_rom()).unwrap(),
+            options,
+            Box::new(NullSink::new(48_000)),
+        );
+
+        session.load_rom(&dir.join("zelda.nes")).unwrap();
+        session.run_frame();
+        session.emulator.cpu_mut().mem_write(0x6000, 0x42);
+        session.load_rom(&dir.join("plain.nes")).unwrap();
+        assert_eq!(fs::read(dir.join("saves/zelda.sav")).unwrap()[0], 0x42);
+        assert_eq!(session.emulator.frame_count(), 0);
+        assert!(session.load_rom(&dir.join("junk.nes")).is_err());
+        assert!(session.load_rom(&dir.join("missing.nes")).is_err());
+
+        session.load_rom(&dir.join("zelda.nes")).unwrap();
+        assert_eq!(session.emulator.cpu_mut().mem_read(0x6000), 0x42);
+        session.emulator.cpu_mut().mem_write(0x6001, 0x43);
+        drop(session);
+        assert_eq!(fs::read(dir.join("saves/zelda.sav")).unwrap()[1], 0x43);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_hot_reload() {
+        let dir = std::env::temp_dir().join(format!("nes-hot-reload-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("homebrew.nes");
+        fs::write(&path, counting_rom()).unwrap();
+        let session = |mode| {
+            let options = Options {
+                rom: Some(path.clone()),
+                saves_dir: dir.join("saves"),
+                cheats_dir: dir.join("cheats"),
+                hot_reload: Some(mode),
+                ..Options::default()
+            };
+            let emulator = Emulator::from_rom(&counting_rom()).unwrap();
+            Session::new(emulator, options, Box::new(NullSink::new(48_000)))
+        };
+        // the next build counts NMIs in $01 instead
+        let mut next = counting_rom();
+        next[16 + 0x1001] = 0x01;
+
+        let mut kept = session(ReloadMode::Keep);
+        kept.emulator.run_frames(5);
+        fs::write(&path, &next).unwrap();
+        kept.reload_rom();
+        assert_eq!(kept.emulator.frame_count(), 5);
+        kept.emulator.run_frames(3);
+        assert_eq!(kept.emulator.cpu_mut().mem_read(0x00), 4);
+        assert_eq!(kept.emulator.cpu_mut().mem_read(0x01), 3);
+
+        let mut reset = session(ReloadMode::Reset);
+        reset.emulator.run_frames(5);
+        reset.reload_rom();
+        assert_eq!(reset.emulator.frame_count(), 0);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_cheats_are_kept_per_game() {
+        let dir = std::env::temp_dir().join(format!("nes-cheats-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("smb.nes"), counting_rom()).unwrap();
+        fs::write(dir.join("other.nes"), counting_rom()).unwrap();
+        let options = Options {
+            saves_dir: dir.join("saves"),
+            cheats_dir: dir.join("cheats"),
+            ..Options::default()
+        };
+        let mut session = Session::new(
+            Emulator::from_rom(&counting_rom()).unwrap(),
+            options,
+            Box::new(NullSink::new(48_000)),
+        );
+
+        session.load_rom(&dir.join("smb.nes")).unwrap();
+        let lives = Cheat {
+            name: "Infinite lives".to_string(),
+            ..Cheat::new("SXIOPO".parse::<GameGenie>().unwrap())
+        };
+        session.add_cheat(lives.clone());
+        session.add_cheat(Cheat {
+            group: "Start".to_string(),
+            ..Cheat::new(Code::Freeze("0010:2A".parse().unwrap()))
+        });
+        session.set_cheat_enabled(0, false);
+        session.load_rom(&dir.join("other.nes")).unwrap();
+        assert!(session.emulator.cheats().cheats().is_empty());
+
+        session.load_rom(&dir.join("smb.nes")).unwrap();
+        let cheats = session.emulator.cheats().cheats();
+        assert_eq!(cheats.len(), 2);
+        assert_eq!(
+            cheats[0],
+            Cheat {
+                enabled: false,
+                ..lives
+            }
+        );
+        session.run_frame();
+        assert_eq!(session.emulator.cpu_mut().mem_read(0x0010), 0x2A);
+        session.set_cheat_group_enabled("Start", false);
+        session.emulator.cpu_mut().mem_write(0x0010, 0);
+        session.run_frame();
+        assert_eq!(session.emulator.cpu_mut().mem_read(0x0010), 0);
+
+        session.set_cheat_enabled(0, true);
+        session.perform(Action::ToggleCheats, true);
+        assert_eq!(session.emulator.cpu_mut().mem_read(0x91D9), 0x00);
+        session.perform(Action::ToggleCheats, true);
+        assert_eq!(session.emulator.cpu_mut().mem_read(0x91D9), 0xAD);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_launcher_starts_a_recent_game() {
+        let dir = std::env::temp_dir().join(format!("nes-launch-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let rom = dir.join("counter.nes");
+        fs::write(&rom, counting_rom()).unwrap();
+        let recent_file = dir.join("recent.toml");
+        let mut recent = RecentRoms::default();
+        recent.played(&rom, "counter", 0);
+        recent.save(&recent_file).unwrap();
+
+        let options = Options {
+            recent_file: Some(recent_file.clone()),
+            states_dir: dir.join("states"),
+            cheats_dir: dir.join("cheats"),
+            ..Options::default()
+        };
+        let mut session = Session::new(Emulator::empty(), options, Box::new(NullSink::new(48_000)));
+        assert!(session.in_launcher());
+        session.run_frame();
+        // hotkeys other than quit wait for a game
+        session.perform(Action::SaveState, true);
+        assert!(!dir.join("states").exists());
+
+        session.key_down("Return");
+        session.run_frame();
+        assert!(!session.in_launcher());
+        assert_eq!(session.game, "counter");
+        // playing from that frame on
+        assert_eq!(session.emulator.frame_count(), 1);
+        let recent = RecentRoms::load(&recent_file).unwrap();
+        assert_eq!(recent.roms[0].crc32, session.emulator.crc32());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_osd_is_drawn_over_the_shown_frame_only() {
+        let mut session = session();
+        let plain = session.run_frame().clone();
+        assert!(session.osd().is_empty());
+
+        session.perform(Action::SaveState, true);
+        session.perform(Action::Pause, true);
+        let shown = session.run_frame().clone();
+        assert_eq!(
+            session.osd().messages().collect::<Vec<_>>(),
+            ["State 0 saved"]
+        );
+        fs::remove_dir_all(&session.states_dir).unwrap();
+        assert_ne!(shown, plain);
+        // the emulator's own frame, which screenshots use, is untouched
+        assert_eq!(session.emulator.frame(), &plain);
+
+        let mut quiet = Session::new(
+            Emulator::from_rom(&counting_rom()).unwrap(),
+            Options {
+                osd: false,
+                ..Options::default()
+            },
+            Box::new(NullSink::new(48_000)),
+        );
+        quiet.perform(Action::Pause, true);
+        let frame = quiet.run_frame().clone();
+        assert_eq!(&frame, quiet.emulator.frame());
+    }
+
+    #[test]
+    fn test_battery_ram_is_flushed_while_playing() {
+        let dir = std::env::temp_dir().join(format!("nes-battery-{}", std::process::id()));
+        let mut rom = counting_rom();
+        rom[6] |= 0b10;
+        let options = || Options {
+            saves_dir: dir.clone(),
+            game: "dw".to_string(),
+            ..Options::default()
+        };
+        let sink = || Box::new(NullSink::new(48_000));
+        let sav = dir.join("dw.sav");
+        let mut session = Session::new(Emulator::from_rom(&rom).unwrap(), options(), sink());
+        for _ in 0..BATTERY_FLUSH_INTERVAL {
+            session.run_frame();
+        }
+        // untouched RAM isn't a save
+        assert!(!sav.exists());
+
+        session.emulator.cpu_mut().mem_write(0x6000, 1);
+        assert!(session.battery_dirty());
+        for _ in 0..BATTERY_FLUSH_INTERVAL {
+            session.run_frame();
+        }
+        assert!(!session.battery_dirty());
+        assert_eq!(fs::read(&sav).unwrap()[0], 1);
+
+        session.emulator.cpu_mut().mem_write(0x6000, 2);
+        session.perform(Action::Pause, true);
+        assert_eq!(fs::read(&sav).unwrap()[0], 2);
+        assert!(!dir.join("dw.sav.part").exists());
+
+        // a crash unwinds through the session
+        let mut session = Session::new(Emulator::from_rom(&rom).unwrap(), options(), sink());
+        assert_eq!(session.emulator.cpu_mut().mem_read(0x6000), 2);
+        let crashed = std::panic::catch_unwind(std::panic::AssertUnwindSafe(move || {
+            session.emulator.cpu_mut().mem_write(0x6000, 3);
+            panic!("mapper bug");
+        }));
+        assert!(crashed.is_err());
+        assert_eq!(fs::read(&sav).unwrap()[0], 3);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_mapped_battery_ram_is_in_the_file_a_frame_later() {
+        let dir = std::env::temp_dir().join(format!("nes-mapped-{}", std::process::id()));
+        let mut rom = counting_rom();
+        rom[6] |= 0b10;
+        let options = Options {
+            saves_dir: dir.clone(),
+            battery: BatteryBacking::Mapped,
+            game: "dw".to_string(),
+            ..Options::default()
+        };
+        let sink = Box::new(NullSink::new(48_000));
+        let mut session = Session::new(Emulator::from_rom(&rom).unwrap(), options, sink);
+        session.run_frame();
+        assert!(!dir.join("dw.sav").exists());
+
+        session.emulator.cpu_mut().mem_write(0x6000, 7);
+        session.run_frame();
+        assert!(!session.battery_dirty());
+        assert_eq!(fs::read(dir.join("dw.sav")).unwrap()[0], 7);
+        drop(session);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_state_slots_are_files_per_rom() {
+        let mut session = session();
+        session.run_frame();
+        session.perform(Action::SaveState, true);
+        session.perform(Action::PreviousSlot, true);
+        assert_eq!(session.slot(), 9);
+        assert_eq!(session.osd().messages().last(), Some("Slot 9 (empty)"));
+        session.run_frame();
+        session.perform(Action::SaveState, true);
+
+        session.perform(Action::NextSlot, true);
+        assert_eq!(session.osd().messages().last(), Some("Slot 0"));
+        session.perform(Action::LoadState, true);
+        assert_eq!(session.emulator.frame_count(), 1);
+        session.load_slot(9).unwrap();
+        assert_eq!(session.emulator.frame_count(), 2);
+
+        let name = format!("{:08X}-9.state", session.emulator.crc32());
+        assert_eq!(session.slot_path(9), session.states_dir.join(name));
+        assert_eq!(
+            session.load_slot(5).unwrap_err().kind(),
+            io::ErrorKind::NotFound
+        );
+        session.perform(Action::LoadState, true);
+        fs::remove_dir_all(&session.states_dir).unwrap();
+    }
+
+    #[test]
+    #[cfg(feature = "rcheevos")]
+    fn test_unlocks_achievements_in_hardcore() {
+        let dir = std::env::temp_dir().join(format!("nes-cheevos-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let set = dir.join("set.json");
+        let json = r#"{"Achievements": [{"ID": 7, "Title": "Third frame",
+            "Description": "", "Points": 5, "MemAddr": "0xH0000=3"}]}"#;
+        fs::write(&set, json).unwrap();
+        let mut session = Session::new(
+            Emulator::from_rom(&counting_rom()).unwrap(),
+            Options {
+                states_dir: dir.clone(),
+                cheats_dir: dir.clone(),
+                achievements: Some(set),
+                hardcore: true,
+                ..Options::default()
+            },
+            Box::new(NullSink::new(48_000)),
+        );
+        assert_eq!(
+            session.osd().messages().last(),
+            Some("1 achievements, hardcore")
+        );
+        assert!(session.emulator.cheats().is_suspended());
+
+        for _ in 0..5 {
+            session.run_frame();
+        }
+        assert_eq!(
+            session.take_achievement_events(),
+            [AchievementEvent::Unlocked { id: 7 }]
+        );
+        assert_eq!(
+            session.osd().messages().last(),
+            Some("Achievement unlocked: Third frame (5 points)")
+        );
+        assert!(session.achievements().unwrap().achievements()[0].is_unlocked());
+
+        session.perform(Action::SaveState, true);
+        session.perform(Action::LoadState, true);
+        assert_eq!(
+            session.osd().messages().last(),
+            Some("Not in hardcore mode")
+        );
+        assert_eq!(session.emulator.frame_count(), 5);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_title_follows_the_rom_and_stats() {
+        let mut session = session();
+        let mut title = Title::new("smb.nes");
+        assert_eq!(title.update(&session), Some("smb.nes"));
+        assert_eq!(title.update(&session), None);
+
+        session.show_stats = StatsDisplay::Title;
+        session.rom = Some(PathBuf::from("zelda.nes"));
+        assert_eq!(
+            title.update(&session),
+            Some("zelda.nes | 0 fps 0% audio 0/0ms")
+        );
+    }
+
+    #[test]
+    fn test_window_state_restores_the_windowed_geometry() {
+        let mut state = WindowState::new(FullscreenMode::Exclusive);
+        let windowed = Geometry {
+            position: Some((40, 60)),
+            size: (768, 720),
+        };
+        assert_eq!(state.leave(), None);
+        state.enter(windowed);
+        // a second enter while fullscreen would otherwise save the
+        // fullscreen size
+        state.enter(Geometry {
+            position: Some((0, 0)),
+            size: (1920, 1080),
+        });
+        assert!(state.is_fullscreen());
+        assert_eq!(state.leave(), Some(windowed));
+        assert!(!state.is_fullscreen());
+    }
+
+    #[test]
+    fn test_input_merges_keyboard_and_gamepad() {
+        let mut input = Input::new(KeyBindings::default_bindings(), GamepadMapping::new());
+        input.keyboard.key_down("Return");
+        input.gamepads.handle(GamepadEvent::Connected {
+            id: 7,
+            name: "pad".to_string(),
+        });
+        input.gamepads.handle(GamepadEvent::Button {
+            id: 7,
+            button: PadButton::East,
+            pressed: true,
+        });
+
+        assert_eq!(
+            input.buttons(0),
+            JoypadButton::START | JoypadButton::BUTTON_A
+        );
+        assert_eq!(input.buttons(1), JoypadButton::empty());
+    }
+
+    #[test]
+    fn test_pacer_keeps_schedule_and_drops_long_stalls() {
+        let mut pacer = FramePacer::new(50.0);
+        let start = pacer.deadline();
+        assert!(pacer.is_due(start));
+
+        pacer.advance(start);
+        assert_eq!(pacer.deadline(), start + Duration::from_millis(20));
+        assert!(!pacer.is_due(start + Duration::from_millis(19)));
+
+        // a little late: stay on schedule
+        pacer.advance(start + Duration::from_millis(50));
+        assert_eq!(pacer.deadline(), start + Duration::from_millis(40));
+
+        let stall = start + Duration::from_secs(2);
+        pacer.advance(stall);
+        assert_eq!(pacer.deadline(), stall);
+    }
+
+    #[test]
+    fn test_answers_remote_requests() {
+        let mut session = session();
+        assert_eq!(session.answer(&Request::Pause), Ok(Json::Null));
+        assert!(session.is_paused());
+        session.remote_buttons[0] = Some(JoypadButton::START);
+        let stepped = session.answer(&Request::Step { frames: 3 }).unwrap();
+        assert_eq!(stepped.get("frame").and_then(Json::as_u64), Some(3));
+        assert!(session.is_paused());
+        // a paused session doesn't run frames of its own
+        session.run_frame();
+        assert_eq!(session.emulator.frame_count(), 3);
+
+        let read = Request::ReadMemory {
+            space: MemorySpace::Cpu,
+            addr: 0x0000,
+            len: 2,
+        };
+        assert_eq!(session.answer(&read), Ok(Json::from(vec![2u64, 0])));
+        let pad = session
+            .emulator
+            .cpu_mut()
+            .bus
+            .controllers
+            .joypad(0)
+            .unwrap();
+        assert_eq!(pad.buttons(), JoypadButton::START);
+
+        let frame = session.answer(&Request::Frame).unwrap();
+        let rgb = frame.get("rgb").and_then(Json::as_str).unwrap();
+        assert_eq!(STANDARD.decode(rgb).unwrap(), session.emulator.frame().data);
+        let status = session.answer(&Request::Status).unwrap();
+        assert_eq!(status.get("paused"), Some(&Json::Bool(true)));
+    }
+}
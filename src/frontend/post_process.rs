@@ -0,0 +1,347 @@
+// Runs a PostShader over the pixels texture in place of pixels' own scaler,
+// into the rectangle render::viewport picked. Shaders see a 256x240 source
+// and the viewport's size as the output.
+//
+// Surface textures can't be read, so a filtered screenshot draws the frame
+// a second time into a texture of its own and copies that back.
+
+use std::sync::mpsc;
+use std::time::Instant;
+
+use pixels::wgpu;
+use pixels::wgpu::naga;
+use pixels::{Pixels, PixelsContext};
+
+use crate::render::shader::{PostShader, ShaderUniforms};
+use crate::render::viewport::Viewport;
+
+// Parses and validates the full module, so a broken user shader is reported
+// with line numbers instead of taking the GPU device down.
+pub fn validate(shader: &PostShader) -> Result<(), String> {
+    let source = shader.wgsl();
+    let module = naga::front::wgsl::parse_str(&source)
+        .map_err(|err| format!("{}: {}", shader.name, err.emit_to_string(&source)))?;
+    naga::valid::Validator::new(
+        naga::valid::ValidationFlags::all(),
+        naga::valid::Capabilities::empty(),
+    )
+    .validate(&module)
+    .map_err(|err| format!("{}: {}", shader.name, err.emit_to_string(&source)))?;
+
+    let has_fragment = module
+        .entry_points
+        .iter()
+        .any(|entry| entry.name == "fs_main" && entry.stage == naga::ShaderStage::Fragment);
+    if !has_fragment {
+        return Err(format!("{}: no @fragment fn fs_main", shader.name));
+    }
+    Ok(())
+}
+
+pub struct PostProcess {
+    pipeline: wgpu::RenderPipeline,
+    bind_group: wgpu::BindGroup,
+    uniform_buffer: wgpu::Buffer,
+    uniforms: ShaderUniforms,
+    started: Instant,
+}
+
+impl PostProcess {
+    pub fn new(pixels: &Pixels, shader: &PostShader) -> Result<Self, String> {
+        validate(shader)?;
+
+        let device = pixels.device();
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some(&shader.name),
+            source: wgpu::ShaderSource::Wgsl(shader.wgsl().into()),
+        });
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("post_process_sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Nearest,
+            min_filter: wgpu::FilterMode::Nearest,
+            mipmap_filter: wgpu::MipmapFilterMode::Nearest,
+            lod_min_clamp: 0.0,
+            lod_max_clamp: 1.0,
+            compare: None,
+            anisotropy_clamp: 1,
+            border_color: None,
+        });
+        let uniforms = ShaderUniforms::default();
+        let uniform_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("post_process_uniforms"),
+            size: ShaderUniforms::SIZE as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("post_process_bind_group_layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: wgpu::BufferSize::new(ShaderUniforms::SIZE as u64),
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let texture_view = pixels
+            .texture()
+            .create_view(&wgpu::TextureViewDescriptor::default());
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("post_process_bind_group"),
+            layout: &layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&texture_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: uniform_buffer.as_entire_binding(),
+                },
+            ],
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("post_process_pipeline_layout"),
+            bind_group_layouts: &[Some(&layout)],
+            immediate_size: 0,
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("post_process_pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &module,
+                entry_point: Some("vs_main"),
+                buffers: &[],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            fragment: Some(wgpu::FragmentState {
+                module: &module,
+                entry_point: Some("fs_main"),
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: pixels.render_texture_format(),
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            multiview_mask: None,
+            cache: None,
+        });
+
+        Ok(PostProcess {
+            pipeline,
+            bind_group,
+            uniform_buffer,
+            uniforms,
+            started: Instant::now(),
+        })
+    }
+
+    // For use inside `Pixels::render_with`.
+    pub fn render(
+        &mut self,
+        encoder: &mut wgpu::CommandEncoder,
+        target: &wgpu::TextureView,
+        context: &PixelsContext,
+        view: Viewport,
+        frame_count: u64,
+    ) {
+        self.draw(encoder, target, &context.queue, view, frame_count);
+    }
+
+    // The picture as render draws it into `view`, as packed RGB. Waits for
+    // the GPU.
+    pub fn capture(
+        &mut self,
+        pixels: &Pixels,
+        view: Viewport,
+        frame_count: u64,
+    ) -> Result<Vec<u8>, String> {
+        let device = pixels.device();
+        let format = pixels.render_texture_format();
+        let size = wgpu::Extent3d {
+            width: view.width,
+            height: view.height,
+            depth_or_array_layers: 1,
+        };
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("post_process_capture"),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        // rows are copied out padded to the copy alignment
+        let row_bytes = view.width * 4;
+        let padded_row_bytes = row_bytes.next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT);
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("post_process_capture_buffer"),
+            size: padded_row_bytes as u64 * view.height as u64,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("post_process_capture_encoder"),
+        });
+        let target = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let whole = Viewport {
+            x: 0,
+            y: 0,
+            width: view.width,
+            height: view.height,
+        };
+        self.draw(&mut encoder, &target, pixels.queue(), whole, frame_count);
+        encoder.copy_texture_to_buffer(
+            wgpu::TexelCopyTextureInfo {
+                texture: &texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            wgpu::TexelCopyBufferInfo {
+                buffer: &buffer,
+                layout: wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_row_bytes),
+                    rows_per_image: None,
+                },
+            },
+            size,
+        );
+        pixels.queue().submit([encoder.finish()]);
+
+        let slice = buffer.slice(..);
+        let (sender, receiver) = mpsc::channel();
+        slice.map_async(wgpu::MapMode::Read, move |result| {
+            let _ = sender.send(result);
+        });
+        device
+            .poll(wgpu::PollType::wait_indefinitely())
+            .map_err(|err| err.to_string())?;
+        receiver
+            .recv()
+            .map_err(|err| err.to_string())?
+            .map_err(|err| err.to_string())?;
+
+        let bgra = matches!(
+            format,
+            wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb
+        );
+        let data = slice.get_mapped_range();
+        let mut rgb = Vec::with_capacity((view.width * view.height * 3) as usize);
+        for row in data.chunks_exact(padded_row_bytes as usize) {
+            for pixel in row[..row_bytes as usize].chunks_exact(4) {
+                if bgra {
+                    rgb.extend_from_slice(&[pixel[2], pixel[1], pixel[0]]);
+                } else {
+                    rgb.extend_from_slice(&pixel[..3]);
+                }
+            }
+        }
+        drop(data);
+        buffer.unmap();
+        Ok(rgb)
+    }
+
+    fn draw(
+        &mut self,
+        encoder: &mut wgpu::CommandEncoder,
+        target: &wgpu::TextureView,
+        queue: &wgpu::Queue,
+        view: Viewport,
+        frame_count: u64,
+    ) {
+        self.uniforms.output_size = [view.width as f32, view.height as f32];
+        self.uniforms.frame_count = frame_count as u32;
+        self.uniforms.time = self.started.elapsed().as_secs_f32();
+        queue.write_buffer(&self.uniform_buffer, 0, &self.uniforms.to_bytes());
+
+        let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("post_process_pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: target,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: wgpu::StoreOp::Store,
+                },
+                depth_slice: None,
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+            multiview_mask: None,
+        });
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &self.bind_group, &[]);
+        pass.set_viewport(
+            view.x as f32,
+            view.y as f32,
+            view.width as f32,
+            view.height as f32,
+            0.0,
+            1.0,
+        );
+        pass.draw(0..3, 0..1);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::render::shader::ShaderPreset;
+
+    #[test]
+    fn test_presets_validate() {
+        for preset in ShaderPreset::ALL {
+            validate(&PostShader::preset(preset)).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_broken_shader_is_reported() {
+        let missing = PostShader::from_source("missing", "fn helper() {}");
+        assert!(validate(&missing).unwrap_err().contains("fs_main"));
+
+        let broken = PostShader::from_source("broken", "@fragment fn fs_main( {");
+        assert!(validate(&broken).unwrap_err().starts_with("broken: "));
+    }
+}
@@ -0,0 +1,292 @@
+// SDL2 desktop front end. The frame is streamed into a 256x240 RGB24
+// texture that SDL scales into the window's viewport, keyboard and game controller
+// events go through the shared Input, and audio is pushed to an SDL queue
+// whose depth steers the resampler.
+
+use std::io;
+use std::path::Path;
+
+use sdl2::audio::{AudioQueue, AudioSpecDesired};
+use sdl2::controller::{Axis, Button, GameController};
+use sdl2::event::Event;
+use sdl2::keyboard::Keycode;
+use sdl2::pixels::PixelFormatEnum;
+use sdl2::rect::Rect;
+use sdl2::video::{FullscreenType, Window, WindowPos};
+
+use super::hotkeys::Action;
+use super::{FullscreenMode, Geometry, Options, Pacing, Session, Title, WindowState};
+use crate::audio::AudioSink;
+use crate::emulator::Emulator;
+use crate::input::gamepad::{GamepadEvent, PadAxis, PadButton};
+use crate::render::frame::Frame;
+
+// SDL plays from the queue on its own thread and says nothing when it runs
+// out, so running out is noticed at the next push: an empty queue once
+// something has been played.
+struct QueueSink {
+    queue: AudioQueue<f32>,
+    started: bool,
+    underruns: u64,
+}
+
+impl AudioSink for QueueSink {
+    fn sample_rate(&self) -> u32 {
+        self.queue.spec().freq as u32
+    }
+
+    fn push_samples(&mut self, samples: &[f32]) {
+        if self.queue.size() == 0 {
+            self.underruns += self.started as u64;
+        }
+        self.started |= !samples.is_empty();
+        if let Err(err) = self.queue.queue_audio(samples) {
+            eprintln!("audio queue error: {}", err);
+        }
+    }
+
+    fn queued_samples(&self) -> usize {
+        self.queue.size() as usize / std::mem::size_of::<f32>()
+    }
+
+    fn underruns(&self) -> u64 {
+        self.underruns
+    }
+}
+
+// SDL's own names for a few keys differ from the ones key bindings use.
+fn key_name(keycode: Keycode) -> String {
+    match keycode {
+        Keycode::LSHIFT => "LShift".to_string(),
+        Keycode::RSHIFT => "RShift".to_string(),
+        Keycode::LCTRL => "LCtrl".to_string(),
+        Keycode::RCTRL => "RCtrl".to_string(),
+        Keycode::LALT => "LAlt".to_string(),
+        Keycode::RALT => "RAlt".to_string(),
+        keycode => keycode.name(),
+    }
+}
+
+fn pad_button(button: Button) -> Option<PadButton> {
+    Some(match button {
+        Button::A => PadButton::South,
+        Button::B => PadButton::East,
+        Button::X => PadButton::West,
+        Button::Y => PadButton::North,
+        Button::LeftShoulder => PadButton::LeftTrigger,
+        Button::RightShoulder => PadButton::RightTrigger,
+        Button::Back => PadButton::Select,
+        Button::Start => PadButton::Start,
+        Button::DPadUp => PadButton::DPadUp,
+        Button::DPadDown => PadButton::DPadDown,
+        Button::DPadLeft => PadButton::DPadLeft,
+        Button::DPadRight => PadButton::DPadRight,
+        _ => return None,
+    })
+}
+
+// SDL axes run -32768..=32767 with positive Y pointing down.
+fn pad_axis(axis: Axis, value: i16) -> Option<(PadAxis, f32)> {
+    let value = value as f32 / i16::MAX as f32;
+    match axis {
+        Axis::LeftX => Some((PadAxis::LeftStickX, value)),
+        Axis::LeftY => Some((PadAxis::LeftStickY, -value)),
+        _ => None,
+    }
+}
+
+// Exclusive fullscreen takes the desktop's mode rather than one matching
+// the window's size.
+fn toggle_fullscreen(window: &mut Window, state: &mut WindowState) -> Result<(), String> {
+    if let Some(windowed) = state.leave() {
+        window.set_fullscreen(FullscreenType::Off)?;
+        let (width, height) = windowed.size;
+        window
+            .set_size(width, height)
+            .map_err(|err| err.to_string())?;
+        if let Some((x, y)) = windowed.position {
+            window.set_position(WindowPos::Positioned(x), WindowPos::Positioned(y));
+        }
+        return Ok(());
+    }
+
+    state.enter(Geometry {
+        position: Some(window.position()),
+        size: window.size(),
+    });
+    match state.mode {
+        FullscreenMode::Borderless => window.set_fullscreen(FullscreenType::Desktop),
+        FullscreenMode::Exclusive => {
+            let display = window.display_index()?;
+            let mode = window.subsystem().desktop_display_mode(display)?;
+            window.set_display_mode(mode)?;
+            window.set_fullscreen(FullscreenType::True)
+        }
+    }
+}
+
+// Runs until the window is closed or the quit hotkey is pressed.
+pub fn run(emulator: Emulator, title: &str, options: Options) -> Result<(), String> {
+    let sdl = sdl2::init()?;
+    let video = sdl.video()?;
+    let audio = sdl.audio()?;
+    let controller_subsystem = sdl.game_controller()?;
+
+    let scaling = options.scaling;
+    let (width, height) = scaling.window_size(options.scale);
+    let window = video
+        .window(title, width, height)
+        .position_centered()
+        .resizable()
+        .build()
+        .map_err(|err| err.to_string())?;
+    let mut canvas = window.into_canvas();
+    if options.pacing == Pacing::Vsync {
+        canvas = canvas.present_vsync();
+    }
+    let mut canvas = canvas.build().map_err(|err| err.to_string())?;
+    let mut window_state = WindowState::new(options.fullscreen_mode);
+    if options.fullscreen {
+        toggle_fullscreen(canvas.window_mut(), &mut window_state)?;
+    }
+    let texture_creator = canvas.texture_creator();
+    let mut texture = texture_creator
+        .create_texture_streaming(
+            PixelFormatEnum::RGB24,
+            Frame::WIDTH as u32,
+            Frame::HEIGHT as u32,
+        )
+        .map_err(|err| err.to_string())?;
+
+    let spec = AudioSpecDesired {
+        freq: Some(options.sample_rate as i32),
+        channels: Some(1),
+        samples: Some(
+            options
+                .audio_latency
+                .buffer
+                .map_or(1024, |frames| frames.clamp(64, u16::MAX.into()) as u16),
+        ),
+    };
+    let sink = QueueSink {
+        queue: audio.open_queue(None, &spec)?,
+        started: false,
+        underruns: 0,
+    };
+    sink.queue.resume();
+    let mut session = Session::new(emulator, options, Box::new(sink));
+    let mut title_bar = Title::new(title);
+    // kept open for as long as they are plugged in
+    let mut controllers: Vec<GameController> = Vec::new();
+
+    let mut events = sdl.event_pump()?;
+
+    'running: loop {
+        for event in events.poll_iter() {
+            match event {
+                Event::Quit { .. } => break 'running,
+                // every press goes to the session; only Quit and Fullscreen
+                // need us
+                Event::KeyDown {
+                    keycode: Some(keycode),
+                    repeat: false,
+                    ..
+                } => match session.key_down(&key_name(keycode)) {
+                    Some(Action::Quit) => break 'running,
+                    Some(Action::Fullscreen) => {
+                        if let Err(err) = toggle_fullscreen(canvas.window_mut(), &mut window_state)
+                        {
+                            eprintln!("can't switch fullscreen: {}", err);
+                        }
+                    }
+                    _ => {}
+                },
+                Event::KeyUp {
+                    keycode: Some(keycode),
+                    ..
+                } => session.key_up(&key_name(keycode)),
+                Event::ControllerDeviceAdded { which, .. } => {
+                    match controller_subsystem.open(which) {
+                        Ok(controller) => {
+                            session.input.gamepads.handle(GamepadEvent::Connected {
+                                id: controller.instance_id() as usize,
+                                name: controller.name(),
+                            });
+                            controllers.push(controller);
+                        }
+                        Err(err) => eprintln!("can't open controller {}: {}", which, err),
+                    }
+                }
+                Event::ControllerDeviceRemoved { which, .. } => {
+                    controllers.retain(|controller| controller.instance_id() != which);
+                    session
+                        .input
+                        .gamepads
+                        .handle(GamepadEvent::Disconnected { id: which as usize });
+                }
+                Event::ControllerButtonDown { which, button, .. }
+                | Event::ControllerButtonUp { which, button, .. } => {
+                    if let Some(pad) = pad_button(button) {
+                        session.input.gamepads.handle(GamepadEvent::Button {
+                            id: which as usize,
+                            button: pad,
+                            pressed: matches!(event, Event::ControllerButtonDown { .. }),
+                        });
+                    }
+                }
+                Event::ControllerAxisMotion {
+                    which, axis, value, ..
+                } => {
+                    if let Some((axis, value)) = pad_axis(axis, value) {
+                        session.input.gamepads.handle(GamepadEvent::Axis {
+                            id: which as usize,
+                            axis,
+                            value,
+                        });
+                    }
+                }
+                Event::Window {
+                    win_event: sdl2::event::WindowEvent::FocusLost,
+                    ..
+                } => session.release_keys(),
+                Event::DropFile { filename, .. } => {
+                    if let Err(err) = session.load_rom(Path::new(&filename)) {
+                        eprintln!("can't load {}: {}", filename, err);
+                        session.show_message("Can't load ROM");
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let frame = session.run_frame();
+        texture
+            .update(None, &frame.data, Frame::WIDTH * 3)
+            .map_err(|err| err.to_string())?;
+        let (width, height) = canvas.output_size()?;
+        let view = scaling.viewport(width, height);
+        canvas.clear();
+        let view = Rect::new(view.x as i32, view.y as i32, view.width, view.height);
+        canvas.copy(&texture, None, view)?;
+        if session.take_capture() {
+            let saved = canvas
+                .read_pixels(view, PixelFormatEnum::RGB24)
+                .map_err(io::Error::other)
+                .and_then(|rgb| session.save_capture(view.width(), view.height(), &rgb));
+            if let Err(err) = saved {
+                eprintln!("can't save screenshot: {}", err);
+                session.show_message("Can't save screenshot");
+            }
+        }
+        canvas.present();
+        if let Some(title) = title_bar.update(&session) {
+            if let Err(err) = canvas.window_mut().set_title(title) {
+                eprintln!("can't set title: {}", err);
+            }
+        }
+
+        session.wait();
+    }
+
+    Ok(())
+}
@@ -0,0 +1,165 @@
+// How fast things are really going, so slowdown can be pinned on the game
+// (the console lagging, at full speed) or on us (fewer frames than the
+// console's rate). Frames shown and frames emulated are counted over the
+// last second and the audio queue is sampled as it is then; the numbers
+// are refreshed every half second so they can be read. Audio underruns,
+// the device running dry, are counted over the same second: any at all and
+// the queue wants to be deeper.
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use serde::Deserialize;
+
+const WINDOW: Duration = Duration::from_secs(1);
+const REFRESH: Duration = Duration::from_millis(500);
+
+// Where the stats go, if anywhere.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StatsDisplay {
+    #[default]
+    Off,
+    // the top-right corner of the picture
+    Osd,
+    // after the window title
+    Title,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Stats {
+    // frames shown per second
+    pub fps: f64,
+    // frames emulated per second over the console's rate; 1.0 is full
+    // speed
+    pub speed: f64,
+    // audio waiting at the device, and what rate control keeps it at
+    pub audio_queued: Duration,
+    pub audio_target: Duration,
+    // in the last second
+    pub underruns: u64,
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:.0} fps {:.0}% audio {}/{}ms",
+            self.fps,
+            self.speed * 100.0,
+            self.audio_queued.as_millis(),
+            self.audio_target.as_millis()
+        )?;
+        if self.underruns > 0 {
+            write!(f, " {} underruns", self.underruns)?;
+        }
+        Ok(())
+    }
+}
+
+// The audio side as a frame is shown.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AudioStats {
+    pub queued: Duration,
+    pub target: Duration,
+    // since the sink was opened, as AudioSink::underruns counts them
+    pub underruns: u64,
+}
+
+#[derive(Debug)]
+pub struct StatsMeter {
+    frame_rate: f64,
+    // (when a frame was shown, the emulator's frame count then, the
+    // sink's underruns then)
+    frames: VecDeque<(Instant, u64, u64)>,
+    updated: Option<Instant>,
+    stats: Stats,
+}
+
+impl StatsMeter {
+    pub fn new(frame_rate: f64) -> Self {
+        StatsMeter {
+            frame_rate,
+            frames: VecDeque::new(),
+            updated: None,
+            stats: Stats::default(),
+        }
+    }
+
+    // Once per frame shown. Returns whether the stats changed.
+    pub fn frame(&mut self, now: Instant, frame_count: u64, audio: AudioStats) -> bool {
+        self.frames.push_back((now, frame_count, audio.underruns));
+        while let Some(&(at, _, _)) = self.frames.front() {
+            if now.duration_since(at) <= WINDOW {
+                break;
+            }
+            self.frames.pop_front();
+        }
+        if self
+            .updated
+            .is_some_and(|updated| now.duration_since(updated) < REFRESH)
+        {
+            return false;
+        }
+
+        let (&(first, first_count, first_underruns), &(last, last_count, last_underruns)) =
+            (self.frames.front().unwrap(), self.frames.back().unwrap());
+        let elapsed = last.duration_since(first).as_secs_f64();
+        if elapsed == 0.0 {
+            return false;
+        }
+        self.updated = Some(now);
+        self.stats = Stats {
+            fps: (self.frames.len() - 1) as f64 / elapsed,
+            speed: last_count.saturating_sub(first_count) as f64 / elapsed / self.frame_rate,
+            audio_queued: audio.queued,
+            audio_target: audio.target,
+            underruns: last_underruns.saturating_sub(first_underruns),
+        };
+        true
+    }
+
+    // Zeros until there's been time to measure.
+    pub fn stats(&self) -> Stats {
+        self.stats
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_meter_counts_the_last_second() {
+        let start = Instant::now();
+        let mut meter = StatsMeter::new(60.0);
+        let ms = Duration::from_millis;
+        let audio = |underruns| AudioStats {
+            queued: ms(35),
+            target: ms(40),
+            underruns,
+        };
+        assert!(!meter.frame(start, 0, audio(0)));
+        // 30 frames shown a second, each running two: full speed
+        let mut changed = 0;
+        for n in 1..=90u32 {
+            let at = start + Duration::from_secs_f64(n as f64 / 30.0);
+            // two underruns early on, long gone by the end
+            let underruns = if n < 10 { 0 } else { 2 };
+            changed += meter.frame(at, n as u64 * 2, audio(underruns)) as u32;
+        }
+        // every half second over three seconds
+        assert_eq!(changed, 6);
+        let stats = meter.stats();
+        assert!((stats.fps - 30.0).abs() < 0.1, "{}", stats.fps);
+        assert!((stats.speed - 1.0).abs() < 0.01, "{}", stats.speed);
+        assert_eq!(stats.to_string(), "30 fps 100% audio 35/40ms");
+        assert!(meter.frames.len() <= 31);
+
+        let at = start + Duration::from_secs(4);
+        assert!(meter.frame(at, 240, audio(5)));
+        assert_eq!(meter.stats().underruns, 3);
+        assert!(meter.stats().to_string().ends_with("35/40ms 3 underruns"));
+    }
+}
@@ -0,0 +1,436 @@
+// Terminal front end: no window, no GPU, works over SSH. The frame is drawn
+// with "▀" half blocks (foreground is the upper pixel, background the lower
+// one, so a cell holds two square-ish pixels) or with braille patterns for
+// terminals without true colour.
+//
+// Most terminals only report key presses, never releases. Keys are then
+// held for a short while after each press and refreshed by the terminal's
+// auto-repeat; terminals with the kitty keyboard protocol report real
+// releases and get exact input.
+//
+// Dropping a file on a terminal pastes its path, so a pasted path to a
+// ROM switches to that game.
+
+use std::collections::HashMap;
+use std::io::{self, stdout};
+use std::path::PathBuf;
+use std::time::Duration;
+
+use ratatui::buffer::Buffer;
+use ratatui::crossterm::event::{
+    self, DisableBracketedPaste, EnableBracketedPaste, Event, KeyCode, KeyEvent, KeyEventKind,
+    KeyModifiers, KeyboardEnhancementFlags, ModifierKeyCode, PopKeyboardEnhancementFlags,
+    PushKeyboardEnhancementFlags,
+};
+use ratatui::crossterm::execute;
+use ratatui::crossterm::terminal::{supports_keyboard_enhancement, SetTitle};
+use ratatui::layout::Rect;
+use ratatui::style::Color;
+use ratatui::widgets::Widget;
+
+use super::hotkeys::Action;
+use super::{default_sink, Options, Pacing, ScreenshotStage, Session, Title};
+use crate::emulator::Emulator;
+use crate::render::frame::Frame;
+use crate::render::viewport::Scaling;
+
+type Rgb = (u8, u8, u8);
+
+// Long enough to bridge the terminal's initial auto-repeat delay.
+const HOLD_FRAMES: u32 = 30;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CellMode {
+    #[default]
+    HalfBlock,
+    Braille,
+}
+
+// The frame placed in an area the way render::viewport places it in a
+// window, counting dots rather than pixels.
+pub struct FrameView<'a> {
+    frame: &'a Frame,
+    mode: CellMode,
+    scaling: Scaling,
+}
+
+impl<'a> FrameView<'a> {
+    pub fn new(frame: &'a Frame, mode: CellMode) -> Self {
+        FrameView {
+            frame,
+            mode,
+            scaling: Scaling::default(),
+        }
+    }
+
+    pub fn scaling(mut self, scaling: Scaling) -> Self {
+        self.scaling = scaling;
+        self
+    }
+}
+
+// Pixels per cell for each mode.
+fn cell_size(mode: CellMode) -> (u16, u16) {
+    match mode {
+        CellMode::HalfBlock => (1, 2),
+        CellMode::Braille => (2, 4),
+    }
+}
+
+// Braille dot for each (x, y) within a 2x4 cell.
+const BRAILLE_DOTS: [[u8; 4]; 2] = [[0x01, 0x02, 0x04, 0x40], [0x08, 0x10, 0x20, 0x80]];
+// dots this dark stay off even in an all-dark cell
+const BRAILLE_DARK: u32 = 32;
+
+fn luminance((r, g, b): Rgb) -> u32 {
+    (r as u32 * 299 + g as u32 * 587 + b as u32 * 114) / 1000
+}
+
+// Lights the dots at least as bright as the cell's average, drawn in their
+// average colour.
+fn braille(dots: &[[Rgb; 4]; 2]) -> (char, Color) {
+    let average = dots.iter().flatten().map(|&d| luminance(d)).sum::<u32>() / 8;
+    let mut bits = 0;
+    let mut sum = (0, 0, 0);
+    let mut lit = 0;
+    for (dx, column) in dots.iter().enumerate() {
+        for (dy, &dot) in column.iter().enumerate() {
+            let level = luminance(dot);
+            if level > BRAILLE_DARK && level >= average {
+                bits |= BRAILLE_DOTS[dx][dy];
+                sum.0 += dot.0 as u32;
+                sum.1 += dot.1 as u32;
+                sum.2 += dot.2 as u32;
+                lit += 1;
+            }
+        }
+    }
+    let color = match lit {
+        0 => Color::Reset,
+        n => Color::Rgb((sum.0 / n) as u8, (sum.1 / n) as u8, (sum.2 / n) as u8),
+    };
+    let symbol = char::from_u32(0x2800 + bits as u32).unwrap_or(' ');
+    (symbol, color)
+}
+
+impl Widget for FrameView<'_> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let (cell_w, cell_h) = cell_size(self.mode);
+        let dots_w = (area.width * cell_w) as u32;
+        let dots_h = (area.height * cell_h) as u32;
+        let view = self.scaling.viewport(dots_w, dots_h);
+        if view.width == 0 || view.height == 0 {
+            return;
+        }
+        let scale_x = view.width as f32 / Frame::WIDTH as f32;
+        let scale_y = view.height as f32 / Frame::HEIGHT as f32;
+
+        let cols = (view.width as u16).div_ceil(cell_w);
+        let rows = (view.height as u16).div_ceil(cell_h);
+        let left = area.x + view.x as u16 / cell_w;
+        let top = area.y + view.y as u16 / cell_h;
+
+        let pixel = |dot_x: u16, dot_y: u16| {
+            let x = ((dot_x as f32 / scale_x) as usize).min(Frame::WIDTH - 1);
+            let y = ((dot_y as f32 / scale_y) as usize).min(Frame::HEIGHT - 1);
+            self.frame.pixel(x, y)
+        };
+
+        for row in 0..rows.min(area.height) {
+            for col in 0..cols.min(area.width) {
+                let Some(cell) = buf.cell_mut((left + col, top + row)) else {
+                    continue;
+                };
+                let dot_x = col * cell_w;
+                let dot_y = row * cell_h;
+                match self.mode {
+                    CellMode::HalfBlock => {
+                        let (r, g, b) = pixel(dot_x, dot_y);
+                        let (r2, g2, b2) = pixel(dot_x, dot_y + 1);
+                        cell.set_char('▀')
+                            .set_fg(Color::Rgb(r, g, b))
+                            .set_bg(Color::Rgb(r2, g2, b2));
+                    }
+                    CellMode::Braille => {
+                        let mut dots = [[(0, 0, 0); 4]; 2];
+                        for (dx, column) in dots.iter_mut().enumerate() {
+                            for (dy, dot) in column.iter_mut().enumerate() {
+                                *dot = pixel(dot_x + dx as u16, dot_y + dy as u16);
+                            }
+                        }
+                        let (symbol, color) = braille(&dots);
+                        cell.set_char(symbol).set_fg(color).set_bg(Color::Reset);
+                    }
+                }
+            }
+        }
+    }
+}
+
+// Key names as KeyBindings spells them.
+fn key_name(code: KeyCode) -> Option<String> {
+    Some(match code {
+        KeyCode::Char(' ') => "Space".to_string(),
+        KeyCode::Char(c) => c.to_ascii_uppercase().to_string(),
+        KeyCode::Enter => "Return".to_string(),
+        KeyCode::Esc => "Escape".to_string(),
+        KeyCode::Up => "Up".to_string(),
+        KeyCode::Down => "Down".to_string(),
+        KeyCode::Left => "Left".to_string(),
+        KeyCode::Right => "Right".to_string(),
+        KeyCode::Tab => "Tab".to_string(),
+        KeyCode::Backspace => "Backspace".to_string(),
+        KeyCode::F(n) => format!("F{}", n),
+        KeyCode::Modifier(ModifierKeyCode::LeftShift) => "LShift".to_string(),
+        KeyCode::Modifier(ModifierKeyCode::RightShift) => "RShift".to_string(),
+        KeyCode::Modifier(ModifierKeyCode::LeftControl) => "LCtrl".to_string(),
+        KeyCode::Modifier(ModifierKeyCode::RightControl) => "RCtrl".to_string(),
+        KeyCode::Modifier(ModifierKeyCode::LeftAlt) => "LAlt".to_string(),
+        KeyCode::Modifier(ModifierKeyCode::RightAlt) => "RAlt".to_string(),
+        _ => return None,
+    })
+}
+
+// Synthesises releases for terminals that never send them.
+#[derive(Debug)]
+struct HeldKeys {
+    // None when the terminal reports releases itself
+    timeout: Option<u32>,
+    held: HashMap<String, u32>,
+}
+
+impl HeldKeys {
+    fn new(timeout: Option<u32>) -> Self {
+        HeldKeys {
+            timeout,
+            held: HashMap::new(),
+        }
+    }
+
+    fn press(&mut self, session: &mut Session, key: String) -> Option<Action> {
+        let action = session.key_down(&key);
+        if let Some(timeout) = self.timeout {
+            self.held.insert(key, timeout);
+        }
+        action
+    }
+
+    fn release(&mut self, session: &mut Session, key: &str) {
+        session.key_up(key);
+        self.held.remove(key);
+    }
+
+    // Once per frame.
+    fn tick(&mut self, session: &mut Session) {
+        self.held.retain(|key, frames| {
+            *frames -= 1;
+            if *frames == 0 {
+                session.key_up(key);
+            }
+            *frames > 0
+        });
+    }
+}
+
+fn is_interrupt(key: &KeyEvent) -> bool {
+    key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL)
+}
+
+// Terminals quote a dropped path with spaces in it or escape the spaces.
+fn pasted_path(text: &str) -> PathBuf {
+    let text = text.trim();
+    let unquoted = ['\'', '"']
+        .iter()
+        .find_map(|&quote| text.strip_prefix(quote)?.strip_suffix(quote));
+    match unquoted {
+        Some(path) => PathBuf::from(path),
+        None => PathBuf::from(text.replace("\\ ", " ")),
+    }
+}
+
+// Runs until the quit hotkey or Ctrl+C.
+pub fn run(
+    emulator: Emulator,
+    title: &str,
+    mut options: Options,
+    mode: CellMode,
+) -> io::Result<()> {
+    // there's no refresh to wait for in a terminal, and no reading back
+    // what it drew
+    if options.pacing == Pacing::Vsync {
+        options.pacing = Pacing::Timer;
+    }
+    options.screenshot = ScreenshotStage::Raw;
+    let enhanced = supports_keyboard_enhancement().unwrap_or(false);
+    let mut terminal = ratatui::init();
+    execute!(stdout(), EnableBracketedPaste)?;
+    if enhanced {
+        execute!(
+            stdout(),
+            PushKeyboardEnhancementFlags(
+                KeyboardEnhancementFlags::DISAMBIGUATE_ESCAPE_CODES
+                    | KeyboardEnhancementFlags::REPORT_EVENT_TYPES
+                    | KeyboardEnhancementFlags::REPORT_ALL_KEYS_AS_ESCAPE_CODES
+            )
+        )?;
+    }
+
+    let sink = default_sink(options.sample_rate, options.audio_latency.buffer);
+    let scaling = options.scaling;
+    let mut session = Session::new(emulator, options, sink);
+    let mut title_bar = Title::new(title);
+    let mut held = HeldKeys::new((!enhanced).then_some(HOLD_FRAMES));
+
+    let result = (|| -> io::Result<()> {
+        loop {
+            while event::poll(Duration::ZERO)? {
+                let key = match event::read()? {
+                    Event::Key(key) => key,
+                    Event::Paste(text) => {
+                        let path = pasted_path(&text);
+                        if let Err(err) = session.load_rom(&path) {
+                            eprintln!("can't load {}: {}", path.display(), err);
+                            session.show_message("Can't load ROM");
+                        }
+                        continue;
+                    }
+                    _ => continue,
+                };
+                if is_interrupt(&key) {
+                    return Ok(());
+                }
+                let Some(name) = key_name(key.code) else {
+                    continue;
+                };
+                match key.kind {
+                    KeyEventKind::Press | KeyEventKind::Repeat => {
+                        if held.press(&mut session, name) == Some(Action::Quit) {
+                            return Ok(());
+                        }
+                    }
+                    KeyEventKind::Release => held.release(&mut session, &name),
+                }
+            }
+            held.tick(&mut session);
+
+            let frame = session.run_frame();
+            terminal.draw(|f| {
+                f.render_widget(FrameView::new(frame, mode).scaling(scaling), f.area())
+            })?;
+            if let Some(title) = title_bar.update(&session) {
+                execute!(stdout(), SetTitle(title))?;
+            }
+            session.wait();
+        }
+    })();
+
+    if enhanced {
+        execute!(stdout(), PopKeyboardEnhancementFlags)?;
+    }
+    execute!(stdout(), DisableBracketedPaste)?;
+    ratatui::restore();
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::emulator::test::counting_rom;
+
+    fn frame_with_top_half(rgb: (u8, u8, u8)) -> Frame {
+        let mut frame = Frame::new();
+        for y in 0..Frame::HEIGHT / 2 {
+            for x in 0..Frame::WIDTH {
+                frame.set_pixel(x, y, rgb);
+            }
+        }
+        frame
+    }
+
+    #[test]
+    fn test_half_blocks_use_fg_for_upper_pixel() {
+        let frame = frame_with_top_half((255, 0, 0));
+        let area = Rect::new(0, 0, 64, 30); // 64x60 dots, scale 0.25
+        let mut buf = Buffer::empty(area);
+        FrameView::new(&frame, CellMode::HalfBlock).render(area, &mut buf);
+
+        let top = &buf[(0, 0)];
+        assert_eq!(top.symbol(), "▀");
+        assert_eq!(top.fg, Color::Rgb(255, 0, 0));
+        assert_eq!(top.bg, Color::Rgb(255, 0, 0));
+        let bottom = &buf[(0, 29)];
+        assert_eq!(bottom.fg, Color::Rgb(0, 0, 0));
+    }
+
+    #[test]
+    fn test_view_is_centered_in_wide_areas() {
+        let frame = frame_with_top_half((255, 255, 255));
+        let area = Rect::new(0, 0, 100, 30); // only 64 columns wide at this height
+        let mut buf = Buffer::empty(area);
+        FrameView::new(&frame, CellMode::HalfBlock).render(area, &mut buf);
+
+        assert_eq!(buf[(17, 0)].symbol(), " ");
+        assert_eq!(buf[(18, 0)].symbol(), "▀");
+        assert_eq!(buf[(81, 0)].symbol(), "▀");
+        assert_eq!(buf[(82, 0)].symbol(), " ");
+    }
+
+    #[test]
+    fn test_braille_lights_bright_dots() {
+        let frame = frame_with_top_half((0, 255, 0));
+        // 32x15 cells are 64x60 dots; the middle row straddles the edge
+        let area = Rect::new(0, 0, 32, 15);
+        let mut buf = Buffer::empty(area);
+        FrameView::new(&frame, CellMode::Braille).render(area, &mut buf);
+
+        assert_eq!(buf[(3, 0)].symbol(), "⣿");
+        assert_eq!(buf[(3, 0)].fg, Color::Rgb(0, 255, 0));
+        assert_eq!(buf[(3, 14)].symbol(), "\u{2800}");
+        // upper two rows of dots lit
+        assert_eq!(buf[(3, 7)].symbol(), "⠛");
+    }
+
+    #[test]
+    fn test_pasted_paths_lose_quotes_and_escapes() {
+        assert_eq!(
+            pasted_path("/roms/smb.nes\n"),
+            PathBuf::from("/roms/smb.nes")
+        );
+        assert_eq!(
+            pasted_path("'/roms/Super Mario.nes' "),
+            PathBuf::from("/roms/Super Mario.nes")
+        );
+        assert_eq!(
+            pasted_path("/roms/Super\\ Mario.nes"),
+            PathBuf::from("/roms/Super Mario.nes")
+        );
+    }
+
+    #[test]
+    fn test_held_keys_expire_without_release_events() {
+        let emulator = Emulator::from_rom(&counting_rom()).unwrap();
+        let sink = Box::new(crate::audio::NullSink::new(48_000));
+        let mut session = Session::new(emulator, Options::default(), sink);
+        let mut held = HeldKeys::new(Some(2));
+
+        held.press(&mut session, "Z".to_string());
+        held.tick(&mut session);
+        assert!(!session.input.buttons(0).is_empty());
+        held.tick(&mut session);
+        assert!(session.input.buttons(0).is_empty());
+
+        let mut exact = HeldKeys::new(None);
+        exact.press(&mut session, "Z".to_string());
+        for _ in 0..100 {
+            exact.tick(&mut session);
+        }
+        assert!(!session.input.buttons(0).is_empty());
+        exact.release(&mut session, "Z");
+        assert!(session.input.buttons(0).is_empty());
+
+        // auto-repeat doesn't toggle pause back off
+        held.press(&mut session, "P".to_string());
+        held.press(&mut session, "P".to_string());
+        assert!(session.is_paused());
+    }
+}
@@ -0,0 +1,108 @@
+// Hot reload for homebrew: the ROM file is checked for a new build a few
+// times a second and, once it has stopped changing, loaded in place of
+// the old one, so assembling is all it takes to see a change. Checking is
+// a stat of the file's modification time and size, which works the same
+// everywhere and costs next to nothing.
+//
+// ReloadMode says what becomes of the game that's running: Reset powers
+// the new build on from the start, Keep carries the machine's state over
+// to it (RAM, and the CPU and PPU where they were), so a tweak shows up in
+// the middle of a level. A state the new build's board can't take falls
+// back to the reset. Breakpoints carry over either way, and symbol files
+// are read again, since the addresses will have moved; see
+// Session::reload_rom.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use serde::Deserialize;
+
+// a quarter of a second or so
+const POLL_FRAMES: u32 = 15;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReloadMode {
+    #[default]
+    Reset,
+    Keep,
+}
+
+type Stamp = (SystemTime, u64);
+
+fn stamp(path: &Path) -> Option<Stamp> {
+    let metadata = fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+#[derive(Debug, Clone)]
+pub struct RomWatcher {
+    path: PathBuf,
+    // the build that's running
+    loaded: Option<Stamp>,
+    // what the last check saw, so a build still being written waits
+    seen: Option<Stamp>,
+    frames: u32,
+}
+
+impl RomWatcher {
+    pub fn new(path: &Path) -> Self {
+        let loaded = stamp(path);
+        RomWatcher {
+            path: path.to_path_buf(),
+            loaded,
+            seen: loaded,
+            frames: 0,
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    // Once a frame; whether there's a new build to load. A file that's
+    // gone, mid-build say, is waited out.
+    pub fn poll(&mut self) -> bool {
+        self.frames += 1;
+        if self.frames < POLL_FRAMES {
+            return false;
+        }
+        self.frames = 0;
+        let now = stamp(&self.path);
+        let settled = now.is_some() && now == self.seen && now != self.loaded;
+        self.seen = now;
+        if settled {
+            self.loaded = now;
+        }
+        settled
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn poll(watcher: &mut RomWatcher) -> bool {
+        (0..POLL_FRAMES).fold(false, |changed, _| changed | watcher.poll())
+    }
+
+    #[test]
+    fn test_waits_for_a_build_to_settle() {
+        let path = std::env::temp_dir().join(format!("nes-watch-{}.nes", std::process::id()));
+        fs::write(&path, b"first").unwrap();
+        let mut watcher = RomWatcher::new(&path);
+        assert!(!poll(&mut watcher));
+
+        fs::write(&path, b"second build").unwrap();
+        // seen once, then the same again
+        assert!(!poll(&mut watcher));
+        assert!(poll(&mut watcher));
+        assert!(!poll(&mut watcher));
+
+        fs::remove_file(&path).unwrap();
+        assert!(!poll(&mut watcher));
+        assert!(!poll(&mut watcher));
+        assert_eq!(watcher.path(), path);
+    }
+}
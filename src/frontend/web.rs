@@ -0,0 +1,96 @@
+// wasm-bindgen bindings for running in a browser. The page owns everything
+// a desktop front end would: it paces frames with requestAnimationFrame,
+// draws the RGBA buffer to a canvas and feeds the samples to WebAudio. See
+// examples/web for a minimal page.
+
+use wasm_bindgen::prelude::*;
+
+use crate::cartridge::Cartridge;
+use crate::emulator::Emulator;
+use crate::input::joypad::JoypadButton;
+use crate::input::keymap::MAX_PLAYERS;
+use crate::render::frame::Frame;
+
+#[wasm_bindgen]
+pub struct WebEmulator {
+    emulator: Emulator,
+    rgba: Vec<u8>,
+}
+
+#[wasm_bindgen]
+impl WebEmulator {
+    // iNES bytes, e.g. from a file input or fetch(). Taken by value, so
+    // the copy out of JS memory is the only one.
+    #[wasm_bindgen(js_name = loadRom)]
+    pub fn load_rom(rom: Vec<u8>) -> Result<WebEmulator, JsError> {
+        let emulator = Cartridge::from_rom(rom)
+            .and_then(Emulator::new)
+            .map_err(|err| JsError::new(&err.to_string()))?;
+        Ok(WebEmulator {
+            emulator,
+            rgba: vec![0xFF; Frame::WIDTH * Frame::HEIGHT * 4],
+        })
+    }
+
+    pub fn width() -> usize {
+        Frame::WIDTH
+    }
+
+    pub fn height() -> usize {
+        Frame::HEIGHT
+    }
+
+    pub fn reset(&mut self) {
+        self.emulator.reset();
+    }
+
+    // Runs one frame and converts it to RGBA for ImageData.
+    #[wasm_bindgen(js_name = runFrame)]
+    pub fn run_frame(&mut self) {
+        self.emulator.run_frame();
+        for (rgba, rgb) in self
+            .rgba
+            .chunks_exact_mut(4)
+            .zip(self.emulator.frame().data.chunks_exact(3))
+        {
+            rgba[..3].copy_from_slice(rgb);
+        }
+    }
+
+    // `buttons` is the joypad byte: A, B, Select, Start, Up, Down, Left,
+    // Right from bit 0 up.
+    #[wasm_bindgen(js_name = setButtons)]
+    pub fn set_buttons(&mut self, player: usize, buttons: u8) {
+        if player < MAX_PLAYERS {
+            self.emulator
+                .set_buttons(player, JoypadButton::from_bits_truncate(buttons));
+        }
+    }
+
+    // A copy of the last frame as RGBA.
+    #[wasm_bindgen(js_name = frameBuffer)]
+    pub fn frame_buffer(&self) -> Vec<u8> {
+        self.rgba.clone()
+    }
+
+    // The same buffer without a copy, for a Uint8ClampedArray view over the
+    // module's memory. It stays put for the emulator's lifetime.
+    #[wasm_bindgen(js_name = frameBufferPtr)]
+    pub fn frame_buffer_ptr(&self) -> *const u8 {
+        self.rgba.as_ptr()
+    }
+
+    // Call once with the AudioContext's sample rate.
+    #[wasm_bindgen(js_name = enableAudio)]
+    pub fn enable_audio(&mut self, sample_rate: u32) {
+        self.emulator.enable_audio(sample_rate);
+    }
+
+    // Mono samples produced since the last call.
+    #[wasm_bindgen(js_name = takeAudio)]
+    pub fn take_audio(&mut self) -> Vec<f32> {
+        let mut samples = Vec::new();
+        self.emulator.read_audio(&mut samples);
+        samples
+    }
+}
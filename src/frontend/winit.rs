@@ -0,0 +1,310 @@
+// Pure-Rust desktop front end for systems without SDL2: winit for the window
+// and keyboard, pixels (wgpu underneath) for the frame texture, which is
+// always drawn through a PostProcess pass into the shared viewport. Game
+// controllers go through gilrs when that feature is enabled; without it the
+// front end is keyboard-only.
+
+use std::sync::Arc;
+use std::time::Instant;
+
+use pixels::{Pixels, PixelsBuilder, SurfaceTexture};
+use winit::application::ApplicationHandler;
+use winit::dpi::{LogicalSize, PhysicalPosition, PhysicalSize};
+use winit::event::{ElementState, WindowEvent};
+use winit::event_loop::{ActiveEventLoop, ControlFlow, EventLoop};
+use winit::keyboard::{KeyCode, PhysicalKey};
+use winit::monitor::MonitorHandle;
+use winit::window::{Fullscreen, Window, WindowId};
+
+use super::hotkeys::Action;
+use super::post_process::PostProcess;
+use super::{default_sink, FullscreenMode, Geometry, Options, Pacing, Session, Title, WindowState};
+use crate::emulator::Emulator;
+#[cfg(feature = "gilrs")]
+use crate::input::gilrs_backend::GilrsBackend;
+use crate::render::frame::Frame;
+use crate::render::shader::{PostShader, ShaderPreset};
+use crate::render::viewport::Scaling;
+
+// Key names as KeyBindings spells them ("Z", "Return", "Left", "RShift").
+fn key_name(code: KeyCode) -> String {
+    let name = match code {
+        KeyCode::Enter => "Return",
+        KeyCode::ArrowUp => "Up",
+        KeyCode::ArrowDown => "Down",
+        KeyCode::ArrowLeft => "Left",
+        KeyCode::ArrowRight => "Right",
+        KeyCode::ShiftLeft => "LShift",
+        KeyCode::ShiftRight => "RShift",
+        KeyCode::ControlLeft => "LCtrl",
+        KeyCode::ControlRight => "RCtrl",
+        KeyCode::AltLeft => "LAlt",
+        KeyCode::AltRight => "RAlt",
+        code => {
+            // KeyZ, Digit1, F1, Space, Backspace...
+            let debug = format!("{:?}", code);
+            return debug
+                .strip_prefix("Key")
+                .or_else(|| debug.strip_prefix("Digit"))
+                .unwrap_or(&debug)
+                .to_string();
+        }
+    };
+    name.to_string()
+}
+
+// The monitor's own resolution at its highest refresh rate.
+fn exclusive_mode(monitor: MonitorHandle) -> Option<Fullscreen> {
+    let size = monitor.size();
+    monitor
+        .video_modes()
+        .filter(|mode| mode.size() == size)
+        .max_by_key(|mode| mode.refresh_rate_millihertz())
+        .map(Fullscreen::Exclusive)
+}
+
+struct App {
+    title: Title,
+    scale: u32,
+    start_fullscreen: bool,
+    session: Session,
+    window: Option<Arc<Window>>,
+    pixels: Option<Pixels<'static>>,
+    scaling: Scaling,
+    shader: PostShader,
+    post_process: Option<PostProcess>,
+    window_state: WindowState,
+    #[cfg(feature = "gilrs")]
+    gamepad_backend: Option<GilrsBackend>,
+    error: Option<String>,
+}
+
+impl App {
+    fn fail(&mut self, event_loop: &ActiveEventLoop, err: String) {
+        self.error = Some(err);
+        event_loop.exit();
+    }
+
+    // Exclusive falls back to borderless on monitors without a usable mode.
+    // Wayland won't say where a window is, so there it stays where the
+    // compositor puts it.
+    fn toggle_fullscreen(&mut self) {
+        let Some(window) = self.window.as_ref() else {
+            return;
+        };
+        if let Some(windowed) = self.window_state.leave() {
+            window.set_fullscreen(None);
+            let (width, height) = windowed.size;
+            let _ = window.request_inner_size(PhysicalSize::new(width, height));
+            if let Some((x, y)) = windowed.position {
+                window.set_outer_position(PhysicalPosition::new(x, y));
+            }
+            return;
+        }
+
+        let size = window.inner_size();
+        self.window_state.enter(Geometry {
+            position: window.outer_position().ok().map(|p| (p.x, p.y)),
+            size: (size.width, size.height),
+        });
+        let fullscreen = match self.window_state.mode {
+            FullscreenMode::Borderless => None,
+            FullscreenMode::Exclusive => window.current_monitor().and_then(exclusive_mode),
+        };
+        window.set_fullscreen(Some(fullscreen.unwrap_or(Fullscreen::Borderless(None))));
+    }
+
+    // Emulates into the pixel buffer and asks for it to be drawn.
+    fn step(&mut self) {
+        let frame = self.session.run_frame();
+        if let Some(pixels) = self.pixels.as_mut() {
+            // pixels wants RGBA
+            for (rgba, rgb) in pixels
+                .frame_mut()
+                .chunks_exact_mut(4)
+                .zip(frame.data.chunks_exact(3))
+            {
+                rgba[..3].copy_from_slice(rgb);
+                rgba[3] = 0xFF;
+            }
+        }
+        if let Some(window) = self.window.as_ref() {
+            if let Some(title) = self.title.update(&self.session) {
+                window.set_title(title);
+            }
+            window.request_redraw();
+        }
+    }
+}
+
+impl ApplicationHandler for App {
+    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
+        if self.window.is_some() {
+            return;
+        }
+
+        let (width, height) = self.scaling.window_size(self.scale);
+        let (min_width, min_height) = self.scaling.window_size(1);
+        let attributes = Window::default_attributes()
+            .with_title(self.title.update(&self.session).unwrap_or("nes"))
+            .with_inner_size(LogicalSize::new(width, height))
+            .with_min_inner_size(LogicalSize::new(min_width, min_height));
+        let window = match event_loop.create_window(attributes) {
+            Ok(window) => Arc::new(window),
+            Err(err) => return self.fail(event_loop, err.to_string()),
+        };
+
+        let inner = window.inner_size();
+        let surface = SurfaceTexture::new(inner.width, inner.height, window.clone());
+        // with vsync on, presenting waits for the display and paces
+        // everything; otherwise it would fight the timer
+        let pixels = PixelsBuilder::new(Frame::WIDTH as u32, Frame::HEIGHT as u32, surface)
+            .enable_vsync(self.session.pacing() == Pacing::Vsync)
+            .build();
+        let pixels = match pixels {
+            Ok(pixels) => pixels,
+            Err(err) => return self.fail(event_loop, err.to_string()),
+        };
+        match PostProcess::new(&pixels, &self.shader) {
+            Ok(post_process) => self.post_process = Some(post_process),
+            Err(err) => return self.fail(event_loop, err),
+        }
+        window.request_redraw();
+        self.pixels = Some(pixels);
+        self.window = Some(window);
+        if self.start_fullscreen {
+            self.toggle_fullscreen();
+        }
+    }
+
+    fn window_event(&mut self, event_loop: &ActiveEventLoop, _id: WindowId, event: WindowEvent) {
+        match event {
+            WindowEvent::CloseRequested => event_loop.exit(),
+            WindowEvent::KeyboardInput { event, .. } => {
+                let PhysicalKey::Code(code) = event.physical_key else {
+                    return;
+                };
+                match event.state {
+                    ElementState::Pressed if !event.repeat => {
+                        match self.session.key_down(&key_name(code)) {
+                            Some(Action::Quit) => event_loop.exit(),
+                            Some(Action::Fullscreen) => self.toggle_fullscreen(),
+                            _ => {}
+                        }
+                    }
+                    ElementState::Pressed => {}
+                    ElementState::Released => self.session.key_up(&key_name(code)),
+                }
+            }
+            WindowEvent::Focused(false) => self.session.release_keys(),
+            WindowEvent::DroppedFile(path) => {
+                if let Err(err) = self.session.load_rom(&path) {
+                    eprintln!("can't load {}: {}", path.display(), err);
+                    self.session.show_message("Can't load ROM");
+                }
+            }
+            WindowEvent::Resized(size) => {
+                if let Some(pixels) = self.pixels.as_mut() {
+                    if let Err(err) = pixels.resize_surface(size.width, size.height) {
+                        self.fail(event_loop, err.to_string());
+                    }
+                }
+            }
+            WindowEvent::RedrawRequested => {
+                let (Some(pixels), Some(post_process), Some(window)) = (
+                    self.pixels.as_ref(),
+                    self.post_process.as_mut(),
+                    self.window.as_ref(),
+                ) else {
+                    return;
+                };
+                let size = window.inner_size();
+                let view = self.scaling.viewport(size.width, size.height);
+                let frame_count = self.session.emulator.frame_count();
+                let result = pixels.render_with(|encoder, target, context| {
+                    post_process.render(encoder, target, context, view, frame_count);
+                    Ok(())
+                });
+                if let Err(err) = result {
+                    return self.fail(event_loop, err.to_string());
+                }
+                if self.session.take_capture() {
+                    let saved = post_process
+                        .capture(pixels, view, frame_count)
+                        .and_then(|rgb| {
+                            self.session
+                                .save_capture(view.width, view.height, &rgb)
+                                .map_err(|err| err.to_string())
+                        });
+                    if let Err(err) = saved {
+                        eprintln!("can't save screenshot: {}", err);
+                        self.session.show_message("Can't save screenshot");
+                    }
+                }
+                if self.session.pacing() == Pacing::Vsync {
+                    self.step();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn about_to_wait(&mut self, event_loop: &ActiveEventLoop) {
+        #[cfg(feature = "gilrs")]
+        if let Some(backend) = self.gamepad_backend.as_mut() {
+            self.session.input.gamepads.poll(backend);
+        }
+
+        if self.session.pacing() == Pacing::Vsync {
+            event_loop.set_control_flow(ControlFlow::Wait);
+            return;
+        }
+        let now = Instant::now();
+        if self.session.is_due(now) {
+            self.step();
+            self.session.advance(now);
+        }
+        event_loop.set_control_flow(ControlFlow::WaitUntil(self.session.deadline(now)));
+    }
+}
+
+// Runs until the window is closed or the quit hotkey is pressed.
+pub fn run(emulator: Emulator, title: &str, options: Options) -> Result<(), String> {
+    let event_loop = EventLoop::new().map_err(|err| err.to_string())?;
+    let scale = options.scale;
+    let scaling = options.scaling;
+    let window_state = WindowState::new(options.fullscreen_mode);
+    let start_fullscreen = options.fullscreen;
+    // the passthrough shader is plain scaling
+    let shader = options
+        .shader
+        .clone()
+        .unwrap_or_else(|| PostShader::preset(ShaderPreset::Passthrough));
+    let sink = default_sink(options.sample_rate, options.audio_latency.buffer);
+
+    let mut app = App {
+        title: Title::new(title),
+        scale,
+        start_fullscreen,
+        session: Session::new(emulator, options, sink),
+        window: None,
+        pixels: None,
+        scaling,
+        shader,
+        post_process: None,
+        window_state,
+        #[cfg(feature = "gilrs")]
+        gamepad_backend: GilrsBackend::new()
+            .map_err(|err| eprintln!("no gamepads: {}", err))
+            .ok(),
+        error: None,
+    };
+    event_loop
+        .run_app(&mut app)
+        .map_err(|err| err.to_string())?;
+
+    match app.error {
+        Some(err) => Err(err),
+        None => Ok(()),
+    }
+}
@@ -0,0 +1,331 @@
+// Family BASIC keyboard, on the Famicom expansion port. The 72 keys sit in
+// a 9 row x 2 column matrix of 4 keys each. Writes to $4016:
+//
+//     bit 0: reset to row 0
+//     bit 1: column select; going from 1 back to 0 moves to the next row
+//     bit 2: keyboard enable
+//
+// $4017 reads return the selected half row in bits 1-4, 0 meaning pressed.
+// Past the last row everything reads 0, which is how software detects the
+// keyboard.
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FamilyKey {
+    F1,
+    F2,
+    F3,
+    F4,
+    F5,
+    F6,
+    F7,
+    F8,
+    Num1,
+    Num2,
+    Num3,
+    Num4,
+    Num5,
+    Num6,
+    Num7,
+    Num8,
+    Num9,
+    Num0,
+    Minus,
+    Caret,
+    Yen,
+    Stop,
+    Escape,
+    Q,
+    W,
+    E,
+    R,
+    T,
+    Y,
+    U,
+    I,
+    O,
+    P,
+    At,
+    LeftBracket,
+    Return,
+    Ctrl,
+    A,
+    S,
+    D,
+    F,
+    G,
+    H,
+    J,
+    K,
+    L,
+    Semicolon,
+    Colon,
+    RightBracket,
+    Kana,
+    LeftShift,
+    Z,
+    X,
+    C,
+    V,
+    B,
+    N,
+    M,
+    Comma,
+    Period,
+    Slash,
+    Underscore,
+    RightShift,
+    Graph,
+    Space,
+    ClrHome,
+    Insert,
+    Delete,
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+use FamilyKey::*;
+
+// [row][column] -> keys for $4017 bits 4, 3, 2, 1
+const MATRIX: [[[FamilyKey; 4]; 2]; 9] = [
+    [
+        [RightBracket, LeftBracket, Return, F8],
+        [Stop, Yen, RightShift, Kana],
+    ],
+    [
+        [Semicolon, Colon, At, F7],
+        [Caret, Minus, Slash, Underscore],
+    ],
+    [[K, L, O, F6], [Num0, P, Comma, Period]],
+    [[J, U, I, F5], [Num8, Num9, N, M]],
+    [[H, G, Y, F4], [Num6, Num7, V, B]],
+    [[D, R, T, F3], [Num4, Num5, C, F]],
+    [[A, S, W, F2], [Num3, E, Z, X]],
+    [[Ctrl, Q, Escape, F1], [Num2, Num1, Graph, LeftShift]],
+    [[Left, Right, Up, ClrHome], [Insert, Delete, Space, Down]],
+];
+
+impl FamilyKey {
+    // Host key names as used by the key bindings, for keyboard pass-through.
+    pub fn from_name(name: &str) -> Option<Self> {
+        let name = name.to_ascii_lowercase();
+        let key = match name.as_str() {
+            "f1" => F1,
+            "f2" => F2,
+            "f3" => F3,
+            "f4" => F4,
+            "f5" => F5,
+            "f6" => F6,
+            "f7" => F7,
+            "f8" => F8,
+            "1" => Num1,
+            "2" => Num2,
+            "3" => Num3,
+            "4" => Num4,
+            "5" => Num5,
+            "6" => Num6,
+            "7" => Num7,
+            "8" => Num8,
+            "9" => Num9,
+            "0" => Num0,
+            "-" | "minus" => Minus,
+            "=" | "equals" => Caret,
+            "\\" | "backslash" => Yen,
+            "end" | "pause" => Stop,
+            "escape" => Escape,
+            "q" => Q,
+            "w" => W,
+            "e" => E,
+            "r" => R,
+            "t" => T,
+            "y" => Y,
+            "u" => U,
+            "i" => I,
+            "o" => O,
+            "p" => P,
+            "[" | "leftbracket" => At,
+            "]" | "rightbracket" => LeftBracket,
+            "return" | "enter" => Return,
+            "lctrl" | "rctrl" | "ctrl" => Ctrl,
+            "a" => A,
+            "s" => S,
+            "d" => D,
+            "f" => F,
+            "g" => G,
+            "h" => H,
+            "j" => J,
+            "k" => K,
+            "l" => L,
+            ";" | "semicolon" => Semicolon,
+            "'" | "quote" => Colon,
+            "#" | "hash" => RightBracket,
+            "ralt" | "pagedown" => Kana,
+            "lshift" => LeftShift,
+            "z" => Z,
+            "x" => X,
+            "c" => C,
+            "v" => V,
+            "b" => B,
+            "n" => N,
+            "m" => M,
+            "," | "comma" => Comma,
+            "." | "period" => Period,
+            "/" | "slash" => Slash,
+            "`" | "backquote" => Underscore,
+            "rshift" => RightShift,
+            "lalt" => Graph,
+            "space" => Space,
+            "home" => ClrHome,
+            "insert" => Insert,
+            "delete" | "backspace" => Delete,
+            "up" => Up,
+            "down" => Down,
+            "left" => Left,
+            "right" => Right,
+            _ => return None,
+        };
+        Some(key)
+    }
+
+    fn position(self) -> (usize, usize, usize) {
+        for (row, columns) in MATRIX.iter().enumerate() {
+            for (column, keys) in columns.iter().enumerate() {
+                if let Some(bit) = keys.iter().position(|k| *k == self) {
+                    return (row, column, bit);
+                }
+            }
+        }
+        unreachable!("every key is in the matrix")
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct FamilyKeyboard {
+    // one bit per key, set while held, laid out as [row] = column 1 in the
+    // high nibble, column 0 in the low nibble
+    pressed: [u8; 9],
+    row: usize,
+    column: usize,
+    enabled: bool,
+}
+
+impl FamilyKeyboard {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set_key(&mut self, key: FamilyKey, pressed: bool) {
+        let (row, column, bit) = key.position();
+        let mask = 1 << (column * 4 + 3 - bit);
+        if pressed {
+            self.pressed[row] |= mask;
+        } else {
+            self.pressed[row] &= !mask;
+        }
+    }
+
+    pub fn is_pressed(&self, key: FamilyKey) -> bool {
+        let (row, column, bit) = key.position();
+        self.pressed[row] & (1 << (column * 4 + 3 - bit)) != 0
+    }
+
+    pub fn release_all(&mut self) {
+        self.pressed = [0; 9];
+    }
+
+    // Pass-through of a host key event; returns false for keys with no
+    // counterpart on the Famicom keyboard.
+    pub fn host_key(&mut self, name: &str, pressed: bool) -> bool {
+        match FamilyKey::from_name(name) {
+            Some(key) => {
+                self.set_key(key, pressed);
+                true
+            }
+            None => false,
+        }
+    }
+
+    // $4016 write
+    pub fn write(&mut self, value: u8) {
+        let column = ((value >> 1) & 1) as usize;
+        if self.column == 1 && column == 0 {
+            self.row = self.row.saturating_add(1).min(MATRIX.len());
+        }
+        self.column = column;
+        if value & 1 != 0 {
+            self.row = 0;
+        }
+        self.enabled = value & 0b100 != 0;
+    }
+
+    // Bits 1-4 of a $4017 read.
+    pub fn read(&self) -> u8 {
+        if !self.enabled || self.row >= MATRIX.len() {
+            return 0;
+        }
+        let held = (self.pressed[self.row] >> ((self.column & 1) * 4)) & 0x0F;
+        (!held & 0x0F) << 1
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // Scan the whole matrix the way Family BASIC does and return each half
+    // row's inverted key bits.
+    fn scan(keyboard: &mut FamilyKeyboard) -> Vec<u8> {
+        let mut rows = Vec::new();
+        keyboard.write(0b101);
+        for _ in 0..10 {
+            keyboard.write(0b100);
+            rows.push(keyboard.read());
+            keyboard.write(0b110);
+            rows.push(keyboard.read());
+        }
+        rows
+    }
+
+    #[test]
+    fn test_idle_scan() {
+        let mut keyboard = FamilyKeyboard::new();
+        let rows = scan(&mut keyboard);
+        assert!(rows[..18].iter().all(|&r| r == 0b1_1110));
+        // the detection row
+        assert_eq!(&rows[18..], &[0, 0]);
+    }
+
+    #[test]
+    fn test_key_positions() {
+        let mut keyboard = FamilyKeyboard::new();
+        keyboard.set_key(FamilyKey::Return, true);
+        keyboard.set_key(FamilyKey::Space, true);
+        let rows = scan(&mut keyboard);
+
+        // Return: row 0, column 0, bit 2
+        assert_eq!(rows[0], 0b1_1010);
+        // Space: row 8, column 1, bit 2
+        assert_eq!(rows[17], 0b1_1010);
+        assert!(keyboard.is_pressed(FamilyKey::Return));
+    }
+
+    #[test]
+    fn test_disabled_reads_zero() {
+        let mut keyboard = FamilyKeyboard::new();
+        keyboard.write(0b001);
+        assert_eq!(keyboard.read(), 0);
+    }
+
+    #[test]
+    fn test_host_pass_through() {
+        let mut keyboard = FamilyKeyboard::new();
+        assert!(keyboard.host_key("A", true));
+        assert!(!keyboard.host_key("F12", true));
+        assert!(keyboard.is_pressed(FamilyKey::A));
+        keyboard.host_key("a", false);
+        assert!(!keyboard.is_pressed(FamilyKey::A));
+    }
+}
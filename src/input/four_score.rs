@@ -0,0 +1,93 @@
+// Four Score / NES Satellite multitap. It plugs into both ports: each port
+// reads out 8 bits of its own pad, 8 bits of the pad behind it (3 behind 1,
+// 4 behind 2), then an 8-bit signature games use to detect the adapter.
+
+use serde::{Deserialize, Serialize};
+
+use super::joypad::Joypad;
+
+// read LSB first: bit 19 set on $4016, bit 18 on $4017
+const SIGNATURES: [u8; 2] = [0b0000_1000, 0b0000_0100];
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct FourScore {
+    pub pads: [Joypad; 4],
+    strobe: bool,
+    reads: [u8; 2],
+}
+
+impl FourScore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn write(&mut self, value: u8) {
+        for pad in self.pads.iter_mut() {
+            pad.write(value);
+        }
+        self.strobe = value & 1 == 1;
+        if self.strobe {
+            self.reads = [0, 0];
+        }
+    }
+
+    // `side` is 0 for $4016, 1 for $4017.
+    pub fn read(&mut self, side: usize) -> u8 {
+        let n = self.reads[side];
+        let bit = match n {
+            0..=7 => self.pads[side].read(),
+            8..=15 => self.pads[side + 2].read(),
+            16..=23 => (SIGNATURES[side] >> (n - 16)) & 1,
+            _ => 1,
+        };
+        if !self.strobe && n < 24 {
+            self.reads[side] += 1;
+        }
+        bit
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::input::joypad::JoypadButton;
+
+    fn read_side(four_score: &mut FourScore, side: usize) -> Vec<u8> {
+        (0..24).map(|_| four_score.read(side)).collect()
+    }
+
+    #[test]
+    fn test_reads_two_pads_then_signature_per_port() {
+        let mut four_score = FourScore::new();
+        four_score.pads[0].set_buttons(JoypadButton::BUTTON_A);
+        four_score.pads[2].set_buttons(JoypadButton::BUTTON_B);
+        four_score.pads[3].set_buttons(JoypadButton::RIGHT);
+        four_score.write(1);
+        four_score.write(0);
+
+        let port1 = read_side(&mut four_score, 0);
+        assert_eq!(&port1[0..8], &[1, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(&port1[8..16], &[0, 1, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(&port1[16..24], &[0, 0, 0, 1, 0, 0, 0, 0]);
+
+        let port2 = read_side(&mut four_score, 1);
+        assert_eq!(&port2[0..8], &[0; 8]);
+        assert_eq!(&port2[8..16], &[0, 0, 0, 0, 0, 0, 0, 1]);
+        assert_eq!(&port2[16..24], &[0, 0, 1, 0, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_restrobe_restarts_sequence() {
+        let mut four_score = FourScore::new();
+        four_score.pads[0].set_buttons(JoypadButton::BUTTON_A);
+        four_score.write(1);
+        four_score.write(0);
+        read_side(&mut four_score, 0);
+        assert_eq!(four_score.read(0), 1); // past the signature
+
+        four_score.write(1);
+        four_score.write(0);
+        assert_eq!(four_score.read(0), 1);
+        assert_eq!(four_score.read(0), 0);
+    }
+}
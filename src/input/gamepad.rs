@@ -0,0 +1,329 @@
+// Physical game controllers. A GamepadBackend reports device events in a
+// backend-neutral form; GamepadInput assigns devices to players as they are
+// plugged in and turns their buttons and sticks into NES button states.
+
+use std::collections::HashMap;
+
+use super::joypad::JoypadButton;
+use super::keymap::MAX_PLAYERS;
+
+pub type GamepadId = usize;
+
+// Positional names, so South is the bottom face button whatever it is
+// labelled on a particular pad.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PadButton {
+    South,
+    East,
+    North,
+    West,
+    LeftTrigger,
+    RightTrigger,
+    Select,
+    Start,
+    DPadUp,
+    DPadDown,
+    DPadLeft,
+    DPadRight,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PadAxis {
+    LeftStickX,
+    LeftStickY,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum GamepadEvent {
+    Connected {
+        id: GamepadId,
+        name: String,
+    },
+    Disconnected {
+        id: GamepadId,
+    },
+    Button {
+        id: GamepadId,
+        button: PadButton,
+        pressed: bool,
+    },
+    // -1.0 is left/down, 1.0 is right/up
+    Axis {
+        id: GamepadId,
+        axis: PadAxis,
+        value: f32,
+    },
+}
+
+pub trait GamepadBackend {
+    // Drain everything that happened since the last poll. Devices already
+    // present when the backend starts are reported as Connected too.
+    fn poll(&mut self, events: &mut Vec<GamepadEvent>);
+}
+
+#[derive(Debug, Clone)]
+pub struct GamepadMapping {
+    pub buttons: HashMap<PadButton, JoypadButton>,
+    pub stick_enabled: bool,
+    pub dead_zone: f32,
+}
+
+impl GamepadMapping {
+    // B and A sit left and right on the NES pad, which lines up with
+    // West/South and South/East on most modern layouts.
+    pub fn new() -> Self {
+        let buttons = [
+            (PadButton::South, JoypadButton::BUTTON_B),
+            (PadButton::West, JoypadButton::BUTTON_B),
+            (PadButton::East, JoypadButton::BUTTON_A),
+            (PadButton::North, JoypadButton::BUTTON_A),
+            (PadButton::Select, JoypadButton::SELECT),
+            (PadButton::Start, JoypadButton::START),
+            (PadButton::DPadUp, JoypadButton::UP),
+            (PadButton::DPadDown, JoypadButton::DOWN),
+            (PadButton::DPadLeft, JoypadButton::LEFT),
+            (PadButton::DPadRight, JoypadButton::RIGHT),
+        ];
+        GamepadMapping {
+            buttons: buttons.into_iter().collect(),
+            stick_enabled: true,
+            dead_zone: 0.5,
+        }
+    }
+}
+
+impl Default for GamepadMapping {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ConnectedPad {
+    pub name: String,
+    pub player: Option<usize>,
+    pressed: Vec<PadButton>,
+    stick: (f32, f32),
+}
+
+#[derive(Debug, Default)]
+pub struct GamepadInput {
+    pub mapping: GamepadMapping,
+    pads: HashMap<GamepadId, ConnectedPad>,
+    // players whose pad was unplugged keep their slot until something else
+    // claims it, so a reconnect lands on the same player
+    last_owner: [Option<String>; MAX_PLAYERS],
+}
+
+impl GamepadInput {
+    pub fn new(mapping: GamepadMapping) -> Self {
+        GamepadInput {
+            mapping,
+            pads: HashMap::new(),
+            last_owner: Default::default(),
+        }
+    }
+
+    pub fn poll(&mut self, backend: &mut dyn GamepadBackend) {
+        let mut events = Vec::new();
+        backend.poll(&mut events);
+        for event in events {
+            self.handle(event);
+        }
+    }
+
+    pub fn handle(&mut self, event: GamepadEvent) {
+        match event {
+            GamepadEvent::Connected { id, name } => {
+                let player = self.free_player_for(&name);
+                if let Some(player) = player {
+                    self.last_owner[player] = Some(name.clone());
+                }
+                self.pads.insert(
+                    id,
+                    ConnectedPad {
+                        name,
+                        player,
+                        pressed: Vec::new(),
+                        stick: (0.0, 0.0),
+                    },
+                );
+            }
+            GamepadEvent::Disconnected { id } => {
+                self.pads.remove(&id);
+            }
+            GamepadEvent::Button {
+                id,
+                button,
+                pressed,
+            } => {
+                if let Some(pad) = self.pads.get_mut(&id) {
+                    pad.pressed.retain(|b| *b != button);
+                    if pressed {
+                        pad.pressed.push(button);
+                    }
+                }
+            }
+            GamepadEvent::Axis { id, axis, value } => {
+                if let Some(pad) = self.pads.get_mut(&id) {
+                    match axis {
+                        PadAxis::LeftStickX => pad.stick.0 = value,
+                        PadAxis::LeftStickY => pad.stick.1 = value,
+                    }
+                }
+            }
+        }
+    }
+
+    fn free_player_for(&self, name: &str) -> Option<usize> {
+        let taken = |player: usize| self.pads.values().any(|p| p.player == Some(player));
+        let returning =
+            (0..MAX_PLAYERS).find(|&p| !taken(p) && self.last_owner[p].as_deref() == Some(name));
+        returning.or_else(|| {
+            (0..MAX_PLAYERS)
+                .filter(|&p| !taken(p))
+                .min_by_key(|&p| self.last_owner[p].is_some())
+        })
+    }
+
+    // Moves a pad to another player; whatever pad held that player swaps
+    // into the old slot.
+    pub fn assign(&mut self, id: GamepadId, player: Option<usize>) {
+        let Some(previous) = self.pads.get(&id).map(|p| p.player) else {
+            return;
+        };
+        if let Some(player) = player {
+            assert!(player < MAX_PLAYERS, "player {} out of range", player);
+            for pad in self.pads.values_mut() {
+                if pad.player == Some(player) {
+                    pad.player = previous;
+                }
+            }
+        }
+        let pad = self.pads.get_mut(&id).unwrap();
+        pad.player = player;
+        if let Some(player) = player {
+            self.last_owner[player] = Some(pad.name.clone());
+        }
+    }
+
+    pub fn player_for(&self, id: GamepadId) -> Option<usize> {
+        self.pads.get(&id).and_then(|p| p.player)
+    }
+
+    pub fn connected(&self) -> impl Iterator<Item = (GamepadId, &ConnectedPad)> {
+        self.pads.iter().map(|(id, pad)| (*id, pad))
+    }
+
+    pub fn buttons(&self, player: usize) -> JoypadButton {
+        let mut buttons = JoypadButton::empty();
+        for pad in self.pads.values().filter(|p| p.player == Some(player)) {
+            for pressed in &pad.pressed {
+                if let Some(button) = self.mapping.buttons.get(pressed) {
+                    buttons |= *button;
+                }
+            }
+            if self.mapping.stick_enabled {
+                let (x, y) = pad.stick;
+                let dead_zone = self.mapping.dead_zone;
+                if x <= -dead_zone {
+                    buttons |= JoypadButton::LEFT;
+                }
+                if x >= dead_zone {
+                    buttons |= JoypadButton::RIGHT;
+                }
+                if y <= -dead_zone {
+                    buttons |= JoypadButton::DOWN;
+                }
+                if y >= dead_zone {
+                    buttons |= JoypadButton::UP;
+                }
+            }
+        }
+        buttons
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn connect(input: &mut GamepadInput, id: GamepadId, name: &str) {
+        input.handle(GamepadEvent::Connected {
+            id,
+            name: name.to_string(),
+        });
+    }
+
+    fn press(input: &mut GamepadInput, id: GamepadId, button: PadButton, pressed: bool) {
+        input.handle(GamepadEvent::Button {
+            id,
+            button,
+            pressed,
+        });
+    }
+
+    #[test]
+    fn test_hot_plug_assigns_players_in_order() {
+        let mut input = GamepadInput::default();
+        connect(&mut input, 7, "pad a");
+        connect(&mut input, 3, "pad b");
+
+        assert_eq!(input.player_for(7), Some(0));
+        assert_eq!(input.player_for(3), Some(1));
+
+        press(&mut input, 3, PadButton::Start, true);
+        assert_eq!(input.buttons(1), JoypadButton::START);
+        assert_eq!(input.buttons(0), JoypadButton::empty());
+    }
+
+    #[test]
+    fn test_reconnect_keeps_player() {
+        let mut input = GamepadInput::default();
+        connect(&mut input, 0, "pad a");
+        connect(&mut input, 1, "pad b");
+        input.handle(GamepadEvent::Disconnected { id: 0 });
+        connect(&mut input, 2, "pad c");
+        connect(&mut input, 3, "pad a");
+
+        // pad c skips the slot pad a left behind
+        assert_eq!(input.player_for(2), Some(2));
+        assert_eq!(input.player_for(3), Some(0));
+    }
+
+    #[test]
+    fn test_assign_swaps_players() {
+        let mut input = GamepadInput::default();
+        connect(&mut input, 0, "pad a");
+        connect(&mut input, 1, "pad b");
+        input.assign(1, Some(0));
+
+        assert_eq!(input.player_for(1), Some(0));
+        assert_eq!(input.player_for(0), Some(1));
+    }
+
+    #[test]
+    fn test_default_mapping_and_stick() {
+        let mut input = GamepadInput::default();
+        connect(&mut input, 0, "pad");
+        press(&mut input, 0, PadButton::East, true);
+        press(&mut input, 0, PadButton::DPadUp, true);
+        input.handle(GamepadEvent::Axis {
+            id: 0,
+            axis: PadAxis::LeftStickX,
+            value: -0.9,
+        });
+        assert_eq!(
+            input.buttons(0),
+            JoypadButton::BUTTON_A | JoypadButton::UP | JoypadButton::LEFT
+        );
+
+        press(&mut input, 0, PadButton::East, false);
+        input.handle(GamepadEvent::Axis {
+            id: 0,
+            axis: PadAxis::LeftStickX,
+            value: 0.2,
+        });
+        assert_eq!(input.buttons(0), JoypadButton::UP);
+    }
+}
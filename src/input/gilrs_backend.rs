@@ -0,0 +1,98 @@
+// GamepadBackend on top of gilrs, which picks up connect/disconnect events
+// and ships its own SDL-style mapping database.
+
+use gilrs::{Axis, Button, EventType, Gilrs};
+
+use super::gamepad::{GamepadBackend, GamepadEvent, PadAxis, PadButton};
+
+pub struct GilrsBackend {
+    gilrs: Gilrs,
+    // gilrs only reports devices plugged in before startup by listing them
+    announced: bool,
+}
+
+impl GilrsBackend {
+    pub fn new() -> Result<Self, Box<gilrs::Error>> {
+        let gilrs = match Gilrs::new() {
+            Ok(gilrs) => gilrs,
+            // no backend for this platform; the dummy context never reports
+            // anything, which is fine for an optional input path
+            Err(gilrs::Error::NotImplemented(dummy)) => dummy,
+            Err(err) => return Err(Box::new(err)),
+        };
+        Ok(GilrsBackend {
+            gilrs,
+            announced: false,
+        })
+    }
+}
+
+fn pad_button(button: Button) -> Option<PadButton> {
+    Some(match button {
+        Button::South => PadButton::South,
+        Button::East => PadButton::East,
+        Button::North => PadButton::North,
+        Button::West => PadButton::West,
+        Button::LeftTrigger => PadButton::LeftTrigger,
+        Button::RightTrigger => PadButton::RightTrigger,
+        Button::Select => PadButton::Select,
+        Button::Start => PadButton::Start,
+        Button::DPadUp => PadButton::DPadUp,
+        Button::DPadDown => PadButton::DPadDown,
+        Button::DPadLeft => PadButton::DPadLeft,
+        Button::DPadRight => PadButton::DPadRight,
+        _ => return None,
+    })
+}
+
+fn pad_axis(axis: Axis) -> Option<PadAxis> {
+    match axis {
+        Axis::LeftStickX => Some(PadAxis::LeftStickX),
+        Axis::LeftStickY => Some(PadAxis::LeftStickY),
+        _ => None,
+    }
+}
+
+impl GamepadBackend for GilrsBackend {
+    fn poll(&mut self, events: &mut Vec<GamepadEvent>) {
+        if !self.announced {
+            self.announced = true;
+            for (id, pad) in self.gilrs.gamepads() {
+                events.push(GamepadEvent::Connected {
+                    id: id.into(),
+                    name: pad.name().to_string(),
+                });
+            }
+        }
+
+        while let Some(event) = self.gilrs.next_event() {
+            let id = event.id.into();
+            let translated = match event.event {
+                EventType::Connected => Some(GamepadEvent::Connected {
+                    id,
+                    name: self.gilrs.gamepad(event.id).name().to_string(),
+                }),
+                EventType::Disconnected => Some(GamepadEvent::Disconnected { id }),
+                EventType::ButtonPressed(button, _) => {
+                    pad_button(button).map(|button| GamepadEvent::Button {
+                        id,
+                        button,
+                        pressed: true,
+                    })
+                }
+                EventType::ButtonReleased(button, _) => {
+                    pad_button(button).map(|button| GamepadEvent::Button {
+                        id,
+                        button,
+                        pressed: false,
+                    })
+                }
+                EventType::AxisChanged(axis, value, _) => {
+                    pad_axis(axis).map(|axis| GamepadEvent::Axis { id, axis, value })
+                }
+                _ => None,
+            };
+            events.extend(translated);
+        }
+    }
+}
@@ -0,0 +1,118 @@
+// Standard controller. Writing 1 then 0 to $4016 latches the buttons into
+// a shift register, which then reads out one bit per read in the order A,
+// B, Select, Start, Up, Down, Left, Right. An official pad returns 1 on
+// every read after the eighth.
+
+use bitflags::bitflags;
+use serde::{Deserialize, Serialize};
+
+bitflags! {
+    #[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+    pub struct JoypadButton: u8 {
+        const BUTTON_A = 0b0000_0001;
+        const BUTTON_B = 0b0000_0010;
+        const SELECT   = 0b0000_0100;
+        const START    = 0b0000_1000;
+        const UP       = 0b0001_0000;
+        const DOWN     = 0b0010_0000;
+        const LEFT     = 0b0100_0000;
+        const RIGHT    = 0b1000_0000;
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Joypad {
+    strobe: bool,
+    shift_register: u8,
+    button_status: JoypadButton,
+}
+
+impl Joypad {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn write(&mut self, value: u8) {
+        // buttons are latched when the strobe goes low
+        if self.strobe && value & 1 == 0 {
+            self.shift_register = self.button_status.bits();
+        }
+        self.strobe = value & 1 == 1;
+    }
+
+    pub fn read(&mut self) -> u8 {
+        // while strobed the register keeps reloading, so only A is visible
+        if self.strobe {
+            return self.button_status.bits() & 1;
+        }
+        let response = self.shift_register & 1;
+        self.shift_register = (self.shift_register >> 1) | 0b1000_0000;
+        response
+    }
+
+    // Replaces the whole button state, e.g. once per frame from the frontend.
+    pub fn set_buttons(&mut self, buttons: JoypadButton) {
+        self.button_status = buttons;
+    }
+
+    pub fn set_button_pressed_status(&mut self, button: JoypadButton, pressed: bool) {
+        self.button_status.set(button, pressed);
+    }
+
+    pub fn buttons(&self) -> JoypadButton {
+        self.button_status
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn read_all(joypad: &mut Joypad) -> Vec<u8> {
+        joypad.write(1);
+        joypad.write(0);
+        (0..8).map(|_| joypad.read()).collect()
+    }
+
+    #[test]
+    fn test_reads_buttons_in_order() {
+        let mut joypad = Joypad::new();
+        joypad.set_buttons(JoypadButton::BUTTON_A | JoypadButton::START | JoypadButton::RIGHT);
+
+        assert_eq!(read_all(&mut joypad), vec![1, 0, 0, 1, 0, 0, 0, 1]);
+    }
+
+    #[test]
+    fn test_over_reading_returns_ones() {
+        let mut joypad = Joypad::new();
+        read_all(&mut joypad);
+
+        assert_eq!(joypad.read(), 1);
+        assert_eq!(joypad.read(), 1);
+    }
+
+    #[test]
+    fn test_strobe_high_keeps_returning_a() {
+        let mut joypad = Joypad::new();
+        joypad.set_button_pressed_status(JoypadButton::BUTTON_A, true);
+        joypad.write(1);
+
+        assert_eq!(joypad.read(), 1);
+        assert_eq!(joypad.read(), 1);
+
+        joypad.set_button_pressed_status(JoypadButton::BUTTON_A, false);
+        assert_eq!(joypad.read(), 0);
+    }
+
+    #[test]
+    fn test_button_changes_after_latch_are_not_seen() {
+        let mut joypad = Joypad::new();
+        joypad.set_buttons(JoypadButton::BUTTON_A);
+        joypad.write(1);
+        joypad.write(0);
+        joypad.set_buttons(JoypadButton::BUTTON_B);
+
+        assert_eq!(joypad.read(), 1);
+        assert_eq!(joypad.read(), 0);
+    }
+}
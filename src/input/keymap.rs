@@ -0,0 +1,334 @@
+// Host keyboard to NES button bindings. Keys are identified by name ("Z",
+// "Return", "Left"...) so every frontend can translate its own key events;
+// names are matched case-insensitively. Any number of keys may drive the
+// same button, and one key may drive buttons on several players.
+//
+// The file format is TOML with one table per player:
+//
+//     [player1]
+//     a = ["X", "Space"]
+//     b = ["Z"]
+//     start = ["Return"]
+
+use std::collections::{HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+
+use super::joypad::JoypadButton;
+use super::Controllers;
+
+pub const MAX_PLAYERS: usize = 4;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Key(String);
+
+impl Key {
+    pub fn new(name: &str) -> Self {
+        Key(name.to_ascii_lowercase())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Binding {
+    pub player: usize,
+    pub button: JoypadButton,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(from = "BindingsFile")]
+pub struct KeyBindings {
+    bindings: HashMap<Key, Vec<Binding>>,
+}
+
+// the names the config file and the remote protocol use
+pub const BUTTONS: [(JoypadButton, &str); 8] = [
+    (JoypadButton::BUTTON_A, "a"),
+    (JoypadButton::BUTTON_B, "b"),
+    (JoypadButton::SELECT, "select"),
+    (JoypadButton::START, "start"),
+    (JoypadButton::UP, "up"),
+    (JoypadButton::DOWN, "down"),
+    (JoypadButton::LEFT, "left"),
+    (JoypadButton::RIGHT, "right"),
+];
+
+// on-disk shape of one player's table
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct PlayerKeys {
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    a: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    b: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    select: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    start: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    up: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    down: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    left: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    right: Vec<String>,
+}
+
+impl PlayerKeys {
+    fn keys_mut(&mut self, button: JoypadButton) -> &mut Vec<String> {
+        match button {
+            JoypadButton::BUTTON_A => &mut self.a,
+            JoypadButton::BUTTON_B => &mut self.b,
+            JoypadButton::SELECT => &mut self.select,
+            JoypadButton::START => &mut self.start,
+            JoypadButton::UP => &mut self.up,
+            JoypadButton::DOWN => &mut self.down,
+            JoypadButton::LEFT => &mut self.left,
+            _ => &mut self.right,
+        }
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct BindingsFile {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    player1: Option<PlayerKeys>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    player2: Option<PlayerKeys>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    player3: Option<PlayerKeys>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    player4: Option<PlayerKeys>,
+}
+
+impl BindingsFile {
+    fn players_mut(&mut self) -> [&mut Option<PlayerKeys>; MAX_PLAYERS] {
+        [
+            &mut self.player1,
+            &mut self.player2,
+            &mut self.player3,
+            &mut self.player4,
+        ]
+    }
+}
+
+impl KeyBindings {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn bind(&mut self, key: &str, player: usize, button: JoypadButton) {
+        assert!(player < MAX_PLAYERS, "player {} out of range", player);
+        let binding = Binding { player, button };
+        let targets = self.bindings.entry(Key::new(key)).or_default();
+        if !targets.contains(&binding) {
+            targets.push(binding);
+        }
+    }
+
+    pub fn unbind_key(&mut self, key: &str) {
+        self.bindings.remove(&Key::new(key));
+    }
+
+    pub fn unbind_button(&mut self, player: usize, button: JoypadButton) {
+        for targets in self.bindings.values_mut() {
+            targets.retain(|b| !(b.player == player && b.button == button));
+        }
+        self.bindings.retain(|_, targets| !targets.is_empty());
+    }
+
+    pub fn bindings_for(&self, key: &Key) -> &[Binding] {
+        self.bindings.get(key).map(Vec::as_slice).unwrap_or(&[])
+    }
+
+    // Sorted, for stable display and serialization.
+    pub fn keys_for(&self, player: usize, button: JoypadButton) -> Vec<String> {
+        let mut keys: Vec<String> = self
+            .bindings
+            .iter()
+            .filter(|(_, targets)| targets.contains(&Binding { player, button }))
+            .map(|(key, _)| key.0.clone())
+            .collect();
+        keys.sort();
+        keys
+    }
+
+    pub fn from_toml(text: &str) -> Result<Self, toml::de::Error> {
+        toml::from_str(text)
+    }
+
+    pub fn to_toml(&self) -> String {
+        let mut file = BindingsFile::default();
+
+        for (player, slot) in file.players_mut().into_iter().enumerate() {
+            let mut keys = PlayerKeys::default();
+            let mut any = false;
+            for (button, _) in BUTTONS {
+                let bound = self.keys_for(player, button);
+                any |= !bound.is_empty();
+                *keys.keys_mut(button) = bound;
+            }
+            if any {
+                *slot = Some(keys);
+            }
+        }
+        toml::to_string(&file).expect("bindings always serialize")
+    }
+}
+
+impl From<BindingsFile> for KeyBindings {
+    fn from(mut file: BindingsFile) -> Self {
+        let mut bindings = KeyBindings::new();
+        for (player, keys) in file.players_mut().into_iter().enumerate() {
+            let Some(keys) = keys else { continue };
+            for (button, _) in BUTTONS {
+                for key in keys.keys_mut(button).iter() {
+                    bindings.bind(key, player, button);
+                }
+            }
+        }
+        bindings
+    }
+}
+
+// Arrows + Z/X for player 1, IJKL + G/H for player 2.
+impl KeyBindings {
+    pub fn default_bindings() -> Self {
+        let mut bindings = KeyBindings::new();
+        let layouts = [
+            ["X", "Z", "RShift", "Return", "Up", "Down", "Left", "Right"],
+            ["H", "G", "T", "Y", "I", "K", "J", "L"],
+        ];
+        for (player, keys) in layouts.iter().enumerate() {
+            for ((button, _), key) in BUTTONS.iter().zip(keys) {
+                bindings.bind(key, player, *button);
+            }
+        }
+        bindings
+    }
+}
+
+// Tracks which keys are down and turns them into per-player button states.
+#[derive(Debug, Default)]
+pub struct KeyboardInput {
+    pub bindings: KeyBindings,
+    held: HashSet<Key>,
+}
+
+impl KeyboardInput {
+    pub fn new(bindings: KeyBindings) -> Self {
+        KeyboardInput {
+            bindings,
+            held: HashSet::new(),
+        }
+    }
+
+    pub fn key_down(&mut self, key: &str) {
+        self.held.insert(Key::new(key));
+    }
+
+    pub fn key_up(&mut self, key: &str) {
+        self.held.remove(&Key::new(key));
+    }
+
+    pub fn release_all(&mut self) {
+        self.held.clear();
+    }
+
+    pub fn buttons(&self, player: usize) -> JoypadButton {
+        self.held
+            .iter()
+            .flat_map(|key| self.bindings.bindings_for(key))
+            .filter(|binding| binding.player == player)
+            .fold(JoypadButton::empty(), |acc, binding| acc | binding.button)
+    }
+
+    pub fn apply(&self, controllers: &mut Controllers) {
+        for player in 0..MAX_PLAYERS {
+            controllers.set_buttons(player, self.buttons(player));
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_multiple_keys_per_button() {
+        let mut bindings = KeyBindings::new();
+        bindings.bind("X", 0, JoypadButton::BUTTON_A);
+        bindings.bind("Space", 0, JoypadButton::BUTTON_A);
+        let mut input = KeyboardInput::new(bindings);
+
+        input.key_down("space");
+        assert_eq!(input.buttons(0), JoypadButton::BUTTON_A);
+        input.key_down("x");
+        input.key_up("SPACE");
+        assert_eq!(input.buttons(0), JoypadButton::BUTTON_A);
+        input.key_up("X");
+        assert_eq!(input.buttons(0), JoypadButton::empty());
+    }
+
+    #[test]
+    fn test_players_are_separate() {
+        let mut input = KeyboardInput::new(KeyBindings::default_bindings());
+        input.key_down("Left");
+        input.key_down("H");
+
+        assert_eq!(input.buttons(0), JoypadButton::LEFT);
+        assert_eq!(input.buttons(1), JoypadButton::BUTTON_A);
+        assert_eq!(input.buttons(2), JoypadButton::empty());
+    }
+
+    #[test]
+    fn test_unbind() {
+        let mut bindings = KeyBindings::default_bindings();
+        bindings.unbind_button(0, JoypadButton::START);
+        bindings.unbind_key("z");
+
+        assert!(bindings.keys_for(0, JoypadButton::START).is_empty());
+        assert!(bindings.keys_for(0, JoypadButton::BUTTON_B).is_empty());
+        assert_eq!(bindings.keys_for(0, JoypadButton::BUTTON_A), vec!["x"]);
+    }
+
+    #[test]
+    fn test_toml_round_trip() {
+        let text = r#"
+            [player1]
+            a = ["X", "Space"]
+            start = ["Return"]
+
+            [player3]
+            left = ["Num4"]
+        "#;
+        let bindings = KeyBindings::from_toml(text).unwrap();
+        assert_eq!(
+            bindings.keys_for(0, JoypadButton::BUTTON_A),
+            vec!["space", "x"]
+        );
+        assert_eq!(bindings.keys_for(2, JoypadButton::LEFT), vec!["num4"]);
+
+        let reparsed = KeyBindings::from_toml(&bindings.to_toml()).unwrap();
+        assert_eq!(reparsed, bindings);
+    }
+
+    #[test]
+    fn test_toml_rejects_unknown_buttons() {
+        assert!(KeyBindings::from_toml("[player1]\nturbo = [\"Q\"]").is_err());
+    }
+
+    #[test]
+    fn test_apply_sets_controllers() {
+        let mut controllers = Controllers::new();
+        let mut input = KeyboardInput::new(KeyBindings::default_bindings());
+        input.key_down("Return");
+        input.apply(&mut controllers);
+
+        assert_eq!(
+            controllers.joypad(0).unwrap().buttons(),
+            JoypadButton::START
+        );
+    }
+}
@@ -0,0 +1,353 @@
+// Everything hanging off $4016/$4017: what is plugged into each controller
+// port, an optional Four Score taking over both, and the Famicom expansion
+// port. InputState is what they hold, as an input display shows it.
+
+pub mod family_keyboard;
+pub mod four_score;
+#[cfg(feature = "std")]
+pub mod gamepad;
+#[cfg(feature = "gilrs")]
+pub mod gilrs_backend;
+pub mod joypad;
+#[cfg(feature = "std")]
+pub mod keymap;
+pub mod power_pad;
+pub mod zapper;
+
+use serde::{Deserialize, Serialize};
+
+use family_keyboard::FamilyKeyboard;
+use four_score::FourScore;
+use joypad::{Joypad, JoypadButton};
+use power_pad::PowerPad;
+use zapper::{VsZapper, Zapper};
+
+use crate::render::frame::Frame;
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub enum PortDevice {
+    #[default]
+    Disconnected,
+    Joypad(Joypad),
+    Zapper(Zapper),
+    PowerPad(PowerPad),
+    // a VS. System's; see zapper.rs
+    VsZapper(VsZapper),
+}
+
+impl PortDevice {
+    fn write(&mut self, value: u8) {
+        match self {
+            PortDevice::Disconnected => {}
+            PortDevice::Joypad(joypad) => joypad.write(value),
+            PortDevice::Zapper(_) => {}
+            PortDevice::PowerPad(pad) => pad.write(value),
+            PortDevice::VsZapper(gun) => gun.write(value),
+        }
+    }
+
+    fn read(&mut self) -> u8 {
+        match self {
+            PortDevice::Disconnected => 0,
+            PortDevice::Joypad(joypad) => joypad.read(),
+            PortDevice::Zapper(zapper) => zapper.read(),
+            PortDevice::PowerPad(pad) => pad.read(),
+            PortDevice::VsZapper(gun) => gun.read(),
+        }
+    }
+}
+
+// Famicom expansion port devices see every $4016 write and can drive bit 1
+// of $4016 and bits 1-4 of $4017 alongside whatever is in the ports.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub enum ExpansionDevice {
+    #[default]
+    Disconnected,
+    FamilyKeyboard(FamilyKeyboard),
+}
+
+impl ExpansionDevice {
+    fn write(&mut self, value: u8) {
+        match self {
+            ExpansionDevice::Disconnected => {}
+            ExpansionDevice::FamilyKeyboard(keyboard) => keyboard.write(value),
+        }
+    }
+
+    fn read(&mut self, port: usize) -> u8 {
+        match self {
+            ExpansionDevice::Disconnected => 0,
+            ExpansionDevice::FamilyKeyboard(keyboard) if port == 1 => keyboard.read(),
+            ExpansionDevice::FamilyKeyboard(_) => 0,
+        }
+    }
+}
+
+// The joypads' buttons and where the guns point. Input is set before a
+// frame and held through it, so after one this is what it ran with, not
+// what the host has pressed since.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct InputState {
+    // players 1-4; 3 and 4 only with a Four Score
+    pub joypads: [Option<JoypadButton>; 4],
+    // by port
+    pub zappers: [Option<ZapperState>; 2],
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ZapperState {
+    // None while it points off the screen
+    pub cursor: Option<(u16, u16)>,
+    pub trigger: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Controllers {
+    pub ports: [PortDevice; 2],
+    pub expansion: ExpansionDevice,
+    four_score: Option<FourScore>,
+}
+
+impl Default for Controllers {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Controllers {
+    // A joypad in each port, like the console ships.
+    pub fn new() -> Self {
+        Controllers {
+            ports: [
+                PortDevice::Joypad(Joypad::new()),
+                PortDevice::Joypad(Joypad::new()),
+            ],
+            expansion: ExpansionDevice::Disconnected,
+            four_score: None,
+        }
+    }
+
+    pub fn connect(&mut self, port: usize, device: PortDevice) {
+        self.ports[port] = device;
+    }
+
+    // While a Four Score is attached the devices in `ports` are unplugged.
+    pub fn set_four_score(&mut self, enabled: bool) {
+        self.four_score = enabled.then(FourScore::new);
+    }
+
+    pub fn has_four_score(&self) -> bool {
+        self.four_score.is_some()
+    }
+
+    // Joypad for player 0-3, wherever it is plugged in.
+    pub fn joypad(&mut self, player: usize) -> Option<&mut Joypad> {
+        match self.four_score.as_mut() {
+            Some(four_score) => four_score.pads.get_mut(player),
+            None => match self.ports.get_mut(player) {
+                Some(PortDevice::Joypad(joypad)) => Some(joypad),
+                _ => None,
+            },
+        }
+    }
+
+    pub fn family_keyboard(&mut self) -> Option<&mut FamilyKeyboard> {
+        match &mut self.expansion {
+            ExpansionDevice::FamilyKeyboard(keyboard) => Some(keyboard),
+            _ => None,
+        }
+    }
+
+    // Either kind of gun.
+    pub fn zapper(&mut self, port: usize) -> Option<&mut Zapper> {
+        if self.four_score.is_some() {
+            return None;
+        }
+        match self.ports.get_mut(port) {
+            Some(PortDevice::Zapper(zapper)) => Some(zapper),
+            Some(PortDevice::VsZapper(gun)) => Some(&mut gun.zapper),
+            _ => None,
+        }
+    }
+
+    pub fn power_pad(&mut self, port: usize) -> Option<&mut PowerPad> {
+        match self.ports.get_mut(port) {
+            Some(PortDevice::PowerPad(pad)) if self.four_score.is_none() => Some(pad),
+            _ => None,
+        }
+    }
+
+    pub fn state(&self) -> InputState {
+        let mut state = InputState::default();
+        if let Some(four_score) = self.four_score.as_ref() {
+            for (held, pad) in state.joypads.iter_mut().zip(&four_score.pads) {
+                *held = Some(pad.buttons());
+            }
+            return state;
+        }
+        for (port, device) in self.ports.iter().enumerate() {
+            let zapper = match device {
+                PortDevice::Joypad(joypad) => {
+                    state.joypads[port] = Some(joypad.buttons());
+                    continue;
+                }
+                PortDevice::Zapper(zapper) => zapper,
+                PortDevice::VsZapper(gun) => &gun.zapper,
+                PortDevice::Disconnected | PortDevice::PowerPad(_) => continue,
+            };
+            state.zappers[port] = Some(ZapperState {
+                cursor: zapper.cursor(),
+                trigger: zapper.trigger(),
+            });
+        }
+        state
+    }
+
+    // Lets light guns see the beam; see `Zapper::sense`.
+    pub fn sense_light(&mut self, frame: &Frame, scanline: u16, dot: u16) {
+        for port in 0..2 {
+            if let Some(zapper) = self.zapper(port) {
+                zapper.sense(frame, scanline, dot);
+            }
+        }
+    }
+
+    // Sets a player's buttons for the coming frame; ignored if that player
+    // has no joypad.
+    pub fn set_buttons(&mut self, player: usize, buttons: JoypadButton) {
+        if let Some(joypad) = self.joypad(player) {
+            joypad.set_buttons(buttons);
+        }
+    }
+
+    // $4016 write
+    pub fn write(&mut self, value: u8) {
+        self.expansion.write(value);
+        match self.four_score.as_mut() {
+            Some(four_score) => four_score.write(value),
+            None => {
+                for port in self.ports.iter_mut() {
+                    port.write(value);
+                }
+            }
+        }
+    }
+
+    // Data bits for a $4016 (port 0) or $4017 (port 1) read; the bus adds
+    // the open-bus bits.
+    pub fn read(&mut self, port: usize) -> u8 {
+        let data = match self.four_score.as_mut() {
+            Some(four_score) => four_score.read(port),
+            None => self.ports[port].read(),
+        };
+        data | self.expansion.read(port)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_players_map_to_ports() {
+        let mut controllers = Controllers::new();
+        controllers.set_buttons(1, JoypadButton::BUTTON_A);
+        controllers.set_buttons(3, JoypadButton::BUTTON_A); // nobody there
+        controllers.write(1);
+        controllers.write(0);
+
+        assert_eq!(controllers.read(0), 0);
+        assert_eq!(controllers.read(1), 1);
+    }
+
+    #[test]
+    fn test_disconnected_port_reads_zero() {
+        let mut controllers = Controllers::new();
+        controllers.connect(1, PortDevice::Disconnected);
+        controllers.write(1);
+        controllers.write(0);
+
+        assert!(controllers.joypad(1).is_none());
+        for _ in 0..10 {
+            assert_eq!(controllers.read(1), 0);
+        }
+    }
+
+    #[test]
+    fn test_zapper_on_port_2() {
+        let mut controllers = Controllers::new();
+        controllers.connect(1, PortDevice::Zapper(Zapper::new()));
+        let zapper = controllers.zapper(1).unwrap();
+        zapper.set_cursor(0, 0);
+        zapper.set_trigger(true);
+
+        let mut frame = Frame::new();
+        frame.set_pixel(0, 0, (0xFF, 0xFF, 0xFF));
+        controllers.sense_light(&frame, 1, 0);
+
+        assert_eq!(controllers.read(1), 0b0001_0000);
+        assert!(controllers.zapper(0).is_none());
+    }
+
+    #[test]
+    fn test_state_is_what_is_held() {
+        let mut controllers = Controllers::new();
+        controllers.set_buttons(0, JoypadButton::START | JoypadButton::LEFT);
+        controllers.connect(1, PortDevice::Zapper(Zapper::new()));
+        controllers.zapper(1).unwrap().set_cursor(10, 20);
+        let state = controllers.state();
+        assert_eq!(
+            state.joypads,
+            [
+                Some(JoypadButton::START | JoypadButton::LEFT),
+                None,
+                None,
+                None
+            ]
+        );
+        assert_eq!(
+            state.zappers[1],
+            Some(ZapperState {
+                cursor: Some((10, 20)),
+                trigger: false
+            })
+        );
+
+        controllers.set_four_score(true);
+        controllers.set_buttons(3, JoypadButton::BUTTON_B);
+        let state = controllers.state();
+        assert_eq!(state.joypads[3], Some(JoypadButton::BUTTON_B));
+        assert_eq!(state.zappers, [None, None]);
+    }
+
+    #[test]
+    fn test_four_score_players() {
+        let mut controllers = Controllers::new();
+        controllers.set_four_score(true);
+        controllers.set_buttons(3, JoypadButton::BUTTON_A);
+        controllers.write(1);
+        controllers.write(0);
+
+        let port2: Vec<u8> = (0..24).map(|_| controllers.read(1)).collect();
+        assert_eq!(port2[8], 1);
+        assert_eq!(port2[18], 1);
+
+        controllers.set_four_score(false);
+        assert!(!controllers.has_four_score());
+        assert!(controllers.joypad(3).is_none());
+    }
+
+    #[test]
+    fn test_family_keyboard_shares_4017() {
+        let mut controllers = Controllers::new();
+        controllers.expansion = ExpansionDevice::FamilyKeyboard(FamilyKeyboard::new());
+        controllers.set_buttons(1, JoypadButton::BUTTON_A);
+        // enable + reset also strobes the pads
+        controllers.write(0b101);
+        controllers.write(0b100);
+
+        assert_eq!(controllers.read(1), 0b1_1111);
+        assert_eq!(controllers.read(0), 0);
+        assert!(controllers.family_keyboard().is_some());
+    }
+}
@@ -0,0 +1,245 @@
+// Power Pad / Family Trainer mat, normally on port 2. Buttons are numbered
+// as printed on side B:
+//
+//      1  2  3  4
+//      5  6  7  8
+//      9 10 11 12
+//
+// Strobing latches the mat like a joypad, then every read shifts out two
+// streams at once: bit 3 gives buttons 2, 1, 5, 9, 6, 10, 11, 7 and bit 4
+// gives 4, 3, 12, 8. Both read 1 once they run out. A 1 is a pressed
+// button.
+
+#[cfg(feature = "std")]
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "std")]
+use super::keymap::Key;
+
+pub const POWER_PAD_BUTTONS: usize = 12;
+
+const LOW_ORDER: [usize; 8] = [2, 1, 5, 9, 6, 10, 11, 7];
+const HIGH_ORDER: [usize; 4] = [4, 3, 12, 8];
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct PowerPad {
+    strobe: bool,
+    // bit n-1 set while button n is held
+    pressed: u16,
+    low: u8,
+    high: u8,
+}
+
+impl PowerPad {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set_button(&mut self, button: usize, pressed: bool) {
+        assert!(
+            (1..=POWER_PAD_BUTTONS).contains(&button),
+            "no power pad button {}",
+            button
+        );
+        let mask = 1 << (button - 1);
+        if pressed {
+            self.pressed |= mask;
+        } else {
+            self.pressed &= !mask;
+        }
+    }
+
+    pub fn is_pressed(&self, button: usize) -> bool {
+        (1..=POWER_PAD_BUTTONS).contains(&button) && self.pressed & (1 << (button - 1)) != 0
+    }
+
+    fn latch(&mut self) {
+        let bit = |button: usize| ((self.pressed >> (button - 1)) & 1) as u8;
+        self.low = LOW_ORDER
+            .iter()
+            .enumerate()
+            .fold(0, |acc, (i, &b)| acc | (bit(b) << i));
+        self.high = HIGH_ORDER
+            .iter()
+            .enumerate()
+            .fold(0xF0, |acc, (i, &b)| acc | (bit(b) << i));
+    }
+
+    pub fn write(&mut self, value: u8) {
+        self.strobe = value & 1 == 1;
+        if self.strobe {
+            self.latch();
+        }
+    }
+
+    pub fn read(&mut self) -> u8 {
+        if self.strobe {
+            self.latch();
+        }
+        let value = ((self.low & 1) << 3) | ((self.high & 1) << 4);
+        if !self.strobe {
+            self.low = (self.low >> 1) | 0x80;
+            self.high = (self.high >> 1) | 0x80;
+        }
+        value
+    }
+}
+
+// Host keys for each mat button. Any number of keys can press the same
+// button. In TOML:
+//
+//     [power_pad]
+//     1 = ["Q"]
+//     2 = ["W", "Up"]
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PowerPadBindings {
+    keys: HashMap<Key, usize>,
+}
+
+#[cfg(feature = "std")]
+#[derive(Deserialize)]
+struct BindingsFile {
+    #[serde(default)]
+    power_pad: HashMap<String, Vec<String>>,
+}
+
+#[cfg(feature = "std")]
+#[derive(Debug, PartialEq, Eq)]
+pub enum PowerPadBindingError {
+    Toml(String),
+    BadButton(String),
+}
+
+#[cfg(feature = "std")]
+impl core::fmt::Display for PowerPadBindingError {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self {
+            PowerPadBindingError::Toml(err) => write!(f, "{}", err),
+            PowerPadBindingError::BadButton(name) => {
+                write!(f, "{:?} is not a power pad button (1-12)", name)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl core::error::Error for PowerPadBindingError {}
+
+#[cfg(feature = "std")]
+impl PowerPadBindings {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // The left hand side of a QWERTY keyboard, laid out like the mat.
+    pub fn default_bindings() -> Self {
+        let mut bindings = PowerPadBindings::new();
+        let layout = ["Q", "W", "E", "R", "A", "S", "D", "F", "Z", "X", "C", "V"];
+        for (i, key) in layout.iter().enumerate() {
+            bindings.bind(key, i + 1);
+        }
+        bindings
+    }
+
+    pub fn bind(&mut self, key: &str, button: usize) {
+        assert!(
+            (1..=POWER_PAD_BUTTONS).contains(&button),
+            "no power pad button {}",
+            button
+        );
+        self.keys.insert(Key::new(key), button);
+    }
+
+    pub fn unbind_key(&mut self, key: &str) {
+        self.keys.remove(&Key::new(key));
+    }
+
+    pub fn button_for(&self, key: &str) -> Option<usize> {
+        self.keys.get(&Key::new(key)).copied()
+    }
+
+    // Feeds a host key event to the mat; returns whether the key is bound.
+    pub fn host_key(&self, pad: &mut PowerPad, key: &str, pressed: bool) -> bool {
+        match self.button_for(key) {
+            Some(button) => {
+                pad.set_button(button, pressed);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn from_toml(text: &str) -> Result<Self, PowerPadBindingError> {
+        let file: BindingsFile =
+            toml::from_str(text).map_err(|err| PowerPadBindingError::Toml(err.to_string()))?;
+        let mut bindings = PowerPadBindings::new();
+        for (name, keys) in file.power_pad {
+            let button = name
+                .parse()
+                .ok()
+                .filter(|b| (1..=POWER_PAD_BUTTONS).contains(b))
+                .ok_or_else(|| PowerPadBindingError::BadButton(name.clone()))?;
+            for key in keys {
+                bindings.bind(&key, button);
+            }
+        }
+        Ok(bindings)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn read_all(pad: &mut PowerPad) -> Vec<u8> {
+        pad.write(1);
+        pad.write(0);
+        (0..10).map(|_| pad.read()).collect()
+    }
+
+    #[test]
+    fn test_serial_order() {
+        let mut pad = PowerPad::new();
+        pad.set_button(1, true);
+        pad.set_button(12, true);
+        let reads = read_all(&mut pad);
+
+        // button 1 is the second bit 3 read, 12 the third bit 4 read
+        assert_eq!(reads[0], 0);
+        assert_eq!(reads[1], 0b0_1000);
+        assert_eq!(reads[2], 0b1_0000);
+        assert_eq!(reads[3], 0);
+        // bit 4 runs out after four reads, bit 3 after eight
+        assert_eq!(reads[4..8], [0b1_0000; 4]);
+        assert_eq!(reads[8..], [0b1_1000; 2]);
+    }
+
+    #[test]
+    fn test_strobe_high_reads_first_buttons() {
+        let mut pad = PowerPad::new();
+        pad.set_button(2, true);
+        pad.write(1);
+        assert_eq!(pad.read(), 0b0_1000);
+        assert_eq!(pad.read(), 0b0_1000);
+    }
+
+    #[test]
+    fn test_key_bindings() {
+        let mut pad = PowerPad::new();
+        let bindings = PowerPadBindings::default_bindings();
+        assert!(bindings.host_key(&mut pad, "v", true));
+        assert!(pad.is_pressed(12));
+        assert!(!bindings.host_key(&mut pad, "p", true));
+
+        let custom = PowerPadBindings::from_toml("[power_pad]\n3 = [\"Up\", \"K\"]").unwrap();
+        assert_eq!(custom.button_for("up"), Some(3));
+        assert_eq!(custom.button_for("K"), Some(3));
+        assert_eq!(
+            PowerPadBindings::from_toml("[power_pad]\n13 = [\"Q\"]"),
+            Err(PowerPadBindingError::BadButton("13".to_string()))
+        );
+    }
+}
@@ -0,0 +1,196 @@
+// Zapper light gun. The photodiode only sees the CRT beam as it sweeps past
+// the spot the gun points at, and stays lit for a few scanlines after, so
+// light detection depends on where the PPU is in the frame, not just on
+// what the picture looks like. The emulator loop calls `sense` as
+// scanlines are drawn; port reads report the latest result.
+//
+// Port bits: 3 is 0 while light is detected, 4 is 1 while the trigger is
+// held.
+//
+// The VS. System's gun (VS. Duck Hunt's) reads out one bit at a time like
+// a joypad: after a strobe the fifth read is always 1, the seventh is 1
+// while light is detected and the eighth while the trigger is held.
+
+use serde::{Deserialize, Serialize};
+
+use crate::render::frame::Frame;
+
+// how long the sensor stays lit after the beam passes
+const LIGHT_SCANLINES: u16 = 20;
+// luma needed to register; white and light greys hit, darker colours miss
+const BRIGHTNESS_THRESHOLD: u32 = 128;
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Zapper {
+    cursor: Option<(u16, u16)>,
+    trigger: bool,
+    light: bool,
+}
+
+fn brightness((r, g, b): (u8, u8, u8)) -> u32 {
+    (299 * r as u32 + 587 * g as u32 + 114 * b as u32) / 1000
+}
+
+impl Zapper {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Where the gun points, in frame pixels (frontends map the mouse
+    // here). Anything outside the picture counts as pointing off-screen.
+    pub fn set_cursor(&mut self, x: i32, y: i32) {
+        let on_screen =
+            (0..Frame::WIDTH as i32).contains(&x) && (0..Frame::HEIGHT as i32).contains(&y);
+        self.cursor = on_screen.then_some((x as u16, y as u16));
+    }
+
+    pub fn set_trigger(&mut self, pulled: bool) {
+        self.trigger = pulled;
+    }
+
+    pub fn cursor(&self) -> Option<(u16, u16)> {
+        self.cursor
+    }
+
+    pub fn trigger(&self) -> bool {
+        self.trigger
+    }
+
+    // Updates the sensor for a beam at `scanline`/`dot` drawing `frame`.
+    pub fn sense(&mut self, frame: &Frame, scanline: u16, dot: u16) {
+        self.light = match self.cursor {
+            Some((x, y)) => {
+                let passed = scanline > y || (scanline == y && dot >= x);
+                let recent = scanline < y + LIGHT_SCANLINES;
+                passed
+                    && recent
+                    && brightness(frame.pixel(x as usize, y as usize)) >= BRIGHTNESS_THRESHOLD
+            }
+            None => false,
+        };
+    }
+
+    pub fn read(&self) -> u8 {
+        let mut value = 0;
+        if !self.light {
+            value |= 0b0000_1000;
+        }
+        if self.trigger {
+            value |= 0b0001_0000;
+        }
+        value
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct VsZapper {
+    pub zapper: Zapper,
+    strobe: bool,
+    shift_register: u8,
+}
+
+impl VsZapper {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn report(&self) -> u8 {
+        0b0001_0000 | (self.zapper.light as u8) << 6 | (self.zapper.trigger as u8) << 7
+    }
+
+    pub fn write(&mut self, value: u8) {
+        if self.strobe && value & 1 == 0 {
+            self.shift_register = self.report();
+        }
+        self.strobe = value & 1 == 1;
+    }
+
+    pub fn read(&mut self) -> u8 {
+        if self.strobe {
+            return self.report() & 1;
+        }
+        let bit = self.shift_register & 1;
+        self.shift_register = (self.shift_register >> 1) | 0b1000_0000;
+        bit
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn frame_with_white_box() -> Frame {
+        let mut frame = Frame::new();
+        for y in 100..110 {
+            for x in 50..60 {
+                frame.set_pixel(x, y, (0xFF, 0xFF, 0xFF));
+            }
+        }
+        frame
+    }
+
+    #[test]
+    fn test_no_light_off_screen() {
+        let mut zapper = Zapper::new();
+        zapper.set_cursor(-5, 100);
+        zapper.sense(&frame_with_white_box(), 105, 0);
+
+        assert_eq!(zapper.read() & 0b1000, 0b1000);
+    }
+
+    #[test]
+    fn test_light_only_after_beam_passes() {
+        let frame = frame_with_white_box();
+        let mut zapper = Zapper::new();
+        zapper.set_cursor(55, 105);
+
+        zapper.sense(&frame, 104, 300);
+        assert_eq!(zapper.read() & 0b1000, 0b1000);
+
+        zapper.sense(&frame, 105, 56);
+        assert_eq!(zapper.read() & 0b1000, 0);
+
+        zapper.sense(&frame, 105 + LIGHT_SCANLINES, 0);
+        assert_eq!(zapper.read() & 0b1000, 0b1000);
+    }
+
+    #[test]
+    fn test_dark_target_is_not_detected() {
+        let frame = frame_with_white_box();
+        let mut zapper = Zapper::new();
+        zapper.set_cursor(10, 105);
+        zapper.sense(&frame, 110, 0);
+
+        assert_eq!(zapper.read() & 0b1000, 0b1000);
+    }
+
+    #[test]
+    fn test_vs_zapper_reads_out_serially() {
+        let mut gun = VsZapper::new();
+        gun.zapper.set_cursor(55, 105);
+        gun.zapper.set_trigger(true);
+        gun.zapper.sense(&frame_with_white_box(), 105, 56);
+        gun.write(1);
+        gun.write(0);
+        let bits: Vec<u8> = (0..8).map(|_| gun.read()).collect();
+        assert_eq!(bits, [0, 0, 0, 0, 1, 0, 1, 1]);
+
+        gun.zapper.set_trigger(false);
+        gun.zapper.set_cursor(-1, -1);
+        gun.zapper.sense(&frame_with_white_box(), 105, 56);
+        gun.write(1);
+        gun.write(0);
+        let bits: Vec<u8> = (0..8).map(|_| gun.read()).collect();
+        assert_eq!(bits, [0, 0, 0, 0, 1, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_trigger_bit() {
+        let mut zapper = Zapper::new();
+        zapper.set_trigger(true);
+        assert_eq!(zapper.read() & 0b1_0000, 0b1_0000);
+
+        zapper.set_trigger(false);
+        assert_eq!(zapper.read() & 0b1_0000, 0);
+    }
+}
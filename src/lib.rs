@@ -0,0 +1,73 @@
+// Without the std feature only the core is built, on core and alloc: the
+// CPU, PPU, APU, bus, cartridges and mappers, input devices and
+// savestates. Everything that touches the OS (files, threads, clocks,
+// windows and audio devices) is the front ends' and needs std, Emulator
+// included; a port drives a Cpu and its Bus itself.
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+#[cfg(feature = "std")]
+pub mod achievements;
+pub mod apu;
+#[cfg(feature = "std")]
+pub mod audio;
+#[cfg(feature = "std")]
+pub mod autosplit;
+pub mod bus;
+pub mod cartridge;
+pub mod cheat;
+#[cfg(feature = "std")]
+pub mod config;
+pub mod cpu;
+#[cfg(feature = "std")]
+pub mod crash;
+pub mod debugger;
+#[cfg(feature = "std")]
+pub mod emulator;
+#[cfg(feature = "std")]
+pub mod env;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "std")]
+pub mod frontend;
+pub mod input;
+#[cfg(feature = "libretro")]
+pub mod libretro;
+#[cfg(feature = "std")]
+pub mod logging;
+pub mod mapper;
+#[cfg(feature = "std")]
+pub mod movie;
+#[cfg(feature = "std")]
+pub mod netplay;
+pub mod nsf;
+pub mod opcodes;
+pub mod peripheral;
+pub mod ppu;
+pub mod region;
+#[cfg(feature = "std")]
+pub mod remote;
+pub mod render;
+#[cfg(feature = "std")]
+pub mod rewind;
+pub mod rng;
+pub mod rom;
+#[cfg(feature = "std")]
+pub mod run_ahead;
+#[cfg(feature = "std")]
+pub mod script;
+pub mod state;
+#[cfg(feature = "std")]
+pub mod testing;
+#[cfg(feature = "std")]
+pub mod trace;
+pub mod vs;
+
+pub use apu::Apu;
+pub use bus::Bus;
+pub use cartridge::Cartridge;
+pub use cpu::Cpu;
+#[cfg(feature = "std")]
+pub use emulator::Emulator;
+pub use ppu::Ppu;
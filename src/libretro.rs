@@ -0,0 +1,592 @@
+// libretro core. Built as a cdylib with the `libretro` feature, the crate
+// exports the retro_* entry points and RetroArch (or any other libretro
+// front end) takes care of video, audio, input, shaders, rewind and
+// netplay:
+//
+//     cargo build --release --lib --features libretro
+//
+// The API is a set of free C functions around one loaded game, so the core
+// lives in a thread local; front ends drive a core from a single thread.
+// Pointers handed in are valid as libretro.h describes, which is all the
+// unsafe functions rely on.
+
+#![allow(clippy::missing_safety_doc)]
+
+use std::cell::RefCell;
+use std::ffi::{c_char, c_uint, c_void, CStr};
+use std::ptr;
+
+use crate::cartridge::Cartridge;
+use crate::cheat::{Cheat, Cheats, Code};
+use crate::emulator::Emulator;
+use crate::frontend::NTSC_FRAME_RATE;
+use crate::input::joypad::JoypadButton;
+use crate::region::Region;
+use crate::render::frame::Frame;
+
+const RETRO_API_VERSION: c_uint = 1;
+
+const RETRO_DEVICE_JOYPAD: c_uint = 1;
+
+const RETRO_DEVICE_ID_JOYPAD_B: c_uint = 0;
+const RETRO_DEVICE_ID_JOYPAD_SELECT: c_uint = 2;
+const RETRO_DEVICE_ID_JOYPAD_START: c_uint = 3;
+const RETRO_DEVICE_ID_JOYPAD_UP: c_uint = 4;
+const RETRO_DEVICE_ID_JOYPAD_DOWN: c_uint = 5;
+const RETRO_DEVICE_ID_JOYPAD_LEFT: c_uint = 6;
+const RETRO_DEVICE_ID_JOYPAD_RIGHT: c_uint = 7;
+const RETRO_DEVICE_ID_JOYPAD_A: c_uint = 8;
+
+const RETRO_ENVIRONMENT_SET_PIXEL_FORMAT: c_uint = 10;
+const RETRO_ENVIRONMENT_SET_INPUT_DESCRIPTORS: c_uint = 11;
+const RETRO_PIXEL_FORMAT_XRGB8888: c_uint = 1;
+
+const RETRO_REGION_NTSC: c_uint = 0;
+const RETRO_REGION_PAL: c_uint = 1;
+
+const RETRO_MEMORY_SYSTEM_RAM: c_uint = 2;
+
+const SAMPLE_RATE: u32 = 48_000;
+const PLAYERS: c_uint = 2;
+
+// Savestates vary a little in size (postcard uses variable-length
+// integers) but libretro wants one size up front, so leave some room.
+const STATE_SLACK: usize = 1024;
+
+// libretro button id -> joypad bit, in descriptor order
+const BUTTONS: [(c_uint, JoypadButton, &CStr); 8] = [
+    (RETRO_DEVICE_ID_JOYPAD_A, JoypadButton::BUTTON_A, c"A"),
+    (RETRO_DEVICE_ID_JOYPAD_B, JoypadButton::BUTTON_B, c"B"),
+    (
+        RETRO_DEVICE_ID_JOYPAD_SELECT,
+        JoypadButton::SELECT,
+        c"Select",
+    ),
+    (RETRO_DEVICE_ID_JOYPAD_START, JoypadButton::START, c"Start"),
+    (RETRO_DEVICE_ID_JOYPAD_UP, JoypadButton::UP, c"Up"),
+    (RETRO_DEVICE_ID_JOYPAD_DOWN, JoypadButton::DOWN, c"Down"),
+    (RETRO_DEVICE_ID_JOYPAD_LEFT, JoypadButton::LEFT, c"Left"),
+    (RETRO_DEVICE_ID_JOYPAD_RIGHT, JoypadButton::RIGHT, c"Right"),
+];
+
+#[repr(C)]
+pub struct RetroSystemInfo {
+    pub library_name: *const c_char,
+    pub library_version: *const c_char,
+    pub valid_extensions: *const c_char,
+    pub need_fullpath: bool,
+    pub block_extract: bool,
+}
+
+#[repr(C)]
+pub struct RetroGameGeometry {
+    pub base_width: c_uint,
+    pub base_height: c_uint,
+    pub max_width: c_uint,
+    pub max_height: c_uint,
+    pub aspect_ratio: f32,
+}
+
+#[repr(C)]
+pub struct RetroSystemTiming {
+    pub fps: f64,
+    pub sample_rate: f64,
+}
+
+#[repr(C)]
+pub struct RetroSystemAvInfo {
+    pub geometry: RetroGameGeometry,
+    pub timing: RetroSystemTiming,
+}
+
+#[repr(C)]
+pub struct RetroGameInfo {
+    pub path: *const c_char,
+    pub data: *const c_void,
+    pub size: usize,
+    pub meta: *const c_char,
+}
+
+#[repr(C)]
+struct RetroInputDescriptor {
+    port: c_uint,
+    device: c_uint,
+    index: c_uint,
+    id: c_uint,
+    description: *const c_char,
+}
+
+pub type RetroEnvironment = extern "C" fn(cmd: c_uint, data: *mut c_void) -> bool;
+pub type RetroVideoRefresh =
+    extern "C" fn(data: *const c_void, width: c_uint, height: c_uint, pitch: usize);
+pub type RetroAudioSample = extern "C" fn(left: i16, right: i16);
+pub type RetroAudioSampleBatch = extern "C" fn(data: *const i16, frames: usize) -> usize;
+pub type RetroInputPoll = extern "C" fn();
+pub type RetroInputState =
+    extern "C" fn(port: c_uint, device: c_uint, index: c_uint, id: c_uint) -> i16;
+
+#[derive(Default)]
+struct Callbacks {
+    environment: Option<RetroEnvironment>,
+    video_refresh: Option<RetroVideoRefresh>,
+    audio_sample_batch: Option<RetroAudioSampleBatch>,
+    input_poll: Option<RetroInputPoll>,
+    input_state: Option<RetroInputState>,
+}
+
+struct Core {
+    emulator: Emulator,
+    // XRGB8888
+    video: Vec<u32>,
+    samples: Vec<f32>,
+    // interleaved stereo
+    audio: Vec<i16>,
+}
+
+impl Core {
+    fn new(emulator: Emulator) -> Self {
+        Core {
+            emulator,
+            video: vec![0; Frame::WIDTH * Frame::HEIGHT],
+            samples: Vec::new(),
+            audio: Vec::new(),
+        }
+    }
+
+    fn read_input(&mut self, input_state: RetroInputState) {
+        for port in 0..PLAYERS {
+            let mut buttons = JoypadButton::empty();
+            for (id, button, _) in BUTTONS {
+                if input_state(port, RETRO_DEVICE_JOYPAD, 0, id) != 0 {
+                    buttons |= button;
+                }
+            }
+            self.emulator.set_buttons(port as usize, buttons);
+        }
+    }
+
+    fn convert_video(&mut self) {
+        for (pixel, rgb) in self
+            .video
+            .iter_mut()
+            .zip(self.emulator.frame().data.chunks_exact(3))
+        {
+            *pixel = (rgb[0] as u32) << 16 | (rgb[1] as u32) << 8 | rgb[2] as u32;
+        }
+    }
+
+    fn convert_audio(&mut self) {
+        self.samples.clear();
+        self.emulator.read_audio(&mut self.samples);
+        self.audio.clear();
+        for &sample in &self.samples {
+            let sample = (sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16;
+            self.audio.extend_from_slice(&[sample, sample]);
+        }
+    }
+}
+
+thread_local! {
+    static CALLBACKS: RefCell<Callbacks> = RefCell::default();
+    static CORE: RefCell<Option<Core>> = const { RefCell::new(None) };
+}
+
+fn with_core<T>(default: T, f: impl FnOnce(&mut Core) -> T) -> T {
+    CORE.with_borrow_mut(|core| core.as_mut().map_or(default, f))
+}
+
+fn environment(cmd: c_uint, data: *mut c_void) -> bool {
+    match CALLBACKS.with_borrow(|callbacks| callbacks.environment) {
+        Some(environment) => environment(cmd, data),
+        None => false,
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn retro_api_version() -> c_uint {
+    RETRO_API_VERSION
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_environment(callback: RetroEnvironment) {
+    CALLBACKS.with_borrow_mut(|callbacks| callbacks.environment = Some(callback));
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_video_refresh(callback: RetroVideoRefresh) {
+    CALLBACKS.with_borrow_mut(|callbacks| callbacks.video_refresh = Some(callback));
+}
+
+// Everything goes through the batch callback.
+#[no_mangle]
+pub extern "C" fn retro_set_audio_sample(_callback: RetroAudioSample) {}
+
+#[no_mangle]
+pub extern "C" fn retro_set_audio_sample_batch(callback: RetroAudioSampleBatch) {
+    CALLBACKS.with_borrow_mut(|callbacks| callbacks.audio_sample_batch = Some(callback));
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_input_poll(callback: RetroInputPoll) {
+    CALLBACKS.with_borrow_mut(|callbacks| callbacks.input_poll = Some(callback));
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_input_state(callback: RetroInputState) {
+    CALLBACKS.with_borrow_mut(|callbacks| callbacks.input_state = Some(callback));
+}
+
+#[no_mangle]
+pub extern "C" fn retro_init() {}
+
+#[no_mangle]
+pub extern "C" fn retro_deinit() {
+    CORE.with_borrow_mut(|core| *core = None);
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn retro_get_system_info(info: *mut RetroSystemInfo) {
+    info.write(RetroSystemInfo {
+        library_name: c"nes".as_ptr(),
+        library_version: concat!(env!("CARGO_PKG_VERSION"), "\0").as_ptr().cast(),
+        valid_extensions: c"nes".as_ptr(),
+        need_fullpath: false,
+        block_extract: false,
+    });
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn retro_get_system_av_info(info: *mut RetroSystemAvInfo) {
+    info.write(RetroSystemAvInfo {
+        geometry: RetroGameGeometry {
+            base_width: Frame::WIDTH as c_uint,
+            base_height: Frame::HEIGHT as c_uint,
+            max_width: Frame::WIDTH as c_uint,
+            max_height: Frame::HEIGHT as c_uint,
+            // 8:7 pixels
+            aspect_ratio: (Frame::WIDTH as f32 * 8.0 / 7.0) / Frame::HEIGHT as f32,
+        },
+        timing: RetroSystemTiming {
+            fps: with_core(NTSC_FRAME_RATE, |core| core.emulator.frame_rate()),
+            sample_rate: SAMPLE_RATE as f64,
+        },
+    });
+}
+
+// Only the standard joypad is offered; nothing to switch.
+#[no_mangle]
+pub extern "C" fn retro_set_controller_port_device(_port: c_uint, _device: c_uint) {}
+
+#[no_mangle]
+pub extern "C" fn retro_reset() {
+    with_core((), |core| core.emulator.reset());
+}
+
+#[no_mangle]
+pub extern "C" fn retro_run() {
+    let callbacks = CALLBACKS.with_borrow(|callbacks| {
+        (
+            callbacks.input_poll,
+            callbacks.input_state,
+            callbacks.video_refresh,
+            callbacks.audio_sample_batch,
+        )
+    });
+    let (input_poll, input_state, video_refresh, audio_sample_batch) = callbacks;
+
+    with_core((), |core| {
+        if let Some(input_poll) = input_poll {
+            input_poll();
+        }
+        if let Some(input_state) = input_state {
+            core.read_input(input_state);
+        }
+
+        core.emulator.run_frame();
+
+        core.convert_video();
+        if let Some(video_refresh) = video_refresh {
+            video_refresh(
+                core.video.as_ptr().cast(),
+                Frame::WIDTH as c_uint,
+                Frame::HEIGHT as c_uint,
+                Frame::WIDTH * 4,
+            );
+        }
+        core.convert_audio();
+        if let Some(audio_sample_batch) = audio_sample_batch {
+            // the front end may take fewer frames than offered per call
+            let mut offset = 0;
+            while offset < core.audio.len() {
+                let rest = &core.audio[offset..];
+                let taken = audio_sample_batch(rest.as_ptr(), rest.len() / 2);
+                if taken == 0 {
+                    break;
+                }
+                offset += taken * 2;
+            }
+        }
+    });
+}
+
+#[no_mangle]
+pub extern "C" fn retro_serialize_size() -> usize {
+    with_core(0, |core| core.emulator.save_state().len() + STATE_SLACK)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn retro_serialize(data: *mut c_void, size: usize) -> bool {
+    with_core(false, |core| {
+        let state = core.emulator.save_state();
+        if state.len() > size {
+            return false;
+        }
+        // the padding after the state is ignored when it's loaded
+        let out = std::slice::from_raw_parts_mut(data.cast::<u8>(), size);
+        out[..state.len()].copy_from_slice(&state);
+        out[state.len()..].fill(0);
+        true
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn retro_unserialize(data: *const c_void, size: usize) -> bool {
+    let state = std::slice::from_raw_parts(data.cast::<u8>(), size);
+    with_core(false, |core| core.emulator.load_state(state).is_ok())
+}
+
+#[no_mangle]
+pub extern "C" fn retro_cheat_reset() {
+    with_core((), |core| *core.emulator.cheats_mut() = Cheats::default());
+}
+
+// The front end keeps the list and its index; a cheat is one or more codes
+// joined by '+', see Code's FromStr, and codes that don't parse are left
+// out.
+#[no_mangle]
+pub unsafe extern "C" fn retro_cheat_set(_index: c_uint, enabled: bool, code: *const c_char) {
+    if code.is_null() {
+        return;
+    }
+    let text = CStr::from_ptr(code).to_string_lossy();
+    with_core((), |core| {
+        let cheats = core.emulator.cheats_mut();
+        for code in text
+            .split('+')
+            .filter_map(|code| code.trim().parse::<Code>().ok())
+        {
+            if enabled {
+                cheats.add(Cheat::new(code));
+            } else if let Some(index) = cheats.cheats().iter().position(|cheat| cheat.code == code)
+            {
+                cheats.set_enabled(index, false);
+            }
+        }
+    });
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn retro_load_game(game: *const RetroGameInfo) -> bool {
+    let Some(game) = game.as_ref() else {
+        return false;
+    };
+    if game.data.is_null() {
+        return false;
+    }
+    let rom = std::slice::from_raw_parts(game.data.cast::<u8>(), game.size);
+    let Ok(mut cartridge) = Cartridge::from_bytes(rom) else {
+        return false;
+    };
+    // front ends that load the file themselves still pass its path
+    if !game.path.is_null() {
+        let path = CStr::from_ptr(game.path).to_string_lossy();
+        let name = path.rsplit(['/', '\\']).next().unwrap_or_default();
+        cartridge.guess_region(name);
+    }
+    let mut emulator = match Emulator::new(cartridge) {
+        Ok(emulator) => emulator,
+        Err(_) => return false,
+    };
+
+    let mut format = RETRO_PIXEL_FORMAT_XRGB8888;
+    if !environment(
+        RETRO_ENVIRONMENT_SET_PIXEL_FORMAT,
+        ptr::from_mut(&mut format).cast(),
+    ) {
+        return false;
+    }
+    let mut descriptors = Vec::new();
+    for port in 0..PLAYERS {
+        for (id, _, description) in BUTTONS {
+            descriptors.push(RetroInputDescriptor {
+                port,
+                device: RETRO_DEVICE_JOYPAD,
+                index: 0,
+                id,
+                description: description.as_ptr(),
+            });
+        }
+    }
+    descriptors.push(RetroInputDescriptor {
+        port: 0,
+        device: 0,
+        index: 0,
+        id: 0,
+        description: ptr::null(),
+    });
+    environment(
+        RETRO_ENVIRONMENT_SET_INPUT_DESCRIPTORS,
+        descriptors.as_mut_ptr().cast(),
+    );
+
+    emulator.enable_audio(SAMPLE_RATE);
+    CORE.with_borrow_mut(|core| *core = Some(Core::new(emulator)));
+    true
+}
+
+#[no_mangle]
+pub extern "C" fn retro_load_game_special(
+    _game_type: c_uint,
+    _info: *const RetroGameInfo,
+    _num_info: usize,
+) -> bool {
+    false
+}
+
+#[no_mangle]
+pub extern "C" fn retro_unload_game() {
+    CORE.with_borrow_mut(|core| *core = None);
+}
+
+#[no_mangle]
+pub extern "C" fn retro_get_region() -> c_uint {
+    with_core(RETRO_REGION_NTSC, |core| {
+        match core.emulator.region().console() {
+            Region::Ntsc => RETRO_REGION_NTSC,
+            _ => RETRO_REGION_PAL,
+        }
+    })
+}
+
+// System RAM only, for cheats and achievements. The core stays where it is
+// until the game is unloaded, so the pointer does too. Battery RAM isn't
+// exposed; it is part of savestates.
+#[no_mangle]
+pub extern "C" fn retro_get_memory_data(id: c_uint) -> *mut c_void {
+    match id {
+        RETRO_MEMORY_SYSTEM_RAM => with_core(ptr::null_mut(), |core| {
+            core.emulator.cpu_mut().bus.ram_mut().as_mut_ptr().cast()
+        }),
+        _ => ptr::null_mut(),
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn retro_get_memory_size(id: c_uint) -> usize {
+    match id {
+        RETRO_MEMORY_SYSTEM_RAM => with_core(0, |core| core.emulator.cpu().bus.ram().len()),
+        _ => 0,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::cartridge::test::test_rom;
+    use std::cell::Cell;
+
+    thread_local! {
+        static VIDEO_FRAMES: Cell<u32> = const { Cell::new(0) };
+        static AUDIO_FRAMES: Cell<usize> = const { Cell::new(0) };
+    }
+
+    extern "C" fn environment(cmd: c_uint, _data: *mut c_void) -> bool {
+        cmd == RETRO_ENVIRONMENT_SET_PIXEL_FORMAT
+    }
+
+    extern "C" fn video_refresh(data: *const c_void, width: c_uint, height: c_uint, pitch: usize) {
+        assert!(!data.is_null());
+        assert_eq!((width, height, pitch), (256, 240, 1024));
+        VIDEO_FRAMES.set(VIDEO_FRAMES.get() + 1);
+    }
+
+    extern "C" fn audio_sample_batch(_data: *const i16, frames: usize) -> usize {
+        AUDIO_FRAMES.set(AUDIO_FRAMES.get() + frames);
+        frames
+    }
+
+    extern "C" fn input_poll() {}
+
+    extern "C" fn input_state(_port: c_uint, _device: c_uint, _index: c_uint, id: c_uint) -> i16 {
+        (id == RETRO_DEVICE_ID_JOYPAD_START) as i16
+    }
+
+    // NOPs from $8000 up to a jump back
+    fn spinning_rom() -> Vec<u8> {
+        let mut rom = test_rom(2, 1, 0, 0);
+        let prg = &mut rom[16..16 + 0x8000];
+        prg.fill(0xea);
+        prg[0x7FF0..0x7FF3].copy_from_slice(&[0x4c, 0x00, 0x80]);
+        prg[0x7FFA..].copy_from_slice(&[0x00, 0x80, 0x00, 0x80, 0x00, 0x80]);
+        rom
+    }
+
+    fn load(rom: &[u8]) -> bool {
+        let game = RetroGameInfo {
+            path: ptr::null(),
+            data: rom.as_ptr().cast(),
+            size: rom.len(),
+            meta: ptr::null(),
+        };
+        unsafe { retro_load_game(&game) }
+    }
+
+    #[test]
+    fn test_runs_a_game_through_the_callbacks() {
+        retro_set_environment(environment);
+        retro_set_video_refresh(video_refresh);
+        retro_set_audio_sample_batch(audio_sample_batch);
+        retro_set_input_poll(input_poll);
+        retro_set_input_state(input_state);
+        retro_init();
+        assert!(load(&spinning_rom()));
+
+        retro_run();
+        retro_run();
+        assert_eq!(VIDEO_FRAMES.get(), 2);
+        assert!(
+            (1_500..1_700).contains(&AUDIO_FRAMES.get()),
+            "{}",
+            AUDIO_FRAMES.get()
+        );
+        let pressed = with_core(None, |core| {
+            core.emulator
+                .cpu_mut()
+                .bus
+                .controllers
+                .joypad(0)
+                .map(|pad| pad.buttons())
+        });
+        assert_eq!(pressed, Some(JoypadButton::START));
+        assert_eq!(retro_get_memory_size(RETRO_MEMORY_SYSTEM_RAM), 0x800);
+
+        let mut state = vec![0; retro_serialize_size()];
+        unsafe {
+            assert!(retro_serialize(state.as_mut_ptr().cast(), state.len()));
+            retro_run();
+            assert!(retro_unserialize(state.as_ptr().cast(), state.len()));
+        }
+        assert_eq!(with_core(0, |core| core.emulator.frame_count()), 2);
+
+        retro_unload_game();
+        retro_deinit();
+        assert_eq!(retro_serialize_size(), 0);
+    }
+
+    #[test]
+    fn test_rejects_bad_roms() {
+        retro_set_environment(environment);
+        assert!(!load(b"nope"));
+        assert!(unsafe { !retro_load_game(ptr::null()) });
+    }
+}
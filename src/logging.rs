@@ -0,0 +1,408 @@
+// Log output for the core's tracing events, filtered per subsystem.
+//
+// The core reports what it does through `tracing`, with each module's
+// path as the target: nes::cpu for interrupts and resets, nes::ppu and
+// nes::apu for register writes and frames, nes::bus for DMA, nes::mapper
+// for writes to a board's registers, nes::cartridge for what was loaded.
+// Nothing is printed until a front end installs a Logger, which reads
+// its filter from RUST_LOG the way env_logger does:
+//
+//     RUST_LOG=nes::ppu=trace,nes::apu=warn
+//     RUST_LOG=debug,nes::cpu=off
+//
+// A directive is `target=level` or a bare level for everything else; the
+// longest target that matches an event's wins. Without RUST_LOG only
+// warnings and errors show. Lines go to stderr, under the spans they
+// happened in:
+//
+//     0.512s TRACE nes::ppu: frame{number=30}: write $2006 = $3F
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::env;
+use std::error::Error;
+use std::fmt::{self, Write as _};
+use std::io::{self, Write as _};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Instant;
+
+use tracing::field::{Field, Visit};
+use tracing::level_filters::LevelFilter;
+use tracing::span::{Attributes, Id, Record};
+use tracing::subscriber::{Interest, SetGlobalDefaultError};
+use tracing::{Event, Metadata, Subscriber};
+
+pub const FILTER_VAR: &str = "RUST_LOG";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Filter {
+    default: LevelFilter,
+    // longest target first
+    targets: Vec<(String, LevelFilter)>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct FilterError(pub String);
+
+impl fmt::Display for FilterError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "bad log directive '{}'; TARGET=LEVEL or a level, one of off, error, warn, info, debug or trace",
+            self.0
+        )
+    }
+}
+
+impl Error for FilterError {}
+
+impl Default for Filter {
+    fn default() -> Self {
+        Filter {
+            default: LevelFilter::WARN,
+            targets: Vec::new(),
+        }
+    }
+}
+
+impl FromStr for Filter {
+    type Err = FilterError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let mut filter = Filter::default();
+        for directive in text.split(',').map(str::trim).filter(|d| !d.is_empty()) {
+            let bad = || FilterError(directive.to_string());
+            match directive.split_once('=') {
+                Some((target, level)) if !target.is_empty() => {
+                    let level = level.parse().map_err(|_| bad())?;
+                    filter.targets.retain(|(other, _)| other != target);
+                    filter.targets.push((target.to_string(), level));
+                }
+                Some(_) => return Err(bad()),
+                // a bare target turns everything on for it
+                None => match directive.parse() {
+                    Ok(level) => filter.default = level,
+                    Err(_) if is_target(directive) => {
+                        filter.targets.retain(|(other, _)| other != directive);
+                        filter
+                            .targets
+                            .push((directive.to_string(), LevelFilter::TRACE));
+                    }
+                    Err(_) => return Err(bad()),
+                },
+            }
+        }
+        filter
+            .targets
+            .sort_by_key(|(target, _)| std::cmp::Reverse(target.len()));
+        Ok(filter)
+    }
+}
+
+fn is_target(text: &str) -> bool {
+    text.chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == ':')
+}
+
+impl Filter {
+    // From RUST_LOG, or the default when it isn't set.
+    pub fn from_env() -> Result<Self, FilterError> {
+        match env::var(FILTER_VAR) {
+            Ok(text) => text.parse(),
+            Err(_) => Ok(Filter::default()),
+        }
+    }
+
+    pub fn level_for(&self, target: &str) -> LevelFilter {
+        self.targets
+            .iter()
+            .find(|(prefix, _)| {
+                target
+                    .strip_prefix(prefix.as_str())
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with("::"))
+            })
+            .map_or(self.default, |&(_, level)| level)
+    }
+
+    pub fn enabled(&self, metadata: &Metadata) -> bool {
+        self.level_for(metadata.target()) >= *metadata.level()
+    }
+
+    fn max_level(&self) -> LevelFilter {
+        self.targets
+            .iter()
+            .map(|&(_, level)| level)
+            .fold(self.default, LevelFilter::max)
+    }
+}
+
+struct SpanData {
+    name: &'static str,
+    fields: String,
+    refs: usize,
+}
+
+// Writes the events its filter lets through, to stderr unless told
+// otherwise.
+pub struct Logger {
+    filter: Filter,
+    out: Mutex<Box<dyn io::Write + Send>>,
+    start: Instant,
+    next_id: AtomicU64,
+    spans: Mutex<HashMap<u64, SpanData>>,
+}
+
+thread_local! {
+    // the spans this thread is in, innermost last
+    static ENTERED: RefCell<Vec<u64>> = const { RefCell::new(Vec::new()) };
+}
+
+impl Logger {
+    pub fn new(filter: Filter) -> Self {
+        Logger::with_writer(filter, Box::new(io::stderr()))
+    }
+
+    pub fn with_writer(filter: Filter, out: Box<dyn io::Write + Send>) -> Self {
+        Logger {
+            filter,
+            out: Mutex::new(out),
+            start: Instant::now(),
+            next_id: AtomicU64::new(1),
+            spans: Mutex::new(HashMap::new()),
+        }
+    }
+
+    // Makes this the process-wide logger. Events from before are lost,
+    // and there can only be one.
+    pub fn install(self) -> Result<(), SetGlobalDefaultError> {
+        tracing::subscriber::set_global_default(self)
+    }
+
+    fn line(&self, event: &Event) -> String {
+        let metadata = event.metadata();
+        let mut line = format!(
+            "{:.3}s {:>5} {}: ",
+            self.start.elapsed().as_secs_f64(),
+            metadata.level(),
+            metadata.target()
+        );
+        let spans = self.spans.lock().unwrap();
+        ENTERED.with(|entered| {
+            for id in entered.borrow().iter() {
+                if let Some(span) = spans.get(id) {
+                    let _ = write!(line, "{}", span.name);
+                    if !span.fields.is_empty() {
+                        let _ = write!(line, "{{{}}}", span.fields);
+                    }
+                    line.push_str(": ");
+                }
+            }
+        });
+        let mut fields = Fields::default();
+        event.record(&mut fields);
+        line.push_str(&fields.message);
+        if !fields.rest.is_empty() {
+            if !fields.message.is_empty() {
+                line.push(' ');
+            }
+            line.push_str(&fields.rest);
+        }
+        line
+    }
+}
+
+impl Subscriber for Logger {
+    fn register_callsite(&self, metadata: &'static Metadata<'static>) -> Interest {
+        if self.filter.enabled(metadata) {
+            Interest::always()
+        } else {
+            Interest::never()
+        }
+    }
+
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.filter.enabled(metadata)
+    }
+
+    fn max_level_hint(&self) -> Option<LevelFilter> {
+        Some(self.filter.max_level())
+    }
+
+    fn new_span(&self, span: &Attributes) -> Id {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let mut fields = Fields::default();
+        span.record(&mut fields);
+        let data = SpanData {
+            name: span.metadata().name(),
+            fields: fields.rest,
+            refs: 1,
+        };
+        self.spans.lock().unwrap().insert(id, data);
+        Id::from_u64(id)
+    }
+
+    fn record(&self, span: &Id, values: &Record) {
+        if let Some(data) = self.spans.lock().unwrap().get_mut(&span.into_u64()) {
+            let mut fields = Fields {
+                rest: std::mem::take(&mut data.fields),
+                ..Fields::default()
+            };
+            values.record(&mut fields);
+            data.fields = fields.rest;
+        }
+    }
+
+    fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+    fn event(&self, event: &Event) {
+        let line = self.line(event);
+        let _ = writeln!(self.out.lock().unwrap(), "{}", line);
+    }
+
+    fn enter(&self, span: &Id) {
+        ENTERED.with(|entered| entered.borrow_mut().push(span.into_u64()));
+    }
+
+    fn exit(&self, span: &Id) {
+        ENTERED.with(|entered| {
+            let mut entered = entered.borrow_mut();
+            if let Some(at) = entered.iter().rposition(|&id| id == span.into_u64()) {
+                entered.remove(at);
+            }
+        });
+    }
+
+    fn clone_span(&self, id: &Id) -> Id {
+        if let Some(data) = self.spans.lock().unwrap().get_mut(&id.into_u64()) {
+            data.refs += 1;
+        }
+        id.clone()
+    }
+
+    fn try_close(&self, id: Id) -> bool {
+        let mut spans = self.spans.lock().unwrap();
+        let Some(data) = spans.get_mut(&id.into_u64()) else {
+            return false;
+        };
+        data.refs -= 1;
+        if data.refs > 0 {
+            return false;
+        }
+        spans.remove(&id.into_u64());
+        true
+    }
+}
+
+// An event's message, then its other fields as `name=value`.
+#[derive(Default)]
+struct Fields {
+    message: String,
+    rest: String,
+}
+
+impl Visit for Fields {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == "message" {
+            let _ = write!(self.message, "{:?}", value);
+            return;
+        }
+        if !self.rest.is_empty() {
+            self.rest.push(' ');
+        }
+        let _ = write!(self.rest, "{}={:?}", field.name(), value);
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.message.push_str(value);
+            return;
+        }
+        self.record_debug(field, &value);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::Arc;
+    use tracing::Level;
+
+    fn filter(text: &str) -> Filter {
+        text.parse().unwrap()
+    }
+
+    #[test]
+    fn test_per_subsystem_levels() {
+        let filter = filter("nes::ppu=trace, nes::apu=warn,info");
+        assert_eq!(filter.level_for("nes::ppu"), LevelFilter::TRACE);
+        assert_eq!(filter.level_for("nes::ppu::render"), LevelFilter::TRACE);
+        assert_eq!(filter.level_for("nes::apu"), LevelFilter::WARN);
+        assert_eq!(filter.level_for("nes::cpu"), LevelFilter::INFO);
+        // a target is a whole path segment
+        assert_eq!(filter.level_for("nes::ppu_events"), LevelFilter::INFO);
+        assert_eq!(filter.max_level(), LevelFilter::TRACE);
+        assert!(filter.level_for("nes::ppu") >= Level::TRACE);
+        assert!(filter.level_for("nes::apu") < Level::INFO);
+    }
+
+    #[test]
+    fn test_longest_target_wins() {
+        let filter = filter("nes=debug,nes::mapper=off");
+        assert_eq!(filter.level_for("nes::cpu"), LevelFilter::DEBUG);
+        assert_eq!(filter.level_for("nes::mapper"), LevelFilter::OFF);
+        assert_eq!(filter.level_for("other"), LevelFilter::WARN);
+        assert_eq!(
+            self::filter("nes::cpu").level_for("nes::cpu"),
+            LevelFilter::TRACE
+        );
+        assert_eq!(self::filter(""), Filter::default());
+    }
+
+    #[test]
+    fn test_bad_directives() {
+        for text in ["nes::ppu=loud", "=debug", "verbose!"] {
+            assert!(text.parse::<Filter>().is_err(), "{}", text);
+        }
+    }
+
+    #[derive(Clone, Default)]
+    struct Lines(Arc<Mutex<Vec<u8>>>);
+
+    impl io::Write for Lines {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_events_sit_under_their_spans() {
+        let lines = Lines::default();
+        let logger = Logger::with_writer(filter("nes::ppu=trace"), Box::new(lines.clone()));
+        tracing::subscriber::with_default(logger, || {
+            let span = tracing::warn_span!("frame", number = 3);
+            let _entered = span.enter();
+            tracing::trace!(target: "nes::ppu", value = 0x3F, "write ${:04X}", 0x2006);
+            tracing::trace!(target: "nes::apu", "filtered out");
+            span.record("number", 4);
+            tracing::warn!(target: "nes::ppu", "again");
+        });
+        let text = String::from_utf8(lines.0.lock().unwrap().clone()).unwrap();
+        let lines: Vec<&str> = text
+            .lines()
+            .map(|line| line.split_once(' ').unwrap().1)
+            .collect();
+        assert_eq!(
+            lines,
+            [
+                "TRACE nes::ppu: frame{number=3}: write $2006 value=63",
+                " WARN nes::ppu: frame{number=3 number=4}: again",
+            ]
+        );
+    }
+}
@@ -1,285 +1,1046 @@
-type Address = u16;
-type Value = u8;
-
-#[derive(Debug)]
-#[allow(non_camel_case_types)]
-pub enum AddressingMode {
-    Immediate,
-    ZeroPage,
-    ZeroPage_X,
-    ZeroPage_Y,
-    Absolute,
-    Absolute_X,
-    Absolute_Y,
-    Indirect_X,
-    Indirect_Y,
-    NonAddressing,
+use std::fs::{self, File};
+use std::io::{self, BufWriter};
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::process;
+
+use clap::error::ErrorKind;
+use clap::{Args, CommandFactory, Parser, Subcommand, ValueEnum};
+
+#[cfg(any(feature = "sdl2", feature = "winit", feature = "tui"))]
+use nes::audio::Latency;
+use nes::cartridge::Region;
+use nes::cheat::{Cheat, Code};
+use nes::config::{self, Config};
+use nes::crash;
+use nes::debugger::listing::{self, Cdl};
+use nes::debugger::state_diff::StateDiff;
+use nes::debugger::symbols::Symbols;
+use nes::frontend::headless::{self, HeadlessOptions};
+#[cfg(any(feature = "sdl2", feature = "winit", feature = "tui"))]
+use nes::frontend::stats::StatsDisplay;
+#[cfg(any(feature = "sdl2", feature = "winit", feature = "tui"))]
+use nes::frontend::watch::ReloadMode;
+#[cfg(any(feature = "sdl2", feature = "winit", feature = "tui"))]
+use nes::frontend::{FullscreenMode, NetplayRole, Options, Pacing};
+use nes::logging::{self, Filter, Logger};
+use nes::render::palette::Palette;
+#[cfg(any(feature = "sdl2", feature = "winit", feature = "tui"))]
+use nes::render::viewport::{PixelAspect, Scaling};
+use nes::rng::RamInit;
+use nes::testing::suite::{self, SuiteOptions};
+use nes::trace::{Registers, TraceOptions, Tracer};
+use nes::vs::VsPpu;
+use nes::{Cartridge, Emulator};
+
+#[derive(Parser)]
+#[command(name = "nes", version, about = "NES emulator")]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+    #[command(flatten)]
+    run: RunArgs,
 }
 
-#[derive(Debug)]
-pub struct CPU {
-    pub register_a: Value,
-    pub register_x: Value,
-    pub register_y: Value,
-    pub status: Value,
-    pub program_counter: u16,
-    memory: [Value; 0xFFFF],
+// Parsed once at startup, so RunArgs' size doesn't matter.
+#[allow(clippy::large_enum_variant)]
+#[derive(Subcommand)]
+enum Command {
+    #[command(about = "Play a ROM (the default)")]
+    Run(RunArgs),
+    #[command(about = "Print what a ROM's header says")]
+    Info {
+        #[arg(value_name = "ROM")]
+        rom: PathBuf,
+    },
+    #[command(about = "Show what differs between two savestates")]
+    StateDiff {
+        #[arg(value_name = "STATE")]
+        left: PathBuf,
+        #[arg(value_name = "STATE")]
+        right: PathBuf,
+    },
+    #[command(about = "Run every ROM under a directory as a test and report how each did")]
+    TestSuite(TestSuiteArgs),
+    #[command(about = "Disassemble a ROM's PRG into a listing per 16K bank")]
+    Disasm(DisasmArgs),
 }
 
-impl CPU {
-    pub fn new() -> Self {
-        CPU {
-            register_a: 0,
-            register_x: 0,
-            register_y: 0,
-            status: 0,
-            program_counter: 0,
-            memory: [0; 0xFFFF],
-        }
-    }
+#[derive(Args)]
+struct DisasmArgs {
+    #[arg(value_name = "ROM")]
+    rom: PathBuf,
 
-    fn lda(&mut self, mode: &AddressingMode) {
-        let addr = self.get_op_address(mode);
-        let value = self.mem_read(addr);
+    #[arg(
+        long,
+        value_name = "FILE.cdl",
+        help = "An FCEUX code/data log, to tell code from data and place banks"
+    )]
+    cdl: Option<PathBuf>,
 
-        self.register_a = value;
-        self.update_zero_and_negative_flags(self.register_a)
-    }
+    #[arg(
+        long = "symbols",
+        value_name = "FILE",
+        help = "Labels from an ld65 .dbg or FCEUX .nl file; repeatable"
+    )]
+    symbols: Vec<PathBuf>,
 
-    fn inx(&mut self) {
-        if self.register_x == 0xff {
-            self.register_x = 0;
-        } else {
-            self.register_x += 1;
-        }
+    #[arg(
+        long,
+        value_name = "DIR",
+        default_value = ".",
+        help = "Where to write the listings, named like FCEUX's: {ROM}.{bank}.asm"
+    )]
+    out: PathBuf,
+}
 
-        self.update_zero_and_negative_flags(self.register_x);
-    }
+#[derive(Args)]
+struct TestSuiteArgs {
+    #[arg(value_name = "DIR")]
+    dir: PathBuf,
 
-    fn tax(&mut self) {
-        self.register_x = self.register_a;
-        self.update_zero_and_negative_flags(self.register_x)
-    }
+    #[arg(
+        long,
+        value_name = "N",
+        default_value_t = nes::testing::blargg::MAX_FRAMES,
+        help = "Frames a ROM gets to report through $6000 before it's timed out"
+    )]
+    frames: u64,
 
-    fn get_op_address(&self, mode: &AddressingMode) -> Address {
-        match mode {
-            AddressingMode::Immediate => self.program_counter,
-            AddressingMode::ZeroPage => self.mem_read(self.program_counter) as u16,
-            AddressingMode::Absolute => self.mem_read_u16(self.program_counter),
-            AddressingMode::ZeroPage_X => {
-                let pos = self.mem_read(self.program_counter);
-                let addr = pos.wrapping_add(self.register_x) as u16;
-                addr
-            }
-            AddressingMode::ZeroPage_Y => {
-                let pos = self.mem_read(self.program_counter);
-                let addr = pos.wrapping_add(self.register_y) as u16;
-                addr
-            }
+    #[arg(
+        long,
+        value_name = "N",
+        help = "ROMs to run at once [default: one per CPU]"
+    )]
+    jobs: Option<usize>,
 
-            AddressingMode::Absolute_X => {
-                let base = self.mem_read_u16(self.program_counter);
-                let addr = base.wrapping_add(self.register_x as u16);
-                addr
-            }
+    #[arg(
+        long,
+        value_name = "FILE.xml",
+        help = "Also write the results as JUnit XML"
+    )]
+    junit: Option<PathBuf>,
 
-            AddressingMode::Absolute_Y => {
-                let base = self.mem_read_u16(self.program_counter);
-                let addr = base.wrapping_add(self.register_y as u16);
-                addr
-            }
+    #[arg(
+        long,
+        value_name = "FILE.json",
+        help = "Also write the results as JSON"
+    )]
+    json: Option<PathBuf>,
+}
 
-            AddressingMode::Indirect_X => {
-                let base = self.mem_read(self.program_counter);
+#[derive(Args)]
+struct RunArgs {
+    #[arg(
+        value_name = "ROM",
+        help = "The game to play; without one a launcher lists recent games"
+    )]
+    rom: Option<PathBuf>,
 
-                let ptr = (base as u8).wrapping_add(self.register_x);
-                let lo = self.mem_read(ptr as u16);
-                let hi = self.mem_read(ptr.wrapping_add(1) as u16);
+    #[arg(
+        long,
+        value_name = "FILE.toml",
+        help = "Settings file to use instead of config.toml in the config directory"
+    )]
+    config: Option<PathBuf>,
 
-                (hi as u16) << 8 | (lo as u16)
-            }
+    #[arg(
+        long,
+        value_parser = clap::value_parser!(u32).range(1..=8),
+        help = "Window size as a multiple of 256x240 [default: 3]"
+    )]
+    scale: Option<u32>,
 
-            AddressingMode::Indirect_Y => {
-                let base = self.mem_read(self.program_counter);
+    #[arg(
+        long,
+        value_enum,
+        help = "Pixel shape: square, or 8:7 as on an NTSC TV [default: square]"
+    )]
+    aspect: Option<AspectArg>,
 
-                let lo = self.mem_read(base as u16);
-                let hi = self.mem_read((base as u8).wrapping_add(1) as u16);
-                let deref_base = (hi as u16) << 8 | (lo as u16);
-                let deref = deref_base.wrapping_add(self.register_y as u16);
+    #[arg(long, help = "Only scale by whole multiples, leaving a border")]
+    integer_scaling: bool,
 
-                deref
-            }
+    #[arg(long, help = "Stretch the picture over the whole window")]
+    stretch: bool,
 
-            AddressingMode::NonAddressing => {
-                panic!("mode {:?} is not supported", mode);
-            }
-        }
-    }
+    #[arg(
+        long,
+        help = "Start fullscreen; the fullscreen hotkey (F11) toggles it"
+    )]
+    fullscreen: bool,
 
-    fn mem_read(&self, addr: Address) -> Value {
-        self.memory[addr as usize]
-    }
+    #[arg(
+        long,
+        value_enum,
+        help = "Fullscreen as a borderless window or with the display to itself [default: borderless]"
+    )]
+    fullscreen_mode: Option<FullscreenArg>,
 
-    fn mem_read_u16(&self, addr: Address) -> u16 {
-        let lo = self.mem_read(addr) as u16;
-        let hi = self.mem_read(addr + 1) as u16;
-        (hi << 8) | (lo as u16)
-    }
+    #[arg(
+        long,
+        help = "Don't show messages like \"State saved\" over the picture"
+    )]
+    no_osd: bool,
 
-    fn mem_write(&mut self, addr: Address, value: Value) {
-        self.memory[addr as usize] = value;
-    }
+    #[arg(long, help = "Show the buttons each frame ran with over the picture")]
+    input_display: bool,
 
-    fn mem_write_u16(&mut self, addr: Address, value: u16) {
-        let hi = (value >> 8) as u8;
-        let lo = (value & 0xff) as u8;
-        self.mem_write(addr, lo);
-        self.mem_write(addr + 1, hi)
-    }
+    #[arg(
+        long,
+        value_enum,
+        help = "Show frame rate, speed and audio queue over the picture or in the title [default: off]"
+    )]
+    stats: Option<StatsArg>,
 
-    pub fn reset(&mut self) {
-        self.register_a = 0;
-        self.register_x = 0;
-        self.status = 0;
+    #[arg(
+        long,
+        value_name = "FILE.mkv",
+        help = "Record video and audio with ffmpeg from the start; F9 toggles recording"
+    )]
+    record: Option<PathBuf>,
 
-        self.program_counter = self.mem_read_u16(0xFFFC);
-    }
+    #[arg(
+        long,
+        value_parser = parse_speed,
+        help = "Emulation speed, e.g. 0.5 for slow motion [default: 1]"
+    )]
+    speed: Option<f64>,
 
-    pub fn load(&mut self, program: Vec<Value>) {
-        self.memory[0x8000..(0x8000 + program.len())].copy_from_slice(&program[..]);
-        self.mem_write_u16(0xFFFC, 0x8000);
-    }
+    #[arg(
+        long,
+        value_enum,
+        help = "What paces frames: a timer, the display's vsync or the audio device [default: timer]"
+    )]
+    pacing: Option<PacingArg>,
 
-    pub fn load_and_run(&mut self, program: Vec<Value>) {
-        self.load(program);
-        self.reset();
-        self.run();
-    }
+    #[arg(
+        long,
+        value_name = "SAMPLES",
+        value_parser = clap::value_parser!(u32).range(64..=48_000),
+        help = "Audio to keep queued; more rides out stutters, less is heard sooner [default: 2048]"
+    )]
+    audio_latency: Option<u32>,
 
-    fn update_zero_and_negative_flags(&mut self, result: Value) {
-        // is zero?
-        if result == 0 {
-            self.status = self.status | 0b0000_0010; // set zero flag
-        } else {
-            self.status = self.status & 0b1111_1101; // unset zero flag
-        }
+    #[arg(
+        long,
+        value_name = "SAMPLES",
+        value_parser = clap::value_parser!(u32).range(16..=16_384),
+        help = "The audio device's period; raise it if the stats count underruns [default: the device's]"
+    )]
+    audio_buffer: Option<u32>,
 
-        // is negative?
-        if result & 0b1000_0000 != 0 {
-            self.status = self.status | 0b1000_0000; // set negative flag
-        } else {
-            self.status = self.status & 0b0111_1111; // unset negative flag
-        }
-    }
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = RegionArg::Auto,
+        help = "Console region, for timing; auto goes by the config, the ROM header, then country tags in the file name"
+    )]
+    region: RegionArg,
 
-    pub fn run(&mut self) {
-        loop {
-            let opscode = self.mem_read(self.program_counter);
-            self.program_counter += 1;
+    #[arg(
+        long,
+        value_name = "LINES",
+        value_parser = clap::value_parser!(u16).range(..=1000),
+        help = "Overclock: give the CPU this many scanlines to itself after each frame, so games slow down less [default: 0]"
+    )]
+    extra_scanlines: Option<u16>,
 
-            match opscode {
-                // BRK
-                0x00 => return,
+    #[arg(long, value_enum, help = "What RAM holds at power-on [default: zeros]")]
+    ram_init: Option<RamInitArg>,
 
-                // LDA _
-                0xA9 => {
-                    self.lda(&AddressingMode::Immediate);
-                    self.program_counter += 1; // pass over param
-                }
+    #[arg(
+        long,
+        help = "Seed for everything the console randomizes, so it's the same each run [default: 0]"
+    )]
+    seed: Option<u64>,
 
-                0xA5 => {
-                    self.lda(&AddressingMode::ZeroPage);
-                    self.program_counter += 1; // pass over param
-                }
+    #[arg(
+        long,
+        value_name = "FILE.pal",
+        help = "Colour palette to use instead of the built-in one"
+    )]
+    palette: Option<PathBuf>,
 
-                0xAD => {
-                    self.lda(&AddressingMode::Absolute);
-                    self.program_counter += 1; // pass over param
-                }
+    #[arg(
+        long,
+        value_name = "PPU",
+        help = "A VS. System game's PPU, e.g. 2c04-0003, for dumps whose header doesn't say"
+    )]
+    vs_ppu: Option<VsPpu>,
 
-                0xAA => self.tax(),
-                0xE8 => self.inx(),
-                _ => todo!(""),
-            }
-        }
+    #[arg(
+        long,
+        value_name = "N",
+        help = "A VS. System game's DIP switches as a number, switch 1 in bit 0 [default: 0]"
+    )]
+    dip_switches: Option<u8>,
+
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "Savestate to load before starting, or the state in a .crash dump"
+    )]
+    savestate: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "DIR",
+        help = "If the emulator panics, write the machine's state and what led up to it to a .crash file here"
+    )]
+    crash_dump: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "N",
+        help = "With --crash-dump, keep the last N trace lines for the dump (slow), unless --trace-last already does"
+    )]
+    crash_trace: Option<usize>,
+
+    #[arg(long, help = "Run without video or audio, as fast as possible")]
+    headless: bool,
+
+    #[arg(
+        long,
+        help = "Reload the ROM whenever it's rebuilt, from a reset unless the config's hot_reload says \"keep\""
+    )]
+    watch: bool,
+
+    #[arg(long, default_value_t = 60, help = "Frames to run with --headless")]
+    frames: u64,
+
+    #[arg(
+        long,
+        value_name = "FILE.ppm",
+        help = "With --headless, write the last frame here"
+    )]
+    dump_frame: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "With --headless, write the 2K of RAM here"
+    )]
+    dump_ram: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "FILE",
+        num_args = 0..=1,
+        default_missing_value = "-",
+        help = "Log every CPU instruction to FILE, or to stderr"
+    )]
+    trace: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "START-END",
+        value_parser = parse_range,
+        help = "With --trace, only log instructions at these addresses (hex, inclusive); repeatable"
+    )]
+    trace_range: Vec<(u16, u16)>,
+
+    #[arg(
+        long,
+        help = "With --trace, only log JSR, RTS, RTI, BRK and interrupts"
+    )]
+    trace_calls: bool,
+
+    #[arg(
+        long,
+        help = "With --trace, add the PPU's scanline and dot to each line"
+    )]
+    trace_ppu: bool,
+
+    #[arg(
+        long,
+        value_name = "LIST",
+        help = "With --trace, the registers to log, e.g. a,x,cyc [default: a,x,y,p,sp,cyc]"
+    )]
+    trace_registers: Option<Registers>,
+
+    #[arg(
+        long,
+        value_name = "N",
+        help = "With --trace, keep only the last N lines and write them at exit or on the dump_trace hotkey (F10)"
+    )]
+    trace_last: Option<usize>,
+
+    #[arg(
+        long = "symbols",
+        value_name = "FILE",
+        help = "Names for addresses in traces, from an ld65 .dbg or FCEUX .nl file; repeatable"
+    )]
+    symbols: Vec<PathBuf>,
+
+    #[cfg(feature = "lua")]
+    #[arg(
+        long,
+        value_name = "FILE.lua",
+        help = "Run a Lua script with FCEUX's API alongside the game"
+    )]
+    lua: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "With --headless, sample where the game spends its time and write folded stacks for flamegraph.pl here"
+    )]
+    profile: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "CYCLES",
+        default_value_t = nes::debugger::profiler::DEFAULT_PERIOD,
+        help = "With --profile, CPU cycles between samples"
+    )]
+    profile_period: u64,
+
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "With --headless, write a map of the PRG ROM code that ran here"
+    )]
+    coverage: Option<PathBuf>,
+
+    #[arg(
+        long = "cheat",
+        visible_alias = "genie",
+        value_name = "CODE",
+        help = "Play with a Game Genie code (SXIOPO), Pro Action Rocky code (8 hex digits) or RAM value to hold (075A:09); repeatable"
+    )]
+    cheats: Vec<Code>,
+
+    #[arg(
+        long,
+        value_name = "ADDRESS",
+        num_args = 0..=1,
+        default_missing_value = nes::remote::DEFAULT_ADDRESS,
+        help = "Take commands over WebSocket (JSON-RPC) while playing, on ADDRESS or 127.0.0.1:6502"
+    )]
+    remote: Option<SocketAddr>,
+
+    #[arg(
+        long,
+        value_name = "ADDRESS",
+        num_args = 0..=1,
+        default_missing_value = nes::netplay::DEFAULT_ADDRESS,
+        conflicts_with = "netplay_join",
+        help = "Wait for a second player to join over the network, on ADDRESS or 0.0.0.0:6503"
+    )]
+    netplay_host: Option<SocketAddr>,
+
+    #[arg(
+        long,
+        value_name = "ADDRESS",
+        help = "Play as player 2 in the game hosted at ADDRESS; the same ROM is needed"
+    )]
+    netplay_join: Option<SocketAddr>,
+
+    #[arg(
+        long,
+        value_name = "FRAMES",
+        default_value_t = nes::netplay::DEFAULT_DELAY,
+        help = "When hosting, how many frames the input lags to hide the connection's round trip"
+    )]
+    netplay_delay: u8,
+
+    #[arg(
+        long,
+        value_name = "FRAMES",
+        default_value_t = nes::netplay::DEFAULT_WINDOW,
+        help = "When hosting, how many frames may run on a guess of the other player's input and be redone when it comes; 0 waits for it (lockstep)"
+    )]
+    netplay_rollback: u8,
+
+    #[cfg(feature = "rcheevos")]
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "Track the RetroAchievements set in FILE, the game's patch data from the site"
+    )]
+    achievements: Option<PathBuf>,
+
+    #[cfg(feature = "rcheevos")]
+    #[arg(
+        long,
+        requires = "achievements",
+        help = "Play the set in hardcore mode: no savestate loads, rewind, slow motion, frame advance or cheats"
+    )]
+    hardcore: bool,
+
+    #[arg(
+        long,
+        value_name = "FILE.toml",
+        help = "Time a speedrun by the splits in FILE, which say what in memory starts, splits and resets it"
+    )]
+    autosplit: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "ADDRESS",
+        num_args = 0..=1,
+        default_missing_value = nes::autosplit::livesplit::DEFAULT_ADDRESS,
+        requires = "autosplit",
+        help = "Send the splits to LiveSplit's server component, on ADDRESS or 127.0.0.1:16834"
+    )]
+    livesplit: Option<SocketAddr>,
+
+    #[cfg(any(feature = "sdl2", feature = "winit", feature = "tui"))]
+    #[arg(long, value_enum, default_value_t = Frontend::default())]
+    frontend: Frontend,
+
+    #[cfg(feature = "winit")]
+    #[arg(
+        long,
+        value_name = "PRESET|FILE.wgsl",
+        help = "Post-processing shader for the winit front end: none, scanlines, crt or a file"
+    )]
+    shader: Option<String>,
+
+    #[cfg(feature = "tui")]
+    #[arg(
+        long,
+        help = "Draw the terminal front end with braille dots instead of half blocks"
+    )]
+    braille: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum RegionArg {
+    Auto,
+    Ntsc,
+    Pal,
+    Dendy,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum RamInitArg {
+    Zeros,
+    Ones,
+    Random,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum AspectArg {
+    Square,
+    Ntsc,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum FullscreenArg {
+    Borderless,
+    Exclusive,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum StatsArg {
+    Off,
+    Osd,
+    Title,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum PacingArg {
+    Timer,
+    Vsync,
+    Audio,
+}
+
+#[cfg(any(feature = "sdl2", feature = "winit", feature = "tui"))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Frontend {
+    #[cfg(feature = "sdl2")]
+    Sdl,
+    #[cfg(feature = "winit")]
+    Winit,
+    #[cfg(feature = "tui")]
+    Tui,
+}
+
+#[cfg(any(feature = "sdl2", feature = "winit", feature = "tui"))]
+impl Default for Frontend {
+    // the first one built in
+    fn default() -> Self {
+        Frontend::value_variants()[0]
     }
 }
 
-#[cfg(test)]
-mod test {
-    use super::*;
+fn parse_speed(text: &str) -> Result<f64, String> {
+    match text.parse::<f64>() {
+        Ok(speed) if speed > 0.0 && speed.is_finite() => Ok(speed),
+        _ => Err("expected a number above 0".to_string()),
+    }
+}
 
-    #[test]
-    fn test_mem_read() {
-        let mut cpu = CPU::new();
-        cpu.load(vec![0xa9, 0x05, 0x00]);
+// "8000-80FF", "$8000-$80FF" or just "8000".
+fn parse_range(text: &str) -> Result<(u16, u16), String> {
+    let hex = |text: &str| {
+        let text = text.trim();
+        u16::from_str_radix(text.strip_prefix('$').unwrap_or(text), 16)
+            .map_err(|_| format!("'{}' isn't a hex address", text))
+    };
+    let (start, end) = match text.split_once('-') {
+        Some((start, end)) => (hex(start)?, hex(end)?),
+        None => (hex(text)?, hex(text)?),
+    };
+    Ok((start.min(end), start.max(end)))
+}
 
-        assert_eq!(cpu.mem_read(0x8000), 0xa9);
-        assert_eq!(cpu.mem_read(0x8001), 0x05);
+// Exits with a clap-style error message.
+fn fail(kind: ErrorKind, message: String) -> ! {
+    Cli::command().error(kind, message).exit()
+}
 
-        assert_eq!(cpu.mem_read_u16(0x8000), 0x05a9)
+fn load_cartridge(path: &Path) -> Cartridge {
+    let rom = fs::read(path).unwrap_or_else(|err| {
+        fail(
+            ErrorKind::Io,
+            format!("can't read ROM '{}': {}", path.display(), err),
+        )
+    });
+    let mut cartridge = Cartridge::from_rom(rom).unwrap_or_else(|err| {
+        fail(
+            ErrorKind::InvalidValue,
+            format!("'{}' is not a usable ROM: {}", path.display(), err),
+        )
+    });
+    if let Some(name) = path.file_name() {
+        cartridge.guess_region(&name.to_string_lossy());
     }
+    cartridge
+}
 
-    #[test]
-    fn test_0xa9_lda_load() {
-        let mut cpu = CPU::new();
-        cpu.load_and_run(vec![0xa9, 0x05, 0x00]);
+fn describe(path: &Path, cartridge: &Cartridge) -> String {
+    let vs = match cartridge.vs {
+        Some(cabinet) => format!(", VS. System {}", cabinet.ppu),
+        None => String::new(),
+    };
+    format!(
+        "{}: mapper {}, {}K PRG, {}K CHR, {:?} mirroring, {:?}{}, CRC32 {:08X}",
+        path.display(),
+        cartridge.mapper,
+        cartridge.prg_rom.len() / 1024,
+        cartridge.chr_rom.len() / 1024,
+        cartridge.mirroring,
+        cartridge.region,
+        vs,
+        cartridge.crc32()
+    )
+}
 
-        assert_eq!(cpu.register_a, 0x05);
-        assert!(cpu.status & 0b0000_0010 == 0b00); // zero flag not set
-        assert!(cpu.status & 0b1000_0000 == 0) // negative flag not set
+// --config must exist; the one in the config directory is optional.
+fn load_config(args: &RunArgs, crc32: Option<u32>, region: Region) -> Result<Config, String> {
+    let path = match args.config.clone() {
+        Some(path) => path,
+        None => match config::default_path() {
+            Some(path) if path.exists() => path,
+            _ => return Ok(Config::default()),
+        },
+    };
+    Config::load(&path, crc32, region).map_err(|err| format!("{}: {}", path.display(), err))
+}
+
+fn main() {
+    let cli = Cli::parse();
+    match Filter::from_env() {
+        // nothing else installs one
+        Ok(filter) => Logger::new(filter).install().expect("first logger"),
+        Err(err) => {
+            eprintln!("error: {}: {}", logging::FILTER_VAR, err);
+            process::exit(2);
+        }
+    }
+    let args = match cli.command {
+        Some(Command::Info { rom }) => {
+            println!("{}", describe(&rom, &load_cartridge(&rom)));
+            // load_cartridge has already read it
+            if let Ok(bytes) = fs::read(&rom) {
+                println!(
+                    "RetroAchievements hash {}",
+                    nes::achievements::rom_hash(&bytes)
+                );
+            }
+            return;
+        }
+        Some(Command::StateDiff { left, right }) => {
+            match state_diff(&left, &right) {
+                // like diff(1), 1 when they differ
+                Ok(diff) => {
+                    print!("{}", diff);
+                    process::exit(if diff.is_empty() { 0 } else { 1 });
+                }
+                Err(err) => {
+                    eprintln!("error: {}", err);
+                    process::exit(2);
+                }
+            }
+        }
+        Some(Command::TestSuite(args)) => match test_suite(&args) {
+            Ok(passed) => process::exit(if passed { 0 } else { 1 }),
+            Err(err) => {
+                eprintln!("error: {}", err);
+                process::exit(2);
+            }
+        },
+        Some(Command::Disasm(args)) => match disasm(&args) {
+            Ok(()) => return,
+            Err(err) => {
+                eprintln!("error: {}", err);
+                process::exit(1);
+            }
+        },
+        Some(Command::Run(args)) => args,
+        None => cli.run,
+    };
+    if let Err(err) = run(args) {
+        eprintln!("error: {}", err);
+        process::exit(1);
     }
+}
+
+fn state_diff(left: &Path, right: &Path) -> Result<StateDiff, String> {
+    let read = |path: &Path| fs::read(path).map_err(|err| format!("{}: {}", path.display(), err));
+    nes::debugger::state_diff::diff(&read(left)?, &read(right)?).map_err(|err| err.to_string())
+}
 
-    #[test]
-    fn test_0xa9_lda_zero_flag() {
-        let mut cpu = CPU::new();
-        cpu.load_and_run(vec![0xa9, 0x00, 0x00]); // load 5; break;
-        assert!(cpu.status & 0b0000_0010 == 0b10); // zero flag set
+// True if every ROM passed.
+fn test_suite(args: &TestSuiteArgs) -> Result<bool, String> {
+    let mut options = SuiteOptions {
+        max_frames: args.frames,
+        ..SuiteOptions::default()
+    };
+    if let Some(jobs) = args.jobs {
+        options.jobs = jobs;
     }
+    let report = suite::run(&args.dir, &options, &|case| {
+        eprintln!("{} {}", case.outcome.label(), case.name)
+    })
+    .map_err(|err| format!("{}: {}", args.dir.display(), err))?;
+    if report.cases.is_empty() {
+        return Err(format!("no .nes files under {}", args.dir.display()));
+    }
+    print!("{}", report);
 
-    #[test]
-    fn test_0xaa_tax() {
-        let mut cpu = CPU::new();
-        cpu.load_and_run(vec![0xa9, 0x05, 0xaa, 0x00]); // load 5; tax; break;
+    let write = |path: &Path, text: String| {
+        fs::write(path, text).map_err(|err| format!("{}: {}", path.display(), err))
+    };
+    if let Some(path) = &args.junit {
+        let name = args.dir.file_name().unwrap_or_default().to_string_lossy();
+        write(path, report.to_junit(&name))?;
+    }
+    if let Some(path) = &args.json {
+        write(path, format!("{}\n", report.to_json()))?;
+    }
+    Ok(report.passed())
+}
 
-        assert_eq!(cpu.register_x, 0x05)
+// One file per bank, named after the ROM the way .nl files are.
+fn disasm(args: &DisasmArgs) -> Result<(), String> {
+    let cartridge = load_cartridge(&args.rom);
+    let cdl = match &args.cdl {
+        Some(path) => {
+            let bytes = fs::read(path).map_err(|err| format!("{}: {}", path.display(), err))?;
+            Some(
+                Cdl::parse(&bytes, cartridge.prg_rom.len())
+                    .map_err(|err| format!("{}: {}", path.display(), err))?,
+            )
+        }
+        None => None,
+    };
+    let mut symbols = Symbols::new();
+    for path in &args.symbols {
+        symbols
+            .load(path)
+            .map_err(|err| format!("{}: {}", path.display(), err))?;
     }
 
-    #[test]
-    fn test_5_ops_working_together() {
-        let mut cpu = CPU::new();
-        cpu.load_and_run(vec![0xa9, 0xc0, 0xaa, 0xe8, 0x00]);
+    fs::create_dir_all(&args.out).map_err(|err| format!("{}: {}", args.out.display(), err))?;
+    let name = args.rom.file_name().unwrap_or_default().to_string_lossy();
+    let banks = listing::disassemble_prg(&cartridge.prg_rom, cdl.as_ref(), &symbols);
+    for bank in &banks {
+        let path = args.out.join(format!("{}.{:X}.asm", name, bank.number));
+        fs::write(&path, bank.to_string()).map_err(|err| format!("{}: {}", path.display(), err))?;
+    }
+    eprintln!("{} banks written to {}", banks.len(), args.out.display());
+    Ok(())
+}
 
-        assert_eq!(cpu.register_x, 0xc1)
+// Without a ROM the window opens on the launcher.
+fn launch(args: &RunArgs) -> Result<(), String> {
+    if args.headless || !cfg!(any(feature = "sdl2", feature = "winit", feature = "tui")) {
+        return Err("running headless needs a ROM".to_string());
     }
+    let config = load_config(args, None, Region::Ntsc)?;
+    play(Emulator::empty(), "nes", args, &config)
+}
 
-    #[test]
-    fn test_inx_overflow() {
-        let mut cpu = CPU::new();
-        cpu.register_x = 0xff;
+fn run(args: RunArgs) -> Result<(), String> {
+    let Some(path) = args.rom.clone() else {
+        return launch(&args);
+    };
+    let mut cartridge = load_cartridge(&path);
+    println!("{}", describe(&path, &cartridge));
 
-        // lda 0xff
-        // tax
-        // inx
-        // inx
-        // break
-        cpu.load_and_run(vec![0xa9, 0xff, 0xaa, 0xe8, 0xe8, 0x00]);
+    // --region, then [emulation] region, then the ROM; the config's
+    // [region] tables go by the outcome
+    let forced = match args.region {
+        RegionArg::Auto => None,
+        RegionArg::Ntsc => Some(Region::Ntsc),
+        RegionArg::Pal => Some(Region::Pal),
+        RegionArg::Dendy => Some(Region::Dendy),
+    };
+    let mut region = forced.unwrap_or(cartridge.region).console();
+    let mut config = load_config(&args, Some(cartridge.crc32()), region)?;
+    if let Some(chosen) = config.emulation.region.filter(|_| forced.is_none()) {
+        if chosen.console() != region {
+            region = chosen.console();
+            config = load_config(&args, Some(cartridge.crc32()), region)?;
+        }
+    }
+    cartridge.region = region;
 
-        assert_eq!(cpu.register_x, 1)
+    let mut emulator =
+        Emulator::new(cartridge).map_err(|err| format!("{}: {}", path.display(), err))?;
+    emulator.set_sprite_limit(config.video.sprite_limit);
+    let extra_scanlines = args.extra_scanlines.or(config.emulation.extra_scanlines);
+    emulator.set_extra_scanlines(extra_scanlines.unwrap_or(0));
+    let ram_init = args.ram_init.map(|ram| match ram {
+        RamInitArg::Zeros => RamInit::Zeros,
+        RamInitArg::Ones => RamInit::Ones,
+        RamInitArg::Random => RamInit::Random,
+    });
+    emulator.power_on(
+        args.seed.or(config.emulation.seed).unwrap_or(0),
+        ram_init.or(config.emulation.ram_init).unwrap_or_default(),
+    );
+    if emulator.is_vs() {
+        if let Some(ppu) = args.vs_ppu.or(config.vs.ppu) {
+            emulator.set_vs_ppu(ppu);
+        }
+        if let Some(switches) = args.dip_switches.or(config.vs.dip_switches) {
+            emulator.set_dip_switches(switches);
+        }
+    }
+    if let Some(palette) = args.palette.as_ref().or(config.video.palette.as_ref()) {
+        let bytes = fs::read(palette).map_err(|err| format!("{}: {}", palette.display(), err))?;
+        let palette =
+            Palette::from_pal(&bytes).map_err(|err| format!("{}: {}", palette.display(), err))?;
+        emulator.set_palette(palette);
+    }
+    if let Some(savestate) = args.savestate.as_ref() {
+        let loaded = if savestate.extension().is_some_and(|ext| ext == "crash") {
+            fs::read_to_string(savestate)
+                .and_then(|text| crash::state(&text))
+                .and_then(|state| {
+                    emulator
+                        .load_state(&state)
+                        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+                })
+        } else {
+            emulator.load_state_from(savestate)
+        };
+        loaded.map_err(|err| format!("{}: {}", savestate.display(), err))?;
+    }
+    for symbols in &args.symbols {
+        emulator
+            .debugger_mut()
+            .symbols_mut()
+            .load(symbols)
+            .map_err(|err| format!("{}: {}", symbols.display(), err))?;
+    }
+    for &code in &args.cheats {
+        emulator.cheats_mut().add(Cheat::new(code));
+    }
+    if let Some(trace) = args.trace.as_ref() {
+        let out: Box<dyn io::Write + Send> = if trace.as_os_str() == "-" {
+            Box::new(io::stderr())
+        } else {
+            let file =
+                File::create(trace).map_err(|err| format!("{}: {}", trace.display(), err))?;
+            Box::new(BufWriter::new(file))
+        };
+        let options = TraceOptions {
+            ranges: args.trace_range.clone(),
+            calls_only: args.trace_calls,
+            ppu_timing: args.trace_ppu,
+            registers: args.trace_registers.unwrap_or_default(),
+            ring: args.trace_last,
+        };
+        emulator.set_trace(Some(Tracer::with_options(out, options)));
+    } else if let (Some(_), Some(lines)) = (&args.crash_dump, args.crash_trace) {
+        let options = TraceOptions {
+            ring: Some(lines),
+            ..TraceOptions::default()
+        };
+        emulator.set_trace(Some(Tracer::with_options(Box::new(io::sink()), options)));
+    }
+    if args.crash_dump.is_some() {
+        crash::install_hook();
     }
 
-    #[test]
-    fn test_lda_from_memory() {
-        // pointer
-        let mut cpu = CPU::new();
-        cpu.mem_write(0x10, 0x55); // load data at 0x10
-        cpu.load_and_run(vec![0xa5, 0x10, 0x00]); // read data at 0x10
+    let headless =
+        args.headless || !cfg!(any(feature = "sdl2", feature = "winit", feature = "tui"));
+    if headless {
+        match &args.crash_dump {
+            Some(dir) => crash::guard(dir, &mut emulator, |emulator| run_headless(emulator, &args)),
+            None => run_headless(&mut emulator, &args),
+        }
+    } else {
+        play(emulator, &path.display().to_string(), &args, &config)
+    }
+}
 
-        assert_eq!(cpu.register_a, 0x55);
+#[cfg(any(feature = "sdl2", feature = "winit", feature = "tui"))]
+fn play(emulator: Emulator, title: &str, args: &RunArgs, config: &Config) -> Result<(), String> {
+    let defaults = config.options();
+    let options = Options {
+        frame_rate: emulator.frame_rate(),
+        scale: args.scale.unwrap_or(defaults.scale),
+        scaling: Scaling {
+            aspect: match args.aspect {
+                Some(AspectArg::Square) => PixelAspect::Square,
+                Some(AspectArg::Ntsc) => PixelAspect::Ntsc,
+                None => defaults.scaling.aspect,
+            },
+            integer: args.integer_scaling || defaults.scaling.integer,
+            letterbox: !args.stretch && defaults.scaling.letterbox,
+        },
+        speed: args.speed.unwrap_or(defaults.speed),
+        audio_latency: Latency {
+            queued: args
+                .audio_latency
+                .map_or(defaults.audio_latency.queued, |samples| samples as usize),
+            buffer: args.audio_buffer.or(defaults.audio_latency.buffer),
+        },
+        pacing: match args.pacing {
+            Some(PacingArg::Timer) => Pacing::Timer,
+            Some(PacingArg::Vsync) => Pacing::Vsync,
+            Some(PacingArg::Audio) => Pacing::Audio,
+            None => defaults.pacing,
+        },
+        game: args
+            .rom
+            .as_ref()
+            .and_then(|rom| rom.file_stem())
+            .map_or(defaults.game.clone(), |stem| {
+                stem.to_string_lossy().into_owned()
+            }),
+        rom: args.rom.clone(),
+        record: args.record.clone(),
+        remote: args.remote,
+        netplay: match (args.netplay_host, args.netplay_join) {
+            (Some(addr), _) => Some(NetplayRole::Host {
+                addr,
+                delay: args.netplay_delay,
+                window: args.netplay_rollback,
+            }),
+            (None, Some(addr)) => Some(NetplayRole::Join(addr)),
+            (None, None) => None,
+        },
+        #[cfg(feature = "rcheevos")]
+        achievements: args.achievements.clone(),
+        #[cfg(feature = "rcheevos")]
+        hardcore: args.hardcore,
+        autosplit: args.autosplit.clone(),
+        livesplit: args.livesplit,
+        crash_dumps: args.crash_dump.clone(),
+        hot_reload: match defaults.hot_reload {
+            None if args.watch => Some(ReloadMode::Reset),
+            mode => mode,
+        },
+        symbols: args.symbols.clone(),
+        #[cfg(feature = "lua")]
+        script: args.lua.clone(),
+        fullscreen: args.fullscreen || defaults.fullscreen,
+        fullscreen_mode: match args.fullscreen_mode {
+            Some(FullscreenArg::Borderless) => FullscreenMode::Borderless,
+            Some(FullscreenArg::Exclusive) => FullscreenMode::Exclusive,
+            None => defaults.fullscreen_mode,
+        },
+        osd: !args.no_osd && defaults.osd,
+        input_display: args.input_display || defaults.input_display,
+        stats: match args.stats {
+            Some(StatsArg::Off) => StatsDisplay::Off,
+            Some(StatsArg::Osd) => StatsDisplay::Osd,
+            Some(StatsArg::Title) => StatsDisplay::Title,
+            None => defaults.stats,
+        },
+        #[cfg(feature = "winit")]
+        shader: match args.shader.as_ref().or(config.video.shader.as_ref()) {
+            Some(spec) => Some(
+                nes::render::shader::PostShader::load(spec)
+                    .map_err(|err| format!("{}: {}", spec, err))?,
+            ),
+            None => None,
+        },
+        ..defaults
+    };
+    match args.frontend {
+        #[cfg(feature = "sdl2")]
+        Frontend::Sdl => nes::frontend::sdl::run(emulator, title, options),
+        #[cfg(feature = "winit")]
+        Frontend::Winit => nes::frontend::winit::run(emulator, title, options),
+        #[cfg(feature = "tui")]
+        Frontend::Tui => {
+            use nes::frontend::tui::{self, CellMode};
+            let mode = if args.braille {
+                CellMode::Braille
+            } else {
+                CellMode::HalfBlock
+            };
+            tui::run(emulator, title, options, mode).map_err(|err| err.to_string())
+        }
     }
 }
 
-fn main() {
-    let mut cpu = CPU::new();
-    println!("{:?}", cpu);
+#[cfg(not(any(feature = "sdl2", feature = "winit", feature = "tui")))]
+fn play(
+    _emulator: Emulator,
+    _title: &str,
+    _args: &RunArgs,
+    _config: &Config,
+) -> Result<(), String> {
+    unreachable!("builds without a front end always run headless")
+}
+
+fn run_headless(emulator: &mut Emulator, args: &RunArgs) -> Result<(), String> {
+    let options = HeadlessOptions {
+        frames: args.frames,
+        dump_frame: args.dump_frame.clone(),
+        dump_ram: args.dump_ram.clone(),
+    };
+    if args.profile.is_some() {
+        emulator.debugger_mut().start_profiling(args.profile_period);
+    }
+    if args.coverage.is_some() {
+        emulator.start_coverage();
+    }
+    let report = headless::run(emulator, &options).map_err(|err| err.to_string())?;
+    if let Some(trace) = emulator.trace_mut() {
+        trace.dump().map_err(|err| format!("trace: {}", err))?;
+        trace.flush().map_err(|err| format!("trace: {}", err))?;
+    }
+    if let (Some(path), Some(profiler)) = (&args.profile, emulator.debugger_mut().stop_profiling())
+    {
+        let symbols = emulator.debugger().symbols();
+        profiler
+            .write_folded(path, symbols)
+            .map_err(|err| format!("{}: {}", path.display(), err))?;
+        print!("{}", profiler.report(symbols));
+    }
+    if let (Some(path), Some(coverage)) = (&args.coverage, emulator.debugger_mut().stop_coverage())
+    {
+        fs::write(path, coverage.map()).map_err(|err| format!("{}: {}", path.display(), err))?;
+        print!("{}", coverage.summary());
+    }
+    println!(
+        "{} frames, {} CPU cycles in {:.3}s ({:.0} fps)",
+        report.frames,
+        report.cycles,
+        report.elapsed.as_secs_f64(),
+        report.frames_per_second()
+    );
+    Ok(())
 }
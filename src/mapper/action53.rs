@@ -0,0 +1,228 @@
+// Mapper 28, Action 53: the homebrew multicart board. It can stand in for
+// NROM, CNROM, UNROM, AOROM and BNROM, so each game on the cart runs as it
+// would on its own board, inside a slice of the ROM the menu picks.
+//
+// A write to $5000-$5FFF selects one of four registers (bits 7 and 0) and
+// writes to $8000-$FFFF fill it:
+//
+//  $00  CHR bank (bits 0-1, of 32K of CHR RAM)
+//  $01  inner PRG bank, what the game itself switches
+//  $80  mode: mirroring (bits 0-1), PRG banking (bits 2-3) and the size
+//       of the game's slice (bits 4-5, 32K to 256K)
+//  $81  outer PRG bank, which 32K of ROM the slice starts at
+//
+// In the one-screen mirroring modes, bit 4 of a CHR or inner bank write
+// picks the screen, as AOROM's does. The outer bank powers on as $FF, so
+// the menu at the end of the ROM runs first.
+
+use alloc::vec::Vec;
+
+use serde::{Deserialize, Serialize};
+
+use super::{Address, Chr, Mapper, Mirroring, Value};
+use crate::rom::Rom;
+use crate::state::{self, StateError};
+
+const PRG_BANK_SIZE: usize = 0x4000;
+const CHR_BANK_SIZE: usize = 0x2000;
+
+pub struct Action53 {
+    prg_rom: Rom,
+    chr: Chr,
+    registers: Registers,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+struct Registers {
+    select: u8,
+    chr: u8,
+    inner: u8,
+    mode: u8,
+    outer: u8,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Action53State {
+    registers: Registers,
+    chr_ram: Option<Vec<Value>>,
+}
+
+impl Action53 {
+    // An empty `chr` gives the board 32K of CHR RAM.
+    pub fn new(prg_rom: impl Into<Rom>, chr: impl Into<Rom>) -> Self {
+        Action53 {
+            prg_rom: prg_rom.into(),
+            chr: Chr::new(chr.into(), 4 * CHR_BANK_SIZE),
+            registers: Registers {
+                select: 0,
+                chr: 0,
+                inner: 0,
+                mode: 0,
+                outer: 0xFF,
+            },
+        }
+    }
+
+    // The 16K bank at $8000 (`upper` false) or $C000.
+    fn prg_bank(&self, upper: bool) -> usize {
+        let Registers {
+            inner, mode, outer, ..
+        } = self.registers;
+        let outer = (outer as usize) << 1;
+        let inner = inner as usize;
+        // in 16K banks, less one
+        let size = (2 << ((mode >> 4) & 3)) - 1;
+        match ((mode >> 2) & 3, upper) {
+            (0 | 1, _) => (outer & !size) | (((inner << 1) | upper as usize) & size),
+            (2, false) | (3, true) => outer | upper as usize,
+            _ => (outer & !size) | (inner & size),
+        }
+    }
+
+    fn chr_offset(&self, addr: Address) -> usize {
+        ((self.registers.chr & 3) as usize * CHR_BANK_SIZE + addr as usize) % self.chr.len()
+    }
+
+    fn write_register(&mut self, value: Value) {
+        let registers = &mut self.registers;
+        // the one-screen modes take the screen from the game's own writes
+        if matches!(registers.select, 0x00 | 0x01) && registers.mode & 0b10 == 0 {
+            registers.mode = (registers.mode & !1) | ((value >> 4) & 1);
+        }
+        match registers.select {
+            0x00 => registers.chr = value,
+            0x01 => registers.inner = value,
+            0x80 => registers.mode = value,
+            _ => registers.outer = value,
+        }
+    }
+}
+
+impl Mapper for Action53 {
+    fn read_prg(&mut self, addr: Address) -> Value {
+        match self.prg_offset(addr) {
+            Some(offset) => self.prg_rom[offset],
+            None => 0,
+        }
+    }
+
+    fn write_prg(&mut self, addr: Address, value: Value) {
+        match addr {
+            0x5000..=0x5FFF => self.registers.select = value & 0x81,
+            0x8000..=0xFFFF => self.write_register(value),
+            _ => {}
+        }
+    }
+
+    fn prg_offset(&self, addr: Address) -> Option<usize> {
+        if addr < 0x8000 || self.prg_rom.is_empty() {
+            return None;
+        }
+        let bank = self.prg_bank(addr >= 0xC000);
+        Some((bank * PRG_BANK_SIZE + (addr as usize & 0x3FFF)) % self.prg_rom.len())
+    }
+
+    fn read_chr(&mut self, addr: Address) -> Value {
+        self.chr[self.chr_offset(addr)]
+    }
+
+    fn write_chr(&mut self, addr: Address, value: Value) {
+        let offset = self.chr_offset(addr);
+        self.chr.write(offset, value);
+    }
+
+    fn changes_unwritten(&self) -> bool {
+        false
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        match self.registers.mode & 3 {
+            0 => Mirroring::SingleScreenLower,
+            1 => Mirroring::SingleScreenUpper,
+            2 => Mirroring::Vertical,
+            _ => Mirroring::Horizontal,
+        }
+    }
+
+    fn save_state(&self) -> Vec<u8> {
+        state::encode(&Action53State {
+            registers: self.registers,
+            chr_ram: self.chr.ram(),
+        })
+    }
+
+    fn load_state(&mut self, data: &[u8]) -> Result<(), StateError> {
+        let saved: Action53State = state::decode(data)?;
+        self.chr.load_ram(saved.chr_ram)?;
+        self.registers = saved.registers;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // 16K banks, each filled with its number
+    fn banks(count: usize) -> Vec<Value> {
+        (0..count)
+            .flat_map(|bank| vec![bank as Value; PRG_BANK_SIZE])
+            .collect()
+    }
+
+    fn write(board: &mut Action53, register: u8, value: u8) {
+        board.write_prg(0x5000, register);
+        board.write_prg(0x8000, value);
+    }
+
+    #[test]
+    fn test_menu_runs_first() {
+        let mut board = Action53::new(banks(32), Vec::new());
+        assert_eq!(board.read_prg(0x8000), 30);
+        assert_eq!(board.read_prg(0xC000), 31);
+        assert_eq!(board.mirroring(), Mirroring::SingleScreenLower);
+    }
+
+    #[test]
+    fn test_unrom_game_in_a_128k_slice() {
+        let mut board = Action53::new(banks(32), Vec::new());
+        // UNROM-like, fixed $C000, 128K, vertical
+        write(&mut board, 0x80, 0b10_11_10);
+        // the slice's last 32K, banks 14 and 15
+        write(&mut board, 0x81, 7);
+        assert_eq!(board.read_prg(0xC000), 15);
+        // the game's own bank numbers are kept to its slice
+        write(&mut board, 0x01, 3);
+        assert_eq!(board.read_prg(0x8000), 11);
+        write(&mut board, 0x01, 0x0A);
+        assert_eq!(board.read_prg(0x8000), 10);
+        assert_eq!(board.read_prg(0xC000), 15);
+        assert_eq!(board.mirroring(), Mirroring::Vertical);
+    }
+
+    #[test]
+    fn test_aorom_game_picks_its_screen() {
+        let mut board = Action53::new(banks(32), Vec::new());
+        // 32K banks, 256K, one-screen
+        write(&mut board, 0x80, 0b11_00_00);
+        write(&mut board, 0x81, 0);
+        write(&mut board, 0x01, 0x13);
+        assert_eq!(board.read_prg(0x8000), 6);
+        assert_eq!(board.read_prg(0xC000), 7);
+        assert_eq!(board.mirroring(), Mirroring::SingleScreenUpper);
+    }
+
+    #[test]
+    fn test_chr_ram_banks_and_state() {
+        let mut board = Action53::new(banks(2), Vec::new());
+        write(&mut board, 0x00, 2);
+        board.write_chr(0x0010, 0x42);
+        write(&mut board, 0x00, 0);
+        assert_eq!(board.read_chr(0x0010), 0);
+
+        let mut fresh = Action53::new(banks(2), Vec::new());
+        fresh.load_state(&board.save_state()).unwrap();
+        write(&mut fresh, 0x00, 2);
+        assert_eq!(fresh.read_chr(0x0010), 0x42);
+    }
+}
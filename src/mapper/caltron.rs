@@ -0,0 +1,179 @@
+// Mapper 41, the Caltron 6-in-1 board. Its outer register is written
+// through the address: any write to $6000-$67FF stores A0-A5.
+//
+//  bits 0-2  32K PRG bank
+//  bits 3-4  CHR bank, upper two bits
+//  bit 5     mirroring: 1 horizontal, 0 vertical
+//
+// Writes to $8000-$FFFF set the lower two CHR bits, but only while PRG
+// bit 2 is set (the games in the upper half are CNROM ones), and they
+// fight the ROM on the bus like CNROM's.
+
+use alloc::vec::Vec;
+
+use serde::{Deserialize, Serialize};
+
+use super::{Address, Chr, Mapper, Mirroring, Value};
+use crate::rom::Rom;
+use crate::state::{self, StateError};
+
+const PRG_BANK_SIZE: usize = 0x8000;
+const CHR_BANK_SIZE: usize = 0x2000;
+
+pub struct Caltron {
+    prg_rom: Rom,
+    chr: Chr,
+    outer: u8,
+    inner_chr: u8,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CaltronState {
+    outer: u8,
+    inner_chr: u8,
+    chr_ram: Option<Vec<Value>>,
+}
+
+impl Caltron {
+    // An empty `chr` gives the board 8K of CHR RAM.
+    pub fn new(prg_rom: impl Into<Rom>, chr: impl Into<Rom>) -> Self {
+        Caltron {
+            prg_rom: prg_rom.into(),
+            chr: Chr::new(chr.into(), CHR_BANK_SIZE),
+            outer: 0,
+            inner_chr: 0,
+        }
+    }
+
+    fn chr_offset(&self, addr: Address) -> usize {
+        let bank = ((self.outer >> 1) & 0b1100 | self.inner_chr) as usize;
+        (bank * CHR_BANK_SIZE + addr as usize) % self.chr.len()
+    }
+}
+
+impl Mapper for Caltron {
+    fn read_prg(&mut self, addr: Address) -> Value {
+        match self.prg_offset(addr) {
+            Some(offset) => self.prg_rom[offset],
+            None => 0,
+        }
+    }
+
+    fn write_prg(&mut self, addr: Address, value: Value) {
+        match addr {
+            0x6000..=0x67FF => self.outer = (addr & 0x3F) as u8,
+            0x8000..=0xFFFF if self.outer & 0b100 != 0 => {
+                self.inner_chr = (value & self.read_prg(addr)) & 3;
+            }
+            _ => {}
+        }
+    }
+
+    fn prg_offset(&self, addr: Address) -> Option<usize> {
+        if addr < 0x8000 || self.prg_rom.is_empty() {
+            return None;
+        }
+        let bank = (self.outer & 7) as usize;
+        Some((bank * PRG_BANK_SIZE + (addr - 0x8000) as usize) % self.prg_rom.len())
+    }
+
+    fn read_chr(&mut self, addr: Address) -> Value {
+        self.chr[self.chr_offset(addr)]
+    }
+
+    fn write_chr(&mut self, addr: Address, value: Value) {
+        let offset = self.chr_offset(addr);
+        self.chr.write(offset, value);
+    }
+
+    fn changes_unwritten(&self) -> bool {
+        false
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        if self.outer & 0x20 != 0 {
+            Mirroring::Horizontal
+        } else {
+            Mirroring::Vertical
+        }
+    }
+
+    fn save_state(&self) -> Vec<u8> {
+        state::encode(&CaltronState {
+            outer: self.outer,
+            inner_chr: self.inner_chr,
+            chr_ram: self.chr.ram(),
+        })
+    }
+
+    fn load_state(&mut self, data: &[u8]) -> Result<(), StateError> {
+        let saved: CaltronState = state::decode(data)?;
+        self.chr.load_ram(saved.chr_ram)?;
+        self.outer = saved.outer;
+        self.inner_chr = saved.inner_chr;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn banks(count: usize, size: usize) -> Vec<Value> {
+        (0..count)
+            .flat_map(|bank| vec![bank as Value; size])
+            .collect()
+    }
+
+    #[test]
+    fn test_outer_register_is_the_address() {
+        let mut board = Caltron::new(banks(8, PRG_BANK_SIZE), banks(16, CHR_BANK_SIZE));
+        assert_eq!(board.read_prg(0x8000), 0);
+        assert_eq!(board.mirroring(), Mirroring::Vertical);
+
+        board.write_prg(0x6000 | 0b10_1011, 0xFF);
+        assert_eq!(board.read_prg(0xFFFF), 3);
+        assert_eq!(board.read_chr(0), 4);
+        assert_eq!(board.mirroring(), Mirroring::Horizontal);
+    }
+
+    #[test]
+    fn test_inner_chr_only_for_the_upper_games() {
+        let mut prg = banks(8, PRG_BANK_SIZE);
+        // the value the game writes over
+        prg[4 * PRG_BANK_SIZE] = 0xFF;
+        let mut board = Caltron::new(prg, banks(16, CHR_BANK_SIZE));
+        board.write_prg(0x6000 | 0b01_011, 0);
+        board.write_prg(0x8000, 2);
+        assert_eq!(board.read_chr(0), 4);
+
+        board.write_prg(0x6000 | 0b01_100, 0);
+        board.write_prg(0x8000, 2);
+        assert_eq!(board.read_chr(0), 6);
+        // the ROM's 0x04 at $8001 clears both bits
+        board.write_prg(0x8001, 3);
+        assert_eq!(board.read_chr(0), 4);
+
+        let mut fresh = Caltron::new(banks(8, PRG_BANK_SIZE), banks(16, CHR_BANK_SIZE));
+        board.write_prg(0x8000, 1);
+        fresh.load_state(&board.save_state()).unwrap();
+        assert_eq!(fresh.read_chr(0), 5);
+        assert_eq!(fresh.read_prg(0x8000), 4);
+    }
+
+    #[test]
+    fn test_chr_ram_and_state() {
+        let mut board = Caltron::new(banks(8, PRG_BANK_SIZE), Vec::new());
+        board.write_chr(0x0010, 0x42);
+        assert_eq!(board.read_chr(0x0010), 0x42);
+
+        let mut fresh = Caltron::new(banks(8, PRG_BANK_SIZE), Vec::new());
+        fresh.load_state(&board.save_state()).unwrap();
+        assert_eq!(fresh.read_chr(0x0010), 0x42);
+
+        // ROM stays as it is
+        let mut rom = Caltron::new(banks(8, PRG_BANK_SIZE), banks(16, CHR_BANK_SIZE));
+        rom.write_chr(0x0010, 0x42);
+        assert_eq!(rom.read_chr(0x0010), 0);
+    }
+}
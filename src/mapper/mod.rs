@@ -0,0 +1,146 @@
+// Cartridge boards. The CPU sees a mapper at $4020-$FFFF and the PPU sees it
+// at $0000-$1FFF (pattern tables); anything else a board does (bank
+// switching, IRQ counters, extra sound chips) hangs off this trait. Boards
+// are Send, so a machine can move to another thread.
+
+pub mod action53;
+pub mod caltron;
+pub mod nrom;
+pub mod nsf;
+pub mod nwc;
+pub mod vs;
+
+use alloc::vec;
+use alloc::vec::Vec;
+use core::ops::Deref;
+
+use crate::apu::expansion::ExpansionAudio;
+use crate::rom::Rom;
+use crate::state::StateError;
+
+type Address = u16;
+type Value = u8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mirroring {
+    Horizontal,
+    Vertical,
+    SingleScreenLower,
+    SingleScreenUpper,
+    FourScreen,
+}
+
+// Pattern tables: the cartridge's CHR ROM, shared with it, or RAM on
+// boards that have none.
+enum Chr {
+    Rom(Rom),
+    Ram(Vec<Value>),
+}
+
+impl Chr {
+    // An empty `rom` gives `ram_size` bytes of RAM.
+    fn new(rom: Rom, ram_size: usize) -> Self {
+        if rom.is_empty() {
+            Chr::Ram(vec![0; ram_size])
+        } else {
+            Chr::Rom(rom)
+        }
+    }
+
+    // ROM ignores it.
+    fn write(&mut self, offset: usize, value: Value) {
+        if let Chr::Ram(ram) = self {
+            ram[offset] = value;
+        }
+    }
+
+    // For savestates; ROM stays out.
+    fn ram(&self) -> Option<Vec<Value>> {
+        match self {
+            Chr::Rom(_) => None,
+            Chr::Ram(ram) => Some(ram.clone()),
+        }
+    }
+
+    fn load_ram(&mut self, saved: Option<Vec<Value>>) -> Result<(), StateError> {
+        match (self, saved) {
+            (Chr::Ram(ram), Some(saved)) if saved.len() == ram.len() => *ram = saved,
+            (Chr::Rom(_), None) => {}
+            _ => return Err(StateError::WrongCartridge),
+        }
+        Ok(())
+    }
+}
+
+impl Deref for Chr {
+    type Target = [Value];
+
+    fn deref(&self) -> &[Value] {
+        match self {
+            Chr::Rom(rom) => rom,
+            Chr::Ram(ram) => ram,
+        }
+    }
+}
+
+pub trait Mapper: Send {
+    fn read_prg(&mut self, addr: Address) -> Value;
+    fn write_prg(&mut self, addr: Address, value: Value);
+
+    fn read_chr(&mut self, addr: Address) -> Value;
+    fn write_chr(&mut self, addr: Address, value: Value);
+
+    fn mirroring(&self) -> Mirroring;
+
+    // $4016 writes, which the VS. System's board banks on; the
+    // controllers get them too.
+    fn write_4016(&mut self, _value: Value) {}
+
+    // Once per CPU cycle, for boards that count them.
+    fn clock(&mut self) {}
+
+    // Whether the board's state can change without the CPU or PPU writing
+    // to it (a counter clock() runs, a latch reads flip), which frame
+    // hashes can't go by writes alone for; see state::MachineHasher. A
+    // board is taken to unless it says otherwise.
+    fn changes_unwritten(&self) -> bool {
+        true
+    }
+
+    // Whether the board is holding the CPU's IRQ line low.
+    fn irq_pending(&self) -> bool {
+        false
+    }
+
+    // Where in PRG ROM the byte the CPU sees at `addr` comes from, for
+    // debuggers; None for RAM and registers.
+    fn prg_offset(&self, _addr: Address) -> Option<usize> {
+        None
+    }
+
+    // Boards with their own sound chip (VRC6, VRC7, N163, FDS, 5B, MMC5)
+    // return it here so the mixer can add it in.
+    fn expansion_audio(&mut self) -> Option<&mut dyn ExpansionAudio> {
+        None
+    }
+
+    // Whatever the board changes while the game runs (RAM, bank registers,
+    // IRQ counters) for savestates; ROM stays out.
+    fn save_state(&self) -> Vec<u8> {
+        Vec::new()
+    }
+
+    fn load_state(&mut self, _state: &[u8]) -> Result<(), StateError> {
+        Ok(())
+    }
+
+    // Work RAM at $6000-$7FFF, if the board has any. With a battery on
+    // the board it holds the game's saves.
+    fn prg_ram(&self) -> &[Value] {
+        &[]
+    }
+
+    fn prg_ram_mut(&mut self) -> &mut [Value] {
+        &mut []
+    }
+}
@@ -0,0 +1,158 @@
+// Mapper 0: no bank switching. 16K PRG carts are mirrored into both halves
+// of $8000-$FFFF.
+
+use alloc::vec::Vec;
+
+use serde::{Deserialize, Serialize};
+
+use super::{Address, Chr, Mapper, Mirroring, Value};
+use crate::rom::Rom;
+use crate::state::{self, StateError};
+
+pub struct Nrom {
+    prg_rom: Rom,
+    prg_ram: [Value; 0x2000],
+    chr: Chr,
+    mirroring: Mirroring,
+}
+
+#[derive(Serialize, Deserialize)]
+struct NromState {
+    #[serde(with = "crate::state::byte_array")]
+    prg_ram: [Value; 0x2000],
+    chr_ram: Option<Vec<Value>>,
+}
+
+impl Nrom {
+    // An empty `chr` gives the board 8K of CHR RAM.
+    pub fn new(prg_rom: impl Into<Rom>, chr: impl Into<Rom>, mirroring: Mirroring) -> Self {
+        Nrom {
+            prg_rom: prg_rom.into(),
+            prg_ram: [0; 0x2000],
+            chr: Chr::new(chr.into(), 0x2000),
+            mirroring,
+        }
+    }
+}
+
+impl Mapper for Nrom {
+    fn read_prg(&mut self, addr: Address) -> Value {
+        match addr {
+            0x6000..=0x7FFF => self.prg_ram[(addr - 0x6000) as usize],
+            0x8000..=0xFFFF if !self.prg_rom.is_empty() => {
+                self.prg_rom[(addr - 0x8000) as usize % self.prg_rom.len()]
+            }
+            _ => 0,
+        }
+    }
+
+    fn write_prg(&mut self, addr: Address, value: Value) {
+        if let 0x6000..=0x7FFF = addr {
+            self.prg_ram[(addr - 0x6000) as usize] = value;
+        }
+    }
+
+    fn prg_offset(&self, addr: Address) -> Option<usize> {
+        (addr >= 0x8000 && !self.prg_rom.is_empty())
+            .then(|| (addr - 0x8000) as usize % self.prg_rom.len())
+    }
+
+    fn read_chr(&mut self, addr: Address) -> Value {
+        self.chr[addr as usize % self.chr.len()]
+    }
+
+    fn write_chr(&mut self, addr: Address, value: Value) {
+        let offset = addr as usize % self.chr.len();
+        self.chr.write(offset, value);
+    }
+
+    fn changes_unwritten(&self) -> bool {
+        false
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn prg_ram(&self) -> &[Value] {
+        &self.prg_ram
+    }
+
+    fn prg_ram_mut(&mut self) -> &mut [Value] {
+        &mut self.prg_ram
+    }
+
+    fn save_state(&self) -> Vec<u8> {
+        state::encode(&NromState {
+            prg_ram: self.prg_ram,
+            chr_ram: self.chr.ram(),
+        })
+    }
+
+    fn load_state(&mut self, data: &[u8]) -> Result<(), StateError> {
+        let saved: NromState = state::decode(data)?;
+        self.chr.load_ram(saved.chr_ram)?;
+        self.prg_ram = saved.prg_ram;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_16k_prg_is_mirrored() {
+        let mut prg = vec![0; 0x4000];
+        prg[0] = 0x11;
+        prg[0x3FFF] = 0x22;
+        let mut nrom = Nrom::new(prg, vec![0; 0x2000], Mirroring::Horizontal);
+
+        assert_eq!(nrom.read_prg(0x8000), 0x11);
+        assert_eq!(nrom.read_prg(0xC000), 0x11);
+        assert_eq!(nrom.read_prg(0xFFFF), 0x22);
+    }
+
+    #[test]
+    fn test_prg_ram() {
+        let mut nrom = Nrom::new(vec![0; 0x8000], vec![0; 0x2000], Mirroring::Vertical);
+        nrom.write_prg(0x6123, 0x42);
+        nrom.write_prg(0x8000, 0x42); // ROM, ignored
+
+        assert_eq!(nrom.read_prg(0x6123), 0x42);
+        assert_eq!(nrom.read_prg(0x8000), 0x00);
+    }
+
+    #[test]
+    fn test_chr_rom_is_read_only_and_chr_ram_is_not() {
+        let mut rom = Nrom::new(vec![0; 0x4000], vec![0x33; 0x2000], Mirroring::Vertical);
+        rom.write_chr(0x0010, 0x44);
+        assert_eq!(rom.read_chr(0x0010), 0x33);
+
+        let mut ram = Nrom::new(vec![0; 0x4000], Vec::new(), Mirroring::Vertical);
+        ram.write_chr(0x0010, 0x44);
+        assert_eq!(ram.read_chr(0x0010), 0x44);
+    }
+
+    #[test]
+    fn test_no_expansion_audio() {
+        let mut nrom = Nrom::new(vec![0; 0x4000], Vec::new(), Mirroring::Vertical);
+        assert!(nrom.expansion_audio().is_none());
+    }
+
+    #[test]
+    fn test_state_only_loads_into_a_matching_board() {
+        let mut ram = Nrom::new(vec![0; 0x4000], Vec::new(), Mirroring::Vertical);
+        ram.write_prg(0x6000, 0x12);
+        ram.write_chr(0x0000, 0x34);
+        let state = ram.save_state();
+
+        let mut fresh = Nrom::new(vec![0; 0x4000], Vec::new(), Mirroring::Vertical);
+        fresh.load_state(&state).unwrap();
+        assert_eq!(fresh.read_prg(0x6000), 0x12);
+        assert_eq!(fresh.read_chr(0x0000), 0x34);
+
+        let mut rom = Nrom::new(vec![0; 0x4000], vec![0; 0x2000], Mirroring::Vertical);
+        assert_eq!(rom.load_state(&state), Err(StateError::WrongCartridge));
+    }
+}
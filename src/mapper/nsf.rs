@@ -0,0 +1,187 @@
+// The virtual board an NSF runs on: 4K banks selected through $5FF8-$5FFF
+// (or a flat image for non-bankswitched rips), 8K of RAM at $6000, and
+// optionally a sound chip that receives every write the board itself
+// doesn't decode. FDS rips, which also expect RAM at $8000-$DFFF, are not
+// supported yet.
+
+use alloc::boxed::Box;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use serde::{Deserialize, Serialize};
+
+use super::{Address, Mapper, Mirroring, Value};
+use crate::apu::expansion::ExpansionAudio;
+use crate::nsf::Nsf;
+use crate::state::{self, StateError};
+
+const BANK_SIZE: usize = 0x1000;
+
+pub struct NsfMapper {
+    prg: Vec<Value>,
+    bankswitched: bool,
+    bank_init: [u8; 8],
+    banks: [u8; 8],
+    prg_ram: [Value; 0x2000],
+    expansion: Option<Box<dyn ExpansionAudio>>,
+}
+
+// The sound chip's own registers aren't part of it.
+#[derive(Serialize, Deserialize)]
+struct NsfState {
+    banks: [u8; 8],
+    #[serde(with = "crate::state::byte_array")]
+    prg_ram: [Value; 0x2000],
+}
+
+impl NsfMapper {
+    pub fn new(nsf: &Nsf) -> Self {
+        let bankswitched = nsf.is_bankswitched();
+
+        let prg = if bankswitched {
+            // data starts at the load address' offset within its bank
+            let padding = (nsf.load_address as usize) & (BANK_SIZE - 1);
+            let mut prg = vec![0; padding];
+            prg.extend_from_slice(&nsf.data);
+            prg.resize(prg.len().div_ceil(BANK_SIZE) * BANK_SIZE, 0);
+            prg
+        } else {
+            let mut prg = vec![0; 0x8000];
+            let offset = (nsf.load_address - 0x8000) as usize;
+            let len = nsf.data.len().min(prg.len() - offset);
+            prg[offset..offset + len].copy_from_slice(&nsf.data[..len]);
+            prg
+        };
+
+        NsfMapper {
+            prg,
+            bankswitched,
+            bank_init: nsf.bank_init,
+            banks: nsf.bank_init,
+            prg_ram: [0; 0x2000],
+            expansion: None,
+        }
+    }
+
+    pub fn set_expansion_audio(&mut self, chip: Box<dyn ExpansionAudio>) {
+        self.expansion = Some(chip);
+    }
+
+    // Back to the state INIT expects: initial banks and cleared RAM.
+    pub fn reset(&mut self) {
+        self.banks = self.bank_init;
+        self.prg_ram = [0; 0x2000];
+    }
+}
+
+impl Mapper for NsfMapper {
+    fn read_prg(&mut self, addr: Address) -> Value {
+        match addr {
+            0x6000..=0x7FFF => self.prg_ram[(addr - 0x6000) as usize],
+            0x8000..=0xFFFF if self.bankswitched => {
+                let slot = ((addr - 0x8000) as usize) / BANK_SIZE;
+                let offset = self.banks[slot] as usize * BANK_SIZE + (addr as usize & 0x0FFF);
+                self.prg[offset % self.prg.len()]
+            }
+            0x8000..=0xFFFF => self.prg[(addr - 0x8000) as usize],
+            _ => match self.expansion.as_mut() {
+                Some(chip) => chip.read_register(addr).unwrap_or(0),
+                None => 0,
+            },
+        }
+    }
+
+    fn write_prg(&mut self, addr: Address, value: Value) {
+        match addr {
+            0x5FF8..=0x5FFF => self.banks[(addr - 0x5FF8) as usize] = value,
+            0x6000..=0x7FFF => self.prg_ram[(addr - 0x6000) as usize] = value,
+            _ => {
+                if let Some(chip) = self.expansion.as_mut() {
+                    chip.write_register(addr, value);
+                }
+            }
+        }
+    }
+
+    fn read_chr(&mut self, _addr: Address) -> Value {
+        0
+    }
+
+    fn write_chr(&mut self, _addr: Address, _value: Value) {}
+
+    fn changes_unwritten(&self) -> bool {
+        false
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        Mirroring::Horizontal
+    }
+
+    fn expansion_audio(&mut self) -> Option<&mut dyn ExpansionAudio> {
+        match self.expansion.as_mut() {
+            Some(chip) => Some(chip.as_mut()),
+            None => None,
+        }
+    }
+
+    fn save_state(&self) -> Vec<u8> {
+        state::encode(&NsfState {
+            banks: self.banks,
+            prg_ram: self.prg_ram,
+        })
+    }
+
+    fn load_state(&mut self, data: &[u8]) -> Result<(), StateError> {
+        let saved: NsfState = state::decode(data)?;
+        self.banks = saved.banks;
+        self.prg_ram = saved.prg_ram;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::nsf::test::test_nsf_bytes;
+
+    fn bankswitched_nsf() -> Nsf {
+        let mut nsf = Nsf::parse(&test_nsf_bytes()).unwrap();
+        nsf.load_address = 0x8010;
+        nsf.bank_init = [0, 1, 0, 0, 0, 0, 0, 0];
+        nsf.data = vec![0x11; 0x2000];
+        nsf.data[BANK_SIZE - 0x10] = 0x22; // first byte of bank 1
+        nsf
+    }
+
+    #[test]
+    fn test_flat_image_at_load_address() {
+        let mut nsf = Nsf::parse(&test_nsf_bytes()).unwrap();
+        nsf.load_address = 0x8100;
+        let mut mapper = NsfMapper::new(&nsf);
+
+        assert_eq!(mapper.read_prg(0x8000), 0x00);
+        assert_eq!(mapper.read_prg(0x8100), 0x85);
+    }
+
+    #[test]
+    fn test_initial_banks_and_padding() {
+        let mut mapper = NsfMapper::new(&bankswitched_nsf());
+
+        assert_eq!(mapper.read_prg(0x8000), 0x00); // padding
+        assert_eq!(mapper.read_prg(0x8010), 0x11);
+        assert_eq!(mapper.read_prg(0x9000), 0x22);
+    }
+
+    #[test]
+    fn test_bank_switching_and_reset() {
+        let mut mapper = NsfMapper::new(&bankswitched_nsf());
+        mapper.write_prg(0x5FF8, 1);
+        mapper.write_prg(0x6000, 0x99);
+        assert_eq!(mapper.read_prg(0x8000), 0x22);
+        assert_eq!(mapper.read_prg(0x6000), 0x99);
+
+        mapper.reset();
+        assert_eq!(mapper.read_prg(0x8000), 0x00);
+        assert_eq!(mapper.read_prg(0x6000), 0x00);
+    }
+}
@@ -0,0 +1,311 @@
+// Mapper 105, the Nintendo World Championships 1990 cart (NES-EVENT). An
+// MMC1 in front of two 128K PRG chips, 8K of CHR RAM, and a 30-bit
+// counter that times the contest.
+//
+// The MMC1 takes its registers serially, five writes of bit 0, as on
+// SxROM. Its CHR registers bank nothing; bits of the first are
+// repurposed:
+//
+//  bits 1-2  32K bank of the first chip, when bit 3 is clear
+//  bit 3     1 maps the second chip at $8000-$FFFF, banked in 16K as the
+//            MMC1's PRG register and mode bits say
+//  bit 4     1 holds the counter at 0 and drops its IRQ; 0 lets it count
+//
+// Until bit 4 has been set and cleared once, the first 32K stays put; the
+// game's menu does that before the contest. The counter goes up every CPU
+// cycle and raises an IRQ at (16 + switches) * 2^25: 5:00 with every DIP
+// switch off, and 18.75s more for each count. The championships ran with
+// 4, for 6:15.
+
+use alloc::vec::Vec;
+
+use serde::{Deserialize, Serialize};
+
+use super::{Address, Mapper, Mirroring, Value};
+use crate::rom::Rom;
+use crate::state::{self, StateError};
+
+const PRG_BANK_SIZE: usize = 0x4000;
+const SECOND_CHIP: usize = 8;
+const TIMER_SWITCHES: u32 = 4;
+const COUNTER_MASK: u32 = (1 << 30) - 1;
+
+pub struct Nwc {
+    prg_rom: Rom,
+    prg_ram: [Value; 0x2000],
+    chr_ram: [Value; 0x2000],
+    registers: Registers,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+struct Registers {
+    shift: u8,
+    writes: u8,
+    control: u8,
+    chr: u8,
+    prg: u8,
+    // 0 at power on, 1 once the I bit has been set, 2 once cleared again
+    unlock: u8,
+    counter: u32,
+    irq: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+struct NwcState {
+    registers: Registers,
+    #[serde(with = "crate::state::byte_array")]
+    prg_ram: [Value; 0x2000],
+    #[serde(with = "crate::state::byte_array")]
+    chr_ram: [Value; 0x2000],
+}
+
+impl Nwc {
+    pub fn new(prg_rom: impl Into<Rom>) -> Self {
+        Nwc {
+            prg_rom: prg_rom.into(),
+            prg_ram: [0; 0x2000],
+            chr_ram: [0; 0x2000],
+            registers: Registers {
+                shift: 0,
+                writes: 0,
+                control: 0x0C,
+                // counting waits for the game
+                chr: 0x10,
+                prg: 0,
+                unlock: 0,
+                counter: 0,
+                irq: false,
+            },
+        }
+    }
+
+    // CPU cycles left before the timer runs out.
+    pub fn time_left(&self) -> u32 {
+        timer_target() - self.registers.counter.min(timer_target())
+    }
+
+    fn counter_held(&self) -> bool {
+        self.registers.chr & 0x10 != 0
+    }
+
+    fn write_serial(&mut self, addr: Address, value: Value) {
+        let registers = &mut self.registers;
+        if value & 0x80 != 0 {
+            registers.shift = 0;
+            registers.writes = 0;
+            registers.control |= 0x0C;
+            return;
+        }
+        registers.shift |= (value & 1) << registers.writes;
+        registers.writes += 1;
+        if registers.writes < 5 {
+            return;
+        }
+        let data = core::mem::take(&mut registers.shift);
+        registers.writes = 0;
+        match addr {
+            0x8000..=0x9FFF => registers.control = data,
+            0xA000..=0xBFFF => {
+                registers.chr = data;
+                registers.unlock = match (registers.unlock, data & 0x10 != 0) {
+                    (0, true) => 1,
+                    (1, false) => 2,
+                    (unlock, _) => unlock,
+                };
+                if self.counter_held() {
+                    self.registers.counter = 0;
+                    self.registers.irq = false;
+                }
+            }
+            0xC000..=0xDFFF => {}
+            _ => registers.prg = data,
+        }
+    }
+
+    // The 16K bank at $8000 (`upper` false) or $C000.
+    fn prg_bank(&self, upper: bool) -> usize {
+        let Registers {
+            control,
+            chr,
+            prg,
+            unlock,
+            ..
+        } = self.registers;
+        if unlock < 2 {
+            return upper as usize;
+        }
+        if chr & 0x08 == 0 {
+            return ((chr as usize >> 1) & 3) << 1 | upper as usize;
+        }
+        let prg = (prg & 7) as usize;
+        SECOND_CHIP
+            + match ((control >> 2) & 3, upper) {
+                (0 | 1, _) => (prg & !1) | upper as usize,
+                (2, false) => 0,
+                (3, true) => 7,
+                _ => prg,
+            }
+    }
+}
+
+fn timer_target() -> u32 {
+    (16 + TIMER_SWITCHES) << 25
+}
+
+impl Mapper for Nwc {
+    fn read_prg(&mut self, addr: Address) -> Value {
+        match addr {
+            0x6000..=0x7FFF => self.prg_ram[(addr - 0x6000) as usize],
+            _ => match self.prg_offset(addr) {
+                Some(offset) => self.prg_rom[offset],
+                None => 0,
+            },
+        }
+    }
+
+    fn write_prg(&mut self, addr: Address, value: Value) {
+        match addr {
+            0x6000..=0x7FFF => self.prg_ram[(addr - 0x6000) as usize] = value,
+            0x8000..=0xFFFF => self.write_serial(addr, value),
+            _ => {}
+        }
+    }
+
+    fn prg_offset(&self, addr: Address) -> Option<usize> {
+        if addr < 0x8000 || self.prg_rom.is_empty() {
+            return None;
+        }
+        let bank = self.prg_bank(addr >= 0xC000);
+        Some((bank * PRG_BANK_SIZE + (addr as usize & 0x3FFF)) % self.prg_rom.len())
+    }
+
+    fn read_chr(&mut self, addr: Address) -> Value {
+        self.chr_ram[addr as usize & 0x1FFF]
+    }
+
+    fn write_chr(&mut self, addr: Address, value: Value) {
+        self.chr_ram[addr as usize & 0x1FFF] = value;
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        match self.registers.control & 3 {
+            0 => Mirroring::SingleScreenLower,
+            1 => Mirroring::SingleScreenUpper,
+            2 => Mirroring::Vertical,
+            _ => Mirroring::Horizontal,
+        }
+    }
+
+    fn clock(&mut self) {
+        if self.counter_held() {
+            return;
+        }
+        let registers = &mut self.registers;
+        registers.counter = (registers.counter + 1) & COUNTER_MASK;
+        if registers.counter == timer_target() {
+            registers.irq = true;
+        }
+    }
+
+    fn irq_pending(&self) -> bool {
+        self.registers.irq
+    }
+
+    fn prg_ram(&self) -> &[Value] {
+        &self.prg_ram
+    }
+
+    fn prg_ram_mut(&mut self) -> &mut [Value] {
+        &mut self.prg_ram
+    }
+
+    fn save_state(&self) -> Vec<u8> {
+        state::encode(&NwcState {
+            registers: self.registers,
+            prg_ram: self.prg_ram,
+            chr_ram: self.chr_ram,
+        })
+    }
+
+    fn load_state(&mut self, data: &[u8]) -> Result<(), StateError> {
+        let saved: NwcState = state::decode(data)?;
+        self.registers = saved.registers;
+        self.prg_ram = saved.prg_ram;
+        self.chr_ram = saved.chr_ram;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn board() -> Nwc {
+        let prg: Vec<Value> = (0..16)
+            .flat_map(|bank| vec![bank as Value; PRG_BANK_SIZE])
+            .collect();
+        Nwc::new(prg)
+    }
+
+    fn write(board: &mut Nwc, addr: Address, value: u8) {
+        for bit in 0..5 {
+            board.write_prg(addr, value >> bit);
+        }
+    }
+
+    #[test]
+    fn test_locked_until_the_i_bit_goes_high_then_low() {
+        let mut board = board();
+        write(&mut board, 0xA000, 0b0_0100);
+        assert_eq!(board.read_prg(0x8000), 0);
+        assert_eq!(board.read_prg(0xC000), 1);
+        write(&mut board, 0xA000, 0b1_0100);
+        write(&mut board, 0xA000, 0b0_0100);
+        // the third 32K of the first chip
+        assert_eq!(board.read_prg(0x8000), 4);
+        assert_eq!(board.read_prg(0xFFFF), 5);
+    }
+
+    #[test]
+    fn test_second_chip_banks_like_mmc1() {
+        let mut board = board();
+        write(&mut board, 0xA000, 0x10);
+        write(&mut board, 0xA000, 0x08);
+        // fixed $C000, horizontal
+        write(&mut board, 0x8000, 0b0_1111);
+        write(&mut board, 0xE000, 3);
+        assert_eq!(board.read_prg(0x8000), 11);
+        assert_eq!(board.read_prg(0xC000), 15);
+        assert_eq!(board.mirroring(), Mirroring::Horizontal);
+        // a write with bit 7 set starts over and fixes $C000 again
+        board.write_prg(0x8000, 1);
+        board.write_prg(0x8000, 0x80);
+        write(&mut board, 0xE000, 2);
+        assert_eq!(board.read_prg(0x8000), 10);
+    }
+
+    #[test]
+    fn test_timer_raises_an_irq() {
+        let mut board = board();
+        board.clock();
+        assert_eq!(board.time_left(), timer_target());
+        write(&mut board, 0xA000, 0);
+        for _ in 0..10 {
+            board.clock();
+        }
+        assert_eq!(board.time_left(), timer_target() - 10);
+
+        board.registers.counter = timer_target() - 1;
+        assert!(!board.irq_pending());
+        board.clock();
+        assert!(board.irq_pending());
+        assert_eq!(board.time_left(), 0);
+
+        let mut fresh = self::board();
+        fresh.load_state(&board.save_state()).unwrap();
+        assert!(fresh.irq_pending());
+        write(&mut fresh, 0xA000, 0x10);
+        assert!(!fresh.irq_pending());
+        assert_eq!(fresh.time_left(), timer_target());
+    }
+}
@@ -0,0 +1,172 @@
+// Mapper 99, the VS. System's own board. Bit 2 of $4016 writes picks one
+// of two 8K CHR banks and, on the one game with 40K of PRG (Gumshoe), one
+// of two 8K banks at $8000; the rest of PRG sits at $A000-$FFFF. Smaller
+// PRG is mirrored as on NROM. 2K of RAM fills $6000-$7FFF, and the
+// cabinet has VRAM for all four nametables.
+
+use alloc::vec::Vec;
+
+use serde::{Deserialize, Serialize};
+
+use super::{Address, Chr, Mapper, Mirroring, Value};
+use crate::rom::Rom;
+use crate::state::{self, StateError};
+
+const BANK_SIZE: usize = 0x2000;
+const GUMSHOE_PRG: usize = 5 * BANK_SIZE;
+
+pub struct VsUnisystem {
+    prg_rom: Rom,
+    prg_ram: [Value; 0x800],
+    chr: Chr,
+    // bit 2 of the last $4016 write
+    bank: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+struct VsState {
+    #[serde(with = "crate::state::byte_array")]
+    prg_ram: [Value; 0x800],
+    chr_ram: Option<Vec<Value>>,
+    bank: bool,
+}
+
+impl VsUnisystem {
+    // An empty `chr` gives the board 8K of CHR RAM.
+    pub fn new(prg_rom: impl Into<Rom>, chr: impl Into<Rom>) -> Self {
+        VsUnisystem {
+            prg_rom: prg_rom.into(),
+            prg_ram: [0; 0x800],
+            chr: Chr::new(chr.into(), BANK_SIZE),
+            bank: false,
+        }
+    }
+
+    fn chr_offset(&self, addr: Address) -> usize {
+        (self.bank as usize * BANK_SIZE + addr as usize) % self.chr.len()
+    }
+}
+
+impl Mapper for VsUnisystem {
+    fn read_prg(&mut self, addr: Address) -> Value {
+        match addr {
+            0x6000..=0x7FFF => self.prg_ram[addr as usize & 0x07FF],
+            0x8000..=0xFFFF => match self.prg_offset(addr) {
+                Some(offset) => self.prg_rom[offset],
+                None => 0,
+            },
+            _ => 0,
+        }
+    }
+
+    fn write_prg(&mut self, addr: Address, value: Value) {
+        if let 0x6000..=0x7FFF = addr {
+            self.prg_ram[addr as usize & 0x07FF] = value;
+        }
+    }
+
+    fn write_4016(&mut self, value: Value) {
+        self.bank = value & 0b100 != 0;
+    }
+
+    fn prg_offset(&self, addr: Address) -> Option<usize> {
+        if addr < 0x8000 || self.prg_rom.is_empty() {
+            return None;
+        }
+        let offset = (addr - 0x8000) as usize;
+        Some(match self.prg_rom.len() {
+            GUMSHOE_PRG if offset < BANK_SIZE && self.bank => 4 * BANK_SIZE + offset,
+            GUMSHOE_PRG => offset,
+            len => offset % len,
+        })
+    }
+
+    fn read_chr(&mut self, addr: Address) -> Value {
+        self.chr[self.chr_offset(addr)]
+    }
+
+    fn write_chr(&mut self, addr: Address, value: Value) {
+        let offset = self.chr_offset(addr);
+        self.chr.write(offset, value);
+    }
+
+    fn changes_unwritten(&self) -> bool {
+        false
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        Mirroring::FourScreen
+    }
+
+    fn prg_ram(&self) -> &[Value] {
+        &self.prg_ram
+    }
+
+    fn prg_ram_mut(&mut self) -> &mut [Value] {
+        &mut self.prg_ram
+    }
+
+    fn save_state(&self) -> Vec<u8> {
+        state::encode(&VsState {
+            prg_ram: self.prg_ram,
+            chr_ram: self.chr.ram(),
+            bank: self.bank,
+        })
+    }
+
+    fn load_state(&mut self, data: &[u8]) -> Result<(), StateError> {
+        let saved: VsState = state::decode(data)?;
+        self.chr.load_ram(saved.chr_ram)?;
+        self.prg_ram = saved.prg_ram;
+        self.bank = saved.bank;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn banks(count: usize) -> Vec<Value> {
+        (0..count)
+            .flat_map(|bank| vec![bank as Value; BANK_SIZE])
+            .collect()
+    }
+
+    #[test]
+    fn test_4016_switches_chr() {
+        let mut board = VsUnisystem::new(banks(4), banks(2));
+        assert_eq!(board.read_chr(0x1000), 0);
+        board.write_4016(0b101);
+        assert_eq!(board.read_chr(0x1000), 1);
+        // and 32K of PRG stays put
+        assert_eq!(board.read_prg(0x8000), 0);
+        assert_eq!(board.read_prg(0xE000), 3);
+        assert_eq!(board.mirroring(), Mirroring::FourScreen);
+    }
+
+    #[test]
+    fn test_40k_prg_switches_8000() {
+        let mut board = VsUnisystem::new(banks(5), banks(2));
+        assert_eq!(board.read_prg(0x8000), 0);
+        assert_eq!(board.read_prg(0xA000), 1);
+        assert_eq!(board.read_prg(0xFFFF), 3);
+        board.write_4016(0b100);
+        assert_eq!(board.read_prg(0x9FFF), 4);
+        assert_eq!(board.prg_offset(0x8000), Some(4 * BANK_SIZE));
+        assert_eq!(board.read_prg(0xA000), 1);
+    }
+
+    #[test]
+    fn test_ram_is_mirrored_and_saved() {
+        let mut board = VsUnisystem::new(banks(2), banks(2));
+        board.write_prg(0x6001, 0x42);
+        board.write_4016(0b100);
+        assert_eq!(board.read_prg(0x7801), 0x42);
+
+        let mut fresh = VsUnisystem::new(banks(2), banks(2));
+        fresh.load_state(&board.save_state()).unwrap();
+        assert_eq!(fresh.read_prg(0x6001), 0x42);
+        assert_eq!(fresh.read_chr(0), 1);
+    }
+}
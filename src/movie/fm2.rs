@@ -0,0 +1,335 @@
+// FCEUX's text movie format. A header of "key value" lines, then one line
+// per frame:
+//
+//     |commands|RLDUTSBA|RLDUTSBA|port2|
+//
+// with a column per joypad (four of them when `fourscore 1`), where any
+// character other than '.' or ' ' means the button is held. A movie with a
+// `savestate` key starts from that state instead of power-on.
+//
+// Only gamepads are supported; zapper columns and binary FM2 are rejected.
+
+use std::fmt;
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+
+use super::{FrameInput, Movie, MovieCommand, MovieStart, MOVIE_PLAYERS};
+use crate::input::joypad::JoypadButton;
+
+const BUTTON_CHARS: &[u8; 8] = b"RLDUTSBA";
+
+// FM2 port device ids
+const SI_NONE: u32 = 0;
+const SI_GAMEPAD: u32 = 1;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Fm2Error {
+    MissingVersion,
+    UnsupportedVersion(String),
+    Binary,
+    UnsupportedDevice(u32),
+    BadHeader(usize),
+    BadFrame(usize),
+    BadBlob(usize),
+}
+
+impl fmt::Display for Fm2Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Fm2Error::MissingVersion => write!(f, "missing version line"),
+            Fm2Error::UnsupportedVersion(v) => write!(f, "unsupported FM2 version {}", v),
+            Fm2Error::Binary => write!(f, "binary FM2 movies are not supported"),
+            Fm2Error::UnsupportedDevice(id) => write!(f, "unsupported port device {}", id),
+            Fm2Error::BadHeader(line) => write!(f, "malformed header on line {}", line),
+            Fm2Error::BadFrame(line) => write!(f, "malformed input on line {}", line),
+            Fm2Error::BadBlob(line) => write!(f, "bad base64/hex data on line {}", line),
+        }
+    }
+}
+
+impl std::error::Error for Fm2Error {}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Fm2 {
+    pub movie: Movie,
+    pub rom_filename: String,
+    // MD5 of the ROM as FCEUX computes it; kept as-is
+    pub rom_checksum: Vec<u8>,
+    pub guid: String,
+    pub pal: bool,
+    pub four_score: bool,
+    pub comments: Vec<String>,
+    // keys we don't interpret, written back out on export
+    pub extra: Vec<(String, String)>,
+}
+
+impl Fm2 {
+    pub fn new(movie: Movie) -> Self {
+        Fm2 {
+            four_score: movie
+                .frames
+                .iter()
+                .any(|f| !(f.pads[2] | f.pads[3]).is_empty()),
+            movie,
+            ..Fm2::default()
+        }
+    }
+
+    pub fn parse(text: &str) -> Result<Self, Fm2Error> {
+        let mut fm2 = Fm2::default();
+        let mut version = None;
+        let mut ports = [SI_GAMEPAD, SI_GAMEPAD];
+
+        for (index, raw) in text.lines().enumerate() {
+            let number = index + 1;
+            let line = raw.trim_end_matches('\r');
+            if line.is_empty() {
+                continue;
+            }
+            if line.starts_with('|') {
+                let frame =
+                    parse_frame(line, fm2.four_score, ports).ok_or(Fm2Error::BadFrame(number))?;
+                fm2.movie.frames.push(frame);
+                continue;
+            }
+
+            let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+            let number_value = || {
+                value
+                    .trim()
+                    .parse::<u32>()
+                    .map_err(|_| Fm2Error::BadHeader(number))
+            };
+            match key {
+                "version" => version = Some(value.to_string()),
+                "binary" => {
+                    if number_value()? != 0 {
+                        return Err(Fm2Error::Binary);
+                    }
+                }
+                "rerecordCount" => fm2.movie.rerecords = number_value()?,
+                "palFlag" => fm2.pal = number_value()? != 0,
+                "fourscore" => fm2.four_score = number_value()? != 0,
+                "port0" => ports[0] = number_value()?,
+                "port1" => ports[1] = number_value()?,
+                "romFilename" => fm2.rom_filename = value.to_string(),
+                "romChecksum" => {
+                    fm2.rom_checksum = decode_blob(value).ok_or(Fm2Error::BadBlob(number))?
+                }
+                "guid" => fm2.guid = value.to_string(),
+                "comment" => fm2.comments.push(value.to_string()),
+                "savestate" => {
+                    let state = decode_blob(value).ok_or(Fm2Error::BadBlob(number))?;
+                    fm2.movie.start = MovieStart::Savestate(state);
+                }
+                _ => fm2.extra.push((key.to_string(), value.to_string())),
+            }
+        }
+
+        match version.as_deref() {
+            None => Err(Fm2Error::MissingVersion),
+            Some("3") => {
+                if !fm2.four_score {
+                    if let Some(&id) = ports.iter().find(|&&id| id != SI_NONE && id != SI_GAMEPAD) {
+                        return Err(Fm2Error::UnsupportedDevice(id));
+                    }
+                }
+                Ok(fm2)
+            }
+            Some(other) => Err(Fm2Error::UnsupportedVersion(other.to_string())),
+        }
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut out = String::new();
+        let mut header = |key: &str, value: &str| {
+            out.push_str(key);
+            out.push(' ');
+            out.push_str(value);
+            out.push('\n');
+        };
+
+        header("version", "3");
+        header("emuVersion", "0");
+        header("rerecordCount", &self.movie.rerecords.to_string());
+        header("palFlag", if self.pal { "1" } else { "0" });
+        header("romFilename", &self.rom_filename);
+        header("romChecksum", &encode_blob(&self.rom_checksum));
+        header("guid", &self.guid);
+        header("fourscore", if self.four_score { "1" } else { "0" });
+        header("microphone", "0");
+        let port = if self.four_score { "0" } else { "1" };
+        header("port0", port);
+        header("port1", port);
+        header("port2", "0");
+        for comment in &self.comments {
+            header("comment", comment);
+        }
+        for (key, value) in &self.extra {
+            header(key, value);
+        }
+        if let MovieStart::Savestate(state) = &self.movie.start {
+            header("savestate", &encode_blob(state));
+        }
+
+        let pads = if self.four_score { MOVIE_PLAYERS } else { 2 };
+        for frame in &self.movie.frames {
+            out.push('|');
+            out.push_str(&frame.commands.bits().to_string());
+            for pad in &frame.pads[..pads] {
+                out.push('|');
+                for (i, c) in BUTTON_CHARS.iter().enumerate() {
+                    let held = pad.bits() & (0x80 >> i) != 0;
+                    out.push(if held { *c as char } else { '.' });
+                }
+            }
+            out.push_str("||\n");
+        }
+        out
+    }
+}
+
+fn parse_frame(line: &str, four_score: bool, ports: [u32; 2]) -> Option<FrameInput> {
+    let mut fields = line.strip_prefix('|')?.split('|');
+    let mut frame = FrameInput {
+        commands: MovieCommand::from_bits_retain(fields.next()?.trim().parse().ok()?),
+        ..FrameInput::default()
+    };
+
+    let present: Vec<usize> = if four_score {
+        (0..MOVIE_PLAYERS).collect()
+    } else {
+        (0..2).filter(|&p| ports[p] == SI_GAMEPAD).collect()
+    };
+    for player in present {
+        frame.pads[player] = parse_pad(fields.next()?)?;
+    }
+    Some(frame)
+}
+
+fn parse_pad(field: &str) -> Option<JoypadButton> {
+    if field.len() != BUTTON_CHARS.len() {
+        return None;
+    }
+    let bits = field
+        .bytes()
+        .enumerate()
+        .filter(|&(_, c)| c != b'.' && c != b' ')
+        .fold(0u8, |bits, (i, _)| bits | (0x80 >> i));
+    Some(JoypadButton::from_bits_retain(bits))
+}
+
+// Binary header values are "base64:..." or "0x..." hex.
+fn decode_blob(value: &str) -> Option<Vec<u8>> {
+    let value = value.trim();
+    if let Some(encoded) = value.strip_prefix("base64:") {
+        return STANDARD.decode(encoded).ok();
+    }
+    let hex = value.strip_prefix("0x")?;
+    if hex.len() % 2 != 0 {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+fn encode_blob(bytes: &[u8]) -> String {
+    format!("base64:{}", STANDARD.encode(bytes))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const SAMPLE: &str = "version 3\n\
+        emuVersion 22020\n\
+        rerecordCount 12\n\
+        palFlag 0\n\
+        romFilename Super Mario Bros.\n\
+        romChecksum base64:jjYwGG411HcjG/j9UOVM3Q==\n\
+        guid 1F5C4E5A-0000-0000-0000-000000000000\n\
+        fourscore 0\n\
+        port0 1\n\
+        port1 1\n\
+        port2 0\n\
+        comment author someone\n\
+        |0|........|........||\n\
+        |1|....T...|........||\n\
+        |0|R......A|.L....B.||\n";
+
+    #[test]
+    fn test_parse() {
+        let fm2 = Fm2::parse(SAMPLE).unwrap();
+        assert_eq!(fm2.rom_filename, "Super Mario Bros.");
+        assert_eq!(fm2.rom_checksum.len(), 16);
+        assert_eq!(fm2.comments, vec!["author someone"]);
+        assert_eq!(fm2.movie.rerecords, 12);
+        assert_eq!(fm2.movie.start, MovieStart::PowerOn { seed: 0 });
+
+        let frames = &fm2.movie.frames;
+        assert_eq!(frames.len(), 3);
+        assert_eq!(frames[1].commands, MovieCommand::SOFT_RESET);
+        assert_eq!(frames[1].pads[0], JoypadButton::START);
+        assert_eq!(
+            frames[2].pads[0],
+            JoypadButton::RIGHT | JoypadButton::BUTTON_A
+        );
+        assert_eq!(
+            frames[2].pads[1],
+            JoypadButton::LEFT | JoypadButton::BUTTON_B
+        );
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let fm2 = Fm2::parse(SAMPLE).unwrap();
+        let reparsed = Fm2::parse(&fm2.to_text()).unwrap();
+        assert_eq!(reparsed.movie, fm2.movie);
+        assert_eq!(reparsed.rom_checksum, fm2.rom_checksum);
+        assert_eq!(reparsed.guid, fm2.guid);
+    }
+
+    #[test]
+    fn test_four_score_and_savestate() {
+        let mut movie = Movie::new(MovieStart::Savestate(vec![0xDE, 0xAD]));
+        let mut frame = FrameInput::default();
+        frame.pads[3] = JoypadButton::UP;
+        movie.push_frame(frame);
+
+        let fm2 = Fm2::new(movie);
+        assert!(fm2.four_score);
+        let text = fm2.to_text();
+        assert!(text.contains("|0|........|........|........|...U....||"));
+        assert_eq!(Fm2::parse(&text).unwrap().movie, fm2.movie);
+
+        let hex = Fm2::parse("version 3\nsavestate 0xdead\n").unwrap();
+        assert_eq!(hex.movie.start, MovieStart::Savestate(vec![0xDE, 0xAD]));
+    }
+
+    #[test]
+    fn test_single_port() {
+        let fm2 = Fm2::parse("version 3\nport0 1\nport1 0\n|0|.......A||\n").unwrap();
+        assert_eq!(fm2.movie.frames[0].pads[0], JoypadButton::BUTTON_A);
+        assert_eq!(fm2.movie.frames[0].pads[1], JoypadButton::empty());
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(
+            Fm2::parse("|0|........|........||\n"),
+            Err(Fm2Error::MissingVersion)
+        );
+        assert_eq!(Fm2::parse("version 3\nbinary 1\n"), Err(Fm2Error::Binary));
+        assert_eq!(
+            Fm2::parse("version 3\nport1 2\n"),
+            Err(Fm2Error::UnsupportedDevice(2))
+        );
+        assert_eq!(
+            Fm2::parse("version 3\n|0|...||\n"),
+            Err(Fm2Error::BadFrame(2))
+        );
+    }
+}
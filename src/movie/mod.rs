@@ -0,0 +1,334 @@
+// Input movies: the state the run starts from plus every frame's controller
+// input and console commands. Replaying the same frames from the same start
+// reproduces the run exactly, which is what TAS tools, regression tests and
+// netplay input exchange are built on.
+//
+// Only joypads are recorded; light guns and other port devices depend on
+// host pointer state that has to be captured separately.
+
+pub mod fm2;
+pub mod replay;
+
+use std::fmt;
+
+use bitflags::bitflags;
+
+use crate::input::joypad::JoypadButton;
+use crate::input::Controllers;
+
+const MOVIE_MAGIC: &[u8; 4] = b"NMV\x1a";
+const MOVIE_VERSION: u8 = 1;
+
+pub const MOVIE_PLAYERS: usize = 4;
+
+bitflags! {
+    // Same bit values FM2 uses for its command column.
+    #[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+    pub struct MovieCommand: u8 {
+        const SOFT_RESET = 0b0000_0001;
+        const POWER      = 0b0000_0010;
+        // into a VS. System's first coin slot
+        const VS_COIN    = 0b0001_0000;
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct FrameInput {
+    pub pads: [JoypadButton; MOVIE_PLAYERS],
+    // applied before the frame runs
+    pub commands: MovieCommand,
+}
+
+impl FrameInput {
+    pub fn capture(controllers: &mut Controllers) -> Self {
+        let mut input = FrameInput::default();
+        for (player, pad) in input.pads.iter_mut().enumerate() {
+            if let Some(joypad) = controllers.joypad(player) {
+                *pad = joypad.buttons();
+            }
+        }
+        input
+    }
+
+    pub fn apply(&self, controllers: &mut Controllers) {
+        for (player, pad) in self.pads.iter().enumerate() {
+            controllers.set_buttons(player, *pad);
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MovieStart {
+    // Cold boot; the seed drives anything the power-on state randomizes
+    // (see replay.rs).
+    PowerOn { seed: u64 },
+    // Serialized machine state the movie was recorded from.
+    Savestate(Vec<u8>),
+}
+
+impl Default for MovieStart {
+    fn default() -> Self {
+        MovieStart::PowerOn { seed: 0 }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum MovieError {
+    BadMagic,
+    UnsupportedVersion(u8),
+    Truncated,
+    BadStart(u8),
+}
+
+impl fmt::Display for MovieError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MovieError::BadMagic => write!(f, "not a movie file"),
+            MovieError::UnsupportedVersion(v) => write!(f, "unsupported movie version {}", v),
+            MovieError::Truncated => write!(f, "movie is truncated"),
+            MovieError::BadStart(kind) => write!(f, "unknown movie start type {}", kind),
+        }
+    }
+}
+
+impl std::error::Error for MovieError {}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Movie {
+    pub start: MovieStart,
+    pub frames: Vec<FrameInput>,
+    pub rerecords: u32,
+}
+
+impl Movie {
+    pub fn new(start: MovieStart) -> Self {
+        Movie {
+            start,
+            frames: Vec::new(),
+            rerecords: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    pub fn push_frame(&mut self, input: FrameInput) {
+        self.frames.push(input);
+    }
+
+    // Drops everything from `frame` on so recording can resume there, and
+    // counts it as a rerecord.
+    pub fn truncate(&mut self, frame: usize) {
+        if frame < self.frames.len() {
+            self.frames.truncate(frame);
+            self.rerecords += 1;
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(32 + self.frames.len() * 5);
+        out.extend_from_slice(MOVIE_MAGIC);
+        out.push(MOVIE_VERSION);
+        match &self.start {
+            MovieStart::PowerOn { seed } => {
+                out.push(0);
+                out.extend_from_slice(&seed.to_le_bytes());
+            }
+            MovieStart::Savestate(state) => {
+                out.push(1);
+                out.extend_from_slice(&(state.len() as u32).to_le_bytes());
+                out.extend_from_slice(state);
+            }
+        }
+        out.extend_from_slice(&self.rerecords.to_le_bytes());
+        out.extend_from_slice(&(self.frames.len() as u32).to_le_bytes());
+        for frame in &self.frames {
+            out.extend(frame.pads.iter().map(|pad| pad.bits()));
+            out.push(frame.commands.bits());
+        }
+        out
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, MovieError> {
+        let mut reader = Reader { bytes, pos: 0 };
+        if reader.take(4)? != MOVIE_MAGIC {
+            return Err(MovieError::BadMagic);
+        }
+        let version = reader.u8()?;
+        if version != MOVIE_VERSION {
+            return Err(MovieError::UnsupportedVersion(version));
+        }
+        let start = match reader.u8()? {
+            0 => MovieStart::PowerOn {
+                seed: u64::from_le_bytes(reader.take(8)?.try_into().unwrap()),
+            },
+            1 => {
+                let len = reader.u32()? as usize;
+                MovieStart::Savestate(reader.take(len)?.to_vec())
+            }
+            kind => return Err(MovieError::BadStart(kind)),
+        };
+        let rerecords = reader.u32()?;
+        let count = reader.u32()? as usize;
+
+        let data = reader.take(count.checked_mul(5).ok_or(MovieError::Truncated)?)?;
+        let frames = data
+            .chunks_exact(5)
+            .map(|chunk| FrameInput {
+                pads: [0, 1, 2, 3].map(|p| JoypadButton::from_bits_retain(chunk[p])),
+                commands: MovieCommand::from_bits_retain(chunk[4]),
+            })
+            .collect();
+
+        Ok(Movie {
+            start,
+            frames,
+            rerecords,
+        })
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], MovieError> {
+        let end = self.pos.checked_add(len).ok_or(MovieError::Truncated)?;
+        let slice = self.bytes.get(self.pos..end).ok_or(MovieError::Truncated)?;
+        self.pos = end;
+        Ok(slice)
+    }
+
+    fn u8(&mut self) -> Result<u8, MovieError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32, MovieError> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+}
+
+// Feeds a movie back one frame at a time.
+#[derive(Debug)]
+pub struct MoviePlayback {
+    movie: Movie,
+    frame: usize,
+}
+
+impl MoviePlayback {
+    pub fn new(movie: Movie) -> Self {
+        MoviePlayback { movie, frame: 0 }
+    }
+
+    pub fn movie(&self) -> &Movie {
+        &self.movie
+    }
+
+    pub fn frame(&self) -> usize {
+        self.frame
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.frame >= self.movie.frames.len()
+    }
+
+    // Input for the next frame, already applied to the controllers. The
+    // caller handles the returned commands (reset, power cycle) before
+    // running the frame.
+    pub fn next_frame(&mut self, controllers: &mut Controllers) -> Option<FrameInput> {
+        let input = *self.movie.frames.get(self.frame)?;
+        input.apply(controllers);
+        self.frame += 1;
+        Some(input)
+    }
+
+    // Stop playing and keep recording from the current frame.
+    pub fn into_recording(mut self) -> Movie {
+        self.movie.truncate(self.frame);
+        self.movie
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn sample_movie() -> Movie {
+        let mut movie = Movie::new(MovieStart::PowerOn { seed: 0xDEAD_BEEF });
+        movie.push_frame(FrameInput::default());
+        movie.push_frame(FrameInput {
+            pads: [
+                JoypadButton::START,
+                JoypadButton::empty(),
+                JoypadButton::BUTTON_A | JoypadButton::LEFT,
+                JoypadButton::empty(),
+            ],
+            commands: MovieCommand::empty(),
+        });
+        movie.push_frame(FrameInput {
+            commands: MovieCommand::SOFT_RESET,
+            ..FrameInput::default()
+        });
+        movie
+    }
+
+    #[test]
+    fn test_bytes_round_trip() {
+        let movie = sample_movie();
+        assert_eq!(Movie::from_bytes(&movie.to_bytes()).unwrap(), movie);
+
+        let mut from_state = Movie::new(MovieStart::Savestate(vec![1, 2, 3]));
+        from_state.rerecords = 42;
+        assert_eq!(
+            Movie::from_bytes(&from_state.to_bytes()).unwrap(),
+            from_state
+        );
+    }
+
+    #[test]
+    fn test_bad_input() {
+        let bytes = sample_movie().to_bytes();
+        assert_eq!(
+            Movie::from_bytes(&bytes[..bytes.len() - 1]),
+            Err(MovieError::Truncated)
+        );
+        assert_eq!(Movie::from_bytes(b"RIFF...."), Err(MovieError::BadMagic));
+    }
+
+    #[test]
+    fn test_record_and_play_back() {
+        let mut controllers = Controllers::new();
+        let mut movie = Movie::default();
+        for buttons in [JoypadButton::UP, JoypadButton::BUTTON_B] {
+            controllers.set_buttons(0, buttons);
+            movie.push_frame(FrameInput::capture(&mut controllers));
+        }
+
+        let mut replay = Controllers::new();
+        let mut playback = MoviePlayback::new(movie);
+        playback.next_frame(&mut replay).unwrap();
+        assert_eq!(replay.joypad(0).unwrap().buttons(), JoypadButton::UP);
+        playback.next_frame(&mut replay).unwrap();
+        assert_eq!(replay.joypad(0).unwrap().buttons(), JoypadButton::BUTTON_B);
+        assert!(playback.is_finished());
+        assert!(playback.next_frame(&mut replay).is_none());
+    }
+
+    #[test]
+    fn test_rerecord_from_playback() {
+        let mut controllers = Controllers::new();
+        let mut playback = MoviePlayback::new(sample_movie());
+        playback.next_frame(&mut controllers);
+
+        let movie = playback.into_recording();
+        assert_eq!(movie.len(), 1);
+        assert_eq!(movie.rerecords, 1);
+    }
+}
@@ -0,0 +1,291 @@
+// Plays a movie back from its start, and the check that everything built
+// on replaying (rewind, run-ahead, TAS tools, netplay) relies on: the same
+// ROM, start and input always give the same machine, frame after frame.
+// check_determinism plays a movie twice and compares the frame hashes
+// of every frame (see Emulator::frame_hash), which replays turn on.
+//
+// What keeps that true is that the core never asks the host anything:
+// no clocks, no randomness, no HashMap order in what's emulated. The
+// frame timing, audio rate control and everything else that reads a
+// clock lives in the front ends, and a test below keeps the rest of the
+// source free of them. What the core does model as random comes from one
+// seeded generator (rng.rs): a power-on start's seed seeds it, and every
+// power cycle after; the generator's state is in the state hash. RAM
+// still powers on to zeros here, as the movie doesn't say otherwise.
+
+use std::fmt;
+
+use super::{Movie, MovieCommand, MovieStart};
+use crate::cartridge::CartridgeError;
+use crate::emulator::Emulator;
+use crate::rng::RamInit;
+use crate::state::StateError;
+
+#[derive(Debug)]
+pub enum ReplayError {
+    Rom(CartridgeError),
+    State(StateError),
+    // 0-based; the hashes of the two runs after it
+    Desync {
+        frame: usize,
+        first: u64,
+        second: u64,
+    },
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ReplayError::Rom(err) => write!(f, "{}", err),
+            ReplayError::State(err) => write!(f, "{}", err),
+            ReplayError::Desync {
+                frame,
+                first,
+                second,
+            } => write!(
+                f,
+                "runs differ after frame {}: {:016x} and {:016x}",
+                frame, first, second
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ReplayError {}
+
+impl From<CartridgeError> for ReplayError {
+    fn from(err: CartridgeError) -> Self {
+        ReplayError::Rom(err)
+    }
+}
+
+impl From<StateError> for ReplayError {
+    fn from(err: StateError) -> Self {
+        ReplayError::State(err)
+    }
+}
+
+// The machine as the movie starts.
+pub fn start(rom: &[u8], start: &MovieStart) -> Result<Emulator, ReplayError> {
+    let mut emulator = Emulator::from_rom(rom)?;
+    match start {
+        MovieStart::PowerOn { seed } => emulator.power_on(*seed, RamInit::Zeros),
+        MovieStart::Savestate(state) => emulator.load_state(state)?,
+    }
+    Ok(emulator)
+}
+
+// Plays every frame, calling `each` after it with its index, with frame
+// hashing on. A power cycle starts a new machine, keeping the battery
+// RAM.
+pub fn replay(
+    rom: &[u8],
+    movie: &Movie,
+    mut each: impl FnMut(usize, &Emulator),
+) -> Result<Emulator, ReplayError> {
+    let mut emulator = start(rom, &movie.start)?;
+    emulator.set_state_hashing(true);
+    for (frame, input) in movie.frames.iter().enumerate() {
+        if input.commands.contains(MovieCommand::POWER) {
+            let battery = emulator.battery_ram().map(<[u8]>::to_vec);
+            let seed = emulator.seed();
+            emulator = Emulator::from_rom(rom)?;
+            emulator.power_on(seed, RamInit::Zeros);
+            if let Some(battery) = battery {
+                emulator.load_battery_ram(&battery);
+            }
+            emulator.set_state_hashing(true);
+        }
+        emulator.play_frame(input);
+        each(frame, &emulator);
+    }
+    Ok(emulator)
+}
+
+// The state hash after every frame.
+pub fn state_hashes(rom: &[u8], movie: &Movie) -> Result<Vec<u64>, ReplayError> {
+    let mut hashes = Vec::with_capacity(movie.len());
+    replay(rom, movie, |_, emulator| {
+        hashes.push(
+            emulator
+                .frame_hash()
+                .expect("replays hash every frame")
+                .state,
+        );
+    })?;
+    Ok(hashes)
+}
+
+pub fn first_divergence(first: &[u64], second: &[u64]) -> Option<usize> {
+    let differ = first.iter().zip(second).position(|(a, b)| a != b);
+    differ.or((first.len() != second.len()).then(|| first.len().min(second.len())))
+}
+
+// Plays the movie twice, returning the hashes if both runs agree on every
+// frame.
+pub fn check_determinism(rom: &[u8], movie: &Movie) -> Result<Vec<u64>, ReplayError> {
+    let first = state_hashes(rom, movie)?;
+    let second = state_hashes(rom, movie)?;
+    match first_divergence(&first, &second) {
+        None => Ok(first),
+        Some(frame) => Err(ReplayError::Desync {
+            frame,
+            first: first.get(frame).copied().unwrap_or_default(),
+            second: second.get(frame).copied().unwrap_or_default(),
+        }),
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use std::fs;
+    use std::path::Path;
+
+    use super::*;
+    use crate::bus::Mem;
+    use crate::cartridge::test::test_rom;
+    use crate::input::joypad::JoypadButton;
+    use crate::movie::FrameInput;
+    use crate::testing::asm::assemble;
+
+    // Reads pad 1 every NMI and keeps a running sum of what it read.
+    pub(crate) fn input_rom() -> Vec<u8> {
+        let program = assemble(
+            0x8000,
+            "
+                lda #$80; sta $2000
+            loop: jmp loop
+            nmi:
+                lda #1; sta $4016; lda #0; sta $4016
+                ldx #8
+            read:
+                lda $4016; lsr a; rol $01
+                dex; bne read
+                lda $01; clc; adc $02; sta $02
+                inc $00
+                rti
+            ",
+        )
+        .unwrap();
+        let nmi = program.label("nmi").unwrap();
+        let mut rom = test_rom(2, 1, 0, 0);
+        let prg = &mut rom[16..16 + 0x8000];
+        prg.fill(0);
+        prg[..program.bytes.len()].copy_from_slice(&program.bytes);
+        prg[0x7FFA..0x7FFC].copy_from_slice(&nmi.to_le_bytes());
+        prg[0x7FFC..0x7FFE].copy_from_slice(&[0x00, 0x80]);
+        rom
+    }
+
+    fn input_movie(start: MovieStart, frames: usize) -> Movie {
+        let mut movie = Movie::new(start);
+        for frame in 0..frames {
+            let mut input = FrameInput::default();
+            input.pads[0] = JoypadButton::from_bits_retain((frame * 37) as u8);
+            if frame == 8 {
+                input.commands = MovieCommand::SOFT_RESET;
+            }
+            if frame == 16 {
+                input.commands = MovieCommand::POWER;
+            }
+            movie.push_frame(input);
+        }
+        movie
+    }
+
+    #[test]
+    fn test_same_movie_same_states() {
+        let rom = input_rom();
+        let movie = input_movie(MovieStart::PowerOn { seed: 1 }, 24);
+        let hashes = check_determinism(&rom, &movie).unwrap();
+        assert_eq!(hashes.len(), 24);
+        assert!(hashes.windows(2).all(|pair| pair[0] != pair[1]));
+
+        // the seed is part of the machine from the start, input from its
+        // frame
+        let seeded = input_movie(MovieStart::PowerOn { seed: 2 }, 24);
+        let reseeded = state_hashes(&rom, &seeded).unwrap();
+        assert_eq!(first_divergence(&hashes, &reseeded), Some(0));
+        let mut other = movie.clone();
+        other.frames[12].pads[0] = JoypadButton::empty();
+        let changed = state_hashes(&rom, &other).unwrap();
+        assert_eq!(first_divergence(&hashes, &changed), Some(12));
+
+        let mut emulator = replay(&rom, &movie, |_, _| {}).unwrap();
+        // counted from the power cycle at frame 16
+        assert!((6..=8).contains(&emulator.cpu_mut().mem_read(0x00)));
+        assert_ne!(emulator.cpu_mut().mem_read(0x02), 0);
+    }
+
+    #[test]
+    fn test_replays_from_a_savestate() {
+        let rom = input_rom();
+        let movie = input_movie(MovieStart::PowerOn { seed: 0 }, 20);
+        let mut state = Vec::new();
+        let hashes = {
+            let mut hashes = Vec::new();
+            replay(&rom, &movie, |frame, emulator| {
+                hashes.push(emulator.state_hash());
+                if frame == 9 {
+                    state = emulator.save_state();
+                }
+            })
+            .unwrap();
+            hashes
+        };
+        let mut rest = Movie::new(MovieStart::Savestate(state));
+        rest.frames = movie.frames[10..].to_vec();
+        assert_eq!(check_determinism(&rom, &rest).unwrap(), hashes[10..]);
+
+        let junk = Movie::new(MovieStart::Savestate(b"junk".to_vec()));
+        assert!(matches!(
+            check_determinism(&rom, &junk),
+            Err(ReplayError::State(_))
+        ));
+        assert_eq!(first_divergence(&[1, 2], &[1, 2, 3]), Some(2));
+        assert_eq!(first_divergence(&[1, 2], &[1, 2]), None);
+    }
+
+    // Everything outside the front ends, the remote server, the logger
+    // (which stamps lines with the time) and the test helpers is the core.
+    fn core_files(dir: &Path, files: &mut Vec<std::path::PathBuf>) {
+        for entry in fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            let name = path.file_name().unwrap().to_string_lossy().into_owned();
+            if path.is_dir() {
+                if !["frontend", "remote", "testing"].contains(&name.as_str()) {
+                    core_files(&path, files);
+                }
+            } else if name.ends_with(".rs") && !["main.rs", "logging.rs"].contains(&name.as_str()) {
+                files.push(path);
+            }
+        }
+    }
+
+    #[test]
+    fn test_core_reads_no_clock_or_entropy() {
+        let src = Path::new(env!("CARGO_MANIFEST_DIR")).join("src");
+        let mut files = Vec::new();
+        core_files(&src, &mut files);
+        assert!(files.len() > 20);
+        // split up so this file doesn't match itself
+        let banned = [
+            ["Instant", "::"].concat(),
+            ["System", "Time"].concat(),
+            ["thread", "_rng"].concat(),
+            ["Random", "State"].concat(),
+            ["rand", "::"].concat(),
+        ];
+        for file in files {
+            let source = fs::read_to_string(&file).unwrap();
+            for word in &banned {
+                assert!(
+                    !source.contains(word.as_str()),
+                    "{} uses {}",
+                    file.display(),
+                    word
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,520 @@
+// Two-player netplay. One instance hosts and the other joins it over
+// TCP; the guest says which ROM it has, and if it's the same one the host
+// sends its input delay, rollback window and a savestate, so both
+// machines start the same. From there each side sends its pad for every
+// frame, `delay` frames ahead of running it. With a window of 0 that's
+// lockstep: a frame only runs once both pads for it are in. Otherwise up
+// to `window` frames run on a guess of the other pad and are redone when
+// it turns out wrong; see rollback.rs. Both machines end up running the
+// same input from the same state, so they stay the same (see
+// movie/replay.rs); after every confirmed frame they swap frame hashes
+// (see Emulator::frame_hash, which netplay turns on) to catch it if they
+// don't, say because one has a cheat on, and say on which frame.
+//
+// The host is player 1 and the guest player 2. A reset goes out with the
+// pad as a movie command and happens on both machines on the same frame.
+// TCP keeps the messages in order, which both modes lean on, so there's
+// no UDP. A thread reads the socket so that waiting never blocks the
+// caller.
+
+pub mod protocol;
+pub mod rollback;
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::{self, BufReader};
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+use std::time::Duration;
+
+use crate::emulator::Emulator;
+use crate::input::joypad::JoypadButton;
+use crate::movie::{FrameInput, MovieCommand};
+use crate::run_ahead;
+use crate::state::{Snapshot, StateError};
+
+use self::protocol::{Message, ProtocolError, VERSION};
+use self::rollback::RollbackSync;
+
+// hosting is for another machine, so on every interface
+pub const DEFAULT_ADDRESS: &str = "0.0.0.0:6503";
+pub const DEFAULT_DELAY: u8 = 1;
+pub const DEFAULT_WINDOW: u8 = 8;
+// for the other end to say hello or welcome
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug)]
+pub enum NetplayError {
+    Io(io::Error),
+    Protocol(ProtocolError),
+    // the guest has a different ROM
+    WrongGame,
+    Version(u16),
+    // the host turned us away, saying why
+    Rejected(String),
+    // the machines differ after this frame
+    Desync { frame: u32 },
+    Disconnected,
+    State(StateError),
+}
+
+impl fmt::Display for NetplayError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            NetplayError::Io(err) => write!(f, "{}", err),
+            NetplayError::Protocol(err) => write!(f, "{}", err),
+            NetplayError::WrongGame => write!(f, "the other player has a different ROM"),
+            NetplayError::Version(version) => {
+                write!(f, "netplay version {} (this is {})", version, VERSION)
+            }
+            NetplayError::Rejected(reason) => write!(f, "host refused: {}", reason),
+            NetplayError::Desync { frame } => write!(f, "out of sync at frame {}", frame),
+            NetplayError::Disconnected => write!(f, "the other player left"),
+            NetplayError::State(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for NetplayError {}
+
+impl From<io::Error> for NetplayError {
+    fn from(err: io::Error) -> Self {
+        NetplayError::Io(err)
+    }
+}
+
+impl From<ProtocolError> for NetplayError {
+    fn from(err: ProtocolError) -> Self {
+        match err {
+            ProtocolError::Closed => NetplayError::Disconnected,
+            err => NetplayError::Protocol(err),
+        }
+    }
+}
+
+impl From<StateError> for NetplayError {
+    fn from(err: StateError) -> Self {
+        NetplayError::State(err)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Advance {
+    // a frame ran, with this input
+    Ran(FrameInput),
+    // the other player's input is too far behind, or we're holding a
+    // frame for them to catch up
+    Waiting,
+}
+
+pub struct Netplay {
+    stream: TcpStream,
+    incoming: Receiver<Result<Message, ProtocolError>>,
+    player: usize,
+    delay: u32,
+    // each frame's snapshot with its frame hash, sent once it's confirmed
+    sync: RollbackSync<(Snapshot, u64)>,
+    // commands held until the next pad goes out
+    pending: MovieCommand,
+    // by frame, until the other side's comes in
+    our_hashes: BTreeMap<u32, u64>,
+    their_hashes: BTreeMap<u32, u64>,
+    // the other end left; what it sent before that still counts
+    gone: bool,
+}
+
+impl Netplay {
+    // Waits for a guest on `listener` and starts it from the state
+    // `emulator` is in.
+    pub fn host(
+        listener: &TcpListener,
+        emulator: &mut Emulator,
+        delay: u8,
+        window: u8,
+    ) -> Result<Self, NetplayError> {
+        let (mut stream, _) = listener.accept()?;
+        stream.set_read_timeout(Some(HANDSHAKE_TIMEOUT))?;
+        let (version, crc32) = match Message::read_from(&mut stream)? {
+            Message::Hello { version, crc32 } => (version, crc32),
+            _ => return Err(ProtocolError::Malformed("expected hello").into()),
+        };
+        let refusal = if version != VERSION {
+            Some(("different netplay version", NetplayError::Version(version)))
+        } else if crc32 != emulator.crc32() {
+            Some(("different ROM", NetplayError::WrongGame))
+        } else {
+            None
+        };
+        if let Some((reason, err)) = refusal {
+            let _ = Message::Reject(reason.to_string()).write_to(&mut stream);
+            return Err(err);
+        }
+        let state = emulator.save_state();
+        let welcome = Message::Welcome {
+            delay,
+            window,
+            state,
+        };
+        welcome.write_to(&mut stream)?;
+        Self::start(stream, emulator, 0, delay, window)
+    }
+
+    // Joins the host at `addr`, loading its state into `emulator`.
+    pub fn join(addr: SocketAddr, emulator: &mut Emulator) -> Result<Self, NetplayError> {
+        let mut stream = TcpStream::connect_timeout(&addr, HANDSHAKE_TIMEOUT)?;
+        stream.set_read_timeout(Some(HANDSHAKE_TIMEOUT))?;
+        let hello = Message::Hello {
+            version: VERSION,
+            crc32: emulator.crc32(),
+        };
+        hello.write_to(&mut stream)?;
+        match Message::read_from(&mut stream)? {
+            Message::Welcome {
+                delay,
+                window,
+                state,
+            } => {
+                emulator.load_state(&state)?;
+                Self::start(stream, emulator, 1, delay, window)
+            }
+            Message::Reject(reason) => Err(NetplayError::Rejected(reason)),
+            _ => Err(ProtocolError::Malformed("expected welcome").into()),
+        }
+    }
+
+    fn start(
+        stream: TcpStream,
+        emulator: &mut Emulator,
+        player: usize,
+        delay: u8,
+        window: u8,
+    ) -> Result<Self, NetplayError> {
+        stream.set_read_timeout(None)?;
+        stream.set_nodelay(true)?;
+        let (sender, incoming) = mpsc::channel();
+        let mut reader = BufReader::new(stream.try_clone()?);
+        thread::spawn(move || loop {
+            let message = Message::read_from(&mut reader);
+            let last = message.is_err();
+            if sender.send(message).is_err() || last {
+                break;
+            }
+        });
+        emulator.set_state_hashing(true);
+        let start = run_ahead::Rollback::save_state(&mut Hashed(emulator));
+        let sync = RollbackSync::new(player, delay as u32, window as u32, start);
+        Ok(Netplay {
+            stream,
+            incoming,
+            player,
+            delay: delay as u32,
+            sync,
+            pending: MovieCommand::empty(),
+            our_hashes: BTreeMap::new(),
+            their_hashes: BTreeMap::new(),
+            gone: false,
+        })
+    }
+
+    // 0 hosting, 1 joined.
+    pub fn player(&self) -> usize {
+        self.player
+    }
+
+    pub fn delay(&self) -> u32 {
+        self.delay
+    }
+
+    // 0 is lockstep.
+    pub fn window(&self) -> u32 {
+        self.sync.window()
+    }
+
+    // Frames run since the start, some maybe on guesses.
+    pub fn frame(&self) -> u32 {
+        self.sync.frame()
+    }
+
+    // Frames run with both players' real input.
+    pub fn confirmed(&self) -> u32 {
+        self.sync.confirmed()
+    }
+
+    pub fn rollbacks(&self) -> u64 {
+        self.sync.rollbacks()
+    }
+
+    pub fn peer(&self) -> Option<SocketAddr> {
+        self.stream.peer_addr().ok()
+    }
+
+    // Sends this frame's pad and runs the next frame if the other
+    // player's is in or may be guessed, first redoing any that ran on a
+    // wrong guess. Commands given while waiting go out with the next pad
+    // sent.
+    pub fn advance(
+        &mut self,
+        emulator: &mut Emulator,
+        buttons: JoypadButton,
+        commands: MovieCommand,
+    ) -> Result<Advance, NetplayError> {
+        self.pending |= commands;
+        if self.sync.next_local() <= self.sync.frame() + self.delay {
+            let pad = (buttons, std::mem::take(&mut self.pending));
+            let input = Message::Input {
+                frame: self.sync.next_local(),
+                buttons: pad.0,
+                commands: pad.1,
+                ahead: self.sync.ahead().clamp(-128, 127) as i8,
+            };
+            self.send(&input)?;
+            self.sync.add_local(pad);
+        }
+        self.receive()?;
+        if self.sync.should_wait() && !self.gone {
+            return Ok(Advance::Waiting);
+        }
+
+        let mut hashes = Vec::new();
+        let ran = self.sync.run(&mut Hashed(emulator), |frame, &(_, hash)| {
+            hashes.push((frame, hash))
+        });
+        for (frame, hash) in hashes {
+            self.send(&Message::Hash { frame, hash })?;
+            self.our_hashes.insert(frame, hash);
+        }
+        self.compare_hashes()?;
+        match ran {
+            Some(input) => Ok(Advance::Ran(input)),
+            // nothing more is coming to run on
+            None if self.gone => Err(NetplayError::Disconnected),
+            None => Ok(Advance::Waiting),
+        }
+    }
+
+    // Once the other end is gone only what it sent before matters.
+    fn send(&mut self, message: &Message) -> Result<(), NetplayError> {
+        match message.write_to(&mut self.stream) {
+            Err(err) if hung_up(&err) => self.gone = true,
+            result => result?,
+        }
+        Ok(())
+    }
+
+    fn receive(&mut self) -> Result<(), NetplayError> {
+        while !self.gone {
+            let message = match self.incoming.try_recv() {
+                Ok(Err(ProtocolError::Closed)) | Err(TryRecvError::Disconnected) => {
+                    self.gone = true;
+                    break;
+                }
+                Ok(Err(ProtocolError::Io(err))) if hung_up(&err) => {
+                    self.gone = true;
+                    break;
+                }
+                Ok(message) => message?,
+                Err(TryRecvError::Empty) => break,
+            };
+            match message {
+                Message::Input {
+                    frame,
+                    buttons,
+                    commands,
+                    ahead,
+                } => {
+                    if !self.sync.add_remote(frame, (buttons, commands)) {
+                        return Err(ProtocolError::Malformed("input out of order").into());
+                    }
+                    self.sync.set_remote_ahead(ahead as i32);
+                }
+                Message::Hash { frame, hash } => {
+                    self.their_hashes.insert(frame, hash);
+                }
+                Message::Bye => self.gone = true,
+                _ => return Err(ProtocolError::Malformed("unexpected message").into()),
+            }
+        }
+        Ok(())
+    }
+
+    fn compare_hashes(&mut self) -> Result<(), NetplayError> {
+        while let Some((&frame, &theirs)) = self.their_hashes.first_key_value() {
+            let Some(ours) = self.our_hashes.remove(&frame) else {
+                break;
+            };
+            self.their_hashes.remove(&frame);
+            if ours != theirs {
+                return Err(NetplayError::Desync { frame });
+            }
+        }
+        Ok(())
+    }
+}
+
+// The emulator as netplay rolls it back, each snapshot taken with the
+// hash of the frame that led to it.
+struct Hashed<'a>(&'a mut Emulator);
+
+impl run_ahead::Rollback for Hashed<'_> {
+    type State = (Snapshot, u64);
+
+    fn save_state(&mut self) -> (Snapshot, u64) {
+        let hash = match self.0.frame_hash() {
+            Some(hash) => hash.state,
+            None => self.0.state_hash(),
+        };
+        (self.0.snapshot(), hash)
+    }
+
+    fn load_state(&mut self, state: &(Snapshot, u64)) {
+        run_ahead::Rollback::load_state(self.0, &state.0);
+    }
+
+    fn run_frame(&mut self, input: &FrameInput, present: bool) {
+        run_ahead::Rollback::run_frame(self.0, input, present);
+    }
+}
+
+fn hung_up(err: &io::Error) -> bool {
+    matches!(
+        err.kind(),
+        io::ErrorKind::BrokenPipe
+            | io::ErrorKind::ConnectionReset
+            | io::ErrorKind::ConnectionAborted
+    )
+}
+
+impl Drop for Netplay {
+    fn drop(&mut self) {
+        let _ = Message::Bye.write_to(&mut self.stream);
+        let _ = self.stream.shutdown(Shutdown::Both);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::bus::Mem;
+    use crate::emulator::test::counting_rom;
+    use crate::movie::replay::test::input_rom;
+
+    type Played = (Vec<FrameInput>, u64, u64);
+
+    // Until `frames` are confirmed, pressing what `buttons` says for the
+    // frame running.
+    fn run(
+        netplay: &mut Netplay,
+        emulator: &mut Emulator,
+        buttons: fn(u32) -> JoypadButton,
+        frames: u32,
+    ) -> Result<Played, NetplayError> {
+        let mut inputs = Vec::new();
+        while netplay.confirmed() < frames {
+            let pressed = buttons(netplay.frame());
+            match netplay.advance(emulator, pressed, MovieCommand::empty())? {
+                Advance::Ran(input) => inputs.push(input),
+                Advance::Waiting => thread::sleep(Duration::from_millis(1)),
+            }
+        }
+        Ok((inputs, emulator.state_hash(), netplay.rollbacks()))
+    }
+
+    fn guest(
+        addr: SocketAddr,
+        rom: Vec<u8>,
+        buttons: fn(u32) -> JoypadButton,
+        frames: u32,
+        poke: bool,
+    ) -> thread::JoinHandle<Result<Played, NetplayError>> {
+        thread::spawn(move || {
+            let mut emulator = Emulator::from_rom(&rom).unwrap();
+            let mut netplay = Netplay::join(addr, &mut emulator)?;
+            if poke {
+                emulator.cpu_mut().mem_write(0x10, 1);
+            }
+            run(&mut netplay, &mut emulator, buttons, frames)
+        })
+    }
+
+    fn holds_b(_: u32) -> JoypadButton {
+        JoypadButton::BUTTON_B
+    }
+
+    #[test]
+    fn test_lockstep_over_loopback() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let rom = counting_rom();
+        let mut emulator = Emulator::from_rom(&rom).unwrap();
+        emulator.run_frame();
+        let joined = guest(addr, rom, holds_b, 20, false);
+
+        let mut netplay = Netplay::host(&listener, &mut emulator, 3, 0).unwrap();
+        assert_eq!((netplay.player(), netplay.delay()), (0, 3));
+        let holds_a = |_| JoypadButton::BUTTON_A;
+        let (ours, _, rollbacks) = run(&mut netplay, &mut emulator, holds_a, 20).unwrap();
+        let (theirs, hash, _) = joined.join().unwrap().unwrap();
+
+        assert_eq!(ours, theirs);
+        assert_eq!(rollbacks, 0);
+        assert_eq!(ours[2].pads[0], JoypadButton::empty());
+        assert_eq!(ours[3].pads[0], JoypadButton::BUTTON_A);
+        assert_eq!(ours[3].pads[1], JoypadButton::BUTTON_B);
+        // the guest started from the host's state, a frame in
+        assert_eq!(emulator.state_hash(), hash);
+        assert_eq!(emulator.frame_count(), 21);
+    }
+
+    #[test]
+    fn test_rollback_over_loopback() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        // the game reads the host's pad, which the guest has to guess
+        let rom = input_rom();
+        let mut emulator = Emulator::from_rom(&rom).unwrap();
+        let joined = guest(addr, rom, holds_b, 70, false);
+
+        let mut netplay = Netplay::host(&listener, &mut emulator, 0, 8).unwrap();
+        let changing = |frame: u32| JoypadButton::from_bits_retain((frame / 5 * 37) as u8);
+        // a frame the guest got wrong would fail that frame's hash check
+        let (_, _, ours) = run(&mut netplay, &mut emulator, changing, 70).unwrap();
+        let (_, _, theirs) = joined.join().unwrap().unwrap();
+        assert!(ours + theirs > 0);
+        assert!(netplay.confirmed() >= 70);
+    }
+
+    #[test]
+    fn test_turns_away_and_catches_desyncs() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let rom = counting_rom();
+        let mut emulator = Emulator::from_rom(&rom).unwrap();
+
+        let mut other = rom.clone();
+        other[16 + 0x100] ^= 0xFF;
+        let joined = guest(addr, other, holds_b, 0, false);
+        assert!(matches!(
+            Netplay::host(&listener, &mut emulator, 2, 0),
+            Err(NetplayError::WrongGame)
+        ));
+        assert!(matches!(
+            joined.join().unwrap(),
+            Err(NetplayError::Rejected(_))
+        ));
+
+        let mut emulator = Emulator::from_rom(&rom).unwrap();
+        // whichever side sees the other's hash first stops; the other still
+        // gets it before the goodbye. The poke is there from the first
+        // frame on, so that's the one both name.
+        let joined = guest(addr, rom, holds_b, 120, true);
+        let mut netplay = Netplay::host(&listener, &mut emulator, 2, 4).unwrap();
+        let result = run(&mut netplay, &mut emulator, holds_b, 120);
+        assert!(matches!(result, Err(NetplayError::Desync { frame: 1 })));
+        drop(netplay);
+        assert!(matches!(
+            joined.join().unwrap(),
+            Err(NetplayError::Desync { frame: 1 })
+        ));
+    }
+}
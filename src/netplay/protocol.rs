@@ -0,0 +1,221 @@
+// What the two ends of netplay send each other over TCP. Every message
+// is a tag byte, a little-endian u32 length and that many bytes:
+//
+//     1 Hello     u16 protocol version, u32 ROM crc32     guest -> host
+//     2 Welcome   u8 input delay, u8 rollback window,     host -> guest
+//                 savestate
+//     3 Reject    UTF-8 reason                            host -> guest
+//     4 Input     u32 frame, u8 buttons, u8 commands,     both
+//                 i8 frames ahead
+//     5 Hash      u32 frame, u64 state hash (see          both
+//                 Emulator::frame_hash)
+//     6 Bye                                               both
+//
+// Frames count from the Welcome's state, 0 being the first run after it.
+
+use std::fmt;
+use std::io::{self, Read, Write};
+
+use crate::input::joypad::JoypadButton;
+use crate::movie::MovieCommand;
+
+pub const VERSION: u16 = 3;
+// a savestate is well under this
+const MAX_MESSAGE: usize = 16 << 20;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Message {
+    Hello {
+        version: u16,
+        crc32: u32,
+    },
+    Welcome {
+        delay: u8,
+        window: u8,
+        state: Vec<u8>,
+    },
+    Reject(String),
+    Input {
+        frame: u32,
+        buttons: JoypadButton,
+        commands: MovieCommand,
+        // see RollbackSync::ahead
+        ahead: i8,
+    },
+    Hash {
+        frame: u32,
+        hash: u64,
+    },
+    Bye,
+}
+
+#[derive(Debug)]
+pub enum ProtocolError {
+    Io(io::Error),
+    // the other end closed the connection
+    Closed,
+    Malformed(&'static str),
+}
+
+impl fmt::Display for ProtocolError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ProtocolError::Io(err) => write!(f, "{}", err),
+            ProtocolError::Closed => write!(f, "connection closed"),
+            ProtocolError::Malformed(what) => write!(f, "bad message: {}", what),
+        }
+    }
+}
+
+impl std::error::Error for ProtocolError {}
+
+impl From<io::Error> for ProtocolError {
+    fn from(err: io::Error) -> Self {
+        match err.kind() {
+            io::ErrorKind::UnexpectedEof => ProtocolError::Closed,
+            _ => ProtocolError::Io(err),
+        }
+    }
+}
+
+impl Message {
+    fn encode(&self) -> (u8, Vec<u8>) {
+        match self {
+            Message::Hello { version, crc32 } => {
+                let mut body = version.to_le_bytes().to_vec();
+                body.extend_from_slice(&crc32.to_le_bytes());
+                (1, body)
+            }
+            Message::Welcome {
+                delay,
+                window,
+                state,
+            } => {
+                let mut body = vec![*delay, *window];
+                body.extend_from_slice(state);
+                (2, body)
+            }
+            Message::Reject(reason) => (3, reason.as_bytes().to_vec()),
+            Message::Input {
+                frame,
+                buttons,
+                commands,
+                ahead,
+            } => {
+                let mut body = frame.to_le_bytes().to_vec();
+                body.extend_from_slice(&[buttons.bits(), commands.bits(), *ahead as u8]);
+                (4, body)
+            }
+            Message::Hash { frame, hash } => {
+                let mut body = frame.to_le_bytes().to_vec();
+                body.extend_from_slice(&hash.to_le_bytes());
+                (5, body)
+            }
+            Message::Bye => (6, Vec::new()),
+        }
+    }
+
+    fn decode(tag: u8, body: &[u8]) -> Result<Message, ProtocolError> {
+        let short = ProtocolError::Malformed("too short");
+        let u32_at = |at: usize| {
+            body.get(at..at + 4)
+                .map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()))
+        };
+        Ok(match tag {
+            1 if body.len() == 6 => Message::Hello {
+                version: u16::from_le_bytes([body[0], body[1]]),
+                crc32: u32_at(2).ok_or(short)?,
+            },
+            2 if body.len() >= 2 => Message::Welcome {
+                delay: body[0],
+                window: body[1],
+                state: body[2..].to_vec(),
+            },
+            3 => Message::Reject(String::from_utf8_lossy(body).into_owned()),
+            4 if body.len() == 7 => Message::Input {
+                frame: u32_at(0).ok_or(short)?,
+                buttons: JoypadButton::from_bits_retain(body[4]),
+                commands: MovieCommand::from_bits_retain(body[5]),
+                ahead: body[6] as i8,
+            },
+            5 if body.len() == 12 => Message::Hash {
+                frame: u32_at(0).ok_or(short)?,
+                hash: u64::from_le_bytes(body[4..12].try_into().unwrap()),
+            },
+            6 => Message::Bye,
+            1..=6 => return Err(ProtocolError::Malformed("wrong length")),
+            _ => return Err(ProtocolError::Malformed("unknown tag")),
+        })
+    }
+
+    pub fn write_to(&self, writer: &mut impl Write) -> io::Result<()> {
+        let (tag, body) = self.encode();
+        let mut out = Vec::with_capacity(5 + body.len());
+        out.push(tag);
+        out.extend_from_slice(&(body.len() as u32).to_le_bytes());
+        out.extend_from_slice(&body);
+        writer.write_all(&out)?;
+        writer.flush()
+    }
+
+    pub fn read_from(reader: &mut impl Read) -> Result<Message, ProtocolError> {
+        let mut head = [0; 5];
+        reader.read_exact(&mut head)?;
+        let len = u32::from_le_bytes(head[1..].try_into().unwrap()) as usize;
+        if len > MAX_MESSAGE {
+            return Err(ProtocolError::Malformed("too long"));
+        }
+        let mut body = vec![0; len];
+        reader.read_exact(&mut body)?;
+        Message::decode(head[0], &body)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_round_trips() {
+        let messages = [
+            Message::Hello {
+                version: VERSION,
+                crc32: 0xDEAD_BEEF,
+            },
+            Message::Welcome {
+                delay: 2,
+                window: 8,
+                state: vec![1, 2, 3],
+            },
+            Message::Reject("wrong game".to_string()),
+            Message::Input {
+                frame: 70_000,
+                buttons: JoypadButton::START | JoypadButton::BUTTON_A,
+                commands: MovieCommand::SOFT_RESET,
+                ahead: -3,
+            },
+            Message::Hash {
+                frame: 5,
+                hash: u64::MAX - 1,
+            },
+            Message::Bye,
+        ];
+        let mut wire = Vec::new();
+        for message in &messages {
+            message.write_to(&mut wire).unwrap();
+        }
+        let mut reader = wire.as_slice();
+        for message in &messages {
+            assert_eq!(&Message::read_from(&mut reader).unwrap(), message);
+        }
+        assert!(matches!(
+            Message::read_from(&mut reader),
+            Err(ProtocolError::Closed)
+        ));
+        let mut bad: &[u8] = &[4, 1, 0, 0, 0, 9];
+        assert!(matches!(
+            Message::read_from(&mut bad),
+            Err(ProtocolError::Malformed("wrong length"))
+        ));
+    }
+}
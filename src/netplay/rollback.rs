@@ -0,0 +1,315 @@
+// Rollback, GGPO style: rather than wait for the other player's pad, a
+// frame runs on a guess of it, the pad they last sent held on, and the
+// machine is snapshotted after every frame. When their real pad comes in
+// and the guess was wrong, the machine goes back to the state before that
+// frame and runs everything since again with what's known now, only the
+// newest frame being seen or heard. A frame is confirmed once their real
+// pad for it is in; up to `window` frames past the last confirmed one
+// may run on guesses before it has to wait. A window of 0 is lockstep.
+//
+// Nothing here touches the network; Netplay feeds it pads both ways. The
+// emulator is driven through run_ahead's Rollback trait, with the same
+// cheap snapshots.
+//
+// The side that's further ahead guesses more and rolls back more, so
+// each says how far ahead it thinks it is, and the one ahead by more
+// than PACE_FRAMES of the two views holds a frame now and then to let
+// the other catch up.
+
+use std::collections::VecDeque;
+
+use crate::input::joypad::JoypadButton;
+use crate::movie::{FrameInput, MovieCommand};
+use crate::run_ahead::Rollback;
+
+pub type Pad = (JoypadButton, MovieCommand);
+
+const PACE_FRAMES: i32 = 2;
+
+#[derive(Debug)]
+pub struct RollbackSync<S> {
+    player: usize,
+    delay: u32,
+    window: u32,
+    // the next frame to run
+    frame: u32,
+    // the first frame without the other player's pad
+    confirmed: u32,
+    // pads for frames from `confirmed` on: ours, and theirs as they came
+    local: VecDeque<Pad>,
+    remote: VecDeque<Pad>,
+    // what frames from `confirmed` to `frame` ran with for theirs
+    ran_with: VecDeque<Pad>,
+    // the state before `confirmed`, and after each frame run since
+    base: S,
+    states: VecDeque<S>,
+    last_remote: JoypadButton,
+    // the first frame that ran on a wrong guess
+    wrong: Option<u32>,
+    remote_ahead: i32,
+    rollbacks: u64,
+}
+
+impl<S> RollbackSync<S> {
+    // From `base`, as player `player` of 2.
+    pub fn new(player: usize, delay: u32, window: u32, base: S) -> Self {
+        // nobody presses anything in the frames before the first pads
+        // arrive
+        let nothing: VecDeque<Pad> = vec![Pad::default(); delay as usize].into();
+        RollbackSync {
+            player,
+            delay,
+            window,
+            frame: 0,
+            confirmed: 0,
+            local: nothing.clone(),
+            remote: nothing,
+            ran_with: VecDeque::new(),
+            base,
+            states: VecDeque::new(),
+            last_remote: JoypadButton::empty(),
+            wrong: None,
+            remote_ahead: 0,
+            rollbacks: 0,
+        }
+    }
+
+    pub fn frame(&self) -> u32 {
+        self.frame
+    }
+
+    pub fn confirmed(&self) -> u32 {
+        self.confirmed
+    }
+
+    pub fn window(&self) -> u32 {
+        self.window
+    }
+
+    pub fn rollbacks(&self) -> u64 {
+        self.rollbacks
+    }
+
+    // What the next pad each way is for.
+    pub fn next_local(&self) -> u32 {
+        self.confirmed + self.local.len() as u32
+    }
+
+    pub fn next_remote(&self) -> u32 {
+        self.confirmed + self.remote.len() as u32
+    }
+
+    pub fn add_local(&mut self, pad: Pad) {
+        self.local.push_back(pad);
+    }
+
+    // False if it isn't for next_remote(); pads come in order.
+    pub fn add_remote(&mut self, frame: u32, pad: Pad) -> bool {
+        if frame != self.next_remote() {
+            return false;
+        }
+        let guessed = self.ran_with.get(self.remote.len());
+        if guessed.is_some_and(|&guessed| guessed != pad) && self.wrong.is_none() {
+            self.wrong = Some(frame);
+        }
+        self.remote.push_back(pad);
+        self.last_remote = pad.0;
+        true
+    }
+
+    // How many frames we've run past the other player's, as far as
+    // their pads tell.
+    pub fn ahead(&self) -> i32 {
+        self.frame as i32 + self.delay as i32 - self.next_remote() as i32
+    }
+
+    // What the other player said ahead() was on their side.
+    pub fn set_remote_ahead(&mut self, ahead: i32) {
+        self.remote_ahead = ahead;
+    }
+
+    // Hold a frame to let the other player catch up.
+    pub fn should_wait(&self) -> bool {
+        (self.ahead() - self.remote_ahead) / 2 >= PACE_FRAMES
+    }
+
+    fn can_run(&self) -> bool {
+        let index = self.frame - self.confirmed;
+        index < self.local.len() as u32 && self.frame < self.next_remote() + self.window
+    }
+
+    fn guess(&self, index: usize) -> Pad {
+        // a reset is never guessed
+        self.remote
+            .get(index)
+            .copied()
+            .unwrap_or((self.last_remote, MovieCommand::empty()))
+    }
+
+    // Redoes what ran on a wrong guess, then runs the next frame if it
+    // can, returning its input. `confirmed` gets the frame count and
+    // state after each frame that became confirmed.
+    pub fn run<T: Rollback<State = S>>(
+        &mut self,
+        target: &mut T,
+        mut confirmed: impl FnMut(u32, &S),
+    ) -> Option<FrameInput> {
+        if let Some(wrong) = self.wrong.take() {
+            let keep = (wrong - self.confirmed) as usize;
+            let before = match keep {
+                0 => &self.base,
+                _ => &self.states[keep - 1],
+            };
+            target.load_state(before);
+            self.states.truncate(keep);
+            self.ran_with.truncate(keep);
+            let redo = std::mem::replace(&mut self.frame, wrong);
+            while self.frame < redo {
+                self.step(target, false);
+            }
+            self.rollbacks += 1;
+        }
+        let input = self.can_run().then(|| self.step(target, true));
+        while !self.remote.is_empty() && !self.ran_with.is_empty() {
+            self.local.pop_front();
+            self.remote.pop_front();
+            self.ran_with.pop_front();
+            if let Some(state) = self.states.pop_front() {
+                self.base = state;
+            }
+            self.confirmed += 1;
+            confirmed(self.confirmed, &self.base);
+        }
+        input
+    }
+
+    fn step<T: Rollback<State = S>>(&mut self, target: &mut T, present: bool) -> FrameInput {
+        let index = (self.frame - self.confirmed) as usize;
+        let ours = self.local[index];
+        let theirs = self.guess(index);
+        let mut input = FrameInput::default();
+        input.pads[self.player] = ours.0;
+        input.pads[1 - self.player] = theirs.0;
+        input.commands = ours.1 | theirs.1;
+        target.run_frame(&input, present);
+        self.states.push_back(target.save_state());
+        self.ran_with.push_back(theirs);
+        self.frame += 1;
+        input
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // A "game" whose state is a hash of every input it has seen.
+    #[derive(Default)]
+    struct Machine {
+        frame: u32,
+        hash: u64,
+        frames_run: usize,
+        presented: usize,
+    }
+
+    impl Rollback for Machine {
+        type State = (u32, u64);
+
+        fn save_state(&mut self) -> (u32, u64) {
+            (self.frame, self.hash)
+        }
+
+        fn load_state(&mut self, state: &(u32, u64)) {
+            (self.frame, self.hash) = *state;
+        }
+
+        fn run_frame(&mut self, input: &FrameInput, present: bool) {
+            self.frame += 1;
+            let pads = input.pads[0].bits() as u64 | (input.pads[1].bits() as u64) << 8;
+            self.hash = self.hash.wrapping_mul(31).wrapping_add(pads);
+            self.frames_run += 1;
+            self.presented += present as usize;
+        }
+    }
+
+    fn pad(bits: u8) -> Pad {
+        (JoypadButton::from_bits_retain(bits), MovieCommand::empty())
+    }
+
+    #[test]
+    fn test_guesses_then_corrects() {
+        let mut machine = Machine::default();
+        let mut sync = RollbackSync::new(0, 0, 4, machine.save_state());
+        let ours = [1, 1, 2, 2, 3, 3];
+        let theirs = [8, 8, 8, 16, 16, 16];
+
+        // four frames on guesses, then the window is full
+        for &bits in &ours {
+            sync.add_local(pad(bits));
+        }
+        let mut ran = 0;
+        while sync.run(&mut machine, |_, _| {}).is_some() {
+            ran += 1;
+        }
+        assert_eq!((ran, sync.frame(), sync.confirmed()), (4, 4, 0));
+
+        // the first three pads are what default guessing can't know
+        let mut confirmed = Vec::new();
+        for (frame, &bits) in theirs.iter().enumerate() {
+            assert!(sync.add_remote(frame as u32, pad(bits)));
+            sync.run(&mut machine, |frame, state| confirmed.push((frame, *state)));
+        }
+        assert!(!sync.add_remote(2, pad(0)));
+        assert_eq!(sync.frame(), 6);
+        assert_eq!(sync.confirmed(), 6);
+        assert_eq!(sync.rollbacks(), 2);
+
+        let mut reference = Machine::default();
+        for (&a, &b) in ours.iter().zip(&theirs) {
+            let mut input = FrameInput::default();
+            input.pads[0] = pad(a).0;
+            input.pads[1] = pad(b).0;
+            reference.run_frame(&input, true);
+            let frame = reference.frame;
+            assert!(confirmed.contains(&(frame, reference.save_state())));
+        }
+        assert_eq!(machine.save_state(), reference.save_state());
+        assert!(machine.frames_run > 6);
+        assert_eq!(machine.presented, 6);
+    }
+
+    #[test]
+    fn test_window_of_zero_is_lockstep() {
+        let mut machine = Machine::default();
+        let mut sync = RollbackSync::new(1, 2, 0, machine.save_state());
+        sync.add_local(pad(1));
+        // the delay frames run on nothing from either side
+        assert!(sync.run(&mut machine, |_, _| {}).is_some());
+        assert!(sync.run(&mut machine, |_, _| {}).is_some());
+        assert!(sync.run(&mut machine, |_, _| {}).is_none());
+        assert_eq!(sync.next_remote(), 2);
+        sync.add_remote(2, pad(4));
+        let input = sync.run(&mut machine, |_, _| {}).unwrap();
+        assert_eq!((input.pads[1].bits(), input.pads[0].bits()), (1, 4));
+        assert_eq!(sync.rollbacks(), 0);
+        assert_eq!(machine.frames_run, 3);
+    }
+
+    #[test]
+    fn test_pacing() {
+        let mut machine = Machine::default();
+        let mut sync = RollbackSync::new(0, 0, 8, machine.save_state());
+        for _ in 0..6 {
+            sync.add_local(pad(0));
+            sync.run(&mut machine, |_, _| {});
+        }
+        // six frames past anything from them, and they see themselves
+        // level
+        assert_eq!(sync.ahead(), 6);
+        assert!(sync.should_wait());
+        sync.set_remote_ahead(-4);
+        assert!(sync.should_wait());
+        sync.set_remote_ahead(3);
+        assert!(!sync.should_wait());
+    }
+}
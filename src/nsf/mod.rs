@@ -0,0 +1,388 @@
+// NSF and NSFe music rips: the sound driver and data from a game, with a
+// header saying where to load it and which routines to call.
+
+pub mod player;
+
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+use core::time::Duration;
+
+use crate::apu::expansion::ExpansionChip;
+
+type Address = u16;
+
+const NSF_MAGIC: &[u8; 5] = b"NESM\x1a";
+const NSFE_MAGIC: &[u8; 4] = b"NSFE";
+const NSF_HEADER_LEN: usize = 0x80;
+
+// NSFe files without a RATE chunk play at the standard vblank rate
+const DEFAULT_NTSC_SPEED: u16 = 16639;
+const DEFAULT_PAL_SPEED: u16 = 19997;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum NsfError {
+    BadMagic,
+    Truncated,
+    MissingChunk(&'static str),
+    UnknownRequiredChunk([u8; 4]),
+    BadLoadAddress(Address),
+}
+
+impl fmt::Display for NsfError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            NsfError::BadMagic => write!(f, "not an NSF or NSFe file"),
+            NsfError::Truncated => write!(f, "file is truncated"),
+            NsfError::MissingChunk(id) => write!(f, "missing required {} chunk", id),
+            NsfError::UnknownRequiredChunk(id) => write!(
+                f,
+                "unsupported required chunk {:?}",
+                String::from_utf8_lossy(id)
+            ),
+            NsfError::BadLoadAddress(addr) => write!(f, "load address {:#06x} is not in ROM", addr),
+        }
+    }
+}
+
+impl core::error::Error for NsfError {}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct TrackInfo {
+    pub title: Option<String>,
+    pub length: Option<Duration>,
+    pub fade: Option<Duration>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Nsf {
+    pub load_address: Address,
+    pub init_address: Address,
+    pub play_address: Address,
+    pub title: String,
+    pub artist: String,
+    pub copyright: String,
+    pub ripper: Option<String>,
+    // play routine rates, in microseconds between calls
+    pub ntsc_speed: u16,
+    pub pal_speed: u16,
+    pub bank_init: [u8; 8],
+    pub pal: bool,
+    // what the file asks for; see NsfPlayer::silent_chips
+    pub expansion_chips: Vec<ExpansionChip>,
+    // 0-based, unlike the NSF header field
+    pub starting_track: u8,
+    pub tracks: Vec<TrackInfo>,
+    pub data: Vec<u8>,
+}
+
+fn expansion_chips(flags: u8) -> Vec<ExpansionChip> {
+    [
+        ExpansionChip::Vrc6,
+        ExpansionChip::Vrc7,
+        ExpansionChip::Fds,
+        ExpansionChip::Mmc5,
+        ExpansionChip::Namco163,
+        ExpansionChip::Sunsoft5B,
+    ]
+    .into_iter()
+    .enumerate()
+    .filter(|(bit, _)| flags & (1 << bit) != 0)
+    .map(|(_, chip)| chip)
+    .collect()
+}
+
+fn u16_at(bytes: &[u8], at: usize) -> u16 {
+    u16::from_le_bytes([bytes[at], bytes[at + 1]])
+}
+
+fn fixed_string(bytes: &[u8]) -> String {
+    let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+    String::from_utf8_lossy(&bytes[..end]).into_owned()
+}
+
+fn strings(bytes: &[u8]) -> Vec<String> {
+    let bytes = bytes.strip_suffix(&[0]).unwrap_or(bytes);
+    bytes
+        .split(|&b| b == 0)
+        .map(|s| String::from_utf8_lossy(s).into_owned())
+        .collect()
+}
+
+fn durations(bytes: &[u8]) -> Vec<Option<Duration>> {
+    bytes
+        .chunks_exact(4)
+        .map(|ms| i32::from_le_bytes(ms.try_into().unwrap()))
+        .map(|ms| (ms >= 0).then(|| Duration::from_millis(ms as u64)))
+        .collect()
+}
+
+impl Nsf {
+    pub fn parse(bytes: &[u8]) -> Result<Nsf, NsfError> {
+        if bytes.starts_with(NSF_MAGIC) {
+            Self::parse_nsf(bytes)
+        } else if bytes.starts_with(NSFE_MAGIC) {
+            Self::parse_nsfe(bytes)
+        } else {
+            Err(NsfError::BadMagic)
+        }
+    }
+
+    fn parse_nsf(bytes: &[u8]) -> Result<Nsf, NsfError> {
+        if bytes.len() < NSF_HEADER_LEN {
+            return Err(NsfError::Truncated);
+        }
+        let header = &bytes[..NSF_HEADER_LEN];
+
+        let nsf = Nsf {
+            load_address: u16_at(header, 0x08),
+            init_address: u16_at(header, 0x0A),
+            play_address: u16_at(header, 0x0C),
+            title: fixed_string(&header[0x0E..0x2E]),
+            artist: fixed_string(&header[0x2E..0x4E]),
+            copyright: fixed_string(&header[0x4E..0x6E]),
+            ripper: None,
+            ntsc_speed: u16_at(header, 0x6E),
+            pal_speed: u16_at(header, 0x78),
+            bank_init: header[0x70..0x78].try_into().unwrap(),
+            // bit 1 means dual region, which we play as NTSC
+            pal: header[0x7A] & 0b11 == 0b01,
+            expansion_chips: expansion_chips(header[0x7B]),
+            starting_track: header[0x07].saturating_sub(1),
+            tracks: vec![TrackInfo::default(); header[0x06] as usize],
+            data: bytes[NSF_HEADER_LEN..].to_vec(),
+        };
+        nsf.validate()
+    }
+
+    fn parse_nsfe(bytes: &[u8]) -> Result<Nsf, NsfError> {
+        let mut nsf = Nsf {
+            load_address: 0,
+            init_address: 0,
+            play_address: 0,
+            title: String::new(),
+            artist: String::new(),
+            copyright: String::new(),
+            ripper: None,
+            ntsc_speed: DEFAULT_NTSC_SPEED,
+            pal_speed: DEFAULT_PAL_SPEED,
+            bank_init: [0; 8],
+            pal: false,
+            expansion_chips: Vec::new(),
+            starting_track: 0,
+            tracks: Vec::new(),
+            data: Vec::new(),
+        };
+        let (mut seen_info, mut seen_data) = (false, false);
+        let mut titles = Vec::new();
+        let mut lengths = Vec::new();
+        let mut fades = Vec::new();
+
+        let mut rest = &bytes[NSFE_MAGIC.len()..];
+        while !rest.is_empty() {
+            if rest.len() < 8 {
+                return Err(NsfError::Truncated);
+            }
+            let len = u32::from_le_bytes(rest[0..4].try_into().unwrap()) as usize;
+            let id: [u8; 4] = rest[4..8].try_into().unwrap();
+            let end = 8usize.checked_add(len).ok_or(NsfError::Truncated)?;
+            let chunk = rest.get(8..end).ok_or(NsfError::Truncated)?;
+            rest = &rest[end..];
+
+            match &id {
+                b"INFO" => {
+                    if chunk.len() < 8 {
+                        return Err(NsfError::Truncated);
+                    }
+                    nsf.load_address = u16_at(chunk, 0);
+                    nsf.init_address = u16_at(chunk, 2);
+                    nsf.play_address = u16_at(chunk, 4);
+                    nsf.pal = chunk[6] & 0b11 == 0b01;
+                    nsf.expansion_chips = expansion_chips(chunk[7]);
+                    let count = chunk.get(8).copied().unwrap_or(1);
+                    nsf.tracks = vec![TrackInfo::default(); count as usize];
+                    nsf.starting_track = chunk.get(9).copied().unwrap_or(0);
+                    seen_info = true;
+                }
+                b"DATA" => {
+                    nsf.data = chunk.to_vec();
+                    seen_data = true;
+                }
+                b"BANK" => {
+                    for (slot, bank) in nsf.bank_init.iter_mut().zip(chunk) {
+                        *slot = *bank;
+                    }
+                }
+                b"RATE" => {
+                    if chunk.len() >= 2 {
+                        nsf.ntsc_speed = u16_at(chunk, 0);
+                    }
+                    if chunk.len() >= 4 {
+                        nsf.pal_speed = u16_at(chunk, 2);
+                    }
+                }
+                b"auth" => {
+                    let mut fields = strings(chunk).into_iter();
+                    nsf.title = fields.next().unwrap_or_default();
+                    nsf.artist = fields.next().unwrap_or_default();
+                    nsf.copyright = fields.next().unwrap_or_default();
+                    nsf.ripper = fields.next();
+                }
+                b"tlbl" => titles = strings(chunk),
+                b"time" => lengths = durations(chunk),
+                b"fade" => fades = durations(chunk),
+                b"NEND" => break,
+                // an uppercase first letter marks a chunk players must
+                // understand; anything else is safe to skip
+                _ if id[0].is_ascii_uppercase() => return Err(NsfError::UnknownRequiredChunk(id)),
+                _ => {}
+            }
+        }
+
+        if !seen_info {
+            return Err(NsfError::MissingChunk("INFO"));
+        }
+        if !seen_data {
+            return Err(NsfError::MissingChunk("DATA"));
+        }
+
+        for (n, track) in nsf.tracks.iter_mut().enumerate() {
+            track.title = titles.get(n).cloned();
+            track.length = lengths.get(n).copied().flatten();
+            track.fade = fades.get(n).copied().flatten();
+        }
+
+        nsf.validate()
+    }
+
+    fn validate(self) -> Result<Nsf, NsfError> {
+        if !self.is_bankswitched() && self.load_address < 0x8000 {
+            return Err(NsfError::BadLoadAddress(self.load_address));
+        }
+        Ok(self)
+    }
+
+    pub fn is_bankswitched(&self) -> bool {
+        self.bank_init.iter().any(|&bank| bank != 0)
+    }
+
+    pub fn track_count(&self) -> usize {
+        self.tracks.len()
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+
+    // A minimal NSF: INIT stores the track number in $00, PLAY increments
+    // $01.
+    pub(crate) fn test_nsf_bytes() -> Vec<u8> {
+        let mut bytes = vec![0; NSF_HEADER_LEN];
+        bytes[..5].copy_from_slice(NSF_MAGIC);
+        bytes[0x05] = 1; // version
+        bytes[0x06] = 3; // songs
+        bytes[0x07] = 2; // starting song
+        bytes[0x08..0x0A].copy_from_slice(&0x8000u16.to_le_bytes());
+        bytes[0x0A..0x0C].copy_from_slice(&0x8000u16.to_le_bytes());
+        bytes[0x0C..0x0E].copy_from_slice(&0x8003u16.to_le_bytes());
+        bytes[0x0E..0x12].copy_from_slice(b"Test");
+        bytes[0x2E..0x31].copy_from_slice(b"Me!");
+        bytes[0x6E..0x70].copy_from_slice(&16639u16.to_le_bytes());
+        bytes[0x7B] = 0b0000_0001; // VRC6
+
+        // init: sta $00; rts; play: inc $01; rts
+        bytes.extend_from_slice(&[0x85, 0x00, 0x60, 0xe6, 0x01, 0x60]);
+        bytes
+    }
+
+    fn chunk(id: &[u8; 4], data: &[u8]) -> Vec<u8> {
+        let mut bytes = (data.len() as u32).to_le_bytes().to_vec();
+        bytes.extend_from_slice(id);
+        bytes.extend_from_slice(data);
+        bytes
+    }
+
+    #[test]
+    fn test_parse_nsf_header() {
+        let nsf = Nsf::parse(&test_nsf_bytes()).unwrap();
+
+        assert_eq!(nsf.track_count(), 3);
+        assert_eq!(nsf.starting_track, 1);
+        assert_eq!(nsf.init_address, 0x8000);
+        assert_eq!(nsf.play_address, 0x8003);
+        assert_eq!(nsf.title, "Test");
+        assert_eq!(nsf.artist, "Me!");
+        assert_eq!(nsf.expansion_chips, vec![ExpansionChip::Vrc6]);
+        assert!(!nsf.is_bankswitched());
+        assert!(!nsf.pal);
+        assert_eq!(nsf.data.len(), 6);
+    }
+
+    #[test]
+    fn test_parse_rejects_garbage() {
+        assert_eq!(Nsf::parse(b"hello").unwrap_err(), NsfError::BadMagic);
+        assert_eq!(
+            Nsf::parse(b"NESM\x1a\x01").unwrap_err(),
+            NsfError::Truncated
+        );
+        let mut huge = NSFE_MAGIC.to_vec();
+        huge.extend_from_slice(&u32::MAX.to_le_bytes());
+        huge.extend_from_slice(b"DATA");
+        assert_eq!(Nsf::parse(&huge).unwrap_err(), NsfError::Truncated);
+    }
+
+    #[test]
+    fn test_parse_nsfe_with_metadata() {
+        let mut info = vec![];
+        info.extend_from_slice(&0x8000u16.to_le_bytes());
+        info.extend_from_slice(&0x8000u16.to_le_bytes());
+        info.extend_from_slice(&0x8003u16.to_le_bytes());
+        info.extend_from_slice(&[0, 0, 2, 1]);
+
+        let mut time = 90_000i32.to_le_bytes().to_vec();
+        time.extend_from_slice(&(-1i32).to_le_bytes());
+
+        let mut bytes = NSFE_MAGIC.to_vec();
+        bytes.extend(chunk(b"INFO", &info));
+        bytes.extend(chunk(b"DATA", &[0x60]));
+        bytes.extend(chunk(b"auth", b"Game\0Composer\0(c) Co\0Ripper\0"));
+        bytes.extend(chunk(b"tlbl", b"Title Theme\0Ending\0"));
+        bytes.extend(chunk(b"time", &time));
+        bytes.extend(chunk(b"xtra", b"skipped"));
+        bytes.extend(chunk(b"NEND", &[]));
+
+        let nsf = Nsf::parse(&bytes).unwrap();
+        assert_eq!(nsf.title, "Game");
+        assert_eq!(nsf.ripper.as_deref(), Some("Ripper"));
+        assert_eq!(nsf.starting_track, 1);
+        assert_eq!(nsf.ntsc_speed, DEFAULT_NTSC_SPEED);
+        assert_eq!(nsf.tracks[0].title.as_deref(), Some("Title Theme"));
+        assert_eq!(nsf.tracks[0].length, Some(Duration::from_secs(90)));
+        assert_eq!(nsf.tracks[1].title.as_deref(), Some("Ending"));
+        assert_eq!(nsf.tracks[1].length, None);
+    }
+
+    #[test]
+    fn test_nsfe_unknown_required_chunk() {
+        let mut bytes = NSFE_MAGIC.to_vec();
+        bytes.extend(chunk(b"ZZZZ", &[]));
+
+        assert_eq!(
+            Nsf::parse(&bytes).unwrap_err(),
+            NsfError::UnknownRequiredChunk(*b"ZZZZ")
+        );
+    }
+
+    #[test]
+    fn test_nsfe_requires_info_and_data() {
+        let mut bytes = NSFE_MAGIC.to_vec();
+        bytes.extend(chunk(b"DATA", &[0x60]));
+
+        assert_eq!(
+            Nsf::parse(&bytes).unwrap_err(),
+            NsfError::MissingChunk("INFO")
+        );
+    }
+}
@@ -0,0 +1,225 @@
+// Drives an NSF the way a hardware player does: INIT once per track with
+// the track number in A, then PLAY at the rate the header asks for. Calls
+// are made by pushing a return address into unmapped I/O space and running
+// the CPU until an RTS lands there. No expansion chip is emulated yet: a
+// file that asks for one plays without it, with a warning when it loads.
+
+use alloc::boxed::Box;
+
+use super::{Nsf, TrackInfo};
+use crate::apu::expansion::ExpansionChip;
+use crate::apu::{NTSC_CPU_CLOCK, PAL_CPU_CLOCK};
+use crate::bus::Mem;
+use crate::cpu::{Cpu, BREAK2, INTERRUPT_DISABLE, STACK_RESET};
+use crate::mapper::nsf::NsfMapper;
+
+type Address = u16;
+
+const RETURN_TRAP: Address = 0x4100;
+
+pub struct NsfPlayer {
+    cpu: Cpu,
+    nsf: Nsf,
+    track: u8,
+    play_period: u64,
+    period_end: u64,
+    // PLAY ran past its period and is still on the stack
+    in_play: bool,
+}
+
+impl NsfPlayer {
+    pub fn new(nsf: Nsf) -> Self {
+        let (speed, clock) = if nsf.pal {
+            (nsf.pal_speed, PAL_CPU_CLOCK)
+        } else {
+            (nsf.ntsc_speed, NTSC_CPU_CLOCK)
+        };
+
+        for chip in &nsf.expansion_chips {
+            tracing::warn!(target: "nes::nsf", "{:?} audio isn't emulated; its channels stay silent", chip);
+        }
+        let mut cpu = Cpu::new();
+        cpu.bus.insert_cartridge(Box::new(NsfMapper::new(&nsf)));
+
+        let mut player = NsfPlayer {
+            cpu,
+            track: nsf.starting_track,
+            play_period: (speed as f64 * clock / 1_000_000.0) as u64,
+            period_end: 0,
+            in_play: false,
+            nsf,
+        };
+        player.select_track(player.track);
+        player
+    }
+
+    pub fn nsf(&self) -> &Nsf {
+        &self.nsf
+    }
+
+    // The expansion chips the file asks for that play nothing here, which
+    // is all of them for now: the player attaches none.
+    pub fn silent_chips(&self) -> &[ExpansionChip] {
+        &self.nsf.expansion_chips
+    }
+
+    pub fn current_track(&self) -> u8 {
+        self.track
+    }
+
+    pub fn track_info(&self) -> Option<&TrackInfo> {
+        self.nsf.tracks.get(self.track as usize)
+    }
+
+    // CPU cycles between PLAY calls.
+    pub fn play_period(&self) -> u64 {
+        self.play_period
+    }
+
+    pub fn cpu(&mut self) -> &mut Cpu {
+        &mut self.cpu
+    }
+
+    // Resets the machine and runs INIT for `track` (0-based). Returns false
+    // if INIT did not return within a second of emulated time.
+    pub fn select_track(&mut self, track: u8) -> bool {
+        let track = track.min(self.nsf.track_count().saturating_sub(1) as u8);
+        self.track = track;
+
+        let cpu = &mut self.cpu;
+        for addr in 0x0000..0x0800 {
+            cpu.mem_write(addr, 0);
+        }
+        for addr in 0x6000..0x8000 {
+            cpu.mem_write(addr, 0);
+        }
+        for addr in 0x4000..0x4014 {
+            cpu.mem_write(addr, 0);
+        }
+        cpu.mem_write(0x4015, 0x00);
+        cpu.mem_write(0x4015, 0x0F);
+        cpu.mem_write(0x4017, 0x40);
+        if self.nsf.is_bankswitched() {
+            for (n, bank) in self.nsf.bank_init.iter().enumerate() {
+                cpu.mem_write(0x5FF8 + n as u16, *bank);
+            }
+        }
+
+        cpu.register_a = track;
+        cpu.register_x = self.nsf.pal as u8;
+        cpu.register_y = 0;
+        cpu.stack_pointer = STACK_RESET;
+        cpu.status = INTERRUPT_DISABLE | BREAK2;
+
+        self.in_play = false;
+        self.start_call(self.nsf.init_address);
+        let deadline = self.cpu.cycles + self.play_period * 60;
+        let returned = self.run_until_return(deadline);
+        self.period_end = self.cpu.cycles + self.play_period;
+        returned
+    }
+
+    pub fn next_track(&mut self) -> bool {
+        let next = (self.track as usize + 1) % self.nsf.track_count().max(1);
+        self.select_track(next as u8)
+    }
+
+    pub fn previous_track(&mut self) -> bool {
+        let count = self.nsf.track_count().max(1);
+        let previous = (self.track as usize + count - 1) % count;
+        self.select_track(previous as u8)
+    }
+
+    // Runs one PLAY call and idles the APU for the rest of the period.
+    // A PLAY that overruns its period is resumed next time rather than being
+    // called again on top of itself.
+    pub fn run_play_period(&mut self) {
+        if !self.in_play {
+            self.start_call(self.nsf.play_address);
+        }
+        self.in_play = !self.run_until_return(self.period_end);
+
+        while self.cpu.cycles < self.period_end {
+            self.cpu.bus.tick(1);
+            self.cpu.cycles += 1;
+        }
+        self.period_end += self.play_period;
+    }
+
+    fn start_call(&mut self, addr: Address) {
+        self.cpu.stack_push_u16(RETURN_TRAP - 1);
+        self.cpu.program_counter = addr;
+    }
+
+    fn run_until_return(&mut self, deadline: u64) -> bool {
+        while self.cpu.program_counter != RETURN_TRAP {
+            if self.cpu.cycles >= deadline || !self.cpu.step() {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::nsf::test::test_nsf_bytes;
+
+    fn player() -> NsfPlayer {
+        NsfPlayer::new(Nsf::parse(&test_nsf_bytes()).unwrap())
+    }
+
+    #[test]
+    fn test_starts_on_header_track() {
+        let mut player = player();
+
+        assert_eq!(player.current_track(), 1);
+        assert_eq!(player.cpu().mem_read(0x00), 1);
+        // the test file asks for a VRC6
+        assert_eq!(player.silent_chips(), [ExpansionChip::Vrc6]);
+    }
+
+    #[test]
+    fn test_play_is_called_once_per_period() {
+        let mut player = player();
+        assert_eq!(player.play_period(), 29780);
+
+        for _ in 0..3 {
+            player.run_play_period();
+        }
+        assert_eq!(player.cpu().mem_read(0x01), 3);
+        assert_eq!(player.cpu().stack_pointer, STACK_RESET);
+    }
+
+    #[test]
+    fn test_track_selection_wraps_and_resets_ram() {
+        let mut player = player();
+        player.run_play_period();
+
+        assert!(player.next_track());
+        assert_eq!(player.current_track(), 2);
+        assert_eq!(player.cpu().mem_read(0x00), 2);
+        assert_eq!(player.cpu().mem_read(0x01), 0);
+
+        player.next_track();
+        assert_eq!(player.current_track(), 0);
+        player.previous_track();
+        assert_eq!(player.current_track(), 2);
+    }
+
+    #[test]
+    fn test_overrunning_play_is_resumed() {
+        let mut bytes = test_nsf_bytes();
+        // play: jmp play
+        let len = bytes.len();
+        bytes[len - 3..].copy_from_slice(&[0x4c, 0x03, 0x80]);
+        let mut player = NsfPlayer::new(Nsf::parse(&bytes).unwrap());
+
+        for _ in 0..3 {
+            player.run_play_period();
+        }
+        // only the first call's return address is on the stack
+        assert_eq!(player.cpu().stack_pointer, STACK_RESET - 2);
+    }
+}
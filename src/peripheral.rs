@@ -0,0 +1,227 @@
+// Devices of the host's own on the CPU bus: a debug port, a serial link, a
+// coprocessor for a homebrew board. Each claims a range of addresses from
+// $4018 up, and reads and writes there go to it before the cartridge sees
+// them; the first attached wins where ranges overlap. One can also pull
+// the IRQ line, and gets a clock every CPU cycle the mapper does.
+//
+// They're attached to the bus, not part of the board, so they stay put
+// across savestate loads and cartridge swaps. What a device reports from
+// save_state goes into the savestate's "PERI" section, one entry each in
+// the order they were attached, and back through load_state. Devices are
+// Send, like boards.
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::fmt;
+use core::ops::RangeInclusive;
+
+use crate::state::StateError;
+
+type Address = u16;
+type Value = u8;
+
+// Below this is the PPU and the console's own registers.
+pub const FIRST_ADDRESS: Address = 0x4018;
+
+pub trait Peripheral: Send {
+    fn read(&mut self, addr: Address) -> Value;
+
+    fn write(&mut self, addr: Address, value: Value);
+
+    // A read without side effects, for debuggers; open bus by default.
+    fn peek(&self, _addr: Address) -> Value {
+        0
+    }
+
+    fn clock(&mut self) {}
+
+    fn irq_pending(&self) -> bool {
+        false
+    }
+
+    fn save_state(&self) -> Vec<u8> {
+        Vec::new()
+    }
+
+    fn load_state(&mut self, _state: &[u8]) -> Result<(), StateError> {
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PeripheralError {
+    // reaches below $4018, or is empty
+    BadRange(RangeInclusive<Address>),
+}
+
+impl fmt::Display for PeripheralError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PeripheralError::BadRange(range) => write!(
+                f,
+                "can't attach a device at ${:04X}-${:04X}; it must be within ${:04X}-$FFFF",
+                range.start(),
+                range.end(),
+                FIRST_ADDRESS
+            ),
+        }
+    }
+}
+
+impl core::error::Error for PeripheralError {}
+
+type Attached = (RangeInclusive<Address>, Box<dyn Peripheral>);
+
+// The bus's attached devices, by handle: the index they went in at.
+#[derive(Default)]
+pub struct Peripherals {
+    devices: Vec<Option<Attached>>,
+    attached: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PeripheralId(usize);
+
+impl Peripherals {
+    pub fn attach(
+        &mut self,
+        range: RangeInclusive<Address>,
+        device: Box<dyn Peripheral>,
+    ) -> Result<PeripheralId, PeripheralError> {
+        if range.is_empty() || *range.start() < FIRST_ADDRESS {
+            return Err(PeripheralError::BadRange(range));
+        }
+        self.devices.push(Some((range, device)));
+        self.attached += 1;
+        Ok(PeripheralId(self.devices.len() - 1))
+    }
+
+    pub fn detach(&mut self, id: PeripheralId) -> Option<Box<dyn Peripheral>> {
+        let (_, device) = self.devices.get_mut(id.0)?.take()?;
+        self.attached -= 1;
+        Some(device)
+    }
+
+    pub fn get_mut(&mut self, id: PeripheralId) -> Option<&mut (dyn Peripheral + 'static)> {
+        let (_, device) = self.devices.get_mut(id.0)?.as_mut()?;
+        Some(device.as_mut())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.attached == 0
+    }
+
+    pub fn len(&self) -> usize {
+        self.attached
+    }
+
+    fn iter(&self) -> impl Iterator<Item = &Attached> {
+        self.devices.iter().flatten()
+    }
+
+    fn iter_mut(&mut self) -> impl Iterator<Item = &mut Attached> {
+        self.devices.iter_mut().flatten()
+    }
+
+    // The device at `addr`, if one claims it.
+    pub(crate) fn claiming(&mut self, addr: Address) -> Option<&mut (dyn Peripheral + 'static)> {
+        if self.attached == 0 {
+            return None;
+        }
+        self.iter_mut()
+            .find(|(range, _)| range.contains(&addr))
+            .map(|(_, device)| device.as_mut())
+    }
+
+    pub(crate) fn peek(&self, addr: Address) -> Option<Value> {
+        self.iter()
+            .find(|(range, _)| range.contains(&addr))
+            .map(|(_, device)| device.peek(addr))
+    }
+
+    pub(crate) fn clock(&mut self) {
+        for (_, device) in self.iter_mut() {
+            device.clock();
+        }
+    }
+
+    pub(crate) fn irq_pending(&self) -> bool {
+        self.iter().any(|(_, device)| device.irq_pending())
+    }
+
+    pub(crate) fn save_state(&self) -> Vec<Vec<u8>> {
+        self.iter().map(|(_, device)| device.save_state()).collect()
+    }
+
+    // Before anything's loaded, so a state for other devices changes
+    // nothing; one that fails part way is its own business, as with the
+    // mapper.
+    pub(crate) fn check_state(&self, states: &[Vec<u8>]) -> Result<(), StateError> {
+        if states.len() != self.attached {
+            return Err(StateError::WrongPeripherals);
+        }
+        Ok(())
+    }
+
+    pub(crate) fn load_state(&mut self, states: &[Vec<u8>]) -> Result<(), StateError> {
+        self.check_state(states)?;
+        for ((_, device), state) in self.iter_mut().zip(states) {
+            device.load_state(state)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[derive(Default)]
+    struct Latch(Value);
+
+    impl Peripheral for Latch {
+        fn read(&mut self, _addr: Address) -> Value {
+            self.0
+        }
+
+        fn write(&mut self, _addr: Address, value: Value) {
+            self.0 = value;
+        }
+    }
+
+    #[test]
+    fn test_ranges_are_checked() {
+        let mut peripherals = Peripherals::default();
+        #[allow(clippy::reversed_empty_ranges)]
+        for range in [0x4017..=0x4018, 0x2000..=0x2007, 0x5001..=0x5000] {
+            assert!(matches!(
+                peripherals.attach(range, Box::new(Latch::default())),
+                Err(PeripheralError::BadRange(_))
+            ));
+        }
+        assert!(peripherals.is_empty());
+        assert!(peripherals
+            .attach(0x4018..=0x401F, Box::new(Latch::default()))
+            .is_ok());
+    }
+
+    #[test]
+    fn test_first_attached_wins() {
+        let mut peripherals = Peripherals::default();
+        let first = peripherals
+            .attach(0x5000..=0x5FFF, Box::new(Latch(1)))
+            .unwrap();
+        peripherals
+            .attach(0x5800..=0x6FFF, Box::new(Latch(2)))
+            .unwrap();
+        assert_eq!(peripherals.claiming(0x5800).unwrap().read(0x5800), 1);
+        assert_eq!(peripherals.claiming(0x6000).unwrap().read(0x6000), 2);
+        assert!(peripherals.claiming(0x7000).is_none());
+
+        assert!(peripherals.detach(first).is_some());
+        assert!(peripherals.detach(first).is_none());
+        assert_eq!(peripherals.claiming(0x5800).unwrap().read(0x5800), 2);
+        assert!(peripherals.claiming(0x5000).is_none());
+        assert_eq!(peripherals.len(), 1);
+    }
+}
@@ -0,0 +1,920 @@
+// 2C02 picture processing unit. The CPU talks to it through eight registers
+// mirrored over $2000-$3FFF (plus OAM DMA at $4014); it reads pattern
+// tables from the cartridge and keeps 2K of nametable RAM, 32 bytes of
+// palette RAM and 256 bytes of sprite OAM itself.
+//
+// Timing is dot based: 341 dots per scanline, 262 scanlines per frame, with
+// vblank (and the NMI) starting at scanline 241; PAL and Dendy consoles
+// have 312, and the Dendy starts vblank at 291 (see region.rs). Pixels are
+// produced a scanline at a time at the start of each visible line from the
+// scroll position the loopy registers hold at that point, which covers the
+// usual split-screen tricks done between lines. Sprite zero hit is still
+// raised at the dot where the overlapping pixel is drawn. Laying the
+// sprites over the background and looking colours up is SIMD where the
+// host has it; see pixels.rs.
+//
+// A VS. System's PPU (see vs.rs) changes the colours, and a 2C05 also
+// its registers.
+
+mod pixels;
+pub mod registers;
+
+use alloc::vec::Vec;
+
+use serde::{Deserialize, Serialize};
+
+use pixels::{LineColours, SpriteLine, BEHIND, SPRITE_ZERO};
+use registers::{ControlRegister, MaskRegister, StatusRegister};
+
+use crate::mapper::{Mapper, Mirroring};
+use crate::region::Region;
+use crate::render::frame::Frame;
+use crate::render::palette::Palette;
+use crate::state::Dirty;
+use crate::vs::VsPpu;
+
+type Address = u16;
+type Value = u8;
+
+pub const DOTS_PER_SCANLINE: u16 = 341;
+pub const VISIBLE_SCANLINES: u16 = 240;
+
+const MAX_SPRITES_PER_LINE: usize = 8;
+
+#[derive(Serialize, Deserialize)]
+pub struct Ppu {
+    pub ctrl: ControlRegister,
+    pub mask: MaskRegister,
+    pub status: StatusRegister,
+    pub oam_addr: Value,
+    #[serde(with = "crate::state::byte_array")]
+    oam: [Value; 256],
+    // room for four-screen boards; everything else uses the first 2K
+    #[serde(with = "crate::state::byte_array")]
+    vram: [Value; 0x1000],
+    palette: [Value; 32],
+    // what's been written since the last frame hash, CHR included; see
+    // state::MachineHasher
+    #[serde(skip)]
+    pub(crate) oam_dirty: Dirty,
+    #[serde(skip)]
+    pub(crate) vram_dirty: Dirty,
+    #[serde(skip)]
+    pub(crate) chr_dirty: Dirty,
+
+    // loopy registers: current and temporary VRAM address, fine X scroll
+    // and the shared $2005/$2006 write toggle
+    v: u16,
+    t: u16,
+    fine_x: u8,
+    w: bool,
+
+    read_buffer: Value,
+    open_bus: Value,
+
+    scanline: u16,
+    dot: u16,
+    frame_count: u64,
+    odd_frame: bool,
+    nmi_pending: bool,
+    sprite_zero_dot: Option<u16>,
+    #[serde(skip)]
+    frame: Frame,
+    #[serde(skip)]
+    colours: Palette,
+    // off draws every sprite on a line; the overflow flag still works
+    #[serde(skip, default = "sprite_limit_default")]
+    sprite_limit: bool,
+    // which chip this is, when it isn't a 2C02; it comes with the
+    // cartridge
+    #[serde(skip)]
+    vs: Option<VsPpu>,
+    #[serde(skip, default = "colour_map_default")]
+    colour_map: &'static [u8; 64],
+    // also the cartridge's, or what the player picked
+    #[serde(skip)]
+    region: Region,
+}
+
+fn sprite_limit_default() -> bool {
+    true
+}
+
+fn colour_map_default() -> &'static [u8; 64] {
+    VsPpu::Rp2c03.colour_map()
+}
+
+impl Default for Ppu {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl core::fmt::Debug for Ppu {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_struct("Ppu")
+            .field("ctrl", &self.ctrl)
+            .field("mask", &self.mask)
+            .field("status", &self.status)
+            .field("v", &self.v)
+            .field("scanline", &self.scanline)
+            .field("dot", &self.dot)
+            .field("frame_count", &self.frame_count)
+            .finish()
+    }
+}
+
+impl Ppu {
+    pub fn new() -> Self {
+        Ppu {
+            ctrl: ControlRegister::empty(),
+            mask: MaskRegister::empty(),
+            status: StatusRegister::empty(),
+            oam_addr: 0,
+            oam: [0; 256],
+            vram: [0; 0x1000],
+            palette: [0; 32],
+            oam_dirty: Dirty::default(),
+            vram_dirty: Dirty::default(),
+            chr_dirty: Dirty::default(),
+            v: 0,
+            t: 0,
+            fine_x: 0,
+            w: false,
+            read_buffer: 0,
+            open_bus: 0,
+            scanline: 0,
+            dot: 0,
+            frame_count: 0,
+            odd_frame: false,
+            nmi_pending: false,
+            sprite_zero_dot: None,
+            frame: Frame::new(),
+            colours: Palette::default(),
+            sprite_limit: true,
+            vs: None,
+            colour_map: colour_map_default(),
+            region: Region::Ntsc,
+        }
+    }
+
+    pub fn set_region(&mut self, region: Region) {
+        self.region = region.console();
+    }
+
+    pub fn region(&self) -> Region {
+        self.region
+    }
+
+    pub fn set_vs(&mut self, vs: Option<VsPpu>) {
+        self.vs = vs;
+        self.colour_map = vs.unwrap_or_default().colour_map();
+    }
+
+    pub fn vs(&self) -> Option<VsPpu> {
+        self.vs
+    }
+
+    // How palette entries look on screen.
+    pub fn set_colours(&mut self, colours: Palette) {
+        self.colours = colours;
+    }
+
+    pub fn colours(&self) -> Palette {
+        self.colours
+    }
+
+    // Hardware shows at most eight sprites per scanline, which is where
+    // most flicker comes from.
+    pub fn set_sprite_limit(&mut self, enabled: bool) {
+        self.sprite_limit = enabled;
+    }
+
+    pub fn sprite_limit(&self) -> bool {
+        self.sprite_limit
+    }
+
+    // The picture and display settings aren't in savestates; keep the ones
+    // we have.
+    pub(crate) fn take_host_state(&mut self, old: &mut Ppu) {
+        core::mem::swap(&mut self.frame, &mut old.frame);
+        self.colours = old.colours;
+        self.sprite_limit = old.sprite_limit;
+    }
+
+    // The reset line only clears the write toggle and the registers that
+    // depend on it; memory keeps its contents.
+    pub fn reset(&mut self) {
+        self.ctrl = ControlRegister::empty();
+        self.mask = MaskRegister::empty();
+        self.w = false;
+        self.t = 0;
+        self.fine_x = 0;
+        self.read_buffer = 0;
+        self.scanline = 0;
+        self.dot = 0;
+        self.odd_frame = false;
+        self.nmi_pending = false;
+    }
+
+    pub fn frame(&self) -> &Frame {
+        &self.frame
+    }
+
+    // Frames are counted when vblank starts, so the picture is complete
+    // the moment this changes.
+    pub fn frame_count(&self) -> u64 {
+        self.frame_count
+    }
+
+    pub fn scanline(&self) -> u16 {
+        self.scanline
+    }
+
+    pub fn dot(&self) -> u16 {
+        self.dot
+    }
+
+    // Sprite memory, four bytes a sprite.
+    pub fn oam(&self) -> &[Value; 256] {
+        &self.oam
+    }
+
+    pub fn oam_mut(&mut self) -> &mut [Value; 256] {
+        self.oam_dirty.mark_all();
+        &mut self.oam
+    }
+
+    // The nametable RAM, unmirrored.
+    pub(crate) fn vram(&self) -> &[Value] {
+        &self.vram
+    }
+
+    pub fn vram_addr(&self) -> Address {
+        self.v
+    }
+
+    // Whether the NMI line went low since the last call.
+    pub fn poll_nmi(&mut self) -> bool {
+        core::mem::take(&mut self.nmi_pending)
+    }
+
+    // CPU read of $2000-$2007 (already mirrored down).
+    pub fn read_register(&mut self, addr: Address, mapper: &mut dyn Mapper) -> Value {
+        let value = match addr & 0x2007 {
+            0x2002 => {
+                let value = self.status.bits() | self.status_low_bits();
+                self.status.remove(StatusRegister::VBLANK_STARTED);
+                self.w = false;
+                value
+            }
+            0x2004 => {
+                let value = self.oam[self.oam_addr as usize];
+                // attribute bits 2-4 don't exist
+                if self.oam_addr & 3 == 2 {
+                    value & 0xE3
+                } else {
+                    value
+                }
+            }
+            0x2007 => {
+                let addr = self.v & 0x3FFF;
+                let value = if addr >= 0x3F00 {
+                    // palette reads skip the buffer, which gets the
+                    // nametable byte underneath instead
+                    self.read_buffer = self.read(addr - 0x1000, mapper);
+                    (self.read_palette(addr) & 0x3F) | (self.open_bus & 0xC0)
+                } else {
+                    let value = self.read(addr, mapper);
+                    core::mem::replace(&mut self.read_buffer, value)
+                };
+                self.increment_vram_addr();
+                value
+            }
+            // write-only registers read back whatever was last on the bus
+            _ => self.open_bus,
+        };
+        self.open_bus = value;
+        value
+    }
+
+    // Reads without side effects, for debuggers.
+    pub fn peek_register(&self, addr: Address) -> Value {
+        match addr & 0x2007 {
+            0x2002 => self.status.bits() | self.status_low_bits(),
+            0x2004 => self.oam[self.oam_addr as usize],
+            0x2007 => self.read_buffer,
+            _ => self.open_bus,
+        }
+    }
+
+    // Open bus, or a 2C05's ID.
+    fn status_low_bits(&self) -> Value {
+        match self.vs.and_then(VsPpu::id) {
+            Some(id) => id,
+            None => self.open_bus & 0x1F,
+        }
+    }
+
+    pub fn write_register(&mut self, addr: Address, value: Value, mapper: &mut dyn Mapper) {
+        tracing::trace!("write ${:04X} = ${:02X}", addr, value);
+        self.open_bus = value;
+        let swapped = self.vs.is_some_and(VsPpu::swaps_control);
+        let register = match addr & 0x2007 {
+            0x2000 if swapped => 0x2001,
+            0x2001 if swapped => 0x2000,
+            register => register,
+        };
+        match register {
+            0x2000 => {
+                let was_enabled = self.ctrl.contains(ControlRegister::GENERATE_NMI);
+                self.ctrl = ControlRegister::from_bits_retain(value);
+                self.t = (self.t & !0x0C00) | ((value as u16 & 0x03) << 10);
+                // enabling NMI during vblank fires one straight away
+                if !was_enabled
+                    && self.ctrl.contains(ControlRegister::GENERATE_NMI)
+                    && self.status.contains(StatusRegister::VBLANK_STARTED)
+                {
+                    self.nmi_pending = true;
+                }
+            }
+            0x2001 => self.mask = MaskRegister::from_bits_retain(value),
+            0x2003 => self.oam_addr = value,
+            0x2004 => {
+                self.oam[self.oam_addr as usize] = value;
+                self.oam_dirty.mark(self.oam_addr as usize);
+                self.oam_addr = self.oam_addr.wrapping_add(1);
+            }
+            0x2005 => {
+                if !self.w {
+                    self.t = (self.t & !0x001F) | (value as u16 >> 3);
+                    self.fine_x = value & 0x07;
+                } else {
+                    self.t = (self.t & !0x73E0)
+                        | ((value as u16 & 0x07) << 12)
+                        | ((value as u16 & 0xF8) << 2);
+                }
+                self.w = !self.w;
+            }
+            0x2006 => {
+                if !self.w {
+                    self.t = (self.t & 0x00FF) | ((value as u16 & 0x3F) << 8);
+                } else {
+                    self.t = (self.t & 0xFF00) | value as u16;
+                    self.v = self.t;
+                }
+                self.w = !self.w;
+            }
+            0x2007 => {
+                self.write(self.v & 0x3FFF, value, mapper);
+                self.increment_vram_addr();
+            }
+            _ => {}
+        }
+    }
+
+    // $4014: the bus copies a CPU page, this stores it starting at OAMADDR.
+    pub fn write_oam_dma(&mut self, data: &[Value; 256]) {
+        self.oam_dirty.mark_all();
+        for value in data {
+            self.oam[self.oam_addr as usize] = *value;
+            self.oam_addr = self.oam_addr.wrapping_add(1);
+        }
+    }
+
+    fn increment_vram_addr(&mut self) {
+        self.v = self.v.wrapping_add(self.ctrl.vram_increment()) & 0x7FFF;
+    }
+
+    // PPU address space: pattern tables on the cartridge, then nametables
+    // (mirrored as the board says) and palette RAM.
+    pub fn read(&self, addr: Address, mapper: &mut dyn Mapper) -> Value {
+        match addr & 0x3FFF {
+            0x0000..=0x1FFF => mapper.read_chr(addr),
+            0x2000..=0x3EFF => self.vram[self.nametable_index(addr, mapper.mirroring())],
+            _ => self.read_palette(addr),
+        }
+    }
+
+    pub fn write(&mut self, addr: Address, value: Value, mapper: &mut dyn Mapper) {
+        match addr & 0x3FFF {
+            0x0000..=0x1FFF => {
+                mapper.write_chr(addr, value);
+                self.chr_dirty.mark_all();
+            }
+            0x2000..=0x3EFF => {
+                let index = self.nametable_index(addr, mapper.mirroring());
+                self.vram[index] = value;
+                self.vram_dirty.mark(index);
+            }
+            _ => self.palette[palette_index(addr)] = value & 0x3F,
+        }
+    }
+
+    // read for debuggers: palette entries as stored, ignoring greyscale.
+    pub fn peek(&self, addr: Address, mapper: &mut dyn Mapper) -> Value {
+        match addr & 0x3FFF {
+            0x3F00..=0x3FFF => self.palette[palette_index(addr)],
+            _ => self.read(addr, mapper),
+        }
+    }
+
+    fn read_palette(&self, addr: Address) -> Value {
+        let value = self.palette[palette_index(addr)];
+        if self.mask.contains(MaskRegister::GREYSCALE) {
+            value & 0x30
+        } else {
+            value
+        }
+    }
+
+    fn nametable_index(&self, addr: Address, mirroring: Mirroring) -> usize {
+        let offset = (addr - 0x2000) & 0x0FFF;
+        let table = offset / 0x400;
+        let physical = match mirroring {
+            Mirroring::Horizontal => [0, 0, 1, 1][table as usize],
+            Mirroring::Vertical => [0, 1, 0, 1][table as usize],
+            Mirroring::SingleScreenLower => 0,
+            Mirroring::SingleScreenUpper => 1,
+            Mirroring::FourScreen => table,
+        };
+        (physical * 0x400 + (offset & 0x3FF)) as usize
+    }
+
+    // One PPU dot; there are three per CPU cycle on NTSC.
+    pub fn tick(&mut self, mapper: &mut dyn Mapper) {
+        let rendering = self.mask.rendering_enabled();
+        let vblank = self.region.vblank_scanline();
+        let pre_render = self.region.scanlines() - 1;
+
+        match self.scanline {
+            0..=239 => {
+                if self.dot == 1 {
+                    self.render_scanline(mapper);
+                }
+                if Some(self.dot) == self.sprite_zero_dot {
+                    self.status.insert(StatusRegister::SPRITE_ZERO_HIT);
+                    self.sprite_zero_dot = None;
+                }
+                if rendering {
+                    self.update_scroll();
+                }
+            }
+            line if line == vblank && self.dot == 1 => {
+                self.status.insert(StatusRegister::VBLANK_STARTED);
+                if self.ctrl.contains(ControlRegister::GENERATE_NMI) {
+                    self.nmi_pending = true;
+                }
+                self.frame_count = self.frame_count.wrapping_add(1);
+                tracing::debug!(frame = self.frame_count, "vblank");
+            }
+            line if line == pre_render => {
+                if self.dot == 1 {
+                    self.status.remove(
+                        StatusRegister::VBLANK_STARTED
+                            | StatusRegister::SPRITE_ZERO_HIT
+                            | StatusRegister::SPRITE_OVERFLOW,
+                    );
+                }
+                if rendering {
+                    self.update_scroll();
+                    if (280..=304).contains(&self.dot) {
+                        self.v = (self.v & !0x7BE0) | (self.t & 0x7BE0);
+                    }
+                }
+            }
+            _ => {}
+        }
+
+        self.dot = self.dot.wrapping_add(1);
+        // odd frames skip the last dot of the pre-render line while
+        // rendering is on
+        let skip = self.odd_frame && rendering && self.region.skips_odd_dot();
+        if self.scanline == pre_render && self.dot == 340 && skip {
+            self.dot = DOTS_PER_SCANLINE;
+        }
+        if self.dot >= DOTS_PER_SCANLINE {
+            self.dot = 0;
+            self.scanline = self.scanline.wrapping_add(1);
+            if self.scanline > pre_render {
+                self.scanline = 0;
+                self.odd_frame = !self.odd_frame;
+            }
+        }
+    }
+
+    fn update_scroll(&mut self) {
+        match self.dot {
+            256 => self.increment_y(),
+            // horizontal position reloads from t for the next line
+            257 => self.v = (self.v & !0x041F) | (self.t & 0x041F),
+            _ => {}
+        }
+    }
+
+    fn increment_y(&mut self) {
+        if self.v & 0x7000 != 0x7000 {
+            self.v += 0x1000;
+            return;
+        }
+        self.v &= !0x7000;
+        let mut coarse_y = (self.v & 0x03E0) >> 5;
+        if coarse_y == 29 {
+            coarse_y = 0;
+            self.v ^= 0x0800;
+        } else if coarse_y == 31 {
+            // rows 30 and 31 are attribute data; wraps without switching
+            coarse_y = 0;
+        } else {
+            coarse_y += 1;
+        }
+        self.v = (self.v & !0x03E0) | (coarse_y << 5);
+    }
+
+    fn render_scanline(&mut self, mapper: &mut dyn Mapper) {
+        let y = self.scanline as usize;
+        let background = self.background_line(mapper);
+        let sprites = self.sprite_line(mapper);
+
+        let mut indexes = [0; Frame::WIDTH];
+        if let Some(x) = pixels::compose(&background, &sprites, &mut indexes) {
+            self.sprite_zero_dot.get_or_insert(x as u16 + 1);
+        }
+        let colours = LineColours::from_fn(|index| {
+            let entry = self.read_palette(0x3F00 + index as u16) & 0x3F;
+            self.colours.colours[self.colour_map[entry as usize] as usize]
+        });
+        pixels::to_rgb(&indexes, &colours, self.frame.row_mut(y));
+    }
+
+    // Palette indexes (0-15) for one line of background, from the scroll
+    // position in v.
+    fn background_line(&self, mapper: &mut dyn Mapper) -> [u8; Frame::WIDTH] {
+        let mut line = [0u8; Frame::WIDTH];
+        if !self.mask.contains(MaskRegister::SHOW_BACKGROUND) {
+            return line;
+        }
+
+        let mut v = self.v;
+        let fine_y = (v >> 12) & 0x07;
+        let pattern_base = self.ctrl.background_pattern_addr();
+
+        for tile in 0..33usize {
+            let tile_index = self.read(0x2000 | (v & 0x0FFF), mapper) as u16;
+            let attr_addr = 0x23C0 | (v & 0x0C00) | ((v >> 4) & 0x38) | ((v >> 2) & 0x07);
+            let shift = ((v >> 4) & 0x04) | (v & 0x02);
+            let palette = (self.read(attr_addr, mapper) >> shift) & 0x03;
+
+            let pattern = pattern_base + tile_index * 16 + fine_y;
+            let lo = mapper.read_chr(pattern);
+            let hi = mapper.read_chr(pattern + 8);
+
+            for px in 0..8 {
+                let x = (tile * 8 + px).wrapping_sub(self.fine_x as usize);
+                if x >= Frame::WIDTH {
+                    continue;
+                }
+                let bit = 7 - px;
+                let colour = ((hi >> bit) & 1) << 1 | ((lo >> bit) & 1);
+                if colour != 0 {
+                    line[x] = palette << 2 | colour;
+                }
+            }
+
+            // coarse X, wrapping into the next horizontal nametable
+            if v & 0x001F == 31 {
+                v = (v & !0x001F) ^ 0x0400;
+            } else {
+                v += 1;
+            }
+        }
+
+        if !self.mask.contains(MaskRegister::SHOW_BACKGROUND_LEFT) {
+            line[..8].fill(0);
+        }
+        line
+    }
+
+    fn sprite_line(&mut self, mapper: &mut dyn Mapper) -> SpriteLine {
+        let mut line = SpriteLine::new();
+        if !self.mask.rendering_enabled() {
+            return line;
+        }
+
+        let height = self.ctrl.sprite_height();
+        let scanline = self.scanline;
+        let mut visible = Vec::with_capacity(MAX_SPRITES_PER_LINE);
+        for sprite in 0..64 {
+            let top = self.oam[sprite * 4] as u16 + 1;
+            if (top..top + height).contains(&scanline) {
+                if visible.len() == MAX_SPRITES_PER_LINE {
+                    self.status.insert(StatusRegister::SPRITE_OVERFLOW);
+                    if self.sprite_limit {
+                        break;
+                    }
+                }
+                visible.push(sprite);
+            }
+        }
+        if !self.mask.contains(MaskRegister::SHOW_SPRITES) {
+            return line;
+        }
+
+        // lower OAM index wins, so draw in order and never overwrite
+        for &sprite in &visible {
+            let entry = &self.oam[sprite * 4..sprite * 4 + 4];
+            let (y, tile, attr, x) = (entry[0] as u16, entry[1] as u16, entry[2], entry[3]);
+            let flip_vertical = attr & 0x80 != 0;
+            let flip_horizontal = attr & 0x40 != 0;
+            let behind = attr & 0x20 != 0;
+            let palette = 0x10 | (attr & 0x03) << 2;
+
+            let mut row = scanline - (y + 1);
+            if flip_vertical {
+                row = height - 1 - row;
+            }
+            let addr = if height == 8 {
+                self.ctrl.sprite_pattern_addr() + tile * 16 + row
+            } else {
+                let table = (tile & 1) * 0x1000;
+                let tile = (tile & 0xFE) + row / 8;
+                table + tile * 16 + row % 8
+            };
+            let lo = mapper.read_chr(addr);
+            let hi = mapper.read_chr(addr + 8);
+
+            for px in 0..8 {
+                let sx = x as usize + px;
+                if sx >= Frame::WIDTH {
+                    break;
+                }
+                if sx < 8 && !self.mask.contains(MaskRegister::SHOW_SPRITES_LEFT) {
+                    continue;
+                }
+                let bit = if flip_horizontal { px } else { 7 - px };
+                let colour = ((hi >> bit) & 1) << 1 | ((lo >> bit) & 1);
+                if colour == 0 || line.pixels[sx] != 0 {
+                    continue;
+                }
+                line.pixels[sx] = palette | colour;
+                line.flags[sx] =
+                    if behind { BEHIND } else { 0 } | if sprite == 0 { SPRITE_ZERO } else { 0 };
+            }
+        }
+        line
+    }
+}
+
+// $3F10/$3F14/$3F18/$3F1C are mirrors of the backdrop entries below them.
+fn palette_index(addr: Address) -> usize {
+    let index = (addr & 0x1F) as usize;
+    if index >= 0x10 && index.is_multiple_of(4) {
+        index - 0x10
+    } else {
+        index
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::mapper::nrom::Nrom;
+    use crate::render::palette::SYSTEM_PALETTE;
+
+    fn cartridge(mirroring: Mirroring) -> Nrom {
+        Nrom::new(vec![0; 0x4000], Vec::new(), mirroring)
+    }
+
+    fn set_addr(ppu: &mut Ppu, mapper: &mut Nrom, addr: Address) {
+        ppu.write_register(0x2006, (addr >> 8) as u8, mapper);
+        ppu.write_register(0x2006, addr as u8, mapper);
+    }
+
+    const VBLANK_SCANLINE: u16 = 241;
+    const PRE_RENDER_SCANLINE: u16 = 261;
+
+    fn run_to(ppu: &mut Ppu, mapper: &mut Nrom, scanline: u16, dot: u16) {
+        while !(ppu.scanline == scanline && ppu.dot == dot) {
+            ppu.tick(mapper);
+        }
+    }
+
+    #[test]
+    fn test_vblank_and_nmi() {
+        let mut mapper = cartridge(Mirroring::Horizontal);
+        let mut ppu = Ppu::new();
+        ppu.write_register(0x2000, 0x80, &mut mapper);
+
+        run_to(&mut ppu, &mut mapper, VBLANK_SCANLINE, 2);
+        assert!(ppu.poll_nmi());
+        assert!(!ppu.poll_nmi());
+        assert_eq!(ppu.frame_count(), 1);
+
+        assert_eq!(ppu.read_register(0x2002, &mut mapper) & 0x80, 0x80);
+        assert_eq!(ppu.read_register(0x2002, &mut mapper) & 0x80, 0);
+    }
+
+    #[test]
+    fn test_frames_by_region() {
+        let mut mapper = cartridge(Mirroring::Horizontal);
+        for (region, vblank, lines) in [
+            (Region::Ntsc, 241, 262),
+            (Region::Pal, 241, 312),
+            (Region::Dendy, 291, 312),
+        ] {
+            let mut ppu = Ppu::new();
+            ppu.set_region(region);
+            // rendering on, for NTSC's short odd frames
+            ppu.write_register(0x2001, 0x08, &mut mapper);
+            run_to(&mut ppu, &mut mapper, vblank, 2);
+            assert_eq!(ppu.frame_count(), 1, "{:?}", region);
+            let mut dots = 0;
+            while ppu.frame_count() < 3 {
+                ppu.tick(&mut mapper);
+                dots += 1;
+            }
+            let short = if region == Region::Ntsc { 1 } else { 0 };
+            assert_eq!(dots, 2 * 341 * lines - short, "{:?}", region);
+        }
+    }
+
+    #[test]
+    fn test_data_port_buffers_reads() {
+        let mut mapper = cartridge(Mirroring::Horizontal);
+        let mut ppu = Ppu::new();
+        set_addr(&mut ppu, &mut mapper, 0x2305);
+        ppu.write_register(0x2007, 0x66, &mut mapper);
+        ppu.write_register(0x2007, 0x77, &mut mapper);
+
+        set_addr(&mut ppu, &mut mapper, 0x2305);
+        ppu.read_register(0x2007, &mut mapper); // primes the buffer
+        assert_eq!(ppu.read_register(0x2007, &mut mapper), 0x66);
+        assert_eq!(ppu.read_register(0x2007, &mut mapper), 0x77);
+    }
+
+    #[test]
+    fn test_increment_by_32() {
+        let mut mapper = cartridge(Mirroring::Horizontal);
+        let mut ppu = Ppu::new();
+        ppu.write_register(0x2000, 0b100, &mut mapper);
+        set_addr(&mut ppu, &mut mapper, 0x2000);
+        ppu.write_register(0x2007, 0x11, &mut mapper);
+        ppu.write_register(0x2007, 0x22, &mut mapper);
+
+        assert_eq!(ppu.read(0x2020, &mut mapper), 0x22);
+    }
+
+    #[test]
+    fn test_nametable_mirroring() {
+        let mut horizontal = cartridge(Mirroring::Horizontal);
+        let mut ppu = Ppu::new();
+        ppu.write(0x2005, 0x42, &mut horizontal);
+        assert_eq!(ppu.read(0x2405, &mut horizontal), 0x42);
+        assert_eq!(ppu.read(0x2805, &mut horizontal), 0x00);
+
+        let mut vertical = cartridge(Mirroring::Vertical);
+        let mut ppu = Ppu::new();
+        ppu.write(0x2005, 0x42, &mut vertical);
+        assert_eq!(ppu.read(0x2805, &mut vertical), 0x42);
+        assert_eq!(ppu.read(0x3405, &mut vertical), 0x00);
+    }
+
+    #[test]
+    fn test_palette_mirrors_and_unbuffered_read() {
+        let mut mapper = cartridge(Mirroring::Horizontal);
+        let mut ppu = Ppu::new();
+        set_addr(&mut ppu, &mut mapper, 0x3F10);
+        ppu.write_register(0x2007, 0x2A, &mut mapper);
+
+        set_addr(&mut ppu, &mut mapper, 0x3F00);
+        assert_eq!(ppu.read_register(0x2007, &mut mapper), 0x2A);
+    }
+
+    #[test]
+    fn test_scroll_registers() {
+        let mut mapper = cartridge(Mirroring::Horizontal);
+        let mut ppu = Ppu::new();
+        ppu.write_register(0x2000, 0x01, &mut mapper);
+        ppu.write_register(0x2005, 0b0111_1101, &mut mapper);
+        ppu.write_register(0x2005, 0b0101_1110, &mut mapper);
+
+        assert_eq!(ppu.fine_x, 0b101);
+        assert_eq!(ppu.t, 0b110_0101_0110_1111);
+    }
+
+    // Tile 1 is solid colour 1; put it at the top left of the screen.
+    fn solid_tile_setup(mapper: &mut Nrom, ppu: &mut Ppu) {
+        for row in 0..8 {
+            mapper.write_chr(16 + row, 0xFF);
+        }
+        ppu.write(0x2000, 0x01, mapper);
+        ppu.write(0x3F00, 0x0F, mapper);
+        ppu.write(0x3F01, 0x30, mapper);
+        ppu.write(0x3F11, 0x16, mapper);
+    }
+
+    #[test]
+    fn test_renders_background() {
+        let mut mapper = cartridge(Mirroring::Horizontal);
+        let mut ppu = Ppu::new();
+        solid_tile_setup(&mut mapper, &mut ppu);
+        ppu.write_register(0x2001, 0b0000_1010, &mut mapper);
+
+        run_to(&mut ppu, &mut mapper, VBLANK_SCANLINE, 2);
+        assert_eq!(ppu.frame().pixel(0, 0), SYSTEM_PALETTE[0x30]);
+        assert_eq!(ppu.frame().pixel(7, 7), SYSTEM_PALETTE[0x30]);
+        assert_eq!(ppu.frame().pixel(8, 0), SYSTEM_PALETTE[0x0F]);
+        assert_eq!(ppu.frame().pixel(0, 8), SYSTEM_PALETTE[0x0F]);
+    }
+
+    #[test]
+    fn test_vs_ppus() {
+        let mut mapper = cartridge(Mirroring::Horizontal);
+        let mut ppu = Ppu::new();
+        ppu.set_vs(Some(VsPpu::Rp2c04(4)));
+        solid_tile_setup(&mut mapper, &mut ppu);
+        ppu.write_register(0x2001, 0b0000_1010, &mut mapper);
+        run_to(&mut ppu, &mut mapper, VBLANK_SCANLINE, 2);
+        let shown = VsPpu::Rp2c04(4).colour_map()[0x30];
+        assert_eq!(ppu.frame().pixel(0, 0), SYSTEM_PALETTE[shown as usize]);
+
+        // a 2C05 takes $2000 writes as $2001 and the other way round
+        let mut ppu = Ppu::new();
+        ppu.set_vs(Some(VsPpu::Rc2c05(3)));
+        ppu.write_register(0x2000, 0b0000_1010, &mut mapper);
+        ppu.write_register(0x2001, 0x80, &mut mapper);
+        assert_eq!(ppu.mask.bits(), 0b0000_1010);
+        assert!(ppu.ctrl.contains(ControlRegister::GENERATE_NMI));
+        assert_eq!(ppu.read_register(0x2002, &mut mapper) & 0x1F, 0x1C);
+        assert_eq!(ppu.peek_register(0x2002) & 0x1F, 0x1C);
+    }
+
+    #[test]
+    fn test_sprite_zero_hit() {
+        let mut mapper = cartridge(Mirroring::Horizontal);
+        let mut ppu = Ppu::new();
+        solid_tile_setup(&mut mapper, &mut ppu);
+        // sprite 0 at (4, 3) over the solid background tile
+        ppu.oam[..4].copy_from_slice(&[2, 1, 0, 4]);
+        ppu.write_register(0x2001, 0b0001_1110, &mut mapper);
+
+        run_to(&mut ppu, &mut mapper, 3, 1);
+        assert!(!ppu.status.contains(StatusRegister::SPRITE_ZERO_HIT));
+        run_to(&mut ppu, &mut mapper, 3, 6);
+        assert!(ppu.status.contains(StatusRegister::SPRITE_ZERO_HIT));
+        assert_eq!(ppu.frame().pixel(4, 3), SYSTEM_PALETTE[0x16]);
+
+        run_to(&mut ppu, &mut mapper, PRE_RENDER_SCANLINE, 2);
+        assert!(!ppu.status.contains(StatusRegister::SPRITE_ZERO_HIT));
+    }
+
+    #[test]
+    fn test_sprite_overflow() {
+        let mut mapper = cartridge(Mirroring::Horizontal);
+        let mut ppu = Ppu::new();
+        for sprite in 0..9 {
+            ppu.oam[sprite * 4] = 10;
+        }
+        for sprite in 9..64 {
+            ppu.oam[sprite * 4] = 0xF0;
+        }
+        ppu.write_register(0x2001, 0b0001_0000, &mut mapper);
+
+        run_to(&mut ppu, &mut mapper, 11, 2);
+        assert!(ppu.status.contains(StatusRegister::SPRITE_OVERFLOW));
+    }
+
+    #[test]
+    fn test_sprite_limit_can_be_lifted() {
+        for limit in [true, false] {
+            let mut mapper = cartridge(Mirroring::Horizontal);
+            let mut ppu = Ppu::new();
+            solid_tile_setup(&mut mapper, &mut ppu);
+            ppu.set_sprite_limit(limit);
+            for sprite in 0..64 {
+                ppu.oam[sprite * 4] = if sprite < 9 { 10 } else { 0xF0 };
+                ppu.oam[sprite * 4 + 1] = 1;
+                ppu.oam[sprite * 4 + 3] = (sprite * 16) as u8 + 8;
+            }
+            ppu.write_register(0x2001, 0b0001_0000, &mut mapper);
+
+            run_to(&mut ppu, &mut mapper, VBLANK_SCANLINE, 2);
+            assert!(ppu.status.contains(StatusRegister::SPRITE_OVERFLOW));
+            let ninth = ppu.frame().pixel(8 * 16 + 8, 12);
+            assert_eq!(ninth == SYSTEM_PALETTE[0x16], !limit);
+        }
+    }
+
+    #[test]
+    fn test_oam_dma_starts_at_oam_addr() {
+        let mut mapper = cartridge(Mirroring::Horizontal);
+        let mut ppu = Ppu::new();
+        ppu.write_register(0x2003, 0x10, &mut mapper);
+        let mut page = [0; 256];
+        page[0] = 0x55;
+        ppu.write_oam_dma(&page);
+
+        assert_eq!(ppu.oam[0x10], 0x55);
+        assert_eq!(ppu.oam_addr, 0x10);
+    }
+}
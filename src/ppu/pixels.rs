@@ -0,0 +1,389 @@
+// The two loops every visible line goes through once its background and
+// sprites are fetched: compose() lays the sprites over the background,
+// priority and sprite zero hit included, and to_rgb() turns the palette
+// indexes that leaves into the frame's RGB. Both work on the whole line at
+// once, so they're done 16 pixels at a time where the host has the
+// instructions for it:
+//
+//   x86_64    SSE2 for compose, which every x86_64 has; SSSE3 for to_rgb,
+//             asked of the CPU at run time (with std; without it, only if
+//             the build targets it)
+//   aarch64   NEON for both, which every aarch64 has
+//
+// Anything else (wasm included) runs the scalar loops, which are also
+// what the tests hold the SIMD to. The fetching itself stays scalar: it's
+// reads through the mapper, which can have side effects.
+
+use crate::render::frame::Frame;
+
+const WIDTH: usize = Frame::WIDTH;
+
+// SpriteLine::flags
+pub const BEHIND: u8 = 0x01;
+pub const SPRITE_ZERO: u8 = 0x02;
+
+// One line of sprites as sprite_line() leaves it: the frontmost opaque
+// pixel at each x, 0 where there's none.
+pub struct SpriteLine {
+    // palette indexes, 0x10-0x1F
+    pub pixels: [u8; WIDTH],
+    pub flags: [u8; WIDTH],
+}
+
+impl SpriteLine {
+    pub fn new() -> Self {
+        SpriteLine {
+            pixels: [0; WIDTH],
+            flags: [0; WIDTH],
+        }
+    }
+}
+
+// The 32 palette entries as colours, a plane per channel, the way the
+// table lookups want them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineColours {
+    planes: [[u8; 32]; 3],
+}
+
+impl LineColours {
+    pub fn from_fn(colour: impl FnMut(usize) -> (u8, u8, u8)) -> Self {
+        let rgb: [(u8, u8, u8); 32] = core::array::from_fn(colour);
+        LineColours {
+            planes: [rgb.map(|c| c.0), rgb.map(|c| c.1), rgb.map(|c| c.2)],
+        }
+    }
+}
+
+// Fills `out` with the palette index shown at each x, 0 for the backdrop,
+// and returns the first x where sprite zero is drawn over opaque
+// background, short of the last pixel, which never hits. A transparent
+// pixel is 0 in either line.
+pub fn compose(
+    background: &[u8; WIDTH],
+    sprites: &SpriteLine,
+    out: &mut [u8; WIDTH],
+) -> Option<usize> {
+    #[cfg(all(target_arch = "x86_64", target_feature = "sse2"))]
+    let hit = x86::compose(background, sprites, out);
+    #[cfg(all(target_arch = "aarch64", target_feature = "neon"))]
+    let hit = neon::compose(background, sprites, out);
+    #[cfg(not(any(
+        all(target_arch = "x86_64", target_feature = "sse2"),
+        all(target_arch = "aarch64", target_feature = "neon")
+    )))]
+    let hit = compose_scalar(background, sprites, out);
+    hit.filter(|&x| x != WIDTH - 1)
+}
+
+// `row` is the frame's line, three bytes a pixel.
+pub fn to_rgb(indexes: &[u8; WIDTH], colours: &LineColours, row: &mut [u8]) {
+    let row = &mut row[..WIDTH * 3];
+    #[cfg(target_arch = "x86_64")]
+    if x86::has_ssse3() {
+        // SAFETY: the CPU has SSSE3
+        unsafe { x86::to_rgb(indexes, colours, row) };
+        return;
+    }
+    #[cfg(all(target_arch = "aarch64", target_feature = "neon"))]
+    neon::to_rgb(indexes, colours, row);
+    #[cfg(not(all(target_arch = "aarch64", target_feature = "neon")))]
+    to_rgb_scalar(indexes, colours, row);
+}
+
+#[cfg_attr(
+    any(
+        all(target_arch = "x86_64", target_feature = "sse2"),
+        all(target_arch = "aarch64", target_feature = "neon")
+    ),
+    allow(dead_code)
+)]
+fn compose_scalar(
+    background: &[u8; WIDTH],
+    sprites: &SpriteLine,
+    out: &mut [u8; WIDTH],
+) -> Option<usize> {
+    let mut hit = None;
+    for x in 0..WIDTH {
+        let (bg, sprite, flags) = (background[x], sprites.pixels[x], sprites.flags[x]);
+        let opaque_bg = bg & 0x03 != 0;
+        let opaque_sprite = sprite & 0x03 != 0;
+        if opaque_bg && opaque_sprite && flags & SPRITE_ZERO != 0 {
+            hit.get_or_insert(x);
+        }
+        out[x] = if opaque_sprite && !(opaque_bg && flags & BEHIND != 0) {
+            sprite
+        } else {
+            bg
+        };
+    }
+    hit
+}
+
+#[cfg_attr(
+    all(target_arch = "aarch64", target_feature = "neon"),
+    allow(dead_code)
+)]
+fn to_rgb_scalar(indexes: &[u8; WIDTH], colours: &LineColours, row: &mut [u8]) {
+    let [r, g, b] = &colours.planes;
+    for (pixel, &index) in row.chunks_exact_mut(3).zip(indexes) {
+        let index = index as usize & 0x1F;
+        pixel.copy_from_slice(&[r[index], g[index], b[index]]);
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+mod x86 {
+    use core::arch::x86_64::*;
+
+    use super::{LineColours, SpriteLine, BEHIND, SPRITE_ZERO, WIDTH};
+
+    pub fn has_ssse3() -> bool {
+        #[cfg(feature = "std")]
+        {
+            std::is_x86_feature_detected!("ssse3")
+        }
+        #[cfg(not(feature = "std"))]
+        {
+            cfg!(target_feature = "ssse3")
+        }
+    }
+
+    #[cfg(target_feature = "sse2")]
+    pub fn compose(
+        background: &[u8; WIDTH],
+        sprites: &SpriteLine,
+        out: &mut [u8; WIDTH],
+    ) -> Option<usize> {
+        let mut hit = None;
+        // SAFETY: SSE2 is part of x86_64, and every load and store is 16
+        // bytes from a multiple of 16 into a 256-byte line
+        unsafe {
+            let (zero, low_bits) = (_mm_setzero_si128(), _mm_set1_epi8(0x03));
+            let behind = _mm_set1_epi8(BEHIND as i8);
+            let sprite_zero = _mm_set1_epi8(SPRITE_ZERO as i8);
+            for x in (0..WIDTH).step_by(16) {
+                let bg = _mm_loadu_si128(background.as_ptr().add(x).cast());
+                let sprite = _mm_loadu_si128(sprites.pixels.as_ptr().add(x).cast());
+                let flags = _mm_loadu_si128(sprites.flags.as_ptr().add(x).cast());
+
+                let clear_bg = _mm_cmpeq_epi8(_mm_and_si128(bg, low_bits), zero);
+                let clear_sprite = _mm_cmpeq_epi8(_mm_and_si128(sprite, low_bits), zero);
+                let is_behind = _mm_cmpeq_epi8(_mm_and_si128(flags, behind), behind);
+                // the background shows where the sprite is clear, or behind
+                // opaque background
+                let hidden = _mm_or_si128(clear_sprite, _mm_andnot_si128(clear_bg, is_behind));
+                let pixel =
+                    _mm_or_si128(_mm_and_si128(hidden, bg), _mm_andnot_si128(hidden, sprite));
+                _mm_storeu_si128(out.as_mut_ptr().add(x).cast(), pixel);
+
+                if hit.is_none() {
+                    let is_zero = _mm_cmpeq_epi8(_mm_and_si128(flags, sprite_zero), sprite_zero);
+                    let overlap = _mm_andnot_si128(_mm_or_si128(clear_bg, clear_sprite), is_zero);
+                    let mask = _mm_movemask_epi8(overlap);
+                    if mask != 0 {
+                        hit = Some(x + mask.trailing_zeros() as usize);
+                    }
+                }
+            }
+        }
+        hit
+    }
+
+    // pshufb masks spreading 16 pixels' R, G and B planes into 48 bytes of
+    // packed RGB: for each of the three 16-byte stores, one mask per
+    // plane, 0x80 (nothing) where another plane's byte goes
+    const INTERLEAVE: [[[u8; 16]; 3]; 3] = {
+        let mut masks = [[[0x80; 16]; 3]; 3];
+        let mut byte = 0;
+        while byte < 48 {
+            masks[byte / 16][byte % 3][byte % 16] = (byte / 3) as u8;
+            byte += 1;
+        }
+        masks
+    };
+
+    // One plane's 32 entries, looked up for 16 indexes: pshufb takes a 16
+    // byte table, so each half is looked up and bit 4 picks between them.
+    #[target_feature(enable = "ssse3")]
+    fn lookup(plane: &[u8; 32], nibbles: __m128i, upper: __m128i) -> __m128i {
+        // SAFETY: both halves are 16 bytes in a 32-byte plane
+        let (low, high) = unsafe {
+            (
+                _mm_loadu_si128(plane.as_ptr().cast()),
+                _mm_loadu_si128(plane.as_ptr().add(16).cast()),
+            )
+        };
+        let low = _mm_shuffle_epi8(low, nibbles);
+        let high = _mm_shuffle_epi8(high, nibbles);
+        _mm_or_si128(_mm_and_si128(upper, high), _mm_andnot_si128(upper, low))
+    }
+
+    // SAFETY: for CPUs with SSSE3; `row` is the whole line
+    #[target_feature(enable = "ssse3")]
+    pub unsafe fn to_rgb(indexes: &[u8; WIDTH], colours: &LineColours, row: &mut [u8]) {
+        let [r, g, b] = &colours.planes;
+        let nibble = _mm_set1_epi8(0x0F);
+        let bit_4 = _mm_set1_epi8(0x10);
+        for (x, out) in (0..WIDTH).step_by(16).zip(row.chunks_exact_mut(48)) {
+            // SAFETY: 16 indexes from a multiple of 16 in a 256-byte line
+            let index = unsafe { _mm_loadu_si128(indexes.as_ptr().add(x).cast()) };
+            let nibbles = _mm_and_si128(index, nibble);
+            let upper = _mm_cmpeq_epi8(_mm_and_si128(index, bit_4), bit_4);
+            let planes = [
+                lookup(r, nibbles, upper),
+                lookup(g, nibbles, upper),
+                lookup(b, nibbles, upper),
+            ];
+            for (part, masks) in INTERLEAVE.iter().enumerate() {
+                let mut rgb = _mm_setzero_si128();
+                for (&plane, mask) in planes.iter().zip(masks) {
+                    // SAFETY: a 16-byte mask
+                    let mask = unsafe { _mm_loadu_si128(mask.as_ptr().cast()) };
+                    rgb = _mm_or_si128(rgb, _mm_shuffle_epi8(plane, mask));
+                }
+                // SAFETY: 16 of the chunk's 48 bytes
+                unsafe { _mm_storeu_si128(out.as_mut_ptr().add(part * 16).cast(), rgb) };
+            }
+        }
+    }
+}
+
+#[cfg(all(target_arch = "aarch64", target_feature = "neon"))]
+mod neon {
+    use core::arch::aarch64::*;
+
+    use super::{LineColours, SpriteLine, BEHIND, SPRITE_ZERO, WIDTH};
+
+    pub fn compose(
+        background: &[u8; WIDTH],
+        sprites: &SpriteLine,
+        out: &mut [u8; WIDTH],
+    ) -> Option<usize> {
+        let mut hit = None;
+        // SAFETY: NEON is part of aarch64, and every load and store is 16
+        // bytes from a multiple of 16 into a 256-byte line
+        unsafe {
+            let low_bits = vdupq_n_u8(0x03);
+            let behind = vdupq_n_u8(BEHIND);
+            let sprite_zero = vdupq_n_u8(SPRITE_ZERO);
+            for x in (0..WIDTH).step_by(16) {
+                let bg = vld1q_u8(background.as_ptr().add(x));
+                let sprite = vld1q_u8(sprites.pixels.as_ptr().add(x));
+                let flags = vld1q_u8(sprites.flags.as_ptr().add(x));
+
+                let opaque_bg = vtstq_u8(bg, low_bits);
+                let opaque_sprite = vtstq_u8(sprite, low_bits);
+                let hidden_by_bg = vandq_u8(opaque_bg, vtstq_u8(flags, behind));
+                let shown = vbicq_u8(opaque_sprite, hidden_by_bg);
+                vst1q_u8(out.as_mut_ptr().add(x), vbslq_u8(shown, sprite, bg));
+
+                if hit.is_none() {
+                    let overlap = vandq_u8(
+                        vandq_u8(opaque_bg, opaque_sprite),
+                        vtstq_u8(flags, sprite_zero),
+                    );
+                    if vmaxvq_u8(overlap) != 0 {
+                        let mut lanes = [0u8; 16];
+                        vst1q_u8(lanes.as_mut_ptr(), overlap);
+                        hit = lanes
+                            .iter()
+                            .position(|&lane| lane != 0)
+                            .map(|lane| x + lane);
+                    }
+                }
+            }
+        }
+        hit
+    }
+
+    pub fn to_rgb(indexes: &[u8; WIDTH], colours: &LineColours, row: &mut [u8]) {
+        // SAFETY: NEON is part of aarch64; the tables are the 32-byte
+        // planes, and each store is 48 bytes of a 48-byte chunk
+        unsafe {
+            let [r, g, b] = colours
+                .planes
+                .each_ref()
+                .map(|plane| vld1q_u8_x2(plane.as_ptr()));
+            let mask = vdupq_n_u8(0x1F);
+            for (x, out) in (0..WIDTH).step_by(16).zip(row.chunks_exact_mut(48)) {
+                let index = vandq_u8(vld1q_u8(indexes.as_ptr().add(x)), mask);
+                let rgb = uint8x16x3_t(
+                    vqtbl2q_u8(r, index),
+                    vqtbl2q_u8(g, index),
+                    vqtbl2q_u8(b, index),
+                );
+                vst3q_u8(out.as_mut_ptr(), rgb);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::rng::Rng;
+
+    fn random_line(rng: &mut Rng) -> ([u8; WIDTH], SpriteLine) {
+        let mut background = [0; WIDTH];
+        let mut sprites = SpriteLine::new();
+        for (x, pixel) in background.iter_mut().enumerate() {
+            let (bg, sprite) = (rng.next_u8(), rng.next_u8());
+            // transparent is 0, as the fetches leave it
+            *pixel = if bg & 0x03 == 0 { 0 } else { bg & 0x0F };
+            if sprite & 0x03 != 0 {
+                sprites.pixels[x] = 0x10 | (sprite & 0x0F);
+                sprites.flags[x] = rng.next_u8() & (BEHIND | SPRITE_ZERO);
+            }
+        }
+        (background, sprites)
+    }
+
+    #[test]
+    fn test_simd_matches_scalar() {
+        let mut rng = Rng::new(3);
+        let colours = LineColours::from_fn(|index| {
+            let index = index as u8;
+            (index, index ^ 0xFF, index.wrapping_mul(7))
+        });
+        for _ in 0..200 {
+            let (background, mut sprites) = random_line(&mut rng);
+            // a sprite zero hit somewhere past the first chunk, or none
+            let none_before = rng.next_u8() as usize;
+            for flags in &mut sprites.flags[..none_before] {
+                *flags &= !SPRITE_ZERO;
+            }
+
+            let (mut fast, mut slow) = ([0; WIDTH], [0; WIDTH]);
+            let hit = compose(&background, &sprites, &mut fast);
+            let expected = compose_scalar(&background, &sprites, &mut slow);
+            assert_eq!(fast, slow);
+            assert_eq!(hit, expected.filter(|&x| x != WIDTH - 1));
+
+            let (mut fast_rgb, mut slow_rgb) = (vec![0; WIDTH * 3], vec![0; WIDTH * 3]);
+            to_rgb(&fast, &colours, &mut fast_rgb);
+            to_rgb_scalar(&slow, &colours, &mut slow_rgb);
+            assert_eq!(fast_rgb, slow_rgb);
+        }
+    }
+
+    #[test]
+    fn test_priority_and_sprite_zero() {
+        let mut background = [0; WIDTH];
+        let mut sprites = SpriteLine::new();
+        background[..3].copy_from_slice(&[0x05, 0x06, 0x00]);
+        sprites.pixels[..3].copy_from_slice(&[0x11, 0x12, 0x13]);
+        sprites.flags[..3].copy_from_slice(&[0, BEHIND | SPRITE_ZERO, SPRITE_ZERO]);
+        // over the background, behind it, over the backdrop (no hit)
+        let mut out = [0; WIDTH];
+        assert_eq!(compose(&background, &sprites, &mut out), Some(1));
+        assert_eq!(out[..4], [0x11, 0x06, 0x13, 0x00]);
+
+        // the last pixel never hits
+        let mut sprites = SpriteLine::new();
+        background[WIDTH - 1] = 0x01;
+        sprites.pixels[WIDTH - 1] = 0x11;
+        sprites.flags[WIDTH - 1] = SPRITE_ZERO;
+        assert_eq!(compose(&background, &sprites, &mut out), None);
+    }
+}
@@ -0,0 +1,241 @@
+// Which console a game runs on, and what that changes about timing.
+//
+//             CPU clock     PPU dots   scanlines  vblank  frame rate
+//                           per cycle             starts
+//   NTSC      1.789773MHz   3          262        241     60.0988Hz
+//   PAL       1.662607MHz   3.2        312        241     50.0070Hz
+//   Dendy     1.773448MHz   3          312        291     50.0070Hz
+//
+// Only the NTSC PPU drops a dot on odd frames. The PAL APU uses its own
+// noise and DMC period tables and frame counter step times. The Dendy, a
+// famiclone, keeps the NTSC APU and a near-NTSC CPU clock, and puts the
+// 50 extra lines of PAL's frame before vblank, so NTSC games see as many
+// cycles between NMIs as they expect.
+//
+// NES 2.0 headers say which console a dump is for. iNES ones have a PAL
+// bit nearly nobody sets, so for those the file name decides when it
+// carries a No-Intro or GoodNES country tag: "(Europe)", "(E)", "(USA)",
+// "(Russia)" and the like.
+
+use alloc::string::{String, ToString};
+use core::fmt;
+use core::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::apu::{NTSC_CPU_CLOCK, PAL_CPU_CLOCK};
+use crate::ppu::DOTS_PER_SCANLINE;
+
+pub const DENDY_CPU_CLOCK: f64 = 1_773_448.0;
+
+// 1.789773MHz / (341 * 262 / 3 - 0.5) CPU cycles per frame
+pub const NTSC_FRAME_RATE: f64 = 60.0988;
+// 1.662607MHz / (341 * 312 / 3.2) CPU cycles per frame
+pub const PAL_FRAME_RATE: f64 = 50.0070;
+
+// what a PAL CPU cycle's 3.2 dots are counted in
+pub const PAL_DOT_PHASES: u8 = 5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum Region {
+    #[default]
+    Ntsc,
+    Pal,
+    // runs on either
+    Multi,
+    Dendy,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct UnknownRegion(pub String);
+
+impl fmt::Display for UnknownRegion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "unknown region '{}'; one of ntsc, pal or dendy", self.0)
+    }
+}
+
+impl core::error::Error for UnknownRegion {}
+
+impl FromStr for Region {
+    type Err = UnknownRegion;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        match text.to_ascii_lowercase().as_str() {
+            "ntsc" => Ok(Region::Ntsc),
+            "pal" => Ok(Region::Pal),
+            "dendy" => Ok(Region::Dendy),
+            _ => Err(UnknownRegion(text.to_string())),
+        }
+    }
+}
+
+impl TryFrom<String> for Region {
+    type Error = UnknownRegion;
+
+    fn try_from(text: String) -> Result<Self, Self::Error> {
+        text.parse()
+    }
+}
+
+impl From<Region> for String {
+    fn from(region: Region) -> Self {
+        region.to_string()
+    }
+}
+
+impl fmt::Display for Region {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self.console() {
+            Region::Pal => "pal",
+            Region::Dendy => "dendy",
+            _ => "ntsc",
+        })
+    }
+}
+
+impl Region {
+    // The console to run on; a game for either gets an NTSC one.
+    pub fn console(self) -> Region {
+        match self {
+            Region::Multi => Region::Ntsc,
+            region => region,
+        }
+    }
+
+    pub fn cpu_clock(self) -> f64 {
+        match self.console() {
+            Region::Pal => PAL_CPU_CLOCK,
+            Region::Dendy => DENDY_CPU_CLOCK,
+            _ => NTSC_CPU_CLOCK,
+        }
+    }
+
+    pub fn frame_rate(self) -> f64 {
+        match self.console() {
+            Region::Pal | Region::Dendy => PAL_FRAME_RATE,
+            _ => NTSC_FRAME_RATE,
+        }
+    }
+
+    pub fn scanlines(self) -> u16 {
+        match self.console() {
+            Region::Pal | Region::Dendy => 312,
+            _ => 262,
+        }
+    }
+
+    pub fn vblank_scanline(self) -> u16 {
+        match self.console() {
+            Region::Dendy => 291,
+            _ => 241,
+        }
+    }
+
+    pub fn skips_odd_dot(self) -> bool {
+        self.console() == Region::Ntsc
+    }
+
+    // Whether the APU runs from the PAL tables.
+    pub fn pal_apu(self) -> bool {
+        self.console() == Region::Pal
+    }
+
+    // PPU dots in the CPU cycle at `phase`, which counts from 0 to
+    // PAL_DOT_PHASES - 1 on PAL and stays at 0 elsewhere.
+    pub fn ppu_dots(self, phase: u8) -> u8 {
+        match self.console() {
+            Region::Pal if phase == PAL_DOT_PHASES - 1 => 4,
+            _ => 3,
+        }
+    }
+
+    // CPU cycles in `lines` scanlines, rounded down.
+    pub fn scanline_cycles(self, lines: u16) -> u32 {
+        let dots: u32 = (0..PAL_DOT_PHASES)
+            .map(|phase| self.ppu_dots(phase) as u32)
+            .sum();
+        lines as u32 * DOTS_PER_SCANLINE as u32 * PAL_DOT_PHASES as u32 / dots
+    }
+
+    // A console from the country tags in a file name, if it has any.
+    pub fn from_file_name(name: &str) -> Option<Region> {
+        let (mut ntsc, mut pal, mut dendy) = (false, false, false);
+        let tags = name
+            .split(['(', '[', ')', ']'])
+            .skip(1)
+            .step_by(2)
+            .flat_map(|group| group.split(','));
+        for tag in tags {
+            match tag.trim().to_ascii_lowercase().as_str() {
+                "u" | "usa" | "j" | "japan" | "ntsc" | "canada" | "korea" | "brazil" => ntsc = true,
+                "e" | "europe" | "pal" | "a" | "australia" | "g" | "germany" | "f" | "france"
+                | "s" | "spain" | "i" | "italy" | "sw" | "sweden" | "uk" | "netherlands"
+                | "scandinavia" => pal = true,
+                "r" | "russia" | "dendy" => dendy = true,
+                "ju" => ntsc = true,
+                "w" | "world" | "ue" | "jue" => (ntsc, pal) = (true, true),
+                _ => {}
+            }
+        }
+        match (ntsc, pal, dendy) {
+            (_, _, true) => Some(Region::Dendy),
+            (true, true, _) => Some(Region::Multi),
+            (true, false, _) => Some(Region::Ntsc),
+            (false, true, _) => Some(Region::Pal),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_frame_rates_follow_from_clocks() {
+        for region in [Region::Ntsc, Region::Pal, Region::Dendy] {
+            let dots: u32 = (0..PAL_DOT_PHASES)
+                .map(|phase| region.ppu_dots(phase) as u32)
+                .sum();
+            let dots_per_cycle = dots as f64 / PAL_DOT_PHASES as f64;
+            let frame = region.scanlines() as f64 * 341.0;
+            let rate = region.cpu_clock() * dots_per_cycle / frame;
+            // NTSC's odd frames are a dot short
+            assert!(
+                (rate - region.frame_rate()).abs() < 0.01,
+                "{:?} {}",
+                region,
+                rate
+            );
+        }
+        assert_eq!(Region::Multi.console(), Region::Ntsc);
+    }
+
+    #[test]
+    fn test_file_name_tags() {
+        let guess = Region::from_file_name;
+        assert_eq!(guess("Super Mario Bros. (Europe).nes"), Some(Region::Pal));
+        assert_eq!(guess("Zelda (E) [!].nes"), Some(Region::Pal));
+        assert_eq!(guess("Contra (USA).nes"), Some(Region::Ntsc));
+        assert_eq!(guess("Tetris (USA, Europe).nes"), Some(Region::Multi));
+        assert_eq!(guess("Battle City (Russia) (Unl).nes"), Some(Region::Dendy));
+        assert_eq!(
+            guess("Eliminator Boat Duel (World).nes"),
+            Some(Region::Multi)
+        );
+        assert_eq!(guess("homebrew.nes"), None);
+        // not a tag
+        assert_eq!(guess("Europe.nes"), None);
+    }
+
+    #[test]
+    fn test_names_round_trip() {
+        for region in [Region::Ntsc, Region::Pal, Region::Dendy] {
+            assert_eq!(region.to_string().parse(), Ok(region));
+        }
+        assert_eq!("PAL".parse(), Ok(Region::Pal));
+        assert!("secam".parse::<Region>().is_err());
+    }
+}
@@ -0,0 +1,678 @@
+// Remote control over WebSocket, for bots, test scripts and web dashboards
+// that would rather not link against the crate. Messages are JSON-RPC 2.0
+// text:
+//
+//     -> {"jsonrpc": "2.0", "id": 1, "method": "read_memory",
+//         "params": {"addr": 117, "len": 2}}
+//     <- {"jsonrpc": "2.0", "id": 1, "result": [9, 0]}
+//
+// The methods are status, pause, resume, step {frames}, reset,
+// read_memory {space, addr, len}, write_memory {space, addr, values},
+// set_input {player, buttons}, frame, screenshot and subscribe or
+// unsubscribe {events}. Spaces are cpu (the default), ppu, oam and
+// prg_ram; buttons are names as in the key bindings, or null to hand the
+// pad back to the player. Subscribers get notifications without an id:
+// "frame" {frame} after every emulated frame, "input" {frame, joypads,
+// zappers} with the input it ran with, and "pause" {paused} when that
+// changes.
+//
+// Each connection has a thread reading it, which passes requests to the
+// session through a channel; the session answers them between frames,
+// when the machine is still. The server has no authentication, so it
+// should only listen on localhost, as it does unless told otherwise.
+
+pub mod json;
+pub mod websocket;
+
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{self, BufReader};
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread;
+use std::time::Duration;
+
+use crate::debugger::memory::MemorySpace;
+use crate::input::joypad::JoypadButton;
+use crate::input::keymap::{BUTTONS, MAX_PLAYERS};
+use crate::input::InputState;
+
+use self::json::Json;
+
+pub const DEFAULT_ADDRESS: &str = "127.0.0.1:6502";
+// a client that can't take a message this long is dropped
+const WRITE_TIMEOUT: Duration = Duration::from_secs(1);
+const MAX_STEP: u64 = 3600;
+
+// the JSON-RPC error codes
+pub const PARSE_ERROR: i64 = -32700;
+pub const INVALID_REQUEST: i64 = -32600;
+pub const METHOD_NOT_FOUND: i64 = -32601;
+pub const INVALID_PARAMS: i64 = -32602;
+// the launcher is up and there's no game to act on
+pub const NO_GAME: i64 = -32000;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RpcError {
+    pub code: i64,
+    pub message: String,
+}
+
+impl RpcError {
+    pub fn new(code: i64, message: impl Into<String>) -> Self {
+        RpcError {
+            code,
+            message: message.into(),
+        }
+    }
+
+    fn params(message: impl Into<String>) -> Self {
+        Self::new(INVALID_PARAMS, message)
+    }
+}
+
+impl fmt::Display for RpcError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} ({})", self.message, self.code)
+    }
+}
+
+impl std::error::Error for RpcError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    Frame,
+    Input,
+    Pause,
+}
+
+impl Event {
+    pub fn name(self) -> &'static str {
+        match self {
+            Event::Frame => "frame",
+            Event::Input => "input",
+            Event::Pause => "pause",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        [Event::Frame, Event::Input, Event::Pause]
+            .into_iter()
+            .find(|event| event.name() == name)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Request {
+    Status,
+    Pause,
+    Resume,
+    // runs frames at once and stays paused
+    Step {
+        frames: u32,
+    },
+    Reset,
+    ReadMemory {
+        space: MemorySpace,
+        addr: u16,
+        len: usize,
+    },
+    WriteMemory {
+        space: MemorySpace,
+        addr: u16,
+        values: Vec<u8>,
+    },
+    // held until set again; None gives the pad back to the player
+    SetInput {
+        player: usize,
+        buttons: Option<JoypadButton>,
+    },
+    // the raw picture, 256x240 RGB in base64
+    Frame,
+    // the same as a PNG in base64
+    Screenshot,
+}
+
+fn space_from_name(name: &str) -> Option<MemorySpace> {
+    match name {
+        "cpu" => Some(MemorySpace::Cpu),
+        "ppu" => Some(MemorySpace::Ppu),
+        "oam" => Some(MemorySpace::Oam),
+        "prg_ram" => Some(MemorySpace::PrgRam),
+        _ => None,
+    }
+}
+
+// A whole number no bigger than `max`, or `default` if it's left out.
+fn number(params: &Json, key: &str, default: Option<u64>, max: u64) -> Result<u64, RpcError> {
+    match params.get(key) {
+        None | Some(Json::Null) => {
+            default.ok_or_else(|| RpcError::params(format!("{} is missing", key)))
+        }
+        Some(value) => value
+            .as_u64()
+            .filter(|&n| n <= max)
+            .ok_or_else(|| RpcError::params(format!("{} must be a number up to {}", key, max))),
+    }
+}
+
+fn space(params: &Json) -> Result<MemorySpace, RpcError> {
+    match params.get("space") {
+        None | Some(Json::Null) => Ok(MemorySpace::Cpu),
+        Some(value) => value
+            .as_str()
+            .and_then(space_from_name)
+            .ok_or_else(|| RpcError::params("space must be cpu, ppu, oam or prg_ram")),
+    }
+}
+
+fn buttons(value: &Json) -> Result<Option<JoypadButton>, RpcError> {
+    if value.is_null() {
+        return Ok(None);
+    }
+    let names = value
+        .as_array()
+        .ok_or_else(|| RpcError::params("buttons must be a list of names or null"))?;
+    names
+        .iter()
+        .try_fold(JoypadButton::empty(), |held, name| {
+            BUTTONS
+                .iter()
+                .find(|(_, button)| Some(*button) == name.as_str())
+                .map(|&(button, _)| held | button)
+                .ok_or_else(|| RpcError::params(format!("{} isn't a button", name)))
+        })
+        .map(Some)
+}
+
+// The "input" notification: each player's buttons by name, null with no
+// joypad, and each port's gun as {x, y, trigger}, x and y null off the
+// screen.
+pub fn input_json(frame: u64, state: &InputState) -> Json {
+    let joypads = state.joypads.iter().map(|buttons| match buttons {
+        Some(buttons) => Json::from(
+            BUTTONS
+                .iter()
+                .filter(|(button, _)| buttons.contains(*button))
+                .map(|&(_, name)| Json::from(name))
+                .collect::<Vec<_>>(),
+        ),
+        None => Json::Null,
+    });
+    let zappers = state.zappers.iter().map(|zapper| match zapper {
+        Some(zapper) => Json::object([
+            ("x", zapper.cursor.map(|(x, _)| x as u64).into()),
+            ("y", zapper.cursor.map(|(_, y)| y as u64).into()),
+            ("trigger", zapper.trigger.into()),
+        ]),
+        None => Json::Null,
+    });
+    Json::object([
+        ("frame", frame.into()),
+        ("joypads", Json::Array(joypads.collect())),
+        ("zappers", Json::Array(zappers.collect())),
+    ])
+}
+
+impl Request {
+    pub fn parse(method: &str, params: &Json) -> Result<Self, RpcError> {
+        Ok(match method {
+            "status" => Request::Status,
+            "pause" => Request::Pause,
+            "resume" => Request::Resume,
+            "step" => Request::Step {
+                frames: number(params, "frames", Some(1), MAX_STEP)? as u32,
+            },
+            "reset" => Request::Reset,
+            "read_memory" => Request::ReadMemory {
+                space: space(params)?,
+                addr: number(params, "addr", None, 0xFFFF)? as u16,
+                len: number(params, "len", Some(1), 0x10000)? as usize,
+            },
+            "write_memory" => Request::WriteMemory {
+                space: space(params)?,
+                addr: number(params, "addr", None, 0xFFFF)? as u16,
+                values: params
+                    .get("values")
+                    .and_then(Json::as_array)
+                    .and_then(|values| {
+                        values
+                            .iter()
+                            .map(|value| value.as_u64().filter(|&n| n <= 0xFF).map(|n| n as u8))
+                            .collect()
+                    })
+                    .ok_or_else(|| RpcError::params("values must be a list of bytes"))?,
+            },
+            "set_input" => Request::SetInput {
+                player: number(params, "player", Some(0), MAX_PLAYERS as u64 - 1)? as usize,
+                buttons: buttons(params.get("buttons").unwrap_or(&Json::Null))?,
+            },
+            "frame" => Request::Frame,
+            "screenshot" => Request::Screenshot,
+            _ => {
+                return Err(RpcError::new(
+                    METHOD_NOT_FOUND,
+                    format!("no method {}", method),
+                ))
+            }
+        })
+    }
+}
+
+// A request waiting for its answer.
+#[derive(Debug)]
+pub struct Call {
+    client: usize,
+    // None for a notification, which gets no answer
+    id: Option<Json>,
+    pub request: Request,
+}
+
+struct Client {
+    stream: TcpStream,
+    events: Vec<Event>,
+}
+
+type Clients = Arc<Mutex<HashMap<usize, Client>>>;
+
+fn lock(clients: &Clients) -> MutexGuard<'_, HashMap<usize, Client>> {
+    clients
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+// Writes to one client, dropping it if that fails.
+fn send(clients: &Clients, client: usize, opcode: u8, payload: &[u8]) {
+    let mut clients = lock(clients);
+    let Some(entry) = clients.get_mut(&client) else {
+        return;
+    };
+    if websocket::write_frame(&mut entry.stream, opcode, payload, None).is_err() {
+        let _ = entry.stream.shutdown(Shutdown::Both);
+        clients.remove(&client);
+    }
+}
+
+fn reply_json(id: Json, outcome: Result<Json, RpcError>) -> Json {
+    let (key, value) = match outcome {
+        Ok(result) => ("result", result),
+        Err(err) => (
+            "error",
+            Json::object([
+                ("code", Json::from(err.code)),
+                ("message", Json::from(err.message)),
+            ]),
+        ),
+    };
+    Json::object([("jsonrpc", Json::from("2.0")), ("id", id), (key, value)])
+}
+
+pub struct RemoteServer {
+    addr: SocketAddr,
+    requests: Receiver<(usize, String)>,
+    clients: Clients,
+    stopped: Arc<AtomicBool>,
+}
+
+impl RemoteServer {
+    // Starts listening; use port 0 to have one picked.
+    pub fn bind(addr: impl ToSocketAddrs) -> io::Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        let addr = listener.local_addr()?;
+        let (sender, requests) = mpsc::channel();
+        let clients = Clients::default();
+        let stopped = Arc::new(AtomicBool::new(false));
+        let (shared, stop) = (clients.clone(), stopped.clone());
+        thread::spawn(move || {
+            for (id, stream) in listener.incoming().enumerate() {
+                if stop.load(Ordering::Relaxed) {
+                    break;
+                }
+                let Ok(stream) = stream else {
+                    continue;
+                };
+                let (clients, sender) = (shared.clone(), sender.clone());
+                thread::spawn(move || {
+                    if let Err(err) = serve(id, stream, &clients, &sender) {
+                        eprintln!("remote client {}: {}", id, err);
+                    }
+                    lock(&clients).remove(&id);
+                });
+            }
+        });
+        Ok(RemoteServer {
+            addr,
+            requests,
+            clients,
+            stopped,
+        })
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    // The requests that came in since the last call. Ones that aren't
+    // valid JSON-RPC are answered here with the error, and so are
+    // subscriptions, which need nothing from the emulator.
+    pub fn poll(&self) -> Vec<Call> {
+        let mut calls = Vec::new();
+        while let Ok((client, text)) = self.requests.try_recv() {
+            let message = match Json::parse(&text) {
+                Ok(message) => message,
+                Err(err) => {
+                    let error = RpcError::new(PARSE_ERROR, err.to_string());
+                    self.send(client, &reply_json(Json::Null, Err(error)));
+                    continue;
+                }
+            };
+            let id = message.get("id").cloned();
+            let params = message.get("params").unwrap_or(&Json::Null);
+            let request = match message.get("method").and_then(Json::as_str) {
+                Some(method @ ("subscribe" | "unsubscribe")) => {
+                    let outcome = self.subscribe(client, params, method == "subscribe");
+                    if let Some(id) = id {
+                        self.send(client, &reply_json(id, outcome));
+                    }
+                    continue;
+                }
+                Some(method) => Request::parse(method, params),
+                None => Err(RpcError::new(INVALID_REQUEST, "no method")),
+            };
+            match request {
+                Ok(request) => calls.push(Call {
+                    client,
+                    id,
+                    request,
+                }),
+                Err(err) => {
+                    if let Some(id) = id {
+                        self.send(client, &reply_json(id, Err(err)));
+                    }
+                }
+            }
+        }
+        calls
+    }
+
+    fn subscribe(&self, client: usize, params: &Json, on: bool) -> Result<Json, RpcError> {
+        let events = params
+            .get("events")
+            .and_then(Json::as_array)
+            .and_then(|names| {
+                names
+                    .iter()
+                    .map(|name| name.as_str().and_then(Event::from_name))
+                    .collect::<Option<Vec<_>>>()
+            })
+            .ok_or_else(|| RpcError::params("events must be a list of frame or pause"))?;
+        let mut clients = lock(&self.clients);
+        let Some(entry) = clients.get_mut(&client) else {
+            return Ok(Json::Null);
+        };
+        entry.events.retain(|event| !events.contains(event));
+        if on {
+            entry.events.extend(events);
+        }
+        Ok(Json::Null)
+    }
+
+    fn send(&self, client: usize, message: &Json) {
+        send(
+            &self.clients,
+            client,
+            websocket::TEXT,
+            message.to_string().as_bytes(),
+        );
+    }
+
+    pub fn reply(&self, call: Call, outcome: Result<Json, RpcError>) {
+        if let Some(id) = call.id {
+            self.send(call.client, &reply_json(id, outcome));
+        }
+    }
+
+    pub fn has_subscribers(&self, event: Event) -> bool {
+        lock(&self.clients)
+            .values()
+            .any(|client| client.events.contains(&event))
+    }
+
+    // To everyone subscribed to `event`.
+    pub fn notify(&self, event: Event, params: Json) {
+        let message = Json::object([
+            ("jsonrpc", Json::from("2.0")),
+            ("method", Json::from(event.name())),
+            ("params", params),
+        ])
+        .to_string();
+        let subscribers: Vec<usize> = lock(&self.clients)
+            .iter()
+            .filter(|(_, client)| client.events.contains(&event))
+            .map(|(&id, _)| id)
+            .collect();
+        for client in subscribers {
+            send(&self.clients, client, websocket::TEXT, message.as_bytes());
+        }
+    }
+}
+
+// Hangs up on everyone and wakes the listener so it sees it's stopped.
+impl Drop for RemoteServer {
+    fn drop(&mut self) {
+        self.stopped.store(true, Ordering::Relaxed);
+        for client in lock(&self.clients).values() {
+            let _ = client.stream.shutdown(Shutdown::Both);
+        }
+        let _ = TcpStream::connect(self.addr);
+    }
+}
+
+fn serve(
+    id: usize,
+    stream: TcpStream,
+    clients: &Clients,
+    requests: &Sender<(usize, String)>,
+) -> Result<(), websocket::WebSocketError> {
+    stream.set_write_timeout(Some(WRITE_TIMEOUT))?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut writer = stream.try_clone()?;
+    websocket::handshake(&mut reader, &mut writer)?;
+    lock(clients).insert(
+        id,
+        Client {
+            stream,
+            events: Vec::new(),
+        },
+    );
+    loop {
+        let pong = |payload: &[u8]| {
+            send(clients, id, websocket::PONG, payload);
+            Ok(())
+        };
+        match websocket::read_message(&mut reader, pong)? {
+            Some(text) => {
+                // the server's gone
+                if requests.send((id, text)).is_err() {
+                    return Ok(());
+                }
+            }
+            None => {
+                send(clients, id, websocket::CLOSE, &[]);
+                return Ok(());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::{BufRead, Read, Write};
+    use std::time::Instant;
+
+    #[test]
+    fn test_parses_requests() {
+        let params = Json::parse(r#"{"space":"oam","addr":16,"len":4}"#).unwrap();
+        assert_eq!(
+            Request::parse("read_memory", &params),
+            Ok(Request::ReadMemory {
+                space: MemorySpace::Oam,
+                addr: 16,
+                len: 4,
+            })
+        );
+        let params = Json::parse(r#"{"player":1,"buttons":["a","start"]}"#).unwrap();
+        assert_eq!(
+            Request::parse("set_input", &params),
+            Ok(Request::SetInput {
+                player: 1,
+                buttons: Some(JoypadButton::BUTTON_A | JoypadButton::START),
+            })
+        );
+        assert_eq!(
+            Request::parse("step", &Json::Null),
+            Ok(Request::Step { frames: 1 })
+        );
+
+        let code = |method, params: &str| {
+            Request::parse(method, &Json::parse(params).unwrap())
+                .unwrap_err()
+                .code
+        };
+        assert_eq!(code("read_memory", "{}"), INVALID_PARAMS);
+        assert_eq!(code("read_memory", r#"{"addr":65536}"#), INVALID_PARAMS);
+        assert_eq!(
+            code("write_memory", r#"{"addr":0,"values":[256]}"#),
+            INVALID_PARAMS
+        );
+        assert_eq!(
+            code("set_input", r#"{"buttons":["turbo"]}"#),
+            INVALID_PARAMS
+        );
+        assert_eq!(code("set_input", r#"{"player":4}"#), INVALID_PARAMS);
+        assert_eq!(code("fly", "{}"), METHOD_NOT_FOUND);
+    }
+
+    struct TestClient(BufReader<TcpStream>);
+
+    impl TestClient {
+        fn connect(addr: SocketAddr) -> Self {
+            let mut stream = TcpStream::connect(addr).unwrap();
+            stream
+                .set_read_timeout(Some(Duration::from_secs(5)))
+                .unwrap();
+            stream
+                .write_all(
+                    b"GET / HTTP/1.1\r\nHost: localhost\r\nUpgrade: websocket\r\n\
+                      Connection: Upgrade\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\
+                      Sec-WebSocket-Version: 13\r\n\r\n",
+                )
+                .unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            assert!(line.starts_with("HTTP/1.1 101"), "{}", line);
+            while line != "\r\n" {
+                line.clear();
+                reader.read_line(&mut line).unwrap();
+            }
+            TestClient(reader)
+        }
+
+        fn send(&mut self, text: &str) {
+            let stream = self.0.get_mut();
+            websocket::write_frame(stream, websocket::TEXT, text.as_bytes(), Some([9, 8, 7, 6]))
+                .unwrap();
+        }
+
+        fn receive(&mut self) -> Json {
+            let mut head = [0; 2];
+            self.0.read_exact(&mut head).unwrap();
+            assert_eq!(head[0], 0x80 | websocket::TEXT);
+            let len = match head[1] {
+                126 => {
+                    let mut len = [0; 2];
+                    self.0.read_exact(&mut len).unwrap();
+                    u16::from_be_bytes(len) as usize
+                }
+                127 => {
+                    let mut len = [0; 8];
+                    self.0.read_exact(&mut len).unwrap();
+                    u64::from_be_bytes(len) as usize
+                }
+                len => len as usize,
+            };
+            let mut payload = vec![0; len];
+            self.0.read_exact(&mut payload).unwrap();
+            Json::parse(std::str::from_utf8(&payload).unwrap()).unwrap()
+        }
+    }
+
+    #[test]
+    fn test_input_notification() {
+        use crate::input::ZapperState;
+        let mut state = InputState::default();
+        state.joypads[0] = Some(JoypadButton::BUTTON_A | JoypadButton::LEFT);
+        state.zappers[1] = Some(ZapperState {
+            cursor: None,
+            trigger: true,
+        });
+        assert_eq!(
+            input_json(7, &state).to_string(),
+            r#"{"frame":7,"joypads":[["a","left"],null,null,null],"zappers":[null,{"x":null,"y":null,"trigger":true}]}"#
+        );
+        assert_eq!(Event::from_name("input"), Some(Event::Input));
+    }
+
+    // The reading thread hands requests over in its own time.
+    fn wait_for_calls(server: &RemoteServer) -> Vec<Call> {
+        let start = Instant::now();
+        loop {
+            let calls = server.poll();
+            if !calls.is_empty() || start.elapsed() > Duration::from_secs(5) {
+                return calls;
+            }
+            thread::sleep(Duration::from_millis(5));
+        }
+    }
+
+    #[test]
+    fn test_serves_requests_and_events() {
+        let server = RemoteServer::bind("127.0.0.1:0").unwrap();
+        let mut client = TestClient::connect(server.local_addr());
+
+        client.send("{oops");
+        client.send(
+            r#"{"jsonrpc":"2.0","id":"a","method":"subscribe","params":{"events":["frame"]}}"#,
+        );
+        client.send(r#"{"jsonrpc":"2.0","id":2,"method":"step","params":{"frames":3}}"#);
+        let calls = wait_for_calls(&server);
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].request, Request::Step { frames: 3 });
+        assert_eq!(
+            client.receive().get("error").and_then(|e| e.get("code")),
+            Some(&Json::from(PARSE_ERROR))
+        );
+        assert_eq!(
+            client.receive().to_string(),
+            r#"{"jsonrpc":"2.0","id":"a","result":null}"#
+        );
+
+        assert!(server.has_subscribers(Event::Frame));
+        assert!(!server.has_subscribers(Event::Pause));
+        server.notify(Event::Frame, Json::object([("frame", Json::from(1u64))]));
+        let call = calls.into_iter().next().unwrap();
+        server.reply(call, Ok(Json::from(true)));
+        assert_eq!(
+            client.receive().to_string(),
+            r#"{"jsonrpc":"2.0","method":"frame","params":{"frame":1}}"#
+        );
+        assert_eq!(
+            client.receive().to_string(),
+            r#"{"jsonrpc":"2.0","id":2,"result":true}"#
+        );
+    }
+}
@@ -0,0 +1,311 @@
+// The server half of RFC 6455, as little of it as a local tool needs: the
+// HTTP upgrade, then text messages each way. Client frames come masked
+// and server frames go plain; fragments are joined, pings answered, and
+// a close is echoed before the connection ends. Binary messages and
+// extensions aren't offered.
+
+use std::fmt;
+use std::io::{self, BufRead, Read, Write};
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+
+const GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+// longer messages end the connection
+pub const MAX_MESSAGE: usize = 1 << 20;
+
+const CONTINUATION: u8 = 0x0;
+pub(crate) const TEXT: u8 = 0x1;
+const BINARY: u8 = 0x2;
+pub(crate) const CLOSE: u8 = 0x8;
+const PING: u8 = 0x9;
+pub(crate) const PONG: u8 = 0xA;
+
+#[derive(Debug)]
+pub enum WebSocketError {
+    Io(io::Error),
+    // not an upgrade request, or a frame breaking the rules
+    Protocol(&'static str),
+}
+
+impl fmt::Display for WebSocketError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            WebSocketError::Io(err) => write!(f, "{}", err),
+            WebSocketError::Protocol(problem) => write!(f, "websocket: {}", problem),
+        }
+    }
+}
+
+impl std::error::Error for WebSocketError {}
+
+impl From<io::Error> for WebSocketError {
+    fn from(err: io::Error) -> Self {
+        WebSocketError::Io(err)
+    }
+}
+
+pub(crate) fn sha1(data: &[u8]) -> [u8; 20] {
+    let mut h: [u32; 5] = [
+        0x6745_2301,
+        0xEFCD_AB89,
+        0x98BA_DCFE,
+        0x1032_5476,
+        0xC3D2_E1F0,
+    ];
+    let mut padded = data.to_vec();
+    padded.push(0x80);
+    while padded.len() % 64 != 56 {
+        padded.push(0);
+    }
+    padded.extend_from_slice(&((data.len() as u64) * 8).to_be_bytes());
+    for block in padded.chunks(64) {
+        let mut w = [0u32; 80];
+        for (i, word) in block.chunks(4).enumerate() {
+            w[i] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+        }
+        for i in 16..80 {
+            w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+        }
+        let [mut a, mut b, mut c, mut d, mut e] = h;
+        for (i, &word) in w.iter().enumerate() {
+            let (f, k) = match i {
+                0..=19 => ((b & c) | (!b & d), 0x5A82_7999),
+                20..=39 => (b ^ c ^ d, 0x6ED9_EBA1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8F1B_BCDC),
+                _ => (b ^ c ^ d, 0xCA62_C1D6),
+            };
+            let next = a
+                .rotate_left(5)
+                .wrapping_add(f)
+                .wrapping_add(e)
+                .wrapping_add(k)
+                .wrapping_add(word);
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = next;
+        }
+        for (state, value) in h.iter_mut().zip([a, b, c, d, e]) {
+            *state = state.wrapping_add(value);
+        }
+    }
+    let mut digest = [0; 20];
+    for (out, word) in digest.chunks_mut(4).zip(h) {
+        out.copy_from_slice(&word.to_be_bytes());
+    }
+    digest
+}
+
+// The Sec-WebSocket-Accept for a client's key.
+pub(crate) fn accept_key(key: &str) -> String {
+    STANDARD.encode(sha1(format!("{}{}", key.trim(), GUID).as_bytes()))
+}
+
+// Reads the client's upgrade request and agrees to it. Anything else gets
+// a 400, as plain HTTP to the port would.
+pub fn handshake(reader: &mut impl BufRead, writer: &mut impl Write) -> Result<(), WebSocketError> {
+    let mut key = None;
+    let mut first = true;
+    loop {
+        let mut line = String::new();
+        if reader.take(8192).read_line(&mut line)? == 0 {
+            return Err(WebSocketError::Protocol(
+                "connection closed during handshake",
+            ));
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if first {
+            first = false;
+            if !line.starts_with("GET ") {
+                key = None;
+                break;
+            }
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("sec-websocket-key") {
+                key = Some(value.trim().to_string());
+            }
+        }
+    }
+    let Some(key) = key else {
+        writer.write_all(b"HTTP/1.1 400 Bad Request\r\nContent-Length: 0\r\n\r\n")?;
+        return Err(WebSocketError::Protocol("not a websocket upgrade"));
+    };
+    write!(
+        writer,
+        "HTTP/1.1 101 Switching Protocols\r\n\
+         Upgrade: websocket\r\n\
+         Connection: Upgrade\r\n\
+         Sec-WebSocket-Accept: {}\r\n\r\n",
+        accept_key(&key)
+    )?;
+    writer.flush()?;
+    Ok(())
+}
+
+// One frame; `mask` is for writing as a client, which only tests do.
+pub(crate) fn write_frame(
+    writer: &mut impl Write,
+    opcode: u8,
+    payload: &[u8],
+    mask: Option<[u8; 4]>,
+) -> io::Result<()> {
+    let mut frame = vec![0x80 | opcode];
+    let masked = if mask.is_some() { 0x80 } else { 0 };
+    match payload.len() {
+        len @ 0..=125 => frame.push(masked | len as u8),
+        len @ 126..=0xFFFF => {
+            frame.push(masked | 126);
+            frame.extend_from_slice(&(len as u16).to_be_bytes());
+        }
+        len => {
+            frame.push(masked | 127);
+            frame.extend_from_slice(&(len as u64).to_be_bytes());
+        }
+    }
+    match mask {
+        Some(mask) => {
+            frame.extend_from_slice(&mask);
+            frame.extend(payload.iter().zip(mask.iter().cycle()).map(|(b, m)| b ^ m));
+        }
+        None => frame.extend_from_slice(payload),
+    }
+    writer.write_all(&frame)?;
+    writer.flush()
+}
+
+pub fn write_text(writer: &mut impl Write, text: &str) -> io::Result<()> {
+    write_frame(writer, TEXT, text.as_bytes(), None)
+}
+
+// The next text message, fragments joined, or None once the client
+// closes. Pongs are skipped, and pings go to `ping` to be answered, as the
+// caller is the one that can write.
+pub fn read_message(
+    reader: &mut impl Read,
+    mut ping: impl FnMut(&[u8]) -> io::Result<()>,
+) -> Result<Option<String>, WebSocketError> {
+    let mut message: Option<Vec<u8>> = None;
+    loop {
+        let mut head = [0; 2];
+        reader.read_exact(&mut head)?;
+        let fin = head[0] & 0x80 != 0;
+        let opcode = head[0] & 0x0F;
+        if head[0] & 0x70 != 0 {
+            return Err(WebSocketError::Protocol("reserved bits set"));
+        }
+        if head[1] & 0x80 == 0 {
+            return Err(WebSocketError::Protocol("client frames must be masked"));
+        }
+        let len = match head[1] & 0x7F {
+            126 => {
+                let mut len = [0; 2];
+                reader.read_exact(&mut len)?;
+                u16::from_be_bytes(len) as u64
+            }
+            127 => {
+                let mut len = [0; 8];
+                reader.read_exact(&mut len)?;
+                u64::from_be_bytes(len)
+            }
+            len => len as u64,
+        };
+        let sofar = message.as_ref().map_or(0, Vec::len) as u64;
+        if len.saturating_add(sofar) > MAX_MESSAGE as u64 {
+            return Err(WebSocketError::Protocol("message too long"));
+        }
+        let mut mask = [0; 4];
+        reader.read_exact(&mut mask)?;
+        let mut payload = vec![0; len as usize];
+        reader.read_exact(&mut payload)?;
+        for (byte, m) in payload.iter_mut().zip(mask.iter().cycle()) {
+            *byte ^= m;
+        }
+
+        match opcode {
+            CLOSE => return Ok(None),
+            PING if fin => {
+                ping(&payload)?;
+                continue;
+            }
+            PONG if fin => continue,
+            TEXT if message.is_none() => message = Some(payload),
+            CONTINUATION if message.is_some() => {
+                message.as_mut().unwrap().extend_from_slice(&payload)
+            }
+            BINARY => return Err(WebSocketError::Protocol("binary messages aren't supported")),
+            _ => return Err(WebSocketError::Protocol("unexpected frame")),
+        }
+        if fin {
+            let text = String::from_utf8(message.take().unwrap_or_default())
+                .map_err(|_| WebSocketError::Protocol("text that isn't UTF-8"))?;
+            return Ok(Some(text));
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_handshake_answers_the_key() {
+        // the example from RFC 6455
+        assert_eq!(
+            accept_key("dGhlIHNhbXBsZSBub25jZQ=="),
+            "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+        );
+        let request = "GET /chat HTTP/1.1\r\nHost: localhost\r\nUpgrade: websocket\r\n\
+                       Connection: Upgrade\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\
+                       Sec-WebSocket-Version: 13\r\n\r\n";
+        let mut reply = Vec::new();
+        handshake(&mut Cursor::new(request), &mut reply).unwrap();
+        let reply = String::from_utf8(reply).unwrap();
+        assert!(reply.starts_with("HTTP/1.1 101"));
+        assert!(reply.contains("Sec-WebSocket-Accept: s3pPLMBiTxaQ9kYGzzhZRbK+xOo=\r\n"));
+
+        let mut reply = Vec::new();
+        let plain = "GET / HTTP/1.1\r\nHost: localhost\r\n\r\n";
+        assert!(handshake(&mut Cursor::new(plain), &mut reply).is_err());
+        assert!(reply.starts_with(b"HTTP/1.1 400"));
+    }
+
+    #[test]
+    fn test_reads_masked_fragments() {
+        let mut wire = Vec::new();
+        let mask = Some([1, 2, 3, 4]);
+        // "hel" + "lo", with a ping in between
+        wire.extend_from_slice(&[TEXT, 0x83, 1, 2, 3, 4, b'h' ^ 1, b'e' ^ 2, b'l' ^ 3]);
+        write_frame(&mut wire, PING, b"?", mask).unwrap();
+        write_frame(&mut wire, PONG, b"", mask).unwrap();
+        write_frame(&mut wire, CONTINUATION, b"lo", mask).unwrap();
+        let long = "x".repeat(300);
+        write_frame(&mut wire, TEXT, long.as_bytes(), mask).unwrap();
+        write_frame(&mut wire, CLOSE, b"", mask).unwrap();
+
+        let mut reader = Cursor::new(wire);
+        let mut pings = Vec::new();
+        let mut read = || {
+            read_message(&mut reader, |payload| {
+                pings.push(payload.to_vec());
+                Ok(())
+            })
+            .unwrap()
+        };
+        assert_eq!(read(), Some("hello".to_string()));
+        assert_eq!(read(), Some(long));
+        assert_eq!(read(), None);
+        assert_eq!(pings, [b"?"]);
+
+        let mut unmasked = Vec::new();
+        write_text(&mut unmasked, "hi").unwrap();
+        assert_eq!(unmasked, [0x81, 2, b'h', b'i']);
+        assert!(read_message(&mut Cursor::new(unmasked), |_| Ok(())).is_err());
+    }
+}
@@ -0,0 +1,266 @@
+// The last few seconds of play, always kept so they can be saved as an
+// animated GIF when something worth sharing has just happened. Frames are
+// stored as indexes into the emulator's 64-colour palette, which is also
+// the GIF's one palette, so there's no quantizing to speak of: a pixel is
+// looked up by its colour, and only colours that aren't in the palette at
+// all get the nearest one.
+//
+// Every other frame is kept. GIF delays are in hundredths of a second and
+// 30fps is about as fast as viewers reliably play them. Each GIF frame
+// only covers the part of the screen that changed, and frames without any
+// change stretch the previous one, which keeps files small.
+
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use crate::render::frame::Frame;
+use crate::render::palette::Palette;
+
+type Rgb = (u8, u8, u8);
+
+const FRAME_STEP: usize = 2;
+
+pub struct ClipRecorder {
+    palette: Palette,
+    frame_rate: f64,
+    frames: VecDeque<Vec<u8>>,
+    capacity: usize,
+    // frames seen, for keeping every FRAME_STEP-th
+    seen: usize,
+    last: (Rgb, u8),
+}
+
+impl ClipRecorder {
+    pub fn new(palette: Palette, seconds: f64, frame_rate: f64) -> Self {
+        ClipRecorder {
+            palette,
+            frame_rate,
+            frames: VecDeque::new(),
+            capacity: (seconds * frame_rate / FRAME_STEP as f64).ceil() as usize,
+            seen: 0,
+            last: (palette.colours[0], 0),
+        }
+    }
+
+    pub fn push(&mut self, frame: &Frame) {
+        self.seen += 1;
+        if self.capacity == 0 || !(self.seen - 1).is_multiple_of(FRAME_STEP) {
+            return;
+        }
+        let mut indexes = if self.frames.len() == self.capacity {
+            self.frames.pop_front().unwrap()
+        } else {
+            Vec::with_capacity(Frame::WIDTH * Frame::HEIGHT)
+        };
+        indexes.clear();
+        for rgb in frame.data.chunks_exact(3) {
+            indexes.push(self.index_of((rgb[0], rgb[1], rgb[2])));
+        }
+        self.frames.push_back(indexes);
+    }
+
+    // Neighbouring pixels are mostly the same colour, so the last lookup
+    // is usually the answer.
+    fn index_of(&mut self, rgb: Rgb) -> u8 {
+        if self.last.0 == rgb {
+            return self.last.1;
+        }
+        let colours = &self.palette.colours;
+        let index = colours
+            .iter()
+            .position(|&colour| colour == rgb)
+            .unwrap_or_else(|| {
+                let distance = |(r, g, b): Rgb| {
+                    let d = |x: u8, y: u8| (x as i32 - y as i32).pow(2);
+                    d(r, rgb.0) + d(g, rgb.1) + d(b, rgb.2)
+                };
+                (0..colours.len())
+                    .min_by_key(|&i| distance(colours[i]))
+                    .unwrap()
+            }) as u8;
+        self.last = (rgb, index);
+        index
+    }
+
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    // A copy of what's held, to save while play goes on.
+    pub fn clip(&self) -> Clip {
+        Clip {
+            palette: self.palette,
+            frame_delay: FRAME_STEP as f64 * 100.0 / self.frame_rate,
+            frames: self.frames.iter().cloned().collect(),
+        }
+    }
+}
+
+pub struct Clip {
+    palette: Palette,
+    // in hundredths of a second
+    frame_delay: f64,
+    frames: Vec<Vec<u8>>,
+}
+
+// The smallest rectangle holding every pixel that differs, as
+// (left, top, width, height); None if nothing does.
+fn changed_rect(before: &[u8], after: &[u8]) -> Option<(usize, usize, usize, usize)> {
+    let row = |y: usize| y * Frame::WIDTH..(y + 1) * Frame::WIDTH;
+    let rows_differ = |y: usize| before[row(y)] != after[row(y)];
+    let top = (0..Frame::HEIGHT).find(|&y| rows_differ(y))?;
+    let bottom = (0..Frame::HEIGHT).rfind(|&y| rows_differ(y))?;
+    let column_differs = |x: usize| {
+        (top..=bottom).any(|y| before[y * Frame::WIDTH + x] != after[y * Frame::WIDTH + x])
+    };
+    let left = (0..Frame::WIDTH).find(|&x| column_differs(x))?;
+    let right = (0..Frame::WIDTH).rfind(|&x| column_differs(x))?;
+    Some((left, top, right - left + 1, bottom - top + 1))
+}
+
+impl Clip {
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    pub fn encode<W: Write>(&self, out: W) -> io::Result<()> {
+        let colours: Vec<u8> = self
+            .palette
+            .colours
+            .iter()
+            .flat_map(|&(r, g, b)| [r, g, b])
+            .collect();
+        let mut encoder =
+            gif::Encoder::new(out, Frame::WIDTH as u16, Frame::HEIGHT as u16, &colours)
+                .map_err(io::Error::other)?;
+        encoder
+            .set_repeat(gif::Repeat::Infinite)
+            .map_err(io::Error::other)?;
+
+        // held back until the next frame shows how long it stays up
+        let mut pending: Option<gif::Frame> = None;
+        let mut previous: Option<&[u8]> = None;
+        for (i, indexes) in self.frames.iter().enumerate() {
+            // delays that add up to the real running time
+            let start = (i as f64 * self.frame_delay).round();
+            let end = ((i + 1) as f64 * self.frame_delay).round();
+            let delay = (end - start) as u16;
+
+            let rect = match previous {
+                None => Some((0, 0, Frame::WIDTH, Frame::HEIGHT)),
+                Some(before) => changed_rect(before, indexes),
+            };
+            previous = Some(indexes);
+            let Some((left, top, width, height)) = rect else {
+                if let Some(frame) = pending.as_mut() {
+                    frame.delay = frame.delay.saturating_add(delay);
+                }
+                continue;
+            };
+
+            let mut buffer = Vec::with_capacity(width * height);
+            for y in top..top + height {
+                let start = y * Frame::WIDTH + left;
+                buffer.extend_from_slice(&indexes[start..start + width]);
+            }
+            let mut frame =
+                gif::Frame::from_indexed_pixels(width as u16, height as u16, buffer, None);
+            frame.left = left as u16;
+            frame.top = top as u16;
+            frame.delay = delay;
+            if let Some(done) = pending.replace(frame) {
+                encoder.write_frame(&done).map_err(io::Error::other)?;
+            }
+        }
+        if let Some(done) = pending {
+            encoder.write_frame(&done).map_err(io::Error::other)?;
+        }
+        encoder.into_inner().map_err(io::Error::other)?.flush()
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        self.encode(BufWriter::new(File::create(path)?))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::Cursor;
+
+    fn filled(rgb: Rgb) -> Frame {
+        let mut frame = Frame::new();
+        for y in 0..Frame::HEIGHT {
+            for x in 0..Frame::WIDTH {
+                frame.set_pixel(x, y, rgb);
+            }
+        }
+        frame
+    }
+
+    #[test]
+    fn test_keeps_every_other_frame_up_to_capacity() {
+        let palette = Palette::default();
+        // 0.1s at 60fps is 3 kept frames
+        let mut clip = ClipRecorder::new(palette, 0.1, 60.0);
+        for i in 0..10 {
+            clip.push(&filled(palette.colours[i]));
+        }
+        assert_eq!(clip.len(), 3);
+        // frames 4, 6 and 8
+        assert_eq!(clip.frames[0][0], 4);
+        assert_eq!(clip.frames[2][Frame::WIDTH * Frame::HEIGHT - 1], 8);
+
+        // off-palette colours get the nearest entry
+        assert_eq!(clip.index_of((0xFE, 0xFF, 0xFF)), 0x20);
+        let mut off = ClipRecorder::new(palette, 0.0, 60.0);
+        off.push(&Frame::new());
+        assert!(off.is_empty());
+    }
+
+    #[test]
+    fn test_gif_shows_changes_only() {
+        let palette = Palette::default();
+        let mut recorder = ClipRecorder::new(palette, 1.0, 60.0);
+        let mut frame = filled(palette.colours[0x0D]);
+        recorder.push(&frame);
+        recorder.push(&frame);
+        // unchanged
+        recorder.push(&frame);
+        recorder.push(&frame);
+        frame.set_pixel(10, 20, palette.colours[0x21]);
+        frame.set_pixel(12, 21, palette.colours[0x21]);
+        recorder.push(&frame);
+        let clip = recorder.clip();
+        assert_eq!(clip.len(), 3);
+
+        let mut gif = Vec::new();
+        clip.encode(Cursor::new(&mut gif)).unwrap();
+        let mut options = gif::DecodeOptions::new();
+        options.set_color_output(gif::ColorOutput::Indexed);
+        let mut decoder = options.read_info(Cursor::new(gif)).unwrap();
+        assert_eq!(decoder.global_palette().unwrap().len(), 64 * 3);
+
+        let first = decoder.read_next_frame().unwrap().unwrap();
+        assert_eq!((first.width, first.height), (256, 240));
+        // two frames' worth: 3.33 + 3.33 hundredths
+        assert_eq!(first.delay, 7);
+        assert_eq!(first.buffer[0], 0x0D);
+        let change = decoder.read_next_frame().unwrap().unwrap();
+        assert_eq!((change.left, change.top), (10, 20));
+        assert_eq!((change.width, change.height), (3, 2));
+        assert_eq!(change.buffer[0], 0x21);
+        assert_eq!(change.delay, 3);
+        assert!(decoder.read_next_frame().unwrap().is_none());
+    }
+}
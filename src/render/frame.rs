@@ -0,0 +1,122 @@
+use alloc::format;
+use alloc::vec;
+use alloc::vec::Vec;
+
+type Rgb = (u8, u8, u8);
+
+// One 256x240 picture, packed RGB.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    pub data: Vec<u8>,
+}
+
+impl Default for Frame {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Frame {
+    pub const WIDTH: usize = 256;
+    pub const HEIGHT: usize = 240;
+
+    pub fn new() -> Self {
+        Frame {
+            data: vec![0; Frame::WIDTH * Frame::HEIGHT * 3],
+        }
+    }
+
+    pub fn set_pixel(&mut self, x: usize, y: usize, rgb: Rgb) {
+        let base = (y * Frame::WIDTH + x) * 3;
+        if base + 2 < self.data.len() {
+            self.data[base] = rgb.0;
+            self.data[base + 1] = rgb.1;
+            self.data[base + 2] = rgb.2;
+        }
+    }
+
+    // Line `y`, three bytes a pixel.
+    pub fn row_mut(&mut self, y: usize) -> &mut [u8] {
+        let width = Frame::WIDTH * 3;
+        &mut self.data[y * width..(y + 1) * width]
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> Rgb {
+        let base = (y * Frame::WIDTH + x) * 3;
+        (self.data[base], self.data[base + 1], self.data[base + 2])
+    }
+
+    // Binary PPM: a short text header, then the RGB bytes as they are.
+    pub fn to_ppm(&self) -> Vec<u8> {
+        let mut ppm = format!("P6\n{} {}\n255\n", Frame::WIDTH, Frame::HEIGHT).into_bytes();
+        ppm.extend_from_slice(&self.data);
+        ppm
+    }
+
+    #[cfg(feature = "std")]
+    pub fn to_png(&self) -> Vec<u8> {
+        encode_png(Frame::WIDTH as u32, Frame::HEIGHT as u32, &self.data)
+    }
+}
+
+// Packed RGB rows of any size, e.g. a frame as a shader drew it.
+#[cfg(feature = "std")]
+pub fn encode_png(width: u32, height: u32, rgb: &[u8]) -> Vec<u8> {
+    let mut png = Vec::new();
+    let mut encoder = png::Encoder::new(&mut png, width, height);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    // writing to a Vec only fails on a size mismatch
+    let mut writer = encoder.write_header().expect("png header");
+    writer.write_image_data(rgb).expect("png size");
+    writer.finish().expect("png end");
+    png
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_set_and_get_pixel() {
+        let mut frame = Frame::new();
+        frame.set_pixel(255, 239, (1, 2, 3));
+
+        assert_eq!(frame.pixel(255, 239), (1, 2, 3));
+        assert_eq!(frame.pixel(0, 0), (0, 0, 0));
+    }
+
+    #[test]
+    fn test_ppm() {
+        let mut frame = Frame::new();
+        frame.set_pixel(0, 0, (1, 2, 3));
+        let ppm = frame.to_ppm();
+
+        assert!(ppm.starts_with(b"P6\n256 240\n255\n"));
+        assert_eq!(ppm.len(), 15 + 256 * 240 * 3);
+        assert_eq!(&ppm[15..18], &[1, 2, 3]);
+    }
+
+    #[test]
+    fn test_png_round_trip() {
+        let mut frame = Frame::new();
+        frame.set_pixel(255, 239, (1, 2, 3));
+        let png = frame.to_png();
+
+        let decoder = png::Decoder::new(std::io::Cursor::new(png));
+        let mut reader = decoder.read_info().unwrap();
+        let mut data = vec![0; reader.output_buffer_size().unwrap()];
+        let info = reader.next_frame(&mut data).unwrap();
+        assert_eq!((info.width, info.height), (256, 240));
+        assert_eq!(info.color_type, png::ColorType::Rgb);
+        assert_eq!(data, frame.data);
+    }
+
+    #[test]
+    fn test_out_of_bounds_write_is_ignored() {
+        let mut frame = Frame::new();
+        frame.set_pixel(0, 240, (1, 2, 3));
+
+        assert_eq!(frame, Frame::new());
+    }
+}
@@ -0,0 +1,135 @@
+// Input display: the joypads' buttons and where the guns point, drawn over
+// the picture for streams, tutorials and checking what a movie presses.
+// It shows what the machine held for the frame (see InputState), so a
+// replayed movie shows its own input rather than the keyboard's. Pads
+// stack up from the bottom-right corner, the one the OSD leaves free,
+// each a player number and a small controller: pressed buttons are white,
+// the rest grey. A gun is a cross where it points, red with the trigger
+// held.
+
+use crate::input::joypad::JoypadButton;
+use crate::input::{InputState, ZapperState};
+use crate::render::frame::Frame;
+use crate::render::osd::{darken, draw_text};
+
+type Rgb = (u8, u8, u8);
+
+const PRESSED: Rgb = (0xFF, 0xFF, 0xFF);
+const RELEASED: Rgb = (0x60, 0x60, 0x60);
+const FIRING: Rgb = (0xFF, 0x20, 0x20);
+
+// x, y, width and height in a pad, for each button
+const LAYOUT: [(JoypadButton, usize, usize, usize, usize); 8] = [
+    (JoypadButton::UP, 3, 0, 3, 3),
+    (JoypadButton::LEFT, 0, 3, 3, 3),
+    (JoypadButton::RIGHT, 6, 3, 3, 3),
+    (JoypadButton::DOWN, 3, 6, 3, 3),
+    (JoypadButton::SELECT, 12, 5, 4, 2),
+    (JoypadButton::START, 18, 5, 4, 2),
+    (JoypadButton::BUTTON_B, 25, 3, 4, 4),
+    (JoypadButton::BUTTON_A, 31, 3, 4, 4),
+];
+const PAD_WIDTH: usize = 35;
+const PAD_HEIGHT: usize = 9;
+// the number, then a gap
+const LABEL_WIDTH: usize = 8;
+const ROW_HEIGHT: usize = PAD_HEIGHT + 4;
+const MARGIN: usize = 8;
+const CROSS_ARM: usize = 3;
+
+fn fill(frame: &mut Frame, x: usize, y: usize, width: usize, height: usize, rgb: Rgb) {
+    for y in y..(y + height).min(Frame::HEIGHT) {
+        for x in x..(x + width).min(Frame::WIDTH) {
+            frame.set_pixel(x, y, rgb);
+        }
+    }
+}
+
+// One pad with its top-left corner at (x, y).
+pub fn draw_pad(frame: &mut Frame, x: usize, y: usize, buttons: JoypadButton) {
+    darken(frame, x - 1, y - 1, PAD_WIDTH + 2, PAD_HEIGHT + 2);
+    for (button, left, top, width, height) in LAYOUT {
+        let rgb = if buttons.contains(button) {
+            PRESSED
+        } else {
+            RELEASED
+        };
+        fill(frame, x + left, y + top, width, height, rgb);
+    }
+}
+
+fn draw_cross(frame: &mut Frame, zapper: &ZapperState) {
+    let Some((x, y)) = zapper.cursor else {
+        return;
+    };
+    let (x, y) = (x as usize, y as usize);
+    let rgb = if zapper.trigger { FIRING } else { PRESSED };
+    let left = x.saturating_sub(CROSS_ARM);
+    let top = y.saturating_sub(CROSS_ARM);
+    fill(frame, left, y, x + CROSS_ARM + 1 - left, 1, rgb);
+    fill(frame, x, top, 1, y + CROSS_ARM + 1 - top, rgb);
+}
+
+pub fn draw(frame: &mut Frame, state: &InputState) {
+    let x = Frame::WIDTH - MARGIN - PAD_WIDTH;
+    let mut y = Frame::HEIGHT - MARGIN - PAD_HEIGHT;
+    for (player, buttons) in state.joypads.iter().enumerate().rev() {
+        let Some(buttons) = *buttons else {
+            continue;
+        };
+        draw_text(frame, x - LABEL_WIDTH, y + 1, &(player + 1).to_string());
+        draw_pad(frame, x, y, buttons);
+        y -= ROW_HEIGHT;
+    }
+    for zapper in state.zappers.iter().flatten() {
+        draw_cross(frame, zapper);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_draws_what_is_held() {
+        let mut state = InputState::default();
+        state.joypads[0] = Some(JoypadButton::BUTTON_A | JoypadButton::UP);
+        state.zappers[1] = Some(ZapperState {
+            cursor: Some((100, 50)),
+            trigger: true,
+        });
+        let mut frame = Frame::new();
+        draw(&mut frame, &state);
+
+        let (x, y) = (
+            Frame::WIDTH - MARGIN - PAD_WIDTH,
+            Frame::HEIGHT - MARGIN - PAD_HEIGHT,
+        );
+        assert_eq!(frame.pixel(x + 32, y + 4), PRESSED); // A
+        assert_eq!(frame.pixel(x + 26, y + 4), RELEASED); // B
+        assert_eq!(frame.pixel(x + 4, y + 1), PRESSED); // up
+        assert_eq!(frame.pixel(x + 4, y + 7), RELEASED); // down
+        assert_eq!(frame.pixel(100, 50), FIRING);
+        assert_eq!(frame.pixel(103, 50), FIRING);
+        assert_eq!(frame.pixel(104, 50), (0, 0, 0));
+        // nobody else, so nothing above the one pad
+        assert_eq!(frame.pixel(x + 32, y + 4 - ROW_HEIGHT), (0, 0, 0));
+    }
+
+    #[test]
+    fn test_four_players_stack_up() {
+        let state = InputState {
+            joypads: [Some(JoypadButton::all()); 4],
+            ..InputState::default()
+        };
+        let mut frame = Frame::new();
+        draw(&mut frame, &state);
+        let (x, y) = (
+            Frame::WIDTH - MARGIN - PAD_WIDTH,
+            Frame::HEIGHT - MARGIN - PAD_HEIGHT,
+        );
+        for row in 0..4 {
+            assert_eq!(frame.pixel(x + 32, y + 4 - row * ROW_HEIGHT), PRESSED);
+        }
+    }
+}
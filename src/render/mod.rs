@@ -0,0 +1,14 @@
+#[cfg(feature = "std")]
+pub mod clip;
+pub mod frame;
+#[cfg(feature = "std")]
+pub mod input_display;
+#[cfg(feature = "std")]
+pub mod osd;
+pub mod palette;
+#[cfg(feature = "std")]
+pub mod shader;
+#[cfg(feature = "std")]
+pub mod video;
+#[cfg(feature = "std")]
+pub mod viewport;
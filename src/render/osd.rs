@@ -0,0 +1,276 @@
+// On-screen display: short messages ("State saved", "Speed 50%") drawn
+// straight into a copy of the frame, so every front end shows them without
+// a font library or any drawing of its own. Text uses the classic 5x7
+// LCD font at one frame pixel per dot; a line holds 42 characters.
+//
+// Messages stack up from the bottom-left corner and go away after a couple
+// of seconds. The status line in the top-left corner is for whatever is
+// going on right now ("Paused", "Rewinding") and is set every frame by
+// whoever knows; the top-right corner is for numbers like the frame rate.
+
+use std::collections::VecDeque;
+
+use crate::render::frame::Frame;
+
+type Rgb = (u8, u8, u8);
+
+const GLYPH_WIDTH: usize = 5;
+const GLYPH_HEIGHT: usize = 7;
+// a column between characters and a row between lines
+const ADVANCE: usize = GLYPH_WIDTH + 1;
+const LINE_HEIGHT: usize = GLYPH_HEIGHT + 3;
+const MARGIN: usize = 8;
+
+// Display frames a message stays up, about two seconds.
+const MESSAGE_FRAMES: u32 = 120;
+const MAX_MESSAGES: usize = 4;
+
+const TEXT: Rgb = (0xFF, 0xFF, 0xFF);
+
+// Printable ASCII from ' ', a byte per column, least significant bit at
+// the top.
+#[rustfmt::skip]
+const FONT: [[u8; GLYPH_WIDTH]; 95] = [
+    [0x00, 0x00, 0x00, 0x00, 0x00], [0x00, 0x00, 0x5F, 0x00, 0x00], // ' ' !
+    [0x00, 0x07, 0x00, 0x07, 0x00], [0x14, 0x7F, 0x14, 0x7F, 0x14], // " #
+    [0x24, 0x2A, 0x7F, 0x2A, 0x12], [0x23, 0x13, 0x08, 0x64, 0x62], // $ %
+    [0x36, 0x49, 0x55, 0x22, 0x50], [0x00, 0x05, 0x03, 0x00, 0x00], // & '
+    [0x00, 0x1C, 0x22, 0x41, 0x00], [0x00, 0x41, 0x22, 0x1C, 0x00], // ( )
+    [0x08, 0x2A, 0x1C, 0x2A, 0x08], [0x08, 0x08, 0x3E, 0x08, 0x08], // * +
+    [0x00, 0x50, 0x30, 0x00, 0x00], [0x08, 0x08, 0x08, 0x08, 0x08], // , -
+    [0x00, 0x60, 0x60, 0x00, 0x00], [0x20, 0x10, 0x08, 0x04, 0x02], // . /
+    [0x3E, 0x51, 0x49, 0x45, 0x3E], [0x00, 0x42, 0x7F, 0x40, 0x00], // 0 1
+    [0x42, 0x61, 0x51, 0x49, 0x46], [0x21, 0x41, 0x45, 0x4B, 0x31], // 2 3
+    [0x18, 0x14, 0x12, 0x7F, 0x10], [0x27, 0x45, 0x45, 0x45, 0x39], // 4 5
+    [0x3C, 0x4A, 0x49, 0x49, 0x30], [0x01, 0x71, 0x09, 0x05, 0x03], // 6 7
+    [0x36, 0x49, 0x49, 0x49, 0x36], [0x06, 0x49, 0x49, 0x29, 0x1E], // 8 9
+    [0x00, 0x36, 0x36, 0x00, 0x00], [0x00, 0x56, 0x36, 0x00, 0x00], // : ;
+    [0x08, 0x14, 0x22, 0x41, 0x00], [0x14, 0x14, 0x14, 0x14, 0x14], // < =
+    [0x00, 0x41, 0x22, 0x14, 0x08], [0x02, 0x01, 0x51, 0x09, 0x06], // > ?
+    [0x32, 0x49, 0x79, 0x41, 0x3E], [0x7E, 0x11, 0x11, 0x11, 0x7E], // @ A
+    [0x7F, 0x49, 0x49, 0x49, 0x36], [0x3E, 0x41, 0x41, 0x41, 0x22], // B C
+    [0x7F, 0x41, 0x41, 0x22, 0x1C], [0x7F, 0x49, 0x49, 0x49, 0x41], // D E
+    [0x7F, 0x09, 0x09, 0x01, 0x01], [0x3E, 0x41, 0x41, 0x51, 0x32], // F G
+    [0x7F, 0x08, 0x08, 0x08, 0x7F], [0x00, 0x41, 0x7F, 0x41, 0x00], // H I
+    [0x20, 0x40, 0x41, 0x3F, 0x01], [0x7F, 0x08, 0x14, 0x22, 0x41], // J K
+    [0x7F, 0x40, 0x40, 0x40, 0x40], [0x7F, 0x02, 0x04, 0x02, 0x7F], // L M
+    [0x7F, 0x04, 0x08, 0x10, 0x7F], [0x3E, 0x41, 0x41, 0x41, 0x3E], // N O
+    [0x7F, 0x09, 0x09, 0x09, 0x06], [0x3E, 0x41, 0x51, 0x21, 0x5E], // P Q
+    [0x7F, 0x09, 0x19, 0x29, 0x46], [0x46, 0x49, 0x49, 0x49, 0x31], // R S
+    [0x01, 0x01, 0x7F, 0x01, 0x01], [0x3F, 0x40, 0x40, 0x40, 0x3F], // T U
+    [0x1F, 0x20, 0x40, 0x20, 0x1F], [0x7F, 0x20, 0x18, 0x20, 0x7F], // V W
+    [0x63, 0x14, 0x08, 0x14, 0x63], [0x03, 0x04, 0x78, 0x04, 0x03], // X Y
+    [0x61, 0x51, 0x49, 0x45, 0x43], [0x00, 0x7F, 0x41, 0x41, 0x00], // Z [
+    [0x02, 0x04, 0x08, 0x10, 0x20], [0x00, 0x41, 0x41, 0x7F, 0x00], // \ ]
+    [0x04, 0x02, 0x01, 0x02, 0x04], [0x40, 0x40, 0x40, 0x40, 0x40], // ^ _
+    [0x00, 0x01, 0x02, 0x04, 0x00], [0x20, 0x54, 0x54, 0x54, 0x78], // ` a
+    [0x7F, 0x48, 0x44, 0x44, 0x38], [0x38, 0x44, 0x44, 0x44, 0x20], // b c
+    [0x38, 0x44, 0x44, 0x48, 0x7F], [0x38, 0x54, 0x54, 0x54, 0x18], // d e
+    [0x08, 0x7E, 0x09, 0x01, 0x02], [0x08, 0x14, 0x54, 0x54, 0x3C], // f g
+    [0x7F, 0x08, 0x04, 0x04, 0x78], [0x00, 0x44, 0x7D, 0x40, 0x00], // h i
+    [0x20, 0x40, 0x44, 0x3D, 0x00], [0x00, 0x7F, 0x10, 0x28, 0x44], // j k
+    [0x00, 0x41, 0x7F, 0x40, 0x00], [0x7C, 0x04, 0x18, 0x04, 0x78], // l m
+    [0x7C, 0x08, 0x04, 0x04, 0x78], [0x38, 0x44, 0x44, 0x44, 0x38], // n o
+    [0x7C, 0x14, 0x14, 0x14, 0x08], [0x08, 0x14, 0x14, 0x18, 0x7C], // p q
+    [0x7C, 0x08, 0x04, 0x04, 0x08], [0x48, 0x54, 0x54, 0x54, 0x20], // r s
+    [0x04, 0x3F, 0x44, 0x40, 0x20], [0x3C, 0x40, 0x40, 0x20, 0x7C], // t u
+    [0x1C, 0x20, 0x40, 0x20, 0x1C], [0x3C, 0x40, 0x30, 0x40, 0x3C], // v w
+    [0x44, 0x28, 0x10, 0x28, 0x44], [0x0C, 0x50, 0x50, 0x50, 0x3C], // x y
+    [0x44, 0x64, 0x54, 0x4C, 0x44], [0x00, 0x08, 0x36, 0x41, 0x00], // z {
+    [0x00, 0x00, 0x7F, 0x00, 0x00], [0x00, 0x41, 0x36, 0x08, 0x00], // | }
+    [0x02, 0x01, 0x02, 0x04, 0x02],                                 // ~
+];
+
+// Anything outside printable ASCII comes out as '?'.
+fn glyph(c: char) -> &'static [u8; GLYPH_WIDTH] {
+    let index = match c {
+        ' '..='~' => c as usize - ' ' as usize,
+        _ => '?' as usize - ' ' as usize,
+    };
+    &FONT[index]
+}
+
+// Halves the brightness under the text so it reads on any background.
+pub(crate) fn darken(frame: &mut Frame, x: usize, y: usize, width: usize, height: usize) {
+    for y in y..(y + height).min(Frame::HEIGHT) {
+        for x in x..(x + width).min(Frame::WIDTH) {
+            let (r, g, b) = frame.pixel(x, y);
+            frame.set_pixel(x, y, (r / 2, g / 2, b / 2));
+        }
+    }
+}
+
+// One line of text with its top-left corner at (x, y), on a darkened
+// box; whatever runs off the frame is cut.
+pub fn draw_text(frame: &mut Frame, x: usize, y: usize, text: &str) {
+    let width = text.chars().count() * ADVANCE + 1;
+    darken(
+        frame,
+        x.saturating_sub(1),
+        y.saturating_sub(1),
+        width,
+        GLYPH_HEIGHT + 2,
+    );
+    for (i, c) in text.chars().enumerate() {
+        let left = x + i * ADVANCE;
+        for (column, bits) in glyph(c).iter().enumerate() {
+            for row in 0..GLYPH_HEIGHT {
+                let (px, py) = (left + column, y + row);
+                if bits & (1 << row) != 0 && px < Frame::WIDTH && py < Frame::HEIGHT {
+                    frame.set_pixel(px, py, TEXT);
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Message {
+    text: String,
+    frames_left: u32,
+}
+
+#[derive(Debug, Default)]
+pub struct Osd {
+    messages: VecDeque<Message>,
+    status: Option<String>,
+    corner: Option<String>,
+}
+
+impl Osd {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // The same text again just restarts its time, so holding a key down
+    // doesn't fill the screen.
+    pub fn show(&mut self, text: impl Into<String>) {
+        let text = text.into();
+        self.messages.retain(|message| message.text != text);
+        if self.messages.len() == MAX_MESSAGES {
+            self.messages.pop_front();
+        }
+        self.messages.push_back(Message {
+            text,
+            frames_left: MESSAGE_FRAMES,
+        });
+    }
+
+    pub fn set_status(&mut self, status: Option<String>) {
+        self.status = status;
+    }
+
+    pub fn set_corner(&mut self, corner: Option<String>) {
+        self.corner = corner;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.messages.is_empty() && self.status.is_none() && self.corner.is_none()
+    }
+
+    pub fn messages(&self) -> impl Iterator<Item = &str> {
+        self.messages.iter().map(|message| message.text.as_str())
+    }
+
+    // Once per displayed frame, paused or not.
+    pub fn tick(&mut self) {
+        for message in self.messages.iter_mut() {
+            message.frames_left -= 1;
+        }
+        self.messages.retain(|message| message.frames_left > 0);
+    }
+
+    pub fn draw(&self, frame: &mut Frame) {
+        if let Some(status) = self.status.as_ref() {
+            draw_text(frame, MARGIN, MARGIN, status);
+        }
+        if let Some(corner) = self.corner.as_ref() {
+            let width = corner.chars().count() * ADVANCE;
+            let x = Frame::WIDTH.saturating_sub(MARGIN + width);
+            draw_text(frame, x, MARGIN, corner);
+        }
+        let bottom = Frame::HEIGHT - MARGIN - GLYPH_HEIGHT;
+        for (i, message) in self.messages.iter().rev().enumerate() {
+            draw_text(frame, MARGIN, bottom - i * LINE_HEIGHT, &message.text);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // The glyph back out of the frame, a string per row.
+    fn read_back(frame: &Frame, x: usize, y: usize) -> Vec<String> {
+        (y..y + GLYPH_HEIGHT)
+            .map(|y| {
+                (x..x + GLYPH_WIDTH)
+                    .map(|x| if frame.pixel(x, y) == TEXT { '#' } else { '.' })
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_text_is_drawn_on_a_dark_box() {
+        let mut frame = Frame::new();
+        for y in 0..20 {
+            for x in 0..40 {
+                frame.set_pixel(x, y, (0x80, 0x40, 0x20));
+            }
+        }
+        draw_text(&mut frame, 2, 3, "A7");
+        assert_eq!(
+            read_back(&frame, 2, 3),
+            [".###.", "#...#", "#...#", "#...#", "#####", "#...#", "#...#"]
+        );
+        assert_eq!(
+            read_back(&frame, 8, 3),
+            ["#####", "....#", "...#.", "..#..", ".#...", ".#...", ".#..."]
+        );
+        // the gap between characters is darkened, outside the box isn't
+        assert_eq!(frame.pixel(7, 3), (0x40, 0x20, 0x10));
+        assert_eq!(frame.pixel(2, 11), (0x80, 0x40, 0x20));
+        assert_eq!(glyph('é'), glyph('?'));
+    }
+
+    #[test]
+    fn test_messages_stack_and_expire() {
+        let mut osd = Osd::new();
+        assert!(osd.is_empty());
+        osd.show("State saved");
+        for _ in 0..MESSAGE_FRAMES / 2 {
+            osd.tick();
+        }
+        osd.show("Speed 50%");
+        for _ in 0..MESSAGE_FRAMES / 4 {
+            osd.tick();
+        }
+        osd.show("State saved");
+        assert_eq!(
+            osd.messages().collect::<Vec<_>>(),
+            ["Speed 50%", "State saved"]
+        );
+
+        let mut frame = Frame::new();
+        osd.draw(&mut frame);
+        // the newest at the bottom
+        let bottom = Frame::HEIGHT - MARGIN - GLYPH_HEIGHT;
+        let mut expected = Frame::new();
+        draw_text(&mut expected, MARGIN, bottom, "State saved");
+        draw_text(&mut expected, MARGIN, bottom - LINE_HEIGHT, "Speed 50%");
+        assert!(frame.data == expected.data);
+
+        // the one shown again has its time restarted
+        for _ in 0..MESSAGE_FRAMES * 3 / 4 {
+            osd.tick();
+        }
+        assert_eq!(osd.messages().collect::<Vec<_>>(), ["State saved"]);
+        for _ in 0..MESSAGE_FRAMES {
+            osd.tick();
+        }
+        assert!(osd.is_empty());
+        osd.set_status(Some("Paused".to_string()));
+        assert!(!osd.is_empty());
+    }
+}
@@ -0,0 +1,197 @@
+// Post-processing shaders for the GPU video path. A shader is a WGSL file
+// that defines only `fs_main`; it is appended to a shared prelude with the
+// vertex stage, the frame texture and a small uniform block, so user
+// shaders stay a few lines long. A handful of presets are bundled.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::render::frame::Frame;
+
+pub const PRELUDE: &str = include_str!("shaders/prelude.wgsl");
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShaderPreset {
+    Passthrough,
+    Scanlines,
+    Crt,
+}
+
+impl ShaderPreset {
+    pub const ALL: [ShaderPreset; 3] = [
+        ShaderPreset::Passthrough,
+        ShaderPreset::Scanlines,
+        ShaderPreset::Crt,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ShaderPreset::Passthrough => "none",
+            ShaderPreset::Scanlines => "scanlines",
+            ShaderPreset::Crt => "crt",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|preset| preset.name().eq_ignore_ascii_case(name))
+    }
+
+    fn source(self) -> &'static str {
+        match self {
+            ShaderPreset::Passthrough => include_str!("shaders/passthrough.wgsl"),
+            ShaderPreset::Scanlines => include_str!("shaders/scanlines.wgsl"),
+            ShaderPreset::Crt => include_str!("shaders/crt.wgsl"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PostShader {
+    pub name: String,
+    source: String,
+}
+
+impl PostShader {
+    pub fn preset(preset: ShaderPreset) -> Self {
+        PostShader {
+            name: preset.name().to_string(),
+            source: preset.source().to_string(),
+        }
+    }
+
+    pub fn from_source(name: &str, source: &str) -> Self {
+        PostShader {
+            name: name.to_string(),
+            source: source.to_string(),
+        }
+    }
+
+    pub fn from_file(path: &Path) -> io::Result<Self> {
+        let source = fs::read_to_string(path)?;
+        let name = path.file_stem().map_or_else(
+            || path.display().to_string(),
+            |stem| stem.to_string_lossy().into_owned(),
+        );
+        Ok(PostShader { name, source })
+    }
+
+    // A preset name, or else a path to a .wgsl file.
+    pub fn load(spec: &str) -> io::Result<Self> {
+        match ShaderPreset::from_name(spec) {
+            Some(preset) => Ok(Self::preset(preset)),
+            None => Self::from_file(Path::new(spec)),
+        }
+    }
+
+    // The shader's own part, as written.
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    // The complete module handed to the GPU.
+    pub fn wgsl(&self) -> String {
+        format!("{}\n{}", PRELUDE, self.source)
+    }
+}
+
+impl Default for PostShader {
+    fn default() -> Self {
+        Self::preset(ShaderPreset::Passthrough)
+    }
+}
+
+// Mirrors `Uniforms` in the prelude; 32 bytes with the trailing padding.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ShaderUniforms {
+    pub source_size: [f32; 2],
+    pub output_size: [f32; 2],
+    pub frame_count: u32,
+    pub time: f32,
+}
+
+impl Default for ShaderUniforms {
+    fn default() -> Self {
+        let source_size = [Frame::WIDTH as f32, Frame::HEIGHT as f32];
+        ShaderUniforms {
+            source_size,
+            output_size: source_size,
+            frame_count: 0,
+            time: 0.0,
+        }
+    }
+}
+
+impl ShaderUniforms {
+    pub const SIZE: usize = 32;
+
+    pub fn to_bytes(&self) -> [u8; Self::SIZE] {
+        let mut bytes = [0; Self::SIZE];
+        let words = [
+            self.source_size[0].to_bits(),
+            self.source_size[1].to_bits(),
+            self.output_size[0].to_bits(),
+            self.output_size[1].to_bits(),
+            self.frame_count,
+            self.time.to_bits(),
+        ];
+        for (chunk, word) in bytes.chunks_exact_mut(4).zip(words) {
+            chunk.copy_from_slice(&word.to_le_bytes());
+        }
+        bytes
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_preset_names() {
+        for preset in ShaderPreset::ALL {
+            assert_eq!(ShaderPreset::from_name(preset.name()), Some(preset));
+        }
+        assert_eq!(ShaderPreset::from_name("CRT"), Some(ShaderPreset::Crt));
+        assert_eq!(ShaderPreset::from_name("ntsc"), None);
+    }
+
+    #[test]
+    fn test_presets_only_define_the_fragment_stage() {
+        for preset in ShaderPreset::ALL {
+            let shader = PostShader::preset(preset);
+            assert!(shader.source().contains("fn fs_main"));
+            assert!(!shader.source().contains("@vertex"));
+            assert!(shader.wgsl().starts_with(PRELUDE));
+        }
+    }
+
+    #[test]
+    fn test_load_from_file() {
+        let path = std::env::temp_dir().join(format!("nes-test-{}-warm.wgsl", std::process::id()));
+        fs::write(&path, "@fragment fn fs_main() {}").unwrap();
+        let shader = PostShader::load(path.to_str().unwrap()).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert!(shader.name.ends_with("-warm"));
+        assert_eq!(shader.source(), "@fragment fn fs_main() {}");
+        assert_eq!(PostShader::load("scanlines").unwrap().name, "scanlines");
+        assert!(PostShader::load("/no/such/shader.wgsl").is_err());
+    }
+
+    #[test]
+    fn test_uniform_layout() {
+        let uniforms = ShaderUniforms {
+            output_size: [768.0, 720.0],
+            frame_count: 3,
+            ..Default::default()
+        };
+        let bytes = uniforms.to_bytes();
+
+        assert_eq!(&bytes[0..4], &256.0f32.to_le_bytes());
+        assert_eq!(&bytes[12..16], &720.0f32.to_le_bytes());
+        assert_eq!(&bytes[16..20], &3u32.to_le_bytes());
+        assert_eq!(&bytes[24..], &[0; 8]);
+    }
+}
@@ -0,0 +1,30 @@
+// Consumer TV look: barrel curvature, scanlines and an RGB aperture mask
+// across output columns.
+
+const CURVATURE: vec2<f32> = vec2<f32>(4.5, 4.0);
+const SCANLINE_STRENGTH: f32 = 0.4;
+const MASK_DIM: f32 = 0.7;
+// makes up for the light the mask and scanlines take away
+const BRIGHTNESS: f32 = 1.25;
+
+fn warp(uv: vec2<f32>) -> vec2<f32> {
+    let centered = uv * 2.0 - 1.0;
+    let offset = centered.yx / CURVATURE;
+    return (centered + centered * offset * offset) * 0.5 + 0.5;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let uv = warp(in.uv);
+    let inside = all(uv >= vec2<f32>(0.0)) && all(uv <= vec2<f32>(1.0));
+
+    var color = sample_source(clamp(uv, vec2<f32>(0.0), vec2<f32>(1.0))).rgb;
+    let line = fract(uv.y * uniforms.source_size.y);
+    color *= 1.0 - SCANLINE_STRENGTH + SCANLINE_STRENGTH * sin(line * 3.14159265);
+
+    var mask = vec3<f32>(MASK_DIM);
+    mask[u32(in.position.x) % 3u] = 1.0;
+    color *= mask * BRIGHTNESS;
+
+    return vec4<f32>(select(vec3<f32>(0.0), color, inside), 1.0);
+}
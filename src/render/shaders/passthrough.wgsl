@@ -0,0 +1,6 @@
+// Sharp pixels, no effects.
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return sample_source(in.uv);
+}
@@ -0,0 +1,38 @@
+// Shared by every post-processing shader. A shader supplies only
+// `fs_main`; the frame is bound as `source_texture` (256x240, nearest
+// filtering) and this vertex stage draws one triangle over the viewport.
+
+struct Uniforms {
+    // 256x240
+    source_size: vec2<f32>,
+    // the scaled viewport, in physical pixels
+    output_size: vec2<f32>,
+    frame_count: u32,
+    // seconds since the shader was loaded
+    time: f32,
+    _padding: vec2<f32>,
+};
+
+@group(0) @binding(0) var source_texture: texture_2d<f32>;
+@group(0) @binding(1) var source_sampler: sampler;
+@group(0) @binding(2) var<uniform> uniforms: Uniforms;
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+};
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    let x = f32((index << 1u) & 2u);
+    let y = f32(index & 2u);
+    var out: VertexOutput;
+    out.position = vec4<f32>(x * 2.0 - 1.0, 1.0 - y * 2.0, 0.0, 1.0);
+    out.uv = vec2<f32>(x, y);
+    return out;
+}
+
+// Safe to call from non-uniform control flow.
+fn sample_source(uv: vec2<f32>) -> vec4<f32> {
+    return textureSampleLevel(source_texture, source_sampler, uv, 0.0);
+}
@@ -0,0 +1,11 @@
+// Dark gaps between the 240 source lines, as on a low-resolution CRT.
+
+const STRENGTH: f32 = 0.35;
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = sample_source(in.uv).rgb;
+    let line = fract(in.uv.y * uniforms.source_size.y);
+    let weight = 1.0 - STRENGTH + STRENGTH * sin(line * 3.14159265);
+    return vec4<f32>(color * weight, 1.0);
+}
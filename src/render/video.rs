@@ -0,0 +1,274 @@
+// Gameplay recording through ffmpeg, which has to be installed. Frames are
+// piped to it raw while playing and encoded to H.264; the audio goes to a
+// WAV file on the side. When the recording stops a second, quick ffmpeg
+// run copies the video and adds the audio as AAC into the file asked for,
+// MKV or MP4 by its extension, and the two side files are removed.
+//
+// Every emulated frame is recorded with its own audio, so the video is at
+// the console's speed whatever speed it was played at.
+
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdin, Command, Stdio};
+
+use crate::audio::wav::WavWriter;
+use crate::render::frame::Frame;
+
+pub struct VideoRecorder {
+    ffmpeg: PathBuf,
+    path: PathBuf,
+    video_path: PathBuf,
+    audio_path: PathBuf,
+    encoder: Child,
+    stdin: Option<ChildStdin>,
+    audio: Option<WavWriter<BufWriter<File>>>,
+}
+
+// Arguments for the encoding run: raw RGB frames on stdin, scaled up with
+// nearest-neighbour so the pixels stay sharp. x264 wants even sizes.
+fn encode_args(size: (u32, u32), frame_rate: f64, video_path: &Path) -> Vec<String> {
+    let source = format!("{}x{}", Frame::WIDTH, Frame::HEIGHT);
+    let frame_rate = frame_rate.to_string();
+    let scale = format!(
+        "scale={}:{}:flags=neighbor",
+        size.0.next_multiple_of(2),
+        size.1.next_multiple_of(2)
+    );
+    let video_path = video_path.display().to_string();
+    [
+        "-y",
+        "-loglevel",
+        "error",
+        "-f",
+        "rawvideo",
+        "-pixel_format",
+        "rgb24",
+        "-video_size",
+        &source,
+        "-framerate",
+        &frame_rate,
+        "-i",
+        "-",
+        "-vf",
+        &scale,
+        "-c:v",
+        "libx264",
+        "-preset",
+        "veryfast",
+        "-crf",
+        "18",
+        "-pix_fmt",
+        "yuv420p",
+        &video_path,
+    ]
+    .map(String::from)
+    .into()
+}
+
+fn mux_args(video_path: &Path, audio_path: &Path, path: &Path) -> Vec<String> {
+    let (video_path, audio_path) = (
+        video_path.display().to_string(),
+        audio_path.display().to_string(),
+    );
+    let path = path.display().to_string();
+    [
+        "-y",
+        "-loglevel",
+        "error",
+        "-i",
+        &video_path,
+        "-i",
+        &audio_path,
+        "-c:v",
+        "copy",
+        "-c:a",
+        "aac",
+        "-b:a",
+        "192k",
+        &path,
+    ]
+    .map(String::from)
+    .into()
+}
+
+fn side_path(path: &Path, suffix: &str) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!("{}.{}", stem, suffix))
+}
+
+fn run_ffmpeg(ffmpeg: &Path, args: &[String]) -> io::Result<()> {
+    let status = Command::new(ffmpeg)
+        .args(args)
+        .stdin(Stdio::null())
+        .status()?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(io::Error::other(format!("ffmpeg failed: {}", status)))
+    }
+}
+
+impl VideoRecorder {
+    // `size` is what the video is scaled to, e.g. the window's size at 1x
+    // scale and the chosen pixel aspect.
+    pub fn start(
+        ffmpeg: &Path,
+        path: &Path,
+        size: (u32, u32),
+        frame_rate: f64,
+        sample_rate: u32,
+    ) -> io::Result<Self> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let video_path = side_path(path, "video.mkv");
+        let audio_path = side_path(path, "audio.wav");
+        let audio = WavWriter::new(BufWriter::new(File::create(&audio_path)?), sample_rate)?;
+        let spawned = Command::new(ffmpeg)
+            .args(encode_args(size, frame_rate, &video_path))
+            .stdin(Stdio::piped())
+            .spawn();
+        let mut encoder = match spawned {
+            Ok(encoder) => encoder,
+            Err(err) => {
+                drop(audio);
+                let _ = fs::remove_file(&audio_path);
+                return Err(io::Error::new(
+                    err.kind(),
+                    format!("can't run ffmpeg: {}", err),
+                ));
+            }
+        };
+        let stdin = encoder.stdin.take();
+        Ok(VideoRecorder {
+            ffmpeg: ffmpeg.to_path_buf(),
+            path: path.to_path_buf(),
+            video_path,
+            audio_path,
+            encoder,
+            stdin,
+            audio: Some(audio),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn push_frame(&mut self, frame: &Frame, samples: &[f32]) -> io::Result<()> {
+        if let Some(stdin) = self.stdin.as_mut() {
+            stdin.write_all(&frame.data)?;
+        }
+        if let Some(audio) = self.audio.as_mut() {
+            audio.write_samples(samples)?;
+        }
+        Ok(())
+    }
+
+    // Waits for the encoder and muxes; takes a moment for long recordings.
+    pub fn finish(mut self) -> io::Result<PathBuf> {
+        self.stop()?;
+        Ok(self.path.clone())
+    }
+
+    fn stop(&mut self) -> io::Result<()> {
+        // closing stdin is ffmpeg's cue to finish the file
+        if self.stdin.take().is_none() {
+            return Ok(());
+        }
+        let status = self.encoder.wait()?;
+        if let Some(audio) = self.audio.take() {
+            audio.finish()?;
+        }
+        if !status.success() {
+            return Err(io::Error::other(format!("ffmpeg failed: {}", status)));
+        }
+        run_ffmpeg(
+            &self.ffmpeg,
+            &mux_args(&self.video_path, &self.audio_path, &self.path),
+        )?;
+        fs::remove_file(&self.video_path)?;
+        fs::remove_file(&self.audio_path)
+    }
+}
+
+impl Drop for VideoRecorder {
+    // a recording left running when the window closes still gets finished
+    fn drop(&mut self) {
+        if let Err(err) = self.stop() {
+            eprintln!("can't finish recording {}: {}", self.path.display(), err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_ffmpeg_arguments() {
+        let args = encode_args((293, 240), 60.0988, Path::new("/tmp/smb.video.mkv"));
+        let at = |flag: &str| &args[args.iter().position(|arg| arg == flag).unwrap() + 1];
+        assert_eq!(at("-video_size"), "256x240");
+        assert_eq!(at("-framerate"), "60.0988");
+        assert_eq!(at("-vf"), "scale=294:240:flags=neighbor");
+        assert_eq!(args.last().unwrap(), "/tmp/smb.video.mkv");
+
+        let path = Path::new("/tmp/smb.mp4");
+        assert_eq!(
+            side_path(path, "audio.wav"),
+            Path::new("/tmp/smb.audio.wav")
+        );
+        let args = mux_args(Path::new("v.mkv"), Path::new("a.wav"), path);
+        assert_eq!(&args[3..7], ["-i", "v.mkv", "-i", "a.wav"]);
+        assert_eq!(args.last().unwrap(), "/tmp/smb.mp4");
+    }
+
+    // A stand-in ffmpeg that swallows the frames and writes whatever file
+    // it was asked for.
+    #[cfg(unix)]
+    #[test]
+    fn test_recording_runs_ffmpeg_and_cleans_up() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = std::env::temp_dir().join(format!("nes-video-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let ffmpeg = dir.join("ffmpeg");
+        fs::write(
+            &ffmpeg,
+            "#!/bin/sh\nfor last; do :; done\ncat > /dev/null\necho done > \"$last\"\n",
+        )
+        .unwrap();
+        fs::set_permissions(&ffmpeg, fs::Permissions::from_mode(0o755)).unwrap();
+
+        let path = dir.join("clip.mkv");
+        let mut recorder = VideoRecorder::start(&ffmpeg, &path, (256, 240), 60.0, 48_000).unwrap();
+        recorder.push_frame(&Frame::new(), &[0.0; 800]).unwrap();
+        assert!(dir.join("clip.audio.wav").exists());
+        assert_eq!(recorder.finish().unwrap(), path);
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "done\n");
+        assert!(!dir.join("clip.video.mkv").exists());
+        assert!(!dir.join("clip.audio.wav").exists());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_missing_ffmpeg_is_reported() {
+        let dir = std::env::temp_dir().join(format!("nes-video-missing-{}", std::process::id()));
+        let err = VideoRecorder::start(
+            Path::new("/nonexistent/ffmpeg"),
+            &dir.join("clip.mkv"),
+            (256, 240),
+            60.0,
+            48_000,
+        )
+        .err()
+        .unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+        assert!(err.to_string().contains("can't run ffmpeg"));
+        assert!(!dir.join("clip.audio.wav").exists());
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
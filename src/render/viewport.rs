@@ -0,0 +1,147 @@
+// Where the 256x240 picture goes in a window, worked out the same way for
+// every front end:
+//
+// - square pixels, or 8:7 ones as NTSC TVs showed them (256 pixels come out
+//   about 293 wide);
+// - integer scaling keeps every NES pixel the same size and leaves a
+//   border; with 8:7 pixels only the height is a whole multiple, since the
+//   width can't be. Windows smaller than 1x fall back to fitting;
+// - letterboxing keeps the aspect ratio with black bars; without it the
+//   picture is stretched over the whole window.
+
+use serde::Deserialize;
+
+use crate::render::frame::Frame;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PixelAspect {
+    #[default]
+    Square,
+    // 8:7
+    Ntsc,
+}
+
+impl PixelAspect {
+    pub fn width_factor(self) -> f64 {
+        match self {
+            PixelAspect::Square => 1.0,
+            PixelAspect::Ntsc => 8.0 / 7.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Viewport {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Scaling {
+    pub aspect: PixelAspect,
+    pub integer: bool,
+    pub letterbox: bool,
+}
+
+impl Default for Scaling {
+    fn default() -> Self {
+        Scaling {
+            aspect: PixelAspect::Square,
+            integer: false,
+            letterbox: true,
+        }
+    }
+}
+
+impl Scaling {
+    // The picture at a whole-number scale; what windows open at.
+    pub fn window_size(&self, scale: u32) -> (u32, u32) {
+        let width = Frame::WIDTH as f64 * self.aspect.width_factor() * scale as f64;
+        (width.round() as u32, Frame::HEIGHT as u32 * scale)
+    }
+
+    pub fn viewport(&self, width: u32, height: u32) -> Viewport {
+        if !self.letterbox {
+            return Viewport {
+                x: 0,
+                y: 0,
+                width,
+                height,
+            };
+        }
+
+        let source_width = Frame::WIDTH as f64 * self.aspect.width_factor();
+        let source_height = Frame::HEIGHT as f64;
+        let mut scale = (width as f64 / source_width).min(height as f64 / source_height);
+        if self.integer && scale >= 1.0 {
+            scale = scale.floor();
+        }
+        let view_width = ((source_width * scale).round() as u32).min(width);
+        let view_height = ((source_height * scale).round() as u32).min(height);
+        Viewport {
+            x: (width - view_width) / 2,
+            y: (height - view_height) / 2,
+            width: view_width,
+            height: view_height,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_fit_and_integer_scaling() {
+        let fit = Scaling::default();
+        assert_eq!(
+            fit.viewport(1000, 720),
+            Viewport {
+                x: 116,
+                y: 0,
+                width: 768,
+                height: 720
+            }
+        );
+        assert_eq!(fit.viewport(700, 700).height, 656);
+
+        let integer = Scaling {
+            integer: true,
+            ..Scaling::default()
+        };
+        assert_eq!(
+            integer.viewport(700, 700),
+            Viewport {
+                x: 94,
+                y: 110,
+                width: 512,
+                height: 480
+            }
+        );
+        // too small for 1x: fit anyway
+        assert_eq!(integer.viewport(128, 120).width, 128);
+    }
+
+    #[test]
+    fn test_ntsc_pixels_and_stretch() {
+        let ntsc = Scaling {
+            aspect: PixelAspect::Ntsc,
+            integer: true,
+            letterbox: true,
+        };
+        assert_eq!(ntsc.window_size(3), (878, 720));
+        let view = ntsc.viewport(1920, 1080);
+        assert_eq!((view.width, view.height), (1170, 960));
+        assert_eq!((view.x, view.y), (375, 60));
+
+        let stretch = Scaling {
+            letterbox: false,
+            ..ntsc
+        };
+        assert_eq!(stretch.viewport(640, 200).width, 640);
+        assert_eq!(stretch.viewport(640, 200).height, 200);
+    }
+}
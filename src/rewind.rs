@@ -0,0 +1,215 @@
+// Rewinding keeps a savestate every couple of frames for up to a minute or
+// so, which would be tens of megabytes stored whole. Two states a few
+// frames apart are almost the same bytes, though, so only the newest one is
+// kept whole and each older one is stored as the difference from the one
+// after it: the two XORed together, which is mostly zeros, with the runs
+// of zeros squeezed out. Stepping back undoes the newest difference, so
+// going back costs the same however long the buffer is.
+//
+// Memory is bounded two ways: by a number of states, which sets how far
+// back rewinding goes, and by a byte budget for when the game churns
+// through memory and the differences get big. Either way the oldest states
+// go first.
+
+use std::collections::VecDeque;
+
+#[derive(Debug)]
+pub struct RewindBuffer {
+    capacity: usize,
+    budget: usize,
+    newest: Option<Vec<u8>>,
+    // deltas[i] turns state i + 1 back into state i, oldest first
+    deltas: VecDeque<Vec<u8>>,
+    delta_bytes: usize,
+}
+
+impl RewindBuffer {
+    // Up to `capacity` states in about `budget` bytes. A capacity of 0
+    // keeps nothing.
+    pub fn new(capacity: usize, budget: usize) -> Self {
+        RewindBuffer {
+            capacity,
+            budget,
+            newest: None,
+            deltas: VecDeque::new(),
+            delta_bytes: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.newest.as_ref().map_or(0, |_| self.deltas.len() + 1)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.newest.is_none()
+    }
+
+    // What the states take up now.
+    pub fn memory(&self) -> usize {
+        self.newest.as_ref().map_or(0, Vec::len) + self.delta_bytes
+    }
+
+    pub fn clear(&mut self) {
+        self.newest = None;
+        self.deltas.clear();
+        self.delta_bytes = 0;
+    }
+
+    pub fn push(&mut self, state: Vec<u8>) {
+        if self.capacity == 0 {
+            return;
+        }
+        if let Some(newest) = self.newest.take() {
+            let delta = encode_delta(&newest, &state);
+            self.delta_bytes += delta.len();
+            self.deltas.push_back(delta);
+        }
+        self.newest = Some(state);
+        while self.len() > self.capacity || (!self.deltas.is_empty() && self.memory() > self.budget)
+        {
+            let oldest = self.deltas.pop_front().unwrap();
+            self.delta_bytes -= oldest.len();
+        }
+    }
+
+    // The newest state, which is then forgotten.
+    pub fn pop(&mut self) -> Option<Vec<u8>> {
+        let newest = self.newest.take()?;
+        if let Some(delta) = self.deltas.pop_back() {
+            self.delta_bytes -= delta.len();
+            self.newest = Some(apply_delta(&newest, &delta));
+        }
+        Some(newest)
+    }
+}
+
+fn write_varint(out: &mut Vec<u8>, mut value: usize) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn read_varint(data: &[u8], at: &mut usize) -> usize {
+    let mut value = 0;
+    let mut shift = 0;
+    while let Some(&byte) = data.get(*at) {
+        *at += 1;
+        value |= ((byte & 0x7F) as usize) << shift;
+        if byte & 0x80 == 0 {
+            break;
+        }
+        shift += 7;
+    }
+    value
+}
+
+// The old state's length, then (zeros to skip, bytes that differ, the
+// XORed bytes) until the rest is all zeros. States can differ in length,
+// so the shorter one counts as padded with zeros.
+fn encode_delta(old: &[u8], new: &[u8]) -> Vec<u8> {
+    let xor = |i: usize| old.get(i).copied().unwrap_or(0) ^ new.get(i).copied().unwrap_or(0);
+    let len = old.len().max(new.len());
+    let mut out = Vec::new();
+    write_varint(&mut out, old.len());
+    let mut i = 0;
+    while i < len {
+        let zeros_start = i;
+        while i < len && xor(i) == 0 {
+            i += 1;
+        }
+        if i == len {
+            break;
+        }
+        let literal_start = i;
+        // a lone zero between changes is cheaper kept than a new run
+        while i < len && (xor(i) != 0 || (i + 1 < len && xor(i + 1) != 0)) {
+            i += 1;
+        }
+        write_varint(&mut out, literal_start - zeros_start);
+        write_varint(&mut out, i - literal_start);
+        out.extend((literal_start..i).map(xor));
+    }
+    out
+}
+
+fn apply_delta(new: &[u8], delta: &[u8]) -> Vec<u8> {
+    let mut at = 0;
+    let old_len = read_varint(delta, &mut at);
+    let mut old = new.to_vec();
+    old.resize(old_len.max(new.len()), 0);
+    let mut i = 0;
+    while at < delta.len() {
+        i += read_varint(delta, &mut at);
+        let literal = read_varint(delta, &mut at);
+        for (byte, xor) in old[i..i + literal].iter_mut().zip(&delta[at..at + literal]) {
+            *byte ^= xor;
+        }
+        i += literal;
+        at += literal;
+    }
+    old.truncate(old_len);
+    old
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // A few kilobytes with a counter ticking over and the odd byte
+    // changed, the way RAM looks from one frame to the next.
+    fn state(frame: usize) -> Vec<u8> {
+        let mut state = vec![0x55; 4096];
+        state[..8].copy_from_slice(&(frame as u64).to_le_bytes());
+        state[300 + frame % 50] = frame as u8;
+        state[4000] = 0;
+        // the length moves now and then, as varints do
+        state.truncate(4096 - frame / 7);
+        state
+    }
+
+    #[test]
+    fn test_states_come_back_newest_first() {
+        let mut buffer = RewindBuffer::new(100, usize::MAX);
+        for frame in 0..60 {
+            buffer.push(state(frame));
+        }
+        assert_eq!(buffer.len(), 60);
+        // one whole state and small differences
+        assert!(buffer.memory() < 4096 + 60 * 32, "{}", buffer.memory());
+        for frame in (0..60).rev() {
+            assert_eq!(buffer.pop(), Some(state(frame)));
+        }
+        assert_eq!(buffer.pop(), None);
+        assert!(buffer.is_empty());
+        assert_eq!(buffer.memory(), 0);
+    }
+
+    #[test]
+    fn test_oldest_states_go_first() {
+        let mut buffer = RewindBuffer::new(10, usize::MAX);
+        for frame in 0..25 {
+            buffer.push(state(frame));
+        }
+        assert_eq!(buffer.len(), 10);
+        let mut popped = Vec::new();
+        while let Some(state) = buffer.pop() {
+            popped.push(state);
+        }
+        assert_eq!(popped.last(), Some(&state(15)));
+
+        // a budget that fits about one big difference
+        let mut buffer = RewindBuffer::new(100, 3 * 4096);
+        for frame in 0..5u8 {
+            buffer.push(vec![frame.wrapping_mul(0x3B) | 1; 4096]);
+        }
+        assert!(buffer.memory() <= 3 * 4096);
+        assert_eq!(buffer.len(), 2);
+        assert_eq!(buffer.pop().unwrap()[0], 4u8.wrapping_mul(0x3B) | 1);
+
+        let mut off = RewindBuffer::new(0, usize::MAX);
+        off.push(state(0));
+        assert!(off.is_empty());
+    }
+}
@@ -0,0 +1,92 @@
+// The one source of randomness in the machine. Whatever the core models
+// as noise (today the contents of RAM at power-on, see RamInit) draws from
+// the bus's Rng and nothing else, so a seed gives the same "random"
+// console every time: across runs, in a movie that records its seed, and
+// after a savestate, whose "RNG " section carries the generator's state.
+// Nothing here reads a clock or asks the host; the seed comes from the
+// config, the command line or a movie.
+//
+// SplitMix64: tiny, fast, and its whole state is one u64, so it costs a
+// savestate eight bytes. Not for anything that needs to be unpredictable.
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Rng { state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    pub fn next_u8(&mut self) -> u8 {
+        (self.next_u64() >> 56) as u8
+    }
+
+    pub fn fill(&mut self, bytes: &mut [u8]) {
+        for chunk in bytes.chunks_mut(8) {
+            let random = self.next_u64().to_le_bytes();
+            chunk.copy_from_slice(&random[..chunk.len()]);
+        }
+    }
+}
+
+// What the CPU's RAM holds at power-on. Real consoles come up with
+// whatever the chips settle to, mostly but not reliably runs of $00 and
+// $FF; a few games read it before writing and behave differently for it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RamInit {
+    #[default]
+    Zeros,
+    Ones,
+    // from the seed
+    Random,
+}
+
+impl RamInit {
+    pub fn fill(self, ram: &mut [u8], rng: &mut Rng) {
+        match self {
+            RamInit::Zeros => ram.fill(0),
+            RamInit::Ones => ram.fill(0xFF),
+            RamInit::Random => rng.fill(ram),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_seeds_repeat() {
+        let (mut first, mut second) = (Rng::new(7), Rng::new(7));
+        let mut ram = [0; 13];
+        first.fill(&mut ram);
+        let mut again = [0; 13];
+        second.fill(&mut again);
+        assert_eq!(ram, again);
+        assert_ne!(ram, [0; 13]);
+        assert_eq!(first, second);
+        assert_ne!(Rng::new(8).next_u64(), Rng::new(7).next_u64());
+    }
+
+    #[test]
+    fn test_ram_init() {
+        let mut ram = [0x55; 4];
+        RamInit::Ones.fill(&mut ram, &mut Rng::default());
+        assert_eq!(ram, [0xFF; 4]);
+        RamInit::Zeros.fill(&mut ram, &mut Rng::default());
+        assert_eq!(ram, [0; 4]);
+    }
+}
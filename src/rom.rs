@@ -0,0 +1,186 @@
+// The bytes of a ROM image, shared rather than copied. A Rom is a range of
+// some backing store that stays alive as long as any Rom into it does, so
+// parsing a cartridge slices its PRG and CHR out of the file instead of
+// copying them, and cloning a Cartridge (or a board) for another instance
+// costs a reference count. A host running many machines off one game, or
+// a WASM embedder with the file already in memory, keeps one copy of it.
+//
+// The backing store can be:
+//
+//   &'static [u8]       include_bytes!, or a ROM baked into firmware
+//   Arc<[u8]>           a buffer the host shares with its own code
+//   Vec<u8>             a file read the usual way; moved, not copied
+//   anything AsRef      e.g. a memory-mapped file (memmap2::Mmap), which
+//                       the OS then pages in and shares between processes
+//
+// Borrowed bytes that don't live forever (a &[u8] from an FFI caller) are
+// copied once, as the machine outlives the borrow.
+
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::fmt;
+use core::ops::{Deref, Range};
+
+#[derive(Clone)]
+pub struct Rom {
+    store: Store,
+    range: Range<usize>,
+}
+
+#[derive(Clone)]
+enum Store {
+    Static(&'static [u8]),
+    Shared(Arc<[u8]>),
+    Vec(Arc<Vec<u8>>),
+    Owner(Arc<dyn AsRef<[u8]> + Send + Sync>),
+}
+
+impl Store {
+    fn bytes(&self) -> &[u8] {
+        match self {
+            Store::Static(bytes) => bytes,
+            Store::Shared(bytes) => bytes,
+            Store::Vec(bytes) => bytes,
+            Store::Owner(owner) => (**owner).as_ref(),
+        }
+    }
+}
+
+impl Rom {
+    fn new(store: Store) -> Self {
+        let len = store.bytes().len();
+        Rom {
+            store,
+            range: 0..len,
+        }
+    }
+
+    pub fn from_static(bytes: &'static [u8]) -> Self {
+        Rom::new(Store::Static(bytes))
+    }
+
+    // For stores that aren't one of the above, a memory map most of all.
+    // What `owner` derefs to mustn't change while it's alive.
+    pub fn from_owner(owner: impl AsRef<[u8]> + Send + Sync + 'static) -> Self {
+        Rom::new(Store::Owner(Arc::new(owner)))
+    }
+
+    // Part of this Rom, sharing its store.
+    pub fn slice(&self, range: Range<usize>) -> Rom {
+        assert!(
+            range.start <= range.end && range.end <= self.len(),
+            "{:?} is outside a {} byte ROM",
+            range,
+            self.len()
+        );
+        Rom {
+            store: self.store.clone(),
+            range: self.range.start + range.start..self.range.start + range.end,
+        }
+    }
+
+    // Whether both are views of the same store.
+    pub fn shares_store(&self, other: &Rom) -> bool {
+        core::ptr::eq(self.store.bytes(), other.store.bytes())
+    }
+}
+
+impl Deref for Rom {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.store.bytes()[self.range.clone()]
+    }
+}
+
+impl AsRef<[u8]> for Rom {
+    fn as_ref(&self) -> &[u8] {
+        self
+    }
+}
+
+impl Default for Rom {
+    fn default() -> Self {
+        Rom::from_static(&[])
+    }
+}
+
+impl From<Vec<u8>> for Rom {
+    fn from(bytes: Vec<u8>) -> Self {
+        Rom::new(Store::Vec(Arc::new(bytes)))
+    }
+}
+
+impl From<Arc<[u8]>> for Rom {
+    fn from(bytes: Arc<[u8]>) -> Self {
+        Rom::new(Store::Shared(bytes))
+    }
+}
+
+impl From<&[u8]> for Rom {
+    fn from(bytes: &[u8]) -> Self {
+        Rom::new(Store::Shared(Arc::from(bytes)))
+    }
+}
+
+impl PartialEq for Rom {
+    fn eq(&self, other: &Rom) -> bool {
+        **self == **other
+    }
+}
+
+impl Eq for Rom {}
+
+impl fmt::Debug for Rom {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Rom({} bytes)", self.len())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_slices_share_the_store() {
+        let rom = Rom::from((0..=255).collect::<Vec<u8>>());
+        let prg = rom.slice(16..32);
+        let inner = prg.slice(4..8);
+        assert_eq!(*inner, [20, 21, 22, 23]);
+        assert!(inner.shares_store(&rom));
+        assert!(inner.clone().shares_store(&prg));
+
+        // equal bytes from elsewhere are equal, but not shared
+        let copy = Rom::from(&rom[20..24]);
+        assert_eq!(copy, inner);
+        assert!(!copy.shares_store(&inner));
+    }
+
+    #[test]
+    fn test_stores() {
+        struct Mapped([u8; 4]);
+        impl AsRef<[u8]> for Mapped {
+            fn as_ref(&self) -> &[u8] {
+                &self.0
+            }
+        }
+
+        let shared: Arc<[u8]> = Arc::from(&[1, 2, 3, 4][..]);
+        let roms = [
+            Rom::from_static(&[1, 2, 3, 4]),
+            Rom::from(shared.clone()),
+            Rom::from(alloc::vec![1, 2, 3, 4]),
+            Rom::from_owner(Mapped([1, 2, 3, 4])),
+        ];
+        for rom in &roms {
+            assert_eq!(rom.slice(1..3), Rom::from_static(&[2, 3]));
+        }
+        assert_eq!(roms[1].as_ptr(), shared.as_ptr());
+    }
+
+    #[test]
+    #[should_panic(expected = "2..5 is outside a 4 byte ROM")]
+    fn test_slice_past_the_end() {
+        Rom::from_static(&[0; 4]).slice(2..5);
+    }
+}
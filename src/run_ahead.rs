@@ -0,0 +1,209 @@
+// Run-ahead hides the frames of lag a game spends between reading the pad
+// and showing the result. Every host frame the machine is run to the
+// frame that would be shown N frames from now, assuming the player keeps
+// holding the same buttons, and that frame is presented instead.
+//
+// States after the last real frame and each predicted one are kept. While
+// the input matches the prediction only one new frame has to be run; when
+// it changes, emulation rolls back to the last real frame and the
+// prediction is redone with the new input. This needs savestates that are
+// cheap to take and restore, and completely deterministic emulation.
+
+use std::collections::VecDeque;
+
+use crate::movie::FrameInput;
+
+pub trait Rollback {
+    type State;
+
+    fn save_state(&mut self) -> Self::State;
+    fn load_state(&mut self, state: &Self::State);
+    // `present` is false for frames whose video and audio would be thrown
+    // away anyway, so the target can skip producing them.
+    fn run_frame(&mut self, input: &FrameInput, present: bool);
+}
+
+#[derive(Debug)]
+pub struct RunAhead<S> {
+    frames: usize,
+    predicted: Option<FrameInput>,
+    // states[0] is after the last real frame, states[k] after k predicted
+    // frames on top of it
+    states: VecDeque<S>,
+    rollbacks: u64,
+}
+
+impl<S> RunAhead<S> {
+    pub fn new(frames: usize) -> Self {
+        RunAhead {
+            frames,
+            predicted: None,
+            states: VecDeque::new(),
+            rollbacks: 0,
+        }
+    }
+
+    pub fn frames(&self) -> usize {
+        self.frames
+    }
+
+    // Takes effect on the next frame.
+    pub fn set_frames(&mut self, frames: usize) {
+        if frames != self.frames {
+            self.frames = frames;
+            self.predicted = None;
+        }
+    }
+
+    // Forget the prediction after the machine state was changed behind our
+    // back (savestate load, reset, new ROM). Whatever state it is in now is
+    // taken as real.
+    pub fn invalidate(&mut self) {
+        self.predicted = None;
+        self.states.clear();
+    }
+
+    // State after the last real frame; this, not the machine's current
+    // state, is what should go into user savestates and movies.
+    pub fn confirmed_state(&self) -> Option<&S> {
+        self.states.front()
+    }
+
+    pub fn rollbacks(&self) -> u64 {
+        self.rollbacks
+    }
+
+    pub fn run_frame<T: Rollback<State = S>>(&mut self, target: &mut T, input: FrameInput) {
+        if self.frames == 0 {
+            if let Some(confirmed) = self.states.front() {
+                target.load_state(confirmed);
+                self.states.clear();
+            }
+            self.predicted = None;
+            target.run_frame(&input, true);
+            return;
+        }
+
+        if self.predicted == Some(input) && self.states.len() == self.frames + 1 {
+            // the first predicted frame was right, so it becomes real
+            self.states.pop_front();
+            target.run_frame(&input, true);
+            self.states.push_back(target.save_state());
+            return;
+        }
+
+        if let Some(confirmed) = self.states.front() {
+            target.load_state(confirmed);
+            if self.predicted.is_some() {
+                self.rollbacks += 1;
+            }
+        }
+        self.states.clear();
+
+        target.run_frame(&input, false);
+        self.states.push_back(target.save_state());
+        for ahead in 1..=self.frames {
+            target.run_frame(&input, ahead == self.frames);
+            self.states.push_back(target.save_state());
+        }
+        self.predicted = Some(input);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::input::joypad::JoypadButton;
+
+    // A "game" whose picture is a hash of every input it has seen.
+    #[derive(Default)]
+    struct Machine {
+        frame: u64,
+        hash: u64,
+        presented: Vec<(u64, u64)>,
+        frames_run: usize,
+    }
+
+    impl Rollback for Machine {
+        type State = (u64, u64);
+
+        fn save_state(&mut self) -> (u64, u64) {
+            (self.frame, self.hash)
+        }
+
+        fn load_state(&mut self, state: &(u64, u64)) {
+            (self.frame, self.hash) = *state;
+        }
+
+        fn run_frame(&mut self, input: &FrameInput, present: bool) {
+            self.frame += 1;
+            self.hash = self.hash.wrapping_mul(31) + input.pads[0].bits() as u64;
+            self.frames_run += 1;
+            if present {
+                self.presented.push((self.frame, self.hash));
+            }
+        }
+    }
+
+    fn pad(buttons: JoypadButton) -> FrameInput {
+        let mut input = FrameInput::default();
+        input.pads[0] = buttons;
+        input
+    }
+
+    #[test]
+    fn test_presents_future_frame() {
+        let inputs = [
+            JoypadButton::empty(),
+            JoypadButton::empty(),
+            JoypadButton::RIGHT,
+            JoypadButton::RIGHT,
+            JoypadButton::RIGHT | JoypadButton::BUTTON_A,
+            JoypadButton::empty(),
+        ];
+        let mut machine = Machine::default();
+        let mut run_ahead = RunAhead::new(2);
+
+        for (i, buttons) in inputs.iter().enumerate() {
+            run_ahead.run_frame(&mut machine, pad(*buttons));
+
+            // what a plain emulator would show two frames later if the
+            // input stayed the same
+            let mut reference = Machine::default();
+            for b in &inputs[..=i] {
+                reference.run_frame(&pad(*b), false);
+            }
+            let confirmed = (reference.frame, reference.hash);
+            for _ in 0..2 {
+                reference.run_frame(&pad(*buttons), false);
+            }
+
+            assert_eq!(machine.presented[i], (reference.frame, reference.hash));
+            assert_eq!(run_ahead.confirmed_state(), Some(&confirmed));
+        }
+        assert_eq!(run_ahead.rollbacks(), 3);
+    }
+
+    #[test]
+    fn test_steady_input_runs_one_frame() {
+        let mut machine = Machine::default();
+        let mut run_ahead = RunAhead::new(3);
+        run_ahead.run_frame(&mut machine, pad(JoypadButton::UP));
+        assert_eq!(machine.frames_run, 4);
+
+        run_ahead.run_frame(&mut machine, pad(JoypadButton::UP));
+        assert_eq!(machine.frames_run, 5);
+        assert_eq!(run_ahead.rollbacks(), 0);
+    }
+
+    #[test]
+    fn test_disabling_returns_to_real_state() {
+        let mut machine = Machine::default();
+        let mut run_ahead = RunAhead::new(2);
+        run_ahead.run_frame(&mut machine, pad(JoypadButton::UP));
+        run_ahead.set_frames(0);
+        run_ahead.run_frame(&mut machine, pad(JoypadButton::UP));
+
+        assert_eq!(machine.frame, 2);
+    }
+}
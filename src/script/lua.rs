@@ -0,0 +1,574 @@
+// Lua scripts with FCEUX's API, on mlua and the Lua 5.4 it builds in.
+// Loading runs the script's body once; what it leaves behind is the
+// functions it gave emu.registerbefore and emu.registerafter, which run
+// as Scripts callbacks around every frame. Bound so far:
+//
+//   memory    readbyte, readbytesigned, readbyteunsigned, readword,
+//             writebyte, getregister, setregister
+//   joypad    get/read(player), set/write(player, {A=true, up=true, ...})
+//   savestate create/object, save(state), load(state)
+//   gui       text(x, y, text), box/drawbox(x1, y1, x2, y2, fill, outline),
+//             pixel/setpixel(x, y, color)
+//   emu       registerbefore, registerafter, frameadvance, framecount,
+//             pause, message
+//
+// Players count from 1 and colours are "#RRGGBB" or a name, as FCEUX has
+// them. Reads peek, as FCEUX's do, so watching a register doesn't
+// acknowledge it.
+//
+// The body runs as a coroutine, and emu.frameadvance yields it: it's
+// resumed after each frame, once the registerafter function has run, so
+// a script can be one loop that does its work and waits for the next
+// frame, as FCEUX's usually are. The yield is Lua's own, as mlua has no
+// way for a Rust function to yield.
+//
+// An error in a callback, or in the body, goes to the OSD and stops it,
+// rather than coming back every frame.
+
+use std::cell::Cell;
+use std::fs;
+use std::path::Path;
+use std::ptr;
+use std::rc::Rc;
+
+use mlua::{AnyUserData, Function, Lua, Table, Thread, ThreadStatus, UserData, Value as LuaValue};
+
+use super::{Api, Drawing, Register, Scripts};
+use crate::emulator::Emulator;
+use crate::input::joypad::JoypadButton;
+
+pub use mlua::Error as LuaError;
+
+type Rgb = (u8, u8, u8);
+
+const BUTTONS: [(&str, JoypadButton); 8] = [
+    ("A", JoypadButton::BUTTON_A),
+    ("B", JoypadButton::BUTTON_B),
+    ("select", JoypadButton::SELECT),
+    ("start", JoypadButton::START),
+    ("up", JoypadButton::UP),
+    ("down", JoypadButton::DOWN),
+    ("left", JoypadButton::LEFT),
+    ("right", JoypadButton::RIGHT),
+];
+
+const COLORS: [(&str, Rgb); 9] = [
+    ("white", (0xFF, 0xFF, 0xFF)),
+    ("black", (0x00, 0x00, 0x00)),
+    ("red", (0xFF, 0x00, 0x00)),
+    ("green", (0x00, 0xFF, 0x00)),
+    ("blue", (0x00, 0x00, 0xFF)),
+    ("yellow", (0xFF, 0xFF, 0x00)),
+    ("orange", (0xFF, 0x80, 0x00)),
+    ("purple", (0x80, 0x00, 0xFF)),
+    ("gray", (0x80, 0x80, 0x80)),
+];
+
+// The Api of the call into Lua in progress, null outside one. The
+// functions bound to it live as long as the Lua state, and a script can
+// keep them anywhere, so they can't borrow the Api; they find it here.
+// `busy` is set while one of them has it, so that if Lua code ever runs
+// in the middle (a metamethod, say) and calls another, that's an error
+// rather than a second &mut Api.
+struct Current {
+    api: Cell<*mut Api<'static>>,
+    busy: Cell<bool>,
+}
+
+impl Current {
+    fn new() -> Self {
+        Current {
+            api: Cell::new(ptr::null_mut()),
+            busy: Cell::new(false),
+        }
+    }
+
+    fn with<T>(&self, f: impl FnOnce(&mut Api) -> T) -> mlua::Result<T> {
+        let api = self.api.get();
+        if api.is_null() {
+            return Err(LuaError::runtime(
+                "the emulator is only there during a callback",
+            ));
+        }
+        if self.busy.replace(true) {
+            return Err(LuaError::runtime("the emulator is busy with another call"));
+        }
+        struct Done<'a>(&'a Cell<bool>);
+        impl Drop for Done<'_> {
+            fn drop(&mut self) {
+                self.0.set(false);
+            }
+        }
+        let _done = Done(&self.busy);
+        // SAFETY: set only by Engine::enter, which clears it before the
+        // Api it points to goes away, and `busy` keeps this the only
+        // reference to it
+        Ok(f(unsafe { &mut *api }))
+    }
+}
+
+// Where emu.registerbefore and emu.registerafter keep their functions,
+// and the body its coroutine while it waits in emu.frameadvance, in
+// Lua's registry rather than anything of ours that Lua would then keep
+// alive in turn.
+const BEFORE: &str = "emu.registerbefore";
+const AFTER: &str = "emu.registerafter";
+const BODY: &str = "emu.frameadvance";
+
+// savestate.create's object, empty until saved to.
+#[derive(Default)]
+struct SavedState(Option<Vec<u8>>);
+
+impl UserData for SavedState {}
+
+#[derive(Clone)]
+struct Engine {
+    lua: Lua,
+    name: Rc<str>,
+    current: Rc<Current>,
+}
+
+impl Engine {
+    fn new(name: &str) -> mlua::Result<Engine> {
+        let engine = Engine {
+            lua: Lua::new(),
+            name: name.into(),
+            current: Rc::new(Current::new()),
+        };
+        engine.bind()?;
+        Ok(engine)
+    }
+
+    // Runs `f` with `api` where the bound functions can get at it.
+    fn enter<T>(&self, api: &mut Api, f: impl FnOnce() -> mlua::Result<T>) -> mlua::Result<T> {
+        struct Leave<'a>(&'a Current);
+        impl Drop for Leave<'_> {
+            fn drop(&mut self) {
+                self.0.api.set(ptr::null_mut());
+            }
+        }
+        self.current.api.set(ptr::from_mut(api).cast());
+        let _leave = Leave(&self.current);
+        f()
+    }
+
+    fn run_hook(&self, api: &mut Api, hook: &str) {
+        let Ok(Some(function)) = self.lua.named_registry_value::<Option<Function>>(hook) else {
+            return;
+        };
+        if let Err(err) = self.enter(api, || function.call::<()>(())) {
+            let _ = self.lua.unset_named_registry_value(hook);
+            api.message(format!("{}: {}", self.name, err));
+        }
+    }
+
+    // Runs the body, or carries on with it from emu.frameadvance, until it
+    // waits for the next frame or ends.
+    fn resume_body(&self, api: &mut Api) -> mlua::Result<()> {
+        let Some(body) = self.lua.named_registry_value::<Option<Thread>>(BODY)? else {
+            return Ok(());
+        };
+        let resumed = self.enter(api, || body.resume::<()>(()));
+        if resumed.is_err() || body.status() != ThreadStatus::Resumable {
+            self.lua.unset_named_registry_value(BODY)?;
+        }
+        resumed
+    }
+
+    fn bind(&self) -> mlua::Result<()> {
+        let lua = &self.lua;
+        let globals = lua.globals();
+        globals.set("memory", self.memory()?)?;
+        globals.set("joypad", self.joypad()?)?;
+        globals.set("savestate", self.savestate()?)?;
+        globals.set("gui", self.gui()?)?;
+        globals.set("emu", self.emu()?)?;
+        Ok(())
+    }
+
+    // A function that gets the Api, with its arguments.
+    fn function<A, R>(
+        &self,
+        f: impl Fn(&mut Api, A) -> mlua::Result<R> + 'static,
+    ) -> mlua::Result<Function>
+    where
+        A: mlua::FromLuaMulti,
+        R: mlua::IntoLuaMulti,
+    {
+        let current = self.current.clone();
+        self.lua
+            .create_function(move |_, args: A| current.with(|api| f(api, args))?)
+    }
+
+    fn memory(&self) -> mlua::Result<Table> {
+        let memory = self.lua.create_table()?;
+        let read = self.function(|api, addr: u16| Ok(api.peek_byte(addr)))?;
+        memory.set("readbyte", read.clone())?;
+        memory.set("readbyteunsigned", read)?;
+        memory.set(
+            "readbytesigned",
+            self.function(|api, addr: u16| Ok(api.peek_byte(addr) as i8))?,
+        )?;
+        memory.set(
+            "readword",
+            self.function(|api, addr: u16| Ok(api.peek_word(addr)))?,
+        )?;
+        memory.set(
+            "writebyte",
+            self.function(|api, (addr, value): (u16, i64)| {
+                api.write_byte(addr, value as u8);
+                Ok(())
+            })?,
+        )?;
+        memory.set(
+            "getregister",
+            self.function(|api, name: String| Ok(api.register(register(&name)?)))?,
+        )?;
+        memory.set(
+            "setregister",
+            self.function(|api, (name, value): (String, i64)| {
+                api.set_register(register(&name)?, value as u16);
+                Ok(())
+            })?,
+        )?;
+        Ok(memory)
+    }
+
+    fn joypad(&self) -> mlua::Result<Table> {
+        let joypad = self.lua.create_table()?;
+        let current = self.current.clone();
+        let get = self.lua.create_function(move |lua, player: usize| {
+            let buttons =
+                current.with(|api| player_index(player).map(|player| api.buttons(player)))??;
+            let table = lua.create_table()?;
+            for (name, button) in BUTTONS {
+                table.set(name, buttons.contains(button))?;
+            }
+            Ok(table)
+        })?;
+        joypad.set("get", get.clone())?;
+        joypad.set("read", get)?;
+        // the table is read before the Api is taken, as reading it can run
+        // the script's metamethods
+        let current = self.current.clone();
+        let set = self
+            .lua
+            .create_function(move |_, (player, table): (usize, Table)| {
+                let mut buttons = JoypadButton::empty();
+                for (name, button) in BUTTONS {
+                    if table.get::<Option<bool>>(name)?.unwrap_or(false) {
+                        buttons |= button;
+                    }
+                }
+                let player = player_index(player)?;
+                current.with(|api| api.set_buttons(player, buttons))
+            })?;
+        joypad.set("set", set.clone())?;
+        joypad.set("write", set)?;
+        Ok(joypad)
+    }
+
+    fn savestate(&self) -> mlua::Result<Table> {
+        let savestate = self.lua.create_table()?;
+        let create = self
+            .lua
+            .create_function(|_, _: mlua::Variadic<LuaValue>| Ok(SavedState::default()))?;
+        savestate.set("create", create.clone())?;
+        savestate.set("object", create)?;
+        savestate.set(
+            "save",
+            self.function(|api, state: AnyUserData| {
+                state.borrow_mut::<SavedState>()?.0 = Some(api.save_state());
+                Ok(())
+            })?,
+        )?;
+        savestate.set(
+            "load",
+            self.function(|api, state: AnyUserData| {
+                let state = state.borrow::<SavedState>()?;
+                let bytes = state
+                    .0
+                    .as_deref()
+                    .ok_or_else(|| LuaError::runtime("nothing has been saved to that state"))?;
+                api.load_state(bytes).map_err(LuaError::external)
+            })?,
+        )?;
+        Ok(savestate)
+    }
+
+    fn gui(&self) -> mlua::Result<Table> {
+        let gui = self.lua.create_table()?;
+        gui.set(
+            "text",
+            self.function(|api, (x, y, text): (i64, i64, String)| {
+                if let (Some(x), Some(y)) = (coord(x), coord(y)) {
+                    api.draw(Drawing::Text { x, y, text });
+                }
+                Ok(())
+            })?,
+        )?;
+        let draw_box = self.function(
+            |api,
+             (x1, y1, x2, y2, fill, outline): (
+                i64,
+                i64,
+                i64,
+                i64,
+                Option<String>,
+                Option<String>,
+            )| {
+                let color = color(outline.or(fill).as_deref())?;
+                let [x1, y1, x2, y2] = [x1, y1, x2, y2].map(|at| at.max(0) as usize);
+                api.draw(Drawing::Box {
+                    x1,
+                    y1,
+                    x2,
+                    y2,
+                    color,
+                });
+                Ok(())
+            },
+        )?;
+        gui.set("box", draw_box.clone())?;
+        gui.set("drawbox", draw_box)?;
+        let pixel = self.function(|api, (x, y, color_name): (i64, i64, Option<String>)| {
+            let color = color(color_name.as_deref())?;
+            if let (Some(x), Some(y)) = (coord(x), coord(y)) {
+                api.draw(Drawing::Pixel { x, y, color });
+            }
+            Ok(())
+        })?;
+        gui.set("pixel", pixel.clone())?;
+        gui.set("setpixel", pixel)?;
+        Ok(gui)
+    }
+
+    fn emu(&self) -> mlua::Result<Table> {
+        let emu = self.lua.create_table()?;
+        for hook in [BEFORE, AFTER] {
+            let register = self
+                .lua
+                .create_function(move |lua, function: Option<Function>| {
+                    lua.set_named_registry_value(hook, function)
+                })?;
+            emu.set(hook.trim_start_matches("emu."), register)?;
+        }
+        let frameadvance = self
+            .lua
+            .load(
+                "local yield, running = coroutine.yield, coroutine.running
+                return function()
+                    local _, main = running()
+                    if main then
+                        error('emu.frameadvance is only for the body of a script', 2)
+                    end
+                    yield()
+                end",
+            )
+            .set_name("emu.frameadvance")
+            .eval::<Function>()?;
+        emu.set("frameadvance", frameadvance)?;
+        emu.set(
+            "framecount",
+            self.function(|api, ()| Ok(api.frame_count()))?,
+        )?;
+        emu.set(
+            "pause",
+            self.function(|api, ()| {
+                api.pause();
+                Ok(())
+            })?,
+        )?;
+        emu.set(
+            "message",
+            self.function(|api, text: String| {
+                api.message(text);
+                Ok(())
+            })?,
+        )?;
+        Ok(emu)
+    }
+}
+
+fn register(name: &str) -> mlua::Result<Register> {
+    Register::from_name(name)
+        .ok_or_else(|| LuaError::runtime(format!("no register called {:?}", name)))
+}
+
+fn player_index(player: usize) -> mlua::Result<usize> {
+    player
+        .checked_sub(1)
+        .ok_or_else(|| LuaError::runtime("players count from 1"))
+}
+
+// Off the top or left of the screen is off it altogether.
+fn coord(at: i64) -> Option<usize> {
+    usize::try_from(at).ok()
+}
+
+// White when there's none.
+fn color(name: Option<&str>) -> mlua::Result<Rgb> {
+    let Some(name) = name else {
+        return Ok(COLORS[0].1);
+    };
+    if let Some(hex) = name.strip_prefix('#').filter(|hex| hex.len() == 6) {
+        if let Ok(rgb) = u32::from_str_radix(hex, 16) {
+            return Ok(((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8));
+        }
+    }
+    COLORS
+        .iter()
+        .find(|(known, _)| known.eq_ignore_ascii_case(name))
+        .map(|&(_, rgb)| rgb)
+        .ok_or_else(|| LuaError::runtime(format!("no colour called {:?}", name)))
+}
+
+// Runs the script's body and hooks up whatever it registered. `name`
+// says where errors come from.
+pub fn load(
+    scripts: &mut Scripts,
+    emulator: &mut Emulator,
+    name: &str,
+    source: &str,
+) -> Result<(), LuaError> {
+    let engine = Engine::new(name)?;
+    let body = engine.lua.load(source).set_name(name).into_function()?;
+    let body = engine.lua.create_thread(body)?;
+    engine.lua.set_named_registry_value(BODY, body)?;
+    scripts.call(emulator, |api| engine.resume_body(api))?;
+    let before = engine.clone();
+    scripts.on_before_frame(move |api| before.run_hook(api, BEFORE));
+    scripts.on_after_frame(move |api| {
+        engine.run_hook(api, AFTER);
+        if let Err(err) = engine.resume_body(api) {
+            api.message(format!("{}: {}", engine.name, err));
+        }
+    });
+    Ok(())
+}
+
+pub fn load_file(
+    scripts: &mut Scripts,
+    emulator: &mut Emulator,
+    path: &Path,
+) -> Result<(), LuaError> {
+    let source = fs::read_to_string(path).map_err(LuaError::external)?;
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    load(scripts, emulator, &name, &source)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::emulator::test::counting_rom;
+    use crate::render::frame::Frame;
+
+    #[test]
+    fn test_runs_a_script() {
+        let mut emulator = Emulator::from_rom(&counting_rom()).unwrap();
+        let mut scripts = Scripts::new();
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("src/script/test.lua");
+        load_file(&mut scripts, &mut emulator, &path).unwrap();
+        assert_eq!(emulator.cpu().register_x, 0xFF);
+
+        for _ in 0..3 {
+            scripts.before_frame(&mut emulator);
+            emulator.run_frame();
+            scripts.after_frame(&mut emulator);
+        }
+        // the NMI from the end of a frame is taken in the next one
+        assert_eq!(scripts.take_messages(), ["count 64"]);
+        assert!(!scripts.take_pause());
+        let pressed = scripts.call(&mut emulator, |api| api.buttons(0));
+        assert_eq!(pressed, JoypadButton::START);
+        let mut frame = Frame::new();
+        scripts.draw(&mut frame);
+        assert_eq!(frame.pixel(3, 1), (0xFF, 0, 0));
+        assert_eq!(frame.pixel(10, 10), (0, 0xFF, 0));
+
+        scripts.before_frame(&mut emulator);
+        emulator.run_frame();
+        scripts.after_frame(&mut emulator);
+        assert!(scripts.take_pause());
+        assert_eq!(emulator.frame_count(), 2);
+        assert_eq!(emulator.cpu_mut().bus.peek(0x0000), 1);
+    }
+
+    #[test]
+    fn test_frameadvance_waits_for_the_next_frame() {
+        let mut emulator = Emulator::from_rom(&counting_rom()).unwrap();
+        let mut scripts = Scripts::new();
+        let source = "
+            for n = 1, 2 do
+                emu.frameadvance()
+                emu.message(n .. ' ' .. emu.framecount())
+            end
+            emu.registerbefore(function() emu.frameadvance() end)";
+        load(&mut scripts, &mut emulator, "loop.lua", source).unwrap();
+        assert!(scripts.take_messages().is_empty());
+        for _ in 0..3 {
+            scripts.before_frame(&mut emulator);
+            emulator.run_frame();
+            scripts.after_frame(&mut emulator);
+        }
+        let messages = scripts.take_messages();
+        assert_eq!(messages[..2], ["1 1", "2 2"]);
+        // not from a callback, and once the body is done that's that
+        assert_eq!(messages.len(), 3);
+        assert!(messages[2].contains("only for the body"), "{}", messages[2]);
+    }
+
+    #[test]
+    fn test_reads_peek_and_calls_dont_nest() {
+        let mut emulator = Emulator::from_rom(&counting_rom()).unwrap();
+        let mut scripts = Scripts::new();
+        let status = &mut emulator.cpu_mut().bus.ppu.status;
+        status.insert(crate::ppu::registers::StatusRegister::VBLANK_STARTED);
+        // a metamethod that gets at the emulator while joypad.set reads
+        let source = "
+            memory.readbyte(0x2002)
+            local held = setmetatable({}, {__index = function(_, name)
+                memory.writebyte(0x0010, 1)
+                return name == 'B'
+            end})
+            joypad.set(1, held)";
+        load(&mut scripts, &mut emulator, "peek.lua", source).unwrap();
+        assert_eq!(emulator.cpu_mut().bus.peek(0x2002) & 0x80, 0x80);
+        assert_eq!(emulator.cpu_mut().bus.peek(0x0010), 1);
+        let held = scripts.call(&mut emulator, |api| api.buttons(0));
+        assert_eq!(held, JoypadButton::BUTTON_B);
+
+        let current = Current::new();
+        scripts.call(&mut emulator, |api| {
+            current.api.set(ptr::from_mut(api).cast());
+            let nested = current.with(|_| current.with(|_| ()));
+            assert!(matches!(nested, Ok(Err(_))));
+            assert!(current.with(|_| ()).is_ok());
+            current.api.set(ptr::null_mut());
+        });
+    }
+
+    #[test]
+    fn test_errors_stop_the_callback() {
+        let mut emulator = Emulator::from_rom(&counting_rom()).unwrap();
+        let mut scripts = Scripts::new();
+        let source = "emu.registerafter(function() memory.getregister('q') end)";
+        load(&mut scripts, &mut emulator, "bad.lua", source).unwrap();
+        for _ in 0..2 {
+            emulator.run_frame();
+            scripts.after_frame(&mut emulator);
+        }
+        let messages = scripts.take_messages();
+        assert_eq!(messages.len(), 1);
+        assert!(messages[0].starts_with("bad.lua: "), "{}", messages[0]);
+        assert!(messages[0].contains("no register called \"q\""));
+
+        assert!(load(
+            &mut scripts,
+            &mut emulator,
+            "broken.lua",
+            "emu.registerafter("
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,370 @@
+// Scripting, after FCEUX's Lua API: a script registers callbacks to run
+// before and after every frame, and from them reads and writes memory and
+// registers, holds buttons down, saves and loads states and draws over
+// the picture. This is the host side, what an engine binds its functions
+// to; each call in Api stands for one of FCEUX's (memory.readbyte,
+// joypad.set, gui.text and so on), so a binding is a thin translation.
+// lua.rs is that binding for Lua, with the `lua` feature.
+//
+// Buttons a script sets last for the one frame, taking the place of the
+// player's, as joypad.set does. Drawings last until the next frame is
+// run, so a paused game keeps them and the script needn't draw again.
+
+#[cfg(feature = "lua")]
+pub mod lua;
+
+use crate::bus::Mem;
+use crate::emulator::Emulator;
+use crate::input::joypad::JoypadButton;
+use crate::input::keymap::MAX_PLAYERS;
+use crate::render::frame::Frame;
+use crate::render::osd::draw_text;
+use crate::state::StateError;
+
+type Address = u16;
+type Value = u8;
+type Rgb = (u8, u8, u8);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Register {
+    A,
+    X,
+    Y,
+    P,
+    Sp,
+    Pc,
+}
+
+impl Register {
+    // The names memory.getregister takes.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "a" => Some(Register::A),
+            "x" => Some(Register::X),
+            "y" => Some(Register::Y),
+            "p" => Some(Register::P),
+            "s" | "sp" => Some(Register::Sp),
+            "pc" => Some(Register::Pc),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Drawing {
+    Text {
+        x: usize,
+        y: usize,
+        text: String,
+    },
+    // corners inclusive, outlined
+    Box {
+        x1: usize,
+        y1: usize,
+        x2: usize,
+        y2: usize,
+        color: Rgb,
+    },
+    Pixel {
+        x: usize,
+        y: usize,
+        color: Rgb,
+    },
+}
+
+impl Drawing {
+    fn draw(&self, frame: &mut Frame) {
+        let mut put = |x: usize, y: usize, color| {
+            if x < Frame::WIDTH && y < Frame::HEIGHT {
+                frame.set_pixel(x, y, color);
+            }
+        };
+        match *self {
+            Drawing::Text { x, y, ref text } => draw_text(frame, x, y, text),
+            Drawing::Box {
+                x1,
+                y1,
+                x2,
+                y2,
+                color,
+            } => {
+                let (left, right) = (x1.min(x2), x1.max(x2));
+                let (top, bottom) = (y1.min(y2), y1.max(y2));
+                for x in left..=right {
+                    put(x, top, color);
+                    put(x, bottom, color);
+                }
+                for y in top..=bottom {
+                    put(left, y, color);
+                    put(right, y, color);
+                }
+            }
+            Drawing::Pixel { x, y, color } => put(x, y, color),
+        }
+    }
+}
+
+// What scripts leave for the host to pick up.
+#[derive(Debug, Default)]
+struct Requests {
+    buttons: [Option<JoypadButton>; MAX_PLAYERS],
+    drawings: Vec<Drawing>,
+    messages: Vec<String>,
+    pause: bool,
+}
+
+// What a callback can do, for the length of the call.
+pub struct Api<'a> {
+    emulator: &'a mut Emulator,
+    requests: &'a mut Requests,
+}
+
+impl Api<'_> {
+    // Reads have the side effects a CPU read would; peek doesn't.
+    pub fn read_byte(&mut self, addr: Address) -> Value {
+        self.emulator.cpu_mut().mem_read(addr)
+    }
+
+    pub fn peek_byte(&mut self, addr: Address) -> Value {
+        self.emulator.cpu_mut().bus.peek(addr)
+    }
+
+    // Little-endian.
+    pub fn read_word(&mut self, addr: Address) -> u16 {
+        self.emulator.cpu_mut().mem_read_u16(addr)
+    }
+
+    pub fn peek_word(&mut self, addr: Address) -> u16 {
+        let lo = self.peek_byte(addr);
+        let hi = self.peek_byte(addr.wrapping_add(1));
+        u16::from_le_bytes([lo, hi])
+    }
+
+    pub fn write_byte(&mut self, addr: Address, value: Value) {
+        self.emulator.cpu_mut().mem_write(addr, value);
+    }
+
+    pub fn register(&self, register: Register) -> u16 {
+        let cpu = self.emulator.cpu();
+        match register {
+            Register::A => cpu.register_a as u16,
+            Register::X => cpu.register_x as u16,
+            Register::Y => cpu.register_y as u16,
+            Register::P => cpu.status as u16,
+            Register::Sp => cpu.stack_pointer as u16,
+            Register::Pc => cpu.program_counter,
+        }
+    }
+
+    // 8-bit registers take the low byte.
+    pub fn set_register(&mut self, register: Register, value: u16) {
+        let cpu = self.emulator.cpu_mut();
+        let byte = value as Value;
+        match register {
+            Register::A => cpu.register_a = byte,
+            Register::X => cpu.register_x = byte,
+            Register::Y => cpu.register_y = byte,
+            Register::P => cpu.status = byte,
+            Register::Sp => cpu.stack_pointer = byte,
+            Register::Pc => cpu.program_counter = value,
+        }
+    }
+
+    pub fn frame_count(&self) -> u64 {
+        self.emulator.frame_count()
+    }
+
+    // The buttons the game will see next, a script's if it set any.
+    pub fn buttons(&mut self, player: usize) -> JoypadButton {
+        if let Some(Some(buttons)) = self.requests.buttons.get(player) {
+            return *buttons;
+        }
+        self.emulator
+            .cpu_mut()
+            .bus
+            .controllers
+            .joypad(player)
+            .map_or(JoypadButton::empty(), |joypad| joypad.buttons())
+    }
+
+    // For the coming frame only.
+    pub fn set_buttons(&mut self, player: usize, buttons: JoypadButton) {
+        if let Some(slot) = self.requests.buttons.get_mut(player) {
+            *slot = Some(buttons);
+        }
+    }
+
+    pub fn save_state(&self) -> Vec<u8> {
+        self.emulator.save_state()
+    }
+
+    pub fn load_state(&mut self, state: &[u8]) -> Result<(), StateError> {
+        self.emulator.load_state(state)
+    }
+
+    // Pauses the game once the callback returns, as emu.pause does.
+    pub fn pause(&mut self) {
+        self.requests.pause = true;
+    }
+
+    // On the OSD, like the emulator's own messages.
+    pub fn message(&mut self, text: impl Into<String>) {
+        self.requests.messages.push(text.into());
+    }
+
+    pub fn draw(&mut self, drawing: Drawing) {
+        self.requests.drawings.push(drawing);
+    }
+
+    // Lets the engine get at anything Api doesn't cover.
+    pub fn emulator(&mut self) -> &mut Emulator {
+        self.emulator
+    }
+}
+
+pub type Callback = Box<dyn FnMut(&mut Api)>;
+
+#[derive(Default)]
+pub struct Scripts {
+    before_frame: Vec<Callback>,
+    after_frame: Vec<Callback>,
+    requests: Requests,
+}
+
+impl Scripts {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.before_frame.is_empty() && self.after_frame.is_empty()
+    }
+
+    // emu.registerbefore
+    pub fn on_before_frame(&mut self, callback: impl FnMut(&mut Api) + 'static) {
+        self.before_frame.push(Box::new(callback));
+    }
+
+    // emu.registerafter; an engine resumes a script waiting in
+    // emu.frameadvance from here
+    pub fn on_after_frame(&mut self, callback: impl FnMut(&mut Api) + 'static) {
+        self.after_frame.push(Box::new(callback));
+    }
+
+    // Drops every callback, for a script that stopped, and what it drew.
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+
+    // Runs `f` against the emulator outside any callback, e.g. for the
+    // body of a script as it's loaded.
+    pub fn call<T>(&mut self, emulator: &mut Emulator, f: impl FnOnce(&mut Api) -> T) -> T {
+        f(&mut Api {
+            emulator,
+            requests: &mut self.requests,
+        })
+    }
+
+    // Just before a frame runs, with the player's input already in.
+    pub fn before_frame(&mut self, emulator: &mut Emulator) {
+        self.requests.drawings.clear();
+        for callback in &mut self.before_frame {
+            callback(&mut Api {
+                emulator,
+                requests: &mut self.requests,
+            });
+        }
+        for (player, buttons) in self.requests.buttons.iter_mut().enumerate() {
+            if let Some(buttons) = buttons.take() {
+                emulator.set_buttons(player, buttons);
+            }
+        }
+    }
+
+    pub fn after_frame(&mut self, emulator: &mut Emulator) {
+        for callback in &mut self.after_frame {
+            callback(&mut Api {
+                emulator,
+                requests: &mut self.requests,
+            });
+        }
+    }
+
+    pub fn has_drawings(&self) -> bool {
+        !self.requests.drawings.is_empty()
+    }
+
+    pub fn draw(&self, frame: &mut Frame) {
+        for drawing in &self.requests.drawings {
+            drawing.draw(frame);
+        }
+    }
+
+    pub fn take_messages(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.requests.messages)
+    }
+
+    // Whether a script asked to pause since the last call.
+    pub fn take_pause(&mut self) -> bool {
+        std::mem::take(&mut self.requests.pause)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::emulator::test::counting_rom;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    #[test]
+    fn test_callbacks_see_and_steer_the_game() {
+        let mut emulator = Emulator::from_rom(&counting_rom()).unwrap();
+        let mut scripts = Scripts::new();
+        let seen = Rc::new(RefCell::new(Vec::new()));
+        let log = seen.clone();
+        scripts.on_before_frame(|api| {
+            api.set_buttons(0, JoypadButton::START);
+            api.draw(Drawing::Pixel {
+                x: 1,
+                y: 2,
+                color: (0xFF, 0, 0),
+            });
+        });
+        scripts.on_after_frame(move |api| {
+            let count = api.read_byte(0x0000);
+            log.borrow_mut().push(count);
+            if api.frame_count() == 3 {
+                api.write_byte(0x0000, 0x40);
+                api.pause();
+                api.message("three");
+            }
+        });
+
+        for _ in 0..4 {
+            scripts.before_frame(&mut emulator);
+            emulator.run_frame();
+            scripts.after_frame(&mut emulator);
+        }
+        // the NMI from the end of a frame is taken in the next one
+        assert_eq!(*seen.borrow(), [0, 1, 2, 0x41]);
+        assert!(scripts.take_pause());
+        assert!(!scripts.take_pause());
+        assert_eq!(scripts.take_messages(), ["three"]);
+
+        let pressed = scripts.call(&mut emulator, |api| api.buttons(0));
+        assert_eq!(pressed, JoypadButton::START);
+        assert!(scripts.has_drawings());
+        let mut frame = Frame::new();
+        scripts.draw(&mut frame);
+        assert_eq!(frame.pixel(1, 2), (0xFF, 0, 0));
+
+        let pc = scripts.call(&mut emulator, |api| {
+            api.set_register(Register::X, 0x1FF);
+            api.register(Register::Pc)
+        });
+        assert_eq!(emulator.cpu().register_x, 0xFF);
+        assert_eq!(pc, emulator.cpu().program_counter);
+        assert_eq!(Register::from_name("SP"), Some(Register::Sp));
+    }
+}
@@ -0,0 +1,26 @@
+-- Run by the test in lua.rs, against emulator::test::counting_rom, which
+-- counts NMIs at $0000.
+local saved = savestate.create()
+-- kept, as scripts do, and called later from a callback
+local readbyte = memory.readbyte
+
+memory.setregister("x", 0x1FF)
+
+emu.registerbefore(function()
+  joypad.set(1, {start = true, A = false})
+  gui.box(0, 0, 3, 3, "red")
+  gui.pixel(10, 10, "#00FF00")
+end)
+
+emu.registerafter(function()
+  local frame = emu.framecount()
+  if frame == 2 then
+    savestate.save(saved)
+  elseif frame == 3 then
+    memory.writebyte(0x0000, 0x40)
+    emu.message("count " .. readbyte(0x0000))
+  elseif frame == 4 then
+    savestate.load(saved)
+    emu.pause()
+  end
+end)
@@ -0,0 +1,897 @@
+// Savestates. A state is a small container of sections, one per part of
+// the machine, each serialized with serde into postcard's compact binary
+// encoding:
+//
+//     "NESS"           magic
+//     u16              container format
+//     u8 + bytes       version of the core that wrote it, for the curious
+//     sections, to the end:
+//         [u8; 4]      tag: "CPU ", "RAM ", "PPU ", "APU ", "INPT", "MAPR",
+//                      "VS  " for VS. System games, "PAL " for PAL
+//                      consoles, "OVCL" when a state is taken in the
+//                      middle of overclocked scanlines, "JAM " when
+//                      the CPU has jammed, "PERI" with peripherals
+//                      attached and "RNG " for the random number
+//                      generator, which a state without it resets
+//         u8           the section's own version
+//         u32          length
+//         bytes
+//
+// Numbers are little-endian. A section a build doesn't know is skipped,
+// so states from newer builds load as long as the parts this one knows
+// haven't changed; when one does, its version goes up and older states
+// can still be told apart and converted. The mapper section is whatever
+// the cartridge board reports about itself. ROM contents are not
+// included, so a state only loads into the game it was taken from.
+//
+// Loading checks the framing and that every field decodes, not what's in
+// them. A corrupt state can still load; the parts of the machine take
+// any value without panicking, wrapping or masking the counters and
+// indexes that would otherwise run off their ends.
+//
+// Host-side parts of a component (the audio resampler, mixer settings, the
+// last finished picture) and the cartridge itself are `#[serde(skip)]`; a
+// loaded machine takes them over from the one it replaces.
+//
+// States are written straight into one buffer, which a Snapshot keeps
+// between calls, so taking one every frame for rewind or run-ahead costs a
+// copy of the machine and no allocation. Files are the same bytes run
+// through zstd; without it (the web build, or one without std) they're
+// written as they are, which loading tells apart by zstd's own magic
+// number.
+
+use alloc::borrow::Cow;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt;
+use core::mem;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::bus::Bus;
+use crate::cpu::Cpu;
+use crate::region::Region;
+
+const MAGIC: &[u8; 4] = b"NESS";
+const FORMAT: u16 = 1;
+
+type Tag = [u8; 4];
+
+// Every section this build writes, all at version 1 so far.
+const SECTION_VERSION: u8 = 1;
+const CPU: Tag = *b"CPU ";
+const RAM: Tag = *b"RAM ";
+const PPU: Tag = *b"PPU ";
+const APU: Tag = *b"APU ";
+const INPUT: Tag = *b"INPT";
+const MAPPER: Tag = *b"MAPR";
+const VS: Tag = *b"VS  ";
+const PAL: Tag = *b"PAL ";
+const OVERCLOCK: Tag = *b"OVCL";
+const JAM: Tag = *b"JAM ";
+const PERIPHERALS: Tag = *b"PERI";
+const RNG: Tag = *b"RNG ";
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum StateError {
+    Decode(postcard::Error),
+    // no magic: not a savestate, or one from before sections
+    NotAState,
+    // written by a newer build in a way this one can't read
+    NewerFormat(u16),
+    NewerSection(Tag, u8),
+    MissingSection(Tag),
+    // the state is for a different board or memory size
+    WrongCartridge,
+    // taken with a different number of peripherals attached
+    WrongPeripherals,
+    // zstd-compressed, and this build was made without it
+    Compressed,
+    Decompress(String),
+}
+
+fn tag_name(tag: &Tag) -> String {
+    String::from_utf8_lossy(tag).trim_end().to_string()
+}
+
+impl fmt::Display for StateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StateError::Decode(err) => write!(f, "corrupt savestate: {}", err),
+            StateError::NotAState => write!(f, "not a savestate"),
+            StateError::NewerFormat(format) => {
+                write!(f, "savestate format {} is newer than this build", format)
+            }
+            StateError::NewerSection(tag, version) => write!(
+                f,
+                "savestate {} section version {} is newer than this build",
+                tag_name(tag),
+                version
+            ),
+            StateError::MissingSection(tag) => {
+                write!(f, "savestate has no {} section", tag_name(tag))
+            }
+            StateError::WrongCartridge => write!(f, "savestate is for a different cartridge"),
+            StateError::WrongPeripherals => {
+                write!(f, "savestate is for a different set of peripherals")
+            }
+            StateError::Compressed => {
+                write!(f, "savestate is compressed; this build can't read it")
+            }
+            StateError::Decompress(err) => write!(f, "corrupt savestate: {}", err),
+        }
+    }
+}
+
+impl core::error::Error for StateError {}
+
+impl From<postcard::Error> for StateError {
+    fn from(err: postcard::Error) -> Self {
+        StateError::Decode(err)
+    }
+}
+
+pub fn encode<T: Serialize + ?Sized>(value: &T) -> Vec<u8> {
+    postcard::to_allocvec(value).expect("machine state always serializes")
+}
+
+pub fn decode<T: DeserializeOwned>(data: &[u8]) -> Result<T, StateError> {
+    Ok(postcard::from_bytes(data)?)
+}
+
+fn write_section(out: &mut Vec<u8>, tag: Tag, data: &[u8]) {
+    out.extend_from_slice(&tag);
+    out.push(SECTION_VERSION);
+    out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    out.extend_from_slice(data);
+}
+
+// Serializes in place and fills in the length afterwards.
+fn write_encoded<T: Serialize>(out: &mut Vec<u8>, tag: Tag, value: &T) {
+    write_section(out, tag, &[]);
+    let start = out.len();
+    *out = postcard::to_extend(value, mem::take(out)).expect("machine state always serializes");
+    let len = (out.len() - start) as u32;
+    out[start - 4..start].copy_from_slice(&len.to_le_bytes());
+}
+
+pub fn save(cpu: &Cpu) -> Vec<u8> {
+    let mut out = Vec::new();
+    save_into(cpu, &mut out);
+    out
+}
+
+// Overwrites `out`, keeping its allocation.
+pub fn save_into(cpu: &Cpu, out: &mut Vec<u8>) {
+    out.clear();
+    out.extend_from_slice(MAGIC);
+    out.extend_from_slice(&FORMAT.to_le_bytes());
+    let core = env!("CARGO_PKG_VERSION");
+    out.push(core.len() as u8);
+    out.extend_from_slice(core.as_bytes());
+    write_sections(cpu, out);
+}
+
+// Where a machine's sections go, in order: into a state, or through
+// MachineHasher without one being written.
+trait SectionSink {
+    fn encoded<T: Serialize>(&mut self, tag: Tag, value: &T);
+    fn mapper(&mut self, bus: &Bus);
+}
+
+impl SectionSink for Vec<u8> {
+    fn encoded<T: Serialize>(&mut self, tag: Tag, value: &T) {
+        write_encoded(self, tag, value);
+    }
+
+    fn mapper(&mut self, bus: &Bus) {
+        write_section(self, MAPPER, &bus.cartridge_state());
+    }
+}
+
+fn write_sections(cpu: &Cpu, sink: &mut impl SectionSink) {
+    let bus = &cpu.bus;
+    sink.encoded(CPU, cpu);
+    sink.encoded(RAM, bus);
+    sink.encoded(PPU, &bus.ppu);
+    sink.encoded(APU, &bus.apu);
+    sink.encoded(INPUT, &bus.controllers);
+    sink.mapper(bus);
+    if let Some(vs) = bus.vs.as_ref() {
+        sink.encoded(VS, vs);
+    }
+    if bus.region() == Region::Pal {
+        sink.encoded(PAL, &bus.dot_phase);
+    }
+    if bus.overclock_cycles > 0 {
+        sink.encoded(OVERCLOCK, &bus.overclock_cycles);
+    }
+    if cpu.jammed {
+        sink.encoded(JAM, &true);
+    }
+    if !bus.peripherals.is_empty() {
+        sink.encoded(PERIPHERALS, &bus.peripherals.save_state());
+    }
+    sink.encoded(RNG, &bus.rng);
+}
+
+// A state kept in memory, uncompressed; see Emulator::snapshot.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Snapshot {
+    data: Vec<u8>,
+}
+
+impl Snapshot {
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.data
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.data
+    }
+
+    #[cfg(feature = "std")]
+    pub(crate) fn data_mut(&mut self) -> &mut Vec<u8> {
+        &mut self.data
+    }
+}
+
+impl From<Vec<u8>> for Snapshot {
+    fn from(data: Vec<u8>) -> Self {
+        Snapshot { data }
+    }
+}
+
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xB5, 0x2F, 0xFD];
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+const ZSTD_LEVEL: i32 = 9;
+
+// For a file.
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+pub fn compress(state: &[u8]) -> Vec<u8> {
+    zstd::bulk::compress(state, ZSTD_LEVEL).expect("compressing into memory can't fail")
+}
+
+#[cfg(any(not(feature = "std"), target_arch = "wasm32"))]
+pub fn compress(state: &[u8]) -> Vec<u8> {
+    state.to_vec()
+}
+
+// From a file, compressed or not.
+pub fn decompress(data: &[u8]) -> Result<Cow<'_, [u8]>, StateError> {
+    if !data.starts_with(&ZSTD_MAGIC) {
+        return Ok(Cow::Borrowed(data));
+    }
+    #[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+    return zstd::stream::decode_all(data)
+        .map(Cow::Owned)
+        .map_err(|err| StateError::Decompress(err.to_string()));
+    #[cfg(any(not(feature = "std"), target_arch = "wasm32"))]
+    Err(StateError::Compressed)
+}
+
+// The sections of a state this build knows, by tag.
+struct Sections<'a> {
+    core: &'a str,
+    sections: Vec<(Tag, &'a [u8])>,
+}
+
+impl<'a> Sections<'a> {
+    fn parse(data: &'a [u8]) -> Result<Self, StateError> {
+        let truncated = || StateError::Decode(postcard::Error::DeserializeUnexpectedEnd);
+        let mut reader = Reader { data };
+        if reader.take(4) != Some(MAGIC) {
+            return Err(StateError::NotAState);
+        }
+        let format = u16::from_le_bytes(reader.array().ok_or_else(truncated)?);
+        if format > FORMAT {
+            return Err(StateError::NewerFormat(format));
+        }
+        let [len] = reader.array().ok_or_else(truncated)?;
+        let core = reader.take(len as usize).ok_or_else(truncated)?;
+        let core = core::str::from_utf8(core).unwrap_or("?");
+
+        let mut sections = Vec::new();
+        while !reader.data.is_empty() {
+            let tag: Tag = reader.array().ok_or_else(truncated)?;
+            let [version] = reader.array().ok_or_else(truncated)?;
+            let len = u32::from_le_bytes(reader.array().ok_or_else(truncated)?);
+            let data = reader.take(len as usize).ok_or_else(truncated)?;
+            let known = [
+                CPU,
+                RAM,
+                PPU,
+                APU,
+                INPUT,
+                MAPPER,
+                VS,
+                PAL,
+                OVERCLOCK,
+                JAM,
+                PERIPHERALS,
+                RNG,
+            ]
+            .contains(&tag);
+            if known && version > SECTION_VERSION {
+                return Err(StateError::NewerSection(tag, version));
+            }
+            if known {
+                sections.push((tag, data));
+            }
+        }
+        Ok(Sections { core, sections })
+    }
+
+    fn get(&self, tag: Tag) -> Result<&'a [u8], StateError> {
+        self.sections
+            .iter()
+            .find(|(t, _)| *t == tag)
+            .map(|&(_, data)| data)
+            .ok_or(StateError::MissingSection(tag))
+    }
+
+    fn decode<T: DeserializeOwned>(&self, tag: Tag) -> Result<T, StateError> {
+        decode(self.get(tag)?)
+    }
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        if self.data.len() < len {
+            return None;
+        }
+        let (taken, rest) = self.data.split_at(len);
+        self.data = rest;
+        Some(taken)
+    }
+
+    fn array<const N: usize>(&mut self) -> Option<[u8; N]> {
+        self.take(N).map(|bytes| bytes.try_into().unwrap())
+    }
+}
+
+// The version of nes that wrote a state.
+pub fn core_version(data: &[u8]) -> Result<&str, StateError> {
+    Ok(Sections::parse(data)?.core)
+}
+
+// 64-bit FNV-1a, fixed by this file rather than std's hasher so builds on
+// either end of netplay agree on it.
+pub fn hash(bytes: &[u8]) -> u64 {
+    hash_from(0xcbf2_9ce4_8422_2325, bytes)
+}
+
+fn hash_from(hash: u64, bytes: &[u8]) -> u64 {
+    bytes.iter().fold(hash, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+// Bytes of a section hashed on their own, so a change to one part of a
+// big section (a byte of work RAM) only costs hashing that part again.
+const HASH_BLOCK: usize = 256;
+
+// A machine's hash at the end of every frame, for netplay peers and
+// replays to find the first frame two machines differ on; see
+// MachineHasher, which keeps it cheap enough to take every frame.
+//
+// `state` says whether two machines are the same now; `rolling` takes in
+// every frame's, so two machines whose rolling hashes agree have been the
+// same on every frame so far, and checking it now and then still catches a
+// difference that came and went in between.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FrameHash {
+    // states hashed so far, this one included
+    pub frames: u64,
+    pub state: u64,
+    pub rolling: u64,
+}
+
+impl FrameHash {
+    fn next(self, state: u64) -> FrameHash {
+        FrameHash {
+            frames: self.frames + 1,
+            state,
+            rolling: hash_from(hash(&self.rolling.to_le_bytes()), &state.to_le_bytes()),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct HashedSection {
+    tag: Tag,
+    bytes: Vec<u8>,
+    blocks: Vec<u64>,
+}
+
+impl HashedSection {
+    fn new(tag: Tag) -> Self {
+        HashedSection {
+            tag,
+            bytes: Vec::new(),
+            blocks: Vec::new(),
+        }
+    }
+
+    fn update(&mut self, data: &[u8]) -> u64 {
+        if self.bytes.len() != data.len() {
+            self.bytes.clear();
+            self.bytes.extend_from_slice(data);
+            self.blocks = data.chunks(HASH_BLOCK).map(hash).collect();
+        } else {
+            let blocks = self
+                .bytes
+                .chunks_mut(HASH_BLOCK)
+                .zip(data.chunks(HASH_BLOCK));
+            for ((old, new), block) in blocks.zip(&mut self.blocks) {
+                if old != new {
+                    old.copy_from_slice(new);
+                    *block = hash(new);
+                }
+            }
+        }
+        let hash = hash_from(hash(&self.tag), &(self.bytes.len() as u64).to_le_bytes());
+        self.blocks
+            .iter()
+            .fold(hash, |hash, block| hash_from(hash, &block.to_le_bytes()))
+    }
+}
+
+// Which HASH_BLOCK-byte blocks of a part of the machine were written since
+// MachineHasher last looked, a bit each (wrapping round past 64). It
+// starts out all set, so a machine that's new or just loaded is hashed in
+// full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Dirty(u64);
+
+impl Default for Dirty {
+    fn default() -> Self {
+        Dirty(!0)
+    }
+}
+
+impl Dirty {
+    pub(crate) fn mark(&mut self, offset: usize) {
+        self.0 |= 1 << (offset / HASH_BLOCK % 64);
+    }
+
+    pub(crate) fn mark_all(&mut self) {
+        self.0 = !0;
+    }
+
+    pub(crate) fn take(&mut self) -> Dirty {
+        mem::replace(self, Dirty(0))
+    }
+
+    pub(crate) fn any(self) -> bool {
+        self.0 != 0
+    }
+
+    fn block(self, index: usize) -> bool {
+        self.0 & 1 << (index % 64) != 0
+    }
+}
+
+// The hashes of an array's blocks as of the last frame.
+#[derive(Debug, Clone, Default)]
+struct Blocks(Vec<u64>);
+
+impl Blocks {
+    fn update(&mut self, data: &[u8], dirty: Dirty) -> &[u64] {
+        if self.0.len() != data.len().div_ceil(HASH_BLOCK) {
+            self.0 = data.chunks(HASH_BLOCK).map(hash).collect();
+        } else {
+            let blocks = self.0.iter_mut().zip(data.chunks(HASH_BLOCK));
+            for (index, (block, bytes)) in blocks.enumerate() {
+                if dirty.block(index) {
+                    *block = hash(bytes);
+                }
+            }
+        }
+        &self.0
+    }
+}
+
+// Frame hashes for a running machine, without writing a state each
+// frame. Work RAM, nametables and OAM are kept track of as they're
+// written (see Dirty), and only blocks written since the last frame are
+// hashed again; the cartridge section is serialized and hashed again only
+// when something wrote to the board, or it's one that can change by
+// itself (see Mapper::changes_unwritten). The other sections are small
+// and change every frame anyway, and go through FNV straight from the
+// serializer. The container's header, which names the build, is left out,
+// so builds that agree on the sections agree on the hash.
+#[derive(Debug, Clone)]
+pub struct MachineHasher {
+    ram: Blocks,
+    vram: Blocks,
+    oam: Blocks,
+    mapper: HashedSection,
+    // the mapper section's, while nothing has written to the board
+    mapper_hash: Option<u64>,
+    last: FrameHash,
+}
+
+impl Default for MachineHasher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MachineHasher {
+    pub fn new() -> Self {
+        MachineHasher {
+            ram: Blocks::default(),
+            vram: Blocks::default(),
+            oam: Blocks::default(),
+            mapper: HashedSection::new(MAPPER),
+            mapper_hash: None,
+            last: FrameHash::default(),
+        }
+    }
+
+    // The machine at the end of the next frame; it's only mutable to
+    // clear what was written since the last one.
+    pub fn update(&mut self, cpu: &mut Cpu) -> FrameHash {
+        let bus = &mut cpu.bus;
+        if bus.take_cartridge_written() || self.mapper_hash.is_none() {
+            self.mapper_hash = Some(self.mapper.update(&bus.cartridge_state()));
+        }
+        let written = [
+            bus.ram_dirty.take(),
+            bus.ppu.vram_dirty.take(),
+            bus.ppu.oam_dirty.take(),
+        ];
+
+        let bus = &cpu.bus;
+        let blocks = [
+            self.ram.update(bus.ram(), written[0]),
+            self.vram.update(bus.ppu.vram(), written[1]),
+            self.oam.update(bus.ppu.oam(), written[2]),
+        ];
+        let state = fold_machine(cpu, blocks, self.mapper_hash.unwrap_or_default());
+        self.last = self.last.next(state);
+        self.last
+    }
+
+    pub fn last(&self) -> FrameHash {
+        self.last
+    }
+}
+
+// What a MachineHasher would make of the machine now, hashing all of it.
+pub fn hash_machine(cpu: &Cpu) -> u64 {
+    let bus = &cpu.bus;
+    let blocks = |data: &[u8]| data.chunks(HASH_BLOCK).map(hash).collect::<Vec<_>>();
+    let (ram, vram, oam) = (
+        blocks(bus.ram()),
+        blocks(bus.ppu.vram()),
+        blocks(bus.ppu.oam()),
+    );
+    let mapper = HashedSection::new(MAPPER).update(&bus.cartridge_state());
+    fold_machine(cpu, [&ram, &vram, &oam], mapper)
+}
+
+// Work RAM, nametables and OAM go by their blocks' hashes, in that order.
+fn fold_machine(cpu: &Cpu, blocks: [&[u64]; 3], mapper: u64) -> u64 {
+    let bus = &cpu.bus;
+    let mut sink = Hashing {
+        state: hash(MAGIC),
+        arrays: [
+            (bus.ram(), blocks[0]),
+            (bus.ppu.vram(), blocks[1]),
+            (bus.ppu.oam(), blocks[2]),
+        ],
+        mapper,
+    };
+    write_sections(cpu, &mut sink);
+    sink.state
+}
+
+// Folds each section's hash into the machine's.
+struct Hashing<'a> {
+    state: u64,
+    // the tracked arrays and their blocks' hashes
+    arrays: [(&'a [u8], &'a [u64]); 3],
+    mapper: u64,
+}
+
+impl Hashing<'_> {
+    fn fold(&mut self, section: u64) {
+        self.state = hash_from(self.state, &section.to_le_bytes());
+    }
+}
+
+impl SectionSink for Hashing<'_> {
+    fn encoded<T: Serialize>(&mut self, tag: Tag, value: &T) {
+        let flavor = HashFlavor {
+            hash: hash(&tag),
+            arrays: &self.arrays,
+        };
+        let section = postcard::serialize_with_flavor(value, flavor)
+            .expect("machine state always serializes");
+        self.fold(section);
+    }
+
+    fn mapper(&mut self, _bus: &Bus) {
+        self.fold(self.mapper);
+    }
+}
+
+// Hashes postcard's output as it comes instead of keeping it. A tracked
+// array comes through in one piece, as byte_array has it serialized, and
+// is recognized by its address: its blocks' hashes stand in for it.
+struct HashFlavor<'a> {
+    hash: u64,
+    arrays: &'a [(&'a [u8], &'a [u64])],
+}
+
+impl postcard::ser_flavors::Flavor for HashFlavor<'_> {
+    type Output = u64;
+
+    fn try_push(&mut self, byte: u8) -> postcard::Result<()> {
+        self.hash = hash_from(self.hash, &[byte]);
+        Ok(())
+    }
+
+    fn try_extend(&mut self, data: &[u8]) -> postcard::Result<()> {
+        match self
+            .arrays
+            .iter()
+            .find(|(array, _)| core::ptr::eq(*array, data))
+        {
+            Some((_, blocks)) => {
+                for block in *blocks {
+                    self.hash = hash_from(self.hash, &block.to_le_bytes());
+                }
+            }
+            None => self.hash = hash_from(self.hash, data),
+        }
+        Ok(())
+    }
+
+    fn finalize(self) -> postcard::Result<u64> {
+        Ok(self.hash)
+    }
+}
+
+// Nothing changes if the state can't be loaded.
+pub fn load(cpu: &mut Cpu, data: &[u8]) -> Result<(), StateError> {
+    let sections = Sections::parse(data)?;
+    let mut loaded = machine(&sections)?;
+    // without the section, what's attached carries on as it is
+    let peripherals: Option<Vec<Vec<u8>>> = match sections.get(PERIPHERALS) {
+        Ok(data) => Some(decode(data)?),
+        Err(_) => None,
+    };
+    if let Some(states) = &peripherals {
+        cpu.bus.peripherals.check_state(states)?;
+    }
+    cpu.bus.load_cartridge_state(sections.get(MAPPER)?)?;
+    if let Some(states) = &peripherals {
+        cpu.bus.peripherals.load_state(states)?;
+    }
+
+    mem::swap(cpu, &mut loaded);
+    cpu.bus.take_host_state(&mut loaded.bus);
+    Ok(())
+}
+
+// A state's machine as it was saved, without the cartridge or anything
+// host-side, and the bytes its mapper wrote, for tools that look inside
+// one.
+#[cfg(feature = "std")]
+pub(crate) fn unpack(data: &[u8]) -> Result<(Cpu, &[u8]), StateError> {
+    let sections = Sections::parse(data)?;
+    Ok((machine(&sections)?, sections.get(MAPPER)?))
+}
+
+fn machine(sections: &Sections) -> Result<Cpu, StateError> {
+    let mut loaded: Cpu = sections.decode(CPU)?;
+    loaded.bus = sections.decode(RAM)?;
+    loaded.bus.ppu = sections.decode(PPU)?;
+    loaded.bus.apu = sections.decode(APU)?;
+    loaded.bus.controllers = sections.decode(INPUT)?;
+    loaded.bus.vs = sections.get(VS).ok().map(decode).transpose()?;
+    let dot_phase = sections.get(PAL).ok().map(decode).transpose()?;
+    loaded.bus.dot_phase = dot_phase.unwrap_or(0);
+    let overclock_cycles = sections.get(OVERCLOCK).ok().map(decode).transpose()?;
+    loaded.bus.overclock_cycles = overclock_cycles.unwrap_or(0);
+    let jammed = sections.get(JAM).ok().map(decode).transpose()?;
+    loaded.jammed = jammed.unwrap_or(false);
+    let rng = sections.get(RNG).ok().map(decode).transpose()?;
+    loaded.bus.rng = rng.unwrap_or_default();
+    Ok(loaded)
+}
+
+// serde only handles arrays up to 32 elements; RAM goes through this as a
+// byte string instead.
+pub mod byte_array {
+    use serde::de::Error;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer, const N: usize>(
+        bytes: &[u8; N],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(bytes)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>, const N: usize>(
+        deserializer: D,
+    ) -> Result<[u8; N], D::Error> {
+        let bytes = <&[u8]>::deserialize(deserializer)?;
+        bytes
+            .try_into()
+            .map_err(|_| D::Error::invalid_length(bytes.len(), &"a RAM image of the right size"))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::bus::Mem;
+    use crate::emulator::test::counting_rom;
+    use crate::emulator::Emulator;
+    use crate::mapper::{Mapper, Mirroring};
+
+    #[test]
+    fn test_states_are_sections_in_a_container() {
+        let mut emulator = Emulator::from_rom(&counting_rom()).unwrap();
+        emulator.run_frame();
+        let state = emulator.save_state();
+        assert!(state.starts_with(b"NESS\x01\x00"));
+        assert_eq!(core_version(&state), Ok(env!("CARGO_PKG_VERSION")));
+        let sections = Sections::parse(&state).unwrap();
+        let tags: Vec<_> = sections.sections.iter().map(|(tag, _)| *tag).collect();
+        assert_eq!(tags, [CPU, RAM, PPU, APU, INPUT, MAPPER, RNG]);
+
+        // a newer build's extra section is passed over
+        let mut newer = state.clone();
+        write_section(&mut newer, *b"NEXT", b"from the future");
+        emulator.run_frame();
+        emulator.load_state(&newer).unwrap();
+        assert_eq!(emulator.frame_count(), 1);
+
+        let mut bumped = state.clone();
+        bumped[4] = 2;
+        assert_eq!(
+            emulator.load_state(&bumped),
+            Err(StateError::NewerFormat(2))
+        );
+        assert_eq!(emulator.load_state(b"\x00\x01"), Err(StateError::NotAState));
+
+        let mut header = state[..6 + 1 + state[6] as usize].to_vec();
+        write_section(&mut header, CPU, sections.get(CPU).unwrap());
+        assert_eq!(
+            emulator.load_state(&header),
+            Err(StateError::MissingSection(RAM))
+        );
+        let ppu = state.windows(4).position(|tag| tag == PPU).unwrap();
+        let mut newer_ppu = state.clone();
+        newer_ppu[ppu + 4] = 2;
+        assert_eq!(
+            emulator.load_state(&newer_ppu).unwrap_err().to_string(),
+            "savestate PPU section version 2 is newer than this build"
+        );
+    }
+
+    #[test]
+    fn test_machine_hash_only_redoes_what_was_written() {
+        let run = |poke: u8| {
+            let mut emulator = Emulator::from_rom(&counting_rom()).unwrap();
+            let mut hasher = MachineHasher::new();
+            let mut hashes = Vec::new();
+            for frame in 1..=12 {
+                emulator.run_frame();
+                let bus = &mut emulator.cpu_mut().bus;
+                match frame {
+                    4 => bus.mem_write(0x0345, poke),
+                    6 => bus.poke_ppu(0x2345, poke),
+                    8 => bus.mem_write(0x2004, poke),
+                    _ => {}
+                }
+                let hash = hasher.update(emulator.cpu_mut());
+                // the same as hashing all of it
+                let mut copy = Emulator::from_rom(&counting_rom()).unwrap();
+                copy.load_state(&emulator.save_state()).unwrap();
+                assert_eq!(hash.state, hash_machine(copy.cpu()));
+                assert_eq!(hash.frames, frame);
+                hashes.push(hash);
+            }
+            hashes
+        };
+        let (first, other) = (run(0x55), run(0x66));
+        assert_eq!(first[..3], other[..3]);
+        assert!(first[3..]
+            .iter()
+            .zip(&other[3..])
+            .all(|(a, b)| a.state != b.state));
+        // the rolling hash never agrees again
+        assert!(first[3..]
+            .iter()
+            .zip(&other[3..])
+            .all(|(a, b)| a.rolling != b.rolling));
+    }
+
+    #[test]
+    fn test_machine_hash_follows_the_cartridge() {
+        // PRG RAM on the counting ROM's NROM board
+        let run = |poke: u8| {
+            let mut emulator = Emulator::from_rom(&counting_rom()).unwrap();
+            emulator.set_state_hashing(true);
+            let mut hashes = Vec::new();
+            for frame in 1..=6 {
+                if frame == 3 {
+                    emulator.cpu_mut().bus.mem_write(0x6123, poke);
+                }
+                emulator.run_frame();
+                let hash = emulator.frame_hash().unwrap().state;
+                assert_eq!(hash, hash_machine(emulator.cpu()));
+                hashes.push(hash);
+            }
+            hashes
+        };
+        let (first, other) = (run(0x55), run(0x66));
+        assert_eq!(first[..2], other[..2]);
+        assert!(first[2..].iter().zip(&other[2..]).all(|(a, b)| a != b));
+
+        // a board that counts cycles, and doesn't say whether that changes
+        // it unwritten
+        struct Counter(u64);
+        impl Mapper for Counter {
+            fn read_prg(&mut self, _addr: u16) -> u8 {
+                0
+            }
+            fn write_prg(&mut self, _addr: u16, _value: u8) {}
+            fn read_chr(&mut self, _addr: u16) -> u8 {
+                0
+            }
+            fn write_chr(&mut self, _addr: u16, _value: u8) {}
+            fn mirroring(&self) -> Mirroring {
+                Mirroring::Horizontal
+            }
+            fn clock(&mut self) {
+                self.0 += 1;
+            }
+            fn save_state(&self) -> Vec<u8> {
+                encode(&self.0)
+            }
+        }
+        let mut emulator = Emulator::from_rom(&counting_rom()).unwrap();
+        emulator
+            .cpu_mut()
+            .bus
+            .insert_cartridge(Box::new(Counter(0)));
+        emulator.set_state_hashing(true);
+        let mut last = Vec::new();
+        for _ in 0..3 {
+            emulator.run_frame();
+            let mapper = emulator.cpu().bus.cartridge_state();
+            assert_ne!(mapper, last);
+            assert_eq!(
+                emulator.frame_hash().unwrap().state,
+                hash_machine(emulator.cpu())
+            );
+            last = mapper;
+        }
+    }
+
+    #[test]
+    fn test_states_carry_the_rng() {
+        let mut emulator = Emulator::from_rom(&counting_rom()).unwrap();
+        emulator
+            .cpu_mut()
+            .bus
+            .power_on(99, crate::rng::RamInit::Random);
+        let state = emulator.save_state();
+        let drawn = emulator.cpu_mut().bus.rng_mut().next_u64();
+        emulator.load_state(&state).unwrap();
+        assert_eq!(emulator.cpu_mut().bus.rng_mut().next_u64(), drawn);
+    }
+}
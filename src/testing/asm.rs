@@ -0,0 +1,527 @@
+// A small 6502 assembler for writing test programs as text rather than
+// opcode bytes:
+//
+//     let program = asm("
+//         ldx #$05
+//     loop:
+//         dex
+//         bne loop
+//         brk
+//     ");
+//
+// Statements end at a newline or a `;`, and `//` starts a comment. The
+// official instructions are covered in the syntax trace::disassemble prints: #imm,
+// zp, abs, zp/abs with ,x or ,y, (zp,x), (zp),y, (abs) for JMP, A or
+// nothing for accumulator shifts. Numbers are $hex, %binary, decimal or
+// 'c'; an operand may add and subtract them, labels (`name:` marks one,
+// `name = value` sets one), and * for the statement's own address; < and
+// > in front take its low and high byte. .byte and .word lay down data.
+//
+// An operand whose value is known by the time it's read and fits in a
+// byte gets the zero-page form; forward references get the absolute one,
+// so the first pass can give every statement its size.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::cpu::AddressingMode;
+use crate::opcodes::{OpCode, CPU_OPS_CODES};
+
+type Address = u16;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AsmError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for AsmError {}
+
+#[derive(Debug, Clone)]
+pub struct Assembly {
+    pub origin: Address,
+    pub bytes: Vec<u8>,
+    labels: HashMap<String, Address>,
+}
+
+impl Assembly {
+    pub fn label(&self, name: &str) -> Option<Address> {
+        self.labels.get(&name.to_ascii_lowercase()).copied()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Part {
+    Whole,
+    Low,
+    High,
+}
+
+#[derive(Debug, Clone)]
+enum Term {
+    Number(i64),
+    Label(String),
+    Here,
+}
+
+#[derive(Debug, Clone)]
+struct Expr {
+    part: Part,
+    // each with its sign
+    terms: Vec<(bool, Term)>,
+}
+
+impl Expr {
+    // None for a label not yet defined.
+    fn eval(&self, labels: &HashMap<String, Address>, here: Address) -> Option<i64> {
+        let mut value = 0;
+        for (negative, term) in &self.terms {
+            let term = match term {
+                Term::Number(n) => *n,
+                Term::Label(name) => *labels.get(name)? as i64,
+                Term::Here => here as i64,
+            };
+            value += if *negative { -term } else { term };
+        }
+        Some(match self.part {
+            Part::Whole => value,
+            Part::Low => value & 0xFF,
+            Part::High => (value >> 8) & 0xFF,
+        })
+    }
+
+    fn unknown<'a>(&'a self, labels: &HashMap<String, Address>) -> Option<&'a str> {
+        self.terms.iter().find_map(|(_, term)| match term {
+            Term::Label(name) if !labels.contains_key(name) => Some(name.as_str()),
+            _ => None,
+        })
+    }
+}
+
+enum Item {
+    Op {
+        opcode: &'static OpCode,
+        operand: Option<Expr>,
+    },
+    Bytes(Vec<Expr>),
+    Words(Vec<Expr>),
+}
+
+struct Statement {
+    line: usize,
+    addr: Address,
+    item: Item,
+}
+
+fn error(line: usize, message: impl Into<String>) -> AsmError {
+    AsmError {
+        line,
+        message: message.into(),
+    }
+}
+
+fn is_name(text: &str) -> bool {
+    let mut chars = text.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn parse_number(text: &str) -> Option<i64> {
+    if let Some(hex) = text.strip_prefix('$') {
+        i64::from_str_radix(hex, 16).ok()
+    } else if let Some(binary) = text.strip_prefix('%') {
+        i64::from_str_radix(binary, 2).ok()
+    } else if let Some(quoted) = text.strip_prefix('\'') {
+        let mut chars = quoted.strip_suffix('\'')?.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) if c.is_ascii() => Some(c as i64),
+            _ => None,
+        }
+    } else {
+        text.parse().ok()
+    }
+}
+
+// An operand with its whitespace already gone.
+fn parse_expr(text: &str, line: usize) -> Result<Expr, AsmError> {
+    let (part, mut rest) = match text.as_bytes().first() {
+        Some(b'<') => (Part::Low, &text[1..]),
+        Some(b'>') => (Part::High, &text[1..]),
+        _ => (Part::Whole, text),
+    };
+    let mut terms = Vec::new();
+    let mut negative = false;
+    loop {
+        if let Some(after) = rest.strip_prefix('-') {
+            negative = !negative;
+            rest = after;
+            continue;
+        }
+        // a char literal may hold + or -
+        let end = if let Some(quoted) = rest.strip_prefix('\'') {
+            quoted.find('\'').map_or(rest.len(), |i| i + 2)
+        } else {
+            rest.find(['+', '-']).unwrap_or(rest.len())
+        };
+        let (word, after) = rest.split_at(end);
+        let term = if word == "*" {
+            Term::Here
+        } else if is_name(word) {
+            Term::Label(word.to_ascii_lowercase())
+        } else {
+            Term::Number(
+                parse_number(word).ok_or_else(|| error(line, format!("bad value `{}`", text)))?,
+            )
+        };
+        terms.push((negative, term));
+        negative = false;
+        match after.as_bytes().first() {
+            None => break,
+            Some(b'+') => rest = &after[1..],
+            Some(_) => rest = after,
+        }
+        if rest.is_empty() {
+            return Err(error(line, format!("bad value `{}`", text)));
+        }
+    }
+    Ok(Expr { part, terms })
+}
+
+fn find_opcode(mnemonic: &str, mode: AddressingMode, len: u8) -> Option<&'static OpCode> {
+    CPU_OPS_CODES
+        .iter()
+        .find(|op| op.mnemonic == mnemonic && op.mode == mode && op.len == len)
+}
+
+fn is_mnemonic(mnemonic: &str) -> bool {
+    CPU_OPS_CODES.iter().any(|op| op.mnemonic == mnemonic)
+}
+
+// Picks the opcode for `operand`, the zero-page form if `value` (None
+// while unknown) fits in one.
+fn choose(
+    mnemonic: &str,
+    operand: &str,
+    value: impl Fn(&Expr) -> Option<i64>,
+    line: usize,
+) -> Result<(&'static OpCode, Option<Expr>), AsmError> {
+    use AddressingMode::*;
+    let lower = operand.to_ascii_lowercase();
+    let unsupported = || error(line, format!("{} can't take `{}`", mnemonic, operand));
+    let pick = |mode, len| find_opcode(mnemonic, mode, len).ok_or_else(unsupported);
+    let sized = |expr: Expr, zero_page: AddressingMode, absolute: AddressingMode| {
+        let fits = value(&expr).is_some_and(|value| (0..0x100).contains(&value));
+        match find_opcode(mnemonic, zero_page, 2) {
+            Some(op) if fits => Ok((op, Some(expr))),
+            _ => Ok((pick(absolute, 3)?, Some(expr))),
+        }
+    };
+
+    if operand.is_empty() || lower == "a" {
+        return Ok((pick(NonAddressing, 1)?, None));
+    }
+    if let Some(imm) = operand.strip_prefix('#') {
+        return Ok((pick(Immediate, 2)?, Some(parse_expr(imm, line)?)));
+    }
+    if lower.starts_with('(') {
+        let inside = |end: usize| parse_expr(&operand[1..operand.len() - end], line);
+        if lower.ends_with(",x)") {
+            return Ok((pick(Indirect_X, 2)?, Some(inside(3)?)));
+        }
+        if lower.ends_with("),y") {
+            return Ok((pick(Indirect_Y, 2)?, Some(inside(3)?)));
+        }
+        if lower.ends_with(')') {
+            // JMP (abs), the one NonAddressing opcode with an operand
+            return Ok((pick(NonAddressing, 3)?, Some(inside(1)?)));
+        }
+        return Err(unsupported());
+    }
+    if let Some(base) = lower.strip_suffix(",x") {
+        let expr = parse_expr(&operand[..base.len()], line)?;
+        return sized(expr, ZeroPage_X, Absolute_X);
+    }
+    if let Some(base) = lower.strip_suffix(",y") {
+        let expr = parse_expr(&operand[..base.len()], line)?;
+        return sized(expr, ZeroPage_Y, Absolute_Y);
+    }
+    let expr = parse_expr(operand, line)?;
+    if let Some(op) = find_opcode(mnemonic, Relative, 2) {
+        return Ok((op, Some(expr)));
+    }
+    sized(expr, ZeroPage, Absolute)
+}
+
+fn strip_comment(line: &str) -> &str {
+    match line.find("//") {
+        Some(i) => &line[..i],
+        None => line,
+    }
+}
+
+fn byte(value: i64, line: usize) -> Result<u8, AsmError> {
+    match value {
+        -0x80..=0xFF => Ok(value as u8),
+        _ => Err(error(line, format!("${:X} doesn't fit in a byte", value))),
+    }
+}
+
+fn word(value: i64, line: usize) -> Result<Address, AsmError> {
+    match value {
+        0..=0xFFFF => Ok(value as Address),
+        _ => Err(error(line, format!("${:X} isn't an address", value))),
+    }
+}
+
+pub fn assemble(origin: Address, source: &str) -> Result<Assembly, AsmError> {
+    let mut labels = HashMap::new();
+    let mut statements = Vec::new();
+    let mut addr = origin as u32;
+
+    for (number, text) in source.lines().enumerate() {
+        let line = number + 1;
+        for mut text in strip_comment(text).split(';').map(str::trim) {
+            while let Some((name, rest)) = text.split_once(':') {
+                let name = name.trim();
+                if !is_name(name) {
+                    break;
+                }
+                let name = name.to_ascii_lowercase();
+                if labels.insert(name.clone(), addr as Address).is_some() {
+                    return Err(error(line, format!("`{}` is defined twice", name)));
+                }
+                text = rest.trim();
+            }
+            if text.is_empty() {
+                continue;
+            }
+            let (head, rest) = text.split_once(char::is_whitespace).unwrap_or((text, ""));
+            let operand: String = rest.chars().filter(|c| !c.is_whitespace()).collect();
+
+            if let Some(value) = operand.strip_prefix('=').filter(|_| is_name(head)) {
+                let expr = parse_expr(value, line)?;
+                let value = expr.eval(&labels, addr as Address).ok_or_else(|| {
+                    error(
+                        line,
+                        format!("`{}` isn't defined yet", head.to_ascii_lowercase()),
+                    )
+                })?;
+                if labels
+                    .insert(head.to_ascii_lowercase(), word(value, line)?)
+                    .is_some()
+                {
+                    return Err(error(line, format!("`{}` is defined twice", head)));
+                }
+                continue;
+            }
+
+            let item = match head.to_ascii_lowercase().as_str() {
+                directive @ (".byte" | ".db" | ".word" | ".dw") => {
+                    let values = operand
+                        .split(',')
+                        .map(|value| parse_expr(value, line))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    if directive.ends_with("byte") || directive == ".db" {
+                        Item::Bytes(values)
+                    } else {
+                        Item::Words(values)
+                    }
+                }
+                _ => {
+                    let mnemonic = head.to_ascii_uppercase();
+                    if !is_mnemonic(&mnemonic) {
+                        return Err(error(line, format!("unknown instruction `{}`", head)));
+                    }
+                    let here = addr as Address;
+                    let value = |expr: &Expr| expr.eval(&labels, here);
+                    let (opcode, operand) = choose(&mnemonic, &operand, value, line)?;
+                    Item::Op { opcode, operand }
+                }
+            };
+            let len = match &item {
+                Item::Op { opcode, .. } => opcode.len as u32,
+                Item::Bytes(values) => values.len() as u32,
+                Item::Words(values) => 2 * values.len() as u32,
+            };
+            statements.push(Statement {
+                line,
+                addr: addr as Address,
+                item,
+            });
+            addr += len;
+            if addr > 0x10000 {
+                return Err(error(line, "past the end of memory"));
+            }
+        }
+    }
+
+    let mut bytes = Vec::new();
+    for Statement { line, addr, item } in &statements {
+        let (line, addr) = (*line, *addr);
+        let eval = |expr: &Expr| {
+            expr.eval(&labels, addr).ok_or_else(|| {
+                let name = expr.unknown(&labels).unwrap_or_default();
+                error(line, format!("`{}` isn't defined", name))
+            })
+        };
+        match item {
+            Item::Op { opcode, operand } => {
+                bytes.push(opcode.code);
+                let Some(operand) = operand else {
+                    continue;
+                };
+                let value = eval(operand)?;
+                match (opcode.mode, opcode.len) {
+                    (AddressingMode::Relative, _) => {
+                        let offset = value - (addr as i64 + 2);
+                        if !(-128..=127).contains(&offset) {
+                            return Err(error(line, "branch out of range"));
+                        }
+                        bytes.push(offset as u8);
+                    }
+                    (AddressingMode::Immediate, _) => bytes.push(byte(value, line)?),
+                    (_, 2) => match value {
+                        0..=0xFF => bytes.push(value as u8),
+                        _ => return Err(error(line, format!("${:X} isn't zero page", value))),
+                    },
+                    _ => bytes.extend_from_slice(&word(value, line)?.to_le_bytes()),
+                }
+            }
+            Item::Bytes(values) => {
+                for value in values {
+                    bytes.push(byte(eval(value)?, line)?);
+                }
+            }
+            Item::Words(values) => {
+                for value in values {
+                    bytes.extend_from_slice(&word(eval(value)?, line)?.to_le_bytes());
+                }
+            }
+        }
+    }
+    Ok(Assembly {
+        origin,
+        bytes,
+        labels,
+    })
+}
+
+// For tests: `source` assembled at $8000, where Cpu::load puts programs,
+// panicking on an error.
+pub fn asm(source: &str) -> Vec<u8> {
+    match assemble(0x8000, source) {
+        Ok(assembly) => assembly.bytes,
+        Err(err) => panic!("{}", err),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::bus::Mem;
+    use crate::cpu::Cpu;
+
+    #[test]
+    fn test_addressing_modes() {
+        let program = asm("
+            lda #$05; ldx #%101; ldy #'A'
+            lda $10; lda $10,x; ldx $10,y
+            lda $1234; lda $1234,x; lda $1234,y
+            lda ($20,x); lda ($20),y
+            jmp ($fffc); asl a; lsr; rol $10
+            sta $0010 // still zero page: the value fits
+        ");
+        assert_eq!(
+            program,
+            [
+                0xa9, 0x05, 0xa2, 0x05, 0xa0, 0x41, //
+                0xa5, 0x10, 0xb5, 0x10, 0xb6, 0x10, //
+                0xad, 0x34, 0x12, 0xbd, 0x34, 0x12, 0xb9, 0x34, 0x12, //
+                0xa1, 0x20, 0xb1, 0x20, //
+                0x6c, 0xfc, 0xff, 0x0a, 0x4a, 0x26, 0x10, //
+                0x85, 0x10,
+            ]
+        );
+        // no zero-page form
+        assert_eq!(asm("lda $10,y"), [0xb9, 0x10, 0x00]);
+        assert_eq!(asm("jmp $10"), [0x4c, 0x10, 0x00]);
+    }
+
+    #[test]
+    fn test_labels_and_data() {
+        let assembly = assemble(
+            0xC000,
+            "
+            counter = $02
+            start:
+                jsr sub
+                bne start
+                inc counter
+                jmp (vector)
+            sub: rts
+            vector: .word sub, start+1
+            table: .byte <vector, >vector, -1, * - table
+            ",
+        )
+        .unwrap();
+        assert_eq!(
+            assembly.bytes,
+            [
+                0x20, 0x0a, 0xc0, // jsr sub
+                0xd0, 0xfb, // bne start
+                0xe6, 0x02, // inc counter
+                0x6c, 0x0b, 0xc0, // jmp (vector)
+                0x60, // rts
+                0x0a, 0xc0, 0x01, 0xc0, //
+                0x0b, 0xc0, 0xff, 0x00,
+            ]
+        );
+        assert_eq!(assembly.label("sub"), Some(0xC00A));
+        assert_eq!(assembly.label("Counter"), Some(0x02));
+        assert_eq!(assembly.label("nowhere"), None);
+    }
+
+    #[test]
+    fn test_errors() {
+        let err = |source| assemble(0x8000, source).unwrap_err();
+        assert_eq!(
+            err("nop\nfoo #1"),
+            AsmError {
+                line: 2,
+                message: "unknown instruction `foo`".to_string()
+            }
+        );
+        assert_eq!(err("lda missing").message, "`missing` isn't defined");
+        assert_eq!(err("lda #$100").message, "$100 doesn't fit in a byte");
+        assert_eq!(err("stx $1234,x").message, "STX can't take `$1234,x`");
+        assert_eq!(err("a: nop; a: nop").message, "`a` is defined twice");
+        assert_eq!(err("lda ($1234),y").message, "$1234 isn't zero page");
+        let far = format!("bne far\n.byte {}\nfar: nop", vec!["0"; 200].join(","));
+        assert_eq!(err(&far).message, "branch out of range");
+    }
+
+    #[test]
+    fn test_runs_on_the_cpu() {
+        let mut cpu = Cpu::new();
+        cpu.load_and_run(asm("
+                ldx #$05
+                lda #0
+            loop:
+                clc
+                adc #3
+                dex
+                bne loop
+                sta $10
+                brk
+        "));
+        assert_eq!(cpu.mem_read(0x10), 15);
+    }
+}
@@ -0,0 +1,299 @@
+// Blargg's test ROMs and the suites built the same way (instr_test,
+// ppu_vbl_nmi, apu_test, mmc3_test...) report through cartridge RAM: once
+// $6001-$6003 hold DE B0 61, $6000 is $80 while the test runs, $81 when it
+// wants the reset button pressed (at least 100ms later), and otherwise
+// the result, 0 for a pass. $6004 on is the text it printed, ending in a
+// 0. run_test_rom plays the part of the person watching, and the tests
+// below make each ROM of the suites a test case of its own. The ones this
+// core is known to fail are ignored, each with what it's missing; the rest
+// are expected to pass.
+//
+// ROMs that only show their result on screen, or beep it, aren't
+// covered; they time out.
+
+use std::fmt;
+
+use crate::cartridge::{Cartridge, CartridgeError};
+use crate::debugger::memory::MemorySpace;
+use crate::emulator::Emulator;
+
+const STATUS: u16 = 0x6000;
+const SIGNATURE: [u8; 3] = [0xDE, 0xB0, 0x61];
+const TEXT: u16 = 0x6004;
+const RUNNING: u8 = 0x80;
+const RESET: u8 = 0x81;
+// a little over the 100ms asked for
+const RESET_DELAY: u64 = 8;
+// long enough for the slowest of the suites
+pub const MAX_FRAMES: u64 = 60 * 60;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TestRomResult {
+    pub code: u8,
+    pub text: String,
+    // how long it took
+    pub frames: u64,
+    // of the picture then; see suite::frame_hash
+    pub frame_hash: u64,
+}
+
+impl TestRomResult {
+    pub fn passed(&self) -> bool {
+        self.code == 0
+    }
+}
+
+#[derive(Debug)]
+pub enum TestRomError {
+    Rom(CartridgeError),
+    // still running, or never started to report, when time ran out; the
+    // picture then, as TestRomResult has it
+    TimedOut { text: String, frame_hash: u64 },
+}
+
+impl fmt::Display for TestRomError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TestRomError::Rom(err) => write!(f, "{}", err),
+            TestRomError::TimedOut { text, .. } if text.is_empty() => {
+                write!(f, "no result through $6000")
+            }
+            TestRomError::TimedOut { text, .. } => write!(f, "no result yet: {}", text),
+        }
+    }
+}
+
+impl std::error::Error for TestRomError {}
+
+impl From<CartridgeError> for TestRomError {
+    fn from(err: CartridgeError) -> Self {
+        TestRomError::Rom(err)
+    }
+}
+
+fn read_text(emulator: &mut Emulator) -> String {
+    let mut text = Vec::new();
+    for addr in TEXT..0x8000 {
+        match emulator.read_memory(MemorySpace::Cpu, addr) {
+            0 => break,
+            byte => text.push(byte),
+        }
+    }
+    String::from_utf8_lossy(&text).trim_end().to_string()
+}
+
+// Runs the ROM until it reports, for at most `max_frames`.
+pub fn run_test_rom(rom: &[u8], max_frames: u64) -> Result<TestRomResult, TestRomError> {
+    let mut emulator = Emulator::new(Cartridge::from_bytes(rom)?)?;
+    let mut reset_at = None;
+    // a reset asked for and given, until the ROM says something else
+    let mut reset_done = false;
+    let mut reporting = false;
+    for frame in 1..=max_frames {
+        emulator.run_frame();
+        let signature = SIGNATURE.iter().enumerate().all(|(i, &byte)| {
+            emulator.read_memory(MemorySpace::Cpu, STATUS + 1 + i as u16) == byte
+        });
+        reporting |= signature;
+        if !reporting {
+            continue;
+        }
+        match emulator.read_memory(MemorySpace::Cpu, STATUS) {
+            RUNNING => reset_done = false,
+            RESET if reset_done => {}
+            RESET => match reset_at {
+                None => reset_at = Some(frame + RESET_DELAY),
+                Some(at) if frame >= at => {
+                    emulator.reset();
+                    reset_at = None;
+                    reset_done = true;
+                }
+                Some(_) => {}
+            },
+            code => {
+                return Ok(TestRomResult {
+                    code,
+                    text: read_text(&mut emulator),
+                    frames: frame,
+                    frame_hash: crate::testing::suite::frame_hash(&emulator),
+                })
+            }
+        }
+    }
+    Err(TestRomError::TimedOut {
+        text: if reporting {
+            read_text(&mut emulator)
+        } else {
+            String::new()
+        },
+        frame_hash: crate::testing::suite::frame_hash(&emulator),
+    })
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+    use crate::cartridge::test::test_rom;
+    use crate::testing::asm::asm;
+    use crate::testing::{find_rom, test_roms_dir};
+
+    // Asks for a reset, remembering in RAM that it did, then reports
+    // `code` and `text` after it.
+    pub(crate) fn protocol_rom(code: u8, text: &str) -> Vec<u8> {
+        let mut source = String::from(
+            "
+                lda $6010
+                bne after_reset
+                lda #$80; sta $6000
+                lda #$de; sta $6001; lda #$b0; sta $6002; lda #$61; sta $6003
+                lda #1; sta $6010
+                lda #$81; sta $6000
+                jmp *
+            after_reset:
+                ldx #0
+            copy:
+                lda text,x; sta $6004,x
+                inx
+                cmp #0; bne copy
+            ",
+        );
+        source.push_str(&format!("lda #{}; sta $6000; jmp *\n", code));
+        let text: Vec<_> = text.bytes().chain([0]).map(|b| b.to_string()).collect();
+        source.push_str(&format!("text: .byte {}", text.join(",")));
+        let program = asm(&source);
+
+        let mut rom = test_rom(2, 1, 0, 0);
+        let prg = &mut rom[16..16 + 0x8000];
+        prg.fill(0);
+        prg[..program.len()].copy_from_slice(&program);
+        prg[0x7FFA..].copy_from_slice(&[0x00, 0x80, 0x00, 0x80, 0x00, 0x80]);
+        rom
+    }
+
+    #[test]
+    fn test_follows_the_6000_protocol() {
+        let result = run_test_rom(&protocol_rom(0, "Passed\n"), 60).unwrap();
+        assert!(result.passed());
+        assert_eq!(result.text, "Passed");
+        // the first frame, then the wait for the reset
+        assert!(result.frames > RESET_DELAY, "{}", result.frames);
+
+        let result = run_test_rom(&protocol_rom(3, "Failed #3"), 60).unwrap();
+        assert_eq!((result.code, result.text.as_str()), (3, "Failed #3"));
+
+        // one that never signs its status
+        let silent = crate::emulator::test::counting_rom();
+        assert!(matches!(
+            run_test_rom(&silent, 10),
+            Err(TestRomError::TimedOut { text, .. }) if text.is_empty()
+        ));
+        assert!(matches!(
+            run_test_rom(b"junk", 10),
+            Err(TestRomError::Rom(_))
+        ));
+    }
+
+    fn check(path: &str) {
+        let dir = test_roms_dir();
+        if !dir.is_dir() {
+            eprintln!("skipped: no test ROMs in {}", dir.display());
+            return;
+        }
+        let Some(file) = find_rom(path) else {
+            panic!("{} isn't in {}", path, dir.display());
+        };
+        let rom = std::fs::read(&file).unwrap();
+        match run_test_rom(&rom, MAX_FRAMES) {
+            Ok(result) => assert!(
+                result.passed(),
+                "{} failed with {}:\n{}",
+                path,
+                result.code,
+                result.text
+            ),
+            Err(err) => panic!("{}: {}", path, err),
+        }
+    }
+
+    macro_rules! suite {
+        ($($(#[$attr:meta])* $name:ident: $path:literal,)*) => {
+            $(
+                #[test]
+                $(#[$attr])*
+                fn $name() {
+                    check($path);
+                }
+            )*
+        };
+    }
+
+    suite! {
+        test_instr_01_basics: "instr_test-v5/rom_singles/01-basics.nes",
+        #[ignore = "the CPU jams on the unofficial implied NOPs"]
+        test_instr_02_implied: "instr_test-v5/rom_singles/02-implied.nes",
+        #[ignore = "the CPU jams on ANC, ALR, ARR, ATX, AXS and the immediate NOPs"]
+        test_instr_03_immediate: "instr_test-v5/rom_singles/03-immediate.nes",
+        #[ignore = "the CPU jams on the unofficial zero page opcodes (SLO, RLA, SRE, RRA, SAX, LAX, DCP, ISC, NOP)"]
+        test_instr_04_zero_page: "instr_test-v5/rom_singles/04-zero_page.nes",
+        #[ignore = "the CPU jams on the unofficial zero page,X/Y opcodes (SLO, RLA, SRE, RRA, SAX, LAX, DCP, ISC, NOP)"]
+        test_instr_05_zp_xy: "instr_test-v5/rom_singles/05-zp_xy.nes",
+        #[ignore = "the CPU jams on the unofficial absolute opcodes (SLO, RLA, SRE, RRA, SAX, LAX, DCP, ISC, NOP)"]
+        test_instr_06_absolute: "instr_test-v5/rom_singles/06-absolute.nes",
+        #[ignore = "the CPU jams on the unofficial absolute,X/Y opcodes (SLO, RLA, SRE, RRA, LAX, DCP, ISC, NOP, SHY, SHX, AHX, TAS, LAS)"]
+        test_instr_07_abs_xy: "instr_test-v5/rom_singles/07-abs_xy.nes",
+        #[ignore = "the CPU jams on the unofficial (ind,X) opcodes (SLO, RLA, SRE, RRA, SAX, LAX, DCP, ISC)"]
+        test_instr_08_ind_x: "instr_test-v5/rom_singles/08-ind_x.nes",
+        #[ignore = "the CPU jams on the unofficial (ind),Y opcodes (SLO, RLA, SRE, RRA, AHX, LAX, DCP, ISC)"]
+        test_instr_09_ind_y: "instr_test-v5/rom_singles/09-ind_y.nes",
+        test_instr_10_branches: "instr_test-v5/rom_singles/10-branches.nes",
+        test_instr_11_stack: "instr_test-v5/rom_singles/11-stack.nes",
+        test_instr_12_jmp_jsr: "instr_test-v5/rom_singles/12-jmp_jsr.nes",
+        test_instr_13_rts: "instr_test-v5/rom_singles/13-rts.nes",
+        test_instr_14_rti: "instr_test-v5/rom_singles/14-rti.nes",
+        test_instr_15_brk: "instr_test-v5/rom_singles/15-brk.nes",
+        test_instr_16_special: "instr_test-v5/rom_singles/16-special.nes",
+
+        #[ignore = "reads of $2002 aren't timed within the instruction that makes them"]
+        test_ppu_vbl_nmi_01_vbl_basics: "ppu_vbl_nmi/rom_singles/01-vbl_basics.nes",
+        #[ignore = "the VBL flag's set time needs reads timed to the PPU dot, not the instruction"]
+        test_ppu_vbl_nmi_02_vbl_set_time: "ppu_vbl_nmi/rom_singles/02-vbl_set_time.nes",
+        #[ignore = "the VBL flag's clear time needs reads timed to the PPU dot, not the instruction"]
+        test_ppu_vbl_nmi_03_vbl_clear_time: "ppu_vbl_nmi/rom_singles/03-vbl_clear_time.nes",
+        #[ignore = "NMI enable takes effect at the end of the instruction, not the write's cycle"]
+        test_ppu_vbl_nmi_04_nmi_control: "ppu_vbl_nmi/rom_singles/04-nmi_control.nes",
+        #[ignore = "NMI timing is only exact to the instruction"]
+        test_ppu_vbl_nmi_05_nmi_timing: "ppu_vbl_nmi/rom_singles/05-nmi_timing.nes",
+        #[ignore = "reading $2002 on the dot the flag sets doesn't suppress the NMI"]
+        test_ppu_vbl_nmi_06_suppression: "ppu_vbl_nmi/rom_singles/06-suppression.nes",
+        #[ignore = "NMI enable takes effect at the end of the instruction, not the write's cycle"]
+        test_ppu_vbl_nmi_07_nmi_on_timing: "ppu_vbl_nmi/rom_singles/07-nmi_on_timing.nes",
+        #[ignore = "NMI disable takes effect at the end of the instruction, not the write's cycle"]
+        test_ppu_vbl_nmi_08_nmi_off_timing: "ppu_vbl_nmi/rom_singles/08-nmi_off_timing.nes",
+        #[ignore = "the skipped odd-frame dot isn't seen by reads within an instruction"]
+        test_ppu_vbl_nmi_09_even_odd_frames: "ppu_vbl_nmi/rom_singles/09-even_odd_frames.nes",
+        #[ignore = "the skipped odd-frame dot isn't seen by reads within an instruction"]
+        test_ppu_vbl_nmi_10_even_odd_timing: "ppu_vbl_nmi/rom_singles/10-even_odd_timing.nes",
+
+        test_apu_1_len_ctr: "apu_test/rom_singles/1-len_ctr.nes",
+        test_apu_2_len_table: "apu_test/rom_singles/2-len_table.nes",
+        test_apu_3_irq_flag: "apu_test/rom_singles/3-irq_flag.nes",
+        test_apu_4_jitter: "apu_test/rom_singles/4-jitter.nes",
+        test_apu_5_len_timing: "apu_test/rom_singles/5-len_timing.nes",
+        test_apu_6_irq_flag_timing: "apu_test/rom_singles/6-irq_flag_timing.nes",
+        test_apu_7_dmc_basics: "apu_test/rom_singles/7-dmc_basics.nes",
+        test_apu_8_dmc_rates: "apu_test/rom_singles/8-dmc_rates.nes",
+
+        #[ignore = "needs the MMC3 mapper"]
+        test_mmc3_1_clocking: "mmc3_test_2/rom_singles/1-clocking.nes",
+        #[ignore = "needs the MMC3 mapper"]
+        test_mmc3_2_details: "mmc3_test_2/rom_singles/2-details.nes",
+        #[ignore = "needs the MMC3 mapper"]
+        test_mmc3_3_a12_clocking: "mmc3_test_2/rom_singles/3-A12_clocking.nes",
+        #[ignore = "needs the MMC3 mapper"]
+        test_mmc3_4_scanline_timing: "mmc3_test_2/rom_singles/4-scanline_timing.nes",
+        #[ignore = "needs the MMC3 mapper"]
+        test_mmc3_5_mmc3: "mmc3_test_2/rom_singles/5-MMC3.nes",
+        #[ignore = "needs the MMC3 mapper"]
+        test_mmc3_6_mmc3_alt: "mmc3_test_2/rom_singles/6-MMC3_alt.nes",
+    }
+}
@@ -0,0 +1,172 @@
+// What the fuzz targets under fuzz/ run, here so the same inputs can be
+// replayed from a test or a debugger without cargo-fuzz:
+//
+//   run_program   the bytes as a program on one of the boards, run for a
+//                 few frames, through a savestate and on again
+//   load_rom      the bytes as a ROM file, run if it loads
+//   load_state    the bytes as a savestate, into a running game
+//
+// None of them may panic, whatever it's given. Errors are fine; so is a
+// jammed CPU.
+
+use crate::cartridge::Cartridge;
+use crate::emulator::Emulator;
+use crate::input::joypad::JoypadButton;
+
+// boards with nothing to set up first, for run_program's first byte
+const MAPPERS: [u8; 4] = [0, 28, 41, 105];
+const PRG_SIZE: usize = 0x8000;
+const FRAMES: u64 = 3;
+
+// An iNES image of `mapper` with `program` at $8000, repeated to fill
+// 32K of PRG, and CHR RAM. The vectors are whatever the program's bytes
+// put there, but for reset, which is $8000.
+pub fn program_rom(mapper: u8, program: &[u8]) -> Vec<u8> {
+    let mut rom = b"NES\x1a\x02\x00".to_vec();
+    rom.extend([(mapper & 0x0F) << 4, mapper & 0xF0]);
+    rom.resize(16, 0);
+    let start = rom.len();
+    if program.is_empty() {
+        rom.resize(start + PRG_SIZE, 0);
+    } else {
+        rom.extend(program.iter().cycle().take(PRG_SIZE));
+    }
+    rom[start + PRG_SIZE - 4..start + PRG_SIZE - 2].copy_from_slice(&[0x00, 0x80]);
+    rom
+}
+
+pub fn run_program(data: &[u8]) {
+    let Some((&board, program)) = data.split_first() else {
+        return;
+    };
+    let mapper = MAPPERS[board as usize % MAPPERS.len()];
+    let mut emulator =
+        Emulator::from_rom(&program_rom(mapper, program)).expect("a board that's supported");
+    // the program's own bytes press buttons too
+    for player in 0..2 {
+        let buttons = program.get(player).copied().unwrap_or(0);
+        emulator.set_buttons(player, JoypadButton::from_bits_retain(buttons));
+    }
+    emulator.run_frames(FRAMES);
+    let state = emulator.save_state();
+    emulator.run_frame();
+    let hash = emulator.state_hash();
+    emulator
+        .load_state(&state)
+        .expect("a state loads into the machine it came from");
+    emulator.run_frame();
+    assert_eq!(
+        emulator.state_hash(),
+        hash,
+        "a loaded state runs on the same"
+    );
+}
+
+pub fn load_rom(data: &[u8]) {
+    let Ok(mut cartridge) = Cartridge::from_bytes(data) else {
+        return;
+    };
+    cartridge.guess_region("fuzz.nes");
+    if let Ok(mut emulator) = Emulator::new(cartridge) {
+        emulator.run_frames(FRAMES);
+        emulator.reset();
+        emulator.run_frame();
+    }
+}
+
+pub fn load_state(data: &[u8]) {
+    for mapper in MAPPERS {
+        let mut emulator =
+            Emulator::from_rom(&program_rom(mapper, &[])).expect("a board that's supported");
+        emulator.run_frame();
+        let before = emulator.save_state();
+        match emulator.load_state(data) {
+            Ok(()) => emulator.run_frames(FRAMES),
+            // a state that doesn't load changes nothing
+            Err(_) => assert_eq!(emulator.save_state(), before),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // xorshift64*, so every run tries the same inputs
+    struct Bytes(u64);
+
+    impl Bytes {
+        fn next(&mut self) -> u8 {
+            self.0 ^= self.0 >> 12;
+            self.0 ^= self.0 << 25;
+            self.0 ^= self.0 >> 27;
+            (self.0.wrapping_mul(0x2545_F491_4F6C_DD1D) >> 56) as u8
+        }
+
+        fn take(&mut self, len: usize) -> Vec<u8> {
+            (0..len).map(|_| self.next()).collect()
+        }
+    }
+
+    #[test]
+    fn test_random_programs_run() {
+        let mut bytes = Bytes(0x9E37_79B9_7F4A_7C15);
+        for len in (0..24).map(|i| i * 37 % 600) {
+            run_program(&bytes.take(len));
+        }
+        run_program(&[]);
+        run_program(&[0]);
+    }
+
+    #[test]
+    fn test_unknown_opcode_jams() {
+        // lda #$01; sta $00; then $02, a KIL
+        let mut emulator =
+            Emulator::from_rom(&program_rom(0, &[0xa9, 0x01, 0x85, 0x00, 0x02])).unwrap();
+        emulator.run_frames(2);
+        let cpu = emulator.cpu();
+        assert!(cpu.jammed());
+        assert_eq!(cpu.program_counter, 0x8004);
+        assert_eq!(emulator.frame_count(), 2);
+
+        let state = emulator.save_state();
+        emulator.reset();
+        assert!(!emulator.cpu().jammed());
+        emulator.load_state(&state).unwrap();
+        assert!(emulator.cpu().jammed());
+    }
+
+    #[test]
+    fn test_broken_roms_and_states() {
+        let mut bytes = Bytes(1);
+        let rom = program_rom(0, &bytes.take(100));
+        for len in [0, 3, 4, 15, 16, 17, 100, rom.len() - 1, rom.len()] {
+            load_rom(&rom[..len]);
+        }
+        for flags in [0x0F, 0xF0, 0x08, 0x0C] {
+            let mut header = rom.clone();
+            header[7] = flags;
+            header[9] = 0xFF;
+            load_rom(&header);
+        }
+        // no PRG at all
+        for mapper in MAPPERS {
+            let mut header = program_rom(mapper, &[]);
+            header[4] = 0;
+            load_rom(&header[..16]);
+        }
+
+        let mut emulator = Emulator::from_rom(&rom).unwrap();
+        emulator.run_frame();
+        let state = emulator.save_state();
+        for len in (0..state.len()).step_by(997) {
+            load_state(&state[..len]);
+        }
+        for _ in 0..12 {
+            let mut corrupt = state.clone();
+            let at = (bytes.next() as usize * 256 + bytes.next() as usize) % corrupt.len();
+            corrupt[at] ^= bytes.next() | 1;
+            load_state(&corrupt);
+        }
+    }
+}
@@ -0,0 +1,294 @@
+// Golden frames: run a ROM for some frames with no front end and hold the
+// picture up against a reference PNG, for the PPU changes a test of
+// registers and state would let through. A pixel counts as different
+// when a channel is off by more than the tolerance allows, and the check
+// fails when more pixels than it allows are; palettes being tuned, or a
+// ROM not quite settled on the frame taken, shouldn't fail every test.
+//
+// With NES_BLESS set, check_frame writes the reference instead of
+// reading it, which is how one is made and how one is updated after a
+// change meant to alter the picture. On a mismatch it leaves NAME.actual.png
+// beside the reference, and NAME.diff.png with the differing pixels in red
+// over a dimmed copy of it.
+
+use std::env;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::cartridge::{Cartridge, CartridgeError};
+use crate::emulator::Emulator;
+use crate::render::frame::Frame;
+
+pub const BLESS_VAR: &str = "NES_BLESS";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Tolerance {
+    // how far a channel may be off for the pixel still to match
+    pub channel: u8,
+    // how many pixels may not match
+    pub pixels: usize,
+}
+
+impl Tolerance {
+    pub const EXACT: Tolerance = Tolerance {
+        channel: 0,
+        pixels: 0,
+    };
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Comparison {
+    pub differing: usize,
+    // the largest channel difference anywhere
+    pub max_delta: u8,
+    // the first differing pixel, top to bottom
+    pub first: Option<(usize, usize)>,
+}
+
+impl Comparison {
+    pub fn within(&self, tolerance: Tolerance) -> bool {
+        self.differing <= tolerance.pixels
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    Matched(Comparison),
+    Blessed,
+}
+
+#[derive(Debug)]
+pub enum GoldenError {
+    Io(PathBuf, io::Error),
+    Rom(CartridgeError),
+    Png(PathBuf, png::DecodingError),
+    // not a 256x240 RGB image
+    Format(PathBuf),
+    Missing(PathBuf),
+    Mismatch {
+        comparison: Comparison,
+        actual: PathBuf,
+    },
+}
+
+impl fmt::Display for GoldenError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GoldenError::Io(path, err) => write!(f, "{}: {}", path.display(), err),
+            GoldenError::Rom(err) => write!(f, "{}", err),
+            GoldenError::Png(path, err) => write!(f, "{}: {}", path.display(), err),
+            GoldenError::Format(path) => {
+                write!(f, "{}: not a 256x240 RGB picture", path.display())
+            }
+            GoldenError::Missing(path) => write!(
+                f,
+                "no reference at {}; run with {}=1 to make it",
+                path.display(),
+                BLESS_VAR
+            ),
+            GoldenError::Mismatch { comparison, actual } => {
+                write!(
+                    f,
+                    "{} pixels differ, by up to {}",
+                    comparison.differing, comparison.max_delta
+                )?;
+                if let Some((x, y)) = comparison.first {
+                    write!(f, ", first at {},{}", x, y)?;
+                }
+                write!(f, "; the frame is in {}", actual.display())
+            }
+        }
+    }
+}
+
+impl std::error::Error for GoldenError {}
+
+impl From<CartridgeError> for GoldenError {
+    fn from(err: CartridgeError) -> Self {
+        GoldenError::Rom(err)
+    }
+}
+
+pub fn blessing() -> bool {
+    env::var_os(BLESS_VAR).is_some_and(|value| !value.is_empty() && value != "0")
+}
+
+pub fn compare(frame: &Frame, reference: &Frame, tolerance: Tolerance) -> Comparison {
+    let mut comparison = Comparison {
+        differing: 0,
+        max_delta: 0,
+        first: None,
+    };
+    let pixels = frame.data.chunks(3).zip(reference.data.chunks(3));
+    for (i, (a, b)) in pixels.enumerate() {
+        let delta = a.iter().zip(b).map(|(a, b)| a.abs_diff(*b)).max();
+        let delta = delta.unwrap_or(0);
+        comparison.max_delta = comparison.max_delta.max(delta);
+        if delta > tolerance.channel {
+            comparison.differing += 1;
+            comparison
+                .first
+                .get_or_insert((i % Frame::WIDTH, i / Frame::WIDTH));
+        }
+    }
+    comparison
+}
+
+pub fn load_png(path: &Path) -> Result<Frame, GoldenError> {
+    let bytes = fs::read(path).map_err(|err| match err.kind() {
+        io::ErrorKind::NotFound => GoldenError::Missing(path.to_path_buf()),
+        _ => GoldenError::Io(path.to_path_buf(), err),
+    })?;
+    let png_error = |err| GoldenError::Png(path.to_path_buf(), err);
+    let decoder = png::Decoder::new(io::Cursor::new(bytes));
+    let mut reader = decoder.read_info().map_err(png_error)?;
+    let size = reader.output_buffer_size().unwrap_or(0);
+    let mut data = vec![0; size];
+    let info = reader.next_frame(&mut data).map_err(png_error)?;
+    if (info.width as usize, info.height as usize) != (Frame::WIDTH, Frame::HEIGHT)
+        || info.color_type != png::ColorType::Rgb
+        || info.bit_depth != png::BitDepth::Eight
+    {
+        return Err(GoldenError::Format(path.to_path_buf()));
+    }
+    data.truncate(info.buffer_size());
+    Ok(Frame { data })
+}
+
+// NAME.png becomes NAME.SUFFIX.png
+fn beside(reference: &Path, suffix: &str) -> PathBuf {
+    let stem = reference.file_stem().unwrap_or_default().to_string_lossy();
+    reference.with_file_name(format!("{}.{}.png", stem, suffix))
+}
+
+fn diff_image(frame: &Frame, reference: &Frame, tolerance: Tolerance) -> Frame {
+    let mut diff = Frame::new();
+    for y in 0..Frame::HEIGHT {
+        for x in 0..Frame::WIDTH {
+            let (a, b) = (frame.pixel(x, y), reference.pixel(x, y));
+            let delta =
+                a.0.abs_diff(b.0)
+                    .max(a.1.abs_diff(b.1))
+                    .max(a.2.abs_diff(b.2));
+            if delta > tolerance.channel {
+                diff.set_pixel(x, y, (255, 0, 0));
+            } else {
+                diff.set_pixel(x, y, (b.0 / 4, b.1 / 4, b.2 / 4));
+            }
+        }
+    }
+    diff
+}
+
+fn write(path: &Path, frame: &Frame) -> Result<(), GoldenError> {
+    fs::write(path, frame.to_png()).map_err(|err| GoldenError::Io(path.to_path_buf(), err))
+}
+
+// Checks `frame` against the PNG at `reference`, or with `bless` writes it
+// there.
+pub fn check(
+    frame: &Frame,
+    reference: &Path,
+    tolerance: Tolerance,
+    bless: bool,
+) -> Result<Outcome, GoldenError> {
+    if bless {
+        if let Some(dir) = reference.parent() {
+            fs::create_dir_all(dir).map_err(|err| GoldenError::Io(dir.to_path_buf(), err))?;
+        }
+        write(reference, frame)?;
+        return Ok(Outcome::Blessed);
+    }
+    let expected = load_png(reference)?;
+    let comparison = compare(frame, &expected, tolerance);
+    if comparison.within(tolerance) {
+        return Ok(Outcome::Matched(comparison));
+    }
+    let actual = beside(reference, "actual");
+    write(&actual, frame)?;
+    write(
+        &beside(reference, "diff"),
+        &diff_image(frame, &expected, tolerance),
+    )?;
+    Err(GoldenError::Mismatch { comparison, actual })
+}
+
+// Runs the ROM for `frames` frames and checks the last against
+// `reference`, blessing it if NES_BLESS is set.
+pub fn check_frame(
+    rom: &[u8],
+    frames: u64,
+    reference: &Path,
+    tolerance: Tolerance,
+) -> Result<Outcome, GoldenError> {
+    let mut emulator = Emulator::new(Cartridge::from_bytes(rom)?)?;
+    emulator.run_frames(frames);
+    check(emulator.frame(), reference, tolerance, blessing())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_compares_with_tolerance() {
+        let reference = Frame::new();
+        let mut frame = Frame::new();
+        frame.set_pixel(3, 2, (4, 0, 0));
+        frame.set_pixel(200, 100, (0, 0, 30));
+
+        let comparison = compare(&frame, &reference, Tolerance::EXACT);
+        assert_eq!(comparison.differing, 2);
+        assert_eq!(comparison.max_delta, 30);
+        assert_eq!(comparison.first, Some((3, 2)));
+        let tolerance = Tolerance {
+            channel: 5,
+            pixels: 0,
+        };
+        assert_eq!(compare(&frame, &reference, tolerance).differing, 1);
+        let tolerance = Tolerance {
+            channel: 5,
+            pixels: 1,
+        };
+        assert!(compare(&frame, &reference, tolerance).within(tolerance));
+    }
+
+    #[test]
+    fn test_blesses_then_checks_a_reference() {
+        let dir = env::temp_dir().join(format!("nes-golden-{}", std::process::id()));
+        let reference = dir.join("counting.png");
+        let rom = crate::emulator::test::counting_rom();
+        let mut emulator = Emulator::from_rom(&rom).unwrap();
+        emulator.run_frames(3);
+
+        assert!(matches!(
+            check(emulator.frame(), &reference, Tolerance::EXACT, false),
+            Err(GoldenError::Missing(_))
+        ));
+        assert_eq!(
+            check(emulator.frame(), &reference, Tolerance::EXACT, true).unwrap(),
+            Outcome::Blessed
+        );
+        assert_eq!(load_png(&reference).unwrap(), *emulator.frame());
+        assert!(matches!(
+            check_frame(&rom, 3, &reference, Tolerance::EXACT),
+            Ok(Outcome::Matched(Comparison { differing: 0, .. }))
+        ));
+
+        let mut changed = emulator.frame().clone();
+        changed.set_pixel(10, 20, (255, 255, 255));
+        match check(&changed, &reference, Tolerance::EXACT, false) {
+            Err(GoldenError::Mismatch { comparison, actual }) => {
+                assert_eq!(comparison.first, Some((10, 20)));
+                assert_eq!(actual, dir.join("counting.actual.png"));
+                assert_eq!(load_png(&actual).unwrap(), changed);
+                let diff = load_png(&dir.join("counting.diff.png")).unwrap();
+                assert_eq!(diff.pixel(10, 20), (255, 0, 0));
+            }
+            other => panic!("{:?}", other),
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
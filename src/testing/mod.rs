@@ -0,0 +1,36 @@
+// Harnesses for checking the emulator against ROMs: blargg.rs runs the
+// accuracy suites that report through $6000, golden.rs compares frames
+// with reference pictures, suite.rs runs a directory of ROMs either way
+// for `nes test-suite`, run.rs checks what a ROM leaves in memory and
+// the registers, asm.rs assembles the programs tests run and fuzz.rs is
+// what the fuzz targets feed arbitrary bytes to.
+//
+// The ROMs themselves aren't part of the crate. Tests look for them under
+// the directory NES_TEST_ROMS names, or test-roms/ next to Cargo.toml,
+// by their paths in the suites' own archives (ppu_vbl_nmi/rom_singles/
+// 01-vbl_basics.nes and so on). Without that directory the tests skip;
+// with it, a ROM missing from it fails its test.
+
+pub mod asm;
+pub mod blargg;
+pub mod fuzz;
+pub mod golden;
+pub mod run;
+pub mod suite;
+
+pub use run::{run_rom_file_frames, run_rom_frames, RomRun};
+
+use std::env;
+use std::path::PathBuf;
+
+pub fn test_roms_dir() -> PathBuf {
+    env::var_os("NES_TEST_ROMS")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("test-roms"))
+}
+
+// The ROM at `path` under test_roms_dir, if it's been put there.
+pub fn find_rom(path: &str) -> Option<PathBuf> {
+    let path = test_roms_dir().join(path);
+    path.is_file().then_some(path)
+}
@@ -0,0 +1,211 @@
+// Terse checks for what a ROM leaves behind, for tests of a game's
+// behaviour or a homebrew's own unit tests:
+//
+//     run_rom_frames(&rom, 300)
+//         .assert_ram(0x07DE, 3)
+//         .assert_reg_a(0x00);
+//
+// A RomRun is the machine after the frames, from power-on with nothing
+// pressed. press() and run() carry on from there, for input in between.
+// The asserts panic with the address (or register), what was expected
+// and what was there, pointing at the test's line rather than this file.
+
+use std::path::Path;
+
+use crate::emulator::Emulator;
+use crate::input::joypad::JoypadButton;
+
+type Address = u16;
+type Value = u8;
+
+#[derive(Debug)]
+pub struct RomRun {
+    emulator: Emulator,
+}
+
+// Panics if the ROM doesn't load.
+#[track_caller]
+pub fn run_rom_frames(rom: &[u8], frames: u64) -> RomRun {
+    let emulator = match Emulator::from_rom(rom) {
+        Ok(emulator) => emulator,
+        Err(err) => panic!("the ROM doesn't load: {}", err),
+    };
+    RomRun { emulator }.run(frames)
+}
+
+#[track_caller]
+pub fn run_rom_file_frames(path: impl AsRef<Path>, frames: u64) -> RomRun {
+    let path = path.as_ref();
+    match std::fs::read(path) {
+        Ok(rom) => run_rom_frames(&rom, frames),
+        Err(err) => panic!("can't read {}: {}", path.display(), err),
+    }
+}
+
+impl RomRun {
+    pub fn run(mut self, frames: u64) -> Self {
+        self.emulator.run_frames(frames);
+        self
+    }
+
+    // Player one's buttons from here on; empty lets go.
+    pub fn press(mut self, buttons: JoypadButton) -> Self {
+        self.emulator.set_buttons(0, buttons);
+        self
+    }
+
+    // Anywhere the CPU sees, without side effects.
+    pub fn peek(&mut self, addr: Address) -> Value {
+        self.emulator.cpu_mut().bus.peek(addr)
+    }
+
+    pub fn emulator(&self) -> &Emulator {
+        &self.emulator
+    }
+
+    pub fn emulator_mut(&mut self) -> &mut Emulator {
+        &mut self.emulator
+    }
+
+    pub fn into_emulator(self) -> Emulator {
+        self.emulator
+    }
+
+    // The internal 2K, mirrors and all.
+    #[track_caller]
+    pub fn assert_ram(self, addr: Address, expected: Value) -> Self {
+        assert!(addr < 0x2000, "${:04X} isn't RAM; see assert_memory", addr);
+        self.assert_memory(addr, expected)
+    }
+
+    #[track_caller]
+    pub fn assert_memory(mut self, addr: Address, expected: Value) -> Self {
+        let found = self.peek(addr);
+        assert!(
+            found == expected,
+            "${:04X} is ${:02X}, expected ${:02X} (frame {})",
+            addr,
+            found,
+            expected,
+            self.emulator.frame_count()
+        );
+        self
+    }
+
+    // `expected` byte for byte from `addr` up.
+    #[track_caller]
+    pub fn assert_bytes(mut self, addr: Address, expected: &[Value]) -> Self {
+        let found: Vec<_> = (0..expected.len())
+            .map(|offset| self.peek(addr.wrapping_add(offset as Address)))
+            .collect();
+        assert!(
+            found == expected,
+            "${:04X}.. is {:02X?}, expected {:02X?}",
+            addr,
+            found,
+            expected
+        );
+        self
+    }
+
+    #[track_caller]
+    fn assert_register(self, name: &str, found: u16, expected: u16) -> Self {
+        assert!(
+            found == expected,
+            "{} is ${:02X}, expected ${:02X}",
+            name,
+            found,
+            expected
+        );
+        self
+    }
+
+    #[track_caller]
+    pub fn assert_reg_a(self, expected: Value) -> Self {
+        let found = self.emulator.cpu().register_a;
+        self.assert_register("A", found.into(), expected.into())
+    }
+
+    #[track_caller]
+    pub fn assert_reg_x(self, expected: Value) -> Self {
+        let found = self.emulator.cpu().register_x;
+        self.assert_register("X", found.into(), expected.into())
+    }
+
+    #[track_caller]
+    pub fn assert_reg_y(self, expected: Value) -> Self {
+        let found = self.emulator.cpu().register_y;
+        self.assert_register("Y", found.into(), expected.into())
+    }
+
+    #[track_caller]
+    pub fn assert_reg_sp(self, expected: Value) -> Self {
+        let found = self.emulator.cpu().stack_pointer;
+        self.assert_register("SP", found.into(), expected.into())
+    }
+
+    #[track_caller]
+    pub fn assert_reg_pc(self, expected: Address) -> Self {
+        let found = self.emulator.cpu().program_counter;
+        self.assert_register("PC", found, expected)
+    }
+
+    // Every bit in `flags` (cpu::CARRY and so on) set.
+    #[track_caller]
+    pub fn assert_flags(self, flags: Value) -> Self {
+        let status = self.emulator.cpu().status;
+        assert!(
+            status & flags == flags,
+            "the status is %{:08b}, expected %{:08b} set",
+            status,
+            flags
+        );
+        self
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::cpu::CARRY;
+    use crate::testing::asm::asm;
+    use crate::testing::fuzz::program_rom;
+
+    fn rom() -> Vec<u8> {
+        program_rom(
+            0,
+            &asm("
+                ldx #$03
+                stx $07DE
+                sec
+            loop:
+                lda #1; sta $4016; lda #0; sta $4016
+                lda $4016 // A
+                and #1
+                sta $10
+                jmp loop
+            "),
+        )
+    }
+
+    #[test]
+    fn test_asserts_chain() {
+        run_rom_frames(&rom(), 2)
+            .assert_ram(0x07DE, 3)
+            .assert_ram(0x0FDE, 3)
+            .assert_bytes(0x07DE, &[3])
+            .assert_reg_x(3)
+            .assert_reg_a(0)
+            .assert_flags(CARRY)
+            .assert_memory(0x8000, 0xA2)
+            .press(JoypadButton::BUTTON_A)
+            .run(1)
+            .assert_ram(0x10, 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "$07DE is $03, expected $04")]
+    fn test_says_what_was_there() {
+        run_rom_frames(&rom(), 1).assert_ram(0x07DE, 4);
+    }
+}
@@ -0,0 +1,446 @@
+// A directory of test ROMs run as one suite, for `nes test-suite`. Every
+// .nes under it, however deep, is a case, and is checked one of two ways:
+//
+//   NAME.hash beside NAME.nes   run for the frames it gives, then the
+//                               picture must hash to what it gives:
+//                               "600 0123456789abcdef", the hash as the
+//                               report shows it
+//   otherwise                   blargg's $6000 protocol; see blargg.rs
+//
+// and fails when the ROM reports otherwise, times out when it never says,
+// or is an error when it can't be read or run. Cases run in parallel, one
+// machine to a thread. The report prints as a table, and writes JUnit XML
+// for CI and JSON for anything else keeping track.
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::cartridge::Cartridge;
+use crate::emulator::Emulator;
+use crate::remote::json::Json;
+use crate::state;
+use crate::testing::blargg::{self, TestRomError};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SuiteOptions {
+    // how long a ROM reporting through $6000 gets
+    pub max_frames: u64,
+    pub jobs: usize,
+}
+
+impl Default for SuiteOptions {
+    fn default() -> Self {
+        SuiteOptions {
+            max_frames: blargg::MAX_FRAMES,
+            jobs: thread::available_parallelism().map_or(1, |n| n.get()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Check {
+    Status,
+    FrameHash { frames: u64, hash: u64 },
+}
+
+impl Check {
+    fn name(&self) -> &'static str {
+        match self {
+            Check::Status => "status",
+            Check::FrameHash { .. } => "frame-hash",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    Passed,
+    Failed(String),
+    TimedOut(String),
+    Error(String),
+}
+
+impl Outcome {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Outcome::Passed => "PASS",
+            Outcome::Failed(_) => "FAIL",
+            Outcome::TimedOut(_) => "TIME",
+            Outcome::Error(_) => "ERR",
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        match self {
+            Outcome::Passed => "",
+            Outcome::Failed(message) | Outcome::TimedOut(message) | Outcome::Error(message) => {
+                message
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Case {
+    // under the suite's directory, with forward slashes
+    pub name: String,
+    pub check: Check,
+    pub outcome: Outcome,
+    pub frames: u64,
+    // of the last frame run, for writing a NAME.hash
+    pub frame_hash: u64,
+    pub duration: Duration,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Report {
+    pub cases: Vec<Case>,
+}
+
+impl Report {
+    fn count(&self, label: &str) -> u64 {
+        let cases = self.cases.iter();
+        cases.filter(|case| case.outcome.label() == label).count() as u64
+    }
+
+    pub fn passed(&self) -> bool {
+        self.cases
+            .iter()
+            .all(|case| case.outcome == Outcome::Passed)
+    }
+
+    pub fn to_json(&self) -> Json {
+        let cases = self.cases.iter().map(|case| {
+            let (frames, hash) = match case.check {
+                Check::Status => (None, None),
+                Check::FrameHash { frames, hash } => (Some(frames), Some(format!("{:016x}", hash))),
+            };
+            Json::object([
+                ("name", case.name.as_str().into()),
+                ("check", case.check.name().into()),
+                ("outcome", case.outcome.label().to_lowercase().into()),
+                ("message", case.outcome.message().into()),
+                ("frames", case.frames.into()),
+                ("frame_hash", format!("{:016x}", case.frame_hash).into()),
+                ("expected_frames", frames.into()),
+                ("expected_hash", hash.into()),
+                ("seconds", Json::Number(case.duration.as_secs_f64())),
+            ])
+        });
+        Json::object([
+            ("passed", self.count("PASS").into()),
+            ("failed", self.count("FAIL").into()),
+            ("timed_out", self.count("TIME").into()),
+            ("errors", self.count("ERR").into()),
+            ("cases", Json::Array(cases.collect())),
+        ])
+    }
+
+    // One <testsuite>; timeouts are failures to JUnit.
+    pub fn to_junit(&self, suite: &str) -> String {
+        let seconds: f64 = self.cases.iter().map(|c| c.duration.as_secs_f64()).sum();
+        let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        xml.push_str(&format!(
+            "<testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" errors=\"{}\" time=\"{:.3}\">\n",
+            escape(suite),
+            self.cases.len(),
+            self.count("FAIL") + self.count("TIME"),
+            self.count("ERR"),
+            seconds
+        ));
+        for case in &self.cases {
+            xml.push_str(&format!(
+                "  <testcase name=\"{}\" classname=\"{}\" time=\"{:.3}\"",
+                escape(&case.name),
+                escape(suite),
+                case.duration.as_secs_f64()
+            ));
+            let element = match case.outcome {
+                Outcome::Passed => {
+                    xml.push_str("/>\n");
+                    continue;
+                }
+                Outcome::Failed(_) | Outcome::TimedOut(_) => "failure",
+                Outcome::Error(_) => "error",
+            };
+            xml.push_str(&format!(
+                ">\n    <{} message=\"{}\"/>\n  </testcase>\n",
+                element,
+                escape(case.outcome.message())
+            ));
+        }
+        xml.push_str("</testsuite>\n");
+        xml
+    }
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\n' => escaped.push_str("&#10;"),
+            // not allowed in XML 1.0 at all
+            c if (c as u32) < 0x20 && c != '\t' => escaped.push(' '),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+// The table: a line a case, then the totals.
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let width = self.cases.iter().map(|c| c.name.len()).max().unwrap_or(0);
+        for case in &self.cases {
+            write!(
+                f,
+                "{:<4}  {:<width$}  {:>6} frames",
+                case.outcome.label(),
+                case.name,
+                case.frames,
+                width = width
+            )?;
+            // on one line, however much the ROM printed
+            let message = case.outcome.message().split_whitespace();
+            let message: Vec<_> = message.collect();
+            if !message.is_empty() {
+                write!(f, "  {}", message.join(" "))?;
+            }
+            writeln!(f)?;
+        }
+        writeln!(
+            f,
+            "{} of {} passed, {} failed, {} timed out, {} errors",
+            self.count("PASS"),
+            self.cases.len(),
+            self.count("FAIL"),
+            self.count("TIME"),
+            self.count("ERR")
+        )
+    }
+}
+
+// Every .nes under `dir`, sorted.
+pub fn find_roms(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut roms = Vec::new();
+    let mut dirs = vec![dir.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        for entry in fs::read_dir(&dir)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                dirs.push(path);
+            } else if path
+                .extension()
+                .is_some_and(|ext| ext.eq_ignore_ascii_case("nes"))
+            {
+                roms.push(path);
+            }
+        }
+    }
+    roms.sort();
+    Ok(roms)
+}
+
+// What NAME.hash asks for, if there is one.
+pub fn expectation(rom: &Path) -> Result<Check, String> {
+    let path = rom.with_extension("hash");
+    let text = match fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Check::Status),
+        Err(err) => return Err(format!("{}: {}", path.display(), err)),
+    };
+    let bad = || format!("{}: expected \"FRAMES HASH\"", path.display());
+    let mut fields = text.split_whitespace();
+    let frames = fields.next().and_then(|f| f.parse().ok()).ok_or_else(bad)?;
+    let hash = fields.next().ok_or_else(bad)?;
+    let hash = hash.strip_prefix("0x").unwrap_or(hash);
+    let hash = u64::from_str_radix(hash, 16).map_err(|_| bad())?;
+    if fields.next().is_some() {
+        return Err(bad());
+    }
+    Ok(Check::FrameHash { frames, hash })
+}
+
+pub fn frame_hash(emulator: &Emulator) -> u64 {
+    state::hash(&emulator.frame().data)
+}
+
+fn run_case(dir: &Path, rom: &Path, options: &SuiteOptions) -> Case {
+    let started = Instant::now();
+    let name = rom.strip_prefix(dir).unwrap_or(rom);
+    let name = name.to_string_lossy().replace('\\', "/");
+    let mut case = Case {
+        name,
+        check: Check::Status,
+        outcome: Outcome::Passed,
+        frames: 0,
+        frame_hash: 0,
+        duration: Duration::ZERO,
+    };
+    let mut run = || -> Result<(), String> {
+        case.check = expectation(rom)?;
+        let bytes = fs::read(rom).map_err(|err| err.to_string())?;
+        case.outcome = match case.check {
+            Check::Status => match blargg::run_test_rom(&bytes, options.max_frames) {
+                Ok(result) => {
+                    case.frames = result.frames;
+                    case.frame_hash = result.frame_hash;
+                    if result.passed() {
+                        Outcome::Passed
+                    } else {
+                        Outcome::Failed(format!("code {}: {}", result.code, result.text))
+                    }
+                }
+                Err(TestRomError::Rom(err)) => Outcome::Error(err.to_string()),
+                Err(err @ TestRomError::TimedOut { frame_hash, .. }) => {
+                    case.frames = options.max_frames;
+                    case.frame_hash = frame_hash;
+                    Outcome::TimedOut(err.to_string())
+                }
+            },
+            Check::FrameHash { frames, hash } => {
+                let cartridge = Cartridge::from_bytes(&bytes).map_err(|err| err.to_string())?;
+                let mut emulator = Emulator::new(cartridge).map_err(|err| err.to_string())?;
+                emulator.run_frames(frames);
+                case.frames = frames;
+                case.frame_hash = frame_hash(&emulator);
+                if case.frame_hash == hash {
+                    Outcome::Passed
+                } else {
+                    Outcome::Failed(format!(
+                        "frame hash {:016x}, expected {:016x}",
+                        case.frame_hash, hash
+                    ))
+                }
+            }
+        };
+        Ok(())
+    };
+    if let Err(err) = run() {
+        case.outcome = Outcome::Error(err);
+    }
+    case.duration = started.elapsed();
+    case
+}
+
+// Runs every ROM under `dir`, `options.jobs` at a time; `progress` hears
+// about each case as it finishes, in whatever order they do.
+pub fn run(
+    dir: &Path,
+    options: &SuiteOptions,
+    progress: &(dyn Fn(&Case) + Sync),
+) -> io::Result<Report> {
+    let roms = find_roms(dir)?;
+    let next = AtomicUsize::new(0);
+    let cases = Mutex::new(Vec::with_capacity(roms.len()));
+    thread::scope(|scope| {
+        for _ in 0..options.jobs.clamp(1, roms.len().max(1)) {
+            scope.spawn(|| {
+                while let Some(rom) = roms.get(next.fetch_add(1, Ordering::Relaxed)) {
+                    let case = run_case(dir, rom, options);
+                    progress(&case);
+                    cases.lock().unwrap().push(case);
+                }
+            });
+        }
+    });
+    let mut cases = cases.into_inner().unwrap();
+    cases.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(Report { cases })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::testing::blargg::test::protocol_rom;
+    use crate::testing::fuzz::program_rom;
+
+    #[test]
+    fn test_runs_a_directory() {
+        let dir = std::env::temp_dir().join(format!("nes-suite-{}", std::process::id()));
+        fs::create_dir_all(dir.join("deeper")).unwrap();
+        fs::write(dir.join("pass.nes"), protocol_rom(0, "Passed")).unwrap();
+        fs::write(dir.join("deeper/fail.nes"), protocol_rom(2, "Failed #2")).unwrap();
+        fs::write(
+            dir.join("silent.nes"),
+            crate::emulator::test::counting_rom(),
+        )
+        .unwrap();
+        fs::write(dir.join("junk.nes"), b"junk").unwrap();
+        fs::write(dir.join("notes.txt"), b"not a ROM").unwrap();
+
+        let picture = program_rom(0, &[0x4c, 0x00, 0x80]);
+        let mut emulator = Emulator::from_rom(&picture).unwrap();
+        emulator.run_frames(5);
+        let hash = frame_hash(&emulator);
+        fs::write(dir.join("hashed.nes"), &picture).unwrap();
+        fs::write(dir.join("hashed.hash"), format!("5 {:016x}\n", hash)).unwrap();
+        fs::write(dir.join("wrong.nes"), &picture).unwrap();
+        fs::write(dir.join("wrong.hash"), "5 0123\n").unwrap();
+        fs::write(dir.join("garbled.nes"), &picture).unwrap();
+        fs::write(dir.join("garbled.hash"), "five frames\n").unwrap();
+
+        let options = SuiteOptions {
+            max_frames: 30,
+            jobs: 3,
+        };
+        let seen = AtomicUsize::new(0);
+        let report = run(&dir, &options, &|_| {
+            seen.fetch_add(1, Ordering::Relaxed);
+        })
+        .unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(seen.into_inner(), 7);
+        let outcomes: Vec<_> = report
+            .cases
+            .iter()
+            .map(|case| (case.name.as_str(), case.outcome.label()))
+            .collect();
+        assert_eq!(
+            outcomes,
+            [
+                ("deeper/fail.nes", "FAIL"),
+                ("garbled.nes", "ERR"),
+                ("hashed.nes", "PASS"),
+                ("junk.nes", "ERR"),
+                ("pass.nes", "PASS"),
+                ("silent.nes", "TIME"),
+                ("wrong.nes", "FAIL"),
+            ]
+        );
+        assert!(!report.passed());
+        assert_eq!(report.cases[0].outcome.message(), "code 2: Failed #2");
+        assert_eq!(report.cases[2].frame_hash, hash);
+        // a timed-out case has the picture it stopped on too
+        let mut silent = Emulator::from_rom(&crate::emulator::test::counting_rom()).unwrap();
+        silent.run_frames(30);
+        assert_eq!(report.cases[5].frame_hash, frame_hash(&silent));
+
+        let table = report.to_string();
+        assert!(table.ends_with("2 of 7 passed, 2 failed, 1 timed out, 2 errors\n"));
+
+        let json = Json::parse(&report.to_json().to_string()).unwrap();
+        assert_eq!(json.get("errors").and_then(Json::as_u64), Some(2));
+        let cases = json.get("cases").and_then(Json::as_array).unwrap();
+        assert_eq!(
+            cases[2].get("expected_hash").and_then(Json::as_str),
+            Some(format!("{:016x}", hash).as_str())
+        );
+
+        let junit = report.to_junit("roms");
+        assert!(junit.contains("tests=\"7\" failures=\"3\" errors=\"2\""));
+        assert!(junit.contains("<testcase name=\"pass.nes\" classname=\"roms\""));
+        assert!(junit.contains("<failure message=\"code 2: Failed #2\"/>"));
+    }
+}
@@ -0,0 +1,388 @@
+// CPU trace logs, one line per instruction before it executes, laid out
+// like nestest.log so a trace can be diffed against other emulators':
+//
+//     C000  4C F5 C5  JMP $C5F5                       A:00 X:00 Y:00 P:24 SP:FD CYC:7
+//
+// Operand bytes are read with Bus::peek, so tracing never disturbs the
+// machine. With symbols loaded (debugger/symbols.rs) operands show names
+// and a labelled instruction gets a `name:` line of its own above it.
+// TraceOptions trims the log down, and taken interrupts get a line of
+// their own.
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::io::{self, Write};
+use std::str::FromStr;
+
+use bitflags::bitflags;
+
+use crate::bus::Bus;
+use crate::cpu::{AddressingMode, Cpu};
+use crate::debugger::symbols::{self, Symbols};
+use crate::opcodes::{OpCode, OPCODES_MAP};
+
+// The instruction at `addr` as assembly, e.g. "LDA ($44),Y". Unknown
+// opcodes come out as a data byte.
+pub fn disassemble(bus: &mut Bus, addr: u16) -> (String, u8) {
+    let code = bus.peek(addr);
+    let Some(opcode) = OPCODES_MAP.get(&code) else {
+        return (format!(".db ${:02X}", code), 1);
+    };
+    let lo = bus.peek(addr.wrapping_add(1));
+    let hi = bus.peek(addr.wrapping_add(2));
+    (format_operand(opcode, addr, lo, hi), opcode.len)
+}
+
+pub(crate) fn format_operand(opcode: &OpCode, addr: u16, lo: u8, hi: u8) -> String {
+    let word = u16::from_le_bytes([lo, hi]);
+    let operand = match opcode.mode {
+        AddressingMode::Immediate => format!("#${:02X}", lo),
+        AddressingMode::ZeroPage => format!("${:02X}", lo),
+        AddressingMode::ZeroPage_X => format!("${:02X},X", lo),
+        AddressingMode::ZeroPage_Y => format!("${:02X},Y", lo),
+        AddressingMode::Absolute => format!("${:04X}", word),
+        AddressingMode::Absolute_X => format!("${:04X},X", word),
+        AddressingMode::Absolute_Y => format!("${:04X},Y", word),
+        AddressingMode::Indirect_X => format!("(${:02X},X)", lo),
+        AddressingMode::Indirect_Y => format!("(${:02X}),Y", lo),
+        AddressingMode::Relative => {
+            let target = addr.wrapping_add(2).wrapping_add(lo as i8 as u16);
+            format!("${:04X}", target)
+        }
+        AddressingMode::NonAddressing => match opcode.code {
+            0x6c => format!("(${:04X})", word),
+            0x0a | 0x2a | 0x4a | 0x6a => "A".to_string(),
+            _ => String::new(),
+        },
+    };
+    if operand.is_empty() {
+        opcode.mnemonic.to_string()
+    } else {
+        format!("{} {}", opcode.mnemonic, operand)
+    }
+}
+
+pub fn trace_line(cpu: &mut Cpu) -> String {
+    trace_line_with(cpu, &Symbols::new(), &TraceOptions::default())
+}
+
+// With names for addresses and only the chosen columns.
+pub fn trace_line_with(cpu: &mut Cpu, symbols: &Symbols, options: &TraceOptions) -> String {
+    let pc = cpu.program_counter;
+    let (assembly, len) = disassemble(&mut cpu.bus, pc);
+    let assembly = symbols.substitute(&cpu.bus, &assembly);
+    let bytes = (0..len as u16)
+        .map(|i| format!("{:02X}", cpu.bus.peek(pc.wrapping_add(i))))
+        .collect::<Vec<_>>()
+        .join(" ");
+    let registers = options.registers;
+    let mut columns = Vec::new();
+    for (register, name, value) in [
+        (Registers::A, "A", cpu.register_a),
+        (Registers::X, "X", cpu.register_x),
+        (Registers::Y, "Y", cpu.register_y),
+        (Registers::P, "P", cpu.status),
+        (Registers::SP, "SP", cpu.stack_pointer),
+    ] {
+        if registers.contains(register) {
+            columns.push(format!("{}:{:02X}", name, value));
+        }
+    }
+    if options.ppu_timing {
+        let ppu = &cpu.bus.ppu;
+        columns.push(format!("PPU:{:3},{:3}", ppu.scanline(), ppu.dot()));
+    }
+    if registers.contains(Registers::CYC) {
+        columns.push(format!("CYC:{}", cpu.cycles));
+    }
+    format!(
+        "{:04X}  {:<8}  {:<32}{}",
+        pc,
+        bytes,
+        assembly,
+        columns.join(" ")
+    )
+    .trim_end()
+    .to_string()
+}
+
+bitflags! {
+    // The columns after the instruction.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct Registers: u8 {
+        const A = 0b0000_0001;
+        const X = 0b0000_0010;
+        const Y = 0b0000_0100;
+        const P = 0b0000_1000;
+        const SP = 0b0001_0000;
+        const CYC = 0b0010_0000;
+    }
+}
+
+impl Default for Registers {
+    fn default() -> Self {
+        Registers::all()
+    }
+}
+
+impl FromStr for Registers {
+    type Err = String;
+
+    // "a,x,cyc"
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let mut registers = Registers::empty();
+        for name in text
+            .split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+        {
+            registers |= Registers::from_name(&name.to_ascii_uppercase()).ok_or_else(|| {
+                format!(
+                    "unknown register '{}'; expected a, x, y, p, sp or cyc",
+                    name
+                )
+            })?;
+        }
+        Ok(registers)
+    }
+}
+
+// What goes in the log. Whole traces run to gigabytes a minute, so most
+// of this is about leaving things out.
+#[derive(Debug, Clone, Default)]
+pub struct TraceOptions {
+    // inclusive; only instructions in one of them are logged, or
+    // everywhere if there are none
+    pub ranges: Vec<(u16, u16)>,
+    // JSR, RTS, RTI, BRK and interrupts only
+    pub calls_only: bool,
+    // the PPU's scanline and dot before CYC
+    pub ppu_timing: bool,
+    pub registers: Registers,
+    // keep only the last this many lines in memory, for Tracer::dump
+    pub ring: Option<usize>,
+}
+
+impl TraceOptions {
+    fn covers(&self, pc: u16) -> bool {
+        self.ranges.is_empty()
+            || self
+                .ranges
+                .iter()
+                .any(|&(start, end)| (start..=end).contains(&pc))
+    }
+}
+
+// JSR, RTS, RTI, BRK
+const CALLS: [u8; 4] = [0x20, 0x60, 0x40, 0x00];
+
+// Where an Emulator sends its trace. Write errors stop the trace rather
+// than the emulation.
+pub struct Tracer {
+    out: Box<dyn Write + Send>,
+    failed: bool,
+    options: TraceOptions,
+    ring: VecDeque<String>,
+}
+
+impl fmt::Debug for Tracer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Tracer")
+            .field("failed", &self.failed)
+            .field("options", &self.options)
+            .finish()
+    }
+}
+
+impl Tracer {
+    pub fn new(out: Box<dyn Write + Send>) -> Self {
+        Self::with_options(out, TraceOptions::default())
+    }
+
+    pub fn with_options(out: Box<dyn Write + Send>, options: TraceOptions) -> Self {
+        Tracer {
+            out,
+            failed: false,
+            options,
+            ring: VecDeque::new(),
+        }
+    }
+
+    pub fn options(&self) -> &TraceOptions {
+        &self.options
+    }
+
+    // Before the instruction at the PC.
+    pub fn log(&mut self, cpu: &mut Cpu, symbols: &Symbols) {
+        let pc = cpu.program_counter;
+        if self.failed || !self.options.covers(pc) {
+            return;
+        }
+        if self.options.calls_only && !CALLS.contains(&cpu.bus.peek(pc)) {
+            return;
+        }
+        let label = symbols
+            .lookup(pc, symbols::bank(&cpu.bus, pc))
+            .filter(|symbol| symbol.addr == pc);
+        if let Some(symbol) = label {
+            self.emit(format!("{}:", symbol.name));
+        }
+        let line = trace_line_with(cpu, symbols, &self.options);
+        self.emit(line);
+    }
+
+    // An NMI or IRQ has just moved the PC to its handler.
+    pub fn interrupt(&mut self, cpu: &Cpu) {
+        let pc = cpu.program_counter;
+        if !self.failed && self.options.covers(pc) {
+            self.emit(format!("{:04X}  {:<8}  <interrupt>", pc, ""));
+        }
+    }
+
+    fn emit(&mut self, line: String) {
+        match self.options.ring {
+            Some(capacity) => {
+                if self.ring.len() >= capacity {
+                    self.ring.pop_front();
+                }
+                if capacity > 0 {
+                    self.ring.push_back(line);
+                }
+            }
+            None => self.failed = writeln!(self.out, "{}", line).is_err(),
+        }
+    }
+
+    // In ring mode, writes out the lines kept so far and starts again;
+    // returns how many. Does nothing otherwise.
+    pub fn dump(&mut self) -> io::Result<usize> {
+        let count = self.ring.len();
+        for line in self.ring.drain(..) {
+            writeln!(self.out, "{}", line)?;
+        }
+        self.out.flush()?;
+        Ok(count)
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+
+    // In ring mode, the lines kept so far, oldest first, leaving them be.
+    pub fn recent(&self) -> impl Iterator<Item = &str> {
+        self.ring.iter().map(String::as_str)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::bus::Mem;
+
+    fn cpu_with(program: &[u8]) -> Cpu {
+        let mut cpu = Cpu::new();
+        for (i, byte) in program.iter().enumerate() {
+            cpu.mem_write(0x0600 + i as u16, *byte);
+        }
+        cpu.program_counter = 0x0600;
+        cpu
+    }
+
+    #[test]
+    fn test_disassemble_modes() {
+        let mut cpu = cpu_with(&[
+            0xb1, 0x44, // lda ($44),y
+            0x6c, 0x34, 0x12, // jmp ($1234)
+            0x0a, // asl a
+            0xd0, 0xfe, // bne *
+            0x02, // not an opcode
+        ]);
+        let bus = &mut cpu.bus;
+
+        assert_eq!(disassemble(bus, 0x0600), ("LDA ($44),Y".to_string(), 2));
+        assert_eq!(disassemble(bus, 0x0602), ("JMP ($1234)".to_string(), 3));
+        assert_eq!(disassemble(bus, 0x0605), ("ASL A".to_string(), 1));
+        assert_eq!(disassemble(bus, 0x0606), ("BNE $0606".to_string(), 2));
+        assert_eq!(disassemble(bus, 0x0608), (".db $02".to_string(), 1));
+    }
+
+    #[test]
+    fn test_trace_line_layout() {
+        let mut cpu = cpu_with(&[0xa9, 0x42]);
+        cpu.status = 0x24;
+        cpu.stack_pointer = 0xFD;
+        cpu.cycles = 7;
+
+        assert_eq!(
+            trace_line(&mut cpu),
+            "0600  A9 42     LDA #$42                        A:00 X:00 Y:00 P:24 SP:FD CYC:7"
+        );
+    }
+
+    // A Write the test can look into after the Tracer has it.
+    #[derive(Clone, Default)]
+    struct Shared(std::sync::Arc<std::sync::Mutex<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Shared {
+        fn lines(&self) -> Vec<String> {
+            let text = String::from_utf8(self.0.lock().unwrap().clone()).unwrap();
+            text.lines().map(str::to_string).collect()
+        }
+    }
+
+    #[test]
+    fn test_options_filter_the_log() {
+        use crate::emulator::test::counting_rom;
+        use crate::emulator::Emulator;
+
+        let mut emulator = Emulator::from_rom(&counting_rom()).unwrap();
+        let out = Shared::default();
+        let options = TraceOptions {
+            ranges: vec![(0x9000, 0x90FF)],
+            ppu_timing: true,
+            registers: "a,cyc".parse().unwrap(),
+            ..TraceOptions::default()
+        };
+        emulator.set_trace(Some(Tracer::with_options(Box::new(out.clone()), options)));
+        emulator.run_frames(2);
+        // the NMI handler, once: the first frame's NMI is taken in the second
+        let lines = out.lines();
+        assert_eq!(lines.len(), 3, "{:?}", lines);
+        assert!(lines[0].starts_with("9000") && lines[0].ends_with("<interrupt>"));
+        assert!(lines[1].starts_with("9000  E6 00     INC $00"));
+        assert!(lines[1].contains("A:08 PPU:"));
+        assert!(!lines[1].contains("X:"));
+        assert!(lines[2].contains("RTI"));
+
+        // the last few calls and interrupts only
+        let out = Shared::default();
+        let options = TraceOptions {
+            calls_only: true,
+            ring: Some(2),
+            ..TraceOptions::default()
+        };
+        emulator.set_trace(Some(Tracer::with_options(Box::new(out.clone()), options)));
+        emulator.run_frames(3);
+        assert!(out.lines().is_empty());
+        let trace = emulator.trace_mut().unwrap();
+        assert_eq!(trace.recent().count(), 2);
+        assert_eq!(trace.dump().unwrap(), 2);
+        let lines = out.lines();
+        assert!(lines[0].ends_with("<interrupt>"));
+        assert!(lines[1].contains("RTI"));
+        assert_eq!(trace.dump().unwrap(), 0);
+
+        assert!("a,q".parse::<Registers>().is_err());
+    }
+}
@@ -0,0 +1,322 @@
+// Nintendo's VS. System: the arcade cabinets built from NES parts. A game
+// runs as it would on the console, except for:
+//
+// - the PPU. The boards have RGB PPUs with their own palettes. The 2C03
+//   has the NES colours. Each 2C04 has them in a scrambled order, which
+//   doubles as copy protection: a game shows the wrong colours on any
+//   other one. The 2C05s have the 2C03's colours but swap $2000 and
+//   $2001, and put an ID in the low bits of $2002 that some games check.
+// - $4016/$4017 reads, which carry the coin slots, a service button and
+//   eight DIP switches beside the controllers. What the switches do
+//   (difficulty, lives, coins per credit) is up to each game; switch 1
+//   is bit 0 here.
+// - mapper 99's board, which banks on $4016 writes; see mapper/vs.rs.
+//
+// An iNES header only says a ROM is a VS. game, and mapper 99 implies
+// it. NES 2.0 also says which PPU; without that a 2C03 is assumed, and
+// [vs] in config (or --vs-ppu) can name the right one. The other
+// hardware types NES 2.0 knows aren't emulated: the protection chips of
+// RBI Baseball, TKO Boxing and Super Xevious are on Namco 108 boards,
+// which this core doesn't have either, and the DualSystem's games need
+// its second CPU and screen.
+//
+// The coin and DIP switch state is part of the machine and goes into
+// savestates as a section of its own, which only VS. games have.
+
+use alloc::string::{String, ToString};
+use core::fmt;
+use core::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+// how long a coin holds the slot's switch closed
+const COIN_FRAMES: u8 = 4;
+
+#[rustfmt::skip]
+static IDENTITY: [u8; 64] = [
+    0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0A, 0x0B, 0x0C, 0x0D, 0x0E, 0x0F,
+    0x10, 0x11, 0x12, 0x13, 0x14, 0x15, 0x16, 0x17, 0x18, 0x19, 0x1A, 0x1B, 0x1C, 0x1D, 0x1E, 0x1F,
+    0x20, 0x21, 0x22, 0x23, 0x24, 0x25, 0x26, 0x27, 0x28, 0x29, 0x2A, 0x2B, 0x2C, 0x2D, 0x2E, 0x2F,
+    0x30, 0x31, 0x32, 0x33, 0x34, 0x35, 0x36, 0x37, 0x38, 0x39, 0x3A, 0x3B, 0x3C, 0x3D, 0x3E, 0x3F,
+];
+
+// The 2C03 colour each 2C04 entry shows.
+#[rustfmt::skip]
+static RP2C04: [[u8; 64]; 4] = [
+    [
+        0x35, 0x23, 0x16, 0x22, 0x1C, 0x09, 0x1D, 0x15, 0x20, 0x00, 0x27, 0x05, 0x04, 0x28, 0x08, 0x20,
+        0x21, 0x3E, 0x1F, 0x29, 0x3C, 0x32, 0x36, 0x12, 0x3F, 0x2B, 0x2E, 0x1E, 0x3D, 0x2D, 0x24, 0x01,
+        0x0E, 0x31, 0x33, 0x2A, 0x2C, 0x0C, 0x1B, 0x14, 0x2E, 0x07, 0x34, 0x06, 0x13, 0x02, 0x26, 0x2E,
+        0x2E, 0x19, 0x10, 0x0A, 0x39, 0x03, 0x37, 0x17, 0x0F, 0x11, 0x0B, 0x0D, 0x38, 0x25, 0x18, 0x3A,
+    ],
+    [
+        0x2E, 0x27, 0x18, 0x39, 0x3A, 0x25, 0x1C, 0x31, 0x16, 0x13, 0x38, 0x34, 0x20, 0x23, 0x3C, 0x0B,
+        0x0F, 0x21, 0x06, 0x3D, 0x1B, 0x29, 0x1E, 0x22, 0x1D, 0x24, 0x0E, 0x2B, 0x32, 0x08, 0x2E, 0x03,
+        0x04, 0x36, 0x26, 0x33, 0x11, 0x1F, 0x10, 0x02, 0x14, 0x3F, 0x00, 0x09, 0x12, 0x2E, 0x28, 0x20,
+        0x3E, 0x0D, 0x2A, 0x17, 0x0C, 0x01, 0x15, 0x19, 0x2E, 0x2C, 0x07, 0x37, 0x35, 0x05, 0x0A, 0x2D,
+    ],
+    [
+        0x14, 0x25, 0x3A, 0x10, 0x0B, 0x20, 0x31, 0x09, 0x01, 0x2E, 0x36, 0x08, 0x15, 0x3D, 0x3E, 0x3C,
+        0x22, 0x1C, 0x05, 0x12, 0x19, 0x18, 0x17, 0x1B, 0x00, 0x03, 0x2E, 0x02, 0x16, 0x06, 0x34, 0x35,
+        0x23, 0x0F, 0x0E, 0x37, 0x0D, 0x27, 0x26, 0x20, 0x29, 0x04, 0x21, 0x24, 0x11, 0x2D, 0x2E, 0x1F,
+        0x2C, 0x1E, 0x39, 0x33, 0x07, 0x2A, 0x28, 0x1D, 0x0A, 0x2E, 0x32, 0x38, 0x13, 0x2B, 0x3F, 0x0C,
+    ],
+    [
+        0x18, 0x03, 0x1C, 0x28, 0x2E, 0x35, 0x01, 0x17, 0x10, 0x1F, 0x2A, 0x0E, 0x36, 0x37, 0x0B, 0x39,
+        0x25, 0x1E, 0x12, 0x34, 0x2E, 0x1D, 0x06, 0x26, 0x3E, 0x1B, 0x22, 0x19, 0x04, 0x2E, 0x3A, 0x21,
+        0x05, 0x0A, 0x07, 0x02, 0x13, 0x14, 0x00, 0x15, 0x0C, 0x3D, 0x11, 0x0F, 0x0D, 0x38, 0x2D, 0x24,
+        0x33, 0x20, 0x08, 0x16, 0x3F, 0x2B, 0x20, 0x3C, 0x2E, 0x27, 0x23, 0x31, 0x29, 0x32, 0x2C, 0x09,
+    ],
+];
+
+// What the 2C05s put in the low five bits of $2002, by number; the -05
+// puts nothing there.
+const RC2C05_IDS: [Option<u8>; 5] = [Some(0x1B), Some(0x3D), Some(0x1C), Some(0x1B), None];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum VsPpu {
+    // and the RC2C03s, which are the same to a game
+    #[default]
+    Rp2c03,
+    // RP2C04-0001 to -0004
+    Rp2c04(u8),
+    // RC2C05-01 to -05
+    Rc2c05(u8),
+}
+
+impl VsPpu {
+    // Byte 13's low nibble in an NES 2.0 header.
+    pub fn from_nes2(code: u8) -> Option<Self> {
+        match code {
+            0 | 1 | 6 | 7 => Some(VsPpu::Rp2c03),
+            2..=5 => Some(VsPpu::Rp2c04(code - 1)),
+            8..=12 => Some(VsPpu::Rc2c05(code - 7)),
+            _ => None,
+        }
+    }
+
+    // The 2C03 colour shown for each palette entry.
+    pub fn colour_map(self) -> &'static [u8; 64] {
+        match self {
+            VsPpu::Rp2c04(n) => &RP2C04[(n as usize + 3) % 4],
+            _ => &IDENTITY,
+        }
+    }
+
+    // What $2002 reads have in bits 0-4 instead of open bus.
+    pub fn id(self) -> Option<u8> {
+        match self {
+            VsPpu::Rc2c05(n) => RC2C05_IDS[(n as usize + 4) % 5],
+            _ => None,
+        }
+    }
+
+    // $2000 and $2001 trade places.
+    pub fn swaps_control(self) -> bool {
+        matches!(self, VsPpu::Rc2c05(_))
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct UnknownPpu(pub String);
+
+impl fmt::Display for UnknownPpu {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "unknown VS. PPU '{}'; one of 2c03, 2c04-0001 to 2c04-0004 or 2c05-01 to 2c05-05",
+            self.0
+        )
+    }
+}
+
+impl core::error::Error for UnknownPpu {}
+
+impl FromStr for VsPpu {
+    type Err = UnknownPpu;
+
+    // "2C04-0004", with or without the RP/RC and in either case.
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let name = text.to_ascii_lowercase();
+        let name = name
+            .strip_prefix("rp")
+            .or_else(|| name.strip_prefix("rc"))
+            .unwrap_or(&name);
+        let number = |digits: &str, len: usize, max: u8| {
+            (digits.len() == len)
+                .then(|| digits.parse::<u8>().ok())
+                .flatten()
+                .filter(|n| (1..=max).contains(n))
+        };
+        let ppu = match name.split_once('-') {
+            None if name == "2c03" => Some(VsPpu::Rp2c03),
+            Some(("2c04", digits)) => number(digits, 4, 4).map(VsPpu::Rp2c04),
+            Some(("2c05", digits)) => number(digits, 2, 5).map(VsPpu::Rc2c05),
+            _ => None,
+        };
+        ppu.ok_or_else(|| UnknownPpu(text.to_string()))
+    }
+}
+
+impl TryFrom<String> for VsPpu {
+    type Error = UnknownPpu;
+
+    fn try_from(text: String) -> Result<Self, Self::Error> {
+        text.parse()
+    }
+}
+
+impl From<VsPpu> for String {
+    fn from(ppu: VsPpu) -> Self {
+        ppu.to_string()
+    }
+}
+
+impl fmt::Display for VsPpu {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            VsPpu::Rp2c03 => write!(f, "RP2C03"),
+            VsPpu::Rp2c04(n) => write!(f, "RP2C04-{:04}", n),
+            VsPpu::Rc2c05(n) => write!(f, "RC2C05-{:02}", n),
+        }
+    }
+}
+
+// Byte 13's high nibble in an NES 2.0 header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum VsHardware {
+    #[default]
+    Unisystem,
+    RbiBaseball,
+    TkoBoxing,
+    SuperXevious,
+    IceClimber,
+    DualSystem,
+    RaidOnBungelingBay,
+}
+
+impl VsHardware {
+    pub fn from_nes2(code: u8) -> Self {
+        match code {
+            1 => VsHardware::RbiBaseball,
+            2 => VsHardware::TkoBoxing,
+            3 => VsHardware::SuperXevious,
+            4 => VsHardware::IceClimber,
+            5 => VsHardware::DualSystem,
+            6 => VsHardware::RaidOnBungelingBay,
+            _ => VsHardware::Unisystem,
+        }
+    }
+
+    // Whether a game for it runs here; see the top of the file.
+    pub fn is_supported(self) -> bool {
+        matches!(self, VsHardware::Unisystem | VsHardware::IceClimber)
+    }
+}
+
+// What the header says about a VS. game.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct VsCabinet {
+    pub ppu: VsPpu,
+    pub hardware: VsHardware,
+}
+
+// The cabinet's switches and coin slots, as $4016/$4017 reads see them.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VsSystem {
+    pub dip_switches: u8,
+    // frames left with each slot's switch closed
+    coins: [u8; 2],
+    service: bool,
+}
+
+impl VsSystem {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // A coin into slot 0 or 1.
+    pub fn insert_coin(&mut self, slot: usize) {
+        self.coins[slot % 2] = COIN_FRAMES;
+    }
+
+    pub fn set_service(&mut self, pressed: bool) {
+        self.service = pressed;
+    }
+
+    // At the end of every frame.
+    pub fn frame_done(&mut self) {
+        for coin in &mut self.coins {
+            *coin = coin.saturating_sub(1);
+        }
+    }
+
+    // The bits a $4016 (port 0) or $4017 (port 1) read gets from the
+    // cabinet: service button, DIP switches 1-2 and the coins on $4016,
+    // switches 3-8 on $4017.
+    pub fn read(&self, port: usize) -> u8 {
+        if port == 1 {
+            return self.dip_switches & 0xFC;
+        }
+        (self.service as u8) << 2
+            | (self.dip_switches & 0x03) << 3
+            | ((self.coins[0] > 0) as u8) << 5
+            | ((self.coins[1] > 0) as u8) << 6
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_ppu_names_and_codes() {
+        assert_eq!("2C04-0004".parse(), Ok(VsPpu::Rp2c04(4)));
+        assert_eq!("rc2c05-03".parse(), Ok(VsPpu::Rc2c05(3)));
+        assert_eq!("RP2C03".parse(), Ok(VsPpu::Rp2c03));
+        assert!("2c04-0005".parse::<VsPpu>().is_err());
+        assert!("2c04-4".parse::<VsPpu>().is_err());
+        assert_eq!(VsPpu::Rp2c04(1).to_string(), "RP2C04-0001");
+        assert_eq!(VsPpu::Rc2c05(2).to_string(), "RC2C05-02");
+
+        assert_eq!(VsPpu::from_nes2(5), Some(VsPpu::Rp2c04(4)));
+        assert_eq!(VsPpu::from_nes2(8), Some(VsPpu::Rc2c05(1)));
+        assert_eq!(VsPpu::from_nes2(13), None);
+        assert_eq!(VsPpu::Rc2c05(2).id(), Some(0x3D));
+        assert!(VsPpu::Rc2c05(5).swaps_control());
+        assert!(!VsPpu::Rp2c04(1).swaps_control());
+    }
+
+    #[test]
+    fn test_2c04_palettes_scramble_the_same_colours() {
+        let used = |n| {
+            let mut used = VsPpu::Rp2c04(n).colour_map().to_vec();
+            used.sort();
+            used.dedup();
+            used
+        };
+        // black and white fill the entries of the four left out
+        assert_eq!(used(1).len(), 60);
+        for n in 2..=4 {
+            assert_eq!(used(n), used(1), "RP2C04-000{}", n);
+        }
+        assert_eq!(VsPpu::Rp2c03.colour_map(), &IDENTITY);
+    }
+
+    #[test]
+    fn test_coins_and_switches() {
+        let mut vs = VsSystem::new();
+        vs.dip_switches = 0b1010_0110;
+        assert_eq!(vs.read(0), 0b0001_0000);
+        assert_eq!(vs.read(1), 0b1010_0100);
+
+        vs.insert_coin(1);
+        vs.set_service(true);
+        for _ in 0..COIN_FRAMES {
+            assert_eq!(vs.read(0), 0b0101_0100);
+            vs.frame_done();
+        }
+        assert_eq!(vs.read(0) & 0x60, 0);
+    }
+}